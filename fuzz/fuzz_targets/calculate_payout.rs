@@ -0,0 +1,56 @@
+// Fuzz target for PPLNS payout math
+//
+// Run with `cargo +nightly fuzz run calculate_payout` from the repository root.
+// Checks that no share window can panic the payout calculation or pay out more
+// than the block reward.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dmpool::PplnsSimulator;
+use libfuzzer_sys::fuzz_target;
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    reward: u64,
+    fee_bps: u16,
+    window_days: u64,
+    /// (miner index, difficulty, n_time) per share
+    shares: Vec<(u8, u64, u64)>,
+}
+
+fuzz_target!(|input: Input| {
+    let shares: Vec<SimplePplnsShare> = input.shares.iter()
+        .enumerate()
+        .map(|(i, (miner, difficulty, n_time))| SimplePplnsShare {
+            user_id: *miner as u64,
+            difficulty: *difficulty,
+            btcaddress: Some(format!("bc1qminer{}", miner % 32)),
+            workername: Some("rig".to_string()),
+            n_time: *n_time,
+            job_id: format!("job-{}", i),
+            extranonce2: "00000000".to_string(),
+            nonce: format!("{:08x}", i),
+        })
+        .collect();
+
+    let simulator = PplnsSimulator::new(input.reward, input.fee_bps, input.window_days);
+
+    // Validation helpers must reject bad input without panicking
+    let _ = simulator.validate_difficulty_bounds(&shares);
+    let _ = simulator.validate_window_size(&shares, input.window_days);
+
+    for address in shares.iter().filter_map(|s| s.btcaddress.as_deref()).take(4) {
+        if let Some(calc) = simulator.calculate_payout(&shares, address) {
+            assert!(calc.payout_satoshis <= input.reward);
+            assert!(calc.final_payout_satoshis <= calc.payout_satoshis);
+        }
+    }
+
+    let result = simulator.simulate_payouts(&shares);
+    assert!(result.total_payout_satoshis <= input.reward);
+    if input.fee_bps <= 10_000 {
+        assert!(result.valid, "{:?}", result.errors);
+    }
+});
@@ -0,0 +1,24 @@
+-- DMPool Share Rollups Migration
+-- Version: 002
+-- Description: Hourly share rollups used by hashrate charts and the backfill tool
+--
+-- Rollups can be rebuilt at any time by replaying historical shares from the
+-- RocksDB store (POST /api/admin/backfill).
+
+-- ============================================================================
+-- Hourly Share Rollups Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS share_rollups_hourly (
+    miner_address VARCHAR(255) NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    share_count BIGINT NOT NULL DEFAULT 0,
+    total_difficulty BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (miner_address, hour)
+);
+
+-- Index for time range scans
+CREATE INDEX IF NOT EXISTS idx_share_rollups_hourly_hour ON share_rollups_hourly(hour DESC);
+
+-- Migration complete
+SELECT 'Migration 002 completed successfully' as status;
@@ -0,0 +1,23 @@
+-- DMPool Block Payouts Migration
+-- Version: 003
+-- Description: Per-miner PPLNS distribution for each found block
+--
+-- Rows are written by the block-found pipeline alongside block_details_cache.
+
+-- ============================================================================
+-- Block Payouts Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS block_payouts (
+    block_height INTEGER NOT NULL,
+    miner_address VARCHAR(255) NOT NULL,
+    shares BIGINT NOT NULL,
+    reward_sats BIGINT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (block_height, miner_address)
+);
+
+-- Index for per-miner lookups
+CREATE INDEX IF NOT EXISTS idx_block_payouts_miner ON block_payouts(miner_address);
+
+-- Migration complete
+SELECT 'Migration 003 completed successfully' as status;
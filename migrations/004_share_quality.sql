@@ -0,0 +1,27 @@
+-- DMPool Share Quality Migration
+-- Version: 004
+-- Description: Hourly accepted/rejected/stale/duplicate share counts per worker
+--
+-- Rows are incremented by the share quality tracker and read by the Observer
+-- worker stats.
+
+-- ============================================================================
+-- Worker Share Quality Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS worker_share_quality_hourly (
+    miner_address VARCHAR(255) NOT NULL,
+    worker_name VARCHAR(255) NOT NULL,
+    hour TIMESTAMPTZ NOT NULL,
+    accepted BIGINT NOT NULL DEFAULT 0,
+    rejected BIGINT NOT NULL DEFAULT 0,
+    stale BIGINT NOT NULL DEFAULT 0,
+    duplicate BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (miner_address, worker_name, hour)
+);
+
+-- Index for time range scans
+CREATE INDEX IF NOT EXISTS idx_worker_share_quality_hour ON worker_share_quality_hourly(hour DESC);
+
+-- Migration complete
+SELECT 'Migration 004 completed successfully' as status;
@@ -0,0 +1,26 @@
+-- DMPool Worker Tags Migration
+-- Version: 005
+-- Description: Rig, location and tags parsed from structured worker names
+--
+-- Worker names follow rig[@location][#tag...]. Rows are kept in sync with
+-- worker_status_cache and used for Observer worker filters and tag alerts.
+
+-- ============================================================================
+-- Worker Tags Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS worker_tags (
+    miner_address VARCHAR(255) NOT NULL,
+    worker_name VARCHAR(255) NOT NULL,
+    rig VARCHAR(255) NOT NULL,
+    location VARCHAR(255),
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    PRIMARY KEY (miner_address, worker_name)
+);
+
+-- Indexes for tag and location filters
+CREATE INDEX IF NOT EXISTS idx_worker_tags_tags ON worker_tags USING GIN (tags);
+CREATE INDEX IF NOT EXISTS idx_worker_tags_location ON worker_tags(location);
+
+-- Migration complete
+SELECT 'Migration 005 completed successfully' as status;
@@ -0,0 +1,33 @@
+-- DMPool Fee Revenue Migration
+-- Version: 006
+-- Description: Ledger of pool fee income, payout run network fees and donations
+--
+-- Income is positive and expenses negative. Block fee entries are written
+-- with the block record; (kind, reference) keeps retries from double counting.
+
+-- ============================================================================
+-- Fee Revenue Ledger Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS fee_revenue_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    amount_sats BIGINT NOT NULL,
+    block_height INTEGER,
+    reference VARCHAR(255),
+    note TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    CONSTRAINT unique_ledger_reference UNIQUE (kind, reference)
+);
+
+-- Index for period summaries
+CREATE INDEX IF NOT EXISTS idx_fee_revenue_ledger_created_at ON fee_revenue_ledger(created_at DESC);
+
+-- Backfill the pool cut of blocks recorded before the ledger existed
+INSERT INTO fee_revenue_ledger (kind, amount_sats, block_height, reference, created_at)
+SELECT 'block_fee', pool_fee_sats, block_height, block_height::TEXT, block_time
+FROM block_details_cache
+WHERE pool_fee_sats > 0
+ON CONFLICT (kind, reference) DO NOTHING;
+
+-- Migration complete
+SELECT 'Migration 006 completed successfully' as status;
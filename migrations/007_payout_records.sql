@@ -0,0 +1,28 @@
+-- DMPool Payout Records Migration
+-- Version: 007
+-- Description: Sent payouts persisted for historical payout statistics
+--
+-- Rows are upserted by the payment manager when a payout is broadcast or
+-- confirmed; daily/weekly/monthly statistics are bucketed from broadcast_at.
+
+-- ============================================================================
+-- Payout Records Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS payout_records (
+    payout_id VARCHAR(64) PRIMARY KEY,
+    miner_address VARCHAR(255) NOT NULL,
+    amount_sats BIGINT NOT NULL,
+    fee_sats BIGINT NOT NULL DEFAULT 0,
+    txid VARCHAR(64),
+    status VARCHAR(16) NOT NULL,
+    confirmations INTEGER NOT NULL DEFAULT 0,
+    broadcast_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Indexes for time bucketing and per-address charts
+CREATE INDEX IF NOT EXISTS idx_payout_records_broadcast_at ON payout_records(broadcast_at DESC);
+CREATE INDEX IF NOT EXISTS idx_payout_records_address ON payout_records(miner_address, broadcast_at DESC);
+
+-- Migration complete
+SELECT 'Migration 007 completed successfully' as status;
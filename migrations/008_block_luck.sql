@@ -0,0 +1,16 @@
+-- DMPool Block Luck Migration
+-- Version: 008
+-- Description: Network difficulty and effort stored with each found block
+--
+-- Effort is the PPLNS window difficulty as a percentage of the network
+-- difficulty at the block. Rolling luck is computed from these columns;
+-- blocks recorded before this migration have no effort and are left out.
+
+-- ============================================================================
+-- Block Effort Columns
+-- ============================================================================
+ALTER TABLE block_details_cache ADD COLUMN IF NOT EXISTS network_difficulty DOUBLE PRECISION;
+ALTER TABLE block_details_cache ADD COLUMN IF NOT EXISTS effort_percent DOUBLE PRECISION;
+
+-- Migration complete
+SELECT 'Migration 008 completed successfully' as status;
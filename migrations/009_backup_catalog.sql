@@ -0,0 +1,35 @@
+-- DMPool Backup Catalog Migration
+-- Version: 009
+-- Description: Catalog of every backup, its validation state and lineage
+--
+-- Rows are written by the backup manager as backups are created, validated
+-- and deleted; the *.meta.json files next to each archive remain the source
+-- for restores. Remote copies point at the local backup they were made from
+-- through parent_id. Deleted backups keep their row with deleted_at set.
+
+-- ============================================================================
+-- Backup Catalog Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS backup_catalog (
+    backup_id VARCHAR(64) PRIMARY KEY,
+    location VARCHAR(16) NOT NULL CHECK (location IN ('local', 'remote')),
+    uri TEXT NOT NULL,
+    parent_id VARCHAR(64) REFERENCES backup_catalog(backup_id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    original_size BIGINT NOT NULL DEFAULT 0,
+    backup_size BIGINT NOT NULL DEFAULT 0,
+    checksum VARCHAR(64) NOT NULL,
+    schema_version INTEGER NOT NULL,
+    validated BOOLEAN NOT NULL DEFAULT false,
+    validated_at TIMESTAMPTZ,
+    validation_error TEXT,
+    deleted_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Index for "last validated backup before" lookups
+CREATE INDEX IF NOT EXISTS idx_backup_catalog_validated ON backup_catalog(created_at DESC) WHERE validated AND deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_backup_catalog_parent ON backup_catalog(parent_id);
+
+-- Migration complete
+SELECT 'Migration 009 completed successfully' as status;
@@ -0,0 +1,24 @@
+-- DMPool Restore Drills Migration
+-- Version: 010
+-- Description: Results of periodic restore drills
+--
+-- Each drill restores the latest validated backup into a temporary
+-- directory and records the outcome of every check it ran.
+
+-- ============================================================================
+-- Restore Drills Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS restore_drills (
+    drill_id VARCHAR(64) PRIMARY KEY,
+    backup_id VARCHAR(64) REFERENCES backup_catalog(backup_id) ON DELETE SET NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL,
+    passed BOOLEAN NOT NULL,
+    checks JSONB NOT NULL DEFAULT '[]'
+);
+
+-- Index for drill history
+CREATE INDEX IF NOT EXISTS idx_restore_drills_started_at ON restore_drills(started_at DESC);
+
+-- Migration complete
+SELECT 'Migration 010 completed successfully' as status;
@@ -0,0 +1,31 @@
+-- DMPool Login History Migration
+-- Version: 011
+-- Description: Every admin login attempt, successful or not
+--
+-- Rows are written by the admin server on each login. Country and
+-- coordinates come from the reverse proxy's geolocation headers when it
+-- sets them; suspicious login checks compare them with earlier logins.
+
+-- ============================================================================
+-- Login History Table
+-- ============================================================================
+CREATE TABLE IF NOT EXISTS login_history (
+    attempt_id VARCHAR(64) PRIMARY KEY,
+    username VARCHAR(255) NOT NULL,
+    ip_address INET NOT NULL,
+    user_agent TEXT,
+    success BOOLEAN NOT NULL,
+    two_factor_method VARCHAR(16) CHECK (two_factor_method IN ('totp', 'backup_code')),
+    failure_reason TEXT,
+    country VARCHAR(2),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    suspicious JSONB NOT NULL DEFAULT '[]',
+    attempted_at TIMESTAMPTZ NOT NULL
+);
+
+-- Index for per-user history
+CREATE INDEX IF NOT EXISTS idx_login_history_username ON login_history(username, attempted_at DESC);
+
+-- Migration complete
+SELECT 'Migration 011 completed successfully' as status;
@@ -0,0 +1,13 @@
+-- DMPool Payout Source Migration
+-- Version: 012
+-- Description: Tag payout records with where they were made
+--
+-- 'pool' rows are sent by this pool's wallet; 'imported' rows come from a
+-- previous pool's history, loaded by the transaction history importer.
+
+ALTER TABLE payout_records ADD COLUMN IF NOT EXISTS source VARCHAR(16) NOT NULL DEFAULT 'pool';
+
+CREATE INDEX IF NOT EXISTS idx_payout_records_source ON payout_records(source);
+
+-- Migration complete
+SELECT 'Migration 012 completed successfully' as status;
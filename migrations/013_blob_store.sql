@@ -0,0 +1,17 @@
+-- DMPool Blob Store Migration
+-- Version: 013
+-- Description: Shared state for managers using the postgres storage backend
+--
+-- One row per manager namespace and key, holding what the file backend
+-- keeps as a file under the data directory.
+
+CREATE TABLE IF NOT EXISTS dmpool_blobs (
+    namespace VARCHAR(64) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    value BYTEA NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (namespace, key)
+);
+
+-- Migration complete
+SELECT 'Migration 013 completed successfully' as status;
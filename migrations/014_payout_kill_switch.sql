@@ -0,0 +1,17 @@
+-- DMPool Payout Kill Switch Migration
+-- Version: 014
+-- Description: Global switch halting payout broadcasts and wallet sweeps
+--
+-- A single row, shared by the pool and dmpool-admin processes; every
+-- broadcast reads it right before sending.
+
+CREATE TABLE IF NOT EXISTS payout_kill_switch (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    reason TEXT,
+    changed_by VARCHAR(255),
+    changed_at TIMESTAMPTZ
+);
+
+-- Migration complete
+SELECT 'Migration 014 completed successfully' as status;
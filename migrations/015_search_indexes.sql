@@ -0,0 +1,14 @@
+-- DMPool Search Indexes Migration
+-- Version: 015
+-- Description: Trigram indexes for admin miner and worker search
+--
+-- Prefix (ILIKE 'abc%') and similarity (%) lookups on miner addresses and
+-- worker names. pg_trgm ships with PostgreSQL's contrib package.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_miners_address_trgm ON miners USING gin (address gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_worker_status_worker_name_trgm ON worker_status_cache USING gin (worker_name gin_trgm_ops);
+
+-- Migration complete
+SELECT 'Migration 015 completed successfully' as status;
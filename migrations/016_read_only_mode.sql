@@ -0,0 +1,17 @@
+-- DMPool Read-Only Mode Migration
+-- Version: 016
+-- Description: Read-only maintenance switch shared by the pool and dmpool-admin
+--
+-- A single row; every mutating admin request and payout creation reads it
+-- before running.
+
+CREATE TABLE IF NOT EXISTS maintenance_read_only (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    read_only BOOLEAN NOT NULL DEFAULT FALSE,
+    reason TEXT,
+    changed_by VARCHAR(255),
+    changed_at TIMESTAMPTZ
+);
+
+-- Migration complete
+SELECT 'Migration 016 completed successfully' as status;
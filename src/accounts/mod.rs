@@ -0,0 +1,411 @@
+// Miner Accounts Module for DMPool
+// Groups addresses with verified ownership under one account for farm-level views
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::bitcoin::BitcoinRpcClient;
+use crate::db::{AddressEarning, DatabaseManager};
+use crate::payment::PaymentManager;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Maximum addresses linked to one account
+const MAX_ADDRESSES_PER_ACCOUNT: usize = 500;
+
+/// Verifies that the holder of an address signed a message
+#[async_trait]
+pub trait OwnershipVerifier: Send + Sync {
+    async fn verify(&self, address: &str, message: &str, signature: &str) -> Result<bool>;
+}
+
+#[async_trait]
+impl OwnershipVerifier for BitcoinRpcClient {
+    async fn verify(&self, address: &str, message: &str, signature: &str) -> Result<bool> {
+        self.verify_message(address, signature, message).await
+    }
+}
+
+/// An address linked to an account
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkedAddress {
+    pub address: String,
+    pub verified_at: DateTime<Utc>,
+}
+
+/// A group of addresses owned by one farm
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerAccount {
+    /// Unique account name
+    pub name: String,
+    /// Address consolidated payouts are sent to
+    pub primary_address: String,
+    pub addresses: Vec<LinkedAddress>,
+    /// Move balances of linked addresses to the primary before payouts
+    pub consolidate_payouts: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MinerAccount {
+    /// All linked addresses, primary first
+    pub fn address_list(&self) -> Vec<String> {
+        let mut addresses = vec![self.primary_address.clone()];
+        addresses.extend(
+            self.addresses.iter()
+                .map(|a| a.address.clone())
+                .filter(|a| *a != self.primary_address),
+        );
+        addresses
+    }
+}
+
+/// Per-address line of an account summary
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountAddressStats {
+    pub address: String,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate_3h: u64,
+    pub shares_in_window: u64,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
+    pub estimated_reward_window: f64,
+    pub workers: usize,
+}
+
+/// Aggregate statistics across an account's addresses
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountStats {
+    pub name: String,
+    pub primary_address: String,
+    pub consolidate_payouts: bool,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate_3h: u64,
+    pub shares_in_window: u64,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
+    pub estimated_reward_window: f64,
+    pub workers: usize,
+    pub addresses: Vec<AccountAddressStats>,
+}
+
+/// Manages miner accounts
+pub struct AccountManager {
+    accounts: Arc<RwLock<HashMap<String, MinerAccount>>>,
+    verifier: Arc<dyn OwnershipVerifier>,
+    data_dir: PathBuf,
+}
+
+impl AccountManager {
+    /// Create a new account manager
+    pub fn new(data_dir: PathBuf, verifier: Arc<dyn OwnershipVerifier>) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create accounts data directory")?;
+
+        Ok(Self {
+            accounts: Arc::new(RwLock::new(HashMap::new())),
+            verifier,
+            data_dir,
+        })
+    }
+
+    /// Load persisted accounts
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("accounts.json");
+        if let Some(accounts) = persist::read_json::<HashMap<String, MinerAccount>>(&path).await
+            .context("Failed to load accounts file")?
+        {
+            info!("Loaded {} miner accounts", accounts.len());
+            *self.accounts.write().await = accounts;
+        }
+        Ok(())
+    }
+
+    /// Save accounts to disk
+    pub async fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&*self.accounts.read().await)
+            .context("Failed to serialize accounts")?;
+        persist::write_atomic(&self.data_dir.join("accounts.json"), json.as_bytes()).await
+            .context("Failed to write accounts file")
+    }
+
+    /// Message an address must sign to join an account
+    pub fn ownership_message(account: &str, address: &str) -> String {
+        format!("DMPool account {} includes {}", account, address)
+    }
+
+    /// Check an ownership signature
+    async fn verify_ownership(&self, account: &str, address: &str, signature: &str) -> Result<()> {
+        let message = Self::ownership_message(account, address);
+        if !self.verifier.verify(address, &message, signature).await? {
+            return Err(anyhow::anyhow!("Invalid ownership signature for {}", address));
+        }
+        Ok(())
+    }
+
+    /// Create an account owned by the signer of `primary_address`
+    pub async fn create_account(&self, name: &str, primary_address: &str, signature: &str) -> Result<MinerAccount> {
+        if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(anyhow::anyhow!("Account name must be 1-64 characters of [A-Za-z0-9_-]"));
+        }
+        if self.accounts.read().await.contains_key(name) {
+            return Err(anyhow::anyhow!("Account {} already exists", name));
+        }
+        if let Some(owner) = self.account_for_address(primary_address).await {
+            return Err(anyhow::anyhow!("{} already belongs to account {}", primary_address, owner.name));
+        }
+        self.verify_ownership(name, primary_address, signature).await?;
+
+        let now = Utc::now();
+        let account = MinerAccount {
+            name: name.to_string(),
+            primary_address: primary_address.to_string(),
+            addresses: vec![LinkedAddress { address: primary_address.to_string(), verified_at: now }],
+            consolidate_payouts: false,
+            created_at: now,
+        };
+
+        {
+            let mut accounts = self.accounts.write().await;
+            // Re-check after the (slow) signature verification
+            if accounts.contains_key(name) {
+                return Err(anyhow::anyhow!("Account {} already exists", name));
+            }
+            accounts.insert(name.to_string(), account.clone());
+        }
+        self.save().await?;
+
+        info!("Created miner account {} with primary {}", name, primary_address);
+        Ok(account)
+    }
+
+    /// Link another address to an account
+    pub async fn link_address(&self, name: &str, address: &str, signature: &str) -> Result<MinerAccount> {
+        if let Some(owner) = self.account_for_address(address).await {
+            return Err(anyhow::anyhow!("{} already belongs to account {}", address, owner.name));
+        }
+        self.verify_ownership(name, address, signature).await?;
+
+        let account = {
+            let mut accounts = self.accounts.write().await;
+            let account = accounts.get_mut(name)
+                .ok_or_else(|| anyhow::anyhow!("Account {} not found", name))?;
+            if account.addresses.len() >= MAX_ADDRESSES_PER_ACCOUNT {
+                return Err(anyhow::anyhow!("Account {} has reached {} addresses", name, MAX_ADDRESSES_PER_ACCOUNT));
+            }
+            account.addresses.push(LinkedAddress { address: address.to_string(), verified_at: Utc::now() });
+            account.clone()
+        };
+        self.save().await?;
+
+        info!("Linked {} to miner account {}", address, name);
+        Ok(account)
+    }
+
+    /// Remove an address from an account (the primary cannot be removed)
+    pub async fn unlink_address(&self, name: &str, address: &str) -> Result<MinerAccount> {
+        let account = {
+            let mut accounts = self.accounts.write().await;
+            let account = accounts.get_mut(name)
+                .ok_or_else(|| anyhow::anyhow!("Account {} not found", name))?;
+            if account.primary_address == address {
+                return Err(anyhow::anyhow!("Cannot unlink the primary address"));
+            }
+            let before = account.addresses.len();
+            account.addresses.retain(|a| a.address != address);
+            if account.addresses.len() == before {
+                return Err(anyhow::anyhow!("{} is not linked to account {}", address, name));
+            }
+            account.clone()
+        };
+        self.save().await?;
+
+        info!("Unlinked {} from miner account {}", address, name);
+        Ok(account)
+    }
+
+    /// Change the primary address and payout consolidation setting
+    pub async fn update_payouts(&self, name: &str, primary_address: Option<&str>, consolidate: Option<bool>) -> Result<MinerAccount> {
+        let account = {
+            let mut accounts = self.accounts.write().await;
+            let account = accounts.get_mut(name)
+                .ok_or_else(|| anyhow::anyhow!("Account {} not found", name))?;
+            if let Some(primary) = primary_address {
+                if !account.addresses.iter().any(|a| a.address == primary) {
+                    return Err(anyhow::anyhow!("{} is not linked to account {}", primary, name));
+                }
+                account.primary_address = primary.to_string();
+            }
+            if let Some(consolidate) = consolidate {
+                account.consolidate_payouts = consolidate;
+            }
+            account.clone()
+        };
+        self.save().await?;
+        Ok(account)
+    }
+
+    /// Get an account by name
+    pub async fn get_account(&self, name: &str) -> Option<MinerAccount> {
+        self.accounts.read().await.get(name).cloned()
+    }
+
+    /// Account an address belongs to
+    pub async fn account_for_address(&self, address: &str) -> Option<MinerAccount> {
+        self.accounts.read().await.values()
+            .find(|a| a.addresses.iter().any(|l| l.address == address))
+            .cloned()
+    }
+
+    /// All accounts
+    pub async fn list_accounts(&self) -> Vec<MinerAccount> {
+        let mut accounts: Vec<_> = self.accounts.read().await.values().cloned().collect();
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+        accounts
+    }
+
+    /// Aggregate Observer statistics across an account
+    pub async fn aggregate_stats(&self, name: &str, db: &DatabaseManager) -> Result<AccountStats> {
+        let account = self.get_account(name).await
+            .ok_or_else(|| anyhow::anyhow!("Account {} not found", name))?;
+
+        let mut stats = AccountStats {
+            name: account.name.clone(),
+            primary_address: account.primary_address.clone(),
+            consolidate_payouts: account.consolidate_payouts,
+            hashrate_3h: 0,
+            shares_in_window: 0,
+            estimated_reward_window: 0.0,
+            workers: 0,
+            addresses: Vec::new(),
+        };
+
+        for address in account.address_list() {
+            let line = match db.get_miner_stats(&address).await? {
+                Some(miner) => AccountAddressStats {
+                    address,
+                    hashrate_3h: miner.hashrate_3h,
+                    shares_in_window: miner.shares_in_window,
+                    estimated_reward_window: miner.estimated_reward_window,
+                    workers: miner.workers.len(),
+                },
+                None => AccountAddressStats {
+                    address,
+                    hashrate_3h: 0,
+                    shares_in_window: 0,
+                    estimated_reward_window: 0.0,
+                    workers: 0,
+                },
+            };
+            stats.hashrate_3h += line.hashrate_3h;
+            stats.shares_in_window += line.shares_in_window;
+            stats.estimated_reward_window += line.estimated_reward_window;
+            stats.workers += line.workers;
+            stats.addresses.push(line);
+        }
+
+        Ok(stats)
+    }
+
+    /// Block earnings of every address in an account, newest first
+    pub async fn combined_earnings(&self, name: &str, db: &DatabaseManager, limit: i64) -> Result<Vec<AddressEarning>> {
+        let account = self.get_account(name).await
+            .ok_or_else(|| anyhow::anyhow!("Account {} not found", name))?;
+        db.get_earnings_for_addresses(&account.address_list(), limit).await
+    }
+
+    /// Move balances of linked addresses to the primary for accounts that consolidate
+    ///
+    /// Returns the total satoshis moved.
+    pub async fn consolidate_balances(&self, payments: &PaymentManager) -> Result<u64> {
+        let accounts: Vec<MinerAccount> = self.accounts.read().await.values()
+            .filter(|a| a.consolidate_payouts)
+            .cloned()
+            .collect();
+
+        let mut moved = 0;
+        for account in accounts {
+            for address in account.address_list().into_iter().skip(1) {
+                match payments.transfer_balance(&address, &account.primary_address).await {
+                    Ok(amount) => moved += amount,
+                    Err(e) => warn!("Failed to consolidate {} into {}: {}", address, account.primary_address, e),
+                }
+            }
+        }
+
+        if moved > 0 {
+            info!("Consolidated {} satoshis into primary addresses", moved);
+        }
+        Ok(moved)
+    }
+}
+
+/// Render earnings as CSV
+pub fn earnings_csv(earnings: &[AddressEarning]) -> String {
+    let mut csv = String::from("block_height,address,shares,reward_satoshis,credited_at\n");
+    for e in earnings {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            e.block_height, e.address, e.shares, e.reward_sats, e.credited_at.to_rfc3339()
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::PaymentConfig;
+    use tempfile::TempDir;
+
+    /// Accepts signatures of the form "sig:<address>"
+    struct FakeVerifier;
+
+    #[async_trait]
+    impl OwnershipVerifier for FakeVerifier {
+        async fn verify(&self, address: &str, _message: &str, signature: &str) -> Result<bool> {
+            Ok(signature == format!("sig:{}", address))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_requires_valid_signature() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = AccountManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+
+        manager.create_account("farm-1", "bc1qa", "sig:bc1qa").await.unwrap();
+        assert!(manager.link_address("farm-1", "bc1qb", "sig:bc1qx").await.is_err());
+        manager.link_address("farm-1", "bc1qb", "sig:bc1qb").await.unwrap();
+
+        // An address belongs to at most one account
+        assert!(manager.create_account("farm-2", "bc1qb", "sig:bc1qb").await.is_err());
+        assert!(manager.unlink_address("farm-1", "bc1qa").await.is_err());
+
+        let reloaded = AccountManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.account_for_address("bc1qb").await.unwrap().name, "farm-1");
+        assert_eq!(reloaded.get_account("farm-1").await.unwrap().address_list(), vec!["bc1qa", "bc1qb"]);
+    }
+
+    #[tokio::test]
+    async fn test_consolidate_balances() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = AccountManager::new(temp_dir.path().join("accounts"), Arc::new(FakeVerifier)).unwrap();
+        let payments = PaymentManager::new(temp_dir.path().join("payments"), PaymentConfig::default()).unwrap();
+
+        manager.create_account("farm", "bc1qa", "sig:bc1qa").await.unwrap();
+        manager.link_address("farm", "bc1qb", "sig:bc1qb").await.unwrap();
+        payments.add_earnings("bc1qa".to_string(), 100, 1).await.unwrap();
+        payments.add_earnings("bc1qb".to_string(), 250, 1).await.unwrap();
+
+        // Disabled by default
+        assert_eq!(manager.consolidate_balances(&payments).await.unwrap(), 0);
+
+        manager.update_payouts("farm", None, Some(true)).await.unwrap();
+        assert_eq!(manager.consolidate_balances(&payments).await.unwrap(), 250);
+        assert_eq!(payments.get_balance("bc1qa").await.unwrap().balance_satoshis, 350);
+        assert_eq!(payments.get_balance("bc1qb").await.unwrap().balance_satoshis, 0);
+    }
+}
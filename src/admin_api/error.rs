@@ -7,6 +7,8 @@ use axum::{
 };
 use serde_json::json;
 
+use crate::error::{kind_of, DmpoolError, ErrorKind};
+
 /// Admin API error type
 #[derive(Debug)]
 pub enum AdminError {
@@ -15,6 +17,8 @@ pub enum AdminError {
     InvalidInput(String),
     Unauthorized(String),
     Forbidden(String),
+    /// Mutation rejected in read-only maintenance mode
+    ReadOnly(String),
     Internal(String),
 }
 
@@ -26,6 +30,7 @@ impl std::fmt::Display for AdminError {
             AdminError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             AdminError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AdminError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            AdminError::ReadOnly(msg) => write!(f, "Read-only: {}", msg),
             AdminError::Internal(msg) => write!(f, "Internal error: {}", msg),
         }
     }
@@ -52,6 +57,9 @@ impl IntoResponse for AdminError {
             AdminError::Forbidden(msg) => {
                 (StatusCode::FORBIDDEN, msg.as_str(), "FORBIDDEN")
             }
+            AdminError::ReadOnly(msg) => {
+                (StatusCode::SERVICE_UNAVAILABLE, msg.as_str(), "READ_ONLY")
+            }
             AdminError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", "INTERNAL_ERROR")
@@ -69,7 +77,22 @@ impl IntoResponse for AdminError {
 
 impl From<anyhow::Error> for AdminError {
     fn from(err: anyhow::Error) -> Self {
-        AdminError::Internal(err.to_string())
+        // Keep the whole context chain in the message
+        let message = format!("{:#}", err);
+        match kind_of(&err) {
+            ErrorKind::NotFound => AdminError::NotFound(message),
+            ErrorKind::InvalidInput | ErrorKind::Conflict => AdminError::InvalidInput(message),
+            ErrorKind::Unauthorized => AdminError::Unauthorized(message),
+            ErrorKind::Unavailable => AdminError::Database(message),
+            ErrorKind::ReadOnly => AdminError::ReadOnly(message),
+            ErrorKind::Internal => AdminError::Internal(message),
+        }
+    }
+}
+
+impl From<DmpoolError> for AdminError {
+    fn from(err: DmpoolError) -> Self {
+        AdminError::from(anyhow::Error::from(err))
     }
 }
 
@@ -1,43 +1,97 @@
 // Admin API Middleware
 //
-// Provides authentication middleware for protecting admin endpoints
+// Protects admin-only endpoints with the tokens dmpool-admin issues. Tokens
+// are checked against the shared JWT secret, so the pool process needs the
+// same `JWT_SECRET` as dmpool-admin.
 
 use axum::{
-    extract::Request,
-    http::StatusCode,
+    extract::{Request, State},
+    http::header,
     middleware::Next,
     response::Response,
 };
+use std::sync::Arc;
+use tracing::warn;
+
 use crate::admin_api::error::AdminError;
+use crate::auth::AuthManager;
+
+/// Role admin-only endpoints require
+pub const ADMIN_ROLE: &str = "admin";
 
-/// Authentication middleware for admin endpoints
+/// Authentication middleware for admin-only endpoints
+///
+/// Requires a dmpool-admin Bearer token with the admin role and adds its
+/// `Claims` to the request extensions. Without a configured secret every
+/// request is rejected.
 pub async fn auth_middleware(
-    req: Request,
+    State(tokens): State<Option<Arc<AuthManager>>>,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, AdminError> {
-    // For now, we'll implement basic JWT authentication
-    // In production, this should validate the JWT token
+    let Some(tokens) = tokens else {
+        return Err(AdminError::Forbidden("Admin tokens are not configured, set JWT_SECRET".to_string()));
+    };
 
-    // Extract Authorization header
-    let auth_header = req
+    let token = req
         .headers()
-        .get("authorization")
+        .get(header::AUTHORIZATION)
         .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| AdminError::Unauthorized("Missing Authorization header".to_string()))?;
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| AdminError::Unauthorized("Missing Bearer token".to_string()))?;
 
-    // Validate Bearer token format
-    if !auth_header.starts_with("Bearer ") {
-        return Err(AdminError::Unauthorized("Invalid Authorization format".to_string()));
+    let claims = tokens.verify_token(token).map_err(|e| {
+        warn!("Invalid admin token for {}: {}", req.uri().path(), e);
+        AdminError::Unauthorized("Invalid token".to_string())
+    })?;
+    if claims.role != ADMIN_ROLE {
+        warn!("User '{}' with role '{}' denied {}", claims.name, claims.role, req.uri().path());
+        return Err(AdminError::Forbidden("Admin role required".to_string()));
     }
 
-    let token = &auth_header[7..]; // Skip "Bearer "
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Claims, User};
+    use axum::{body::Body, http::StatusCode, routing::post, Extension, Router};
+    use tower::ServiceExt;
 
-    // TODO: Validate JWT token
-    // For now, we'll do basic validation
-    if token.is_empty() {
-        return Err(AdminError::Unauthorized("Empty token".to_string()));
-    }
+    #[tokio::test]
+    async fn test_requires_admin_token() {
+        let tokens = Arc::new(AuthManager::new("0123456789abcdef0123456789abcdef".to_string()));
+        let router = |tokens: Option<Arc<AuthManager>>| Router::new()
+            .route("/purge", post(|Extension(claims): Extension<Claims>| async move { claims.name }))
+            .route_layer(axum::middleware::from_fn_with_state(tokens, auth_middleware));
+        let call = |router: Router, token: Option<String>| async move {
+            let mut request = Request::builder().method("POST").uri("/purge");
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+        };
+        let token = |role: &str| {
+            let user = User {
+                username: "alice".to_string(),
+                password_hash: String::new(),
+                role: role.to_string(),
+                created_at: 0,
+                last_login: None,
+                disabled: false,
+                must_change_password: false,
+                identity_provider: None,
+            };
+            tokens.generate_token(&user).unwrap()
+        };
 
-    // Token is valid, proceed with request
-    Ok(next.run(req).await)
+        assert_eq!(call(router(Some(tokens.clone())), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(router(Some(tokens.clone())), Some("garbage".to_string())).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(router(Some(tokens.clone())), Some(token("viewer"))).await, StatusCode::FORBIDDEN);
+        assert_eq!(call(router(Some(tokens.clone())), Some(token("admin"))).await, StatusCode::OK);
+        assert_eq!(call(router(None), Some(token("admin"))).await, StatusCode::FORBIDDEN);
+    }
 }
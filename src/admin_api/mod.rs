@@ -2,16 +2,29 @@
 //
 // This module provides internal-only API endpoints for:
 // - Dashboard monitoring
-// - Miner management
+// - Miner management and address/worker search
 // - Worker monitoring
-// - Payment management
+// - Payment management, hot/cold wallet balances and the coinbase payout plan
 // - Block management
-// - System monitoring
-// - Notification configuration
-// - System configuration
+// - System monitoring (live stratum statistics, health checks, disk space,
+//   stratum SLA reports, runtime metrics in JSON or Prometheus format and
+//   per-route request metrics with SLO burn rates, database schema drift)
+// - Notification configuration, alert rule templates and dead letters
+// - Announcements to miners
+// - System configuration: confirmed changes, versions, diffs and rollback
+// - Share backfill
+// - Farm account grouping
+// - Pool fee revenue ledger
+// - Referred miners and referral credits
+// - Audit trail, database backups, config versions and 2FA lockouts
+// - Observer API rate limit counters, top offenders and IP blocks
+// - Runtime log filter
+// - Data retention policies, share window holds and miner data purges
+// - Read-only maintenance mode
 //
-// These endpoints require authentication and should only be accessible
-// from internal network or VPN.
+// These endpoints should only be accessible from internal network or VPN.
+// Destructive ones additionally require a dmpool-admin token with the admin
+// role (see `middleware::auth_middleware`).
 
 pub mod routes;
 pub mod error;
@@ -22,24 +35,332 @@ use axum::{Router, routing::get, routing::post, routing::put, routing::delete};
 use std::sync::Arc;
 use tracing::info;
 
-use crate::db::DatabaseManager;
+use crate::accounts::AccountManager;
+use crate::alert::AlertManager;
+use crate::audit::AuditLogger;
+use crate::auth::AuthManager;
+use crate::backfill::BackfillManager;
+use crate::backup::BackupManager;
+use crate::bitcoin::MempoolMonitor;
+use crate::announcements::AnnouncementBoard;
+use crate::config_mgt::ConfigManager;
+use crate::confirmation::ConfigConfirmation;
+use crate::db::{DatabaseManager, SchemaMonitor};
+use crate::disk::DiskMonitor;
+use crate::health::HealthChecker;
+use crate::http_compression::CompressionConfig;
+use crate::http_metrics::{http_metrics_middleware, HttpMetrics, ADMIN_API};
+use crate::logging::{request_id::request_id_middleware, LogControl};
+use crate::maintenance::{read_only_middleware, ReadOnlyMode};
+use crate::payment::{CoinbasePlanner, WalletMonitor, WalletTiers};
+use crate::rate_limit::{RateLimiterState, StratumScorer};
+use crate::referrals::ReferralProgram;
+use crate::retention::RetentionManager;
+use crate::revenue::RevenueLedger;
+use crate::runtime_metrics::RuntimeMetrics;
+use crate::service_auth::{service_auth_middleware, ServiceAuth};
+use crate::sla::SlaTracker;
+use crate::stratum_stats::StratumStats;
+use crate::two_factor::TwoFactorManager;
 
 /// Application state for Admin API
 #[derive(Clone)]
 pub struct AdminState {
     pub db: Arc<DatabaseManager>,
+    pub backfill: Option<Arc<BackfillManager>>,
+    pub accounts: Option<Arc<AccountManager>>,
+    pub revenue: Option<Arc<RevenueLedger>>,
+    pub alerts: Option<Arc<AlertManager>>,
+    pub audit: Option<Arc<AuditLogger>>,
+    pub backups: Option<Arc<BackupManager>>,
+    pub config_versions: Option<Arc<ConfigManager>>,
+    pub config_confirmation: Option<Arc<ConfigConfirmation>>,
+    pub announcements: Option<Arc<AnnouncementBoard>>,
+    pub referrals: Option<Arc<ReferralProgram>>,
+    pub two_factor: Option<Arc<TwoFactorManager>>,
+    pub logging: Option<Arc<LogControl>>,
+    pub stratum_stats: Option<Arc<StratumStats>>,
+    pub health: Option<Arc<HealthChecker>>,
+    pub disk: Option<Arc<DiskMonitor>>,
+    pub runtime: Option<Arc<RuntimeMetrics>>,
+    pub retention: Option<Arc<RetentionManager>>,
+    /// When set, every request must be signed by another dmpool service
+    pub service_auth: Option<Arc<ServiceAuth>>,
+    /// Verifies dmpool-admin tokens on admin-only routes; they are refused without it
+    pub admin_tokens: Option<Arc<AuthManager>>,
+    pub stratum_scorer: Option<Arc<StratumScorer>>,
+    /// Observer API rate limiter, for inspection and IP blocks
+    pub rate_limiter: Option<Arc<RateLimiterState>>,
+    pub wallet_tiers: Option<Arc<WalletTiers>>,
+    pub wallet_monitor: Option<Arc<WalletMonitor>>,
+    pub coinbase: Option<Arc<CoinbasePlanner>>,
+    pub sla: Option<Arc<SlaTracker>>,
+    /// Request metrics of this and the Observer API
+    pub http_metrics: Option<Arc<HttpMetrics>>,
+    /// Keeps schema checks made here for the health report
+    pub schema: Option<Arc<SchemaMonitor>>,
+    /// Mempool activity and payout propagation from bitcoind ZMQ
+    pub mempool: Option<Arc<MempoolMonitor>>,
+    /// When set and enabled, mutating requests are rejected
+    pub read_only: Option<Arc<ReadOnlyMode>>,
+    /// Responses are compressed when set
+    pub compression: Option<CompressionConfig>,
+}
+
+impl AdminState {
+    /// Create state backed only by the database
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self {
+            db,
+            backfill: None,
+            accounts: None,
+            revenue: None,
+            alerts: None,
+            audit: None,
+            backups: None,
+            config_versions: None,
+            config_confirmation: None,
+            announcements: None,
+            referrals: None,
+            two_factor: None,
+            logging: None,
+            stratum_stats: None,
+            health: None,
+            disk: None,
+            runtime: None,
+            retention: None,
+            service_auth: None,
+            admin_tokens: None,
+            stratum_scorer: None,
+            rate_limiter: None,
+            wallet_tiers: None,
+            wallet_monitor: None,
+            coinbase: None,
+            sla: None,
+            http_metrics: None,
+            schema: None,
+            mempool: None,
+            read_only: None,
+            compression: None,
+        }
+    }
+
+    /// Attach the share backfill manager
+    pub fn with_backfill(mut self, backfill: Arc<BackfillManager>) -> Self {
+        self.backfill = Some(backfill);
+        self
+    }
+
+    /// Attach miner accounts
+    pub fn with_accounts(mut self, accounts: Arc<AccountManager>) -> Self {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    /// Attach the fee revenue ledger
+    pub fn with_revenue(mut self, revenue: Arc<RevenueLedger>) -> Self {
+        self.revenue = Some(revenue);
+        self
+    }
+
+    /// Attach the alert manager (delivery queue and dead letters)
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Attach the audit logger
+    pub fn with_audit(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Attach the backup manager
+    pub fn with_backups(mut self, backups: Arc<BackupManager>) -> Self {
+        self.backups = Some(backups);
+        self
+    }
+
+    /// Attach configuration version history
+    pub fn with_config_versions(mut self, config_versions: Arc<ConfigManager>) -> Self {
+        self.config_versions = Some(config_versions);
+        self
+    }
+
+    /// Attach operator announcements
+    pub fn with_announcements(mut self, announcements: Arc<AnnouncementBoard>) -> Self {
+        self.announcements = Some(announcements);
+        self
+    }
+
+    /// Attach the referral program
+    pub fn with_referrals(mut self, referrals: Arc<ReferralProgram>) -> Self {
+        self.referrals = Some(referrals);
+        self
+    }
+
+    /// Require confirmation for risky config changes before they become a version
+    pub fn with_config_confirmation(mut self, config_confirmation: Arc<ConfigConfirmation>) -> Self {
+        self.config_confirmation = Some(config_confirmation);
+        self
+    }
+
+    /// Attach the 2FA manager
+    pub fn with_two_factor(mut self, two_factor: Arc<TwoFactorManager>) -> Self {
+        self.two_factor = Some(two_factor);
+        self
+    }
+
+    /// Attach the runtime log filter control
+    pub fn with_logging(mut self, logging: Arc<LogControl>) -> Self {
+        self.logging = Some(logging);
+        self
+    }
+
+    /// Attach live stratum statistics
+    pub fn with_stratum_stats(mut self, stratum_stats: Arc<StratumStats>) -> Self {
+        self.stratum_stats = Some(stratum_stats);
+        self
+    }
+
+    /// Attach the health checker
+    pub fn with_health(mut self, health: Arc<HealthChecker>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Attach free disk space monitoring
+    pub fn with_disk(mut self, disk: Arc<DiskMonitor>) -> Self {
+        self.disk = Some(disk);
+        self
+    }
+
+    /// Attach tokio, in-memory cache and channel metrics
+    pub fn with_runtime(mut self, runtime: Arc<RuntimeMetrics>) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Attach data retention and miner purges
+    pub fn with_retention(mut self, retention: Arc<RetentionManager>) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Require signed service-to-service requests
+    pub fn with_service_auth(mut self, service_auth: Arc<ServiceAuth>) -> Self {
+        self.service_auth = Some(service_auth);
+        self
+    }
+
+    /// Accept dmpool-admin tokens signed with the shared JWT secret
+    pub fn with_admin_tokens(mut self, admin_tokens: Arc<AuthManager>) -> Self {
+        self.admin_tokens = Some(admin_tokens);
+        self
+    }
+
+    /// Attach per-IP stratum abuse scores
+    pub fn with_stratum_scorer(mut self, stratum_scorer: Arc<StratumScorer>) -> Self {
+        self.stratum_scorer = Some(stratum_scorer);
+        self
+    }
+
+    /// Attach hot/cold wallet balances and sweeps
+    pub fn with_wallet_tiers(mut self, wallet_tiers: Arc<WalletTiers>) -> Self {
+        self.wallet_tiers = Some(wallet_tiers);
+        self
+    }
+
+    /// Attach hot wallet coverage of pending payouts
+    pub fn with_wallet_monitor(mut self, wallet_monitor: Arc<WalletMonitor>) -> Self {
+        self.wallet_monitor = Some(wallet_monitor);
+        self
+    }
+
+    /// Attach the coinbase payout planner
+    pub fn with_coinbase(mut self, coinbase: Arc<CoinbasePlanner>) -> Self {
+        self.coinbase = Some(coinbase);
+        self
+    }
+
+    /// Attach daily stratum SLA history
+    pub fn with_sla(mut self, sla: Arc<SlaTracker>) -> Self {
+        self.sla = Some(sla);
+        self
+    }
+
+    /// Share schema drift checks with the health report
+    pub fn with_schema_monitor(mut self, schema: Arc<SchemaMonitor>) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Inspect the Observer API rate limiter and block IPs
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiterState>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Record per-route request metrics and serve them with SLO burn rates
+    pub fn with_http_metrics(mut self, http_metrics: Arc<HttpMetrics>) -> Self {
+        self.http_metrics = Some(http_metrics);
+        self
+    }
+
+    /// Serve mempool activity and payout propagation
+    pub fn with_mempool(mut self, mempool: Arc<MempoolMonitor>) -> Self {
+        self.mempool = Some(mempool);
+        self
+    }
+
+    /// Attach the read-only maintenance switch
+    pub fn with_read_only(mut self, read_only: Arc<ReadOnlyMode>) -> Self {
+        self.read_only = Some(read_only);
+        self
+    }
+
+    /// Compress responses with gzip or brotli
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
 }
 
 /// Create the Admin API router (with authentication middleware)
 pub fn create_router(db: Arc<DatabaseManager>) -> Router {
-    let state = AdminState { db };
+    create_router_with_state(AdminState::new(db))
+}
 
-    Router::new()
+/// Create the Admin API router from a prepared state
+pub fn create_router_with_state(state: AdminState) -> Router {
+    let service_auth = state.service_auth.clone();
+    let read_only = state.read_only.clone();
+    let http_metrics = state.http_metrics.clone();
+    let compression = state.compression;
+    // Destructive actions need an admin token, whatever other checks apply
+    let admin_only = Router::new()
+        .route("/api/admin/miners/:address/purge", post(routes::retention::purge_miner))
+        .route("/api/admin/retention/run", post(routes::retention::run_retention))
+        .route("/api/admin/retention/holds", post(routes::retention::place_share_hold))
+        .route("/api/admin/retention/holds/:id", delete(routes::retention::release_share_hold))
+        .route("/api/admin/config", put(routes::config::update_config))
+        .route("/api/admin/config/changes/:id", delete(routes::config::cancel_change))
+        .route("/api/admin/config/changes/:id/confirm", post(routes::config::confirm_change))
+        .route("/api/admin/config/changes/:id/apply", post(routes::config::apply_change))
+        .route("/api/admin/config/versions/:id/rollback", post(routes::config::rollback_config))
+        .route("/api/admin/config/versions/:id/promote", post(routes::system::promote_config_version))
+        .route("/api/admin/maintenance/read-only", put(routes::system::set_read_only))
+        .route("/api/admin/rate-limits/blocks/:ip", put(routes::system::block_ip).delete(routes::system::unblock_ip))
+        .route("/api/admin/stratum/bans/:ip", delete(routes::system::unban_stratum_ip))
+        .route("/api/admin/logging", put(routes::system::update_log_filter))
+        .route_layer(axum::middleware::from_fn_with_state(state.admin_tokens.clone(), middleware::auth_middleware));
+    let router = Router::new()
         // Dashboard
         .route("/api/admin/dashboard", get(routes::dashboard::get_dashboard))
 
         // Miner management
         .route("/api/admin/miners", get(routes::miners::get_miners))
+        .route("/api/admin/search", get(routes::miners::search_miners))
         .route("/api/admin/miners/:address", get(routes::miners::get_miner_detail))
         .route("/api/admin/miners/:address/ban", post(routes::miners::ban_miner))
         .route("/api/admin/miners/:address/ban", delete(routes::miners::unban_miner))
@@ -52,6 +373,10 @@ pub fn create_router(db: Arc<DatabaseManager>) -> Router {
         .route("/api/admin/payments/pending", get(routes::payments::get_pending_payouts))
         .route("/api/admin/payments/trigger/:address", post(routes::payments::trigger_payout))
         .route("/api/admin/payments/history", get(routes::payments::get_payment_history))
+        .route("/api/admin/payments/stats", get(routes::payments::get_payout_stats))
+        .route("/api/admin/payments/wallets", get(routes::payments::get_wallet_tiers))
+        .route("/api/admin/payments/wallets/coverage", get(routes::payments::get_wallet_coverage))
+        .route("/api/admin/payments/coinbase", get(routes::payments::get_coinbase_plan))
 
         // Blocks
         .route("/api/admin/blocks", get(routes::blocks::get_blocks))
@@ -61,27 +386,117 @@ pub fn create_router(db: Arc<DatabaseManager>) -> Router {
         // Monitoring
         .route("/api/admin/monitoring/stratum", get(routes::monitoring::get_stratum_stats))
         .route("/api/admin/monitoring/database", get(routes::monitoring::get_database_stats))
+        .route("/api/admin/monitoring/database/schema", get(routes::monitoring::check_database_schema))
+        .route("/api/admin/monitoring/health", get(routes::monitoring::get_health))
+        .route("/api/admin/monitoring/disk", get(routes::monitoring::get_disk_status))
+        .route("/api/admin/monitoring/runtime", get(routes::monitoring::get_runtime_metrics))
+        .route("/api/admin/monitoring/sla", get(routes::monitoring::get_sla_report))
+        .route("/api/admin/monitoring/http", get(routes::monitoring::get_http_metrics))
+        .route("/api/admin/monitoring/mempool", get(routes::monitoring::get_mempool_status))
+        .route("/api/admin/monitoring/metrics", get(routes::monitoring::get_prometheus_metrics))
         .route("/api/admin/logs", get(routes::monitoring::get_logs))
 
         // Notifications
         .route("/api/admin/notifications/config", get(routes::notifications::get_config))
         .route("/api/admin/notifications/config", put(routes::notifications::update_config))
         .route("/api/admin/notifications/history", get(routes::notifications::get_history))
+        .route("/api/admin/notifications/rules", get(routes::notifications::get_rules))
+        .route("/api/admin/notifications/rules/:id", put(routes::notifications::save_rule))
+        .route("/api/admin/notifications/rules/:id/resolve", post(routes::notifications::resolve_rule))
+        .route("/api/admin/notifications/alerts/:id/acknowledge", post(routes::notifications::acknowledge_alert))
+        .route("/api/admin/notifications/templates/preview", post(routes::notifications::preview_template))
+        .route("/api/admin/notifications/channels/:name/test", post(routes::notifications::test_channel))
+        .route("/api/admin/notifications/delivery", get(routes::notifications::get_delivery_stats))
+        .route("/api/admin/notifications/stats", get(routes::notifications::get_alert_stats))
+        .route("/api/admin/notifications/dead-letters", get(routes::notifications::get_dead_letters))
+        .route("/api/admin/notifications/dead-letters/:id", delete(routes::notifications::discard_dead_letter))
+        .route("/api/admin/notifications/dead-letters/:id/redrive", post(routes::notifications::redrive_dead_letter))
 
         // System Config
         .route("/api/admin/config", get(routes::config::get_config))
-        .route("/api/admin/config", put(routes::config::update_config))
+        .route("/api/admin/config/changes", get(routes::config::get_pending_changes))
+        .route("/api/admin/config/changes/:id", get(routes::config::get_change))
+        .route("/api/admin/config/diff", get(routes::config::get_config_diff))
+        .route("/api/admin/config/versions", get(routes::system::get_config_versions))
+        .route("/api/admin/config/versions/:id", get(routes::config::get_config_version))
+
+        // Audit, backups and 2FA
+        .route("/api/admin/audit", get(routes::system::get_audit_logs))
+        .route("/api/admin/backups", get(routes::system::get_backups))
+        .route("/api/admin/backups", post(routes::system::create_backup))
+        .route("/api/admin/backups/stats", get(routes::system::get_backup_stats))
+        .route("/api/admin/backups/catalog", get(routes::system::get_backup_catalog))
+        .route("/api/admin/backups/drills", get(routes::system::get_restore_drills))
+        .route("/api/admin/backups/drills", post(routes::system::run_restore_drill))
+        .route("/api/admin/2fa/lockouts", get(routes::system::get_two_factor_lockouts))
+        .route("/api/admin/stratum/scores", get(routes::system::get_stratum_scores))
+        .route("/api/admin/rate-limits", get(routes::system::get_rate_limits))
+        .route("/api/admin/rate-limits/:ip", get(routes::system::get_ip_rate_limit))
+        .route("/api/admin/logging", get(routes::system::get_log_filter))
+        .route("/api/admin/maintenance/read-only", get(routes::system::get_read_only))
+
+        // Announcements
+        .route("/api/admin/announcements", get(routes::announcements::list_announcements))
+        .route("/api/admin/announcements", post(routes::announcements::create_announcement))
+        .route("/api/admin/announcements/:id", put(routes::announcements::update_announcement))
+        .route("/api/admin/announcements/:id", delete(routes::announcements::delete_announcement))
+
+        // Referrals
+        .route("/api/admin/referrals", get(routes::referrals::list_referrals))
+        .route("/api/admin/referrals/ledger", get(routes::referrals::get_referral_ledger))
+
+        // Data retention
+        .route("/api/admin/retention", get(routes::retention::get_retention_status))
+        .route("/api/admin/retention/holds", get(routes::retention::list_share_holds))
+
+        // Share backfill
+        .route("/api/admin/backfill", post(routes::backfill::start_backfill))
+        .route("/api/admin/backfill/status", get(routes::backfill::get_backfill_status))
+        .route("/api/admin/backfill/cancel", post(routes::backfill::cancel_backfill))
+
+        // Fee revenue
+        .route("/api/admin/revenue", get(routes::revenue::get_revenue_entries))
+        .route("/api/admin/revenue/summary", get(routes::revenue::get_revenue_summary))
+        .route("/api/admin/revenue/projection", get(routes::revenue::get_revenue_projection))
+        .route("/api/admin/revenue/donations", post(routes::revenue::record_donation))
+
+        // Farm accounts
+        .route("/api/admin/accounts", get(routes::accounts::get_accounts))
+        .route("/api/admin/accounts", post(routes::accounts::create_account))
+        .route("/api/admin/accounts/:name/addresses", post(routes::accounts::link_account_address))
+        .route("/api/admin/accounts/:name/addresses/:address", delete(routes::accounts::unlink_account_address))
+        .route("/api/admin/accounts/:name/payouts", put(routes::accounts::update_account_payouts))
+
+        .merge(admin_only)
+        .with_state(state);
 
-        .with_state(state)
+    let router = match read_only {
+        Some(mode) => router.layer(axum::middleware::from_fn_with_state(mode, read_only_middleware)),
+        None => router,
+    };
+    let router = match service_auth {
+        Some(auth) => router.layer(axum::middleware::from_fn_with_state(auth, service_auth_middleware)),
+        None => router,
+    };
+    // Outermost, so rejected requests are counted too
+    let router = match http_metrics {
+        Some(metrics) => router.layer(axum::middleware::from_fn_with_state((metrics, ADMIN_API), http_metrics_middleware)),
+        None => router,
+    };
+    let router = match compression {
+        Some(compression) => compression.apply(router),
+        None => router,
+    };
+    router.layer(axum::middleware::from_fn(request_id_middleware))
 }
 
 /// Start the Admin API server
 pub async fn start_admin_api(
-    db: Arc<DatabaseManager>,
+    state: AdminState,
     host: String,
     port: u16,
 ) -> Result<tokio::task::JoinHandle<()>> {
-    let app = create_router(db);
+    let app = create_router_with_state(state);
     let addr = format!("{}:{}", host, port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
@@ -0,0 +1,133 @@
+// Miner Account endpoints
+//
+// Provides endpoints for grouping farm addresses under one account
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::accounts::{AccountManager, MinerAccount};
+use crate::logging::request_id::current_request_id;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccountRequest {
+    pub name: String,
+    pub primary_address: String,
+    /// Signature of AccountManager::ownership_message(name, primary_address)
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkAddressRequest {
+    pub address: String,
+    /// Signature of AccountManager::ownership_message(name, address)
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAccountPayoutsRequest {
+    pub primary_address: Option<String>,
+    pub consolidate_payouts: Option<bool>,
+}
+
+/// Get the account manager or fail if accounts are not enabled
+fn account_manager(state: &AdminState) -> Result<&AccountManager, AdminError> {
+    state.accounts.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Miner accounts are not enabled".to_string()))
+}
+
+/// Record an account change in the admin audit log
+async fn audit(state: &AdminState, action: &str, account: &str, detail: String) -> Result<(), AdminError> {
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', $1, 'account', $2, $3, $4)",
+        &[&action, &account, &detail, &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+    Ok(())
+}
+
+/// GET /api/admin/accounts
+///
+/// Returns all miner accounts
+pub async fn get_accounts(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<MinerAccount>>, AdminError> {
+    Ok(Json(account_manager(&state)?.list_accounts().await))
+}
+
+/// POST /api/admin/accounts
+///
+/// Creates an account after verifying ownership of the primary address
+pub async fn create_account(
+    State(state): State<AdminState>,
+    Json(req): Json<CreateAccountRequest>,
+) -> Result<Json<MinerAccount>, AdminError> {
+    let account = account_manager(&state)?
+        .create_account(&req.name, &req.primary_address, &req.signature)
+        .await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    audit(&state, "account_create", &account.name, format!("primary: {}", account.primary_address)).await?;
+    Ok(Json(account))
+}
+
+/// POST /api/admin/accounts/:name/addresses
+///
+/// Links an address after verifying its ownership signature
+pub async fn link_account_address(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(req): Json<LinkAddressRequest>,
+) -> Result<Json<MinerAccount>, AdminError> {
+    let account = account_manager(&state)?
+        .link_address(&name, &req.address, &req.signature)
+        .await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    audit(&state, "account_link", &name, format!("address: {}", req.address)).await?;
+    Ok(Json(account))
+}
+
+/// DELETE /api/admin/accounts/:name/addresses/:address
+///
+/// Removes an address from an account
+pub async fn unlink_account_address(
+    State(state): State<AdminState>,
+    Path((name, address)): Path<(String, String)>,
+) -> Result<Json<MinerAccount>, AdminError> {
+    let account = account_manager(&state)?
+        .unlink_address(&name, &address)
+        .await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    audit(&state, "account_unlink", &name, format!("address: {}", address)).await?;
+    Ok(Json(account))
+}
+
+/// PUT /api/admin/accounts/:name/payouts
+///
+/// Sets the primary address and whether payouts are consolidated to it
+pub async fn update_account_payouts(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(req): Json<UpdateAccountPayoutsRequest>,
+) -> Result<Json<MinerAccount>, AdminError> {
+    let account = account_manager(&state)?
+        .update_payouts(&name, req.primary_address.as_deref(), req.consolidate_payouts)
+        .await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    audit(
+        &state,
+        "account_payouts",
+        &name,
+        format!("primary: {}, consolidate: {}", account.primary_address, account.consolidate_payouts),
+    ).await?;
+    Ok(Json(account))
+}
@@ -0,0 +1,66 @@
+// Announcement endpoints
+//
+// Operator notices to miners, served on the Observer API while in effect
+
+use super::super::error::AdminError;
+use super::system::{log_system_action, UNAUTHENTICATED_ACTOR};
+use super::AdminState;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::announcements::{Announcement, AnnouncementBoard, NewAnnouncement};
+
+fn board(state: &AdminState) -> Result<&AnnouncementBoard, AdminError> {
+    state.announcements.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Announcements are not enabled".to_string()))
+}
+
+/// GET /api/admin/announcements
+///
+/// Returns all announcements, including scheduled and ended ones, newest first
+pub async fn list_announcements(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<Announcement>>, AdminError> {
+    Ok(Json(board(&state)?.list().await))
+}
+
+/// POST /api/admin/announcements
+///
+/// Creates an announcement; with `notify` it is pushed to miners once it takes effect
+pub async fn create_announcement(
+    State(state): State<AdminState>,
+    Json(req): Json<NewAnnouncement>,
+) -> Result<Json<Announcement>, AdminError> {
+    let announcement = board(&state)?.create(req, "admin").await?;
+    log_system_action(&state, UNAUTHENTICATED_ACTOR, "announcement_create", "announcement", &announcement.id).await?;
+    Ok(Json(announcement))
+}
+
+/// PUT /api/admin/announcements/:id
+pub async fn update_announcement(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    Json(req): Json<NewAnnouncement>,
+) -> Result<Json<Announcement>, AdminError> {
+    let announcement = board(&state)?.update(&id, req).await?;
+    log_system_action(&state, UNAUTHENTICATED_ACTOR, "announcement_update", "announcement", &id).await?;
+    Ok(Json(announcement))
+}
+
+/// DELETE /api/admin/announcements/:id
+pub async fn delete_announcement(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    if !board(&state)?.remove(&id).await? {
+        return Err(AdminError::NotFound(format!("Announcement {} not found", id)));
+    }
+    log_system_action(&state, UNAUTHENTICATED_ACTOR, "announcement_delete", "announcement", &id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+    })))
+}
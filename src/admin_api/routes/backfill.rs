@@ -0,0 +1,68 @@
+// Share Backfill endpoints
+//
+// Provides endpoints for replaying historical shares into the statistics database
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{extract::State, Json};
+
+use crate::backfill::{BackfillManager, BackfillProgress, BackfillRequest};
+use crate::logging::request_id::current_request_id;
+
+/// Get the backfill manager or fail if the share store is not attached
+fn backfill_manager(state: &AdminState) -> Result<&BackfillManager, AdminError> {
+    state.backfill.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Share backfill is not available".to_string()))
+}
+
+/// POST /api/admin/backfill
+///
+/// Starts replaying historical shares from the store into Postgres
+pub async fn start_backfill(
+    State(state): State<AdminState>,
+    Json(req): Json<BackfillRequest>,
+) -> Result<Json<BackfillProgress>, AdminError> {
+    let manager = backfill_manager(&state)?;
+
+    let progress = manager.start(req.clone()).await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'share_backfill', 'system', $1, $2, $3)",
+        &[&progress.id, &format!("range: {}..{}, overwrite: {}", req.start_time, req.end_time, req.overwrite), &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(progress))
+}
+
+/// GET /api/admin/backfill/status
+///
+/// Returns progress of the current or last backfill job
+pub async fn get_backfill_status(
+    State(state): State<AdminState>,
+) -> Result<Json<BackfillProgress>, AdminError> {
+    backfill_manager(&state)?
+        .progress()
+        .await
+        .map(Json)
+        .ok_or_else(|| AdminError::NotFound("No backfill job has been run".to_string()))
+}
+
+/// POST /api/admin/backfill/cancel
+///
+/// Requests cancellation of the running backfill job
+pub async fn cancel_backfill(
+    State(state): State<AdminState>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let cancelled = backfill_manager(&state)?.cancel().await;
+    if !cancelled {
+        return Err(AdminError::NotFound("No backfill job is running".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({
+        "cancelled": true,
+    })))
+}
@@ -1,31 +1,319 @@
 // System configuration endpoints
 //
-// Provides dynamic system configuration management
+// Provides dynamic system configuration management. Changes are proposed as
+// confirmation requests carrying the parameter's risk, confirmed, then applied
+// as a new config version; versions can be compared and rolled back.
 
 use super::super::error::AdminError;
+use super::system::{config_versions, log_system_action};
 use super::AdminState;
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
+use crate::auth::Claims;
+use crate::config_mgt::{ConfigDiff, ConfigManager, ConfigProfile, ConfigVersion, ValidationStatus};
+use crate::confirmation::{ConfigChangeRequest, ConfigConfirmation, ConfigMeta, RiskLevel};
+use crate::rate_limit::extract_client_ip_with_default_config;
+
+fn confirmation(state: &AdminState) -> Result<&ConfigConfirmation, AdminError> {
+    state.config_confirmation.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Config confirmation is not enabled".to_string()))
+}
+
+/// Key of a parameter's risk metadata: the full path, else its last segment
+/// (`stratum.start_difficulty` -> `start_difficulty`)
+fn risk_key<'a>(confirmation: &ConfigConfirmation, parameter: &'a str) -> &'a str {
+    if confirmation.get_config_meta(parameter).is_some() {
+        return parameter;
+    }
+    parameter.rsplit('.').next().unwrap_or(parameter)
+}
+
+/// Config data of the current version, or the schema defaults before the first version
+async fn current_config(manager: &ConfigManager) -> serde_json::Value {
+    match manager.current_version().await {
+        Some(version) => version.config_data,
+        None => manager.get_schema().await.into_iter()
+            .filter_map(|(path, schema)| schema.default_value.map(|value| (path, value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+/// `config` with one parameter replaced, checked against the schema
+async fn proposed_config(
+    manager: &ConfigManager,
+    mut config: serde_json::Value,
+    parameter: &str,
+    value: serde_json::Value,
+) -> Result<serde_json::Value, AdminError> {
+    let object = config.as_object_mut()
+        .ok_or_else(|| AdminError::Internal("Current config version is not an object".to_string()))?;
+    object.insert(parameter.to_string(), value);
+
+    match manager.validate_config(&config).await {
+        ValidationStatus::Invalid { errors } => Err(AdminError::InvalidInput(errors.join("; "))),
+        _ => Ok(config),
+    }
+}
+
+#[derive(Serialize)]
+pub struct CurrentConfig {
+    pub version_id: Option<String>,
+    pub profile: ConfigProfile,
+    pub created_at: Option<DateTime<Utc>>,
+    pub created_by: Option<String>,
+    pub config: serde_json::Value,
+}
+
+/// GET /api/admin/config
+///
+/// Returns the config of the current version, or the schema defaults before the first one
 pub async fn get_config(
-    State(_state): State<AdminState>,
-) -> Result<axum::Json<serde_json::Value>, AdminError> {
-    // TODO: Implement - fetch from system_configs table
-    Ok(axum::Json(serde_json::json!({
-        "pool_fee_percent": 1.0,
-        "min_payout_btc": 0.01,
-        "pplns_window_days": 7,
-        "stratum_port": 3333,
-        "api_port": 8081
-    })))
+    State(state): State<AdminState>,
+) -> Result<Json<CurrentConfig>, AdminError> {
+    let manager = config_versions(&state)?;
+    Ok(Json(match manager.current_version().await {
+        Some(version) => CurrentConfig {
+            version_id: Some(version.id),
+            profile: version.profile,
+            created_at: Some(version.created_at),
+            created_by: Some(version.created_by),
+            config: version.config_data,
+        },
+        None => CurrentConfig {
+            version_id: None,
+            profile: manager.profile(),
+            created_at: None,
+            created_by: None,
+            config: current_config(manager).await,
+        },
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ConfigUpdate {
+    /// Parameter path, e.g. `stratum.start_difficulty`
+    pub parameter: String,
+    pub value: serde_json::Value,
 }
 
+#[derive(Serialize)]
+pub struct ProposedChange {
+    pub request: ConfigChangeRequest,
+    pub risk_level: RiskLevel,
+    pub meta: Option<ConfigMeta>,
+    /// False for low-risk parameters, which are confirmed on creation
+    pub requires_confirmation: bool,
+}
+
+/// PUT /api/admin/config
+///
+/// Proposes a change to one parameter. The change is validated against the
+/// current version and waits for confirmation before it can be applied.
 pub async fn update_config(
-    State(_state): State<AdminState>,
-    Json(_req): Json<serde_json::Value>,
-) -> Result<axum::Json<serde_json::Value>, AdminError> {
-    // TODO: Implement - update system_configs table
-    Ok(axum::Json(serde_json::json!({
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(update): Json<ConfigUpdate>,
+) -> Result<Json<ProposedChange>, AdminError> {
+    let manager = config_versions(&state)?;
+    let confirmation = confirmation(&state)?;
+    let key = risk_key(confirmation, &update.parameter);
+    confirmation.validate_value(key, &update.value)
+        .map_err(|e| AdminError::InvalidInput(format!("Invalid value for {}: {}", update.parameter, e)))?;
+
+    let current = current_config(manager).await;
+    let old_value = current.get(&update.parameter).cloned().unwrap_or(serde_json::Value::Null);
+    if old_value == update.value {
+        return Err(AdminError::InvalidInput(format!("{} already has this value", update.parameter)));
+    }
+    proposed_config(manager, current, &update.parameter, update.value.clone()).await?;
+
+    let mut request = confirmation.create_change_request(
+        update.parameter.clone(),
+        old_value,
+        update.value,
+        claims.name.clone(),
+        extract_client_ip_with_default_config(&headers).to_string(),
+    ).await?;
+    let requires_confirmation = confirmation.requires_confirmation(key);
+    if !requires_confirmation {
+        request.confirmed = confirmation.confirm_change(&request.id, &claims.name).await?;
+    }
+    log_system_action(&state, &claims.name, "config_propose", "config_change", &request.id).await?;
+
+    Ok(Json(ProposedChange {
+        risk_level: confirmation.get_risk_level(key),
+        meta: confirmation.get_config_meta(key).cloned(),
+        requires_confirmation,
+        request,
+    }))
+}
+
+/// GET /api/admin/config/changes
+///
+/// Returns changes waiting for confirmation or to be applied
+pub async fn get_pending_changes(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<ConfigChangeRequest>>, AdminError> {
+    let mut pending = confirmation(&state)?.get_pending().await;
+    pending.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(Json(pending))
+}
+
+/// GET /api/admin/config/changes/:id
+pub async fn get_change(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<ConfigChangeRequest>, AdminError> {
+    confirmation(&state)?.get_request(&id).await
+        .map(Json)
+        .ok_or_else(|| AdminError::NotFound(format!("Config change {} not found", id)))
+}
+
+/// POST /api/admin/config/changes/:id/confirm
+///
+/// High-risk changes must be confirmed by an admin other than the proposer
+pub async fn confirm_change(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<ConfigChangeRequest>, AdminError> {
+    let confirmation = confirmation(&state)?;
+    if confirmation.get_request(&id).await.is_none()
+        || !confirmation.confirm_change(&id, &claims.name).await?
+    {
+        return Err(AdminError::NotFound(format!("Config change {} not found or expired", id)));
+    }
+    log_system_action(&state, &claims.name, "config_confirm", "config_change", &id).await?;
+
+    confirmation.get_request(&id).await
+        .map(Json)
+        .ok_or_else(|| AdminError::NotFound(format!("Config change {} not found", id)))
+}
+
+#[derive(Serialize)]
+pub struct AppliedChange {
+    pub request: ConfigChangeRequest,
+    pub version: ConfigVersion,
+}
+
+/// POST /api/admin/config/changes/:id/apply
+///
+/// Applies a confirmed change as a new config version. Fails if the parameter
+/// changed since the change was proposed.
+pub async fn apply_change(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<AppliedChange>, AdminError> {
+    let manager = config_versions(&state)?;
+    let confirmation = confirmation(&state)?;
+    let request = confirmation.get_request(&id).await
+        .ok_or_else(|| AdminError::NotFound(format!("Config change {} not found", id)))?;
+    if !request.confirmed {
+        return Err(AdminError::InvalidInput(format!("Config change {} is not confirmed", id)));
+    }
+
+    let current = current_config(manager).await;
+    if current.get(&request.parameter).unwrap_or(&serde_json::Value::Null) != &request.old_value {
+        return Err(AdminError::InvalidInput(format!(
+            "{} changed since change {} was proposed", request.parameter, id
+        )));
+    }
+    let config = proposed_config(manager, current, &request.parameter, request.new_value.clone()).await?;
+
+    let request = confirmation.apply_change(&id).await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+    let version = manager.create_version(
+        config,
+        format!("Set {} (change {})", request.parameter, request.id),
+        request.username.clone(),
+    ).await?;
+    log_system_action(&state, &claims.name, "config_apply", "config_version", &version.id).await?;
+
+    Ok(Json(AppliedChange { request, version }))
+}
+
+/// DELETE /api/admin/config/changes/:id
+pub async fn cancel_change(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    if !confirmation(&state)?.cancel_change(&id).await? {
+        return Err(AdminError::NotFound(format!("Config change {} not found", id)));
+    }
+    log_system_action(&state, &claims.name, "config_cancel", "config_change", &id).await?;
+
+    Ok(Json(serde_json::json!({
         "success": true,
-        "reload_required": false
+        "id": id,
     })))
 }
+
+/// GET /api/admin/config/versions/:id
+pub async fn get_config_version(
+    State(state): State<AdminState>,
+    Path(version_id): Path<String>,
+) -> Result<Json<ConfigVersion>, AdminError> {
+    config_versions(&state)?.get_version(&version_id).await
+        .map(Json)
+        .ok_or_else(|| AdminError::NotFound(format!("Config version {} not found", version_id)))
+}
+
+#[derive(Deserialize)]
+pub struct ConfigDiffQuery {
+    pub from: String,
+    /// Defaults to the current version
+    pub to: Option<String>,
+}
+
+/// GET /api/admin/config/diff?from=&to=
+pub async fn get_config_diff(
+    State(state): State<AdminState>,
+    Query(query): Query<ConfigDiffQuery>,
+) -> Result<Json<ConfigDiff>, AdminError> {
+    let manager = config_versions(&state)?;
+    let to = match query.to {
+        Some(to) => to,
+        None => manager.current_version().await
+            .map(|version| version.id)
+            .ok_or_else(|| AdminError::NotFound("No current config version".to_string()))?,
+    };
+    manager.diff_versions(&query.from, &to).await
+        .map(Json)
+        .map_err(|e| AdminError::NotFound(e.to_string()))
+}
+
+#[derive(Deserialize)]
+pub struct RollbackRequest {
+    pub reason: String,
+}
+
+/// POST /api/admin/config/versions/:id/rollback
+///
+/// Makes a copy of the version the current one
+pub async fn rollback_config(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(version_id): Path<String>,
+    Json(req): Json<RollbackRequest>,
+) -> Result<Json<ConfigVersion>, AdminError> {
+    let manager = config_versions(&state)?;
+    if manager.get_version(&version_id).await.is_none() {
+        return Err(AdminError::NotFound(format!("Config version {} not found", version_id)));
+    }
+    manager.rollback(&version_id, req.reason, claims.name.clone()).await?;
+    log_system_action(&state, &claims.name, "config_rollback", "config_version", &version_id).await?;
+
+    manager.current_version().await
+        .map(Json)
+        .ok_or_else(|| AdminError::Internal("Rollback left no current version".to_string()))
+}
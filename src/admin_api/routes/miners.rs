@@ -10,6 +10,8 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use crate::db::{SearchMatch, MIN_SEARCH_CHARS};
+use crate::logging::request_id::current_request_id;
 
 #[derive(Debug, Deserialize)]
 pub struct MinersQuery {
@@ -99,6 +101,37 @@ pub async fn get_miners(
     Ok(Json(MinersListResponse { total, miners }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// GET /api/admin/search?q=bc1qxy&limit=10
+///
+/// Returns miner addresses and worker names matching a prefix or similar to
+/// the query, best first, for autocomplete
+pub async fn search_miners(
+    State(state): State<AdminState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, AdminError> {
+    let q = query.q.trim();
+    if q.chars().count() < MIN_SEARCH_CHARS || q.len() > 255 {
+        return Err(AdminError::InvalidInput(format!(
+            "Search queries must be {} to 255 characters", MIN_SEARCH_CHARS
+        )));
+    }
+    let limit = query.limit.unwrap_or(10).clamp(1, 50);
+    let matches = state.db.search_miners(q, limit).await?;
+    Ok(Json(SearchResponse { query: q.to_string(), matches }))
+}
+
 /// GET /api/admin/miners/:address
 ///
 /// Returns detailed information about a specific miner
@@ -206,8 +239,8 @@ pub async fn ban_miner(
 
     // Log audit
     conn.execute(
-        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value) VALUES ('admin', 'ban_miner', 'miner', $1, $2)",
-        &[&address, &format!("reason: {}, expires: {:?}", req.reason, expires_at)]
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'ban_miner', 'miner', $1, $2, $3)",
+        &[&address, &format!("reason: {}, expires: {:?}", req.reason, expires_at), &current_request_id()]
     )
     .await
     .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
@@ -242,8 +275,8 @@ pub async fn unban_miner(
 
     // Log audit
     conn.execute(
-        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id) VALUES ('admin', 'unban_miner', 'miner', $1)",
-        &[&address]
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, request_id) VALUES ('admin', 'unban_miner', 'miner', $1, $2)",
+        &[&address, &current_request_id()]
     )
     .await
     .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
@@ -276,8 +309,8 @@ pub async fn update_threshold(
 
     // Log audit
     conn.execute(
-        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value) VALUES ('admin', 'update_threshold', 'miner', $1, $2)",
-        &[&address, &format!("threshold_btc: {}", req.threshold_btc)]
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'update_threshold', 'miner', $1, $2, $3)",
+        &[&address, &format!("threshold_btc: {}", req.threshold_btc), &current_request_id()]
     )
     .await
     .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
@@ -2,6 +2,9 @@
 //
 // All endpoints require authentication and internal network access
 
+pub mod accounts;
+pub mod announcements;
+pub mod backfill;
 pub mod blocks;
 pub mod dashboard;
 pub mod config;
@@ -9,6 +12,10 @@ pub mod miners;
 pub mod monitoring;
 pub mod notifications;
 pub mod payments;
+pub mod referrals;
+pub mod retention;
+pub mod revenue;
+pub mod system;
 pub mod workers;
 
 use super::error::AdminError;
@@ -21,6 +28,9 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 // Re-export submodules
+pub use accounts::*;
+pub use announcements::*;
+pub use backfill::*;
 pub use blocks::*;
 pub use dashboard::*;
 pub use config::*;
@@ -28,4 +38,8 @@ pub use miners::*;
 pub use monitoring::*;
 pub use notifications::*;
 pub use payments::*;
+pub use referrals::*;
+pub use retention::*;
+pub use revenue::*;
+pub use system::*;
 pub use workers::*;
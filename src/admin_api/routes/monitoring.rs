@@ -4,30 +4,189 @@
 
 use super::super::error::AdminError;
 use super::AdminState;
-use axum::{extract::State, Query};
+use axum::{extract::State, http::header, Query};
 
+use crate::bitcoin::MempoolStatus;
+use crate::db::{PoolHealth, PoolProbe, SchemaReport};
+use crate::disk::DiskStatus;
+use crate::health::HealthStatus;
+use crate::http_metrics::HttpMetricsSummary;
+use crate::runtime_metrics::RuntimeSnapshot;
+use crate::sla::{SlaPeriod, SlaReport};
+use crate::stratum_stats::LiveStats;
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+
+/// GET /api/admin/monitoring/stratum
+///
+/// Current worker counts and share rates from the stratum layer
 pub async fn get_stratum_stats(
-    State(_state): State<AdminState>,
-) -> Result<axum::Json<serde_json::Value>, AdminError> {
-    // TODO: Implement
-    Ok(axum::Json(serde_json::json!({
-        "connections": 342,
-        "unique_ips": 89,
-        "shares_per_second": 1234,
-        "average_difficulty": 4500
-    })))
+    State(state): State<AdminState>,
+) -> Result<axum::Json<LiveStats>, AdminError> {
+    let stats = state.stratum_stats.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Live stratum statistics are not available".to_string()))?;
+    Ok(axum::Json(stats.current().await))
+}
+
+/// GET /api/admin/monitoring/health
+///
+/// Full health check of the database, Bitcoin node, stratum and ZMQ
+pub async fn get_health(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<HealthStatus>, AdminError> {
+    let health = state.health.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Health checks are not enabled".to_string()))?;
+    Ok(axum::Json(health.check().await))
 }
 
+/// GET /api/admin/monitoring/disk
+///
+/// Free space on the store, backup and log volumes and any protective actions in force
+pub async fn get_disk_status(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<DiskStatus>, AdminError> {
+    let disk = state.disk.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Disk monitoring is not enabled".to_string()))?;
+    Ok(axum::Json(disk.status().await))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlaQuery {
+    pub period: Option<SlaPeriod>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// GET /api/admin/monitoring/sla?period=day|week&from=&to=
+///
+/// Share acceptance, stratum availability, response times and downtime windows per
+/// UTC day or ISO week; defaults to the last 30 days or 12 weeks
+pub async fn get_sla_report(
+    State(state): State<AdminState>,
+    Query(query): Query<SlaQuery>,
+) -> Result<axum::Json<Vec<SlaReport>>, AdminError> {
+    let sla = state.sla.as_deref()
+        .ok_or_else(|| AdminError::NotFound("SLA reporting is not enabled".to_string()))?;
+    let period = query.period.unwrap_or_default();
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let default_days = match period {
+        SlaPeriod::Day => 29,
+        SlaPeriod::Week => 83,
+    };
+    let from = query.from.unwrap_or(to - Duration::days(default_days));
+    if from > to {
+        return Err(AdminError::InvalidInput("from must not be after to".to_string()));
+    }
+    Ok(axum::Json(sla.report(period, from, to).await))
+}
+
+/// GET /api/admin/monitoring/http
+///
+/// Request counts, status codes and latency percentiles per route of both APIs,
+/// with SLO burn rates per window
+pub async fn get_http_metrics(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<HttpMetricsSummary>, AdminError> {
+    let metrics = state.http_metrics.as_deref()
+        .ok_or_else(|| AdminError::NotFound("HTTP request metrics are not enabled".to_string()))?;
+    Ok(axum::Json(metrics.summary()))
+}
+
+/// GET /api/admin/monitoring/mempool
+///
+/// Transaction arrival rate from bitcoind ZMQ and broadcast payouts not yet mined
+pub async fn get_mempool_status(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<MempoolStatus>, AdminError> {
+    let mempool = state.mempool.as_deref()
+        .ok_or_else(|| AdminError::NotFound("ZMQ subscriptions are not enabled".to_string()))?;
+    Ok(axum::Json(mempool.status().await))
+}
+
+/// GET /api/admin/monitoring/runtime
+///
+/// Tokio task and queue counters, in-memory cache sizes and channel saturation
+pub async fn get_runtime_metrics(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<RuntimeSnapshot>, AdminError> {
+    let runtime = state.runtime.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Runtime metrics are not collected".to_string()))?;
+    Ok(axum::Json(runtime.snapshot().await))
+}
+
+/// GET /api/admin/monitoring/metrics
+///
+/// Runtime metrics, template freshness, ZMQ subscriptions, mempool activity, wallet coverage, HTTP request metrics, rate limiting and share pruning in the Prometheus text exposition format
+pub async fn get_prometheus_metrics(
+    State(state): State<AdminState>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), AdminError> {
+    let runtime = state.runtime.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Runtime metrics are not collected".to_string()))?;
+    let mut metrics = runtime.snapshot().await.to_prometheus();
+    if let Some(health) = state.health.as_deref() {
+        if let Some(jobs) = health.job_freshness().await {
+            metrics.push_str(&jobs.to_prometheus());
+        }
+        if let Some(zmq) = health.zmq() {
+            metrics.push_str(&zmq.to_prometheus().await);
+        }
+    }
+    if let Some(mempool) = state.mempool.as_deref() {
+        metrics.push_str(&mempool.status().await.to_prometheus());
+    }
+    if let Some(monitor) = state.wallet_monitor.as_deref() {
+        if let Some(coverage) = monitor.latest().await {
+            metrics.push_str(&coverage.to_prometheus());
+        }
+    }
+    if let Some(http_metrics) = state.http_metrics.as_deref() {
+        metrics.push_str(&http_metrics.to_prometheus());
+    }
+    if let Some(limiter) = state.rate_limiter.as_deref() {
+        metrics.push_str(&limiter.to_prometheus().await);
+    }
+    if let Some(holds) = state.retention.as_deref().and_then(|r| r.holds()) {
+        metrics.push_str(&holds.to_prometheus().await);
+    }
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    ))
+}
+
+/// GET /api/admin/monitoring/database
+///
+/// PostgreSQL pool health: state, saturation, ping latency, reconnects and recycled connections
 pub async fn get_database_stats(
-    State(_state): State<AdminState>,
-) -> Result<axum::Json<serde_json::Value>, AdminError> {
-    // TODO: Implement
-    Ok(axum::Json(serde_json::json!({
-        "connections": 5,
-        "database_size_mb": 1234,
-        "shares_count": 12345678,
-        "avg_query_time_ms": 5
-    })))
+    State(state): State<AdminState>,
+) -> Result<axum::Json<PoolHealth>, AdminError> {
+    let monitored = match state.health.as_deref() {
+        Some(health) => health.pool_health().await,
+        None => None,
+    };
+    // Unmonitored pools still report their connection counts
+    let health = monitored.unwrap_or_else(|| {
+        let counts = PoolProbe::counts(state.db.as_ref());
+        PoolHealth {
+            counts,
+            saturation_percent: counts.saturation_percent(),
+            ..Default::default()
+        }
+    });
+    Ok(axum::Json(health))
+}
+
+/// GET /api/admin/monitoring/database/schema
+///
+/// Check the live schema against the applied migrations now
+pub async fn check_database_schema(
+    State(state): State<AdminState>,
+) -> Result<axum::Json<SchemaReport>, AdminError> {
+    let report = match state.schema.as_deref() {
+        Some(schema) => schema.check().await,
+        None => state.db.check_schema().await,
+    };
+    Ok(axum::Json(report?))
 }
 
 pub async fn get_logs(
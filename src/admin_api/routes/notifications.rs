@@ -1,10 +1,32 @@
 // Notification configuration endpoints
 //
-// Provides notification config management
+// Provides notification config management, alert rule templates, channel test-fire,
+// delivery metrics, dead-letter re-drive and alert acknowledgement / incident resolution
 
 use super::super::error::AdminError;
 use super::AdminState;
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{
+    webhook_body, Alert, AlertChannel, AlertManager, AlertRule, AlertStats, DeadLetter, DeliveryQueue, DeliveryStats,
+    MessageTemplate,
+};
+use crate::logging::request_id::current_request_id;
+
+#[derive(Debug, Deserialize)]
+pub struct TemplatePreviewRequest {
+    pub rule_id: String,
+    pub template: MessageTemplate,
+    /// Sample alert context
+    #[serde(default)]
+    pub context: serde_json::Value,
+}
 
 pub async fn get_config(
     State(_state): State<AdminState>,
@@ -38,3 +60,289 @@ pub async fn get_history(
         "notifications": []
     })))
 }
+
+/// Get the alert manager or fail if it is not attached
+fn alert_manager(state: &AdminState) -> Result<&AlertManager, AdminError> {
+    state.alerts.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Alert manager is not available".to_string()))
+}
+
+/// GET /api/admin/notifications/rules
+///
+/// Returns alert rules with their message templates and the config version
+pub async fn get_rules(
+    State(state): State<AdminState>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    // Channels are left out, they hold credentials
+    let config = alert_manager(&state)?.get_config().await;
+    Ok(Json(serde_json::json!({
+        "version": config.version,
+        "rules": config.rules,
+    })))
+}
+
+/// PUT /api/admin/notifications/rules/:id
+///
+/// Creates or replaces a rule; its templates are validated before saving
+pub async fn save_rule(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    Json(mut rule): Json<AlertRule>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    rule.id = id.clone();
+    let version = alert_manager(&state)?
+        .save_rule(rule)
+        .await
+        .map_err(|e| AdminError::InvalidInput(format!("{:#}", e)))?;
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'alert_rule_save', 'alert_rule', $1, $2, $3)",
+        &[&id, &format!("version {}", version), &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+        "version": version,
+    })))
+}
+
+/// POST /api/admin/notifications/templates/preview
+///
+/// Renders a template for a rule against a sample context without saving it
+pub async fn preview_template(
+    State(state): State<AdminState>,
+    Json(req): Json<TemplatePreviewRequest>,
+) -> Result<Json<Alert>, AdminError> {
+    let rule = find_rule(alert_manager(&state)?, &req.rule_id).await?;
+    let channel = rule.channels.first().cloned().unwrap_or_default();
+    let sample = sample_alert(&rule, channel, req.context);
+    let rendered = req.template.render(&rule, &sample)
+        .map_err(|e| AdminError::InvalidInput(format!("{:#}", e)))?;
+    Ok(Json(rendered))
+}
+
+async fn find_rule(alerts: &AlertManager, rule_id: &str) -> Result<AlertRule, AdminError> {
+    alerts.get_rules().await
+        .into_iter()
+        .find(|r| r.id == rule_id)
+        .ok_or_else(|| AdminError::NotFound(format!("Alert rule {} not found", rule_id)))
+}
+
+/// Alert a rule would raise through a channel for a sample context
+fn sample_alert(rule: &AlertRule, channel: String, context: serde_json::Value) -> Alert {
+    Alert {
+        id: "preview".to_string(),
+        rule_id: rule.id.clone(),
+        level: rule.level,
+        title: format!("{} Alert: {}", rule.level, rule.name),
+        message: rule.description.clone(),
+        context,
+        triggered_at: Utc::now(),
+        acknowledged: false,
+        channel,
+        template_format: None,
+        deliveries: Vec::new(),
+        resolution: None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelTestRequest {
+    pub rule_id: String,
+    /// Sample alert context
+    #[serde(default)]
+    pub context: serde_json::Value,
+    /// Only render the payload when false
+    #[serde(default)]
+    pub send: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelTestResult {
+    pub channel: String,
+    /// Alert after the rule's template for the channel
+    pub alert: Alert,
+    /// Request body, for webhook channels
+    pub payload: Option<serde_json::Value>,
+    pub sent: bool,
+}
+
+/// POST /api/admin/notifications/channels/:name/test
+///
+/// Renders the alert a rule would send through a channel, including the
+/// webhook payload, and sends it when `send` is true
+pub async fn test_channel(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(req): Json<ChannelTestRequest>,
+) -> Result<Json<ChannelTestResult>, AdminError> {
+    let alerts = alert_manager(&state)?;
+    let channel = alerts.get_channels().await.remove(&name)
+        .ok_or_else(|| AdminError::NotFound(format!("Alert channel {} not found", name)))?;
+    let rule = find_rule(alerts, &req.rule_id).await?;
+
+    let mut alert = sample_alert(&rule, name.clone(), req.context);
+    if let Some(template) = rule.template_for(&name) {
+        alert = template.render(&rule, &alert)
+            .map_err(|e| AdminError::InvalidInput(format!("{:#}", e)))?;
+    }
+    let payload = match &channel {
+        AlertChannel::Webhook { payload, .. } => Some(
+            webhook_body(payload.as_ref(), &alert).map_err(|e| AdminError::InvalidInput(format!("{:#}", e)))?,
+        ),
+        _ => None,
+    };
+
+    if req.send {
+        alerts.test_fire(&name, &alert).await
+            .map_err(|e| AdminError::Internal(format!("Test alert through {} failed: {:#}", name, e)))?;
+        log_alert_action(&state, "alert_channel_test", "alert_channel", &name).await?;
+    }
+
+    Ok(Json(ChannelTestResult {
+        channel: name,
+        alert,
+        payload,
+        sent: req.send,
+    }))
+}
+
+/// Get the alert delivery queue or fail if retries are not enabled
+fn delivery_queue(state: &AdminState) -> Result<&DeliveryQueue, AdminError> {
+    alert_manager(state)?.delivery()
+        .map(|delivery| delivery.as_ref())
+        .ok_or_else(|| AdminError::NotFound("Alert delivery queue is not available".to_string()))
+}
+
+/// Record an admin action on a dead letter
+async fn log_dead_letter_action(state: &AdminState, action: &str, id: &str) -> Result<(), AdminError> {
+    log_alert_action(state, action, "alert_dead_letter", id).await
+}
+
+/// Record an admin action on an alert, rule or dead letter
+async fn log_alert_action(state: &AdminState, action: &str, target_type: &str, id: &str) -> Result<(), AdminError> {
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, request_id) VALUES ('admin', $1, $2, $3, $4)",
+        &[&action, &target_type, &id, &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+    Ok(())
+}
+
+/// GET /api/admin/notifications/delivery
+///
+/// Returns pending retries, dead letters and per-channel delivery success rates
+pub async fn get_delivery_stats(
+    State(state): State<AdminState>,
+) -> Result<Json<DeliveryStats>, AdminError> {
+    Ok(Json(delivery_queue(&state)?.stats().await))
+}
+
+/// GET /api/admin/notifications/stats
+///
+/// Returns alert counts with per-channel delivery and failover outcomes
+pub async fn get_alert_stats(
+    State(state): State<AdminState>,
+) -> Result<Json<AlertStats>, AdminError> {
+    Ok(Json(alert_manager(&state)?.get_stats().await))
+}
+
+/// POST /api/admin/notifications/alerts/:id/acknowledge
+///
+/// Acknowledges an alert; the rule's incidents resolve once all its alerts are acknowledged
+pub async fn acknowledge_alert(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let acknowledged = alert_manager(&state)?.acknowledge_alert(&id).await
+        .map_err(|e| AdminError::Internal(format!("{:#}", e)))?;
+    if !acknowledged {
+        return Err(AdminError::NotFound(format!("Alert {} not found", id)));
+    }
+    log_alert_action(&state, "alert_acknowledge", "alert", &id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+    })))
+}
+
+/// POST /api/admin/notifications/rules/:id/resolve
+///
+/// Marks a rule's condition cleared: its alerts are resolved, a resolution
+/// notice is sent and its open PagerDuty/Opsgenie incidents are resolved
+pub async fn resolve_rule(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let alerts = alert_manager(&state)?;
+    let resolution = alerts.clear_rule(&id, serde_json::json!({ "cleared_by": "admin" })).await
+        .map_err(|e| AdminError::Internal(format!("{:#}", e)))?;
+    // Incidents can outlive the firing state, e.g. once the alert was acknowledged
+    let incidents = alerts.resolve_rule(&id).await
+        .map_err(|e| AdminError::Internal(format!("{:#}", e)))?;
+    log_alert_action(&state, "alert_resolve", "alert_rule", &id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+        "resolution": resolution,
+        "incidents_resolved": incidents,
+    })))
+}
+
+/// GET /api/admin/notifications/dead-letters
+///
+/// Returns alerts that exhausted their delivery attempts, newest first
+pub async fn get_dead_letters(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<DeadLetter>>, AdminError> {
+    Ok(Json(delivery_queue(&state)?.dead_letters().await))
+}
+
+/// POST /api/admin/notifications/dead-letters/:id/redrive
+///
+/// Requeues a dead letter and attempts delivery immediately
+pub async fn redrive_dead_letter(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    if !delivery_queue(&state)?.redrive(&id).await? {
+        return Err(AdminError::NotFound(format!("Dead letter {} not found", id)));
+    }
+    let delivered = match &state.alerts {
+        Some(alerts) => alerts.retry_deliveries().await,
+        None => 0,
+    };
+    log_dead_letter_action(&state, "alert_redrive", &id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+        "delivered": delivered,
+    })))
+}
+
+/// DELETE /api/admin/notifications/dead-letters/:id
+///
+/// Discards a dead letter
+pub async fn discard_dead_letter(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    if !delivery_queue(&state)?.discard(&id).await? {
+        return Err(AdminError::NotFound(format!("Dead letter {} not found", id)));
+    }
+    log_dead_letter_action(&state, "alert_discard", &id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "id": id,
+    })))
+}
@@ -1,6 +1,7 @@
 // Payment Management endpoints
 //
-// Provides endpoints for viewing pending payments, manual payouts, and payment history
+// Provides endpoints for viewing pending payments, manual payouts, payment history,
+// hot/cold wallet balances and the coinbase payout plan
 
 use super::super::error::AdminError;
 use super::AdminState;
@@ -10,6 +11,9 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::logging::request_id::current_request_id;
+use crate::payment::{CoinbasePlan, PayoutStatsBucket, PayoutStatsInterval, WalletCoverage, WalletTierStatus, WalletTiers};
+
 #[derive(Debug, Deserialize)]
 pub struct PendingPaymentsQuery {
     pub limit: Option<i64>,
@@ -187,8 +191,8 @@ pub async fn trigger_payout(
 
     // Log audit
     conn.execute(
-        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value) VALUES ('admin', 'manual_payout', 'miner', $1, $2)",
-        &[&address, &format!("amount_btc: {}", payout_sats as f64 / 100_000_000.0)]
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'manual_payout', 'miner', $1, $2, $3)",
+        &[&address, &format!("amount_btc: {}", payout_sats as f64 / 100_000_000.0), &current_request_id()]
     )
     .await
     .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
@@ -263,3 +267,83 @@ pub async fn get_payment_history(
         payments,
     }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct PayoutStatsQuery {
+    pub interval: Option<PayoutStatsInterval>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub address: Option<String>,
+}
+
+/// GET /api/admin/payments/stats?interval=day|week|month&from=&to=&address=
+///
+/// Returns historical payout totals, fees and averages per time bucket
+pub async fn get_payout_stats(
+    State(state): State<AdminState>,
+    Query(query): Query<PayoutStatsQuery>,
+) -> Result<Json<Vec<PayoutStatsBucket>>, AdminError> {
+    let to = query.to.unwrap_or_else(chrono::Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(90));
+    if from >= to {
+        return Err(AdminError::InvalidInput("from must be before to".to_string()));
+    }
+
+    let stats = state.db
+        .get_payout_stats(query.address.as_deref(), query.interval.unwrap_or_default(), from, to)
+        .await?;
+    Ok(Json(stats))
+}
+
+fn wallet_tiers(state: &AdminState) -> Result<&WalletTiers, AdminError> {
+    state.wallet_tiers.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Wallet tiers are not enabled".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletTiersQuery {
+    pub sweeps: Option<usize>,
+}
+
+/// GET /api/admin/payments/wallets?sweeps=
+///
+/// Returns hot and cold wallet balances, the low-balance flag and recent sweeps
+pub async fn get_wallet_tiers(
+    State(state): State<AdminState>,
+    Query(query): Query<WalletTiersQuery>,
+) -> Result<Json<WalletTierStatus>, AdminError> {
+    let status = wallet_tiers(&state)?
+        .status(query.sweeps.unwrap_or(20).min(1000))
+        .await
+        .map_err(|e| AdminError::Internal(format!("{:#}", e)))?;
+    Ok(Json(status))
+}
+
+/// GET /api/admin/payments/wallets/coverage
+///
+/// Hot wallet balance against owed payouts plus fee headroom, from the last check (or a new one)
+pub async fn get_wallet_coverage(
+    State(state): State<AdminState>,
+) -> Result<Json<WalletCoverage>, AdminError> {
+    let monitor = state.wallet_monitor.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Wallet monitoring is not enabled".to_string()))?;
+    let coverage = match monitor.latest().await {
+        Some(coverage) => coverage,
+        None => monitor.check().await.map_err(|e| AdminError::Internal(format!("{:#}", e)))?,
+    };
+    Ok(Json(coverage))
+}
+
+/// GET /api/admin/payments/coinbase
+///
+/// Returns the coinbase outputs planned for the next block
+pub async fn get_coinbase_plan(
+    State(state): State<AdminState>,
+) -> Result<Json<CoinbasePlan>, AdminError> {
+    let planner = state.coinbase.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Coinbase payouts are not enabled".to_string()))?;
+    let plan = planner.latest()
+        .await
+        .ok_or_else(|| AdminError::NotFound("No coinbase plan has been made yet".to_string()))?;
+    Ok(Json(plan.as_ref().clone()))
+}
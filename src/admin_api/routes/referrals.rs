@@ -0,0 +1,48 @@
+// Referral endpoints
+//
+// Referred miners and the referral credit ledger
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::referrals::{ReferralCredit, ReferralLink, ReferralProgram};
+
+fn referrals(state: &AdminState) -> Result<&ReferralProgram, AdminError> {
+    state.referrals.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Referral program is not enabled".to_string()))
+}
+
+/// GET /api/admin/referrals
+///
+/// Returns every referred miner with its referrer and code, oldest first
+pub async fn list_referrals(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<ReferralLink>>, AdminError> {
+    Ok(Json(referrals(&state)?.links().await))
+}
+
+#[derive(Deserialize)]
+pub struct ReferralLedgerQuery {
+    pub referrer: Option<String>,
+    #[serde(default = "default_ledger_limit")]
+    pub limit: usize,
+}
+
+fn default_ledger_limit() -> usize {
+    100
+}
+
+/// GET /api/admin/referrals/ledger?referrer=&limit=
+///
+/// Returns referral credits, newest first
+pub async fn get_referral_ledger(
+    State(state): State<AdminState>,
+    Query(query): Query<ReferralLedgerQuery>,
+) -> Result<Json<Vec<ReferralCredit>>, AdminError> {
+    Ok(Json(referrals(&state)?.ledger(query.referrer.as_deref(), query.limit.min(1000)).await))
+}
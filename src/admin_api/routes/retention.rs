@@ -0,0 +1,162 @@
+// Data Retention endpoints
+//
+// Provides endpoints for retention policies, manual purge runs, holds on
+// share windows and erasing a miner's data on request
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::Serialize;
+
+use crate::auth::Claims;
+use crate::logging::request_id::current_request_id;
+use crate::retention::{
+    MinerPurgeRequest, PlaceHoldRequest, PurgeReport, RetentionManager, RetentionPolicy, RetentionRun, ShareHold, ShareHolds,
+};
+
+fn retention_manager(state: &AdminState) -> Result<&RetentionManager, AdminError> {
+    state.retention.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Data retention is not available".to_string()))
+}
+
+fn share_holds(state: &AdminState) -> Result<&ShareHolds, AdminError> {
+    retention_manager(state)?.holds().map(|h| h.as_ref())
+        .ok_or_else(|| AdminError::NotFound("Share holds are not enabled".to_string()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionStatusResponse {
+    pub scheduled: bool,
+    pub interval_hours: u64,
+    pub policies: Vec<RetentionPolicy>,
+    pub last_run: Option<RetentionRun>,
+}
+
+/// GET /api/admin/retention
+///
+/// Returns the retention policies and the last purge run
+pub async fn get_retention_status(
+    State(state): State<AdminState>,
+) -> Result<Json<RetentionStatusResponse>, AdminError> {
+    let manager = retention_manager(&state)?;
+    let config = manager.config();
+
+    Ok(Json(RetentionStatusResponse {
+        scheduled: config.enabled,
+        interval_hours: config.interval_hours,
+        policies: config.policies(),
+        last_run: manager.last_run().await,
+    }))
+}
+
+/// POST /api/admin/retention/run
+///
+/// Applies every retention policy now
+pub async fn run_retention(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<RetentionRun>, AdminError> {
+    let run = retention_manager(&state)?.run().await;
+
+    let deleted: u64 = run.purged.iter().flat_map(|p| p.rows.values()).sum();
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ($1, 'retention_run', 'system', 'retention', $2, $3)",
+        &[&claims.name, &format!("deleted: {}, errors: {}", deleted, run.errors.len()), &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(run))
+}
+
+/// GET /api/admin/retention/holds
+///
+/// Lists active holds on share windows, earliest window first
+pub async fn list_share_holds(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<ShareHold>>, AdminError> {
+    Ok(Json(share_holds(&state)?.list().await?))
+}
+
+/// POST /api/admin/retention/holds
+///
+/// Keeps shares in a window from being purged until the hold expires or is released
+pub async fn place_share_hold(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<PlaceHoldRequest>,
+) -> Result<Json<ShareHold>, AdminError> {
+    let hold = share_holds(&state)?.place(&req, &claims.name).await?;
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ($1, 'place_share_hold', 'share_hold', $2, $3, $4)",
+        &[
+            &claims.name,
+            &hold.id,
+            &format!("window: {} to {}, expires: {}, reason: {}", hold.window_start, hold.window_end, hold.expires_at, hold.reason),
+            &current_request_id(),
+        ]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(hold))
+}
+
+/// DELETE /api/admin/retention/holds/:id
+///
+/// Releases a hold, letting its shares be purged again
+pub async fn release_share_hold(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    if !share_holds(&state)?.release(&id).await? {
+        return Err(AdminError::NotFound(format!("Share hold {} not found", id)));
+    }
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, request_id) VALUES ($1, 'release_share_hold', 'share_hold', $2, $3)",
+        &[&claims.name, &id, &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "released": id })))
+}
+
+/// POST /api/admin/miners/:address/purge
+///
+/// Deletes or anonymizes all data stored for a miner address. Needs a
+/// dmpool-admin token with the admin role; the audit entry records its
+/// username and the pseudonym, never the address.
+pub async fn purge_miner(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(address): Path<String>,
+    Json(req): Json<MinerPurgeRequest>,
+) -> Result<Json<PurgeReport>, AdminError> {
+    let report = retention_manager(&state)?.purge_miner(&address, &req).await?;
+
+    let rows: u64 = report.rows.values().sum();
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ($1, 'purge_miner', 'miner', $2, $3, $4)",
+        &[
+            &claims.name,
+            &report.pseudonym,
+            &format!("mode: {:?}, rows: {}, removed: {}, reason: {}", report.mode, rows, report.removed.len(), req.reason.as_deref().unwrap_or("-")),
+            &current_request_id(),
+        ]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(report))
+}
@@ -0,0 +1,108 @@
+// Fee Revenue endpoints
+//
+// Provides the pool operator's fee revenue ledger, period summaries and projections
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use crate::logging::request_id::current_request_id;
+use crate::revenue::{LedgerEntry, RevenueLedger, RevenueProjection, RevenueSummary, SummaryPeriod};
+
+/// Default summary range when `from` is omitted (days)
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct RevenueQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub period: Option<SummaryPeriod>,
+    pub limit: Option<i64>,
+}
+
+impl RevenueQuery {
+    fn range(&self) -> Result<(DateTime<Utc>, DateTime<Utc>), AdminError> {
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self.from.unwrap_or(to - Duration::days(DEFAULT_RANGE_DAYS));
+        if from >= to {
+            return Err(AdminError::InvalidInput("from must be before to".to_string()));
+        }
+        Ok((from, to))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DonationRequest {
+    pub amount_satoshis: u64,
+    pub note: Option<String>,
+}
+
+/// Get the revenue ledger or fail if it is not attached
+fn revenue_ledger(state: &AdminState) -> Result<&RevenueLedger, AdminError> {
+    state.revenue.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Revenue ledger is not available".to_string()))
+}
+
+/// GET /api/admin/revenue?from=&to=&limit=
+///
+/// Returns ledger entries, newest first
+pub async fn get_revenue_entries(
+    State(state): State<AdminState>,
+    Query(query): Query<RevenueQuery>,
+) -> Result<Json<Vec<LedgerEntry>>, AdminError> {
+    let (from, to) = query.range()?;
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    Ok(Json(revenue_ledger(&state)?.entries(from, to, limit).await?))
+}
+
+/// GET /api/admin/revenue/summary?period=daily|monthly&from=&to=
+///
+/// Returns fee income, payout fees, donations and net revenue per period
+pub async fn get_revenue_summary(
+    State(state): State<AdminState>,
+    Query(query): Query<RevenueQuery>,
+) -> Result<Json<Vec<RevenueSummary>>, AdminError> {
+    let (from, to) = query.range()?;
+    let period = query.period.unwrap_or_default();
+    Ok(Json(revenue_ledger(&state)?.summary(period, from, to).await?))
+}
+
+/// GET /api/admin/revenue/projection
+///
+/// Returns projected daily and monthly fee income at the current hashrate
+pub async fn get_revenue_projection(
+    State(state): State<AdminState>,
+) -> Result<Json<RevenueProjection>, AdminError> {
+    Ok(Json(revenue_ledger(&state)?.projection().await?))
+}
+
+/// POST /api/admin/revenue/donations
+///
+/// Records an operational donation paid from pool revenue
+pub async fn record_donation(
+    State(state): State<AdminState>,
+    Json(req): Json<DonationRequest>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    revenue_ledger(&state)?
+        .record_donation(req.amount_satoshis, req.note.clone())
+        .await
+        .map_err(|e| AdminError::InvalidInput(e.to_string()))?;
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ('admin', 'revenue_donation', 'system', 'revenue', $1, $2)",
+        &[&format!("{} sats: {}", req.amount_satoshis, req.note.as_deref().unwrap_or("")), &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "amount_satoshis": req.amount_satoshis,
+    })))
+}
@@ -0,0 +1,388 @@
+// System manager endpoints
+//
+// Audit trail, database backups, configuration version history, 2FA lockouts,
+// stratum IP scores, Observer API rate limits and IP blocks, the runtime log
+// filter and read-only maintenance mode
+
+use super::super::error::AdminError;
+use super::AdminState;
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::audit::{AuditFilter, AuditLog, AuditLogger};
+use crate::auth::Claims;
+use crate::backup::{BackupManager, BackupMetadata, BackupStats, CatalogEntry, DrillResult};
+use crate::config_mgt::{ConfigManager, ConfigProfile, ConfigVersion};
+use crate::logging::{request_id::current_request_id, LogControl, LogFilterStatus};
+use crate::maintenance::{ReadOnlyMode, ReadOnlyStatus};
+use crate::rate_limit::{IpBlock, IpScore, RateLimitStatus, RateLimitSummary, RateLimiterState, StratumScorer};
+use crate::two_factor::{TwoFactorLockout, TwoFactorManager};
+
+/// Default number of audit entries returned
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// Default number of backup catalog entries returned
+const DEFAULT_CATALOG_LIMIT: i64 = 100;
+
+/// Default number of rate limit offenders returned
+const DEFAULT_OFFENDER_LIMIT: usize = 20;
+
+fn audit_logger(state: &AdminState) -> Result<&AuditLogger, AdminError> {
+    state.audit.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Audit logging is not enabled".to_string()))
+}
+
+fn backup_manager(state: &AdminState) -> Result<&BackupManager, AdminError> {
+    state.backups.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Backups are not enabled".to_string()))
+}
+
+pub(super) fn config_versions(state: &AdminState) -> Result<&ConfigManager, AdminError> {
+    state.config_versions.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Config versioning is not enabled".to_string()))
+}
+
+fn two_factor(state: &AdminState) -> Result<&TwoFactorManager, AdminError> {
+    state.two_factor.as_deref()
+        .ok_or_else(|| AdminError::NotFound("2FA is not enabled".to_string()))
+}
+
+fn stratum_scorer(state: &AdminState) -> Result<&StratumScorer, AdminError> {
+    state.stratum_scorer.as_deref()
+        .ok_or_else(|| AdminError::NotFound("The stratum guard is not enabled".to_string()))
+}
+
+fn rate_limiter(state: &AdminState) -> Result<&RateLimiterState, AdminError> {
+    state.rate_limiter.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Observer API rate limiting is not enabled".to_string()))
+}
+
+fn parse_ip(ip: &str) -> Result<std::net::IpAddr, AdminError> {
+    ip.parse().map_err(|_| AdminError::InvalidInput(format!("Invalid IP address: {}", ip)))
+}
+
+fn log_control(state: &AdminState) -> Result<&LogControl, AdminError> {
+    state.logging.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Runtime log control is not enabled".to_string()))
+}
+
+/// Actor recorded by routes that are not behind an admin token
+pub(super) const UNAUTHENTICATED_ACTOR: &str = "admin";
+
+/// Record an admin action on a system resource
+pub(super) async fn log_system_action(state: &AdminState, actor: &str, action: &str, target_type: &str, target_id: &str) -> Result<(), AdminError> {
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, request_id) VALUES ($1, $2, $3, $4, $5)",
+        &[&actor, &action, &target_type, &target_id, &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+    Ok(())
+}
+
+/// GET /api/admin/audit?username=&action=&resource=&start_time=&end_time=&limit=
+///
+/// Returns audit entries matching the filter, newest first
+pub async fn get_audit_logs(
+    State(state): State<AdminState>,
+    Query(mut filter): Query<AuditFilter>,
+) -> Result<Json<Vec<AuditLog>>, AdminError> {
+    filter.limit = Some(filter.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, 1000));
+    Ok(Json(audit_logger(&state)?.query(filter).await))
+}
+
+/// GET /api/admin/backups
+///
+/// Returns existing backups
+pub async fn get_backups(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<BackupMetadata>>, AdminError> {
+    Ok(Json(backup_manager(&state)?.list_backups()?))
+}
+
+/// POST /api/admin/backups
+///
+/// Creates a backup of the share store now
+pub async fn create_backup(
+    State(state): State<AdminState>,
+) -> Result<Json<BackupMetadata>, AdminError> {
+    let metadata = backup_manager(&state)?.create_backup().await?;
+    log_system_action(&state, UNAUTHENTICATED_ACTOR, "backup_create", "backup", &metadata.id).await?;
+    Ok(Json(metadata))
+}
+
+/// GET /api/admin/backups/stats
+///
+/// Returns backup count, sizes and latest backup time
+pub async fn get_backup_stats(
+    State(state): State<AdminState>,
+) -> Result<Json<BackupStats>, AdminError> {
+    Ok(Json(backup_manager(&state)?.get_stats()?))
+}
+
+#[derive(Deserialize)]
+pub struct CatalogQuery {
+    pub limit: Option<i64>,
+}
+
+/// GET /api/admin/backups/catalog?limit=
+///
+/// Returns cataloged backups (local, remote and deleted) with validation state, newest first
+pub async fn get_backup_catalog(
+    State(state): State<AdminState>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<Vec<CatalogEntry>>, AdminError> {
+    let limit = query.limit.unwrap_or(DEFAULT_CATALOG_LIMIT).clamp(1, 1000);
+    Ok(Json(backup_manager(&state)?.catalog_entries(limit).await?))
+}
+
+/// GET /api/admin/backups/drills?limit=
+///
+/// Returns restore drill results, newest first
+pub async fn get_restore_drills(
+    State(state): State<AdminState>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<Vec<DrillResult>>, AdminError> {
+    let limit = query.limit.unwrap_or(DEFAULT_CATALOG_LIMIT).clamp(1, 1000);
+    Ok(Json(backup_manager(&state)?.restore_drills(limit).await?))
+}
+
+/// POST /api/admin/backups/drills
+///
+/// Runs a restore drill of the latest validated backup now
+pub async fn run_restore_drill(
+    State(state): State<AdminState>,
+) -> Result<Json<DrillResult>, AdminError> {
+    let result = backup_manager(&state)?.run_restore_drill().await;
+    log_system_action(&state, UNAUTHENTICATED_ACTOR, "restore_drill", "backup", result.backup_id.as_deref().unwrap_or(&result.id)).await?;
+    Ok(Json(result))
+}
+
+/// GET /api/admin/config/versions?profile=
+///
+/// Returns stored configuration versions, optionally of one profile
+pub async fn get_config_versions(
+    State(state): State<AdminState>,
+    Query(query): Query<ConfigVersionsQuery>,
+) -> Result<Json<Vec<ConfigVersion>>, AdminError> {
+    let manager = config_versions(&state)?;
+    Ok(Json(match query.profile {
+        Some(profile) => manager.list_profile_versions(profile).await,
+        None => manager.list_versions().await,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ConfigVersionsQuery {
+    pub profile: Option<ConfigProfile>,
+}
+
+#[derive(Deserialize)]
+pub struct PromoteRequest {
+    pub profile: ConfigProfile,
+}
+
+/// POST /api/admin/config/versions/:id/promote
+///
+/// Copies a version into the next profile (dev to staging, staging to prod)
+pub async fn promote_config_version(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(version_id): Path<String>,
+    Json(req): Json<PromoteRequest>,
+) -> Result<Json<ConfigVersion>, AdminError> {
+    let version = config_versions(&state)?.promote(&version_id, req.profile, claims.name.clone()).await?;
+
+    let conn = state.db.get_conn().await?;
+    conn.execute(
+        "INSERT INTO admin_audit_logs (admin_user, action, target_type, target_id, new_value, request_id) VALUES ($1, 'config_promote', 'config_version', $2, $3, $4)",
+        &[&claims.name, &version_id, &format!("{} -> {} ({})", version_id, version.id, req.profile), &current_request_id()]
+    )
+    .await
+    .map_err(|e| AdminError::Internal(format!("Failed to log audit: {}", e)))?;
+
+    Ok(Json(version))
+}
+
+/// GET /api/admin/2fa/lockouts
+///
+/// Returns users with failed 2FA attempts. Lockouts are cleared by an admin
+/// in dmpool-admin, not here.
+pub async fn get_two_factor_lockouts(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<TwoFactorLockout>>, AdminError> {
+    Ok(Json(two_factor(&state)?.list_lockouts().await))
+}
+
+/// GET /api/admin/stratum/scores
+///
+/// Returns stratum client IPs by abuse score, with any active ban
+pub async fn get_stratum_scores(
+    State(state): State<AdminState>,
+) -> Result<Json<Vec<IpScore>>, AdminError> {
+    Ok(Json(stratum_scorer(&state)?.scores(chrono::Utc::now()).await))
+}
+
+/// DELETE /api/admin/stratum/bans/:ip
+///
+/// Lifts a stratum IP ban and resets its score
+pub async fn unban_stratum_ip(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(ip): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let parsed = parse_ip(&ip)?;
+    if !stratum_scorer(&state)?.unban(parsed).await {
+        return Err(AdminError::NotFound(format!("No stratum score for {}", ip)));
+    }
+    log_system_action(&state, &claims.name, "stratum_unban", "ip", &ip).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "ip": ip,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct OffenderQuery {
+    pub limit: Option<usize>,
+}
+
+/// GET /api/admin/rate-limits?limit=
+///
+/// Returns allowed, rejected and blocked requests per class, the clients
+/// rejected most often and active IP blocks
+pub async fn get_rate_limits(
+    State(state): State<AdminState>,
+    Query(query): Query<OffenderQuery>,
+) -> Result<Json<RateLimitSummary>, AdminError> {
+    let limit = query.limit.unwrap_or(DEFAULT_OFFENDER_LIMIT);
+    Ok(Json(rate_limiter(&state)?.summary(limit).await))
+}
+
+/// GET /api/admin/rate-limits/:ip
+///
+/// Returns the current limit status of an IP
+pub async fn get_ip_rate_limit(
+    State(state): State<AdminState>,
+    Path(ip): Path<String>,
+) -> Result<Json<RateLimitStatus>, AdminError> {
+    let parsed = parse_ip(&ip)?;
+    Ok(Json(rate_limiter(&state)?.get_rate_limit_status(parsed).await))
+}
+
+#[derive(Deserialize)]
+pub struct IpBlockRequest {
+    pub minutes: u64,
+    pub reason: Option<String>,
+}
+
+/// PUT /api/admin/rate-limits/blocks/:ip
+///
+/// Refuses every Observer API request from an IP for a while
+pub async fn block_ip(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(ip): Path<String>,
+    Json(req): Json<IpBlockRequest>,
+) -> Result<Json<IpBlock>, AdminError> {
+    let parsed = parse_ip(&ip)?;
+    let block = rate_limiter(&state)?.block(parsed, req.minutes, req.reason, &claims.name).await?;
+    log_system_action(&state, &claims.name, "rate_limit_block", "ip", &ip).await?;
+    Ok(Json(block))
+}
+
+/// DELETE /api/admin/rate-limits/blocks/:ip
+///
+/// Lifts a manual IP block
+pub async fn unblock_ip(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(ip): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let parsed = parse_ip(&ip)?;
+    if !rate_limiter(&state)?.unblock(parsed).await {
+        return Err(AdminError::NotFound(format!("{} is not blocked", ip)));
+    }
+    log_system_action(&state, &claims.name, "rate_limit_unblock", "ip", &ip).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "ip": ip,
+    })))
+}
+
+/// GET /api/admin/logging
+///
+/// Returns the active log filter
+pub async fn get_log_filter(
+    State(state): State<AdminState>,
+) -> Result<Json<LogFilterStatus>, AdminError> {
+    Ok(Json(log_control(&state)?.status()))
+}
+
+#[derive(Deserialize)]
+pub struct LogFilterUpdate {
+    /// Tracing filter directives, e.g. `info,dmpool::payment=debug`
+    pub filter: String,
+}
+
+/// PUT /api/admin/logging
+///
+/// Replaces the active log filter without a restart
+pub async fn update_log_filter(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(update): Json<LogFilterUpdate>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let control = log_control(&state)?;
+    let previous = control.set_filter(&update.filter, &claims.name)
+        .map_err(|e| AdminError::InvalidInput(format!("{:#}", e)))?;
+    log_system_action(&state, &claims.name, "log_filter_update", "logging", &update.filter).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "previous": previous,
+        "filter": control.status().filter,
+    })))
+}
+
+fn read_only_mode(state: &AdminState) -> Result<&ReadOnlyMode, AdminError> {
+    state.read_only.as_deref()
+        .ok_or_else(|| AdminError::NotFound("Read-only mode is not available".to_string()))
+}
+
+/// GET /api/admin/maintenance/read-only
+pub async fn get_read_only(
+    State(state): State<AdminState>,
+) -> Result<Json<ReadOnlyStatus>, AdminError> {
+    Ok(Json(read_only_mode(&state)?.status().await))
+}
+
+#[derive(Deserialize)]
+pub struct ReadOnlyUpdate {
+    pub read_only: bool,
+    /// Shown to blocked requests, e.g. "restoring backup"
+    pub reason: Option<String>,
+}
+
+/// PUT /api/admin/maintenance/read-only
+///
+/// Turns read-only maintenance mode on or off; this endpoint stays writable
+pub async fn set_read_only(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Json(update): Json<ReadOnlyUpdate>,
+) -> Result<Json<ReadOnlyStatus>, AdminError> {
+    let status = read_only_mode(&state)?.set(update.read_only, update.reason, &claims.name).await?;
+    let action = if status.read_only { "read_only_enable" } else { "read_only_disable" };
+    // The database may be the thing under maintenance, so a failed audit write doesn't undo the toggle
+    if let Err(e) = log_system_action(&state, &claims.name, action, "maintenance", status.reason.as_deref().unwrap_or("")).await {
+        tracing::warn!("Failed to audit read-only toggle: {}", e);
+    }
+    Ok(Json(status))
+}
@@ -0,0 +1,266 @@
+// Hashrate anomaly detection
+//
+// Models expected pool and per-miner hashrate with an EWMA baseline plus an
+// hour-of-day seasonal component, and raises HashrateAnomaly alerts on
+// sudden drops or suspicious spikes.
+
+use super::{AlertCondition, AlertManager};
+use anyhow::Result;
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// Default EWMA smoothing factor
+const DEFAULT_ALPHA: f64 = 0.1;
+
+/// Default samples required before a baseline is trusted
+const DEFAULT_MIN_SAMPLES: u32 = 12;
+
+/// Seasonal bucket samples required before it replaces the overall mean
+const MIN_SEASONAL_SAMPLES: u32 = 3;
+
+/// Deviation floor as a fraction of the expected value, so a very steady
+/// baseline doesn't alert on tiny fluctuations
+const MIN_DEVIATION_RATIO: f64 = 0.05;
+
+/// Direction of an anomaly
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyDirection {
+    Drop,
+    Spike,
+}
+
+/// How far a sample is from its baseline
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HashrateDeviation {
+    /// Miner address, or None for the whole pool
+    pub miner: Option<String>,
+    pub observed: f64,
+    pub expected: f64,
+    /// Standard deviations from the expected value
+    pub z_score: f64,
+    pub direction: AnomalyDirection,
+}
+
+/// EWMA mean/variance with hour-of-day seasonal means
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HashrateBaseline {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: u32,
+    /// Per-hour (UTC) EWMA mean and sample count
+    pub hourly: Vec<(f64, u32)>,
+}
+
+impl HashrateBaseline {
+    /// Expected hashrate at the given time
+    pub fn expected(&self, at: DateTime<Utc>) -> f64 {
+        match self.hourly.get(at.hour() as usize) {
+            Some(&(mean, samples)) if samples >= MIN_SEASONAL_SAMPLES => mean,
+            _ => self.mean,
+        }
+    }
+
+    /// Fold a sample into the baseline
+    pub fn update(&mut self, hashrate: f64, at: DateTime<Utc>, alpha: f64) {
+        if self.samples == 0 {
+            self.mean = hashrate;
+            self.variance = 0.0;
+        } else {
+            let diff = hashrate - self.mean;
+            self.mean += alpha * diff;
+            self.variance = (1.0 - alpha) * (self.variance + alpha * diff * diff);
+        }
+        self.samples = self.samples.saturating_add(1);
+
+        if self.hourly.len() != 24 {
+            self.hourly = vec![(0.0, 0); 24];
+        }
+        let bucket = &mut self.hourly[at.hour() as usize];
+        bucket.0 = if bucket.1 == 0 { hashrate } else { bucket.0 + alpha * (hashrate - bucket.0) };
+        bucket.1 = bucket.1.saturating_add(1);
+    }
+
+    /// Deviation of a sample from the baseline (None during warm-up)
+    pub fn deviation(&self, hashrate: f64, at: DateTime<Utc>, min_samples: u32) -> Option<(f64, f64)> {
+        if self.samples < min_samples {
+            return None;
+        }
+        let expected = self.expected(at);
+        let std_dev = self.variance.sqrt().max(expected.abs() * MIN_DEVIATION_RATIO);
+        if std_dev <= 0.0 {
+            return None;
+        }
+        Some((expected, (hashrate - expected) / std_dev))
+    }
+}
+
+/// Tracks hashrate baselines and raises anomaly alerts
+pub struct HashrateAnomalyDetector {
+    alpha: f64,
+    min_samples: u32,
+    baselines: RwLock<HashMap<Option<String>, HashrateBaseline>>,
+}
+
+impl Default for HashrateAnomalyDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_ALPHA, DEFAULT_MIN_SAMPLES)
+    }
+}
+
+impl HashrateAnomalyDetector {
+    /// Create a detector with the given smoothing factor and warm-up length
+    pub fn new(alpha: f64, min_samples: u32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.001, 1.0),
+            min_samples,
+            baselines: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a sample, returning its deviation from the prior baseline
+    pub async fn observe(&self, miner: Option<&str>, hashrate: f64, at: DateTime<Utc>) -> Option<HashrateDeviation> {
+        let mut baselines = self.baselines.write().await;
+        let baseline = baselines.entry(miner.map(str::to_string)).or_default();
+
+        let deviation = baseline.deviation(hashrate, at, self.min_samples)
+            .map(|(expected, z_score)| HashrateDeviation {
+                miner: miner.map(str::to_string),
+                observed: hashrate,
+                expected,
+                z_score,
+                direction: if z_score < 0.0 { AnomalyDirection::Drop } else { AnomalyDirection::Spike },
+            });
+
+        baseline.update(hashrate, at, self.alpha);
+        deviation
+    }
+
+    /// Current baseline for the pool (None) or a miner
+    pub async fn baseline(&self, miner: Option<&str>) -> Option<HashrateBaseline> {
+        self.baselines.read().await.get(&miner.map(str::to_string)).cloned()
+    }
+
+    /// Stop tracking a miner
+    pub async fn forget(&self, miner: &str) {
+        self.baselines.write().await.remove(&Some(miner.to_string()));
+    }
+
+    /// Record a sample and trigger every matching HashrateAnomaly rule it breaches
+    ///
+    /// Matching rules the sample does not breach are cleared. Returns the ids
+    /// of the rules that fired.
+    pub async fn check(
+        &self,
+        alerts: &AlertManager,
+        miner: Option<&str>,
+        hashrate: f64,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let deviation = match self.observe(miner, hashrate, at).await {
+            Some(deviation) => deviation,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut fired = Vec::new();
+        for rule in alerts.get_rules().await {
+            if !rule.enabled {
+                continue;
+            }
+            let (rule_miner, sensitivity, detect_spikes) = match &rule.condition {
+                AlertCondition::HashrateAnomaly { miner, sensitivity, detect_spikes } => {
+                    (miner.as_deref(), *sensitivity, *detect_spikes)
+                }
+                _ => continue,
+            };
+            if rule_miner != miner {
+                continue;
+            }
+            let breached = deviation.z_score.abs() >= sensitivity
+                && (deviation.direction != AnomalyDirection::Spike || detect_spikes);
+            if !breached {
+                // Back within the baseline
+                alerts.clear_rule(&rule.id, serde_json::to_value(&deviation)?).await?;
+                continue;
+            }
+
+            warn!(
+                "Hashrate {:?} for {}: {:.2} vs expected {:.2} (z={:.2})",
+                deviation.direction,
+                miner.unwrap_or("pool"),
+                deviation.observed,
+                deviation.expected,
+                deviation.z_score
+            );
+            alerts.trigger_alert(&rule.id, serde_json::to_value(&deviation)?).await?;
+            fired.push(rule.id);
+        }
+
+        Ok(fired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertLevel, AlertRule};
+    use chrono::Duration;
+
+    async fn warm_up(detector: &HashrateAnomalyDetector, miner: Option<&str>, start: DateTime<Utc>) -> DateTime<Utc> {
+        let mut at = start;
+        for i in 0..24 {
+            let hashrate = if i % 2 == 0 { 100.0 } else { 104.0 };
+            detector.observe(miner, hashrate, at).await;
+            at += Duration::minutes(10);
+        }
+        at
+    }
+
+    #[tokio::test]
+    async fn test_detects_drop_after_warm_up() {
+        let detector = HashrateAnomalyDetector::default();
+        let at = warm_up(&detector, None, Utc::now()).await;
+
+        let drop = detector.observe(None, 40.0, at).await.unwrap();
+        assert_eq!(drop.direction, AnomalyDirection::Drop);
+        assert!(drop.z_score < -5.0);
+
+        let normal = detector.observe(None, 101.0, at).await.unwrap();
+        assert!(normal.z_score.abs() < 3.0);
+
+        // Warm-up yields no deviation
+        assert!(detector.observe(Some("bc1qnew"), 1.0, at).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rule_sensitivity_and_scope() {
+        let alerts = AlertManager::default();
+        alerts.add_rule(AlertRule::new(
+            "pool_anomaly",
+            "Pool hashrate anomaly",
+            AlertCondition::HashrateAnomaly { miner: None, sensitivity: 4.0, detect_spikes: false },
+            AlertLevel::Warning,
+            Vec::new(),
+        ).with_cooldown(0)).await;
+        alerts.add_rule(AlertRule::new(
+            "miner_anomaly",
+            "Miner hashrate anomaly",
+            AlertCondition::HashrateAnomaly { miner: Some("bc1qa".to_string()), sensitivity: 4.0, detect_spikes: true },
+            AlertLevel::Warning,
+            Vec::new(),
+        ).with_cooldown(0)).await;
+
+        let detector = HashrateAnomalyDetector::default();
+        let at = warm_up(&detector, None, Utc::now()).await;
+        warm_up(&detector, Some("bc1qa"), Utc::now()).await;
+
+        assert_eq!(detector.check(&alerts, None, 10.0, at).await.unwrap(), vec!["pool_anomaly"]);
+        assert_eq!(detector.check(&alerts, Some("bc1qa"), 500.0, at).await.unwrap(), vec!["miner_anomaly"]);
+        // Pool spikes are ignored by the pool rule
+        assert!(detector.check(&alerts, None, 500.0, at).await.unwrap().is_empty());
+        assert_eq!(alerts.get_history(None).await.len(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+// Audit log anomaly detection
+//
+// Evaluates audit entries as they are logged against AuditAnomaly rules:
+// bursts of failed actions (e.g. logins), changes made outside business
+// hours, and actions performed by a role that should not perform them.
+
+use super::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::audit::AuditLog;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// What an AuditAnomaly rule looks for
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditPattern {
+    /// More than `threshold` failed `action` entries from one user (or IP) within the window
+    RepeatedFailures {
+        action: String,
+        threshold: u32,
+        window_minutes: u64,
+        /// Count per client IP instead of per username
+        #[serde(default)]
+        per_ip: bool,
+    },
+    /// Successful actions starting with `action_prefix` outside business hours (UTC)
+    OutsideHours {
+        action_prefix: String,
+        start_hour: u32,
+        end_hour: u32,
+        /// Saturdays and Sundays are outside business hours
+        #[serde(default)]
+        weekdays_only: bool,
+    },
+    /// Actions starting with `action_prefix` by a role not in `allowed_roles`
+    ///
+    /// Entries without a role (system actions) never match.
+    RoleNotAllowed {
+        action_prefix: String,
+        allowed_roles: Vec<String>,
+    },
+}
+
+impl AuditPattern {
+    /// Whether an entry is inside business hours
+    fn in_hours(at: DateTime<Utc>, start_hour: u32, end_hour: u32, weekdays_only: bool) -> bool {
+        if weekdays_only && matches!(at.weekday(), Weekday::Sat | Weekday::Sun) {
+            return false;
+        }
+        let hour = at.hour();
+        if start_hour <= end_hour {
+            hour >= start_hour && hour < end_hour
+        } else {
+            // Business hours spanning midnight
+            hour >= start_hour || hour < end_hour
+        }
+    }
+}
+
+/// Built-in rules: failed logins, off-hours config changes and payouts by other roles
+pub fn default_audit_rules(channels: Vec<String>) -> Vec<AlertRule> {
+    vec![
+        AlertRule::new(
+            "audit_failed_logins",
+            "Repeated failed logins",
+            AlertCondition::AuditAnomaly {
+                pattern: AuditPattern::RepeatedFailures {
+                    action: "login".to_string(),
+                    threshold: 5,
+                    window_minutes: 10,
+                    per_ip: false,
+                },
+            },
+            AlertLevel::Warning,
+            channels.clone(),
+        ).with_cooldown(10),
+        AlertRule::new(
+            "audit_config_off_hours",
+            "Config change outside business hours",
+            AlertCondition::AuditAnomaly {
+                pattern: AuditPattern::OutsideHours {
+                    action_prefix: "config".to_string(),
+                    start_hour: 8,
+                    end_hour: 18,
+                    weekdays_only: true,
+                },
+            },
+            AlertLevel::Warning,
+            channels.clone(),
+        ).with_cooldown(0),
+        AlertRule::new(
+            "audit_payout_role",
+            "Payout by a non-payout role",
+            AlertCondition::AuditAnomaly {
+                pattern: AuditPattern::RoleNotAllowed {
+                    action_prefix: "payout".to_string(),
+                    allowed_roles: vec!["admin".to_string(), "payout".to_string()],
+                },
+            },
+            AlertLevel::Critical,
+            channels,
+        ).with_cooldown(0),
+    ]
+}
+
+/// Tracks recent failures and raises AuditAnomaly alerts
+#[derive(Default)]
+pub struct AuditAnomalyDetector {
+    /// Failure times per (rule, user or IP)
+    failures: RwLock<HashMap<(String, String), VecDeque<DateTime<Utc>>>>,
+}
+
+impl AuditAnomalyDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluate an entry against every enabled AuditAnomaly rule
+    ///
+    /// Returns the ids of the rules that fired.
+    pub async fn check(&self, alerts: &AlertManager, entry: &AuditLog) -> Result<Vec<String>> {
+        let mut fired = Vec::new();
+        for rule in alerts.get_rules().await {
+            if !rule.enabled {
+                continue;
+            }
+            let pattern = match &rule.condition {
+                AlertCondition::AuditAnomaly { pattern } => pattern,
+                _ => continue,
+            };
+            let context = match self.evaluate(&rule.id, pattern, entry).await {
+                Some(context) => context,
+                None => continue,
+            };
+
+            warn!("Audit anomaly ({}): {} {} {}", rule.id, entry.username, entry.action, entry.resource);
+            alerts.trigger_alert(&rule.id, context).await?;
+            fired.push(rule.id);
+        }
+        Ok(fired)
+    }
+
+    /// Alert context when the entry matches the pattern
+    async fn evaluate(&self, rule_id: &str, pattern: &AuditPattern, entry: &AuditLog) -> Option<serde_json::Value> {
+        let mut context = serde_json::json!({
+            "username": entry.username,
+            "role": entry.role,
+            "action": entry.action,
+            "resource": entry.resource,
+            "ip_address": entry.ip_address,
+            "timestamp": entry.timestamp,
+        });
+
+        match pattern {
+            AuditPattern::RepeatedFailures { action, threshold, window_minutes, per_ip } => {
+                if entry.success || entry.action != *action {
+                    return None;
+                }
+                let key = if *per_ip { &entry.ip_address } else { &entry.username };
+                let window_start = entry.timestamp - Duration::minutes(*window_minutes as i64);
+
+                let mut failures = self.failures.write().await;
+                let times = failures.entry((rule_id.to_string(), key.clone())).or_default();
+                times.push_back(entry.timestamp);
+                while times.front().is_some_and(|t| *t <= window_start) {
+                    times.pop_front();
+                }
+                if times.len() <= *threshold as usize {
+                    return None;
+                }
+                let count = times.len();
+                // Start counting again so one burst raises one alert
+                times.clear();
+                context["failures"] = serde_json::json!(count);
+                context["window_minutes"] = serde_json::json!(window_minutes);
+            }
+            AuditPattern::OutsideHours { action_prefix, start_hour, end_hour, weekdays_only } => {
+                if !entry.success || !entry.action.starts_with(action_prefix.as_str())
+                    || AuditPattern::in_hours(entry.timestamp, *start_hour, *end_hour, *weekdays_only)
+                {
+                    return None;
+                }
+            }
+            AuditPattern::RoleNotAllowed { action_prefix, allowed_roles } => {
+                let role = entry.role.as_ref()?;
+                if !entry.action.starts_with(action_prefix.as_str()) || allowed_roles.contains(role) {
+                    return None;
+                }
+            }
+        }
+        Some(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(username: &str, role: Option<&str>, action: &str, success: bool, at: DateTime<Utc>) -> AuditLog {
+        AuditLog {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: at,
+            username: username.to_string(),
+            role: role.map(str::to_string),
+            action: action.to_string(),
+            resource: "/api/test".to_string(),
+            ip_address: "10.0.0.1".to_string(),
+            details: serde_json::json!({}),
+            success,
+            error: None,
+            request_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failed_logins() {
+        let alerts = AlertManager::default();
+        for rule in default_audit_rules(vec![]) {
+            alerts.add_rule(rule).await;
+        }
+        let detector = AuditAnomalyDetector::new();
+        let start = Utc.with_ymd_and_hms(2026, 3, 3, 10, 0, 0).unwrap();
+
+        for i in 0..5 {
+            let at = start + Duration::minutes(i);
+            assert!(detector.check(&alerts, &entry("mallory", None, "login", false, at)).await.unwrap().is_empty());
+        }
+        // Other users and successful logins are counted separately
+        assert!(detector.check(&alerts, &entry("alice", None, "login", false, start)).await.unwrap().is_empty());
+        assert!(detector.check(&alerts, &entry("mallory", None, "login", true, start)).await.unwrap().is_empty());
+
+        let sixth = entry("mallory", None, "login", false, start + Duration::minutes(6));
+        assert_eq!(detector.check(&alerts, &sixth).await.unwrap(), vec!["audit_failed_logins"]);
+
+        // Failures spread beyond the window don't add up
+        let later = start + Duration::hours(1);
+        for i in 0..6 {
+            let at = later + Duration::minutes(i * 3);
+            assert!(detector.check(&alerts, &entry("bob", None, "login", false, at)).await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_off_hours_changes_and_roles() {
+        let alerts = AlertManager::default();
+        for rule in default_audit_rules(vec![]) {
+            alerts.add_rule(rule).await;
+        }
+        let detector = AuditAnomalyDetector::new();
+
+        // Tuesday 10:00 is inside business hours, 23:00 and Saturday are not
+        let tuesday = Utc.with_ymd_and_hms(2026, 3, 3, 10, 0, 0).unwrap();
+        assert!(detector.check(&alerts, &entry("alice", Some("admin"), "config_update", true, tuesday)).await.unwrap().is_empty());
+        let night = Utc.with_ymd_and_hms(2026, 3, 3, 23, 0, 0).unwrap();
+        assert_eq!(detector.check(&alerts, &entry("alice", Some("admin"), "config_update", true, night)).await.unwrap(), vec!["audit_config_off_hours"]);
+        let saturday = Utc.with_ymd_and_hms(2026, 3, 7, 11, 0, 0).unwrap();
+        assert_eq!(detector.check(&alerts, &entry("alice", Some("admin"), "config_applied", true, saturday)).await.unwrap(), vec!["audit_config_off_hours"]);
+
+        assert!(detector.check(&alerts, &entry("carol", Some("payout"), "payout_create", true, tuesday)).await.unwrap().is_empty());
+        assert!(detector.check(&alerts, &entry("system", None, "payout_broadcast", true, tuesday)).await.unwrap().is_empty());
+        assert_eq!(detector.check(&alerts, &entry("dave", Some("viewer"), "payout_create", true, tuesday)).await.unwrap(), vec!["audit_payout_role"]);
+
+        assert!(AuditPattern::in_hours(Utc.with_ymd_and_hms(2026, 3, 3, 23, 0, 0).unwrap(), 22, 6, false));
+        assert_eq!(alerts.get_history(None).await.len(), 3);
+    }
+}
@@ -0,0 +1,345 @@
+// Alert delivery queue
+//
+// Failed channel deliveries are retried with exponential backoff. Alerts that
+// exhaust their attempts land in a persistent dead-letter store that operators
+// can inspect and re-drive, and per-channel delivery counters back the
+// success-rate metrics.
+
+use super::Alert;
+use crate::persist;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Default delivery attempts before an alert is dead-lettered
+const DEFAULT_MAX_ATTEMPTS: u32 = 6;
+
+/// Default delay before the first retry (seconds)
+const DEFAULT_INITIAL_BACKOFF_SECS: i64 = 10;
+
+/// Default upper bound on the retry delay (seconds)
+const DEFAULT_MAX_BACKOFF_SECS: i64 = 1800;
+
+/// Retry policy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryPolicy {
+    /// Attempts, including the first, before dead-lettering
+    pub max_attempts: u32,
+    pub initial_backoff_secs: i64,
+    pub max_backoff_secs: i64,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff_secs: DEFAULT_INITIAL_BACKOFF_SECS,
+            max_backoff_secs: DEFAULT_MAX_BACKOFF_SECS,
+        }
+    }
+}
+
+impl DeliveryPolicy {
+    /// Delay before the next attempt after `attempts` failures
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1i64 << attempts.saturating_sub(1).min(30);
+        Duration::seconds(self.initial_backoff_secs.saturating_mul(factor).min(self.max_backoff_secs))
+    }
+}
+
+/// Alert waiting for a retry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedDelivery {
+    pub id: String,
+    pub channel: String,
+    pub alert: Alert,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+}
+
+/// Alert that exhausted its delivery attempts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    pub channel: String,
+    pub alert: Alert,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Delivery counters for one channel
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChannelDeliveryStats {
+    pub channel: String,
+    pub delivered: u64,
+    pub failed_attempts: u64,
+    pub dead_lettered: u64,
+    /// Successful attempts over all attempts (1.0 when nothing was sent)
+    pub success_rate: f64,
+}
+
+/// Queue and dead-letter overview
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryStats {
+    pub pending: usize,
+    pub dead_letters: usize,
+    pub channels: Vec<ChannelDeliveryStats>,
+}
+
+/// Persisted queue contents
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueState {
+    pending: Vec<QueuedDelivery>,
+    dead_letters: Vec<DeadLetter>,
+}
+
+/// Retry queue with a persistent dead-letter store
+pub struct DeliveryQueue {
+    policy: DeliveryPolicy,
+    path: PathBuf,
+    state: RwLock<QueueState>,
+    stats: RwLock<HashMap<String, ChannelDeliveryStats>>,
+}
+
+impl DeliveryQueue {
+    /// Create a queue persisted in `data_dir`
+    pub fn new(data_dir: PathBuf, policy: DeliveryPolicy) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create alert delivery directory")?;
+
+        Ok(Self {
+            policy,
+            path: data_dir.join("delivery_queue.json"),
+            state: RwLock::new(QueueState::default()),
+            stats: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Load pending retries and dead letters from disk
+    pub async fn load(&self) -> Result<()> {
+        let Some(state) = persist::read_json::<QueueState>(&self.path).await
+            .context("Failed to load alert delivery queue")?
+        else {
+            return Ok(());
+        };
+        info!("Loaded {} pending alert deliveries and {} dead letters", state.pending.len(), state.dead_letters.len());
+        *self.state.write().await = state;
+        Ok(())
+    }
+
+    /// Persist the queue
+    async fn save(&self, state: &QueueState) -> Result<()> {
+        persist::write_json(&self.path, state).await
+            .context("Failed to write alert delivery queue")
+    }
+
+    /// Count a successful delivery
+    pub async fn record_success(&self, channel: &str) {
+        self.stats.write().await.entry(channel.to_string()).or_default().delivered += 1;
+    }
+
+    /// Count a failed attempt that will not be retried (a failover channel took over)
+    pub async fn record_failure(&self, channel: &str) {
+        self.stats.write().await.entry(channel.to_string()).or_default().failed_attempts += 1;
+    }
+
+    /// Queue a retry for a delivery that failed on its first attempt
+    pub async fn enqueue_failure(&self, channel: &str, alert: &Alert, error: String) -> Result<()> {
+        self.complete(QueuedDelivery {
+            id: uuid::Uuid::new_v4().to_string(),
+            channel: channel.to_string(),
+            alert: alert.clone(),
+            attempts: 0,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+        }, Err(error)).await
+    }
+
+    /// Remove and return deliveries due by `now`
+    pub async fn take_due(&self, now: DateTime<Utc>) -> Vec<QueuedDelivery> {
+        let mut state = self.state.write().await;
+        let (due, waiting) = std::mem::take(&mut state.pending)
+            .into_iter()
+            .partition(|d| d.next_attempt_at <= now);
+        state.pending = waiting;
+        due
+    }
+
+    /// Record the outcome of an attempt, rescheduling or dead-lettering failures
+    pub async fn complete(&self, mut delivery: QueuedDelivery, outcome: std::result::Result<(), String>) -> Result<()> {
+        let mut stats = self.stats.write().await;
+        let channel_stats = stats.entry(delivery.channel.clone()).or_default();
+        let mut state = self.state.write().await;
+
+        match outcome {
+            Ok(()) => {
+                channel_stats.delivered += 1;
+                info!("Alert {} delivered via {} after {} retries", delivery.alert.id, delivery.channel, delivery.attempts);
+            }
+            Err(error) => {
+                channel_stats.failed_attempts += 1;
+                delivery.attempts += 1;
+
+                if delivery.attempts >= self.policy.max_attempts {
+                    channel_stats.dead_lettered += 1;
+                    warn!("Alert {} via {} dead-lettered after {} attempts: {}", delivery.alert.id, delivery.channel, delivery.attempts, error);
+                    state.dead_letters.push(DeadLetter {
+                        id: delivery.id,
+                        channel: delivery.channel,
+                        alert: delivery.alert,
+                        attempts: delivery.attempts,
+                        last_error: error,
+                        failed_at: Utc::now(),
+                    });
+                } else {
+                    delivery.next_attempt_at = Utc::now() + self.policy.backoff(delivery.attempts);
+                    delivery.last_error = Some(error);
+                    state.pending.push(delivery);
+                }
+            }
+        }
+
+        self.save(&state).await
+    }
+
+    /// Pending retries
+    pub async fn pending(&self) -> Vec<QueuedDelivery> {
+        self.state.read().await.pending.clone()
+    }
+
+    /// Dead letters, newest first
+    pub async fn dead_letters(&self) -> Vec<DeadLetter> {
+        let mut dead_letters = self.state.read().await.dead_letters.clone();
+        dead_letters.reverse();
+        dead_letters
+    }
+
+    /// Move a dead letter back into the queue for immediate delivery
+    pub async fn redrive(&self, id: &str) -> Result<bool> {
+        let mut state = self.state.write().await;
+        let pos = match state.dead_letters.iter().position(|d| d.id == id) {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+
+        let dead = state.dead_letters.remove(pos);
+        state.pending.push(QueuedDelivery {
+            id: dead.id,
+            channel: dead.channel,
+            alert: dead.alert,
+            attempts: 0,
+            next_attempt_at: Utc::now(),
+            last_error: Some(dead.last_error),
+        });
+        self.save(&state).await?;
+        Ok(true)
+    }
+
+    /// Drop a dead letter
+    pub async fn discard(&self, id: &str) -> Result<bool> {
+        let mut state = self.state.write().await;
+        let before = state.dead_letters.len();
+        state.dead_letters.retain(|d| d.id != id);
+        if state.dead_letters.len() == before {
+            return Ok(false);
+        }
+        self.save(&state).await?;
+        Ok(true)
+    }
+
+    /// Queue size and per-channel delivery counters
+    pub async fn stats(&self) -> DeliveryStats {
+        let state = self.state.read().await;
+        let mut channels: Vec<ChannelDeliveryStats> = self.stats.read().await.iter()
+            .map(|(channel, stats)| {
+                let attempts = stats.delivered + stats.failed_attempts;
+                ChannelDeliveryStats {
+                    channel: channel.clone(),
+                    success_rate: if attempts == 0 { 1.0 } else { stats.delivered as f64 / attempts as f64 },
+                    ..stats.clone()
+                }
+            })
+            .collect();
+        channels.sort_by(|a, b| a.channel.cmp(&b.channel));
+
+        DeliveryStats {
+            pending: state.pending.len(),
+            dead_letters: state.dead_letters.len(),
+            channels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertLevel;
+    use tempfile::TempDir;
+
+    fn alert() -> Alert {
+        Alert {
+            id: "alert-1".to_string(),
+            rule_id: "rule".to_string(),
+            level: AlertLevel::Warning,
+            title: "Test".to_string(),
+            message: "Test alert".to_string(),
+            context: serde_json::json!({}),
+            triggered_at: Utc::now(),
+            acknowledged: false,
+            channel: "telegram".to_string(),
+            template_format: None,
+            deliveries: Vec::new(),
+            resolution: None,
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_until_capped() {
+        let policy = DeliveryPolicy { max_attempts: 10, initial_backoff_secs: 10, max_backoff_secs: 60 };
+        assert_eq!(policy.backoff(1).num_seconds(), 10);
+        assert_eq!(policy.backoff(2).num_seconds(), 20);
+        assert_eq!(policy.backoff(3).num_seconds(), 40);
+        assert_eq!(policy.backoff(4).num_seconds(), 60);
+        assert_eq!(policy.backoff(40).num_seconds(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_persist_and_redrive() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = DeliveryPolicy { max_attempts: 2, initial_backoff_secs: 0, max_backoff_secs: 0 };
+        let queue = DeliveryQueue::new(temp_dir.path().to_path_buf(), policy.clone()).unwrap();
+
+        queue.record_success("telegram").await;
+        queue.enqueue_failure("telegram", &alert(), "timeout".to_string()).await.unwrap();
+        assert_eq!(queue.pending().await.len(), 1);
+
+        // Second failure exhausts the attempts
+        let due = queue.take_due(Utc::now()).await;
+        assert_eq!(due.len(), 1);
+        queue.complete(due.into_iter().next().unwrap(), Err("502".to_string())).await.unwrap();
+
+        let stats = queue.stats().await;
+        assert_eq!((stats.pending, stats.dead_letters), (0, 1));
+        assert_eq!(stats.channels[0].dead_lettered, 1);
+        assert!((stats.channels[0].success_rate - 1.0 / 3.0).abs() < 1e-9);
+
+        // Dead letters survive a restart and can be re-driven
+        let reloaded = DeliveryQueue::new(temp_dir.path().to_path_buf(), policy).unwrap();
+        reloaded.load().await.unwrap();
+        let dead = reloaded.dead_letters().await;
+        assert_eq!(dead[0].last_error, "502");
+
+        assert!(reloaded.redrive(&dead[0].id).await.unwrap());
+        assert!(!reloaded.redrive(&dead[0].id).await.unwrap());
+        let due = reloaded.take_due(Utc::now()).await;
+        assert_eq!(due[0].attempts, 0);
+        reloaded.complete(due.into_iter().next().unwrap(), Ok(())).await.unwrap();
+        assert_eq!(reloaded.stats().await.channels[0].delivered, 1);
+    }
+}
@@ -0,0 +1,297 @@
+// Incident management delivery
+//
+// Incident channels open an incident in PagerDuty (Events API v2) or Opsgenie
+// (Alert API) instead of sending a message. Every alert for a rule shares one
+// deduplication key, so repeat alerts update the open incident rather than
+// paging again, and the incident is resolved when the alert is acknowledged or
+// its condition clears (`AlertManager::resolve_rule`).
+
+use super::{Alert, AlertChannel, AlertLevel};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default PagerDuty Events API v2 endpoint
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Default Opsgenie Alert API base
+pub const OPSGENIE_API_URL: &str = "https://api.opsgenie.com/v2/alerts";
+
+/// Longest Opsgenie alert message
+const OPSGENIE_MAX_MESSAGE: usize = 130;
+
+/// Incident service the channel talks to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentProviderKind {
+    PagerDuty,
+    Opsgenie,
+}
+
+impl IncidentProviderKind {
+    /// Channel name used for the provider in `[dmpool.alerts]`
+    pub fn channel_name(&self) -> &'static str {
+        match self {
+            Self::PagerDuty => "pagerduty",
+            Self::Opsgenie => "opsgenie",
+        }
+    }
+}
+
+/// Deduplication key shared by every incident a rule raises
+pub fn dedup_key(rule_id: &str) -> String {
+    format!("dmpool/{}", rule_id)
+}
+
+/// PagerDuty event severity for an alert level
+pub fn pagerduty_severity(level: AlertLevel) -> &'static str {
+    match level {
+        AlertLevel::Info => "info",
+        AlertLevel::Warning => "warning",
+        AlertLevel::Critical => "critical",
+    }
+}
+
+/// Opsgenie priority for an alert level
+pub fn opsgenie_priority(level: AlertLevel) -> &'static str {
+    match level {
+        AlertLevel::Info => "P5",
+        AlertLevel::Warning => "P3",
+        AlertLevel::Critical => "P1",
+    }
+}
+
+/// Opens and resolves incidents
+#[async_trait]
+pub trait IncidentClient: Send + Sync {
+    /// Open an incident, or update the open one with the same key
+    async fn trigger(&self, dedup_key: &str, alert: &Alert) -> Result<()>;
+    async fn resolve(&self, dedup_key: &str) -> Result<()>;
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .unwrap_or_default()
+}
+
+/// PagerDuty Events API v2 client
+pub struct PagerDutyClient {
+    url: String,
+    routing_key: String,
+    client: reqwest::Client,
+}
+
+impl PagerDutyClient {
+    pub fn new(api_url: Option<&str>, routing_key: &str) -> Self {
+        Self {
+            url: api_url.unwrap_or(PAGERDUTY_EVENTS_URL).to_string(),
+            routing_key: routing_key.to_string(),
+            client: http_client(),
+        }
+    }
+
+    async fn enqueue(&self, event: serde_json::Value) -> Result<()> {
+        let response = self.client.post(&self.url)
+            .json(&event)
+            .send()
+            .await
+            .context("Failed to reach PagerDuty")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("PagerDuty API error {}: {}", status, detail));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IncidentClient for PagerDutyClient {
+    async fn trigger(&self, dedup_key: &str, alert: &Alert) -> Result<()> {
+        self.enqueue(serde_json::json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": dedup_key,
+            "payload": {
+                "summary": format!("{}: {}", alert.title, alert.message).chars().take(1024).collect::<String>(),
+                "source": "dmpool",
+                "severity": pagerduty_severity(alert.level),
+                "timestamp": alert.triggered_at.to_rfc3339(),
+                "component": alert.rule_id,
+                "custom_details": alert.context,
+            },
+        })).await
+    }
+
+    async fn resolve(&self, dedup_key: &str) -> Result<()> {
+        self.enqueue(serde_json::json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": dedup_key,
+        })).await
+    }
+}
+
+/// Opsgenie Alert API client
+pub struct OpsgenieClient {
+    api_url: String,
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl OpsgenieClient {
+    pub fn new(api_url: Option<&str>, api_key: &str) -> Self {
+        Self {
+            api_url: api_url.unwrap_or(OPSGENIE_API_URL).trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            client: http_client(),
+        }
+    }
+
+    async fn post(&self, url: &str, body: serde_json::Value) -> Result<()> {
+        let response = self.client.post(url)
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to reach Opsgenie")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("Opsgenie API error {}: {}", status, detail));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IncidentClient for OpsgenieClient {
+    async fn trigger(&self, dedup_key: &str, alert: &Alert) -> Result<()> {
+        // Details must be a flat string map
+        let details: serde_json::Map<String, serde_json::Value> = match &alert.context {
+            serde_json::Value::Object(context) => context.iter()
+                .map(|(key, value)| {
+                    let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                    (key.clone(), serde_json::Value::String(value))
+                })
+                .collect(),
+            _ => serde_json::Map::new(),
+        };
+        self.post(&self.api_url, serde_json::json!({
+            "message": alert.title.chars().take(OPSGENIE_MAX_MESSAGE).collect::<String>(),
+            "alias": dedup_key,
+            "description": alert.message,
+            "priority": opsgenie_priority(alert.level),
+            "source": "dmpool",
+            "tags": ["dmpool", alert.rule_id],
+            "details": details,
+        })).await
+    }
+
+    async fn resolve(&self, dedup_key: &str) -> Result<()> {
+        // The key contains '/', so it goes in as an escaped path segment
+        let mut url = reqwest::Url::parse(&self.api_url).context("Invalid Opsgenie API URL")?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid Opsgenie API URL"))?
+            .push(dedup_key)
+            .push("close");
+        url.query_pairs_mut().append_pair("identifierType", "alias");
+        self.post(url.as_str(), serde_json::json!({ "source": "dmpool" })).await
+    }
+}
+
+/// Client for an incident channel
+pub fn client_for(channel: &AlertChannel) -> Option<Arc<dyn IncidentClient>> {
+    match channel {
+        AlertChannel::Incident { provider: IncidentProviderKind::PagerDuty, api_url, integration_key } => {
+            Some(Arc::new(PagerDutyClient::new(api_url.as_deref(), integration_key)))
+        }
+        AlertChannel::Incident { provider: IncidentProviderKind::Opsgenie, api_url, integration_key } => {
+            Some(Arc::new(OpsgenieClient::new(api_url.as_deref(), integration_key)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertCondition, AlertManager, AlertRule};
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingIncidents {
+        calls: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl IncidentClient for RecordingIncidents {
+        async fn trigger(&self, dedup_key: &str, alert: &Alert) -> Result<()> {
+            let action = format!("trigger {}", pagerduty_severity(alert.level));
+            self.calls.lock().await.push((action, dedup_key.to_string()));
+            Ok(())
+        }
+
+        async fn resolve(&self, dedup_key: &str) -> Result<()> {
+            self.calls.lock().await.push(("resolve".to_string(), dedup_key.to_string()));
+            Ok(())
+        }
+    }
+
+    async fn manager(client: Arc<RecordingIncidents>) -> AlertManager {
+        let alerts = AlertManager::default().with_incident_client(client);
+        alerts.add_channel("pagerduty".to_string(), AlertChannel::Incident {
+            provider: IncidentProviderKind::PagerDuty,
+            integration_key: "key".to_string(),
+            api_url: None,
+        }).await;
+        alerts.add_rule(AlertRule::new(
+            "node_down",
+            "Node down",
+            AlertCondition::Custom { message: "bitcoind is unreachable".to_string() },
+            AlertLevel::Critical,
+            vec!["pagerduty".to_string()],
+        ).with_cooldown(0)).await;
+        alerts
+    }
+
+    #[test]
+    fn test_severity_mapping() {
+        assert_eq!(pagerduty_severity(AlertLevel::Warning), "warning");
+        assert_eq!(opsgenie_priority(AlertLevel::Critical), "P1");
+        assert_eq!(opsgenie_priority(AlertLevel::Info), "P5");
+        assert_eq!(dedup_key("node_down"), "dmpool/node_down");
+        assert_eq!(IncidentProviderKind::Opsgenie.channel_name(), "opsgenie");
+    }
+
+    #[tokio::test]
+    async fn test_incident_resolves_on_acknowledgement_and_clearing() {
+        let client = Arc::new(RecordingIncidents::default());
+        let alerts = manager(client.clone()).await;
+
+        alerts.trigger_alert("node_down", serde_json::json!({})).await.unwrap();
+        alerts.trigger_alert("node_down", serde_json::json!({})).await.unwrap();
+        // Both alerts go to the same incident
+        assert_eq!(*client.calls.lock().await, vec![
+            ("trigger critical".to_string(), "dmpool/node_down".to_string()),
+            ("trigger critical".to_string(), "dmpool/node_down".to_string()),
+        ]);
+
+        // Acknowledging one of two open alerts keeps the incident open
+        let history = alerts.get_history(None).await;
+        alerts.acknowledge_alert(&history[0].id).await.unwrap();
+        assert_eq!(client.calls.lock().await.len(), 2);
+        alerts.acknowledge_alert(&history[1].id).await.unwrap();
+        assert_eq!(client.calls.lock().await[2], ("resolve".to_string(), "dmpool/node_down".to_string()));
+
+        // Clearing resolves only incidents that are open
+        assert_eq!(alerts.resolve_rule("node_down").await.unwrap(), 0);
+        alerts.trigger_alert("node_down", serde_json::json!({})).await.unwrap();
+        assert_eq!(alerts.resolve_rule("node_down").await.unwrap(), 1);
+        assert_eq!(client.calls.lock().await.len(), 5);
+    }
+}
@@ -1,15 +1,39 @@
 // Alert System for DMPool
-// Supports multiple alert channels (Email, Telegram, Webhook)
-// with configurable rules and alert aggregation
+// Supports multiple alert channels (Email, Telegram, Webhook, SMS) and
+// PagerDuty/Opsgenie incidents, with configurable rules, message templates,
+// webhook payload templates, alert aggregation, retried delivery, per-rule channel failover and
+// resolution notices when a condition clears
+//
+// With active/standby coordination only the leader raises alerts, so a pair
+// of instances does not page twice.
 
 use anyhow::{Context, Result};
+use crate::leadership::Leadership;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+mod anomaly;
+mod audit;
+mod delivery;
+mod incident;
+mod payload;
+mod sms;
+mod template;
+
+pub use audit::{default_audit_rules, AuditAnomalyDetector, AuditPattern};
+pub use anomaly::{AnomalyDirection, HashrateAnomalyDetector, HashrateBaseline, HashrateDeviation};
+pub use delivery::{ChannelDeliveryStats, DeadLetter, DeliveryPolicy, DeliveryQueue, DeliveryStats, QueuedDelivery};
+pub use incident::{
+    dedup_key, IncidentClient, IncidentProviderKind, OpsgenieClient, PagerDutyClient, OPSGENIE_API_URL, PAGERDUTY_EVENTS_URL,
+};
+pub use payload::{webhook_body, WebhookPayload};
+pub use sms::{SmsProvider, SmsProviderKind, SmsReceipt, TwilioSms, TWILIO_API_URL};
+pub use template::{template_data, validate_templates, MessageFormat, MessageTemplate, DEFAULT_TEMPLATE_KEY};
+
 /// Alert severity levels
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -62,7 +86,41 @@ pub enum AlertChannel {
     Webhook {
         url: String,
         headers: Option<HashMap<String, String>>,
+        /// Request body; the alert JSON when unset
+        #[serde(default)]
+        payload: Option<WebhookPayload>,
+    },
+    Sms {
+        #[serde(default)]
+        provider: SmsProviderKind,
+        /// API base for Twilio-compatible gateways (defaults to Twilio)
+        #[serde(default)]
+        api_url: Option<String>,
+        account_sid: String,
+        auth_token: String,
+        from_number: String,
+        to_numbers: Vec<String>,
     },
+    /// PagerDuty or Opsgenie incident, deduplicated by rule
+    Incident {
+        provider: IncidentProviderKind,
+        /// PagerDuty integration (routing) key or Opsgenie API key
+        integration_key: String,
+        /// API endpoint override, e.g. the Opsgenie EU instance
+        #[serde(default)]
+        api_url: Option<String>,
+    },
+}
+
+impl AlertChannel {
+    /// Lowest level sent through this channel unless configured otherwise
+    pub fn default_min_level(&self) -> AlertLevel {
+        match self {
+            Self::Sms { .. } => AlertLevel::Critical,
+            Self::Incident { .. } => AlertLevel::Warning,
+            _ => AlertLevel::Info,
+        }
+    }
 }
 
 /// Alert condition types
@@ -75,8 +133,23 @@ pub enum AlertCondition {
     HashrateAbove { threshold: f64, duration_minutes: u64 },
     /// Block not found within duration
     NoBlock { duration_minutes: u64 },
+    /// Pool (miner = None) or miner hashrate deviates from its modelled baseline
+    HashrateAnomaly {
+        miner: Option<String>,
+        /// Standard deviations from the baseline before alerting
+        sensitivity: f64,
+        /// Also alert on spikes, not only drops
+        #[serde(default)]
+        detect_spikes: bool,
+    },
+    /// Share reject rate (rejected, stale and duplicate) above threshold on a connection
+    RejectRateAbove { threshold_percent: f64, min_shares: u64 },
     /// Worker count below threshold
     WorkerCountBelow { threshold: u64 },
+    /// Every worker with a tag is offline, pool-wide (miner = None) or for one miner
+    TaggedWorkersOffline { tag: String, miner: Option<String> },
+    /// Audit log entries match a suspicious pattern
+    AuditAnomaly { pattern: AuditPattern },
     /// Database error
     DatabaseError,
     /// API error
@@ -104,11 +177,79 @@ pub struct AlertRule {
     pub channels: Vec<String>,
     /// Cooldown period between alerts (minutes)
     pub cooldown_minutes: u64,
+    /// Message templates by channel name (`*` for all other channels)
+    #[serde(default)]
+    pub templates: HashMap<String, MessageTemplate>,
+    /// Try `channels` in order and stop at the first that delivers,
+    /// instead of sending to all of them
+    #[serde(default)]
+    pub failover: bool,
     /// Last time this rule was triggered
     #[serde(skip)]
     last_triggered: Option<DateTime<Utc>>,
 }
 
+impl AlertRule {
+    /// Create an enabled rule with a 60 minute cooldown
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        condition: AlertCondition,
+        level: AlertLevel,
+        channels: Vec<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            description: String::new(),
+            condition,
+            level,
+            enabled: true,
+            channels,
+            cooldown_minutes: 60,
+            templates: HashMap::new(),
+            failover: false,
+            last_triggered: None,
+        }
+    }
+
+    /// Use a message template for a channel (`*` for all other channels)
+    pub fn with_template(mut self, channel: impl Into<String>, template: MessageTemplate) -> Self {
+        self.templates.insert(channel.into(), template);
+        self
+    }
+
+    /// Template for a channel, falling back to the default template
+    pub fn template_for(&self, channel: &str) -> Option<&MessageTemplate> {
+        self.templates.get(channel).or_else(|| self.templates.get(DEFAULT_TEMPLATE_KEY))
+    }
+
+    /// Set the cooldown period
+    pub fn with_cooldown(mut self, minutes: u64) -> Self {
+        self.cooldown_minutes = minutes;
+        self
+    }
+
+    /// Deliver through the first working channel, in `channels` order
+    pub fn with_failover(mut self) -> Self {
+        self.failover = true;
+        self
+    }
+}
+
+/// Outcome of sending an alert through one channel
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelAttempt {
+    pub channel: String,
+    pub delivered: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Provider message ids, for channels that return them (SMS)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub receipts: Vec<SmsReceipt>,
+    pub attempted_at: DateTime<Utc>,
+}
+
 /// Alert notification
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Alert {
@@ -128,8 +269,34 @@ pub struct Alert {
     pub triggered_at: DateTime<Utc>,
     /// Whether alert has been acknowledged
     pub acknowledged: bool,
-    /// Channel that was used
+    /// Channel that delivered the alert (the first channel if none did)
     pub channel: String,
+    /// Set when title and message were rendered from a template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_format: Option<MessageFormat>,
+    /// Channels tried when the alert was raised, in order
+    #[serde(default)]
+    pub deliveries: Vec<ChannelAttempt>,
+    /// Set once the condition that raised the alert has cleared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<AlertResolution>,
+}
+
+/// How and when a firing rule cleared
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlertResolution {
+    pub resolved_at: DateTime<Utc>,
+    /// Seconds from the rule's first alert to the condition clearing
+    pub duration_secs: i64,
+    /// Resolution notices sent
+    pub deliveries: Vec<ChannelAttempt>,
+}
+
+impl Alert {
+    /// Whether any channel delivered the alert when it was raised
+    pub fn delivered(&self) -> bool {
+        self.deliveries.iter().any(|d| d.delivered)
+    }
 }
 
 /// Alert statistics
@@ -138,8 +305,27 @@ pub struct AlertStats {
     pub total_alerts: usize,
     pub active_alerts: usize,
     pub acknowledged_alerts: usize,
+    /// Alerts whose condition has cleared
+    #[serde(default)]
+    pub resolved_alerts: usize,
     pub alerts_by_level: HashMap<String, usize>,
     pub alerts_by_rule: HashMap<String, usize>,
+    /// Alerts no channel delivered when raised
+    #[serde(default)]
+    pub undelivered_alerts: usize,
+    /// Alerts delivered by a failover channel after the first one failed
+    #[serde(default)]
+    pub failover_deliveries: usize,
+    /// Delivered and failed first attempts per channel
+    #[serde(default)]
+    pub deliveries_by_channel: HashMap<String, ChannelAttemptCounts>,
+}
+
+/// First-attempt outcomes for one channel
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChannelAttemptCounts {
+    pub delivered: usize,
+    pub failed: usize,
 }
 
 /// Alert manager configuration
@@ -151,8 +337,14 @@ pub struct AlertConfig {
     pub channels: HashMap<String, AlertChannel>,
     /// Alert rules
     pub rules: Vec<AlertRule>,
+    /// Lowest level each channel carries, overriding the channel default
+    #[serde(default)]
+    pub channel_min_levels: HashMap<String, AlertLevel>,
     /// Maximum history size
     pub max_history: usize,
+    /// Incremented on every rule save
+    #[serde(default)]
+    pub version: u64,
 }
 
 impl Default for AlertConfig {
@@ -161,7 +353,9 @@ impl Default for AlertConfig {
             enabled: true,
             channels: HashMap::new(),
             rules: Vec::new(),
+            channel_min_levels: HashMap::new(),
             max_history: 1000,
+            version: 0,
         }
     }
 }
@@ -170,6 +364,15 @@ impl Default for AlertConfig {
 pub struct AlertManager {
     config: Arc<RwLock<AlertConfig>>,
     history: Arc<RwLock<Vec<Alert>>>,
+    delivery: Option<Arc<DeliveryQueue>>,
+    sms_provider: Option<Arc<dyn SmsProvider>>,
+    incident_client: Option<Arc<dyn IncidentClient>>,
+    /// Incident channels with an open incident, by rule
+    open_incidents: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    /// Rules whose condition has not cleared, with their first alert time
+    firing: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    /// Alerts are only raised while this instance leads
+    leadership: Option<Arc<Leadership>>,
 }
 
 impl AlertManager {
@@ -178,9 +381,44 @@ impl AlertManager {
         Self {
             config: Arc::new(RwLock::new(config)),
             history: Arc::new(RwLock::new(Vec::new())),
+            delivery: None,
+            sms_provider: None,
+            incident_client: None,
+            open_incidents: Arc::new(RwLock::new(HashMap::new())),
+            firing: Arc::new(RwLock::new(HashMap::new())),
+            leadership: None,
         }
     }
 
+    /// Retry failed deliveries through this queue instead of dropping them
+    pub fn with_delivery(mut self, delivery: Arc<DeliveryQueue>) -> Self {
+        self.delivery = Some(delivery);
+        self
+    }
+
+    /// Send SMS through this provider instead of the one each channel names
+    pub fn with_sms_provider(mut self, provider: Arc<dyn SmsProvider>) -> Self {
+        self.sms_provider = Some(provider);
+        self
+    }
+
+    /// Open incidents through this client instead of the provider each channel names
+    pub fn with_incident_client(mut self, client: Arc<dyn IncidentClient>) -> Self {
+        self.incident_client = Some(client);
+        self
+    }
+
+    /// Leave alerting to the leader when this instance is on standby
+    pub fn with_leadership(mut self, leadership: Arc<Leadership>) -> Self {
+        self.leadership = Some(leadership);
+        self
+    }
+
+    /// Delivery queue, if retries are enabled
+    pub fn delivery(&self) -> Option<&Arc<DeliveryQueue>> {
+        self.delivery.as_ref()
+    }
+
     /// Create with default configuration
     pub fn default() -> Self {
         Self::new(AlertConfig::default())
@@ -193,6 +431,11 @@ impl AlertManager {
         info!("Added alert channel: {}", name);
     }
 
+    /// Only send alerts at or above `level` through a channel
+    pub async fn set_channel_min_level(&self, name: &str, level: AlertLevel) {
+        self.config.write().await.channel_min_levels.insert(name.to_string(), level);
+    }
+
     /// Remove an alert channel
     pub async fn remove_channel(&self, name: &str) -> bool {
         let mut config = self.config.write().await;
@@ -207,6 +450,31 @@ impl AlertManager {
         info!("Added alert rule: {}", name);
     }
 
+    /// Add or replace a rule after validating its templates
+    ///
+    /// Returns the new configuration version.
+    pub async fn save_rule(&self, mut rule: AlertRule) -> Result<u64> {
+        validate_templates(&rule.templates, &rule.channels)
+            .with_context(|| format!("Invalid templates for rule {}", rule.id))?;
+
+        let mut config = self.config.write().await;
+        match config.rules.iter_mut().find(|r| r.id == rule.id) {
+            Some(existing) => {
+                rule.last_triggered = existing.last_triggered;
+                *existing = rule;
+            }
+            None => config.rules.push(rule),
+        }
+        config.version += 1;
+        info!("Saved alert rules, config version {}", config.version);
+        Ok(config.version)
+    }
+
+    /// Current configuration, including rule templates and version
+    pub async fn get_config(&self) -> AlertConfig {
+        self.config.read().await.clone()
+    }
+
     /// Remove an alert rule
     pub async fn remove_rule(&self, rule_id: &str) -> bool {
         let mut config = self.config.write().await;
@@ -229,6 +497,9 @@ impl AlertManager {
         if !config.enabled {
             return Ok(());
         }
+        if self.leadership.as_ref().is_some_and(|leadership| !leadership.is_leader()) {
+            return Ok(());
+        }
 
         let rule = config.rules.iter()
             .find(|r| r.id == rule_id)
@@ -251,7 +522,7 @@ impl AlertManager {
         let rule_level = rule.level;
         let rule_id_clone = rule.id.clone();
 
-        let alert = Alert {
+        let mut alert = Alert {
             id: uuid::Uuid::new_v4().to_string(),
             rule_id: rule.id.clone(),
             level: rule.level,
@@ -261,16 +532,92 @@ impl AlertManager {
             triggered_at: Utc::now(),
             acknowledged: false,
             channel: rule.channels.first().cloned().unwrap_or_default(),
+            template_format: None,
+            deliveries: Vec::new(),
+            resolution: None,
         };
 
-        // Send to channels
+        // Send to channels, or with failover until one delivers
+        let mut failures = Vec::new();
         for channel_name in &rule.channels {
-            if let Some(channel) = config.channels.get(channel_name) {
-                if let Err(e) = self.send_alert(channel, &alert).await {
+            let Some(channel) = config.channels.get(channel_name) else {
+                alert.deliveries.push(ChannelAttempt {
+                    channel: channel_name.clone(),
+                    delivered: false,
+                    error: Some("Channel is not configured".to_string()),
+                    receipts: Vec::new(),
+                    attempted_at: Utc::now(),
+                });
+                continue;
+            };
+            let min_level = config.channel_min_levels.get(channel_name).copied()
+                .unwrap_or_else(|| channel.default_min_level());
+            if rule.level.severity() < min_level.severity() {
+                continue;
+            }
+            let message = match rule.template_for(channel_name) {
+                Some(template) => template.render(rule, &alert).unwrap_or_else(|e| {
+                    warn!("Failed to render {} template for rule {}, using default message: {}", channel_name, rule.id, e);
+                    alert.clone()
+                }),
+                None => alert.clone(),
+            };
+            match self.send_alert(channel, &message).await {
+                Ok(receipts) => {
+                    if let Some(delivery) = &self.delivery {
+                        delivery.record_success(channel_name).await;
+                    }
+                    if matches!(channel, AlertChannel::Incident { .. }) {
+                        self.incident_opened(&rule.id, channel_name).await;
+                    }
+                    alert.deliveries.push(ChannelAttempt {
+                        channel: channel_name.clone(),
+                        delivered: true,
+                        error: None,
+                        receipts,
+                        attempted_at: Utc::now(),
+                    });
+                    if rule.failover {
+                        alert.channel = channel_name.clone();
+                        break;
+                    }
+                }
+                Err(e) => {
                     error!("Failed to send alert via {}: {}", channel_name, e);
+                    alert.deliveries.push(ChannelAttempt {
+                        channel: channel_name.clone(),
+                        delivered: false,
+                        error: Some(e.to_string()),
+                        receipts: Vec::new(),
+                        attempted_at: Utc::now(),
+                    });
+                    failures.push((channel_name.clone(), message, e.to_string()));
+                }
+            }
+        }
+
+        // Failover rules retry only the first channel, and only when nothing delivered
+        if rule.failover && alert.delivered() {
+            for (channel_name, _, _) in failures.drain(..) {
+                if let Some(delivery) = &self.delivery {
+                    delivery.record_failure(&channel_name).await;
                 }
             }
+        } else if rule.failover {
+            failures.truncate(1);
         }
+        if let Some(delivery) = &self.delivery {
+            for (channel_name, message, error) in failures {
+                if let Err(e) = delivery.enqueue_failure(&channel_name, &message, error).await {
+                    error!("Failed to queue alert retry via {}: {}", channel_name, e);
+                }
+            }
+        }
+        if !rule.channels.is_empty() && !alert.delivered() {
+            warn!("Alert for rule {} was not delivered by any channel", rule.id);
+        }
+
+        self.firing.write().await.entry(rule.id.clone()).or_insert(alert.triggered_at);
 
         // Add to history
         let mut history = self.history.write().await;
@@ -294,8 +641,39 @@ impl AlertManager {
         Ok(())
     }
 
+    /// Retry queued deliveries that are due
+    ///
+    /// Returns the number of alerts delivered.
+    pub async fn retry_deliveries(&self) -> usize {
+        let delivery = match &self.delivery {
+            Some(delivery) => delivery,
+            None => return 0,
+        };
+
+        let mut delivered = 0;
+        for queued in delivery.take_due(Utc::now()).await {
+            let channel = self.config.read().await.channels.get(&queued.channel).cloned();
+            let is_incident = matches!(channel, Some(AlertChannel::Incident { .. }));
+            let outcome = match channel {
+                Some(channel) => self.send_alert(&channel, &queued.alert).await.map(|_| ()).map_err(|e| e.to_string()),
+                None => Err(format!("Channel {} no longer exists", queued.channel)),
+            };
+            if outcome.is_ok() {
+                delivered += 1;
+                if is_incident {
+                    self.incident_opened(&queued.alert.rule_id, &queued.channel).await;
+                }
+            }
+            let id = queued.id.clone();
+            if let Err(e) = delivery.complete(queued, outcome).await {
+                error!("Failed to update alert delivery {}: {}", id, e);
+            }
+        }
+        delivered
+    }
+
     /// Format alert message based on condition
-    fn format_message(&self, condition: &AlertCondition, _context: &serde_json::Value) -> Result<String> {
+    fn format_message(&self, condition: &AlertCondition, context: &serde_json::Value) -> Result<String> {
         Ok(match condition {
             AlertCondition::HashrateBelow { threshold, .. } => {
                 format!("Pool hashrate has dropped below {} TH/s", threshold)
@@ -303,12 +681,53 @@ impl AlertManager {
             AlertCondition::HashrateAbove { threshold, .. } => {
                 format!("Pool hashrate has exceeded {} TH/s", threshold)
             }
+            AlertCondition::HashrateAnomaly { miner, .. } => {
+                let target = miner.as_deref().unwrap_or("Pool");
+                let expected = context.get("expected").and_then(|e| e.as_f64());
+                match (context.get("direction").and_then(|d| d.as_str()), expected) {
+                    (Some("spike"), Some(expected)) => format!("{} hashrate spiked well above expected {:.2}", target, expected),
+                    (_, Some(expected)) => format!("{} hashrate dropped well below expected {:.2}", target, expected),
+                    _ => format!("{} hashrate anomaly detected", target),
+                }
+            }
             AlertCondition::NoBlock { duration_minutes } => {
                 format!("No block found in the last {} minutes", duration_minutes)
             }
+            AlertCondition::RejectRateAbove { threshold_percent, .. } => {
+                match (context.get("address").and_then(|a| a.as_str()), context.get("worker").and_then(|w| w.as_str())) {
+                    (Some(address), Some(worker)) => {
+                        format!("Share reject rate for {}.{} has exceeded {}%", address, worker, threshold_percent)
+                    }
+                    _ => format!("Share reject rate has exceeded {}%", threshold_percent),
+                }
+            }
             AlertCondition::WorkerCountBelow { threshold } => {
                 format!("Worker count has dropped below {}", threshold)
             }
+            AlertCondition::TaggedWorkersOffline { tag, miner } => {
+                let workers = context.get("workers").and_then(|w| w.as_u64()).unwrap_or(0);
+                match miner {
+                    Some(miner) => format!("All {} workers tagged \"{}\" for {} are offline", workers, tag, miner),
+                    None => format!("All {} workers tagged \"{}\" are offline", workers, tag),
+                }
+            }
+            AlertCondition::AuditAnomaly { pattern } => {
+                let username = context.get("username").and_then(|u| u.as_str()).unwrap_or("unknown");
+                let action = context.get("action").and_then(|a| a.as_str()).unwrap_or("unknown");
+                match pattern {
+                    AuditPattern::RepeatedFailures { window_minutes, .. } => {
+                        let failures = context.get("failures").and_then(|f| f.as_u64()).unwrap_or(0);
+                        format!("{} failed {} attempts for {} in {} minutes", failures, action, username, window_minutes)
+                    }
+                    AuditPattern::OutsideHours { .. } => {
+                        format!("{} performed {} outside business hours", username, action)
+                    }
+                    AuditPattern::RoleNotAllowed { .. } => {
+                        let role = context.get("role").and_then(|r| r.as_str()).unwrap_or("unknown");
+                        format!("{} performed {} with role {}", username, action, role)
+                    }
+                }
+            }
             AlertCondition::DatabaseError => {
                 "Database error detected".to_string()
             }
@@ -321,43 +740,119 @@ impl AlertManager {
         })
     }
 
-    /// Send alert via a specific channel
-    async fn send_alert(&self, channel: &AlertChannel, alert: &Alert) -> Result<()> {
+    /// Send alert via a specific channel, returning any delivery receipts
+    async fn send_alert(&self, channel: &AlertChannel, alert: &Alert) -> Result<Vec<SmsReceipt>> {
         match channel {
             AlertChannel::Email { .. } => {
                 // TODO: Implement email sending
                 warn!("Email alert not yet implemented: {}", alert.title);
-                Ok(())
+                Ok(Vec::new())
             }
             AlertChannel::Telegram { bot_token, chat_id } => {
-                self.send_telegram_alert(bot_token, chat_id, alert).await
+                self.send_telegram_alert(bot_token, chat_id, alert).await.map(|_| Vec::new())
+            }
+            AlertChannel::Webhook { url, headers, payload } => {
+                self.send_webhook_alert(url, headers, payload.as_ref(), alert).await.map(|_| Vec::new())
             }
-            AlertChannel::Webhook { url, headers } => {
-                self.send_webhook_alert(url, headers, alert).await
+            AlertChannel::Sms { to_numbers, .. } => {
+                let provider = match &self.sms_provider {
+                    Some(provider) => provider.clone(),
+                    None => sms::provider_for(channel)
+                        .ok_or_else(|| anyhow::anyhow!("No SMS provider for channel"))?,
+                };
+                sms::send_all(provider.as_ref(), to_numbers, &sms::sms_body(alert)).await
             }
+            AlertChannel::Incident { .. } => {
+                self.incident_client_for(channel)?
+                    .trigger(&incident::dedup_key(&alert.rule_id), alert).await
+                    .map(|_| Vec::new())
+            }
+        }
+    }
+
+    fn incident_client_for(&self, channel: &AlertChannel) -> Result<Arc<dyn IncidentClient>> {
+        match &self.incident_client {
+            Some(client) => Ok(client.clone()),
+            None => incident::client_for(channel)
+                .ok_or_else(|| anyhow::anyhow!("No incident client for channel")),
         }
     }
 
+    async fn incident_opened(&self, rule_id: &str, channel_name: &str) {
+        self.open_incidents.write().await
+            .entry(rule_id.to_string())
+            .or_default()
+            .insert(channel_name.to_string());
+    }
+
+    /// Resolve the open incidents of a rule whose condition has cleared
+    ///
+    /// Returns the number of incidents resolved. Incidents that fail to
+    /// resolve stay open and are retried on the next call.
+    pub async fn resolve_rule(&self, rule_id: &str) -> Result<usize> {
+        let Some(open) = self.open_incidents.write().await.remove(rule_id) else {
+            return Ok(0);
+        };
+        let channels = self.config.read().await.channels.clone();
+        let key = incident::dedup_key(rule_id);
+        let mut resolved = 0;
+        let mut failed = HashSet::new();
+        let mut errors = Vec::new();
+        for channel_name in open {
+            // Removed channels have nothing left to resolve through
+            let Some(channel) = channels.get(&channel_name) else {
+                continue;
+            };
+            let outcome = match self.incident_client_for(channel) {
+                Ok(client) => client.resolve(&key).await,
+                Err(e) => Err(e),
+            };
+            match outcome {
+                Ok(()) => resolved += 1,
+                Err(e) => {
+                    errors.push(format!("{}: {:#}", channel_name, e));
+                    failed.insert(channel_name);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            self.open_incidents.write().await.entry(rule_id.to_string()).or_default().extend(failed);
+            return Err(anyhow::anyhow!("Failed to resolve incidents for rule {}: {}", rule_id, errors.join(", ")));
+        }
+        if resolved > 0 {
+            info!("Resolved {} incident(s) for rule {}", resolved, rule_id);
+        }
+        Ok(resolved)
+    }
+
     /// Send Telegram alert
     async fn send_telegram_alert(&self, bot_token: &str, chat_id: &str, alert: &Alert) -> Result<()> {
-        let message = format!(
-            "*{}* {}\n\n{}\n\n{}",
-            alert.level,
-            alert.title,
-            alert.message,
-            alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC")
-        );
+        // Templated bodies are sent as written
+        let (message, format) = match alert.template_format {
+            Some(format) => (alert.message.clone(), format),
+            None => (format!(
+                "*{}* {}\n\n{}\n\n{}",
+                alert.level,
+                alert.title,
+                alert.message,
+                alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC")
+            ), MessageFormat::Markdown),
+        };
 
         let url = format!("https://api.telegram.org/bot{}/sendMessage", bot_token);
         let client = reqwest::Client::new();
-        
+
+        let mut body = serde_json::json!({
+            "chat_id": chat_id,
+            "text": message,
+        });
+        if format == MessageFormat::Markdown {
+            body["parse_mode"] = serde_json::json!("Markdown");
+        }
+
         let response = client
             .post(&url)
-            .json(&serde_json::json!({
-                "chat_id": chat_id,
-                "text": message,
-                "parse_mode": "Markdown"
-            }))
+            .json(&body)
             .send()
             .await
             .context("Failed to send Telegram alert")?;
@@ -374,10 +869,12 @@ impl AlertManager {
         &self,
         url: &str,
         headers: &Option<HashMap<String, String>>,
+        payload: Option<&WebhookPayload>,
         alert: &Alert,
     ) -> Result<()> {
+        let body = webhook_body(payload, alert)?;
         let client = reqwest::Client::new();
-        let mut request = client.post(url).json(alert);
+        let mut request = client.post(url).json(&body);
 
         if let Some(hdrs) = headers {
             for (key, value) in hdrs {
@@ -412,15 +909,106 @@ impl AlertManager {
         result
     }
 
+    /// Clear a rule whose condition is back to normal
+    ///
+    /// Marks its alerts resolved with how long the condition lasted, sends a
+    /// resolution notice on the channels that delivered its latest alert and
+    /// resolves its incidents. Returns None if the rule was not firing.
+    pub async fn clear_rule(&self, rule_id: &str, context: serde_json::Value) -> Result<Option<AlertResolution>> {
+        let Some(since) = self.firing.write().await.remove(rule_id) else {
+            return Ok(None);
+        };
+        let resolved_at = Utc::now();
+        let mut resolution = AlertResolution {
+            resolved_at,
+            duration_secs: resolved_at.signed_duration_since(since).num_seconds().max(0),
+            deliveries: Vec::new(),
+        };
+
+        let latest = self.history.read().await.iter().rev().find(|a| a.rule_id == rule_id).cloned();
+        if let Some(latest) = latest {
+            let (rule_name, channels) = {
+                let config = self.config.read().await;
+                let name = config.rules.iter().find(|r| r.id == rule_id)
+                    .map(|r| r.name.clone())
+                    .unwrap_or_else(|| rule_id.to_string());
+                (name, config.channels.clone())
+            };
+            let notice = Alert {
+                id: uuid::Uuid::new_v4().to_string(),
+                rule_id: rule_id.to_string(),
+                level: latest.level,
+                title: format!("RESOLVED: {}", rule_name),
+                message: format!("{} cleared after {}", rule_name, format_duration(resolution.duration_secs)),
+                context,
+                triggered_at: resolved_at,
+                acknowledged: false,
+                channel: latest.channel.clone(),
+                template_format: None,
+                deliveries: Vec::new(),
+                resolution: Some(resolution.clone()),
+            };
+            // Incidents are resolved below rather than notified
+            for attempt in latest.deliveries.iter().filter(|d| d.delivered) {
+                let Some(channel) = channels.get(&attempt.channel) else {
+                    continue;
+                };
+                if matches!(channel, AlertChannel::Incident { .. }) {
+                    continue;
+                }
+                let outcome = self.send_alert(channel, &notice).await;
+                if let Err(e) = &outcome {
+                    error!("Failed to send resolution via {}: {}", attempt.channel, e);
+                }
+                resolution.deliveries.push(ChannelAttempt {
+                    channel: attempt.channel.clone(),
+                    delivered: outcome.is_ok(),
+                    error: outcome.as_ref().err().map(|e| e.to_string()),
+                    receipts: outcome.unwrap_or_default(),
+                    attempted_at: Utc::now(),
+                });
+            }
+        }
+
+        for alert in self.history.write().await.iter_mut() {
+            if alert.rule_id == rule_id && alert.resolution.is_none() {
+                alert.resolution = Some(resolution.clone());
+            }
+        }
+        if let Err(e) = self.resolve_rule(rule_id).await {
+            warn!("{:#}", e);
+        }
+
+        info!("Alert cleared: {} after {}", rule_id, format_duration(resolution.duration_secs));
+        Ok(Some(resolution))
+    }
+
+    /// Rules whose condition has not cleared yet
+    pub async fn firing_rules(&self) -> HashMap<String, DateTime<Utc>> {
+        self.firing.read().await.clone()
+    }
+
     /// Acknowledge an alert
+    ///
+    /// Once every alert of its rule is acknowledged the rule's open incidents
+    /// are resolved.
     pub async fn acknowledge_alert(&self, alert_id: &str) -> Result<bool> {
         let mut history = self.history.write().await;
-        if let Some(alert) = history.iter_mut().find(|a| a.id == alert_id) {
-            alert.acknowledged = true;
-            info!("Alert acknowledged: {}", alert_id);
-            return Ok(true);
+        let Some(alert) = history.iter_mut().find(|a| a.id == alert_id) else {
+            return Ok(false);
+        };
+        alert.acknowledged = true;
+        info!("Alert acknowledged: {}", alert_id);
+
+        let rule_id = alert.rule_id.clone();
+        let outstanding = history.iter().any(|a| a.rule_id == rule_id && !a.acknowledged);
+        drop(history);
+        if !outstanding {
+            if let Err(e) = self.resolve_rule(&rule_id).await {
+                warn!("{:#}", e);
+            }
         }
-        Ok(false)
+        Ok(true)
     }
 
     /// Get alert statistics
@@ -430,18 +1018,39 @@ impl AlertManager {
 
         let mut alerts_by_level = HashMap::new();
         let mut alerts_by_rule = HashMap::new();
+        let mut deliveries_by_channel: HashMap<String, ChannelAttemptCounts> = HashMap::new();
+        let mut undelivered_alerts = 0;
+        let mut failover_deliveries = 0;
 
         for alert in history.iter() {
             *alerts_by_level.entry(alert.level.to_string()).or_insert(0) += 1;
             *alerts_by_rule.entry(alert.rule_id.clone()).or_insert(0) += 1;
+            for attempt in &alert.deliveries {
+                let counts = deliveries_by_channel.entry(attempt.channel.clone()).or_default();
+                if attempt.delivered {
+                    counts.delivered += 1;
+                } else {
+                    counts.failed += 1;
+                }
+            }
+            match alert.deliveries.iter().position(|d| d.delivered) {
+                Some(0) => {}
+                Some(_) => failover_deliveries += 1,
+                None if !alert.deliveries.is_empty() => undelivered_alerts += 1,
+                None => {}
+            }
         }
 
         AlertStats {
             total_alerts: history.len(),
             active_alerts: history.iter().filter(|a| !a.acknowledged).count(),
             acknowledged_alerts: history.iter().filter(|a| a.acknowledged).count(),
+            resolved_alerts: history.iter().filter(|a| a.resolution.is_some()).count(),
             alerts_by_level,
             alerts_by_rule,
+            undelivered_alerts,
+            failover_deliveries,
+            deliveries_by_channel,
         }
     }
 
@@ -451,6 +1060,15 @@ impl AlertManager {
         config.rules.clone()
     }
 
+    /// Send an alert through one channel now, bypassing rules, cooldowns and retries
+    pub async fn test_fire(&self, channel_name: &str, alert: &Alert) -> Result<()> {
+        let channel = self.config.read().await.channels.get(channel_name).cloned()
+            .ok_or_else(|| anyhow::anyhow!("Alert channel {} not found", channel_name))?;
+        self.send_alert(&channel, alert).await?;
+        info!("Test alert sent through channel {}", channel_name);
+        Ok(())
+    }
+
     /// Get all channels
     pub async fn get_channels(&self) -> HashMap<String, AlertChannel> {
         let config = self.config.read().await;
@@ -471,6 +1089,18 @@ impl AlertManager {
     }
 }
 
+/// Short human duration, e.g. "2h 5m"
+fn format_duration(secs: i64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -488,4 +1118,118 @@ mod tests {
         assert_eq!(AlertLevel::Warning.to_string(), "WARNING");
         assert_eq!(AlertLevel::Critical.to_string(), "CRITICAL");
     }
+
+    fn email() -> AlertChannel {
+        AlertChannel::Email {
+            smtp_server: "localhost".to_string(),
+            smtp_port: 25,
+            username: String::new(),
+            password: String::new(),
+            from_address: "pool@example.com".to_string(),
+            to_addresses: vec!["ops@example.com".to_string()],
+        }
+    }
+
+    fn dead_webhook() -> AlertChannel {
+        // Nothing listens on the discard port
+        AlertChannel::Webhook { url: "http://127.0.0.1:9/alerts".to_string(), headers: None, payload: None }
+    }
+
+    async fn manager() -> AlertManager {
+        let manager = AlertManager::default();
+        manager.add_channel("telegram".to_string(), dead_webhook()).await;
+        manager.add_channel("email".to_string(), email()).await;
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_failover_stops_at_first_working_channel() {
+        let manager = manager().await;
+        let channels = vec!["telegram".to_string(), "email".to_string(), "missing".to_string()];
+        manager.add_rule(AlertRule::new(
+            "critical",
+            "Critical",
+            AlertCondition::Custom { message: "down".to_string() },
+            AlertLevel::Critical,
+            channels,
+        ).with_failover()).await;
+
+        manager.trigger_alert("critical", serde_json::json!({})).await.unwrap();
+        let alert = manager.get_history(None).await.remove(0);
+        assert_eq!(alert.channel, "email");
+        assert!(alert.delivered());
+        // The unconfigured channel after the working one is never tried
+        assert_eq!(alert.deliveries.len(), 2);
+        assert!(!alert.deliveries[0].delivered && alert.deliveries[0].error.is_some());
+
+        let stats = manager.get_stats().await;
+        assert_eq!(stats.failover_deliveries, 1);
+        assert_eq!(stats.undelivered_alerts, 0);
+        assert_eq!(stats.deliveries_by_channel["telegram"].failed, 1);
+        assert_eq!(stats.deliveries_by_channel["email"].delivered, 1);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_and_undelivered_alerts() {
+        let manager = manager().await;
+        manager.add_rule(AlertRule::new(
+            "all",
+            "All",
+            AlertCondition::Custom { message: "fanout".to_string() },
+            AlertLevel::Warning,
+            vec!["email".to_string(), "telegram".to_string()],
+        )).await;
+        manager.add_rule(AlertRule::new(
+            "broken",
+            "Broken",
+            AlertCondition::Custom { message: "lost".to_string() },
+            AlertLevel::Critical,
+            vec!["telegram".to_string()],
+        ).with_failover()).await;
+
+        // Without failover every channel is tried
+        manager.trigger_alert("all", serde_json::json!({})).await.unwrap();
+        manager.trigger_alert("broken", serde_json::json!({})).await.unwrap();
+        let history = manager.get_history(None).await;
+        assert_eq!(history[1].deliveries.len(), 2);
+        assert!(!history[0].delivered());
+
+        let stats = manager.get_stats().await;
+        assert_eq!(stats.undelivered_alerts, 1);
+        assert_eq!(stats.failover_deliveries, 0);
+        assert_eq!(stats.deliveries_by_channel["telegram"].failed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_clearing_resolves_alerts_on_delivering_channels() {
+        let manager = manager().await;
+        manager.add_rule(AlertRule::new(
+            "hashrate",
+            "Low hashrate",
+            AlertCondition::HashrateBelow { threshold: 100.0, duration_minutes: 10 },
+            AlertLevel::Warning,
+            vec!["telegram".to_string(), "email".to_string()],
+        ).with_cooldown(0)).await;
+
+        // Nothing fired yet, so nothing to clear
+        assert!(manager.clear_rule("hashrate", serde_json::json!({})).await.unwrap().is_none());
+
+        manager.trigger_alert("hashrate", serde_json::json!({"hashrate": 50.0})).await.unwrap();
+        manager.trigger_alert("hashrate", serde_json::json!({"hashrate": 40.0})).await.unwrap();
+        assert!(manager.firing_rules().await.contains_key("hashrate"));
+
+        let resolution = manager.clear_rule("hashrate", serde_json::json!({"hashrate": 120.0})).await.unwrap().unwrap();
+        // Only the channel that delivered the alert gets the notice
+        assert_eq!(resolution.deliveries.len(), 1);
+        assert_eq!(resolution.deliveries[0].channel, "email");
+        assert!(resolution.deliveries[0].delivered);
+        assert!(resolution.duration_secs >= 0);
+
+        let history = manager.get_history(None).await;
+        assert!(history.iter().all(|a| a.resolution.is_some()));
+        assert_eq!(manager.get_stats().await.resolved_alerts, 2);
+        assert!(manager.clear_rule("hashrate", serde_json::json!({})).await.unwrap().is_none());
+        assert_eq!(format_duration(3_900), "1h 5m");
+        assert_eq!(format_duration(65), "1m 5s");
+    }
 }
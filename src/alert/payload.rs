@@ -0,0 +1,193 @@
+// Webhook payload templates
+//
+// By default a webhook channel posts the alert as JSON. A channel can instead
+// render its body from a Handlebars template over the alert JSON, or build an
+// object from JQ-style paths into it (`.context.workers`, `.deliveries[0]`).
+
+use super::Alert;
+use anyhow::{Context, Result};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Body of a webhook request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookPayload {
+    /// Handlebars template that must render to JSON; interpolated values are
+    /// escaped as JSON string content, `{{{raw}}}` inserts them as-is
+    Template { template: String },
+    /// Object whose fields are taken from paths into the alert JSON
+    Fields { fields: BTreeMap<String, String> },
+}
+
+/// One step of a path
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Parse a JQ-style path: `.`, `.title`, `.context.workers`, `.deliveries[0].channel`
+fn parse_path(path: &str) -> Result<Vec<Segment<'_>>> {
+    let rest = path.strip_prefix('.')
+        .ok_or_else(|| anyhow::anyhow!("Path {:?} must start with '.'", path))?;
+    let mut segments = Vec::new();
+    if rest.is_empty() {
+        return Ok(segments);
+    }
+    for part in rest.split('.') {
+        let (key, mut indexes) = part.split_once('[').unwrap_or((part, ""));
+        if key.is_empty() && indexes.is_empty() {
+            return Err(anyhow::anyhow!("Path {:?} has an empty segment", path));
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        while !indexes.is_empty() {
+            let (index, rest) = indexes.split_once(']')
+                .ok_or_else(|| anyhow::anyhow!("Path {:?} has an unclosed '['", path))?;
+            let index = index.parse()
+                .with_context(|| format!("Path {:?} has an invalid index {:?}", path, index))?;
+            segments.push(Segment::Index(index));
+            indexes = match rest.strip_prefix('[') {
+                Some(next) => next,
+                None if rest.is_empty() => "",
+                None => return Err(anyhow::anyhow!("Path {:?} has text after ']'", path)),
+            };
+        }
+    }
+    Ok(segments)
+}
+
+/// Value at a path, null when it does not exist (as in jq)
+fn select(value: &serde_json::Value, path: &str) -> Result<serde_json::Value> {
+    let mut current = value;
+    for segment in parse_path(path)? {
+        let next = match segment {
+            Segment::Key(key) => current.get(key),
+            Segment::Index(index) => current.get(index),
+        };
+        match next {
+            Some(next) => current = next,
+            None => return Ok(serde_json::Value::Null),
+        }
+    }
+    Ok(current.clone())
+}
+
+/// Handlebars registry escaping interpolated values for JSON strings
+fn registry() -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(|s| {
+        let quoted = serde_json::Value::String(s.to_string()).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    });
+    handlebars
+}
+
+impl WebhookPayload {
+    /// Check that the template compiles and paths parse
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Template { template } => {
+                registry().register_template_string("payload", template)
+                    .context("Invalid payload template")?;
+            }
+            Self::Fields { fields } => {
+                if fields.is_empty() {
+                    return Err(anyhow::anyhow!("Payload fields must not be empty"));
+                }
+                for (name, path) in fields {
+                    parse_path(path).with_context(|| format!("Payload field {}", name))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the request body for an alert
+    pub fn render(&self, alert: &Alert) -> Result<serde_json::Value> {
+        let data = serde_json::to_value(alert).context("Failed to serialize alert")?;
+        match self {
+            Self::Template { template } => {
+                let rendered = registry().render_template(template, &data)
+                    .context("Failed to render payload template")?;
+                serde_json::from_str(&rendered)
+                    .context("Payload template did not render valid JSON")
+            }
+            Self::Fields { fields } => fields.iter()
+                .map(|(name, path)| Ok((name.clone(), select(&data, path)?)))
+                .collect::<Result<serde_json::Map<_, _>>>()
+                .map(serde_json::Value::Object),
+        }
+    }
+}
+
+/// Body a webhook channel sends for an alert
+pub fn webhook_body(payload: Option<&WebhookPayload>, alert: &Alert) -> Result<serde_json::Value> {
+    match payload {
+        Some(payload) => payload.render(alert),
+        None => serde_json::to_value(alert).context("Failed to serialize alert"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertLevel;
+    use chrono::Utc;
+
+    fn alert() -> Alert {
+        Alert {
+            id: "a1".to_string(),
+            rule_id: "rack_offline".to_string(),
+            level: AlertLevel::Critical,
+            title: "Rack \"3\" offline".to_string(),
+            message: "All 4 workers are offline".to_string(),
+            context: serde_json::json!({ "key": "rack-3", "workers": 4, "hosts": ["r3-a", "r3-b"] }),
+            triggered_at: Utc::now(),
+            acknowledged: false,
+            channel: "ops".to_string(),
+            template_format: None,
+            deliveries: Vec::new(),
+            resolution: None,
+        }
+    }
+
+    #[test]
+    fn test_template_payload_escapes_strings() {
+        let payload = WebhookPayload::Template {
+            template: r#"{"text": "{{level}}: {{title}}", "workers": {{{context.workers}}} }"#.to_string(),
+        };
+        payload.validate().unwrap();
+        let body = payload.render(&alert()).unwrap();
+        assert_eq!(body, serde_json::json!({ "text": "critical: Rack \"3\" offline", "workers": 4 }));
+
+        let broken = WebhookPayload::Template { template: "{\"text\": {{title}}}".to_string() };
+        assert!(broken.render(&alert()).is_err());
+    }
+
+    #[test]
+    fn test_field_payload_selects_paths() {
+        let payload = WebhookPayload::Fields {
+            fields: BTreeMap::from([
+                ("summary".to_string(), ".title".to_string()),
+                ("rack".to_string(), ".context.key".to_string()),
+                ("first_host".to_string(), ".context.hosts[0]".to_string()),
+                ("missing".to_string(), ".context.nope[2]".to_string()),
+            ]),
+        };
+        payload.validate().unwrap();
+        assert_eq!(payload.render(&alert()).unwrap(), serde_json::json!({
+            "summary": "Rack \"3\" offline",
+            "rack": "rack-3",
+            "first_host": "r3-a",
+            "missing": null,
+        }));
+
+        for path in ["title", ".a..b", ".hosts[x]", ".hosts[0"] {
+            assert!(parse_path(path).is_err(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// SMS alert delivery
+//
+// SMS channels send a short text to each configured number through an
+// `SmsProvider`. The Twilio Messages API is built in; any service exposing the
+// same endpoint (`POST {api_url}/Accounts/{sid}/Messages.json` with basic
+// auth) works by pointing `api_url` at it. SMS channels only carry Critical
+// alerts unless their minimum level is lowered.
+
+use super::{Alert, AlertChannel};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default Twilio REST API base
+pub const TWILIO_API_URL: &str = "https://api.twilio.com/2010-04-01";
+
+/// Longest SMS body sent (two concatenated segments)
+const MAX_SMS_CHARS: usize = 306;
+
+/// SMS gateway the channel talks to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsProviderKind {
+    /// Twilio or a Twilio-compatible API
+    #[default]
+    Twilio,
+}
+
+/// Provider acknowledgement for one message
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SmsReceipt {
+    /// Provider message id
+    pub id: String,
+    pub to: String,
+    /// Provider status, e.g. "queued"
+    pub status: Option<String>,
+}
+
+/// Sends one SMS
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    async fn send(&self, to: &str, body: &str) -> Result<SmsReceipt>;
+}
+
+/// Twilio Messages API client
+pub struct TwilioSms {
+    api_url: String,
+    account_sid: String,
+    auth_token: String,
+    from_number: String,
+    client: reqwest::Client,
+}
+
+impl TwilioSms {
+    pub fn new(api_url: Option<&str>, account_sid: &str, auth_token: &str, from_number: &str) -> Self {
+        Self {
+            api_url: api_url.unwrap_or(TWILIO_API_URL).trim_end_matches('/').to_string(),
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            from_number: from_number.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TwilioMessage {
+    sid: String,
+    status: Option<String>,
+}
+
+#[async_trait]
+impl SmsProvider for TwilioSms {
+    async fn send(&self, to: &str, body: &str) -> Result<SmsReceipt> {
+        let url = format!("{}/Accounts/{}/Messages.json", self.api_url, self.account_sid);
+        let response = self.client.post(&url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[("To", to), ("From", self.from_number.as_str()), ("Body", body)])
+            .send()
+            .await
+            .context("Failed to send SMS")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("SMS API error {}: {}", status, detail));
+        }
+        let message: TwilioMessage = response.json().await
+            .context("Failed to parse SMS API response")?;
+        Ok(SmsReceipt { id: message.sid, to: to.to_string(), status: message.status })
+    }
+}
+
+/// Provider for an SMS channel
+pub fn provider_for(channel: &AlertChannel) -> Option<Arc<dyn SmsProvider>> {
+    match channel {
+        AlertChannel::Sms { provider: SmsProviderKind::Twilio, api_url, account_sid, auth_token, from_number, .. } => {
+            Some(Arc::new(TwilioSms::new(api_url.as_deref(), account_sid, auth_token, from_number)))
+        }
+        _ => None,
+    }
+}
+
+/// Short text for an alert
+pub fn sms_body(alert: &Alert) -> String {
+    let text = format!("[{}] {}: {}", alert.level, alert.title, alert.message);
+    if text.chars().count() <= MAX_SMS_CHARS {
+        return text;
+    }
+    let mut truncated: String = text.chars().take(MAX_SMS_CHARS - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// Send to every number; fails only if no number was reached
+pub async fn send_all(provider: &dyn SmsProvider, to_numbers: &[String], body: &str) -> Result<Vec<SmsReceipt>> {
+    let mut receipts = Vec::new();
+    let mut errors = Vec::new();
+    for to in to_numbers {
+        match provider.send(to, body).await {
+            Ok(receipt) => receipts.push(receipt),
+            Err(e) => errors.push(format!("{}: {:#}", to, e)),
+        }
+    }
+    if receipts.is_empty() {
+        return Err(anyhow::anyhow!("SMS delivery failed: {}", errors.join(", ")));
+    }
+    if !errors.is_empty() {
+        tracing::warn!("SMS partially delivered: {}", errors.join(", "));
+    }
+    Ok(receipts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSms {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl SmsProvider for RecordingSms {
+        async fn send(&self, to: &str, body: &str) -> Result<SmsReceipt> {
+            if to == "+15550000000" {
+                return Err(anyhow::anyhow!("unreachable number"));
+            }
+            let mut sent = self.sent.lock().await;
+            sent.push((to.to_string(), body.to_string()));
+            Ok(SmsReceipt { id: format!("SM{}", sent.len()), to: to.to_string(), status: Some("queued".to_string()) })
+        }
+    }
+
+    fn sms_channel(to_numbers: &[&str]) -> AlertChannel {
+        AlertChannel::Sms {
+            provider: SmsProviderKind::Twilio,
+            api_url: None,
+            account_sid: "AC123".to_string(),
+            auth_token: "secret".to_string(),
+            from_number: "+15551230000".to_string(),
+            to_numbers: to_numbers.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+
+    fn rule(id: &str, level: AlertLevel) -> AlertRule {
+        AlertRule::new(id, id, AlertCondition::Custom { message: "pool down".to_string() }, level, vec!["sms".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_only_critical_alerts_use_sms_with_receipts() {
+        let provider = Arc::new(RecordingSms::default());
+        let alerts = AlertManager::default().with_sms_provider(provider.clone());
+        alerts.add_channel("sms".to_string(), sms_channel(&["+15551112222", "+15550000000"])).await;
+        alerts.add_rule(rule("warning", AlertLevel::Warning)).await;
+        alerts.add_rule(rule("critical", AlertLevel::Critical)).await;
+
+        alerts.trigger_alert("warning", serde_json::json!({})).await.unwrap();
+        alerts.trigger_alert("critical", serde_json::json!({})).await.unwrap();
+
+        let history = alerts.get_history(None).await;
+        // Warning skipped SMS entirely
+        assert!(history[1].deliveries.is_empty());
+        // One number failed, the other was reached and its receipt kept
+        let attempt = &history[0].deliveries[0];
+        assert!(attempt.delivered);
+        assert_eq!(attempt.receipts.len(), 1);
+        assert_eq!(attempt.receipts[0].id, "SM1");
+        assert_eq!(provider.sent.lock().await[0].1, "[CRITICAL] CRITICAL Alert: critical: pool down");
+
+        // Lowering the channel's level lets warnings through
+        alerts.set_channel_min_level("sms", AlertLevel::Warning).await;
+        alerts.add_rule(rule("warning2", AlertLevel::Warning)).await;
+        alerts.trigger_alert("warning2", serde_json::json!({})).await.unwrap();
+        assert_eq!(provider.sent.lock().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sms_body_and_total_failure() {
+        let provider = RecordingSms::default();
+        let err = send_all(&provider, &["+15550000000".to_string()], "text").await.unwrap_err();
+        assert!(err.to_string().contains("unreachable number"));
+
+        let mut alert = crate::alert::Alert {
+            id: "a".to_string(),
+            rule_id: "r".to_string(),
+            level: AlertLevel::Critical,
+            title: "Down".to_string(),
+            message: "x".repeat(500),
+            context: serde_json::json!({}),
+            triggered_at: chrono::Utc::now(),
+            acknowledged: false,
+            channel: "sms".to_string(),
+            template_format: None,
+            deliveries: Vec::new(),
+            resolution: None,
+        };
+        let body = sms_body(&alert);
+        assert_eq!(body.chars().count(), MAX_SMS_CHARS);
+        assert!(body.ends_with("..."));
+        alert.message = "short".to_string();
+        assert_eq!(sms_body(&alert), "[CRITICAL] Down: short");
+    }
+}
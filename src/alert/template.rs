@@ -0,0 +1,164 @@
+// Alert message templates
+//
+// Operators can override an alert's subject and body per rule and per channel
+// with Handlebars templates. Templates see the rule, level, default title and
+// message, and every field of the alert context.
+
+use super::{Alert, AlertRule};
+use anyhow::{Context, Result};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Template key used for channels without their own template
+pub const DEFAULT_TEMPLATE_KEY: &str = "*";
+
+/// How a rendered body should be interpreted by the channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    #[default]
+    Markdown,
+    Plain,
+}
+
+/// Subject and body templates for one channel
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MessageTemplate {
+    /// Replaces the alert title when set
+    pub subject: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub format: MessageFormat,
+}
+
+/// Handlebars registry for plain-text output (no HTML escaping)
+fn registry() -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+}
+
+impl MessageTemplate {
+    /// Check that subject and body compile
+    pub fn validate(&self) -> Result<()> {
+        let mut handlebars = registry();
+        if let Some(subject) = &self.subject {
+            handlebars.register_template_string("subject", subject)
+                .context("Invalid subject template")?;
+        }
+        handlebars.register_template_string("body", &self.body)
+            .context("Invalid body template")?;
+        Ok(())
+    }
+
+    /// Render the templates over an alert
+    pub fn render(&self, rule: &AlertRule, alert: &Alert) -> Result<Alert> {
+        let handlebars = registry();
+        let data = template_data(rule, alert);
+
+        let mut rendered = alert.clone();
+        if let Some(subject) = &self.subject {
+            rendered.title = handlebars.render_template(subject, &data)
+                .context("Failed to render subject template")?;
+        }
+        rendered.message = handlebars.render_template(&self.body, &data)
+            .context("Failed to render body template")?;
+        rendered.template_format = Some(self.format);
+        Ok(rendered)
+    }
+}
+
+/// Values available to templates
+///
+/// Context fields are available both at the top level (`{{workers}}`) and
+/// under `context` (`{{context.workers}}`); built-in fields win on clashes.
+pub fn template_data(rule: &AlertRule, alert: &Alert) -> serde_json::Value {
+    let mut data = match &alert.context {
+        serde_json::Value::Object(fields) => fields.clone(),
+        _ => serde_json::Map::new(),
+    };
+    let builtins = serde_json::json!({
+        "rule_id": rule.id,
+        "rule_name": rule.name,
+        "level": alert.level.to_string(),
+        "title": alert.title,
+        "message": alert.message,
+        "triggered_at": alert.triggered_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        "context": alert.context,
+    });
+    if let serde_json::Value::Object(builtins) = builtins {
+        data.extend(builtins);
+    }
+    serde_json::Value::Object(data)
+}
+
+/// Validate a rule's templates against its channels
+pub fn validate_templates(templates: &HashMap<String, MessageTemplate>, channels: &[String]) -> Result<()> {
+    for (key, template) in templates {
+        if key != DEFAULT_TEMPLATE_KEY && !channels.contains(key) {
+            return Err(anyhow::anyhow!("Template for unknown channel: {}", key));
+        }
+        template.validate().with_context(|| format!("Template for channel {}", key))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertCondition, AlertLevel};
+    use chrono::Utc;
+
+    fn rule() -> AlertRule {
+        AlertRule::new(
+            "rack_offline",
+            "Rack offline",
+            AlertCondition::TaggedWorkersOffline { tag: "rack-3".to_string(), miner: None },
+            AlertLevel::Critical,
+            vec!["telegram".to_string()],
+        )
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            id: "a1".to_string(),
+            rule_id: "rack_offline".to_string(),
+            level: AlertLevel::Critical,
+            title: "CRITICAL Alert: Rack offline".to_string(),
+            message: "All 4 workers tagged \"rack-3\" are offline".to_string(),
+            context: serde_json::json!({ "key": "rack-3", "workers": 4 }),
+            triggered_at: Utc::now(),
+            acknowledged: false,
+            channel: "telegram".to_string(),
+            template_format: None,
+            deliveries: Vec::new(),
+            resolution: None,
+        }
+    }
+
+    #[test]
+    fn test_render_with_context_fields() {
+        let template = MessageTemplate {
+            subject: Some("[{{level}}] {{rule_name}}".to_string()),
+            body: "{{workers}} rigs down on {{context.key}} <check power>".to_string(),
+            format: MessageFormat::Plain,
+        };
+        let rendered = template.render(&rule(), &alert()).unwrap();
+        assert_eq!(rendered.title, "[CRITICAL] Rack offline");
+        assert_eq!(rendered.message, "4 rigs down on rack-3 <check power>");
+        assert_eq!(rendered.template_format, Some(MessageFormat::Plain));
+    }
+
+    #[test]
+    fn test_validate_templates() {
+        let valid = MessageTemplate { subject: None, body: "{{message}}".to_string(), format: MessageFormat::Markdown };
+        let broken = MessageTemplate { subject: None, body: "{{#if workers}}unclosed".to_string(), format: MessageFormat::Markdown };
+        let channels = vec!["telegram".to_string()];
+
+        let templates = HashMap::from([("telegram".to_string(), valid.clone()), (DEFAULT_TEMPLATE_KEY.to_string(), valid.clone())]);
+        assert!(validate_templates(&templates, &channels).is_ok());
+        assert!(validate_templates(&HashMap::from([("email".to_string(), valid)]), &channels).is_err());
+        assert!(validate_templates(&HashMap::from([("telegram".to_string(), broken)]), &channels).is_err());
+    }
+}
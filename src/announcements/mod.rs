@@ -0,0 +1,378 @@
+// Pool Announcements Module for DMPool
+// Operator notices to miners (fee changes, maintenance) with a validity window
+//
+// Admins create announcements through the Admin API and the Observer API
+// serves the ones currently in effect. Announcements marked `notify` are
+// pushed once through miner notification channels when they take effect.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use crate::error::DmpoolError;
+use crate::miner_notify::{BroadcastReport, MinerNotifier};
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+const MAX_TITLE_CHARS: usize = 200;
+const MAX_BODY_CHARS: usize = 5000;
+
+/// Announcement settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnouncementConfig {
+    pub enabled: bool,
+    /// How often announcements that took effect are pushed to miners
+    pub push_interval_secs: u64,
+    /// Ended announcements are dropped after this many days
+    pub retention_days: i64,
+}
+
+impl Default for AnnouncementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            push_interval_secs: 60,
+            retention_days: 90,
+        }
+    }
+}
+
+impl AnnouncementConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.push_interval_secs < 10 {
+            return Err(anyhow::anyhow!("push_interval_secs must be at least 10"));
+        }
+        if self.retention_days < 1 {
+            return Err(anyhow::anyhow!("retention_days must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementSeverity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Announcement as submitted by an admin
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewAnnouncement {
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub severity: AnnouncementSeverity,
+    /// Defaults to now
+    #[serde(default)]
+    pub starts_at: Option<DateTime<Utc>>,
+    /// Open-ended when absent
+    #[serde(default)]
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Push to miners through their notification channels when it takes effect
+    #[serde(default)]
+    pub notify: bool,
+}
+
+impl NewAnnouncement {
+    fn validate(&self, now: DateTime<Utc>) -> Result<()> {
+        let title = self.title.trim();
+        if title.is_empty() || title.chars().count() > MAX_TITLE_CHARS {
+            return Err(DmpoolError::InvalidInput(format!("Title must be 1-{} characters", MAX_TITLE_CHARS)).into());
+        }
+        if self.body.trim().is_empty() || self.body.chars().count() > MAX_BODY_CHARS {
+            return Err(DmpoolError::InvalidInput(format!("Body must be 1-{} characters", MAX_BODY_CHARS)).into());
+        }
+        if let Some(ends_at) = self.ends_at {
+            if ends_at <= self.starts_at.unwrap_or(now) || ends_at <= now {
+                return Err(DmpoolError::InvalidInput("ends_at must be after starts_at and in the future".to_string()).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub severity: AnnouncementSeverity,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub notify: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When it was pushed to miners
+    #[serde(default)]
+    pub notified_at: Option<DateTime<Utc>>,
+}
+
+impl Announcement {
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        self.starts_at <= at && self.ends_at.is_none_or(|ends_at| at < ends_at)
+    }
+
+    pub fn subject(&self) -> String {
+        match self.severity {
+            AnnouncementSeverity::Info => format!("DMPool: {}", self.title),
+            AnnouncementSeverity::Warning => format!("DMPool warning: {}", self.title),
+            AnnouncementSeverity::Critical => format!("DMPool critical: {}", self.title),
+        }
+    }
+
+    /// Webhook body
+    pub fn notice(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "announcement",
+            "id": self.id,
+            "title": self.title,
+            "body": self.body,
+            "severity": self.severity,
+            "starts_at": self.starts_at,
+            "ends_at": self.ends_at,
+        })
+    }
+}
+
+/// Stores announcements and pushes them to miners
+pub struct AnnouncementBoard {
+    config: AnnouncementConfig,
+    data_dir: PathBuf,
+    announcements: RwLock<Vec<Announcement>>,
+    notifier: Option<Arc<MinerNotifier>>,
+}
+
+impl AnnouncementBoard {
+    /// Create a board persisting to `data_dir`
+    pub fn new(data_dir: PathBuf, config: AnnouncementConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create announcements directory")?;
+
+        Ok(Self {
+            config,
+            data_dir,
+            announcements: RwLock::new(Vec::new()),
+            notifier: None,
+        })
+    }
+
+    /// Push announcements marked `notify` to miners
+    pub fn with_notifier(mut self, notifier: Arc<MinerNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn config(&self) -> &AnnouncementConfig {
+        &self.config
+    }
+
+    /// Load persisted announcements
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("announcements.json");
+        if let Some(announcements) = persist::read_json::<Vec<Announcement>>(&path).await
+            .context("Failed to load announcements file")?
+        {
+            info!("Loaded {} announcements", announcements.len());
+            *self.announcements.write().await = announcements;
+        }
+        Ok(())
+    }
+
+    /// Drop announcements past retention and save the rest
+    async fn save(&self, announcements: &mut Vec<Announcement>) -> Result<()> {
+        let cutoff = Utc::now() - Duration::days(self.config.retention_days);
+        announcements.retain(|a| a.ends_at.is_none_or(|ends_at| ends_at > cutoff));
+        persist::write_json(&self.data_dir.join("announcements.json"), announcements).await
+            .context("Failed to write announcements file")
+    }
+
+    pub async fn create(&self, new: NewAnnouncement, created_by: &str) -> Result<Announcement> {
+        let now = Utc::now();
+        new.validate(now)?;
+        let announcement = Announcement {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: new.title.trim().to_string(),
+            body: new.body,
+            severity: new.severity,
+            starts_at: new.starts_at.unwrap_or(now),
+            ends_at: new.ends_at,
+            notify: new.notify,
+            created_by: created_by.to_string(),
+            created_at: now,
+            updated_at: now,
+            notified_at: None,
+        };
+
+        let mut announcements = self.announcements.write().await;
+        announcements.push(announcement.clone());
+        self.save(&mut announcements).await?;
+
+        info!("Created {:?} announcement {}: {}", announcement.severity, announcement.id, announcement.title);
+        Ok(announcement)
+    }
+
+    /// Replace an announcement's content and window; a sent push is not repeated
+    pub async fn update(&self, id: &str, new: NewAnnouncement) -> Result<Announcement> {
+        let now = Utc::now();
+        new.validate(now)?;
+
+        let mut announcements = self.announcements.write().await;
+        let announcement = announcements.iter_mut().find(|a| a.id == id)
+            .ok_or_else(|| DmpoolError::NotFound(format!("Announcement {} not found", id)))?;
+        announcement.title = new.title.trim().to_string();
+        announcement.body = new.body;
+        announcement.severity = new.severity;
+        announcement.starts_at = new.starts_at.unwrap_or(announcement.starts_at);
+        announcement.ends_at = new.ends_at;
+        announcement.notify = new.notify;
+        announcement.updated_at = now;
+        let updated = announcement.clone();
+        self.save(&mut announcements).await?;
+        Ok(updated)
+    }
+
+    /// Delete an announcement; false if there was none
+    pub async fn remove(&self, id: &str) -> Result<bool> {
+        let mut announcements = self.announcements.write().await;
+        let before = announcements.len();
+        announcements.retain(|a| a.id != id);
+        if announcements.len() == before {
+            return Ok(false);
+        }
+        self.save(&mut announcements).await?;
+        Ok(true)
+    }
+
+    /// All announcements, newest first
+    pub async fn list(&self) -> Vec<Announcement> {
+        let mut list = self.announcements.read().await.clone();
+        list.sort_by(|a, b| b.starts_at.cmp(&a.starts_at));
+        list
+    }
+
+    /// Announcements in effect at `at`, most severe first
+    pub async fn active_at(&self, at: DateTime<Utc>) -> Vec<Announcement> {
+        let mut active: Vec<Announcement> = self.announcements.read().await.iter()
+            .filter(|a| a.is_active_at(at))
+            .cloned()
+            .collect();
+        active.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| b.starts_at.cmp(&a.starts_at)));
+        active
+    }
+
+    /// Push announcements that took effect and were not pushed yet
+    pub async fn push_due(&self) -> Result<Vec<(String, BroadcastReport)>> {
+        let notifier = match &self.notifier {
+            Some(notifier) => notifier,
+            None => return Ok(Vec::new()),
+        };
+
+        let now = Utc::now();
+        let due: Vec<Announcement> = self.announcements.read().await.iter()
+            .filter(|a| a.notify && a.notified_at.is_none() && a.is_active_at(now))
+            .cloned()
+            .collect();
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut reports = Vec::new();
+        for announcement in &due {
+            let report = notifier.broadcast("announcement", &announcement.subject(), &announcement.body, &announcement.notice()).await;
+            reports.push((announcement.id.clone(), report));
+        }
+
+        let mut announcements = self.announcements.write().await;
+        for announcement in announcements.iter_mut().filter(|a| due.iter().any(|d| d.id == a.id)) {
+            announcement.notified_at = Some(now);
+        }
+        self.save(&mut announcements).await?;
+        Ok(reports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn new_announcement(title: &str) -> NewAnnouncement {
+        NewAnnouncement {
+            title: title.to_string(),
+            body: "The pool fee changes to 1.5% on Monday.".to_string(),
+            severity: AnnouncementSeverity::Info,
+            starts_at: None,
+            ends_at: None,
+            notify: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_window_and_persistence() {
+        let temp_dir = TempDir::new().unwrap();
+        let board = AnnouncementBoard::new(temp_dir.path().to_path_buf(), AnnouncementConfig::default()).unwrap();
+        let now = Utc::now();
+
+        let fee = board.create(new_announcement("Fee change"), "admin").await.unwrap();
+        let mut maintenance = new_announcement("Maintenance");
+        maintenance.severity = AnnouncementSeverity::Warning;
+        maintenance.starts_at = Some(now + Duration::hours(2));
+        maintenance.ends_at = Some(now + Duration::hours(3));
+        board.create(maintenance, "admin").await.unwrap();
+
+        assert!(board.create(new_announcement(" "), "admin").await.is_err());
+        let mut ended = new_announcement("Ended");
+        ended.ends_at = Some(now - Duration::hours(1));
+        assert!(board.create(ended, "admin").await.is_err());
+
+        let active: Vec<String> = board.active_at(now).await.into_iter().map(|a| a.title).collect();
+        assert_eq!(active, vec!["Fee change"]);
+        let later: Vec<String> = board.active_at(now + Duration::minutes(150)).await.into_iter().map(|a| a.title).collect();
+        assert_eq!(later, vec!["Maintenance", "Fee change"]);
+
+        let reloaded = AnnouncementBoard::new(temp_dir.path().to_path_buf(), AnnouncementConfig::default()).unwrap();
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.list().await.len(), 2);
+        assert!(reloaded.remove(&fee.id).await.unwrap());
+        assert!(!reloaded.remove(&fee.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_push_only_once() {
+        use crate::miner_notify::{MinerNotifierConfig, NotificationPreferences};
+
+        let temp_dir = TempDir::new().unwrap();
+        let notifier = Arc::new(MinerNotifier::new(temp_dir.path().join("notify"), MinerNotifierConfig::default()).unwrap());
+        notifier.set("bc1qa", NotificationPreferences {
+            email: None,
+            telegram_chat_id: None,
+            webhook_url: Some("https://127.0.0.1:1/hook".to_string()),
+            payout_broadcast: true,
+            payout_confirmed: true,
+            announcements: true,
+            updated_at: Utc::now(),
+        }).await.unwrap();
+        let board = AnnouncementBoard::new(temp_dir.path().join("announcements"), AnnouncementConfig::default())
+            .unwrap()
+            .with_notifier(notifier);
+
+        let mut notice = new_announcement("Fee change");
+        notice.notify = true;
+        let announcement = board.create(notice, "admin").await.unwrap();
+        board.create(new_announcement("Quiet"), "admin").await.unwrap();
+
+        let reports = board.push_due().await.unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].0, announcement.id);
+        // Nothing listens on the webhook, so the one miner counts as failed
+        assert_eq!(reports[0].1.failed, 1);
+        assert!(board.push_due().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,274 @@
+// Miner API Token Module for DMPool
+// Revocable read tokens for programmatic access to per-miner Observer data
+//
+// A miner asks for a challenge for an address, signs it with the address key
+// and exchanges the signature for a token. Challenges are single-use and
+// expire quickly; tokens are stored only as SHA-256 hashes and the plaintext
+// is returned once, at issue.
+
+use crate::accounts::OwnershipVerifier;
+use crate::error::DmpoolError;
+use crate::persist;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Minutes a challenge stays valid
+const CHALLENGE_TTL_MINUTES: i64 = 10;
+
+/// Active tokens allowed per address
+pub const MAX_TOKENS_PER_ADDRESS: usize = 10;
+
+/// Token prefix, so leaked tokens are easy to recognise
+const TOKEN_PREFIX: &str = "dmp";
+
+/// Length of the random part of a token
+const SECRET_LEN: usize = 40;
+
+/// Message to sign before a token is issued
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenChallenge {
+    pub address: String,
+    pub message: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Token metadata (never includes the secret)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerApiToken {
+    pub id: String,
+    pub address: String,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Newly issued token; `token` is not stored and cannot be shown again
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub info: MinerApiToken,
+}
+
+/// Persisted token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    info: MinerApiToken,
+    token_hash: String,
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Split `dmp_<id>_<secret>` into its id
+fn token_id(token: &str) -> Option<&str> {
+    let rest = token.strip_prefix(TOKEN_PREFIX)?.strip_prefix('_')?;
+    let (id, secret) = rest.split_once('_')?;
+    (!id.is_empty() && secret.len() == SECRET_LEN).then_some(id)
+}
+
+/// Issues, checks and revokes miner API tokens
+pub struct MinerTokenManager {
+    verifier: Arc<dyn OwnershipVerifier>,
+    data_dir: PathBuf,
+    tokens: RwLock<HashMap<String, StoredToken>>,
+    /// Outstanding challenges by address
+    challenges: RwLock<HashMap<String, TokenChallenge>>,
+}
+
+impl MinerTokenManager {
+    /// Create a token manager persisting to `data_dir`
+    pub fn new(data_dir: PathBuf, verifier: Arc<dyn OwnershipVerifier>) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create API token directory")?;
+
+        Ok(Self {
+            verifier,
+            data_dir,
+            tokens: RwLock::new(HashMap::new()),
+            challenges: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Load persisted tokens
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("tokens.json");
+        if let Some(tokens) = persist::read_json::<HashMap<String, StoredToken>>(&path).await
+            .context("Failed to load API tokens file")?
+        {
+            info!("Loaded {} miner API tokens", tokens.len());
+            *self.tokens.write().await = tokens;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, tokens: &HashMap<String, StoredToken>) -> Result<()> {
+        persist::write_json(&self.data_dir.join("tokens.json"), tokens).await
+            .context("Failed to write API tokens file")
+    }
+
+    /// Start token issuance for an address, replacing any earlier challenge
+    pub async fn challenge(&self, address: &str) -> TokenChallenge {
+        let challenge = TokenChallenge {
+            address: address.to_string(),
+            message: format!("DMPool API token for {} ({})", address, uuid::Uuid::new_v4().simple()),
+            expires_at: Utc::now() + Duration::minutes(CHALLENGE_TTL_MINUTES),
+        };
+        self.challenges.write().await.insert(address.to_string(), challenge.clone());
+        challenge
+    }
+
+    /// Exchange a signed challenge for a token
+    ///
+    /// The challenge is consumed whether or not the signature checks out.
+    pub async fn issue(&self, address: &str, signature: &str, label: Option<String>) -> Result<IssuedToken> {
+        let challenge = self.challenges.write().await.remove(address)
+            .filter(|c| c.expires_at > Utc::now())
+            .ok_or_else(|| DmpoolError::InvalidInput(format!("No pending challenge for {}", address)))?;
+
+        if !self.verifier.verify(address, &challenge.message, signature).await? {
+            return Err(DmpoolError::InvalidInput(format!("Invalid ownership signature for {}", address)).into());
+        }
+
+        let id = uuid::Uuid::new_v4().simple().to_string()[..16].to_string();
+        let secret: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect();
+        let token = format!("{}_{}_{}", TOKEN_PREFIX, id, secret);
+        let info = MinerApiToken {
+            id: id.clone(),
+            address: address.to_string(),
+            label,
+            created_at: Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        let mut tokens = self.tokens.write().await;
+        let active = tokens.values()
+            .filter(|t| t.info.address == address && t.info.revoked_at.is_none())
+            .count();
+        if active >= MAX_TOKENS_PER_ADDRESS {
+            return Err(DmpoolError::InvalidInput(format!("{} already has {} active tokens", address, MAX_TOKENS_PER_ADDRESS)).into());
+        }
+        tokens.insert(id.clone(), StoredToken { info: info.clone(), token_hash: hash_token(&token) });
+        self.save(&tokens).await?;
+
+        info!("Issued API token {} for {}", id, address);
+        Ok(IssuedToken { token, info })
+    }
+
+    /// Token metadata for a valid, unrevoked token
+    ///
+    /// Usage times are kept in memory and persisted with the next change.
+    pub async fn authenticate(&self, token: &str) -> Option<MinerApiToken> {
+        let id = token_id(token)?;
+        let mut tokens = self.tokens.write().await;
+        let stored = tokens.get_mut(id)?;
+        if stored.info.revoked_at.is_some() || stored.token_hash != hash_token(token) {
+            return None;
+        }
+        stored.info.last_used_at = Some(Utc::now());
+        Some(stored.info.clone())
+    }
+
+    /// Tokens issued for an address, newest first
+    pub async fn list(&self, address: &str) -> Vec<MinerApiToken> {
+        let mut tokens: Vec<MinerApiToken> = self.tokens.read().await.values()
+            .filter(|t| t.info.address == address)
+            .map(|t| t.info.clone())
+            .collect();
+        tokens.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        tokens
+    }
+
+    /// Revoke one of an address's tokens; false if it has no such active token
+    pub async fn revoke(&self, address: &str, id: &str) -> Result<bool> {
+        let mut tokens = self.tokens.write().await;
+        match tokens.get_mut(id) {
+            Some(stored) if stored.info.address == address && stored.info.revoked_at.is_none() => {
+                stored.info.revoked_at = Some(Utc::now());
+            }
+            _ => return Ok(false),
+        }
+        self.save(&tokens).await?;
+
+        info!("Revoked API token {} for {}", id, address);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    /// Accepts signatures of the form "sig:<address>:<message>"
+    struct FakeVerifier;
+
+    #[async_trait]
+    impl OwnershipVerifier for FakeVerifier {
+        async fn verify(&self, address: &str, message: &str, signature: &str) -> Result<bool> {
+            Ok(signature == format!("sig:{}:{}", address, message))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_challenge_is_single_use() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = MinerTokenManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+
+        assert!(manager.issue("bc1qa", "sig", None).await.is_err());
+
+        let challenge = manager.challenge("bc1qa").await;
+        let signature = format!("sig:bc1qa:{}", challenge.message);
+        assert!(manager.issue("bc1qa", "sig:bc1qa:wrong", None).await.is_err());
+        // The failed attempt used up the challenge
+        assert!(manager.issue("bc1qa", &signature, None).await.is_err());
+
+        let challenge = manager.challenge("bc1qa").await;
+        let issued = manager.issue("bc1qa", &format!("sig:bc1qa:{}", challenge.message), Some("grafana".to_string())).await.unwrap();
+        assert!(issued.token.starts_with("dmp_"));
+        assert!(manager.issue("bc1qa", &format!("sig:bc1qa:{}", challenge.message), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_and_revoke() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = MinerTokenManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+        let challenge = manager.challenge("bc1qa").await;
+        let issued = manager.issue("bc1qa", &format!("sig:bc1qa:{}", challenge.message), None).await.unwrap();
+
+        // Only the hash is persisted
+        let stored = std::fs::read_to_string(temp_dir.path().join("tokens.json")).unwrap();
+        assert!(!stored.contains(&issued.token));
+
+        let reloaded = MinerTokenManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.authenticate(&issued.token).await.unwrap().address, "bc1qa");
+        let mut tampered = issued.token.clone();
+        tampered.pop();
+        tampered.push('!');
+        assert!(reloaded.authenticate(&tampered).await.is_none());
+        assert!(reloaded.authenticate("dmp_nope").await.is_none());
+
+        assert!(!reloaded.revoke("bc1qother", &issued.info.id).await.unwrap());
+        assert!(reloaded.revoke("bc1qa", &issued.info.id).await.unwrap());
+        assert!(reloaded.authenticate(&issued.token).await.is_none());
+        assert!(reloaded.list("bc1qa").await[0].revoked_at.is_some());
+    }
+}
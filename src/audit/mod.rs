@@ -1,14 +1,19 @@
 // Audit Logging module for DMPool Admin
 // Records all admin operations for security and compliance
-// Supports file-based persistence for long-term storage
+// Entries are appended to a JSONL log in a blob store (a file by default)
+// Entries can be checked against audit anomaly alert rules as they are logged
 
+use crate::alert::{AlertManager, AuditAnomalyDetector};
+use crate::runtime_metrics::{string_bytes, MemoryFootprint, MemoryReporter};
+use crate::storage::{BlobStore, FileStore};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
@@ -21,6 +26,9 @@ pub struct AuditLog {
     pub timestamp: DateTime<Utc>,
     /// User who performed the action
     pub username: String,
+    /// Role of the user, absent for system actions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
     /// Action performed (e.g., "login", "config_update", "ban_worker")
     pub action: String,
     /// Resource affected (e.g., "/api/config", "worker:address")
@@ -33,6 +41,9 @@ pub struct AuditLog {
     pub success: bool,
     /// Error message if failed
     pub error: Option<String>,
+    /// Correlation ID of the API request that caused the action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 /// Audit log filter options
@@ -65,30 +76,74 @@ impl Default for AuditFilter {
     }
 }
 
+/// Key of the active audit log
+const AUDIT_LOG_KEY: &str = "audit.jsonl";
+
 /// Audit log manager with file persistence
 pub struct AuditLogger {
     /// In-memory cache for recent logs
     logs: Arc<RwLock<Vec<AuditLog>>>,
     /// Maximum number of logs to keep in memory
     max_logs: usize,
-    /// Path to the audit log file (JSONL format)
-    log_file: Option<PathBuf>,
-    /// Whether to enable file persistence
-    persistence_enabled: bool,
+    /// Store holding the audit log, if persistence is enabled
+    store: Option<Arc<dyn BlobStore>>,
+    /// Key of the audit log (JSONL format) in the store
+    log_key: String,
+    /// Raises alerts on anomalous entries
+    watch: Option<Arc<AuditWatch>>,
+    /// Keeps appends out of a purge rewriting the log
+    store_lock: tokio::sync::Mutex<()>,
+}
+
+/// Evaluates logged entries against audit anomaly rules
+struct AuditWatch {
+    alerts: Arc<AlertManager>,
+    detector: AuditAnomalyDetector,
+}
+
+impl AuditWatch {
+    async fn observe(&self, entry: &AuditLog) {
+        if let Err(e) = self.detector.check(&self.alerts, entry).await {
+            error!("Failed to evaluate audit anomaly rules: {}", e);
+        }
+    }
 }
 
 impl AuditLogger {
     /// Create a new audit logger with file persistence
     pub fn new(max_logs: usize, log_file: Option<PathBuf>) -> Self {
-        let persistence_enabled = log_file.is_some();
+        let (store, log_key) = match log_file {
+            Some(path) => {
+                let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+                let key = path.file_name().map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| AUDIT_LOG_KEY.to_string());
+                (Some(Arc::new(FileStore::new(dir)) as Arc<dyn BlobStore>), key)
+            }
+            None => (None, AUDIT_LOG_KEY.to_string()),
+        };
         Self {
             logs: Arc::new(RwLock::new(Vec::new())),
             max_logs,
-            log_file,
-            persistence_enabled,
+            store,
+            log_key,
+            watch: None,
+            store_lock: tokio::sync::Mutex::new(()),
         }
     }
 
+    /// Persist the log in `store` instead of a local file
+    pub fn with_store(mut self, store: Arc<dyn BlobStore>) -> Self {
+        self.store = Some(store);
+        self.log_key = AUDIT_LOG_KEY.to_string();
+        self
+    }
+
+    /// Check logged entries against the AuditAnomaly rules of `alerts`
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.watch = Some(Arc::new(AuditWatch { alerts, detector: AuditAnomalyDetector::new() }));
+        self
+    }
+
     /// Create with default settings and no file persistence
     pub fn default() -> Self {
         Self::new(10000, None)
@@ -115,13 +170,15 @@ impl AuditLogger {
     }
 
     /// Log an action
-    pub async fn log(&self, entry: AuditLog) {
-        // Write to file if persistence is enabled
-        if self.persistence_enabled {
-            if let Some(ref log_file) = self.log_file {
-                if let Err(e) = Self::append_to_file(log_file, &entry).await {
-                    error!("Failed to write audit log to file: {}", e);
-                }
+    pub async fn log(&self, mut entry: AuditLog) {
+        if entry.request_id.is_none() {
+            entry.request_id = crate::logging::request_id::current_request_id();
+        }
+
+        // Write to the store if persistence is enabled
+        if let Some(store) = &self.store {
+            if let Err(e) = self.append_to_store(store.as_ref(), &entry).await {
+                error!("Failed to write audit log to {} store: {}", store.backend(), e);
             }
         }
 
@@ -137,6 +194,8 @@ impl AuditLogger {
             warn!("Removed {} old audit logs to stay under limit", remove_count);
         }
 
+        drop(logs);
+
         // Log to tracing
         if entry.success {
             info!(
@@ -156,47 +215,33 @@ impl AuditLogger {
                 entry.error.as_deref().unwrap_or(&"unknown".to_string())
             );
         }
+
+        if let Some(watch) = &self.watch {
+            watch.observe(&entry).await;
+        }
     }
 
-    /// Append a log entry to the file (JSONL format - one JSON per line)
-    async fn append_to_file(log_file: &PathBuf, entry: &AuditLog) -> Result<()> {
-        let json_str = serde_json::to_string(entry)
+    /// Append a log entry to the store (JSONL format - one JSON per line)
+    async fn append_to_store(&self, store: &dyn BlobStore, entry: &AuditLog) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)
             .context("Failed to serialize audit log")?;
-
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .append(true)
-            .open(log_file)
-            .await
-            .context("Failed to open audit log file")?;
-
-        file.write_all(json_str.as_bytes()).await?;
-        file.write_all(b"\n").await?;
-        file.flush().await?;
-
-        Ok(())
+        line.push(b'\n');
+        let _guard = self.store_lock.lock().await;
+        store.append(&self.log_key, &line).await
+            .context("Failed to append to audit log")
     }
 
-    /// Load audit logs from file on startup
-    pub async fn load_from_file(&self) -> Result<usize> {
-        if !self.persistence_enabled {
+    /// Load audit logs from the store on startup
+    pub async fn load(&self) -> Result<usize> {
+        let Some(store) = &self.store else {
             return Ok(0);
-        }
-
-        let log_file = self.log_file.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No log file configured"))?;
+        };
 
-        if !log_file.exists() {
+        let Some(contents) = store.get(&self.log_key).await
+            .context("Failed to read audit log")?
+        else {
             return Ok(0);
-        }
-
-        let mut file = File::open(log_file).await
-            .context("Failed to open audit log file")?;
-
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await
-            .context("Failed to read audit log file")?;
+        };
 
         let mut logs = self.logs.write().await;
         let initial_count = logs.len();
@@ -215,7 +260,7 @@ impl AuditLogger {
         }
 
         let loaded_count = logs.len() - initial_count;
-        info!("Loaded {} audit logs from file", loaded_count);
+        info!("Loaded {} audit logs from {} store", loaded_count, store.backend());
 
         Ok(loaded_count)
     }
@@ -224,6 +269,7 @@ impl AuditLogger {
     pub fn entry(&self, username: String, action: String, resource: String, ip_address: String) -> AuditLogBuilder {
         AuditLogBuilder {
             username,
+            role: None,
             action,
             resource,
             ip_address,
@@ -231,6 +277,7 @@ impl AuditLogger {
             success: true,
             error: None,
             logger: self.logs.clone(),
+            watch: self.watch.clone(),
         }
     }
 
@@ -292,6 +339,38 @@ impl AuditLogger {
         Ok(original_len - logs.len())
     }
 
+    /// Rewrite the stored log without entries older than `days`, returning how many were dropped
+    pub async fn purge_stored(&self, days: i64) -> Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - chrono::Duration::days(days);
+        let _guard = self.store_lock.lock().await;
+        let Some(contents) = store.get(&self.log_key).await
+            .context("Failed to read audit log")?
+        else {
+            return Ok(0);
+        };
+
+        let mut kept = Vec::with_capacity(contents.len());
+        let mut removed = 0;
+        for line in contents.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+            // Lines that do not parse are kept for inspection
+            let expired = serde_json::from_slice::<AuditLog>(line).is_ok_and(|entry| entry.timestamp <= cutoff);
+            if expired {
+                removed += 1;
+            } else {
+                kept.extend_from_slice(line);
+                kept.push(b'\n');
+            }
+        }
+        if removed > 0 {
+            store.put(&self.log_key, &kept).await
+                .context("Failed to rewrite audit log")?;
+        }
+        Ok(removed)
+    }
+
     /// Get statistics about audit logs
     pub async fn stats(&self) -> AuditStats {
         let logs = self.logs.read().await;
@@ -322,30 +401,31 @@ impl AuditLogger {
         }
     }
 
-    /// Rotate audit log file (move current to archive and start fresh)
-    pub async fn rotate_logs(&self) -> Result<PathBuf> {
-        if !self.persistence_enabled {
-            return Err(anyhow::anyhow!("File persistence not enabled"));
-        }
-
-        let log_file = self.log_file.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No log file configured"))?;
+    /// Rotate audit log (move current to archive and start fresh)
+    ///
+    /// Returns the archive's file path, or its key for stores without files.
+    pub async fn rotate_logs(&self) -> Result<String> {
+        let store = self.store.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Persistence not enabled"))?;
 
-        if !log_file.exists() {
+        if !store.exists(&self.log_key).await? {
             return Err(anyhow::anyhow!("Log file does not exist"));
         }
 
-        // Create archive filename with timestamp
+        // Create archive name with timestamp
         let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
-        let archive_path = log_file.with_file_name(format!("audit_{}.jsonl", timestamp));
+        let archive_key = format!("audit_{}.jsonl", timestamp);
 
         // Move current log to archive
-        tokio::fs::rename(log_file, &archive_path).await
-            .context("Failed to rotate audit log file")?;
+        store.rename(&self.log_key, &archive_key).await
+            .context("Failed to rotate audit log")?;
 
-        info!("Rotated audit log: {:?} -> {:?}", log_file, archive_path);
+        let archive = store.local_path(&archive_key)
+            .map(|path| path.display().to_string())
+            .unwrap_or(archive_key);
+        info!("Rotated audit log: {} -> {}", self.log_key, archive);
 
-        Ok(archive_path)
+        Ok(archive)
     }
 
     /// Export audit logs to JSON file
@@ -368,9 +448,33 @@ impl AuditLogger {
         Ok(logs.len())
     }
 
-    /// Get log file path if persistence is enabled
-    pub fn log_file_path(&self) -> Option<&PathBuf> {
-        self.log_file.as_ref()
+    /// Get log file path if the log is persisted to a file
+    pub fn log_file_path(&self) -> Option<PathBuf> {
+        self.store.as_ref()?.local_path(&self.log_key)
+    }
+}
+
+#[async_trait]
+impl MemoryReporter for AuditLogger {
+    async fn footprints(&self) -> Vec<MemoryFootprint> {
+        let logs = self.logs.read().await;
+        let estimated_bytes = logs.iter()
+            .map(|entry| {
+                // Details are estimated by their serialized size
+                let details = if entry.details.is_null() { 0 } else { entry.details.to_string().len() };
+                (std::mem::size_of::<AuditLog>()
+                    + entry.id.capacity()
+                    + entry.username.capacity()
+                    + entry.action.capacity()
+                    + entry.resource.capacity()
+                    + entry.ip_address.capacity()
+                    + details) as u64
+                    + string_bytes(entry.role.as_ref())
+                    + string_bytes(entry.error.as_ref())
+                    + string_bytes(entry.request_id.as_ref())
+            })
+            .sum();
+        vec![MemoryFootprint { name: "audit_log".to_string(), entries: logs.len(), estimated_bytes }]
     }
 }
 
@@ -388,6 +492,7 @@ pub struct AuditStats {
 /// Builder for creating audit log entries
 pub struct AuditLogBuilder {
     username: String,
+    role: Option<String>,
     action: String,
     resource: String,
     ip_address: String,
@@ -395,9 +500,16 @@ pub struct AuditLogBuilder {
     success: bool,
     error: Option<String>,
     logger: Arc<RwLock<Vec<AuditLog>>>,
+    watch: Option<Arc<AuditWatch>>,
 }
 
 impl AuditLogBuilder {
+    /// Set the user's role
+    pub fn role(mut self, role: impl Into<String>) -> Self {
+        self.role = Some(role.into());
+        self
+    }
+
     /// Add details to the log entry
     pub fn details(mut self, details: serde_json::Value) -> Self {
         self.details = details;
@@ -424,16 +536,17 @@ impl AuditLogBuilder {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             username: self.username,
+            role: self.role,
             action: self.action,
             resource: self.resource,
             ip_address: self.ip_address,
             details: self.details,
             success: self.success,
             error: error_msg.clone(),
+            request_id: crate::logging::request_id::current_request_id(),
         };
 
-        let mut logs = self.logger.write().await;
-        logs.push(entry.clone());
+        self.logger.write().await.push(entry.clone());
 
         // Log to tracing
         if self.success {
@@ -454,6 +567,10 @@ impl AuditLogBuilder {
                 error_msg.as_deref().unwrap_or(&"unknown".to_string())
             );
         }
+
+        if let Some(watch) = &self.watch {
+            watch.observe(&entry).await;
+        }
     }
 }
 
@@ -491,18 +608,47 @@ mod tests {
             id: "test-1".to_string(),
             timestamp: Utc::now(),
             username: "admin".to_string(),
+            role: None,
             action: "login".to_string(),
             resource: "/api/auth/login".to_string(),
             ip_address: "127.0.0.1".to_string(),
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
         };
 
         logger.log(entry).await;
         assert_eq!(logger.all().await.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_memory_footprint_tracks_cache() {
+        let logger = AuditLogger::new(2, None);
+        assert_eq!(logger.footprints().await[0].entries, 0);
+
+        for i in 0..3 {
+            logger.log(AuditLog {
+                id: format!("test-{}", i),
+                timestamp: Utc::now(),
+                username: "admin".to_string(),
+                role: None,
+                action: "login".to_string(),
+                resource: "/api/auth/login".to_string(),
+                ip_address: "127.0.0.1".to_string(),
+                details: json!({"method": "password"}),
+                success: true,
+                error: None,
+                request_id: None,
+            }).await;
+        }
+
+        let footprint = &logger.footprints().await[0];
+        assert_eq!(footprint.name, "audit_log");
+        assert_eq!(footprint.entries, 2);
+        assert!(footprint.estimated_bytes >= 2 * std::mem::size_of::<AuditLog>() as u64);
+    }
+
     #[tokio::test]
     async fn test_audit_log_query() {
         let logger = AuditLogger::new(100, None);
@@ -512,24 +658,28 @@ mod tests {
             id: "1".to_string(),
             timestamp: Utc::now(),
             username: "admin".to_string(),
+            role: None,
             action: "login".to_string(),
             resource: "/api/auth/login".to_string(),
             ip_address: "127.0.0.1".to_string(),
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
         }).await;
 
         logger.log(AuditLog {
             id: "2".to_string(),
             timestamp: Utc::now(),
             username: "user".to_string(),
+            role: None,
             action: "config_update".to_string(),
             resource: "/api/config".to_string(),
             ip_address: "127.0.0.2".to_string(),
             details: json!({}),
             success: true,
             error: None,
+            request_id: None,
         }).await;
 
         // Query for admin logs
@@ -552,12 +702,14 @@ mod tests {
                 id: format!("test-{}", i),
                 timestamp: Utc::now(),
                 username: "admin".to_string(),
+                role: None,
                 action: "test".to_string(),
                 resource: "/test".to_string(),
                 ip_address: "127.0.0.1".to_string(),
                 details: json!({}),
                 success: true,
                 error: None,
+                request_id: None,
             }).await;
         }
 
@@ -565,4 +717,83 @@ mod tests {
         let all = logger.all().await;
         assert_eq!(all.len(), 5);
     }
+
+    #[tokio::test]
+    async fn test_log_persists_and_rotates_in_store() {
+        let store: Arc<dyn BlobStore> = Arc::new(crate::storage::MemoryStore::default());
+        let logger = AuditLogger::new(100, None).with_store(store.clone());
+        logger.log(AuditLog {
+            id: "1".to_string(),
+            timestamp: Utc::now(),
+            username: "admin".to_string(),
+            role: None,
+            action: "config_update".to_string(),
+            resource: "/api/config".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            details: json!({}),
+            success: true,
+            error: None,
+            request_id: None,
+        }).await;
+
+        let restarted = AuditLogger::new(100, None).with_store(store.clone());
+        assert_eq!(restarted.load().await.unwrap(), 1);
+        assert_eq!(restarted.all().await[0].action, "config_update");
+
+        let archive = restarted.rotate_logs().await.unwrap();
+        assert!(archive.starts_with("audit_"));
+        assert_eq!(store.list().await.unwrap(), vec![archive]);
+        assert!(restarted.log_file_path().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_purge_stored_drops_expired_entries() {
+        let store: Arc<dyn BlobStore> = Arc::new(crate::storage::MemoryStore::default());
+        let logger = AuditLogger::new(100, None).with_store(store.clone());
+        for (id, age_days) in [("old", 40), ("new", 1)] {
+            logger.log(AuditLog {
+                id: id.to_string(),
+                timestamp: Utc::now() - chrono::Duration::days(age_days),
+                username: "bc1qminer".to_string(),
+                role: None,
+                action: "observer_access".to_string(),
+                resource: "observer:/api/v1/me/earnings".to_string(),
+                ip_address: "203.0.113.7".to_string(),
+                details: json!({}),
+                success: true,
+                error: None,
+                request_id: None,
+            }).await;
+        }
+
+        assert_eq!(logger.purge_stored(30).await.unwrap(), 1);
+        assert_eq!(logger.purge_stored(30).await.unwrap(), 0);
+        let restarted = AuditLogger::new(100, None).with_store(store);
+        assert_eq!(restarted.load().await.unwrap(), 1);
+        assert_eq!(restarted.all().await[0].id, "new");
+    }
+
+    #[tokio::test]
+    async fn test_audit_anomaly_alerts() {
+        let alerts = Arc::new(AlertManager::default());
+        for rule in crate::alert::default_audit_rules(vec![]) {
+            alerts.add_rule(rule).await;
+        }
+        let logger = AuditLogger::new(100, None).with_alerts(alerts.clone());
+
+        for _ in 0..6 {
+            logger.entry("mallory".to_string(), "login".to_string(), "/api/auth/login".to_string(), "10.0.0.9".to_string())
+                .error("invalid credentials".to_string())
+                .log()
+                .await;
+        }
+        logger.entry("eve".to_string(), "payout_create".to_string(), "payout:1".to_string(), "10.0.0.9".to_string())
+            .role("viewer")
+            .log()
+            .await;
+
+        let fired: Vec<String> = alerts.get_history(None).await.into_iter().map(|a| a.rule_id).collect();
+        assert!(fired.contains(&"audit_failed_logins".to_string()));
+        assert!(fired.contains(&"audit_payout_role".to_string()));
+    }
 }
@@ -0,0 +1,372 @@
+// Login challenges after repeated failures
+//
+// Rate limiting alone locks out everyone behind a shared NAT once one client
+// there starts guessing passwords. Instead, after `failures_before_challenge`
+// failed logins from an IP or for a username within `failure_window_secs`,
+// a login must also carry a solved challenge. The built-in challenge is
+// hashcash-style proof of work: a nonce such that SHA-256("<id>:<nonce>")
+// starts with `difficulty_bits` zero bits. A CAPTCHA service with a
+// siteverify endpoint (hCaptcha, reCAPTCHA, Turnstile) can be used instead,
+// or any other `ChallengeVerifier`. Challenges are single use.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Outstanding challenges kept at most; older ones are dropped
+const MAX_OUTSTANDING_CHALLENGES: usize = 10_000;
+
+/// What a challenged client has to solve
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeProvider {
+    #[default]
+    ProofOfWork,
+    Captcha,
+}
+
+/// The `[dmpool.login_challenge]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoginChallengeConfig {
+    pub enabled: bool,
+    /// Failed logins from an IP or for a username before a challenge is required
+    pub failures_before_challenge: u32,
+    /// Seconds failed logins are counted for
+    pub failure_window_secs: u64,
+    pub provider: ChallengeProvider,
+    /// Leading zero bits of the proof of work hash
+    pub difficulty_bits: u32,
+    /// Seconds a challenge can be solved in
+    pub challenge_ttl_secs: u64,
+    /// CAPTCHA siteverify endpoint
+    pub captcha_verify_url: Option<String>,
+    /// Handed to the client to render the CAPTCHA widget
+    pub captcha_site_key: Option<String>,
+    /// Overridden by LOGIN_CAPTCHA_SECRET
+    #[serde(skip_serializing)]
+    pub captcha_secret: Option<String>,
+}
+
+impl Default for LoginChallengeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failures_before_challenge: 3,
+            failure_window_secs: 900,
+            provider: ChallengeProvider::ProofOfWork,
+            difficulty_bits: 20,
+            challenge_ttl_secs: 300,
+            captcha_verify_url: None,
+            captcha_site_key: None,
+            captcha_secret: None,
+        }
+    }
+}
+
+impl LoginChallengeConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.failures_before_challenge == 0 {
+            return Err(anyhow::anyhow!("failures_before_challenge must be at least 1"));
+        }
+        if self.failure_window_secs == 0 || self.challenge_ttl_secs == 0 {
+            return Err(anyhow::anyhow!("failure_window_secs and challenge_ttl_secs must be at least 1"));
+        }
+        match self.provider {
+            ChallengeProvider::ProofOfWork => {
+                if !(8..=32).contains(&self.difficulty_bits) {
+                    return Err(anyhow::anyhow!("difficulty_bits must be 8 to 32"));
+                }
+            }
+            ChallengeProvider::Captcha => {
+                if self.captcha_verify_url.is_none() || self.captcha_secret.is_none() {
+                    return Err(anyhow::anyhow!("the captcha provider needs captcha_verify_url and captcha_secret"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifier for the configured provider
+    pub fn verifier(&self) -> Result<Arc<dyn ChallengeVerifier>> {
+        Ok(match self.provider {
+            ChallengeProvider::ProofOfWork => Arc::new(ProofOfWork { difficulty_bits: self.difficulty_bits }),
+            ChallengeProvider::Captcha => Arc::new(SiteVerifyCaptcha::new(
+                self.captcha_verify_url.clone().context("captcha_verify_url is not set")?,
+                self.captcha_secret.clone().context("captcha_secret is not set")?,
+            )?),
+        })
+    }
+}
+
+/// Challenge handed to a client whose login was refused
+#[derive(Clone, Debug, Serialize)]
+pub struct LoginChallenge {
+    pub id: String,
+    pub provider: ChallengeProvider,
+    /// For proof of work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty_bits: Option<u32>,
+    /// For CAPTCHA
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_key: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Solved challenge sent with a login
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChallengeSolution {
+    pub id: String,
+    /// Proof of work nonce or CAPTCHA response token
+    pub solution: String,
+}
+
+/// Checks solutions of one kind of challenge
+#[async_trait]
+pub trait ChallengeVerifier: Send + Sync {
+    fn provider(&self) -> ChallengeProvider;
+
+    /// Proof of work difficulty, if this verifier uses one
+    fn difficulty_bits(&self) -> Option<u32> {
+        None
+    }
+
+    async fn verify(&self, challenge_id: &str, solution: &str, ip: IpAddr) -> Result<bool>;
+}
+
+/// Hashcash-style proof of work
+pub struct ProofOfWork {
+    pub difficulty_bits: u32,
+}
+
+/// Leading zero bits of SHA-256("<challenge_id>:<nonce>")
+pub fn proof_of_work_bits(challenge_id: &str, nonce: &str) -> u32 {
+    let digest = Sha256::digest(format!("{}:{}", challenge_id, nonce).as_bytes());
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[async_trait]
+impl ChallengeVerifier for ProofOfWork {
+    fn provider(&self) -> ChallengeProvider {
+        ChallengeProvider::ProofOfWork
+    }
+
+    fn difficulty_bits(&self) -> Option<u32> {
+        Some(self.difficulty_bits)
+    }
+
+    async fn verify(&self, challenge_id: &str, solution: &str, _ip: IpAddr) -> Result<bool> {
+        Ok(solution.len() <= 64 && proof_of_work_bits(challenge_id, solution) >= self.difficulty_bits)
+    }
+}
+
+/// CAPTCHA checked against a siteverify endpoint
+pub struct SiteVerifyCaptcha {
+    url: String,
+    secret: String,
+    http: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+impl SiteVerifyCaptcha {
+    pub fn new(url: String, secret: String) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .context("Failed to build CAPTCHA HTTP client")?;
+        Ok(Self { url, secret, http })
+    }
+}
+
+#[async_trait]
+impl ChallengeVerifier for SiteVerifyCaptcha {
+    fn provider(&self) -> ChallengeProvider {
+        ChallengeProvider::Captcha
+    }
+
+    async fn verify(&self, _challenge_id: &str, solution: &str, ip: IpAddr) -> Result<bool> {
+        let ip = ip.to_string();
+        let response: SiteVerifyResponse = self.http.post(&self.url)
+            .form(&[("secret", self.secret.as_str()), ("response", solution), ("remoteip", ip.as_str())])
+            .send()
+            .await
+            .context("CAPTCHA verification request failed")?
+            .error_for_status()
+            .context("CAPTCHA verification was refused")?
+            .json()
+            .await
+            .context("Invalid CAPTCHA verification response")?;
+        Ok(response.success)
+    }
+}
+
+/// Failed logins and outstanding challenges
+pub struct LoginChallenges {
+    config: LoginChallengeConfig,
+    verifier: Arc<dyn ChallengeVerifier>,
+    /// Failure times by `ip:<ip>` and `user:<name>`
+    failures: RwLock<HashMap<String, Vec<DateTime<Utc>>>>,
+    issued: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl LoginChallenges {
+    pub fn new(config: LoginChallengeConfig, verifier: Arc<dyn ChallengeVerifier>) -> Self {
+        Self { config, verifier, failures: RwLock::new(HashMap::new()), issued: RwLock::new(HashMap::new()) }
+    }
+
+    fn keys(ip: IpAddr, username: &str) -> [String; 2] {
+        [format!("ip:{}", ip), format!("user:{}", username.to_lowercase())]
+    }
+
+    /// Whether a login from `ip` for `username` needs a solved challenge
+    pub async fn required(&self, ip: IpAddr, username: &str) -> bool {
+        let since = Utc::now() - Duration::seconds(self.config.failure_window_secs as i64);
+        let failures = self.failures.read().await;
+        Self::keys(ip, username).iter().any(|key| {
+            failures.get(key).map_or(0, |times| times.iter().filter(|t| **t > since).count())
+                >= self.config.failures_before_challenge as usize
+        })
+    }
+
+    /// Hand out a new challenge
+    pub async fn issue(&self) -> LoginChallenge {
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(self.config.challenge_ttl_secs as i64);
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let mut issued = self.issued.write().await;
+        issued.retain(|_, expires| *expires > now);
+        if issued.len() >= MAX_OUTSTANDING_CHALLENGES {
+            let oldest = issued.iter().min_by_key(|(_, expires)| **expires).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                issued.remove(&oldest);
+            }
+        }
+        issued.insert(id.clone(), expires_at);
+        LoginChallenge {
+            id,
+            provider: self.verifier.provider(),
+            difficulty_bits: self.verifier.difficulty_bits(),
+            site_key: self.config.captcha_site_key.clone().filter(|_| self.verifier.provider() == ChallengeProvider::Captcha),
+            expires_at,
+        }
+    }
+
+    /// Whether `solution` solves a challenge issued here; each challenge is used up by one attempt
+    pub async fn verify(&self, solution: &ChallengeSolution, ip: IpAddr) -> bool {
+        let expires_at = self.issued.write().await.remove(&solution.id);
+        if !expires_at.is_some_and(|expires| expires > Utc::now()) {
+            debug!("Unknown or expired login challenge from {}", ip);
+            return false;
+        }
+        match self.verifier.verify(&solution.id, &solution.solution, ip).await {
+            Ok(valid) => valid,
+            Err(e) => {
+                warn!("Failed to verify login challenge: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// A challenge to refuse the login with, or none if it may go ahead
+    pub async fn check(&self, ip: IpAddr, username: &str, solution: Option<&ChallengeSolution>) -> Option<LoginChallenge> {
+        if !self.required(ip, username).await {
+            return None;
+        }
+        if let Some(solution) = solution {
+            if self.verify(solution, ip).await {
+                return None;
+            }
+        }
+        Some(self.issue().await)
+    }
+
+    /// Count a failed login against both the IP and the username
+    pub async fn record_failure(&self, ip: IpAddr, username: &str) {
+        let now = Utc::now();
+        let since = now - Duration::seconds(self.config.failure_window_secs as i64);
+        let mut failures = self.failures.write().await;
+        failures.retain(|_, times| {
+            times.retain(|t| *t > since);
+            !times.is_empty()
+        });
+        for key in Self::keys(ip, username) {
+            failures.entry(key).or_default().push(now);
+        }
+    }
+
+    /// Forget a username's failures after it logged in; the IP's age out
+    pub async fn record_success(&self, username: &str) {
+        self.failures.write().await.remove(&format!("user:{}", username.to_lowercase()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(challenge: &LoginChallenge) -> String {
+        let bits = challenge.difficulty_bits.unwrap();
+        (0u64..).map(|n| n.to_string()).find(|nonce| proof_of_work_bits(&challenge.id, nonce) >= bits).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_challenge_after_failures_and_single_use() {
+        let config = LoginChallengeConfig { enabled: true, failures_before_challenge: 2, difficulty_bits: 8, ..Default::default() };
+        assert!(config.validate().is_ok());
+        let challenges = LoginChallenges::new(config.clone(), config.verifier().unwrap());
+        let ip: IpAddr = "198.51.100.4".parse().unwrap();
+        let other: IpAddr = "198.51.100.5".parse().unwrap();
+
+        challenges.record_failure(ip, "alice").await;
+        assert!(challenges.check(ip, "alice", None).await.is_none());
+        challenges.record_failure(ip, "alice").await;
+        let challenge = challenges.check(ip, "bob", None).await.expect("the IP is challenged");
+        assert_eq!(challenge.provider, ChallengeProvider::ProofOfWork);
+        // The username is challenged from any IP
+        assert!(challenges.required(other, "Alice").await);
+
+        let nonce = (0u64..).map(|n| n.to_string()).find(|n| proof_of_work_bits(&challenge.id, n) < 8).unwrap();
+        let wrong = ChallengeSolution { id: challenge.id.clone(), solution: nonce };
+        let challenge = challenges.check(ip, "alice", Some(&wrong)).await.expect("a wrong nonce is refused");
+        let solution = ChallengeSolution { id: challenge.id.clone(), solution: solve(&challenge) };
+        assert!(challenges.check(ip, "alice", Some(&solution)).await.is_none());
+        assert!(challenges.check(ip, "alice", Some(&solution)).await.is_some(), "challenges are single use");
+
+        challenges.record_success("alice").await;
+        assert!(!challenges.required(other, "alice").await);
+        assert!(challenges.required(ip, "alice").await);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(LoginChallengeConfig { difficulty_bits: 40, ..Default::default() }.validate().is_err());
+        let captcha = LoginChallengeConfig { provider: ChallengeProvider::Captcha, ..Default::default() };
+        assert!(captcha.validate().is_err());
+        let captcha = LoginChallengeConfig {
+            captcha_verify_url: Some("https://hcaptcha.com/siteverify".to_string()),
+            captcha_secret: Some("secret".to_string()),
+            ..captcha
+        };
+        assert!(captcha.validate().is_ok());
+        assert_eq!(captcha.verifier().unwrap().provider(), ChallengeProvider::Captcha);
+        assert_eq!(proof_of_work_bits("id", "nonce"), proof_of_work_bits("id", "nonce"));
+    }
+}
@@ -0,0 +1,434 @@
+// Login History
+//
+// Records every admin login attempt (user, IP, user agent, outcome, 2FA
+// method and the location reported by the reverse proxy) and flags successful
+// logins that look suspicious next to the user's earlier ones: a country or
+// IP range never seen before, or a location that could not have been reached
+// since the previous login.
+
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::db::DatabaseManager;
+use crate::rate_limit::extract_client_ip_with_default_config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Alert rule triggered by suspicious logins
+pub const SUSPICIOUS_LOGIN_ALERT_RULE: &str = "suspicious_login";
+
+/// Earlier successful logins compared against a new one
+const HISTORY_WINDOW: i64 = 50;
+
+/// Faster than an airliner between consecutive logins is impossible travel
+const MAX_TRAVEL_SPEED_KMH: f64 = 1000.0;
+
+/// Shorter distances are within geolocation accuracy
+const MIN_TRAVEL_DISTANCE_KM: f64 = 100.0;
+
+/// Second factor used for a login
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwoFactorMethod {
+    Totp,
+    BackupCode,
+}
+
+impl TwoFactorMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            TwoFactorMethod::Totp => "totp",
+            TwoFactorMethod::BackupCode => "backup_code",
+        }
+    }
+}
+
+/// Where a login came from, as reported by the reverse proxy
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoginLocation {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl LoginLocation {
+    /// Location from Cloudflare's visitor location headers
+    ///
+    /// Cloudflare reports `XX` when it has no country for the address.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+        Self {
+            country: header("cf-ipcountry")
+                .filter(|c| c.len() == 2 && *c != "XX")
+                .map(str::to_uppercase),
+            latitude: header("cf-iplatitude").and_then(|v| v.parse().ok()),
+            longitude: header("cf-iplongitude").and_then(|v| v.parse().ok()),
+        }
+    }
+
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude?, self.longitude?))
+    }
+}
+
+/// Why a successful login was flagged
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SuspiciousLogin {
+    /// First login from this country
+    NewCountry { country: String },
+    /// First login from this /24 (IPv4) or /48 (IPv6)
+    NewIpRange { range: String },
+    /// Too far from the previous login for the time between them
+    ImpossibleTravel {
+        from_ip: IpAddr,
+        distance_km: f64,
+        hours: f64,
+    },
+}
+
+/// One login attempt
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoginAttempt {
+    pub id: String,
+    pub username: String,
+    pub ip_address: IpAddr,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub two_factor_method: Option<TwoFactorMethod>,
+    pub failure_reason: Option<String>,
+    pub location: LoginLocation,
+    /// Filled in for successful logins when recorded
+    pub suspicious: Vec<SuspiciousLogin>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl LoginAttempt {
+    /// A successful attempt by `username` from the request's client
+    pub fn from_request(username: &str, headers: &HeaderMap) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            username: username.to_string(),
+            ip_address: extract_client_ip_with_default_config(headers),
+            user_agent: headers.get("user-agent").and_then(|v| v.to_str().ok()).map(str::to_string),
+            success: true,
+            two_factor_method: None,
+            failure_reason: None,
+            location: LoginLocation::from_headers(headers),
+            suspicious: Vec::new(),
+            attempted_at: Utc::now(),
+        }
+    }
+
+    /// Record the second factor used
+    pub fn with_two_factor(mut self, method: Option<TwoFactorMethod>) -> Self {
+        self.two_factor_method = method;
+        self
+    }
+
+    /// Mark the attempt failed
+    pub fn failed(mut self, reason: impl Into<String>) -> Self {
+        self.success = false;
+        self.failure_reason = Some(reason.into());
+        self
+    }
+}
+
+/// Stores login attempts
+#[async_trait]
+pub trait LoginHistoryStore: Send + Sync {
+    async fn record_login(&self, attempt: &LoginAttempt) -> Result<()>;
+
+    /// Newest attempts first
+    async fn login_history(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>>;
+
+    /// Newest successful attempts first
+    async fn successful_logins(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>>;
+}
+
+fn attempt_from_row(row: &tokio_postgres::Row) -> Result<LoginAttempt> {
+    let method: Option<String> = row.get("two_factor_method");
+    Ok(LoginAttempt {
+        id: row.get("attempt_id"),
+        username: row.get("username"),
+        ip_address: row.get("ip_address"),
+        user_agent: row.get("user_agent"),
+        success: row.get("success"),
+        two_factor_method: match method.as_deref() {
+            Some("totp") => Some(TwoFactorMethod::Totp),
+            Some("backup_code") => Some(TwoFactorMethod::BackupCode),
+            _ => None,
+        },
+        failure_reason: row.get("failure_reason"),
+        location: LoginLocation {
+            country: row.get("country"),
+            latitude: row.get("latitude"),
+            longitude: row.get("longitude"),
+        },
+        suspicious: serde_json::from_value(row.get("suspicious")).context("Invalid suspicious login flags")?,
+        attempted_at: row.get("attempted_at"),
+    })
+}
+
+#[async_trait]
+impl LoginHistoryStore for DatabaseManager {
+    async fn record_login(&self, attempt: &LoginAttempt) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO login_history (attempt_id, username, ip_address, user_agent, success, two_factor_method, failure_reason, country, latitude, longitude, suspicious, attempted_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+            &[
+                &attempt.id,
+                &attempt.username,
+                &attempt.ip_address,
+                &attempt.user_agent,
+                &attempt.success,
+                &attempt.two_factor_method.map(TwoFactorMethod::as_str),
+                &attempt.failure_reason,
+                &attempt.location.country,
+                &attempt.location.latitude,
+                &attempt.location.longitude,
+                &serde_json::to_value(&attempt.suspicious)?,
+                &attempt.attempted_at,
+            ],
+        )
+        .await
+        .context("Failed to record login attempt")?;
+        Ok(())
+    }
+
+    async fn login_history(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT * FROM login_history WHERE username = $1 ORDER BY attempted_at DESC LIMIT $2",
+                &[&username, &limit],
+            )
+            .await?;
+        rows.iter().map(attempt_from_row).collect()
+    }
+
+    async fn successful_logins(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT * FROM login_history WHERE username = $1 AND success ORDER BY attempted_at DESC LIMIT $2",
+                &[&username, &limit],
+            )
+            .await?;
+        rows.iter().map(attempt_from_row).collect()
+    }
+}
+
+/// Records login attempts and alerts on suspicious ones
+pub struct LoginMonitor {
+    store: Arc<dyn LoginHistoryStore>,
+    alerts: Option<Arc<AlertManager>>,
+}
+
+impl LoginMonitor {
+    pub fn new(store: Arc<dyn LoginHistoryStore>) -> Self {
+        Self { store, alerts: None }
+    }
+
+    /// Raise suspicious login alerts
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Record an attempt, checking successful ones against the user's earlier logins
+    pub async fn record(&self, mut attempt: LoginAttempt) -> Result<LoginAttempt> {
+        if attempt.success {
+            let previous = self.store.successful_logins(&attempt.username, HISTORY_WINDOW).await?;
+            attempt.suspicious = detect_suspicious(&attempt, &previous);
+        }
+        self.store.record_login(&attempt).await?;
+
+        if !attempt.suspicious.is_empty() {
+            warn!("Suspicious login for '{}' from {}: {:?}", attempt.username, attempt.ip_address, attempt.suspicious);
+            self.alert(&attempt).await?;
+        }
+        Ok(attempt)
+    }
+
+    /// A user's attempts, newest first
+    pub async fn history(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>> {
+        self.store.login_history(username, limit).await
+    }
+
+    /// Trigger the suspicious login rule, creating it on first use
+    async fn alert(&self, attempt: &LoginAttempt) -> Result<()> {
+        let alerts = match &self.alerts {
+            Some(alerts) => alerts,
+            None => return Ok(()),
+        };
+        if !alerts.get_rules().await.iter().any(|r| r.id == SUSPICIOUS_LOGIN_ALERT_RULE) {
+            let channels = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                SUSPICIOUS_LOGIN_ALERT_RULE,
+                "Suspicious admin login",
+                AlertCondition::Custom { message: "Admin login from an unusual location".to_string() },
+                AlertLevel::Warning,
+                channels,
+            ).with_cooldown(0)).await;
+        }
+        alerts.trigger_alert(SUSPICIOUS_LOGIN_ALERT_RULE, serde_json::json!({
+            "username": attempt.username,
+            "ip_address": attempt.ip_address,
+            "user_agent": attempt.user_agent,
+            "country": attempt.location.country,
+            "suspicious": attempt.suspicious,
+        })).await
+    }
+}
+
+/// Compare a successful login with earlier ones (newest first)
+///
+/// A user's first login sets the baseline and is never flagged.
+pub fn detect_suspicious(attempt: &LoginAttempt, previous: &[LoginAttempt]) -> Vec<SuspiciousLogin> {
+    let mut flags = Vec::new();
+    let last = match previous.first() {
+        Some(last) => last,
+        None => return flags,
+    };
+
+    // Only compare countries once the proxy has reported one before
+    if let Some(country) = &attempt.location.country {
+        let known: Vec<_> = previous.iter().filter_map(|p| p.location.country.as_ref()).collect();
+        if !known.is_empty() && !known.contains(&country) {
+            flags.push(SuspiciousLogin::NewCountry { country: country.clone() });
+        }
+    }
+
+    let range = ip_range(attempt.ip_address);
+    if !previous.iter().any(|p| ip_range(p.ip_address) == range) {
+        flags.push(SuspiciousLogin::NewIpRange { range });
+    }
+
+    if let (Some(from), Some(to)) = (last.location.coordinates(), attempt.location.coordinates()) {
+        let distance_km = distance_km(from, to);
+        let hours = (attempt.attempted_at - last.attempted_at).num_seconds().max(0) as f64 / 3600.0;
+        if distance_km >= MIN_TRAVEL_DISTANCE_KM && distance_km > hours * MAX_TRAVEL_SPEED_KMH {
+            flags.push(SuspiciousLogin::ImpossibleTravel {
+                from_ip: last.ip_address,
+                distance_km: distance_km.round(),
+                hours: (hours * 100.0).round() / 100.0,
+            });
+        }
+    }
+    flags
+}
+
+/// The /24 (IPv4) or /48 (IPv6) network an address belongs to
+fn ip_range(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+    }
+}
+
+/// Great-circle distance between two (latitude, longitude) points
+fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tokio::sync::RwLock;
+
+    #[derive(Default)]
+    struct MemoryHistory(RwLock<Vec<LoginAttempt>>);
+
+    #[async_trait]
+    impl LoginHistoryStore for MemoryHistory {
+        async fn record_login(&self, attempt: &LoginAttempt) -> Result<()> {
+            self.0.write().await.insert(0, attempt.clone());
+            Ok(())
+        }
+
+        async fn login_history(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>> {
+            Ok(self.0.read().await.iter().filter(|a| a.username == username).take(limit as usize).cloned().collect())
+        }
+
+        async fn successful_logins(&self, username: &str, limit: i64) -> Result<Vec<LoginAttempt>> {
+            Ok(self.0.read().await.iter().filter(|a| a.username == username && a.success).take(limit as usize).cloned().collect())
+        }
+    }
+
+    fn attempt(ip: &str, country: &str, coordinates: (f64, f64), at: DateTime<Utc>) -> LoginAttempt {
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-connecting-ip", ip.parse().unwrap());
+        headers.insert("cf-ipcountry", country.parse().unwrap());
+        headers.insert("cf-iplatitude", coordinates.0.to_string().parse().unwrap());
+        headers.insert("cf-iplongitude", coordinates.1.to_string().parse().unwrap());
+        LoginAttempt { attempted_at: at, ..LoginAttempt::from_request("alice", &headers) }
+    }
+
+    const BERLIN: (f64, f64) = (52.52, 13.40);
+    const NEW_YORK: (f64, f64) = (40.71, -74.01);
+
+    #[tokio::test]
+    async fn test_new_country_and_ip_range() {
+        let alerts = Arc::new(AlertManager::default());
+        let monitor = LoginMonitor::new(Arc::new(MemoryHistory::default())).with_alerts(alerts.clone());
+        let start = Utc::now() - Duration::days(30);
+
+        // The first login is the baseline, later ones from the same network are fine
+        let first = monitor.record(attempt("203.0.113.10", "DE", BERLIN, start)).await.unwrap();
+        assert!(first.suspicious.is_empty());
+        assert_eq!(first.location.country.as_deref(), Some("DE"));
+        let same_range = monitor.record(attempt("203.0.113.99", "de", BERLIN, start + Duration::days(1))).await.unwrap();
+        assert!(same_range.suspicious.is_empty());
+
+        // Failed attempts are recorded but never flagged
+        let failed = monitor.record(attempt("198.51.100.7", "US", NEW_YORK, start + Duration::days(2)).failed("invalid credentials")).await.unwrap();
+        assert!(failed.suspicious.is_empty());
+
+        // A week later from another country is a new country and range, not impossible travel
+        let abroad = monitor.record(attempt("198.51.100.7", "US", NEW_YORK, start + Duration::days(8))).await.unwrap();
+        assert_eq!(abroad.suspicious, vec![
+            SuspiciousLogin::NewCountry { country: "US".to_string() },
+            SuspiciousLogin::NewIpRange { range: "198.51.100.0/24".to_string() },
+        ]);
+        assert_eq!(alerts.get_history(None).await.len(), 1);
+        assert_eq!(monitor.history("alice", 10).await.unwrap().len(), 4);
+        assert_eq!(ip_range("2001:db8:1234:5678::1".parse().unwrap()), "2001:db8:1234::/48");
+    }
+
+    #[test]
+    fn test_impossible_travel() {
+        let start = Utc::now();
+        let berlin = attempt("203.0.113.10", "DE", BERLIN, start);
+
+        // Berlin to New York (about 6400 km) in two hours
+        let flags = detect_suspicious(&attempt("203.0.113.11", "DE", NEW_YORK, start + Duration::hours(2)), &[berlin.clone()]);
+        assert!(matches!(flags.as_slice(), [SuspiciousLogin::ImpossibleTravel { distance_km, .. }] if (6300.0..6500.0).contains(distance_km)));
+
+        // Twelve hours is enough for a flight, and nearby logins are never impossible
+        assert!(detect_suspicious(&attempt("203.0.113.11", "DE", NEW_YORK, start + Duration::hours(12)), &[berlin.clone()]).is_empty());
+        assert!(detect_suspicious(&attempt("203.0.113.11", "DE", (52.40, 13.05), start), &[berlin]).is_empty());
+
+        // Without proxy headers there is no location to compare
+        let location = LoginLocation::from_headers(&HeaderMap::new());
+        assert_eq!(location, LoginLocation::default());
+    }
+}
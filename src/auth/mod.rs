@@ -1,5 +1,20 @@
 // Authentication and Authorization module for DMPool Admin
 // JWT-based authentication with bcrypt password hashing
+// Admin users can be persisted to a JSON file and managed (roles, password
+// resets, disabling) at runtime; repeated failed logins require a challenge
+
+pub mod challenge;
+pub mod login_history;
+pub mod oidc;
+
+pub use login_history::{
+    detect_suspicious, LoginAttempt, LoginHistoryStore, LoginLocation, LoginMonitor, SuspiciousLogin,
+    TwoFactorMethod, SUSPICIOUS_LOGIN_ALERT_RULE,
+};
+pub use challenge::{
+    ChallengeProvider, ChallengeSolution, ChallengeVerifier, LoginChallenge, LoginChallengeConfig, LoginChallenges,
+};
+pub use oidc::{OidcClient, OidcIdentity, OidcSettings};
 
 use anyhow::{Context, Result};
 use axum::{
@@ -8,8 +23,11 @@ use axum::{
     response::{IntoResponse, Json},
 };
 use chrono::{Duration, Utc};
+use crate::error::AuthError;
+use crate::persist;
 use jsonwebtoken::{DecodingKey, EncodingKey};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
@@ -18,6 +36,9 @@ use tracing::{error, info, warn};
 const MIN_PASSWORD_LENGTH: usize = 12;
 const MAX_PASSWORD_LENGTH: usize = 128;
 
+/// Roles that can be assigned to admin users
+pub const ROLES: [&str; 4] = ["admin", "operator", "payout", "viewer"];
+
 /// Password validation result
 #[derive(Debug, Clone)]
 pub struct PasswordValidation {
@@ -121,6 +142,41 @@ pub struct User {
     pub role: String,
     pub created_at: i64,
     pub last_login: Option<i64>,
+    /// Disabled users cannot log in and their tokens are rejected
+    #[serde(default)]
+    pub disabled: bool,
+    /// Set by an admin password reset; only the password change endpoint is allowed
+    #[serde(default)]
+    pub must_change_password: bool,
+    /// OIDC issuer for users provisioned through single sign-on; they have no password
+    #[serde(default)]
+    pub identity_provider: Option<String>,
+}
+
+/// User as shown by the user management API (no password hash)
+#[derive(Clone, Debug, Serialize)]
+pub struct UserSummary {
+    pub username: String,
+    pub role: String,
+    pub created_at: i64,
+    pub last_login: Option<i64>,
+    pub disabled: bool,
+    pub must_change_password: bool,
+    pub identity_provider: Option<String>,
+}
+
+impl From<&User> for UserSummary {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            role: user.role.clone(),
+            created_at: user.created_at,
+            last_login: user.last_login,
+            disabled: user.disabled,
+            must_change_password: user.must_change_password,
+            identity_provider: user.identity_provider.clone(),
+        }
+    }
 }
 
 /// Login request
@@ -128,6 +184,9 @@ pub struct User {
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// Solved challenge, once failed logins require one
+    #[serde(default)]
+    pub challenge: Option<ChallengeSolution>,
 }
 
 /// Login response
@@ -149,6 +208,8 @@ pub struct UserInfo {
 pub struct AuthManager {
     secret: String,
     users: Arc<RwLock<Vec<User>>>,
+    /// JSON file users are saved to after every change
+    storage: Option<PathBuf>,
 }
 
 impl AuthManager {
@@ -156,7 +217,44 @@ impl AuthManager {
         Self {
             secret,
             users: Arc::new(RwLock::new(Vec::new())),
+            storage: None,
+        }
+    }
+
+    /// Persist users to a JSON file
+    pub fn with_storage(mut self, path: PathBuf) -> Self {
+        self.storage = Some(path);
+        self
+    }
+
+    /// Load users from storage, returning how many were loaded
+    pub async fn load(&self) -> Result<usize> {
+        let path = match &self.storage {
+            Some(path) => path,
+            None => return Ok(0),
+        };
+        let loaded: Vec<User> = match persist::read_json(path).await
+            .context("Failed to load users file")?
+        {
+            Some(loaded) => loaded,
+            None => return Ok(0),
+        };
+        let count = loaded.len();
+        *self.users.write().await = loaded;
+        Ok(count)
+    }
+
+    async fn save(&self, users: &[User]) -> Result<()> {
+        let path = match &self.storage {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await
+                .context("Failed to create users directory")?;
         }
+        persist::write_json(path, users).await
+            .context("Failed to write users file")
     }
 
     /// Initialize with default admin user
@@ -166,7 +264,7 @@ impl AuthManager {
         if !validation.is_valid {
             let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
             warn!("{}", error_msg);
-            return Err(anyhow::anyhow!(error_msg)).context("Invalid password");
+            return Err(AuthError::WeakPassword(validation.errors.join("; "))).context("Invalid password");
         }
 
         let mut users = self.users.write().await;
@@ -179,7 +277,7 @@ impl AuthManager {
 
         // Hash password
         let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
-            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+            .map_err(|e| AuthError::PasswordHash(e.to_string()))?;
 
         let user = User {
             username: username.to_string(),
@@ -187,32 +285,38 @@ impl AuthManager {
             role: "admin".to_string(),
             created_at: Utc::now().timestamp(),
             last_login: None,
+            disabled: false,
+            must_change_password: false,
+            identity_provider: None,
         };
 
         users.push(user);
+        self.save(&users).await?;
         info!("Created default admin user '{}'", username);
         Ok(())
     }
 
     /// Authenticate user
+    ///
+    /// Disabled users never authenticate.
     pub async fn authenticate(&self, username: &str, password: &str) -> Result<Option<User>> {
-        let users = self.users.read().await;
-
-        if let Some(user) = users.iter().find(|u| u.username == username) {
-            let is_valid = bcrypt::verify(password, &user.password_hash)
-                .unwrap_or(false);
+        let mut users = self.users.write().await;
 
-            if is_valid {
-                // Update last login
-                let mut users = self.users.write().await;
-                if let Some(u) = users.iter_mut().find(|u| u.username == username) {
-                    u.last_login = Some(Utc::now().timestamp());
-                }
-                return Ok(Some(user.clone()));
-            }
+        let user = match users.iter_mut().find(|u| u.username == username && !u.disabled) {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        if !bcrypt::verify(password, &user.password_hash).unwrap_or(false) {
+            return Ok(None);
         }
 
-        Ok(None)
+        // Update last login
+        user.last_login = Some(Utc::now().timestamp());
+        let user = user.clone();
+        if let Err(e) = self.save(&users).await {
+            warn!("Failed to save last login for '{}': {}", username, e);
+        }
+        Ok(Some(user))
     }
 
     /// Generate JWT token
@@ -232,7 +336,7 @@ impl AuthManager {
 
         let encoding_key = EncodingKey::from_secret(self.secret.as_ref());
         let token = jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &encoding_key)
-            .map_err(|e| anyhow::anyhow!("Failed to encode token: {}", e))?;
+            .map_err(|e| AuthError::TokenEncoding(e.to_string()))?;
 
         Ok(token)
     }
@@ -242,23 +346,18 @@ impl AuthManager {
         let decoding_key = DecodingKey::from_secret(self.secret.as_ref());
         let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
         let decoded = jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation)
-            .map_err(|e| anyhow::anyhow!("Invalid token: {}", e))?;
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))?;
 
         Ok(decoded.claims)
     }
 
     /// Create user
     pub async fn create_user(&self, username: &str, password: &str, role: &str) -> Result<()> {
-        // Validate password strength
-        let validation = validate_password_strength(password);
-        if !validation.is_valid {
-            let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
-            warn!("{}", error_msg);
-            return Err(anyhow::anyhow!(error_msg)).context("Invalid password");
+        validate_role(role)?;
+        if username.trim().is_empty() {
+            return Err(crate::error::DmpoolError::InvalidInput("Username must not be empty".to_string()).into());
         }
-
-        let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
-            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+        let password_hash = hash_password(password)?;
 
         let user = User {
             username: username.to_string(),
@@ -266,10 +365,17 @@ impl AuthManager {
             role: role.to_string(),
             created_at: Utc::now().timestamp(),
             last_login: None,
+            disabled: false,
+            must_change_password: false,
+            identity_provider: None,
         };
 
         let mut users = self.users.write().await;
+        if users.iter().any(|u| u.username == username) {
+            return Err(AuthError::UserExists(username.to_string()).into());
+        }
         users.push(user);
+        self.save(&users).await?;
         info!("Created user '{}' with role '{}'", username, role);
         Ok(())
     }
@@ -279,6 +385,165 @@ impl AuthManager {
         let users = self.users.read().await;
         users.iter().find(|u| u.username == username).cloned()
     }
+
+    /// All users, oldest first
+    pub async fn list_users(&self) -> Vec<UserSummary> {
+        self.users.read().await.iter().map(UserSummary::from).collect()
+    }
+
+    /// An enabled user by name, used to re-check tokens on every request
+    pub async fn active_user(&self, username: &str) -> Option<UserSummary> {
+        let users = self.users.read().await;
+        users.iter().find(|u| u.username == username && !u.disabled).map(UserSummary::from)
+    }
+
+    /// Change a user's role
+    pub async fn set_role(&self, username: &str, role: &str) -> Result<UserSummary> {
+        validate_role(role)?;
+        self.update_user(username, |user| user.role = role.to_string()).await
+    }
+
+    /// Replace a user's password with a temporary one they must change at next login
+    pub async fn reset_password(&self, username: &str, temporary_password: &str) -> Result<UserSummary> {
+        if let Some(issuer) = self.get_user(username).await.and_then(|u| u.identity_provider) {
+            return Err(crate::error::DmpoolError::InvalidInput(format!("User {} signs in through {}", username, issuer)).into());
+        }
+        let password_hash = hash_password(temporary_password)?;
+        self.update_user(username, |user| {
+            user.password_hash = password_hash;
+            user.must_change_password = true;
+        }).await
+    }
+
+    /// Change one's own password, clearing a forced reset
+    pub async fn change_password(&self, username: &str, current_password: &str, new_password: &str) -> Result<()> {
+        let user = self.get_user(username).await
+            .ok_or_else(|| AuthError::UserNotFound(username.to_string()))?;
+        if !bcrypt::verify(current_password, &user.password_hash).unwrap_or(false) {
+            return Err(crate::error::DmpoolError::InvalidInput("Current password is incorrect".to_string()).into());
+        }
+        if current_password == new_password {
+            return Err(AuthError::WeakPassword("New password must differ from the current one".to_string()).into());
+        }
+        let password_hash = hash_password(new_password)?;
+        self.update_user(username, |user| {
+            user.password_hash = password_hash;
+            user.must_change_password = false;
+        }).await?;
+        Ok(())
+    }
+
+    /// Disable or re-enable a user
+    pub async fn set_disabled(&self, username: &str, disabled: bool) -> Result<UserSummary> {
+        self.update_user(username, |user| user.disabled = disabled).await
+    }
+
+    /// Delete a user
+    pub async fn delete_user(&self, username: &str) -> Result<()> {
+        let mut users = self.users.write().await;
+        let index = users.iter().position(|u| u.username == username)
+            .ok_or_else(|| AuthError::UserNotFound(username.to_string()))?;
+        let removed = users.remove(index);
+        if !has_enabled_admin(&users) {
+            users.insert(index, removed);
+            return Err(AuthError::LastAdmin.into());
+        }
+        self.save(&users).await?;
+        info!("Deleted user '{}'", username);
+        Ok(())
+    }
+
+    /// Sign in a user authenticated by an OIDC issuer
+    ///
+    /// Unknown users are created with `role` when `provision` is set. Existing
+    /// users take the role their identity provider maps them to, unless that
+    /// would leave no enabled admin. Local accounts can't be taken over.
+    pub async fn federated_login(&self, issuer: &str, username: &str, role: &str, provision: bool) -> Result<User> {
+        validate_role(role)?;
+
+        let existing = self.get_user(username).await;
+        match existing {
+            Some(user) if user.identity_provider.as_deref() != Some(issuer) => {
+                return Err(AuthError::AccessDenied(format!("{} is not an account of {}", username, issuer)).into());
+            }
+            Some(user) if user.disabled => {
+                return Err(AuthError::AccessDenied(format!("User {} is disabled", username)).into());
+            }
+            Some(user) if user.role != role => {
+                if let Err(e) = self.set_role(username, role).await {
+                    warn!("Keeping role '{}' for '{}': {}", user.role, username, e);
+                } else {
+                    info!("Updated role of '{}' from '{}' to '{}' from {}", username, user.role, role, issuer);
+                }
+            }
+            Some(_) => {}
+            None if provision => {
+                let mut users = self.users.write().await;
+                users.push(User {
+                    username: username.to_string(),
+                    // No bcrypt hash verifies against an empty string, so password login is impossible
+                    password_hash: String::new(),
+                    role: role.to_string(),
+                    created_at: Utc::now().timestamp(),
+                    last_login: None,
+                    disabled: false,
+                    must_change_password: false,
+                    identity_provider: Some(issuer.to_string()),
+                });
+                self.save(&users).await?;
+                info!("Provisioned user '{}' with role '{}' from {}", username, role, issuer);
+            }
+            None => {
+                return Err(AuthError::AccessDenied(format!("User {} is not provisioned", username)).into());
+            }
+        }
+
+        let mut users = self.users.write().await;
+        let user = users.iter_mut().find(|u| u.username == username)
+            .ok_or_else(|| AuthError::UserNotFound(username.to_string()))?;
+        user.last_login = Some(Utc::now().timestamp());
+        let user = user.clone();
+        self.save(&users).await?;
+        Ok(user)
+    }
+
+    /// Apply a change to one user, refusing changes that leave no enabled admin
+    async fn update_user(&self, username: &str, change: impl FnOnce(&mut User)) -> Result<UserSummary> {
+        let mut users = self.users.write().await;
+        let index = users.iter().position(|u| u.username == username)
+            .ok_or_else(|| AuthError::UserNotFound(username.to_string()))?;
+        let previous = users[index].clone();
+        change(&mut users[index]);
+        if !has_enabled_admin(&users) {
+            users[index] = previous;
+            return Err(AuthError::LastAdmin.into());
+        }
+        self.save(&users).await?;
+        Ok(UserSummary::from(&users[index]))
+    }
+}
+
+fn validate_role(role: &str) -> Result<()> {
+    if !ROLES.contains(&role) {
+        return Err(AuthError::InvalidRole(format!("{} (expected one of {})", role, ROLES.join(", "))).into());
+    }
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let validation = validate_password_strength(password);
+    if !validation.is_valid {
+        let error_msg = format!("Password validation failed: {}", validation.errors.join("; "));
+        warn!("{}", error_msg);
+        return Err(AuthError::WeakPassword(validation.errors.join("; "))).context("Invalid password");
+    }
+    let hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AuthError::PasswordHash(e.to_string()))?;
+    Ok(hash)
+}
+
+fn has_enabled_admin(users: &[User]) -> bool {
+    users.iter().any(|u| u.role == "admin" && !u.disabled)
 }
 
 /// Authenticated user extractor
@@ -407,6 +672,9 @@ mod tests {
             role: "user".to_string(),
             created_at: 0,
             last_login: None,
+            disabled: false,
+            must_change_password: false,
+            identity_provider: None,
         };
 
         let token = auth.generate_token(&user).unwrap();
@@ -415,4 +683,78 @@ mod tests {
         assert_eq!(claims.name, "test");
         assert_eq!(claims.role, "user");
     }
+
+    #[tokio::test]
+    async fn test_user_management() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("users.json");
+        let auth = AuthManager::new("secret".to_string()).with_storage(path.clone());
+        auth.init_default_admin("admin", "Adm1n!Password#").await.unwrap();
+
+        auth.create_user("carol", "Car0l!Password#", "operator").await.unwrap();
+        assert!(auth.create_user("carol", "Car0l!Password#", "operator").await.is_err());
+        assert!(auth.create_user("dave", "Dav3!Password#x", "root").await.is_err());
+
+        assert_eq!(auth.set_role("carol", "payout").await.unwrap().role, "payout");
+        auth.set_disabled("carol", true).await.unwrap();
+        assert!(auth.authenticate("carol", "Car0l!Password#").await.unwrap().is_none());
+        assert!(auth.active_user("carol").await.is_none());
+        auth.set_disabled("carol", false).await.unwrap();
+        let user = auth.authenticate("carol", "Car0l!Password#").await.unwrap().unwrap();
+        assert!(user.last_login.is_some());
+
+        // A reset forces a password change, which clears the flag
+        assert!(auth.reset_password("carol", "Temp0rary!Pass#").await.unwrap().must_change_password);
+        assert!(auth.change_password("carol", "wrong", "N3w!Password#xy").await.is_err());
+        auth.change_password("carol", "Temp0rary!Pass#", "N3w!Password#xy").await.unwrap();
+        assert!(!auth.active_user("carol").await.unwrap().must_change_password);
+
+        // Users survive a restart
+        let reloaded = AuthManager::new("secret".to_string()).with_storage(path);
+        assert_eq!(reloaded.load().await.unwrap(), 2);
+        assert!(reloaded.authenticate("carol", "N3w!Password#xy").await.unwrap().is_some());
+        reloaded.delete_user("carol").await.unwrap();
+        assert!(reloaded.delete_user("carol").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_last_admin_is_protected() {
+        let auth = AuthManager::new("secret".to_string());
+        auth.init_default_admin("admin", "Adm1n!Password#").await.unwrap();
+
+        let is_last_admin = |e: anyhow::Error| matches!(e.downcast_ref::<AuthError>(), Some(AuthError::LastAdmin));
+        assert!(is_last_admin(auth.set_role("admin", "viewer").await.unwrap_err()));
+        assert!(is_last_admin(auth.set_disabled("admin", true).await.unwrap_err()));
+        assert!(is_last_admin(auth.delete_user("admin").await.unwrap_err()));
+        assert_eq!(auth.get_user("admin").await.unwrap().role, "admin");
+
+        // With a second admin the first can be demoted
+        auth.create_user("root", "R00t!Password#x", "admin").await.unwrap();
+        auth.set_role("admin", "viewer").await.unwrap();
+        assert!(auth.delete_user("root").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_federated_login() {
+        let auth = AuthManager::new("secret".to_string());
+        auth.init_default_admin("admin", "Adm1n!Password#").await.unwrap();
+        let issuer = "https://sso.example.com";
+
+        assert!(auth.federated_login(issuer, "erin", "operator", false).await.is_err());
+        let user = auth.federated_login(issuer, "erin", "operator", true).await.unwrap();
+        assert_eq!(user.identity_provider.as_deref(), Some(issuer));
+        assert!(user.last_login.is_some());
+        // Provisioned users have no password and can't be given one
+        assert!(auth.authenticate("erin", "").await.unwrap().is_none());
+        assert!(auth.reset_password("erin", "Temp0rary!Pass#").await.is_err());
+
+        // The identity provider's role applies on every login
+        assert_eq!(auth.federated_login(issuer, "erin", "viewer", true).await.unwrap().role, "viewer");
+
+        // Local accounts and disabled users are refused
+        assert!(auth.federated_login(issuer, "admin", "admin", true).await.is_err());
+        assert!(auth.federated_login("https://other.example.com", "erin", "viewer", true).await.is_err());
+        auth.set_disabled("erin", true).await.unwrap();
+        assert!(auth.federated_login(issuer, "erin", "viewer", true).await.is_err());
+    }
 }
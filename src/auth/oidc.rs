@@ -0,0 +1,384 @@
+// OpenID Connect single sign-on
+//
+// Authorization code flow with PKCE against a configured issuer. The
+// provider's endpoints are discovered from its openid-configuration, the ID
+// token is verified against its published keys, and a claim (e.g. groups) is
+// mapped to one of the admin roles. The caller then issues the usual admin
+// JWT for the federated user.
+
+use super::ROLES;
+use crate::error::{AuthError, DmpoolError};
+use anyhow::{Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// How long a started login may take to come back through the callback
+const PENDING_LOGIN_MINUTES: i64 = 10;
+
+/// OIDC client settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OidcSettings {
+    /// Issuer URL; endpoints are discovered from `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// Confidential clients only; public clients rely on PKCE alone
+    pub client_secret: Option<String>,
+    /// Must point at /api/auth/oidc/callback and be registered with the provider
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    /// Claim holding the admin username
+    pub username_claim: String,
+    /// Claim (string or list of strings) mapped to a role
+    pub role_claim: String,
+    /// Claim value to role; the most privileged matching role wins
+    pub role_mapping: HashMap<String, String>,
+    /// Role for users no mapping matches; None refuses them
+    pub default_role: Option<String>,
+    /// Create users on their first login
+    pub auto_provision: bool,
+}
+
+impl OidcSettings {
+    /// Settings from OIDC_* environment variables, None when OIDC_ISSUER is unset
+    ///
+    /// OIDC_ROLE_MAPPING is a comma separated list of `value=role` pairs,
+    /// e.g. `pool-admins=admin,pool-ops=operator`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let issuer = match std::env::var("OIDC_ISSUER") {
+            Ok(issuer) => issuer,
+            Err(_) => return Ok(None),
+        };
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let settings = Self {
+            issuer,
+            client_id: var("OIDC_CLIENT_ID").context("OIDC_CLIENT_ID must be set with OIDC_ISSUER")?,
+            client_secret: var("OIDC_CLIENT_SECRET"),
+            redirect_uri: var("OIDC_REDIRECT_URI").context("OIDC_REDIRECT_URI must be set with OIDC_ISSUER")?,
+            scopes: var("OIDC_SCOPES")
+                .map(|s| s.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_else(|| vec!["openid".to_string(), "profile".to_string(), "email".to_string()]),
+            username_claim: var("OIDC_USERNAME_CLAIM").unwrap_or_else(|| "preferred_username".to_string()),
+            role_claim: var("OIDC_ROLE_CLAIM").unwrap_or_else(|| "groups".to_string()),
+            role_mapping: parse_role_mapping(&var("OIDC_ROLE_MAPPING").unwrap_or_default())?,
+            default_role: var("OIDC_DEFAULT_ROLE"),
+            auto_provision: var("OIDC_AUTO_PROVISION").map(|v| v != "false").unwrap_or(true),
+        };
+        settings.validate()?;
+        Ok(Some(settings))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for role in self.role_mapping.values().chain(&self.default_role) {
+            if !ROLES.contains(&role.as_str()) {
+                return Err(AuthError::InvalidRole(role.clone()).into());
+            }
+        }
+        if !self.scopes.iter().any(|s| s == "openid") {
+            return Err(DmpoolError::InvalidInput("OIDC scopes must include openid".to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Role for a set of ID token claims
+    pub fn map_role(&self, claims: &serde_json::Value) -> Option<String> {
+        let values: Vec<&str> = match &claims[self.role_claim.as_str()] {
+            serde_json::Value::String(value) => vec![value.as_str()],
+            serde_json::Value::Array(values) => values.iter().filter_map(|v| v.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        // ROLES is ordered from most to least privileged
+        ROLES.iter()
+            .find(|role| values.iter().any(|v| self.role_mapping.get(*v).map(String::as_str) == Some(**role)))
+            .map(|role| role.to_string())
+            .or_else(|| self.default_role.clone())
+    }
+}
+
+/// Parse `value=role,value=role`
+pub fn parse_role_mapping(mapping: &str) -> Result<HashMap<String, String>> {
+    mapping.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (value, role) = pair.split_once('=')
+                .ok_or_else(|| DmpoolError::InvalidInput(format!("Role mapping '{}' must look like value=role", pair)))?;
+            Ok((value.trim().to_string(), role.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Endpoints from the provider's discovery document
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// A login waiting for its callback
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    started_at: DateTime<Utc>,
+}
+
+/// A user authenticated by the identity provider
+#[derive(Clone, Debug, Serialize)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub username: String,
+    pub role: String,
+}
+
+/// OIDC relying party
+pub struct OidcClient {
+    settings: OidcSettings,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    keys: RwLock<Option<JwkSet>>,
+    /// Pending logins by state parameter
+    pending: RwLock<HashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    pub fn new(settings: OidcSettings) -> Result<Self> {
+        settings.validate()?;
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .context("Failed to build OIDC HTTP client")?;
+        Ok(Self {
+            settings,
+            http,
+            metadata: RwLock::new(None),
+            keys: RwLock::new(None),
+            pending: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.settings.issuer
+    }
+
+    /// Whether unknown users are created on their first login
+    pub fn auto_provision(&self) -> bool {
+        self.settings.auto_provision
+    }
+
+    /// Start a login: the URL to send the browser to
+    pub async fn authorization_url(&self) -> Result<String> {
+        let metadata = self.metadata().await?;
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+
+        let url = reqwest::Url::parse_with_params(&metadata.authorization_endpoint, &[
+            ("response_type", "code"),
+            ("client_id", self.settings.client_id.as_str()),
+            ("redirect_uri", self.settings.redirect_uri.as_str()),
+            ("scope", self.settings.scopes.join(" ").as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+            ("code_challenge", pkce_challenge(&code_verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ]).context("Invalid authorization endpoint")?;
+
+        let mut pending = self.pending.write().await;
+        let cutoff = Utc::now() - Duration::minutes(PENDING_LOGIN_MINUTES);
+        pending.retain(|_, login| login.started_at > cutoff);
+        pending.insert(state, PendingLogin { code_verifier, nonce, started_at: Utc::now() });
+        Ok(url.to_string())
+    }
+
+    /// Finish a login from the callback's code and state
+    pub async fn complete(&self, code: &str, state: &str) -> Result<OidcIdentity> {
+        let login = self.pending.write().await.remove(state)
+            .filter(|login| login.started_at > Utc::now() - Duration::minutes(PENDING_LOGIN_MINUTES))
+            .ok_or_else(|| AuthError::InvalidToken("Unknown or expired login state".to_string()))?;
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.settings.redirect_uri.as_str()),
+            ("client_id", self.settings.client_id.as_str()),
+            ("code_verifier", login.code_verifier.as_str()),
+        ];
+        if let Some(secret) = &self.settings.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let response = self.http.post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .context("Failed to reach the OIDC token endpoint")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AuthError::InvalidToken(format!("Token endpoint returned {}: {}", status, body)).into());
+        }
+        let tokens: TokenResponse = response.json().await.context("Invalid token response")?;
+
+        let claims = self.verify_id_token(&tokens.id_token, &metadata).await?;
+        if claims["nonce"].as_str() != Some(login.nonce.as_str()) {
+            return Err(AuthError::InvalidToken("ID token nonce does not match".to_string()).into());
+        }
+
+        let username = claims[self.settings.username_claim.as_str()].as_str()
+            .filter(|u| !u.is_empty())
+            .ok_or_else(|| AuthError::InvalidToken(format!("ID token has no {} claim", self.settings.username_claim)))?;
+        let role = self.settings.map_role(&claims)
+            .ok_or_else(|| AuthError::AccessDenied(format!("No role is mapped for {}", username)))?;
+        Ok(OidcIdentity {
+            issuer: metadata.issuer.clone(),
+            subject: claims["sub"].as_str().unwrap_or_default().to_string(),
+            username: username.to_string(),
+            role,
+        })
+    }
+
+    /// Verify an ID token's signature, issuer, audience and expiry
+    async fn verify_id_token(&self, id_token: &str, metadata: &ProviderMetadata) -> Result<serde_json::Value> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|e| AuthError::InvalidToken(format!("Malformed ID token: {}", e)))?;
+        // Shared-secret algorithms would let anyone holding the client secret forge tokens
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(AuthError::InvalidToken(format!("Unsupported ID token algorithm {:?}", header.alg)).into());
+        }
+
+        let key = match self.find_key(header.kid.as_deref(), metadata, false).await? {
+            Some(key) => key,
+            // The provider may have rotated its keys since they were fetched
+            None => self.find_key(header.kid.as_deref(), metadata, true).await?
+                .ok_or_else(|| AuthError::InvalidToken("ID token signed with an unknown key".to_string()))?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[metadata.issuer.as_str()]);
+        validation.set_audience(&[self.settings.client_id.as_str()]);
+        let token = jsonwebtoken::decode::<serde_json::Value>(id_token, &key, &validation)
+            .map_err(|e| AuthError::InvalidToken(format!("ID token rejected: {}", e)))?;
+        Ok(token.claims)
+    }
+
+    async fn find_key(&self, kid: Option<&str>, metadata: &ProviderMetadata, refresh: bool) -> Result<Option<DecodingKey>> {
+        if refresh || self.keys.read().await.is_none() {
+            let keys: JwkSet = self.http.get(&metadata.jwks_uri).send().await
+                .context("Failed to fetch OIDC signing keys")?
+                .json().await
+                .context("Invalid OIDC signing keys")?;
+            *self.keys.write().await = Some(keys);
+        }
+
+        let keys = self.keys.read().await;
+        let keys = match keys.as_ref() {
+            Some(keys) => keys,
+            None => return Ok(None),
+        };
+        let jwk = match kid {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => None,
+        };
+        jwk.map(|jwk| DecodingKey::from_jwk(jwk).context("Unusable OIDC signing key")).transpose()
+    }
+
+    /// Discover the provider's endpoints, once
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.settings.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = self.http.get(&url).send().await
+            .with_context(|| format!("Failed to reach OIDC discovery at {}", url))?
+            .json().await
+            .context("Invalid OIDC discovery document")?;
+        if metadata.issuer.trim_end_matches('/') != self.settings.issuer.trim_end_matches('/') {
+            return Err(anyhow::anyhow!("Discovery document is for issuer {}, expected {}", metadata.issuer, self.settings.issuer));
+        }
+
+        info!("Discovered OIDC provider {}", metadata.issuer);
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+/// PKCE S256 code challenge for a verifier
+fn pkce_challenge(code_verifier: &str) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// 32 random bytes, base64url encoded (state, nonce and PKCE verifier)
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(mapping: &str, default_role: Option<&str>) -> OidcSettings {
+        OidcSettings {
+            issuer: "https://sso.example.com".to_string(),
+            client_id: "dmpool-admin".to_string(),
+            client_secret: None,
+            redirect_uri: "https://pool.example.com/api/auth/oidc/callback".to_string(),
+            scopes: vec!["openid".to_string()],
+            username_claim: "preferred_username".to_string(),
+            role_claim: "groups".to_string(),
+            role_mapping: parse_role_mapping(mapping).unwrap(),
+            default_role: default_role.map(str::to_string),
+            auto_provision: true,
+        }
+    }
+
+    #[test]
+    fn test_role_mapping() {
+        let settings = settings("pool-admins=admin, pool-ops=operator,finance=payout", None);
+        assert!(settings.validate().is_ok());
+
+        // The most privileged matching role wins
+        assert_eq!(settings.map_role(&json!({"groups": ["pool-ops", "pool-admins"]})).as_deref(), Some("admin"));
+        assert_eq!(settings.map_role(&json!({"groups": "finance"})).as_deref(), Some("payout"));
+        assert_eq!(settings.map_role(&json!({"groups": ["engineering"]})), None);
+        assert_eq!(settings.map_role(&json!({})), None);
+
+        let with_default = self::settings("pool-admins=admin", Some("viewer"));
+        assert_eq!(with_default.map_role(&json!({"groups": ["engineering"]})).as_deref(), Some("viewer"));
+
+        assert!(parse_role_mapping("pool-admins").is_err());
+        assert!(self::settings("pool-admins=root", None).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pkce_and_pending_state() {
+        // RFC 7636 appendix B
+        assert_eq!(pkce_challenge("dBjftJeZ4CVP-mJ92IF3ZZTpnpFMb4VSEeSyx4Xj5hY"), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+        assert_ne!(random_token(), random_token());
+
+        // A callback with a state that was never issued is rejected before any request
+        let client = OidcClient::new(settings("", Some("viewer"))).unwrap();
+        let err = client.complete("code", "forged").await.unwrap_err();
+        assert!(err.to_string().contains("Unknown or expired login state"));
+    }
+}
@@ -0,0 +1,389 @@
+// Share Backfill Module for DMPool
+// Replays historical shares from the RocksDB store into the Postgres shares and rollup tables
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::db::DatabaseManager;
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use p2poolv2_lib::store::Store;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+/// Default number of shares written per batch
+const DEFAULT_BATCH_SIZE: usize = 5000;
+
+/// Default replay window (1 hour)
+const DEFAULT_WINDOW_SECS: u64 = 3600;
+
+/// Source of historical shares
+pub trait ShareSource: Send + Sync {
+    /// Fetch up to `limit` shares with n_time in [start, end]
+    fn fetch_shares(&self, start: u64, end: u64, limit: usize) -> Vec<SimplePplnsShare>;
+}
+
+impl ShareSource for Store {
+    fn fetch_shares(&self, start: u64, end: u64, limit: usize) -> Vec<SimplePplnsShare> {
+        self.get_pplns_shares_filtered(Some(limit), Some(start), Some(end))
+    }
+}
+
+/// Backfill request parameters
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackfillRequest {
+    /// Start of the replay range (unix seconds)
+    pub start_time: u64,
+    /// End of the replay range (unix seconds)
+    pub end_time: u64,
+    /// Maximum shares written per batch
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    /// Delete existing shares and rollups in the range before replaying
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Backfill job status
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BackfillStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Backfill job progress
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackfillProgress {
+    /// Job ID
+    pub id: String,
+    /// Current status
+    pub status: BackfillStatus,
+    /// Replay range start (unix seconds)
+    pub start_time: u64,
+    /// Replay range end (unix seconds)
+    pub end_time: u64,
+    /// Replay cursor (unix seconds)
+    pub cursor: u64,
+    /// Shares written to Postgres
+    pub shares_replayed: u64,
+    /// Batches written to Postgres
+    pub batches_completed: u64,
+    /// Windows skipped because they already contained shares
+    pub windows_skipped: u64,
+    /// Completion percentage (0-100)
+    pub percent: f64,
+    /// Job start time
+    pub started_at: DateTime<Utc>,
+    /// Job finish time
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Error message if failed
+    pub error: Option<String>,
+}
+
+impl BackfillProgress {
+    fn update_percent(&mut self) {
+        let total = self.end_time.saturating_sub(self.start_time).max(1);
+        let done = self.cursor.saturating_sub(self.start_time).min(total);
+        self.percent = done as f64 * 100.0 / total as f64;
+    }
+}
+
+/// Backfill manager
+pub struct BackfillManager {
+    /// Postgres database
+    db: Arc<DatabaseManager>,
+    /// Historical share source
+    source: Arc<dyn ShareSource>,
+    /// Progress of the current or last job
+    progress: Arc<RwLock<Option<BackfillProgress>>>,
+    /// Cancellation flag for the running job
+    cancel: Arc<AtomicBool>,
+}
+
+impl BackfillManager {
+    /// Create a new backfill manager
+    pub fn new(db: Arc<DatabaseManager>, source: Arc<dyn ShareSource>) -> Self {
+        Self {
+            db,
+            source,
+            progress: Arc::new(RwLock::new(None)),
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Get progress of the current or last job
+    pub async fn progress(&self) -> Option<BackfillProgress> {
+        self.progress.read().await.clone()
+    }
+
+    /// Request cancellation of the running job
+    pub async fn cancel(&self) -> bool {
+        let running = self.progress.read().await
+            .as_ref()
+            .map_or(false, |p| p.status == BackfillStatus::Running);
+        if running {
+            self.cancel.store(true, Ordering::SeqCst);
+        }
+        running
+    }
+
+    /// Start a backfill job in the background
+    pub async fn start(&self, request: BackfillRequest) -> Result<BackfillProgress> {
+        if request.end_time <= request.start_time {
+            return Err(anyhow::anyhow!("end_time must be after start_time"));
+        }
+
+        let progress = {
+            let mut current = self.progress.write().await;
+            if current.as_ref().map_or(false, |p| p.status == BackfillStatus::Running) {
+                return Err(anyhow::anyhow!("A backfill job is already running"));
+            }
+
+            let progress = BackfillProgress {
+                id: uuid::Uuid::new_v4().to_string(),
+                status: BackfillStatus::Running,
+                start_time: request.start_time,
+                end_time: request.end_time,
+                cursor: request.start_time,
+                shares_replayed: 0,
+                batches_completed: 0,
+                windows_skipped: 0,
+                percent: 0.0,
+                started_at: Utc::now(),
+                finished_at: None,
+                error: None,
+            };
+            *current = Some(progress.clone());
+            progress
+        };
+
+        self.cancel.store(false, Ordering::SeqCst);
+
+        let job = BackfillJob {
+            db: self.db.clone(),
+            source: self.source.clone(),
+            progress: self.progress.clone(),
+            cancel: self.cancel.clone(),
+            batch_size: request.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1),
+            overwrite: request.overwrite,
+        };
+
+        info!("Starting share backfill {} for range {}..{}", progress.id, request.start_time, request.end_time);
+
+        tokio::spawn(async move {
+            let result = job.run(request.start_time, request.end_time).await;
+            let mut guard = job.progress.write().await;
+            if let Some(p) = guard.as_mut() {
+                p.finished_at = Some(Utc::now());
+                match result {
+                    Ok(()) if job.cancel.load(Ordering::SeqCst) => {
+                        p.status = BackfillStatus::Cancelled;
+                        warn!("Share backfill {} cancelled at {}", p.id, p.cursor);
+                    }
+                    Ok(()) => {
+                        p.status = BackfillStatus::Completed;
+                        p.percent = 100.0;
+                        info!("Share backfill {} completed: {} shares in {} batches",
+                            p.id, p.shares_replayed, p.batches_completed);
+                    }
+                    Err(e) => {
+                        p.status = BackfillStatus::Failed;
+                        p.error = Some(e.to_string());
+                        error!("Share backfill {} failed: {}", p.id, e);
+                    }
+                }
+            }
+        });
+
+        Ok(progress)
+    }
+}
+
+/// A single running backfill job
+struct BackfillJob {
+    db: Arc<DatabaseManager>,
+    source: Arc<dyn ShareSource>,
+    progress: Arc<RwLock<Option<BackfillProgress>>>,
+    cancel: Arc<AtomicBool>,
+    batch_size: usize,
+    overwrite: bool,
+}
+
+impl BackfillJob {
+    async fn run(&self, start_time: u64, end_time: u64) -> Result<()> {
+        let conn = self.db.get_conn().await?;
+        conn.batch_execute(include_str!("../../migrations/002_share_rollups.sql"))
+            .await
+            .context("Failed to create share rollup tables")?;
+
+        if self.overwrite {
+            let start = to_datetime(start_time);
+            let end = to_datetime(end_time);
+            conn.execute("DELETE FROM shares WHERE created_at >= $1 AND created_at < $2", &[&start, &end])
+                .await
+                .context("Failed to clear existing shares")?;
+            conn.execute(
+                "DELETE FROM share_rollups_hourly WHERE hour >= date_trunc('hour', $1::timestamptz) AND hour < $2",
+                &[&start, &end],
+            )
+            .await
+            .context("Failed to clear existing rollups")?;
+        }
+        drop(conn);
+
+        let mut cursor = start_time;
+        let mut window = DEFAULT_WINDOW_SECS;
+
+        while cursor < end_time {
+            if self.cancel.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            let window_end = (cursor + window).min(end_time);
+            let shares = self.source.fetch_shares(cursor, window_end - 1, self.batch_size);
+
+            // A full batch may have been truncated; narrow the window and retry
+            if shares.len() >= self.batch_size && window_end - cursor > 1 {
+                window = ((window_end - cursor) / 2).max(1);
+                continue;
+            }
+
+            let written = self.write_batch(cursor, window_end, &shares).await?;
+
+            {
+                let mut guard = self.progress.write().await;
+                if let Some(p) = guard.as_mut() {
+                    p.cursor = window_end;
+                    match written {
+                        Some(count) => {
+                            p.shares_replayed += count;
+                            p.batches_completed += 1;
+                        }
+                        None => p.windows_skipped += 1,
+                    }
+                    p.update_percent();
+                }
+            }
+
+            cursor = window_end;
+            window = DEFAULT_WINDOW_SECS;
+        }
+
+        Ok(())
+    }
+
+    /// Write one window of shares; returns None if the window was skipped
+    async fn write_batch(&self, start: u64, end: u64, shares: &[SimplePplnsShare]) -> Result<Option<u64>> {
+        let mut conn = self.db.get_conn().await?;
+
+        if !self.overwrite {
+            let existing: i64 = conn
+                .query_one(
+                    "SELECT COUNT(*) FROM shares WHERE created_at >= $1 AND created_at < $2",
+                    &[&to_datetime(start), &to_datetime(end)],
+                )
+                .await?
+                .get(0);
+            if existing > 0 {
+                return Ok(None);
+            }
+        }
+
+        let mut addresses = Vec::with_capacity(shares.len());
+        let mut difficulties = Vec::with_capacity(shares.len());
+        let mut times = Vec::with_capacity(shares.len());
+        for share in shares {
+            let Some(address) = share.btcaddress.clone() else { continue };
+            addresses.push(address);
+            difficulties.push(share.difficulty as i64);
+            times.push(to_datetime(share.n_time));
+        }
+
+        if addresses.is_empty() {
+            return Ok(Some(0));
+        }
+
+        let tx = conn.transaction().await?;
+
+        tx.execute(
+            "INSERT INTO miners (address) SELECT DISTINCT a FROM UNNEST($1::text[]) AS a ON CONFLICT (address) DO NOTHING",
+            &[&addresses],
+        )
+        .await
+        .context("Failed to insert miners")?;
+
+        let inserted = tx
+            .execute(
+                "INSERT INTO shares (miner_id, difficulty, created_at) \
+                 SELECT m.id, s.difficulty, s.created_at \
+                 FROM UNNEST($1::text[], $2::bigint[], $3::timestamptz[]) AS s(address, difficulty, created_at) \
+                 JOIN miners m ON m.address = s.address",
+                &[&addresses, &difficulties, &times],
+            )
+            .await
+            .context("Failed to insert shares")?;
+
+        tx.execute(
+            "INSERT INTO share_rollups_hourly (miner_address, hour, share_count, total_difficulty) \
+             SELECT s.address, date_trunc('hour', s.created_at), COUNT(*), SUM(s.difficulty) \
+             FROM UNNEST($1::text[], $2::bigint[], $3::timestamptz[]) AS s(address, difficulty, created_at) \
+             GROUP BY 1, 2 \
+             ON CONFLICT (miner_address, hour) DO UPDATE SET \
+                 share_count = share_rollups_hourly.share_count + EXCLUDED.share_count, \
+                 total_difficulty = share_rollups_hourly.total_difficulty + EXCLUDED.total_difficulty, \
+                 updated_at = NOW()",
+            &[&addresses, &difficulties, &times],
+        )
+        .await
+        .context("Failed to update share rollups")?;
+
+        tx.commit().await?;
+
+        Ok(Some(inserted))
+    }
+}
+
+/// Convert unix seconds to a UTC timestamp
+fn to_datetime(secs: u64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs as i64, 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_percent() {
+        let mut progress = BackfillProgress {
+            id: "test".to_string(),
+            status: BackfillStatus::Running,
+            start_time: 1000,
+            end_time: 2000,
+            cursor: 1250,
+            shares_replayed: 0,
+            batches_completed: 0,
+            windows_skipped: 0,
+            percent: 0.0,
+            started_at: Utc::now(),
+            finished_at: None,
+            error: None,
+        };
+
+        progress.update_percent();
+        assert_eq!(progress.percent, 25.0);
+
+        progress.cursor = 5000;
+        progress.update_percent();
+        assert_eq!(progress.percent, 100.0);
+    }
+
+    #[test]
+    fn test_request_defaults() {
+        let request: BackfillRequest = serde_json::from_str(r#"{"start_time": 0, "end_time": 3600}"#).unwrap();
+        assert_eq!(request.batch_size, None);
+        assert!(!request.overwrite);
+    }
+}
@@ -0,0 +1,373 @@
+// Backup Catalog
+//
+// Records every backup (local archives and remote copies) with its
+// validation state and lineage, so the scheduler and alerts can ask for the
+// last validated backup without scanning the backup directory. Restore drill
+// results are kept alongside.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::db::DatabaseManager;
+use serde::{Deserialize, Serialize};
+
+use super::{BackupMetadata, DrillResult};
+
+/// Where a cataloged backup is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupLocation {
+    Local,
+    Remote,
+}
+
+impl BackupLocation {
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupLocation::Local => "local",
+            BackupLocation::Remote => "remote",
+        }
+    }
+}
+
+/// One backup in the catalog
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub backup_id: String,
+    pub location: BackupLocation,
+    /// Archive path for local backups, object URI for remote copies
+    pub uri: String,
+    /// Backup this one was copied from
+    pub parent_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub original_size: u64,
+    pub backup_size: u64,
+    pub checksum: String,
+    pub schema_version: u32,
+    pub validated: bool,
+    pub validated_at: Option<DateTime<Utc>>,
+    /// Why the last validation failed
+    pub validation_error: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl CatalogEntry {
+    /// Entry for a local archive
+    pub fn local(metadata: &BackupMetadata) -> Self {
+        Self {
+            backup_id: metadata.id.clone(),
+            location: BackupLocation::Local,
+            uri: metadata.file_path.display().to_string(),
+            parent_id: None,
+            created_at: metadata.timestamp,
+            original_size: metadata.original_size,
+            backup_size: metadata.backup_size,
+            checksum: metadata.checksum.clone(),
+            schema_version: metadata.schema_version,
+            validated: metadata.validated,
+            validated_at: None,
+            validation_error: None,
+            deleted_at: None,
+        }
+    }
+
+    /// Entry for a remote copy of a local archive
+    ///
+    /// The copy carries the archive's checksum and starts unvalidated.
+    pub fn remote_copy(metadata: &BackupMetadata, uri: impl Into<String>) -> Self {
+        Self {
+            backup_id: uuid::Uuid::new_v4().to_string(),
+            location: BackupLocation::Remote,
+            uri: uri.into(),
+            parent_id: Some(metadata.id.clone()),
+            created_at: Utc::now(),
+            validated: false,
+            ..Self::local(metadata)
+        }
+    }
+}
+
+/// Stores the backup catalog
+#[async_trait]
+pub trait BackupCatalog: Send + Sync {
+    /// Insert or replace an entry
+    async fn record(&self, entry: &CatalogEntry) -> Result<()>;
+
+    /// Record the outcome of a validation
+    async fn set_validation(&self, backup_id: &str, error: Option<&str>) -> Result<()>;
+
+    /// Mark a backup deleted; its entry is kept
+    async fn mark_deleted(&self, backup_id: &str) -> Result<()>;
+
+    /// Newest validated, undeleted backup created at or before `before`
+    async fn last_validated(&self, before: DateTime<Utc>) -> Result<Option<CatalogEntry>>;
+
+    /// Newest entries first, deleted ones included
+    async fn entries(&self, limit: i64) -> Result<Vec<CatalogEntry>>;
+
+    /// Record a restore drill
+    async fn record_drill(&self, drill: &DrillResult) -> Result<()>;
+
+    /// Newest drills first
+    async fn drills(&self, limit: i64) -> Result<Vec<DrillResult>>;
+}
+
+fn entry_from_row(row: &tokio_postgres::Row) -> Result<CatalogEntry> {
+    let location: String = row.get("location");
+    Ok(CatalogEntry {
+        backup_id: row.get("backup_id"),
+        location: match location.as_str() {
+            "remote" => BackupLocation::Remote,
+            _ => BackupLocation::Local,
+        },
+        uri: row.get("uri"),
+        parent_id: row.get("parent_id"),
+        created_at: row.get("created_at"),
+        original_size: row.get::<_, i64>("original_size").max(0) as u64,
+        backup_size: row.get::<_, i64>("backup_size").max(0) as u64,
+        checksum: row.get("checksum"),
+        schema_version: row.get::<_, i32>("schema_version").max(0) as u32,
+        validated: row.get("validated"),
+        validated_at: row.get("validated_at"),
+        validation_error: row.get("validation_error"),
+        deleted_at: row.get("deleted_at"),
+    })
+}
+
+#[async_trait]
+impl BackupCatalog for DatabaseManager {
+    async fn record(&self, entry: &CatalogEntry) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO backup_catalog (backup_id, location, uri, parent_id, created_at, original_size, backup_size, checksum, schema_version, validated, validated_at, validation_error, deleted_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (backup_id) DO UPDATE SET
+                uri = EXCLUDED.uri,
+                backup_size = EXCLUDED.backup_size,
+                checksum = EXCLUDED.checksum,
+                validated = EXCLUDED.validated,
+                validated_at = COALESCE(EXCLUDED.validated_at, backup_catalog.validated_at),
+                validation_error = EXCLUDED.validation_error,
+                deleted_at = EXCLUDED.deleted_at,
+                updated_at = NOW()",
+            &[
+                &entry.backup_id,
+                &entry.location.as_str(),
+                &entry.uri,
+                &entry.parent_id,
+                &entry.created_at,
+                &(entry.original_size as i64),
+                &(entry.backup_size as i64),
+                &entry.checksum,
+                &(entry.schema_version as i32),
+                &entry.validated,
+                &entry.validated_at,
+                &entry.validation_error,
+                &entry.deleted_at,
+            ],
+        )
+        .await
+        .context("Failed to record backup in catalog")?;
+        Ok(())
+    }
+
+    async fn set_validation(&self, backup_id: &str, error: Option<&str>) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "UPDATE backup_catalog SET validated = $2 IS NULL, validated_at = NOW(), validation_error = $2, updated_at = NOW() WHERE backup_id = $1",
+            &[&backup_id, &error],
+        )
+        .await
+        .context("Failed to record backup validation")?;
+        Ok(())
+    }
+
+    async fn mark_deleted(&self, backup_id: &str) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "UPDATE backup_catalog SET deleted_at = NOW(), updated_at = NOW() WHERE backup_id = $1 AND deleted_at IS NULL",
+            &[&backup_id],
+        )
+        .await
+        .context("Failed to mark backup deleted")?;
+        Ok(())
+    }
+
+    async fn last_validated(&self, before: DateTime<Utc>) -> Result<Option<CatalogEntry>> {
+        let conn = self.get_conn().await?;
+        let row = conn
+            .query_opt(
+                "SELECT * FROM backup_catalog WHERE validated AND deleted_at IS NULL AND created_at <= $1 ORDER BY created_at DESC LIMIT 1",
+                &[&before],
+            )
+            .await?;
+        row.as_ref().map(entry_from_row).transpose()
+    }
+
+    async fn entries(&self, limit: i64) -> Result<Vec<CatalogEntry>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query("SELECT * FROM backup_catalog ORDER BY created_at DESC LIMIT $1", &[&limit])
+            .await?;
+        rows.iter().map(entry_from_row).collect()
+    }
+
+    async fn record_drill(&self, drill: &DrillResult) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO restore_drills (drill_id, backup_id, started_at, finished_at, passed, checks) VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &drill.id,
+                &drill.backup_id,
+                &drill.started_at,
+                &drill.finished_at,
+                &drill.passed,
+                &serde_json::to_value(&drill.checks)?,
+            ],
+        )
+        .await
+        .context("Failed to record restore drill")?;
+        Ok(())
+    }
+
+    async fn drills(&self, limit: i64) -> Result<Vec<DrillResult>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query("SELECT * FROM restore_drills ORDER BY started_at DESC LIMIT $1", &[&limit])
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(DrillResult {
+                    id: row.get("drill_id"),
+                    backup_id: row.get("backup_id"),
+                    started_at: row.get("started_at"),
+                    finished_at: row.get("finished_at"),
+                    passed: row.get("passed"),
+                    checks: serde_json::from_value(row.get("checks")).context("Invalid restore drill checks")?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{BackupConfig, BackupManager};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::sync::RwLock;
+
+    #[derive(Default)]
+    struct MemoryCatalog {
+        entries: RwLock<Vec<CatalogEntry>>,
+        drills: RwLock<Vec<DrillResult>>,
+    }
+
+    #[async_trait]
+    impl BackupCatalog for MemoryCatalog {
+        async fn record(&self, entry: &CatalogEntry) -> Result<()> {
+            let mut entries = self.entries.write().await;
+            entries.retain(|e| e.backup_id != entry.backup_id);
+            entries.push(entry.clone());
+            Ok(())
+        }
+
+        async fn set_validation(&self, backup_id: &str, error: Option<&str>) -> Result<()> {
+            for entry in self.entries.write().await.iter_mut().filter(|e| e.backup_id == backup_id) {
+                entry.validated = error.is_none();
+                entry.validated_at = Some(Utc::now());
+                entry.validation_error = error.map(str::to_string);
+            }
+            Ok(())
+        }
+
+        async fn mark_deleted(&self, backup_id: &str) -> Result<()> {
+            for entry in self.entries.write().await.iter_mut().filter(|e| e.backup_id == backup_id) {
+                entry.deleted_at = Some(Utc::now());
+            }
+            Ok(())
+        }
+
+        async fn last_validated(&self, before: DateTime<Utc>) -> Result<Option<CatalogEntry>> {
+            Ok(self.entries.read().await.iter()
+                .filter(|e| e.validated && e.deleted_at.is_none() && e.created_at <= before)
+                .max_by_key(|e| e.created_at)
+                .cloned())
+        }
+
+        async fn entries(&self, limit: i64) -> Result<Vec<CatalogEntry>> {
+            let mut entries = self.entries.read().await.clone();
+            entries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            entries.truncate(limit as usize);
+            Ok(entries)
+        }
+
+        async fn record_drill(&self, drill: &DrillResult) -> Result<()> {
+            self.drills.write().await.insert(0, drill.clone());
+            Ok(())
+        }
+
+        async fn drills(&self, limit: i64) -> Result<Vec<DrillResult>> {
+            Ok(self.drills.read().await.iter().take(limit as usize).cloned().collect())
+        }
+    }
+
+    fn manager(temp_dir: &TempDir, catalog: Arc<MemoryCatalog>) -> BackupManager {
+        let db_path = temp_dir.path().join("store");
+        std::fs::create_dir_all(&db_path).unwrap();
+        std::fs::write(db_path.join("CURRENT"), b"MANIFEST-000001").unwrap();
+        BackupManager::new(BackupConfig {
+            db_path,
+            backup_dir: temp_dir.path().join("backups"),
+            ..Default::default()
+        }).with_catalog(catalog)
+    }
+
+    #[tokio::test]
+    async fn test_backups_are_cataloged_through_their_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let catalog = Arc::new(MemoryCatalog::default());
+        let backups = manager(&temp_dir, catalog.clone());
+        assert!(backups.is_backup_due().await);
+
+        let metadata = backups.create_backup().await.unwrap();
+        let last = backups.last_validated_backup(Utc::now()).await.unwrap().unwrap();
+        assert_eq!(last.backup_id, metadata.id);
+        assert!(last.validated_at.is_some() && last.validation_error.is_none());
+        assert!(!backups.is_backup_due().await);
+        assert!(!backups.check_stale(48).await.unwrap());
+        // Nothing was validated before the backup was made
+        let before = metadata.timestamp - chrono::Duration::seconds(1);
+        assert!(backups.last_validated_backup(before).await.unwrap().is_none());
+
+        backups.delete_backup(&metadata.id).await.unwrap();
+        assert!(backups.last_validated_backup(Utc::now()).await.unwrap().is_none());
+        assert!(backups.check_stale(48).await.unwrap());
+        let entries = backups.catalog_entries(10).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].deleted_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_remote_copy_lineage_and_failed_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        let catalog = Arc::new(MemoryCatalog::default());
+        let backups = manager(&temp_dir, catalog.clone());
+        let metadata = backups.create_backup().await.unwrap();
+
+        let copy = backups.record_remote_copy(&metadata.id, "s3://pool-backups/latest.tar.gz").await.unwrap();
+        assert_eq!(copy.location, BackupLocation::Remote);
+        assert_eq!(copy.parent_id.as_deref(), Some(metadata.id.as_str()));
+        assert_eq!(copy.checksum, metadata.checksum);
+        assert!(!copy.validated);
+
+        // A corrupted archive fails validation and is recorded as such
+        std::fs::write(&metadata.file_path, b"corrupt").unwrap();
+        assert!(backups.validate_backup(&metadata).await.is_err());
+        let entries = catalog.entries(10).await.unwrap();
+        let local = entries.iter().find(|e| e.backup_id == metadata.id).unwrap();
+        assert!(!local.validated);
+        assert!(local.validation_error.as_deref().unwrap().contains("checksum"));
+    }
+}
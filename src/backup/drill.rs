@@ -0,0 +1,196 @@
+// Restore Drills
+//
+// A drill restores the latest validated backup into a temporary directory
+// and checks the result: the archive checksum, that it extracts, that the
+// restored size and schema version match the backup metadata, and (with a
+// probe) that the restored store opens and answers sample queries. The
+// temporary copy is removed afterwards; the live store is never touched.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::store::Store;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One check of a drill
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DrillCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl DrillCheck {
+    pub fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), passed: true, detail: detail.into() }
+    }
+
+    pub fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), passed: false, detail: detail.into() }
+    }
+}
+
+/// Outcome of a restore drill
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DrillResult {
+    pub id: String,
+    /// None when there was no validated backup to restore
+    pub backup_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub passed: bool,
+    pub checks: Vec<DrillCheck>,
+}
+
+impl DrillResult {
+    pub(super) fn new(backup_id: Option<String>, started_at: DateTime<Utc>, checks: Vec<DrillCheck>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            backup_id,
+            started_at,
+            finished_at: Utc::now(),
+            passed: !checks.is_empty() && checks.iter().all(|c| c.passed),
+            checks,
+        }
+    }
+
+    /// Names of the failed checks
+    pub fn failures(&self) -> Vec<&str> {
+        self.checks.iter().filter(|c| !c.passed).map(|c| c.name.as_str()).collect()
+    }
+}
+
+/// Opens a restored copy and runs sample queries against it
+pub trait RestoreProbe: Send + Sync {
+    fn probe(&self, restored: &Path) -> Vec<DrillCheck>;
+}
+
+/// Probes a restored share store read-only
+pub struct StoreProbe {
+    /// Shares read as the sample query
+    pub sample_size: usize,
+}
+
+impl Default for StoreProbe {
+    fn default() -> Self {
+        Self { sample_size: 1000 }
+    }
+}
+
+impl RestoreProbe for StoreProbe {
+    fn probe(&self, restored: &Path) -> Vec<DrillCheck> {
+        let store = match Store::new(restored.display().to_string(), true) {
+            Ok(store) => store,
+            Err(e) => return vec![DrillCheck::fail("store_open", format!("Restored store does not open: {}", e))],
+        };
+
+        let tip = store.get_chain_tip();
+        let shares = store.get_pplns_shares_filtered(Some(self.sample_size), None, None);
+        vec![
+            DrillCheck::pass("store_open", "Restored store opens read-only"),
+            DrillCheck::pass("chain_tip", format!("Chain tip {}", tip)),
+            DrillCheck::pass("share_sample", format!("{} shares read (limit {})", shares.len(), self.sample_size)),
+        ]
+    }
+}
+
+/// Checks on the extracted files against the backup metadata
+pub(super) fn check_restored_files(restored: &Path, original_size: u64, restored_size: u64, files: usize) -> Vec<DrillCheck> {
+    let mut checks = Vec::new();
+    if files == 0 {
+        checks.push(DrillCheck::fail("files", format!("Nothing restored at {}", restored.display())));
+    } else {
+        checks.push(DrillCheck::pass("files", format!("{} files restored", files)));
+    }
+    if restored_size == original_size {
+        checks.push(DrillCheck::pass("size", format!("{} bytes", restored_size)));
+    } else {
+        checks.push(DrillCheck::fail("size", format!("Restored {} bytes, backup recorded {}", restored_size, original_size)));
+    }
+    checks
+}
+
+/// Files and total bytes under a directory
+pub(super) fn count_files(path: &Path) -> Result<(usize, u64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                let (f, b) = count_files(&entry.path())?;
+                files += f;
+                bytes += b;
+            } else {
+                files += 1;
+                bytes += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok((files, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{BackupConfig, BackupManager};
+    use tempfile::TempDir;
+
+    struct FixedProbe(bool);
+
+    impl RestoreProbe for FixedProbe {
+        fn probe(&self, restored: &Path) -> Vec<DrillCheck> {
+            assert!(restored.join("CURRENT").exists());
+            if self.0 {
+                vec![DrillCheck::pass("sample", "ok")]
+            } else {
+                vec![DrillCheck::fail("sample", "no rows")]
+            }
+        }
+    }
+
+    fn manager(temp_dir: &TempDir, compress: bool) -> BackupManager {
+        let db_path = temp_dir.path().join("store");
+        std::fs::create_dir_all(db_path.join("sub")).unwrap();
+        std::fs::write(db_path.join("CURRENT"), b"MANIFEST-000001").unwrap();
+        std::fs::write(db_path.join("sub").join("000001.sst"), vec![7u8; 4096]).unwrap();
+        BackupManager::new(BackupConfig {
+            db_path,
+            backup_dir: temp_dir.path().join("backups"),
+            compress,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_drill_restores_latest_validated_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let backups = manager(&temp_dir, false).with_restore_probe(std::sync::Arc::new(FixedProbe(true)));
+
+        let result = backups.run_restore_drill().await;
+        assert!(!result.passed);
+        assert!(result.backup_id.is_none());
+
+        let metadata = backups.create_backup().await.unwrap();
+        let result = backups.run_restore_drill().await;
+        assert!(result.passed, "{:?}", result.checks);
+        assert_eq!(result.backup_id.as_deref(), Some(metadata.id.as_str()));
+        assert!(result.checks.iter().any(|c| c.name == "size" && c.passed));
+        assert_eq!(backups.restore_drills(10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_drill_fails_on_probe_or_size_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let backups = manager(&temp_dir, true).with_restore_probe(std::sync::Arc::new(FixedProbe(false)));
+        backups.create_backup().await.unwrap();
+
+        let result = backups.run_restore_drill().await;
+        assert!(!result.passed);
+        assert_eq!(result.failures(), vec!["sample"]);
+
+        let checks = check_restored_files(Path::new("/tmp/x"), 100, 90, 3);
+        assert!(checks[0].passed && !checks[1].passed);
+        assert!(!check_restored_files(Path::new("/tmp/x"), 0, 0, 0)[0].passed);
+    }
+}
@@ -1,13 +1,40 @@
 // Backup Module for DMPool
 // Handles database backup, compression, validation, and recovery
+//
+// Each archive has a *.meta.json file next to it, which restores read. When a
+// catalog is attached, every backup is also recorded there with its
+// validation state and lineage, and the scheduler and staleness alerts query
+// it for the last validated backup. Restore drills periodically prove the
+// latest validated backup actually restores.
+
+pub mod catalog;
+pub mod drill;
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::error::BackupError;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
 use std::process::Command;
-use tracing::info;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+pub use catalog::{BackupCatalog, BackupLocation, CatalogEntry};
+pub use drill::{DrillCheck, DrillResult, RestoreProbe, StoreProbe};
+
+/// Alert rule triggered when no recent validated backup exists
+pub const BACKUP_STALE_ALERT_RULE: &str = "backup_stale";
+
+/// Alert rule triggered when a restore drill fails
+pub const RESTORE_DRILL_ALERT_RULE: &str = "restore_drill_failed";
+
+/// Drill results kept in memory when there is no catalog
+const MAX_DRILL_HISTORY: usize = 50;
 
 /// Validate a path is safe for use with external commands
 fn validate_safe_path(path: &Path) -> Result<()> {
@@ -16,7 +43,7 @@ fn validate_safe_path(path: &Path) -> Result<()> {
 
     // Must be absolute path
     if !path_str.starts_with('/') {
-        return Err(anyhow::anyhow!("Path must be absolute: {}", path_str));
+        return Err(BackupError::InvalidPath(format!("path must be absolute: {}", path_str)).into());
     }
 
     // Check for dangerous characters or patterns
@@ -47,7 +74,7 @@ fn validate_safe_path(path: &Path) -> Result<()> {
                     continue;
                 }
             }
-            return Err(anyhow::anyhow!("Path contains dangerous pattern '{}': {}", pattern, path_str));
+            return Err(BackupError::InvalidPath(format!("path contains dangerous pattern '{}': {}", pattern, path_str)).into());
         }
     }
 
@@ -55,7 +82,7 @@ fn validate_safe_path(path: &Path) -> Result<()> {
     for component in path.components() {
         if let Some(name) = component.as_os_str().to_str() {
             if name.starts_with('-') && name.len() > 1 {
-                return Err(anyhow::anyhow!("Path component starts with dash (could be interpreted as option): {}", name));
+                return Err(BackupError::InvalidPath(format!("path component starts with dash (could be interpreted as option): {}", name)).into());
             }
         }
     }
@@ -134,12 +161,43 @@ pub struct BackupStats {
 /// Backup manager
 pub struct BackupManager {
     config: BackupConfig,
+    catalog: Option<Arc<dyn BackupCatalog>>,
+    alerts: Option<Arc<AlertManager>>,
+    probe: Option<Arc<dyn RestoreProbe>>,
+    drills: RwLock<VecDeque<DrillResult>>,
+    /// Scheduled backups and drills are skipped while set
+    paused: AtomicBool,
 }
 
 impl BackupManager {
     /// Create a new backup manager
     pub fn new(config: BackupConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            catalog: None,
+            alerts: None,
+            probe: None,
+            drills: RwLock::new(VecDeque::new()),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Open restored copies during drills and run sample queries
+    pub fn with_restore_probe(mut self, probe: Arc<dyn RestoreProbe>) -> Self {
+        self.probe = Some(probe);
+        self
+    }
+
+    /// Record backups in a catalog
+    pub fn with_catalog(mut self, catalog: Arc<dyn BackupCatalog>) -> Self {
+        self.catalog = Some(catalog);
+        self
+    }
+
+    /// Alert when the last validated backup gets too old
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
     }
 
     /// Create with default configuration
@@ -147,6 +205,21 @@ impl BackupManager {
         Self::new(BackupConfig::default())
     }
 
+    /// Pause or resume scheduled backups and restore drills
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::SeqCst) != paused {
+            if paused {
+                warn!("Scheduled backups paused");
+            } else {
+                info!("Scheduled backups resumed");
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
     /// Ensure backup directory exists
     fn ensure_backup_dir(&self) -> Result<()> {
         if !self.config.backup_dir.exists() {
@@ -241,7 +314,7 @@ impl BackupManager {
         if db_file_str.contains(';') || db_file_str.contains('&') || db_file_str.contains('|')
             || db_file_str.contains('$') || db_file_str.contains('`') || db_file_str.contains('\\')
             || db_file_str.contains('\n') || db_file_str.contains('\r') {
-            return Err(anyhow::anyhow!("Database file name contains dangerous characters: {}", db_file_str));
+            return Err(BackupError::InvalidPath(format!("database file name contains dangerous characters: {}", db_file_str)).into());
         }
 
         // Create tar archive (optionally compressed)
@@ -270,7 +343,7 @@ impl BackupManager {
         };
 
         if !status.success() {
-            return Err(anyhow::anyhow!("Backup creation failed with exit code: {:?}", status.code()));
+            return Err(BackupError::CommandFailed(format!("Backup creation failed with exit code: {:?}", status.code())).into());
         }
 
         // Get backup size
@@ -302,6 +375,11 @@ impl BackupManager {
 
         // Save metadata
         self.save_metadata(&metadata)?;
+        if let Some(catalog) = &self.catalog {
+            if let Err(e) = catalog.record(&CatalogEntry::local(&metadata)).await {
+                warn!("Failed to catalog backup {}: {:#}", metadata.id, e);
+            }
+        }
 
         // Validate the backup
         self.validate_backup(&metadata).await?;
@@ -345,30 +423,245 @@ impl BackupManager {
     pub async fn validate_backup(&self, metadata: &BackupMetadata) -> Result<bool> {
         info!("Validating backup: {}", metadata.id);
 
+        let result = self.check_integrity(metadata);
+        if let Some(catalog) = &self.catalog {
+            let error = result.as_ref().err().map(|e| format!("{:#}", e));
+            if let Err(e) = catalog.set_validation(&metadata.id, error.as_deref()).await {
+                warn!("Failed to catalog validation of backup {}: {:#}", metadata.id, e);
+            }
+        }
+        result?;
+
+        // Update metadata as validated
+        let mut updated = metadata.clone();
+        updated.validated = true;
+        self.save_metadata(&updated)?;
+
+        info!("Backup validated successfully: {}", metadata.id);
+        Ok(true)
+    }
+
+    /// Check the archive exists and matches its checksum
+    fn check_integrity(&self, metadata: &BackupMetadata) -> Result<()> {
         // Check if backup file exists
         if !metadata.file_path.exists() {
-            return Err(anyhow::anyhow!("Backup file not found: {:?}", metadata.file_path));
+            return Err(BackupError::NotFound(metadata.file_path.display().to_string()).into());
         }
 
         // Verify checksum
         let current_checksum = self.calculate_checksum(&metadata.file_path)?;
         if current_checksum != metadata.checksum {
-            return Err(anyhow::anyhow!(
-                "Backup checksum mismatch: expected {}, got {}",
-                metadata.checksum,
-                current_checksum
-            ));
+            return Err(BackupError::ChecksumMismatch {
+                expected: metadata.checksum.clone(),
+                actual: current_checksum,
+            }.into());
         }
+        Ok(())
+    }
 
-        // Update metadata as validated
-        let mut updated = metadata.clone();
-        updated.validated = true;
-        self.save_metadata(&updated)?;
+    /// Record every backup found on disk in the catalog
+    ///
+    /// Covers backups made before the catalog existed or while it was unreachable.
+    pub async fn sync_catalog(&self) -> Result<usize> {
+        let catalog = match &self.catalog {
+            Some(catalog) => catalog,
+            None => return Ok(0),
+        };
+        let backups = self.list_backups()?;
+        for metadata in &backups {
+            catalog.record(&CatalogEntry::local(metadata)).await?;
+        }
+        Ok(backups.len())
+    }
 
-        info!("Backup validated successfully: {}", metadata.id);
+    /// Catalog a copy of a local backup stored elsewhere, e.g. object storage
+    pub async fn record_remote_copy(&self, backup_id: &str, uri: &str) -> Result<CatalogEntry> {
+        let catalog = self.catalog.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Backup catalog is not enabled"))?;
+        let metadata = self.load_metadata(backup_id)?;
+        let entry = CatalogEntry::remote_copy(&metadata, uri);
+        catalog.record(&entry).await?;
+        info!("Cataloged remote copy of backup {} at {}", backup_id, uri);
+        Ok(entry)
+    }
+
+    /// Catalog entries, newest first; local backups on disk without a catalog
+    pub async fn catalog_entries(&self, limit: i64) -> Result<Vec<CatalogEntry>> {
+        match &self.catalog {
+            Some(catalog) => catalog.entries(limit).await,
+            None => Ok(self.list_backups()?
+                .iter()
+                .take(limit.max(0) as usize)
+                .map(CatalogEntry::local)
+                .collect()),
+        }
+    }
+
+    /// Newest validated backup created at or before `before`
+    pub async fn last_validated_backup(&self, before: DateTime<Utc>) -> Result<Option<CatalogEntry>> {
+        match &self.catalog {
+            Some(catalog) => catalog.last_validated(before).await,
+            None => Ok(self.list_backups()?
+                .iter()
+                .find(|b| b.validated && b.timestamp <= before)
+                .map(CatalogEntry::local)),
+        }
+    }
+
+    /// Whether a scheduled backup should run
+    ///
+    /// False when a validated backup was made within the last half interval,
+    /// e.g. a manual one just before the schedule came round.
+    pub async fn is_backup_due(&self) -> bool {
+        let now = Utc::now();
+        let half_interval = chrono::Duration::minutes((self.config.interval_hours as i64).saturating_mul(30));
+        match self.last_validated_backup(now).await {
+            Ok(Some(last)) => now - last.created_at >= half_interval,
+            Ok(None) => true,
+            Err(e) => {
+                warn!("Failed to look up the last validated backup: {:#}", e);
+                true
+            }
+        }
+    }
+
+    /// Alert if no validated backup is younger than `max_age_hours`; true when stale
+    pub async fn check_stale(&self, max_age_hours: u64) -> Result<bool> {
+        let now = Utc::now();
+        let last = self.last_validated_backup(now).await?;
+        let age_hours = last.as_ref().map(|b| (now - b.created_at).num_hours());
+        let stale = age_hours.map_or(true, |age| age >= max_age_hours as i64);
+        if !stale {
+            return Ok(false);
+        }
+
+        warn!("No validated backup in the last {} hours", max_age_hours);
+        self.alert(BACKUP_STALE_ALERT_RULE, "Backup stale", "No recent validated backup", AlertLevel::Warning, serde_json::json!({
+            "last_validated_backup": last.as_ref().map(|b| b.backup_id.clone()),
+            "last_validated_at": last.as_ref().map(|b| b.created_at),
+            "age_hours": age_hours,
+            "max_age_hours": max_age_hours,
+        })).await?;
         Ok(true)
     }
 
+    /// Restore the latest validated backup into a temporary directory and check it
+    ///
+    /// Never touches the live store. The result is recorded and a failed
+    /// drill raises an alert.
+    pub async fn run_restore_drill(&self) -> DrillResult {
+        let started_at = Utc::now();
+        let (backup_id, checks) = match self.last_validated_backup(started_at).await {
+            Ok(Some(entry)) => {
+                let checks = self.drill_checks(&entry.backup_id).await;
+                (Some(entry.backup_id), checks)
+            }
+            Ok(None) => (None, vec![DrillCheck::fail("backup", "No validated backup to restore")]),
+            Err(e) => (None, vec![DrillCheck::fail("backup", format!("Failed to find the last validated backup: {:#}", e))]),
+        };
+        let result = DrillResult::new(backup_id, started_at, checks);
+
+        {
+            let mut drills = self.drills.write().await;
+            drills.push_front(result.clone());
+            drills.truncate(MAX_DRILL_HISTORY);
+        }
+        if let Some(catalog) = &self.catalog {
+            if let Err(e) = catalog.record_drill(&result).await {
+                warn!("Failed to record restore drill {}: {:#}", result.id, e);
+            }
+        }
+
+        if result.passed {
+            info!("Restore drill passed for backup {}", result.backup_id.as_deref().unwrap_or("-"));
+        } else {
+            warn!("Restore drill failed: {}", result.failures().join(", "));
+            let context = serde_json::json!({
+                "drill_id": result.id,
+                "backup_id": result.backup_id,
+                "failed_checks": result.failures(),
+            });
+            if let Err(e) = self.alert(RESTORE_DRILL_ALERT_RULE, "Restore drill failed", "The latest backup did not restore cleanly", AlertLevel::Critical, context).await {
+                warn!("Failed to send restore drill alert: {}", e);
+            }
+        }
+        result
+    }
+
+    /// Restore drills, newest first
+    pub async fn restore_drills(&self, limit: i64) -> Result<Vec<DrillResult>> {
+        match &self.catalog {
+            Some(catalog) => catalog.drills(limit).await,
+            None => Ok(self.drills.read().await.iter().take(limit.max(0) as usize).cloned().collect()),
+        }
+    }
+
+    async fn drill_checks(&self, backup_id: &str) -> Vec<DrillCheck> {
+        let mut checks = Vec::new();
+        let metadata = match self.load_metadata(backup_id) {
+            Ok(metadata) => metadata,
+            Err(e) => return vec![DrillCheck::fail("metadata", format!("{:#}", e))],
+        };
+
+        match self.check_integrity(&metadata) {
+            Ok(()) => checks.push(DrillCheck::pass("checksum", metadata.checksum.clone())),
+            Err(e) => {
+                checks.push(DrillCheck::fail("checksum", format!("{:#}", e)));
+                return checks;
+            }
+        }
+
+        let schema_version = self.get_schema_version();
+        if metadata.schema_version == schema_version {
+            checks.push(DrillCheck::pass("schema_version", format!("Version {}", schema_version)));
+        } else {
+            checks.push(DrillCheck::fail("schema_version", format!("Backup has version {}, current is {}", metadata.schema_version, schema_version)));
+        }
+
+        // Dropped (and removed) when the drill ends
+        let temp_dir = match tempfile::Builder::new().prefix("dmpool_restore_drill_").tempdir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                checks.push(DrillCheck::fail("extract", format!("Failed to create a temporary directory: {}", e)));
+                return checks;
+            }
+        };
+        let restored = temp_dir.path().join(self.config.db_path.file_name().unwrap_or("store".as_ref()));
+        if let Err(e) = self.restore_backup(backup_id, Some(&restored)).await {
+            checks.push(DrillCheck::fail("extract", format!("{:#}", e)));
+            return checks;
+        }
+        checks.push(DrillCheck::pass("extract", "Archive extracted"));
+
+        match drill::count_files(&restored) {
+            Ok((files, bytes)) => checks.extend(drill::check_restored_files(&restored, metadata.original_size, bytes, files)),
+            Err(e) => checks.push(DrillCheck::fail("files", format!("Failed to read restored files: {}", e))),
+        }
+        if let Some(probe) = &self.probe {
+            checks.extend(probe.probe(&restored));
+        }
+        checks
+    }
+
+    /// Trigger an alert rule, creating it on first use
+    async fn alert(&self, rule_id: &str, name: &str, message: &str, level: AlertLevel, context: serde_json::Value) -> Result<()> {
+        let alerts = match &self.alerts {
+            Some(alerts) => alerts,
+            None => return Ok(()),
+        };
+        if !alerts.get_rules().await.iter().any(|r| r.id == rule_id) {
+            let channels = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                rule_id,
+                name,
+                AlertCondition::Custom { message: message.to_string() },
+                level,
+                channels,
+            )).await;
+        }
+        alerts.trigger_alert(rule_id, context).await
+    }
+
     /// List all backups
     pub fn list_backups(&self) -> Result<Vec<BackupMetadata>> {
         let mut backups = Vec::new();
@@ -427,9 +720,10 @@ impl BackupManager {
         // Validate checksum before restore
         let current_checksum = self.calculate_checksum(&metadata.file_path)?;
         if current_checksum != metadata.checksum {
-            return Err(anyhow::anyhow!(
-                "Backup checksum mismatch - restore aborted"
-            ));
+            return Err(BackupError::ChecksumMismatch {
+                expected: metadata.checksum.clone(),
+                actual: current_checksum,
+            }).context("Restore aborted");
         }
 
         let restore_path = target_path.unwrap_or(&self.config.db_path);
@@ -447,9 +741,10 @@ impl BackupManager {
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("Restore parent path contains invalid UTF-8"))?;
 
+        // tar detects whether the archive is compressed
         let status = Command::new("tar")
             .args([
-                "-xzf",
+                "-xf",
                 backup_file,
                 "-C",
                 restore_dir,
@@ -458,7 +753,7 @@ impl BackupManager {
             .context("Failed to execute tar extract command")?;
 
         if !status.success() {
-            return Err(anyhow::anyhow!("Backup extraction failed with exit code: {:?}", status.code()));
+            return Err(BackupError::CommandFailed(format!("Backup extraction failed with exit code: {:?}", status.code())).into());
         }
 
         info!("Backup restored successfully to: {:?}", restore_path);
@@ -490,6 +785,7 @@ impl BackupManager {
                     fs::remove_file(&meta_path)
                         .context("Failed to delete metadata file")?;
                 }
+                self.catalog_deleted(&backup.id).await;
 
                 info!("Deleted old backup: {}", backup.id);
             }
@@ -514,8 +810,17 @@ impl BackupManager {
             fs::remove_file(&meta_path)
                 .context("Failed to delete metadata file")?;
         }
+        self.catalog_deleted(backup_id).await;
 
         info!("Deleted backup: {}", backup_id);
         Ok(true)
     }
+
+    async fn catalog_deleted(&self, backup_id: &str) {
+        if let Some(catalog) = &self.catalog {
+            if let Err(e) = catalog.mark_deleted(backup_id).await {
+                warn!("Failed to catalog deletion of backup {}: {:#}", backup_id, e);
+            }
+        }
+    }
 }
@@ -3,10 +3,10 @@
 
 use anyhow::Result;
 use axum::{
-    extract::{Path, Query, State, Request},
-    http::StatusCode,
+    extract::{Extension, Path, Query, State, Request},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
-    response::{Html, IntoResponse, Json, Response},
+    response::{Html, IntoResponse, Json, Redirect, Response},
     routing::{get, post},
     Router,
     middleware,
@@ -16,20 +16,32 @@ use p2poolv2_lib::config::Config;
 use p2poolv2_lib::shares::chain::chain_store::ChainStore;
 use p2poolv2_lib::shares::share_block::ShareBlock;
 use p2poolv2_lib::store::Store;
-use dmpool::auth::{AuthManager, LoginRequest, LoginResponse, UserInfo};
-use dmpool::audit::{AuditLogger, AuditFilter};
+use dmpool::alert::{AlertChannel, AlertManager};
+use dmpool::app::DmpoolConfig;
+use dmpool::auth::{AuthManager, ChallengeSolution, Claims, LoginAttempt, LoginChallenges, LoginMonitor, LoginRequest, LoginResponse, OidcClient, OidcSettings, TwoFactorMethod, UserInfo, UserSummary};
+use dmpool::audit::{AuditLogger, AuditFilter, AuditLog};
 use dmpool::backup::{BackupManager, BackupConfig, BackupStats};
+use dmpool::bitcoin::BitcoinRpcClient;
 use dmpool::confirmation::ConfigConfirmation;
+use dmpool::db::DatabaseManager;
+use dmpool::error::{kind_of, PaymentError};
+use dmpool::events::{EventBus, PoolEvent};
 use dmpool::health::HealthChecker;
-use dmpool::payment::{PaymentManager, PaymentConfig, Payout, PayoutStatus, MinerBalance};
+use dmpool::logging::request_id::request_id_middleware;
+use dmpool::logging::{LogControl, LogFormat, DEFAULT_FILTER};
+use dmpool::maintenance::{read_only_middleware, ReadOnlyMode};
+use dmpool::pplns_validator::{run_simulation, synthetic_shares, SimulationRequest, SimulationShares};
+use dmpool::payment::{ConsolidationConfig, InflightConfig, PaymentManager, PaymentConfig, Payout, PayoutFilter, PayoutStatus, MinerBalance, PayoutApprovals, PayoutPause, ImportFormat, ImportKind, ImportOptions, WalletTiers};
+use dmpool::vardiff::{advise, AdvisorSettings};
 use dmpool::two_factor::{TwoFactorManager, TwoFactorSetup, TwoFactorStatus, TwoFactorEnable, TwoFactorLogin};
-use dmpool::rate_limit::{RateLimiterState, RateLimitConfig, rate_limit_middleware, login_rate_limit_middleware};
+use dmpool::reconciliation::Reconciler;
+use dmpool::rate_limit::{RateLimiterState, RateLimitConfig, rate_limit_middleware, login_rate_limit_middleware, extract_client_ip_with_default_config};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{error, info, warn, Level};
+use tracing::{error, info, warn};
 
 /// Admin state
 #[derive(Clone)]
@@ -44,8 +56,28 @@ struct AdminState {
     rate_limiter: Arc<RateLimiterState>,
     audit_logger: Arc<AuditLogger>,
     config_confirmation: Arc<ConfigConfirmation>,
+    events: EventBus,
+    log_control: Arc<LogControl>,
     backup_manager: Arc<BackupManager>,
     payment_manager: Arc<PaymentManager>,
+    /// Second-admin approvals for large payouts and runs
+    payout_approvals: Arc<PayoutApprovals>,
+    /// Login history and suspicious login alerts (needs DATABASE_URL)
+    login_monitor: Option<Arc<LoginMonitor>>,
+    /// Single sign-on (needs OIDC_ISSUER)
+    oidc: Option<Arc<OidcClient>>,
+    /// Challenges after repeated failed logins (`[dmpool.login_challenge]`)
+    login_challenges: Option<Arc<LoginChallenges>>,
+    /// Mempool checks and fee bumps of broadcast payouts (`[dmpool.payout_inflight]`)
+    inflight_config: InflightConfig,
+    /// Limits of UTXO consolidations (`[dmpool.utxo_consolidation]`)
+    consolidation_config: ConsolidationConfig,
+    /// Read-only maintenance switch (starts on with DMPOOL_READ_ONLY=true)
+    read_only: Arc<ReadOnlyMode>,
+    /// Global payout kill switch
+    payout_pause: Arc<PayoutPause>,
+    /// Manual hot wallet sweeps (`[dmpool.wallet_tiers]`)
+    wallet_tiers: Option<Arc<WalletTiers>>,
     start_time: std::time::Instant,
     banned_workers: Arc<RwLock<HashSet<String>>>,
     worker_tags: Arc<RwLock<HashMap<String, Vec<String>>>>,
@@ -188,9 +220,10 @@ struct BanRequest {
 /// Main entry point
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .init();
+    // RUST_LOG sets the starting filter (/api/logs/filter changes it at runtime),
+    // LOG_FORMAT=json switches to one JSON object per line
+    let log_format: LogFormat = std::env::var("LOG_FORMAT").unwrap_or_else(|_| "text".to_string()).parse()?;
+    let log_control = LogControl::init(&std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.to_string()), log_format)?;
 
     let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string());
     let port: u16 = std::env::var("ADMIN_PORT")
@@ -244,10 +277,36 @@ async fn main() -> Result<()> {
     ));
 
     // Initialize auth manager
-    let auth_manager = Arc::new(AuthManager::new(jwt_secret));
+    let auth_manager = Arc::new(AuthManager::new(jwt_secret)
+        .with_storage(std::path::PathBuf::from("./data/admin_users.json")));
+    let loaded = auth_manager.load().await?;
+    if loaded > 0 {
+        info!("Loaded {} admin users", loaded);
+    }
     auth_manager.init_default_admin(&admin_username, &admin_password).await?;
     info!("Initialized admin user: {}", admin_username);
 
+    // Single sign-on through an OpenID Connect provider (OIDC_* variables)
+    let oidc = match OidcSettings::from_env()? {
+        Some(settings) => {
+            info!("Enabled single sign-on with {}", settings.issuer);
+            Some(Arc::new(OidcClient::new(settings)?))
+        }
+        None => None,
+    };
+
+    let dmpool_config = DmpoolConfig::load(&config_path)?;
+
+    // Proof of work or CAPTCHA after repeated failed logins
+    let login_challenge = dmpool_config.login_challenge;
+    let login_challenges = if login_challenge.enabled {
+        login_challenge.validate()?;
+        info!("Logins need a {:?} challenge after {} failures", login_challenge.provider, login_challenge.failures_before_challenge);
+        Some(Arc::new(LoginChallenges::new(login_challenge.clone(), login_challenge.verifier()?)))
+    } else {
+        None
+    };
+
     // Initialize rate limiter
     let rate_limit_config = RateLimitConfig::default();
     let api_rpm = rate_limit_config.api_rpm.get();
@@ -260,6 +319,10 @@ async fn main() -> Result<()> {
     let audit_logger = Arc::new(AuditLogger::default());
     info!("Initialized audit logger (max 10000 entries in memory)");
 
+    // Initialize event bus; payout broadcasts and applied config changes are audited from it
+    let events = EventBus::default();
+    events.attach(audit_logger.clone());
+
     // Initialize config confirmation
     let config_confirmation = Arc::new(ConfigConfirmation::new());
     info!("Initialized config confirmation system");
@@ -284,34 +347,154 @@ async fn main() -> Result<()> {
             .unwrap_or_else(|_| "bitcoin".to_string()),
         bitcoin_rpc_pass: std::env::var("BITCOIN_RPC_PASS")
             .unwrap_or_default(),
-        ..Default::default()
+        ..PaymentConfig::for_network(config.stratum.network)
+    };
+    // Persist sent payouts and login history when the stats database is configured
+    let database = match std::env::var("DATABASE_URL") {
+        Ok(database_url) => match DatabaseManager::new(&database_url) {
+            Ok(db) => Some(Arc::new(db)),
+            Err(e) => {
+                warn!("Payout and login history disabled, failed to connect to database: {}", e);
+                None
+            }
+        },
+        Err(_) => None,
     };
-    let payment_manager = Arc::new(PaymentManager::new(payment_data_dir, payment_config)?);
+
+    // Payment and 2FA state live in the [dmpool.storage] backend (file by default,
+    // postgres shares them with other instances through DATABASE_URL)
+    let storage = &dmpool_config.storage;
+
+    // Suspicious logins and the payout kill switch alert over Telegram when
+    // TELEGRAM_BOT_TOKEN / TELEGRAM_CHAT_ID are set
+    let alerts = AlertManager::default();
+    if let (Ok(bot_token), Ok(chat_id)) = (std::env::var("TELEGRAM_BOT_TOKEN"), std::env::var("TELEGRAM_CHAT_ID")) {
+        alerts.add_channel("telegram".to_string(), AlertChannel::Telegram { bot_token, chat_id }).await;
+    }
+    let alerts = Arc::new(alerts);
+
+    // The payout kill switch is shared with the pool process through the database
+    let mut payout_pause = PayoutPause::default().with_alerts(alerts.clone());
+    match &database {
+        Some(db) => payout_pause = payout_pause.with_store(db.clone()),
+        None => warn!("Payout kill switch only applies to this process, DATABASE_URL is not set"),
+    }
+    let payout_pause = Arc::new(payout_pause);
+
+    // So is read-only maintenance mode, which starts from [dmpool.maintenance]
+    let mut read_only = ReadOnlyMode::new(&dmpool_config.maintenance);
+    match &database {
+        Some(db) => read_only = read_only.with_store(db.clone()),
+        None => warn!("Read-only mode only applies to this process, DATABASE_URL is not set"),
+    }
+    let read_only = Arc::new(read_only);
+    if let Err(e) = read_only.load().await {
+        warn!("Failed to load read-only switch: {:#}", e);
+    }
+
+    // Payouts and runs above the [dmpool.payout_approvals] thresholds wait for a second admin
+    let approval_config = dmpool_config.payout_approvals.clone();
+    if approval_config.enabled {
+        approval_config.validate()?;
+    }
+    // Shared with the pool process, whose automatic runs wait for decisions made here
+    let approval_store = storage.open("payout_approvals", &payment_data_dir.join("approvals"), database.as_ref())?;
+    let payout_approvals = Arc::new(
+        PayoutApprovals::new(payment_data_dir.join("approvals"), approval_config)?.with_store(approval_store),
+    );
+    payout_approvals.load().await?;
+    if payout_approvals.config().enabled {
+        info!("Payout approvals required above {} satoshis (runs above {})",
+            payout_approvals.config().payout_threshold_satoshis, payout_approvals.config().run_threshold_satoshis);
+    }
+
+    let payment_store = storage.open("payment", &payment_data_dir, database.as_ref())?;
+    let mut payment_manager = PaymentManager::new(payment_data_dir, payment_config)?
+        .with_store(payment_store)
+        .with_events(events.clone())
+        .with_approvals(payout_approvals.clone())
+        .with_read_only(read_only.clone())
+        .with_payout_pause(payout_pause.clone());
+    if let Some(db) = &database {
+        payment_manager = payment_manager.with_recorder(db.clone()).with_revenue(db.clone());
+    }
+    let payment_manager = Arc::new(payment_manager);
     payment_manager.load().await?;
+    match payment_manager.sync_recorder().await {
+        Ok(count) if count > 0 => info!("Synced {} sent payouts to payout history", count),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to sync payout history: {}", e),
+    }
     info!("Initialized payment manager");
 
-    // Initialize 2FA manager
+    // Manual hot wallet sweeps; the pool process runs the scheduled checks
+    let wallet_tiers = if dmpool_config.wallet_tiers.enabled {
+        let config = payment_manager.get_config().await;
+        let wallet = Arc::new(BitcoinRpcClient::new(
+            config.bitcoin_rpc_url,
+            config.bitcoin_rpc_user,
+            config.bitcoin_rpc_pass,
+        ));
+        let tiers = WalletTiers::new(
+            std::path::PathBuf::from("./data/payments/wallet"),
+            dmpool_config.wallet_tiers.clone(),
+            wallet,
+        )?
+        .with_payments(payment_manager.clone())
+        .with_payout_pause(payout_pause.clone())
+        .with_alerts(alerts.clone());
+        if let Err(e) = tiers.load().await {
+            warn!("Failed to load wallet sweep history: {}", e);
+        }
+        info!("Wallet tiers enabled, manual sweeps go to {}", dmpool_config.wallet_tiers.cold_address);
+        Some(Arc::new(tiers))
+    } else {
+        None
+    };
+
+    // Initialize 2FA manager (encryption key from DMPOOL_KEY_PROVIDER)
     let two_factor_storage = std::path::PathBuf::from("./data/two_factor");
-    let two_factor_manager = Arc::new(TwoFactorManager::new(
+    let two_factor_store = storage.open("two_factor", &two_factor_storage, database.as_ref())?;
+    let two_factor_manager = Arc::new(TwoFactorManager::with_key_provider(
         two_factor_storage,
         "DMPool Admin".to_string(),
-    ));
+        dmpool::keys::provider_from_env()?,
+    ).with_store(two_factor_store));
     two_factor_manager.initialize().await?;
     info!("Initialized 2FA manager");
 
+    let login_monitor = match &database {
+        Some(db) => {
+            info!("Initialized login history");
+            Some(Arc::new(LoginMonitor::new(db.clone()).with_alerts(alerts.clone())))
+        }
+        None => None,
+    };
+
     let state = AdminState {
         config_path,
         config: Arc::new(RwLock::new(config.clone())),
         store: store.clone(),
         chain_store,
-        health_checker: Arc::new(HealthChecker::new(config).with_store(store.clone())),
+        health_checker: Arc::new(HealthChecker::new(config).with_store(store.clone()).with_payout_pause(payout_pause.clone())),
         auth_manager: auth_manager.clone(),
         two_factor_manager: two_factor_manager.clone(),
         rate_limiter: rate_limiter.clone(),
         audit_logger: audit_logger.clone(),
         config_confirmation: config_confirmation.clone(),
+        events,
+        log_control,
         backup_manager: backup_manager.clone(),
         payment_manager: payment_manager.clone(),
+        payout_approvals,
+        login_monitor,
+        oidc,
+        login_challenges,
+        inflight_config: dmpool_config.payout_inflight,
+        consolidation_config: dmpool_config.utxo_consolidation,
+        read_only: read_only.clone(),
+        payout_pause,
+        wallet_tiers,
         start_time: std::time::Instant::now(),
         banned_workers: Arc::new(RwLock::new(HashSet::new())),
         worker_tags: Arc::new(RwLock::new(HashMap::new())),
@@ -330,6 +513,9 @@ async fn main() -> Result<()> {
         // Login endpoints (stricter rate limiting)
         .route("/api/auth/login", post(login))
         .route("/api/auth/login2fa", post(login_with_2fa))
+        .route("/api/auth/oidc/status", get(oidc_status))
+        .route("/api/auth/oidc/login", get(oidc_login))
+        .route("/api/auth/oidc/callback", get(oidc_callback))
         .route_layer(middleware::from_fn_with_state(
             rate_limiter.clone(),
             rate_limit_middleware,
@@ -354,7 +540,12 @@ async fn main() -> Result<()> {
         .route("/api/blocks", get(blocks_list))
         .route("/api/blocks/:height", get(block_detail))
         .route("/api/logs", get(logs))
+        .route("/api/logs/filter", get(get_log_filter).put(update_log_filter))
+        .route("/api/maintenance/read-only", get(get_read_only).put(set_read_only))
         .route("/api/safety/check", get(safety_check))
+        .route("/api/pplns/simulate", post(simulate_pplns))
+        .route("/api/vardiff/advice", get(vardiff_advice))
+        .route("/api/vardiff/apply", post(vardiff_apply))
         .route("/api/audit/logs", get(audit_logs))
         .route("/api/audit/stats", get(audit_stats))
         .route("/api/audit/rotate", post(audit_rotate))
@@ -373,6 +564,17 @@ async fn main() -> Result<()> {
         .route("/api/2fa/disable", post(two_factor_disable))
         .route("/api/2fa/status", get(two_factor_status))
         .route("/api/2fa/verify", post(two_factor_verify))
+        .route("/api/2fa/rotate-key", post(two_factor_rotate_key))
+        .route("/api/2fa/lockouts", get(two_factor_lockouts))
+        .route("/api/2fa/lockouts/:username/unlock", post(two_factor_unlock))
+        .route("/api/auth/password", post(change_password))
+        .route("/api/users", get(list_users).post(create_user))
+        .route("/api/users/:username/role", post(set_user_role))
+        .route("/api/users/:username/password-reset", post(reset_user_password))
+        .route("/api/users/:username/disable", post(disable_user))
+        .route("/api/users/:username/enable", post(enable_user))
+        .route("/api/users/:username/delete", post(delete_user))
+        .route("/api/users/:username/logins", get(user_login_history))
         .route("/api/backup/:id/delete", post(delete_backup))
         .route("/api/backup/:id/restore", post(restore_backup))
         .route("/api/backup/cleanup", post(cleanup_backups))
@@ -384,9 +586,26 @@ async fn main() -> Result<()> {
         .route("/api/payments/payouts/:address", get(payment_address_payouts))
         .route("/api/payments/create", post(create_payout))
         .route("/api/payments/pending", get(pending_payouts))
+        .route("/api/payments/preview", get(preview_payout_run))
+        .route("/api/payments/reconciliation", get(payment_reconciliation))
         .route("/api/payments/broadcast/:id", post(broadcast_payout))
+        .route("/api/payments/inflight", get(inflight_payouts))
+        .route("/api/payments/bump/:id", post(bump_payout_fee))
+        .route("/api/payments/consolidation", get(consolidation_check))
+        .route("/api/payments/consolidate", post(consolidate_utxos))
+        .route("/api/payments/approvals", get(list_payout_approvals))
+        .route("/api/payments/import", post(import_payment_history))
+        .route("/api/payments/approvals/:id/approve", post(approve_payout))
+        .route("/api/payments/approvals/:id/reject", post(reject_payout))
+        .route("/api/payments/kill-switch", get(get_payout_pause).put(set_payout_pause))
+        .route("/api/payments/wallets/sweep", post(sweep_hot_wallet))
         .route("/api/payments/config", get(get_payment_config))
         .route("/api/payments/config", post(update_payment_config))
+        // Reject mutations during maintenance, once the caller is authenticated
+        .route_layer(middleware::from_fn_with_state(
+            read_only,
+            read_only_middleware,
+        ))
         // Apply rate limiting first
         .route_layer(middleware::from_fn_with_state(
             rate_limiter.clone(),
@@ -403,6 +622,12 @@ async fn main() -> Result<()> {
         .merge(protected_routes)
         .with_state(state)
         .fallback(not_found);
+    let app = if dmpool_config.http_compression.enabled {
+        dmpool_config.http_compression.apply(app)
+    } else {
+        app
+    };
+    let app = app.layer(middleware::from_fn(request_id_middleware));
 
     // Start server
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
@@ -418,21 +643,40 @@ async fn main() -> Result<()> {
 /// Authentication middleware for protected routes
 async fn auth_middleware(
     State(auth): State<Arc<AuthManager>>,
-    req: Request,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     // Extract Authorization header from request
     let auth_header = req
         .headers()
         .get("authorization")
-        .and_then(|h| h.to_str().ok());
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_string());
 
     if let Some(auth_header) = auth_header {
         if auth_header.starts_with("Bearer ") {
             let token = &auth_header[7..];
             match auth.verify_token(token) {
-                Ok(_claims) => {
-                    // Token valid, proceed
+                Ok(mut claims) => {
+                    // Tokens of deleted or disabled users stop working immediately,
+                    // and role changes apply without logging in again
+                    let user = match auth.active_user(&claims.name).await {
+                        Some(user) => user,
+                        None => {
+                            warn!("Token for inactive user '{}'", claims.name);
+                            return Err(StatusCode::UNAUTHORIZED);
+                        }
+                    };
+                    if user.must_change_password && req.uri().path() != "/api/auth/password" {
+                        return Ok((
+                            StatusCode::FORBIDDEN,
+                            Json(ApiResponse::<serde_json::Value>::error("Password change required")),
+                        ).into_response());
+                    }
+                    claims.role = user.role;
+
+                    // Token valid, expose claims to handlers (for audit logging) and proceed
+                    req.extensions_mut().insert(claims);
                     return Ok(next.run(req).await);
                 }
                 Err(e) => {
@@ -443,20 +687,8 @@ async fn auth_middleware(
         }
     }
 
-    // Allow public routes without auth
-    let path = req.uri().path();
-    let public_routes = [
-        "/",
-        "/api/health",
-        "/api/services/status",
-        "/api/auth/login",
-    ];
-
-    if public_routes.iter().any(|r| path == *r || path.starts_with(r)) {
-        return Ok(next.run(req).await);
-    }
-
-    warn!("Unauthorized access attempt to: {}", path);
+    // Public routes are served by their own router and never get here
+    warn!("Unauthorized access attempt to: {}", req.uri().path());
     Err(StatusCode::UNAUTHORIZED)
 }
 
@@ -525,8 +757,12 @@ async fn get_config(State(state): State<AdminState>) -> impl IntoResponse {
 /// Update configuration (runtime only)
 async fn update_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Json(update): Json<ConfigUpdate>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     let mut config = state.config.write().await;
     let mut changes = Vec::new();
 
@@ -561,31 +797,38 @@ async fn update_config(
     }
 
     if changes.is_empty() {
-        return Json(ApiResponse::<serde_json::Value>::error("No valid changes to apply".to_string()));
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::error("No valid changes to apply".to_string())));
     }
+    info!("Config updated by {}: {}", claims.name, changes.join(", "));
 
     let response = serde_json::json!({
         "message": format!("Applied {} change(s)", changes.len()),
         "changes": changes,
     });
 
-    Json(ApiResponse::ok(response))
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
 /// Reload configuration from file
-async fn reload_config(State(state): State<AdminState>) -> impl IntoResponse {
+async fn reload_config(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match Config::load(&state.config_path) {
         Ok(new_config) => {
             *state.config.write().await = new_config;
-            info!("Configuration reloaded from file");
+            info!("Configuration reloaded from file by {}", claims.name);
             let response = serde_json::json!({
                 "message": "Configuration reloaded successfully"
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
         Err(e) => {
             error!("Failed to reload config: {}", e);
-            Json(ApiResponse::<serde_json::Value>::error(format!("Failed to reload: {}", e)))
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(format!("Failed to reload: {}", e))))
         }
     }
 }
@@ -781,11 +1024,15 @@ async fn worker_detail(
 /// Ban worker
 async fn ban_worker(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(address): Path<String>,
     Json(req): Json<BanRequest>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_operator(&claims) {
+        return denied;
+    }
     state.banned_workers.write().await.insert(address.clone());
-    info!("Banned worker: {} - reason: {:?}", address, req.reason);
+    info!("Banned worker: {} by {} - reason: {:?}", address, claims.name, req.reason);
 
     let response = serde_json::json!({
         "address": address,
@@ -793,16 +1040,20 @@ async fn ban_worker(
         "message": "Worker banned successfully"
     });
 
-    Json(ApiResponse::ok(response))
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
 /// Unban worker
 async fn unban_worker(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(address): Path<String>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_operator(&claims) {
+        return denied;
+    }
     state.banned_workers.write().await.remove(&address);
-    info!("Unbanned worker: {}", address);
+    info!("Unbanned worker: {} by {}", address, claims.name);
 
     let response = serde_json::json!({
         "address": address,
@@ -810,7 +1061,7 @@ async fn unban_worker(
         "message": "Worker unbanned successfully"
     });
 
-    Json(ApiResponse::ok(response))
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
 /// Add tag to worker
@@ -821,9 +1072,13 @@ struct AddTagRequest {
 
 async fn add_worker_tag(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(address): Path<String>,
     Json(req): Json<AddTagRequest>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_operator(&claims) {
+        return denied;
+    }
     let mut worker_tags = state.worker_tags.write().await;
     let tags = worker_tags.entry(address.clone()).or_insert_with(Vec::new);
 
@@ -839,14 +1094,18 @@ async fn add_worker_tag(
         "message": "Tag added successfully"
     });
 
-    Json(ApiResponse::ok(response))
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
 /// Remove tag from worker
 async fn remove_worker_tag(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path((address, tag)): Path<(String, String)>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_operator(&claims) {
+        return denied;
+    }
     let mut worker_tags = state.worker_tags.write().await;
 
     if let Some(tags) = worker_tags.get_mut(&address) {
@@ -866,7 +1125,7 @@ async fn remove_worker_tag(
         "message": "Tag removed successfully"
     });
 
-    Json(ApiResponse::ok(response))
+    (StatusCode::OK, Json(ApiResponse::ok(response)))
 }
 
 /// Get blocks list
@@ -904,6 +1163,94 @@ async fn logs(State(_state): State<AdminState>) -> impl IntoResponse {
     Json(ApiResponse::ok(logs))
 }
 
+/// Get the active log filter
+async fn get_log_filter(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.log_control.status()))
+}
+
+#[derive(Deserialize)]
+struct LogFilterUpdate {
+    filter: String,
+}
+
+/// Replace the active log filter
+async fn update_log_filter(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<LogFilterUpdate>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.log_control.set_filter(&req.filter, &claims.name);
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "log_filter_update".to_string(),
+        resource: "logging:filter".to_string(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "old_value": result.as_ref().ok(), "new_value": req.filter }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(previous) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+            "previous": previous,
+            "filter": state.log_control.status().filter,
+        })))),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(ApiResponse::error(format!("{:#}", e)))),
+    }
+}
+
+/// Get the read-only maintenance mode
+async fn get_read_only(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.read_only.status().await))
+}
+
+#[derive(Deserialize)]
+struct ReadOnlyUpdate {
+    read_only: bool,
+    reason: Option<String>,
+}
+
+/// Turn read-only maintenance mode on or off (admins only)
+async fn set_read_only(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<ReadOnlyUpdate>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.read_only.set(req.read_only, req.reason.clone(), &claims.name).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: if req.read_only { "read_only_enable" } else { "read_only_disable" }.to_string(),
+        resource: "maintenance:read_only".to_string(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "reason": req.reason }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(status) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!(status)))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("{:#}", e)))),
+    }
+}
+
 /// Safety check endpoint
 async fn safety_check(State(state): State<AdminState>) -> impl IntoResponse {
     let config = state.config.read().await;
@@ -962,22 +1309,87 @@ async fn safety_check(State(state): State<AdminState>) -> impl IntoResponse {
     })
 }
 
+/// Record a login attempt in the audit log and login history
+///
+/// Failures feed the failed-login alert rule; successful logins are checked
+/// for new countries, new IP ranges and impossible travel.
+async fn audit_login(
+    state: &AdminState,
+    headers: &HeaderMap,
+    username: &str,
+    role: Option<&str>,
+    two_factor: Option<TwoFactorMethod>,
+    error: Option<&str>,
+) {
+    if let Some(monitor) = &state.login_monitor {
+        let mut attempt = LoginAttempt::from_request(username, headers).with_two_factor(two_factor);
+        if let Some(error) = error {
+            attempt = attempt.failed(error);
+        }
+        if let Err(e) = monitor.record(attempt).await {
+            warn!("Failed to record login history for '{}': {}", username, e);
+        }
+    }
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: username.to_string(),
+        role: role.map(str::to_string),
+        action: "login".to_string(),
+        resource: "/api/auth/login".to_string(),
+        ip_address: extract_client_ip_with_default_config(headers).to_string(),
+        details: serde_json::json!({}),
+        success: error.is_none(),
+        error: error.map(str::to_string),
+        request_id: None,
+    }).await;
+}
+
+/// Refuse a login with a challenge while failed logins require one
+async fn require_login_challenge(state: &AdminState, headers: &HeaderMap, username: &str, solution: Option<&ChallengeSolution>) -> Option<Response> {
+    let challenges = state.login_challenges.as_ref()?;
+    let ip = extract_client_ip_with_default_config(headers);
+    let challenge = challenges.check(ip, username, solution).await?;
+    warn!("Login for user '{}' from {} needs a challenge", username, ip);
+    Some((StatusCode::UNAUTHORIZED, Json(serde_json::json!({
+        "status": "error",
+        "message": "Solve the login challenge and try again",
+        "challenge": challenge,
+    }))).into_response())
+}
+
+/// Count a failed login towards a challenge
+async fn record_login_failure(state: &AdminState, headers: &HeaderMap, username: &str) {
+    if let Some(challenges) = &state.login_challenges {
+        challenges.record_failure(extract_client_ip_with_default_config(headers), username).await;
+    }
+}
+
 /// Login endpoint using AdminState
 async fn login(
     State(state): State<AdminState>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+) -> Result<Json<LoginResponse>, Response> {
+    if let Some(refusal) = require_login_challenge(&state, &headers, &req.username, req.challenge.as_ref()).await {
+        return Err(refusal);
+    }
     match state.auth_manager.authenticate(&req.username, &req.password).await {
         Ok(Some(user)) => {
             let token = state.auth_manager.generate_token(&user)
                 .map_err(|e| {
                     error!("Failed to generate token: {}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
                 })?;
 
             let expires_in = 24 * 3600; // 24 hours
 
             info!("User '{}' logged in successfully", req.username);
+            audit_login(&state, &headers, &req.username, Some(user.role.as_str()), None, None).await;
+            if let Some(challenges) = &state.login_challenges {
+                challenges.record_success(&req.username).await;
+            }
 
             Ok(Json(LoginResponse {
                 token,
@@ -990,15 +1402,109 @@ async fn login(
         }
         Ok(None) => {
             warn!("Failed login attempt for user '{}'", req.username);
-            Err(StatusCode::UNAUTHORIZED)
+            audit_login(&state, &headers, &req.username, None, None, Some("invalid credentials")).await;
+            record_login_failure(&state, &headers, &req.username).await;
+            Err(StatusCode::UNAUTHORIZED.into_response())
         }
         Err(e) => {
             error!("Authentication error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Whether single sign-on is available, for the login page
+async fn oidc_status(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(serde_json::json!({
+        "enabled": state.oidc.is_some(),
+        "issuer": state.oidc.as_ref().map(|oidc| oidc.issuer().to_string()),
+    })))
+}
+
+/// Start single sign-on: redirect to the identity provider
+async fn oidc_login(State(state): State<AdminState>) -> Response {
+    let oidc = match &state.oidc {
+        Some(oidc) => oidc,
+        None => return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Single sign-on is not configured"))).into_response(),
+    };
+    match oidc.authorization_url().await {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(e) => {
+            error!("Failed to start single sign-on: {:#}", e);
+            Redirect::to(&sso_error_redirect("Identity provider is unavailable")).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Finish single sign-on and hand the admin JWT to the login page
+///
+/// Local 2FA does not apply; multi-factor authentication is up to the identity provider.
+async fn oidc_callback(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let oidc = match &state.oidc {
+        Some(oidc) => oidc,
+        None => return (StatusCode::NOT_FOUND, Json(ApiResponse::<()>::error("Single sign-on is not configured"))).into_response(),
+    };
+    if let Some(error) = query.error {
+        warn!("Identity provider refused single sign-on: {} {}", error, query.error_description.as_deref().unwrap_or(""));
+        return Redirect::to(&sso_error_redirect(query.error_description.as_deref().unwrap_or(&error))).into_response();
+    }
+    let (code, login_state) = match (query.code, query.state) {
+        (Some(code), Some(login_state)) => (code, login_state),
+        _ => return Redirect::to(&sso_error_redirect("Missing code or state")).into_response(),
+    };
+
+    let identity = match oidc.complete(&code, &login_state).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            warn!("Single sign-on failed: {:#}", e);
+            return Redirect::to(&sso_error_redirect("Single sign-on failed")).into_response();
+        }
+    };
+    let user = match state.auth_manager
+        .federated_login(&identity.issuer, &identity.username, &identity.role, oidc.auto_provision())
+        .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            warn!("Single sign-on refused for '{}': {:#}", identity.username, e);
+            audit_login(&state, &headers, &identity.username, None, None, Some(format!("sso: {}", e).as_str())).await;
+            return Redirect::to(&sso_error_redirect(&e.to_string())).into_response();
+        }
+    };
+
+    match state.auth_manager.generate_token(&user) {
+        Ok(token) => {
+            info!("User '{}' logged in through {}", user.username, identity.issuer);
+            audit_login(&state, &headers, &user.username, Some(user.role.as_str()), None, None).await;
+            // The fragment never reaches the server or its logs
+            Redirect::to(&format!("/#token={}", token)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to generate token: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
     }
 }
 
+/// Back to the login page with an error message
+fn sso_error_redirect(message: &str) -> String {
+    let mut params = reqwest::Url::parse("http://localhost/").expect("static URL");
+    params.query_pairs_mut().append_pair("sso_error", message);
+    format!("/#{}", params.query().unwrap_or_default())
+}
+
 /// Get audit logs
 async fn audit_logs(
     State(state): State<AdminState>,
@@ -1015,24 +1521,36 @@ async fn audit_stats(State(state): State<AdminState>) -> impl IntoResponse {
 }
 
 /// Rotate audit logs
-async fn audit_rotate(State(state): State<AdminState>) -> impl IntoResponse {
+async fn audit_rotate(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.audit_logger.rotate_logs().await {
         Ok(archive_path) => {
             let response = serde_json::json!({
                 "message": "Audit logs rotated successfully",
                 "archive_file": archive_path
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to rotate logs: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to rotate logs: {}", e))),
+        ),
     }
 }
 
 /// Export audit logs
-async fn audit_export(State(state): State<AdminState>) -> impl IntoResponse {
+async fn audit_export(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     let output_path = std::path::PathBuf::from(format!(
         "./audit_export_{}.jsonl",
         Utc::now().format("%Y%m%d_%H%M%S")
@@ -1044,12 +1562,12 @@ async fn audit_export(State(state): State<AdminState>) -> impl IntoResponse {
                 "message": format!("Exported {} audit log entries", count),
                 "file": output_path
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to export logs: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to export logs: {}", e))),
+        ),
     }
 }
 
@@ -1138,67 +1656,87 @@ async fn request_config_change(
 /// Confirm a pending configuration change
 async fn confirm_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.config_confirmation.confirm_change(&id).await {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    match state.config_confirmation.confirm_change(&id, &claims.name).await {
         Ok(true) => {
             let response = serde_json::json!({
                 "message": "Change confirmed. Use /apply to apply the change.",
                 "id": id
             });
-            Json(ApiResponse::ok(response))
-        }
-        Ok(false) => {
-            Json(ApiResponse::<serde_json::Value>::error(
-                "Change request not found or expired".to_string(),
-            ))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to confirm change: {}",
-            e
-        ))),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Change request not found or expired".to_string())),
+        ),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to confirm change: {}", e))),
+        ),
     }
 }
 
 /// Apply a confirmed configuration change
 async fn apply_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.config_confirmation.apply_change(&id).await {
         Ok(request) => {
             // TODO: Actually apply the config change to the running config
             // For now, just log it
+            state.events.publish(PoolEvent::ConfigApplied {
+                parameter: request.parameter.clone(),
+                old_value: request.old_value.clone(),
+                new_value: request.new_value.clone(),
+                applied_by: claims.name.clone(),
+                applied_at: Utc::now(),
+            });
 
             let response = serde_json::json!({
                 "message": format!("Config change applied: {} = {:?}", request.parameter, request.new_value),
                 "request": request
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to apply change: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to apply change: {}", e))),
+        ),
     }
 }
 
 // ===== Backup API Handlers =====
 
 /// Create a new backup
-async fn create_backup(State(state): State<AdminState>) -> impl IntoResponse {
+async fn create_backup(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.create_backup().await {
         Ok(metadata) => {
             let response = serde_json::json!({
                 "message": "Backup created successfully",
                 "backup": metadata
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to create backup: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to create backup: {}", e))),
+        ),
     }
 }
 
@@ -1257,56 +1795,70 @@ async fn get_backup(
 /// Delete a backup
 async fn delete_backup(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.delete_backup(&id).await {
         Ok(_) => {
             let response = serde_json::json!({
                 "message": format!("Backup {} deleted successfully", id)
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to delete backup: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to delete backup: {}", e))),
+        ),
     }
 }
 
 /// Restore from a backup
 async fn restore_backup(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.restore_backup(&id, None).await {
         Ok(_) => {
             let response = serde_json::json!({
                 "message": format!("Backup {} restored successfully", id),
                 "note": "Database service restart may be required"
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to restore backup: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to restore backup: {}", e))),
+        ),
     }
 }
 
 /// Cleanup old backups based on retention policy
-async fn cleanup_backups(State(state): State<AdminState>) -> impl IntoResponse {
+async fn cleanup_backups(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     match state.backup_manager.cleanup_old_backups().await {
         Ok(count) => {
             let response = serde_json::json!({
                 "message": format!("Cleaned up {} old backup(s)", count),
                 "deleted_count": count
             });
-            Json(ApiResponse::ok(response))
+            (StatusCode::OK, Json(ApiResponse::ok(response)))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!(
-            "Failed to cleanup backups: {}",
-            e
-        ))),
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to cleanup backups: {}", e))),
+        ),
     }
 }
 
@@ -1638,39 +2190,33 @@ async fn payment_payouts(
     State(state): State<AdminState>,
     Query(params): Query<PayoutQuery>,
 ) -> impl IntoResponse {
-    let mut result = state.payment_manager.get_all_payouts().await;
+    let mut filter = PayoutFilter::default();
 
     // Filter by status if specified
     if let Some(status) = params.status {
-        let payout_status = match status.as_str() {
+        filter.status = match status.as_str() {
             "pending" => Some(PayoutStatus::Pending),
             "broadcast" => Some(PayoutStatus::Broadcast),
+            "stuck" => Some(PayoutStatus::Stuck),
+            "evicted" => Some(PayoutStatus::Evicted),
             "confirmed" => Some(PayoutStatus::Confirmed),
             "failed" => Some(PayoutStatus::Failed),
             _ => None,
         };
-        if let Some(ps) = payout_status {
-            result.retain(|p| p.status == ps);
-        }
     }
 
-    // Reverse to show newest first
-    result.reverse();
-
-    // Apply pagination
+    // Newest first, continuing into archived payouts
     let page = params.page.unwrap_or(1).max(1);
-    let page_size = params.page_size.unwrap_or(20).min(100);
-    let total = result.len();
-    let total_pages = (total + page_size - 1) / page_size;
-
-    let paginated_payouts: Vec<Payout> = result
-        .into_iter()
-        .skip((page - 1) * page_size)
-        .take(page_size)
-        .collect();
+    let page_size = params.page_size.unwrap_or(20).clamp(1, 100);
+    let result = match state.payment_manager.list_payouts(&filter, (page - 1) * page_size, page_size).await {
+        Ok(result) => result,
+        Err(e) => return Json(ApiResponse::<PayoutsResponse>::error(format!("Failed to list payouts: {}", e))),
+    };
+    let total = result.total;
+    let total_pages = total.div_ceil(page_size);
 
     let response = PayoutsResponse {
-        data: paginated_payouts,
+        data: result.payouts,
         total,
         page,
         page_size,
@@ -1703,22 +2249,40 @@ struct CreatePayoutRequest {
     amount_satoshis: u64,
 }
 
+/// Create a manual payout
+///
+/// An `Idempotency-Key` header makes retries return the original payout.
 async fn create_payout(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Json(req): Json<CreatePayoutRequest>,
 ) -> impl IntoResponse {
-    match state.payment_manager.create_payout(req.address.clone(), req.amount_satoshis).await {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+    let idempotency_key = headers.get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+
+    match state.payment_manager.create_payout_idempotent(req.address.clone(), req.amount_satoshis, idempotency_key).await {
         Ok(payout) => {
-            info!("Created manual payout {} to {} for {} satoshis", payout.id, req.address, req.amount_satoshis);
-            Json(ApiResponse::ok(serde_json::json!({
+            info!("Created manual payout {} to {} for {} satoshis by {}", payout.id, req.address, req.amount_satoshis, claims.name);
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
                 "payout_id": payout.id,
                 "address": payout.address,
                 "amount_satoshis": payout.amount_satoshis,
                 "status": payout.status,
+                "idempotency_key": payout.idempotency_key,
                 "message": "Payout created successfully"
-            })))
+            }))))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Failed to create payout: {}", e)))
+        // Status reflects the failure (e.g. 409 for a reused idempotency key)
+        Err(e) => (
+            kind_of(&e).status_code(),
+            Json(ApiResponse::error(format!("Failed to create payout: {:#}", e))),
+        ),
     }
 }
 
@@ -1732,25 +2296,719 @@ async fn pending_payouts(State(state): State<AdminState>) -> impl IntoResponse {
     })))
 }
 
+/// Preview the next payout run (no records or transactions are created)
+async fn preview_payout_run(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.payment_manager.preview_payout_run().await {
+        Ok(plan) => Json(ApiResponse::ok(serde_json::to_value(plan).unwrap_or_default())),
+        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Failed to preview payout run: {}", e)))
+    }
+}
+
+/// Default cap on real window shares loaded for a simulation
+const SIMULATION_SHARE_LIMIT: usize = 100_000;
+
+/// Simulate PPLNS payouts with custom reward, fee and window parameters
+async fn simulate_pplns(
+    State(state): State<AdminState>,
+    Json(request): Json<SimulationRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = request.validate() {
+        return Json(ApiResponse::<serde_json::Value>::error(format!("Invalid simulation request: {}", e)));
+    }
+
+    let end_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let shares = match &request.shares {
+        SimulationShares::Synthetic { miners } => synthetic_shares(miners, request.window_days, end_time),
+        SimulationShares::Window { limit } => state.store.get_pplns_shares_filtered(
+            Some(limit.unwrap_or(SIMULATION_SHARE_LIMIT).min(SIMULATION_SHARE_LIMIT)),
+            Some(end_time.saturating_sub(request.window_days * 86400)),
+            Some(end_time),
+        ),
+    };
+
+    info!(
+        "Running PPLNS simulation: {} shares, fee {} bps, {} day window",
+        shares.len(), request.pool_fee_bps, request.window_days
+    );
+    let report = run_simulation(request, &shares).await;
+    Json(ApiResponse::ok(serde_json::to_value(report).unwrap_or_default()))
+}
+
+/// Default look-back for the vardiff advisor (hours)
+const VARDIFF_LOOKBACK_HOURS: u64 = 6;
+
+#[derive(Deserialize)]
+struct VardiffQuery {
+    /// Shares per minute each worker should submit
+    target_spm: Option<f64>,
+    /// Hours of shares to analyse
+    hours: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct VardiffApplyRequest {
+    /// "start_difficulty" or "minimum_difficulty"
+    parameter: String,
+    target_spm: Option<f64>,
+    hours: Option<u64>,
+}
+
+/// Build the vardiff report from recent shares and the current stratum config
+async fn vardiff_report(state: &AdminState, target_spm: Option<f64>, hours: Option<u64>) -> dmpool::vardiff::VardiffReport {
+    let config = state.config.read().await;
+    let settings = AdvisorSettings {
+        target_shares_per_minute: target_spm.filter(|t| *t > 0.0).unwrap_or(AdvisorSettings::default().target_shares_per_minute),
+        minimum_difficulty: config.stratum.minimum_difficulty,
+        maximum_difficulty: config.stratum.maximum_difficulty,
+        ..AdvisorSettings::default()
+    };
+    let (start_difficulty, minimum_difficulty) = (config.stratum.start_difficulty, config.stratum.minimum_difficulty);
+    drop(config);
+
+    let end_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let hours = hours.unwrap_or(VARDIFF_LOOKBACK_HOURS).clamp(1, 168);
+    let shares = state.store.get_pplns_shares_filtered(
+        Some(SIMULATION_SHARE_LIMIT),
+        Some(end_time.saturating_sub(hours * 3600)),
+        Some(end_time),
+    );
+
+    advise(&shares, settings, start_difficulty, minimum_difficulty)
+}
+
+/// Recommend per-worker and pool difficulty targets
+async fn vardiff_advice(
+    State(state): State<AdminState>,
+    Query(query): Query<VardiffQuery>,
+) -> impl IntoResponse {
+    let report = vardiff_report(&state, query.target_spm, query.hours).await;
+    Json(ApiResponse::ok(serde_json::to_value(report).unwrap_or_default()))
+}
+
+/// Turn a pool difficulty recommendation into a config change request
+async fn vardiff_apply(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<VardiffApplyRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let report = vardiff_report(&state, req.target_spm, req.hours).await;
+    let pool = match report.pool {
+        Some(pool) => pool,
+        None => return (StatusCode::CONFLICT, Json(ApiResponse::error("Not enough shares for a recommendation".to_string()))),
+    };
+
+    let (old_value, new_value) = match req.parameter.as_str() {
+        "start_difficulty" => (pool.current_start_difficulty, pool.recommended_start_difficulty),
+        "minimum_difficulty" => (pool.current_minimum_difficulty, pool.recommended_minimum_difficulty),
+        other => return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(format!("Unsupported parameter: {}", other)))),
+    };
+    if old_value == new_value {
+        return (StatusCode::CONFLICT, Json(ApiResponse::error(format!("{} is already at the recommended value", req.parameter))));
+    }
+
+    let ip_address = extract_client_ip_with_default_config(&headers).to_string();
+    let result = state.config_confirmation.create_change_request(
+        req.parameter.clone(),
+        serde_json::json!(old_value),
+        serde_json::json!(new_value),
+        claims.name.clone(),
+        ip_address.clone(),
+    ).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "vardiff_recommendation".to_string(),
+        resource: format!("config:{}", req.parameter),
+        ip_address,
+        details: serde_json::json!({ "old_value": old_value, "new_value": new_value }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(request) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+            "message": "Confirmation required for this change",
+            "request": request,
+            "risk_level": state.config_confirmation.get_risk_level(&req.parameter),
+            "meta": state.config_confirmation.get_config_meta(&req.parameter),
+        })))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to create confirmation request: {}", e)))),
+    }
+}
+
+#[derive(Deserialize)]
+struct ReconciliationQuery {
+    /// RFC 3339, defaults to 30 days ago
+    from: Option<String>,
+    /// RFC 3339, defaults to now
+    to: Option<String>,
+    /// "json" (default) or "csv"
+    format: Option<String>,
+}
+
+/// Reconcile confirmed payouts against wallet transactions
+async fn payment_reconciliation(
+    State(state): State<AdminState>,
+    Query(query): Query<ReconciliationQuery>,
+) -> Response {
+    let parse = |value: Option<&str>, default: chrono::DateTime<Utc>| match value {
+        Some(v) => chrono::DateTime::parse_from_rfc3339(v).map(|t| t.with_timezone(&Utc)),
+        None => Ok(default),
+    };
+    let now = Utc::now();
+    let (from, to) = match (
+        parse(query.from.as_deref(), now - chrono::Duration::days(30)),
+        parse(query.to.as_deref(), now),
+    ) {
+        (Ok(from), Ok(to)) if from < to => (from, to),
+        (Ok(_), Ok(_)) => {
+            return Json(ApiResponse::<serde_json::Value>::error("'from' must be before 'to'".to_string())).into_response();
+        }
+        _ => {
+            return Json(ApiResponse::<serde_json::Value>::error("Invalid time range, expected RFC 3339".to_string())).into_response();
+        }
+    };
+
+    let config = state.payment_manager.get_config().await;
+    let bitcoin = Arc::new(BitcoinRpcClient::new(
+        config.bitcoin_rpc_url,
+        config.bitcoin_rpc_user,
+        config.bitcoin_rpc_pass,
+    ));
+    let report = match Reconciler::new(state.payment_manager.clone(), bitcoin).report(from, to).await {
+        Ok(report) => report,
+        Err(e) => {
+            return Json(ApiResponse::<serde_json::Value>::error(format!("Failed to reconcile payouts: {}", e))).into_response();
+        }
+    };
+
+    if query.format.as_deref() == Some("csv") {
+        let filename = format!(
+            "reconciliation_{}_{}.csv",
+            from.format("%Y%m%d"),
+            to.format("%Y%m%d")
+        );
+        return (
+            [
+                (header::CONTENT_TYPE, "text/csv".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+            ],
+            report.to_csv(),
+        ).into_response();
+    }
+
+    Json(ApiResponse::ok(report)).into_response()
+}
+
 /// Broadcast a pending payout
+///
+/// Payouts above the approval threshold are held instead: the response is
+/// 202 with the approval request another admin has to approve.
 async fn broadcast_payout(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match state.payment_manager.broadcast_payout(&id).await {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+    match state.payment_manager.broadcast_payout_as(&id, &claims.name).await {
         Ok(payout) => {
             info!("Broadcast payout {} to {} for {} satoshis", payout.id, payout.address, payout.amount_satoshis);
-            Json(ApiResponse::ok(serde_json::json!({
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
                 "payout_id": payout.id,
                 "txid": payout.txid,
                 "status": payout.status,
+                "idempotency_key": payout.idempotency_key,
+                "intent_state": payout.intent.as_ref().map(|i| i.state),
+                "attempts": payout.intent.as_ref().map(|i| i.attempts),
                 "message": "Payout broadcast successfully"
-            })))
+            }))))
+        }
+        Err(e) => match e.chain().find_map(|cause| cause.downcast_ref::<PaymentError>()) {
+            Some(PaymentError::ApprovalRequired { approval_id, .. }) => {
+                let approval = state.payout_approvals.get(approval_id).await;
+                audit_approval_action(&state, &claims, &headers, "payout_approval_request", approval_id, serde_json::json!({
+                    "payout_id": id,
+                    "amount_satoshis": approval.as_ref().map(|a| a.amount_satoshis),
+                }), None).await;
+                (StatusCode::ACCEPTED, Json(ApiResponse::ok(serde_json::json!({
+                    "payout_id": id,
+                    "approval": approval,
+                    "message": "Payout needs a second admin's approval before broadcast"
+                }))))
+            }
+            _ => (
+                kind_of(&e).status_code(),
+                Json(ApiResponse::error(format!("Failed to broadcast payout: {}", e))),
+            ),
+        },
+    }
+}
+
+/// Mempool position of broadcast payouts; marks them Stuck or Evicted
+async fn inflight_payouts(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.payment_manager.check_inflight(&state.inflight_config).await {
+        Ok(payouts) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+            "count": payouts.len(),
+            "stuck": payouts.iter().filter(|p| p.status == PayoutStatus::Stuck).count(),
+            "evicted": payouts.iter().filter(|p| p.status == PayoutStatus::Evicted).count(),
+            "payouts": payouts,
+        })))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to check in-flight payouts: {:#}", e)))),
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct BumpFeeRequest {
+    /// Defaults to the suggested rate for `conf_target`
+    fee_rate_sat_vb: Option<f64>,
+}
+
+/// Replace a Broadcast or Stuck payout's transaction with a higher-fee one (RBF)
+async fn bump_payout_fee(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    request: Option<Json<BumpFeeRequest>>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let previous_txid = state.payment_manager.get_payout(&id).await.ok().flatten().and_then(|p| p.txid);
+    let result = state.payment_manager.bump_payout_fee(&id, request.fee_rate_sat_vb, &claims.name, &state.inflight_config).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "payout_fee_bump".to_string(),
+        resource: format!("payout:{}", id),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({
+            "previous_txid": previous_txid,
+            "txid": result.as_ref().ok().and_then(|p| p.txid.clone()),
+            "fee_rate_sat_vb": request.fee_rate_sat_vb,
+        }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(payout) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+            "payout_id": payout.id,
+            "previous_txid": previous_txid,
+            "txid": payout.txid,
+            "status": payout.status,
+            "fee_satoshis": payout.intent.as_ref().map(|i| i.fee_satoshis),
+        })))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to bump payout fee: {:#}", e)))),
+    }
+}
+
+/// Fee window and the UTXOs a consolidation would merge
+async fn consolidation_check(State(state): State<AdminState>) -> impl IntoResponse {
+    match state.payment_manager.consolidation_check(&state.consolidation_config).await {
+        Ok(check) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::to_value(check).unwrap_or_default()))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to check UTXO consolidation: {:#}", e)))),
+    }
+}
+
+/// Consolidate small UTXOs now, whatever the fee window
+async fn consolidate_utxos(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+
+    let result = state.payment_manager.consolidate_utxos(&state.consolidation_config, true).await;
+    let consolidation = result.as_ref().ok().and_then(|check| check.consolidation.as_ref());
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "utxo_consolidation".to_string(),
+        resource: "wallet".to_string(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({
+            "txid": consolidation.map(|c| c.txid.clone()),
+            "inputs": consolidation.map(|c| c.inputs),
+            "fee_satoshis": consolidation.map(|c| c.fee_satoshis),
+        }),
+        success: consolidation.is_some(),
+        error: match &result {
+            Ok(check) => check.skipped.clone(),
+            Err(e) => Some(format!("{:#}", e)),
+        },
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(check) if check.consolidation.is_some() => (StatusCode::OK, Json(ApiResponse::ok(serde_json::to_value(check).unwrap_or_default()))),
+        Ok(check) => (StatusCode::CONFLICT, Json(ApiResponse::error(format!(
+            "Nothing consolidated: {}", check.skipped.unwrap_or_default()
+        )))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to consolidate UTXOs: {:#}", e)))),
+    }
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    kind: ImportKind,
+    format: ImportFormat,
+    expected_total_satoshis: Option<u64>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Import another pool's payout history or balances (CSV or JSON body)
+///
+/// Rows are validated first; a file with any invalid row loads nothing and
+/// the errors are returned. The same file cannot be imported twice.
+async fn import_payment_history(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Query(query): Query<ImportQuery>,
+    body: axum::body::Bytes,
+) -> impl IntoResponse {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+
+    let options = ImportOptions {
+        kind: query.kind,
+        format: query.format,
+        network: state.config.read().await.stratum.network,
+        expected_total_satoshis: query.expected_total_satoshis,
+        dry_run: query.dry_run,
+    };
+    let result = state.payment_manager.import_history(&body, &options).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "payment_import".to_string(),
+        resource: format!("import:{:?}", query.kind).to_lowercase(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: match &result {
+            Ok(report) => serde_json::json!({
+                "file_hash": report.file_hash,
+                "rows": report.rows,
+                "imported": report.imported,
+                "skipped": report.skipped,
+                "total_satoshis": report.total_satoshis,
+                "dry_run": report.dry_run,
+                "errors": report.errors.len(),
+            }),
+            Err(_) => serde_json::json!({ "dry_run": query.dry_run }),
+        },
+        success: result.as_ref().is_ok_and(|report| report.errors.is_empty()),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(report) if report.errors.is_empty() => (StatusCode::OK, Json(ApiResponse::ok(serde_json::to_value(report).unwrap_or_default()))),
+        Ok(report) => (StatusCode::UNPROCESSABLE_ENTITY, Json(ApiResponse::ok(serde_json::to_value(report).unwrap_or_default()))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to import: {:#}", e)))),
+    }
+}
+
+#[derive(Deserialize)]
+struct ApprovalListQuery {
+    /// Include decided requests
+    all: Option<bool>,
+    limit: Option<usize>,
+}
+
+/// List payout approval requests (pending only unless `all=true`)
+async fn list_payout_approvals(
+    State(state): State<AdminState>,
+    Query(query): Query<ApprovalListQuery>,
+) -> impl IntoResponse {
+    let requests = if query.all.unwrap_or(false) {
+        state.payout_approvals.list(query.limit.unwrap_or(100).min(1000)).await
+    } else {
+        state.payout_approvals.pending().await
+    };
+    Json(ApiResponse::ok(serde_json::json!({
+        "enabled": state.payout_approvals.config().enabled,
+        "payout_threshold_satoshis": state.payout_approvals.config().payout_threshold_satoshis,
+        "run_threshold_satoshis": state.payout_approvals.config().run_threshold_satoshis,
+        "require_two_factor": state.payout_approvals.config().require_two_factor,
+        "requests": requests,
+    })))
+}
+
+#[derive(Deserialize)]
+struct ApprovePayoutRequest {
+    /// Fresh TOTP code (step-up 2FA)
+    code: Option<String>,
+    backup_code: Option<String>,
+}
+
+/// Approve a held payout or payout run and broadcast it
+///
+/// Needs the admin or payout role, a different admin from the requester and, unless
+/// disabled, a fresh 2FA code.
+async fn approve_payout(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<ApprovePayoutRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+
+    if state.payout_approvals.config().require_two_factor {
+        if let Err((status, message)) = verify_step_up(&state, &claims, req.code.as_deref(), req.backup_code.as_deref(), "approve payouts").await {
+            let error = anyhow::anyhow!(message.clone());
+            audit_approval_action(&state, &claims, &headers, "payout_approve", &id, serde_json::json!({}), Some(&error)).await;
+            return (status, Json(ApiResponse::error(message)));
+        }
+    }
+
+    let request = match state.payout_approvals.approve(&id, &claims.name).await {
+        Ok(request) => request,
+        Err(e) => {
+            audit_approval_action(&state, &claims, &headers, "payout_approve", &id, serde_json::json!({}), Some(&e)).await;
+            return (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to approve payout: {:#}", e))));
+        }
+    };
+    audit_approval_action(&state, &claims, &headers, "payout_approve", &id, serde_json::json!({
+        "subject": request.subject,
+        "amount_satoshis": request.amount_satoshis,
+        "requested_by": request.requested_by,
+    }), None).await;
+    info!("Admin '{}' approved payout request {} ({} satoshis) from '{}'",
+        claims.name, id, request.amount_satoshis, request.requested_by);
+
+    // Broadcasts are audited through PayoutBroadcast events
+    match state.payment_manager.broadcast_approved(&request, &claims.name).await {
+        Ok(payouts) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+            "approval": request,
+            "broadcast": payouts.iter().map(|p| serde_json::json!({
+                "payout_id": p.id,
+                "txid": p.txid,
+                "status": p.status,
+            })).collect::<Vec<_>>(),
+            "message": format!("Approved; {} of {} payouts broadcast", payouts.len(), request.subject.payout_ids().len()),
+        })))),
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Approved, but broadcasting failed: {:#}", e)))),
+    }
+}
+
+#[derive(Deserialize)]
+struct RejectPayoutRequest {
+    reason: Option<String>,
+}
+
+/// Reject a held payout or payout run
+async fn reject_payout(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<RejectPayoutRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_payout_approver(&claims) {
+        return denied;
+    }
+
+    let result = state.payout_approvals.reject(&id, &claims.name, req.reason.clone()).await;
+    audit_approval_action(&state, &claims, &headers, "payout_reject", &id, serde_json::json!({ "reason": req.reason }), result.as_ref().err()).await;
+    match result {
+        Ok(request) => {
+            info!("Admin '{}' rejected payout request {}", claims.name, id);
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::to_value(request).unwrap_or_default())))
+        }
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to reject payout: {:#}", e)))),
+    }
+}
+
+fn require_payout_approver(claims: &Claims) -> Result<(), (StatusCode, Json<ApiResponse<serde_json::Value>>)> {
+    if claims.role == "admin" || claims.role == "payout" {
+        Ok(())
+    } else {
+        warn!("User '{}' with role '{}' denied a payout action", claims.name, claims.role);
+        Err((StatusCode::FORBIDDEN, Json(ApiResponse::error("Admin or payout role required"))))
+    }
+}
+
+/// Check a fresh 2FA code before a sensitive action (step-up 2FA)
+///
+/// Fails with the status and message to deny the request with.
+async fn verify_step_up(
+    state: &AdminState,
+    claims: &Claims,
+    code: Option<&str>,
+    backup_code: Option<&str>,
+    action: &str,
+) -> Result<(), (StatusCode, String)> {
+    if !state.two_factor_manager.get_status(&claims.name).await.enabled {
+        return Err((StatusCode::FORBIDDEN, format!("Enable 2FA to {}", action)));
+    }
+    match state.two_factor_manager.verify_login(&claims.name, code, backup_code).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((StatusCode::UNAUTHORIZED, "Invalid 2FA code".to_string())),
+        Err(e) => {
+            warn!("2FA step-up to {} by '{}' failed: {}", action, claims.name, e);
+            Err((StatusCode::UNAUTHORIZED, "2FA verification failed".to_string()))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Failed to broadcast payout: {}", e)))
     }
 }
 
+/// Get the payout kill switch
+async fn get_payout_pause(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(ApiResponse::ok(state.payout_pause.status().await))
+}
+
+#[derive(Deserialize)]
+struct PayoutPauseUpdate {
+    paused: bool,
+    reason: Option<String>,
+    /// Fresh TOTP code (step-up 2FA)
+    code: Option<String>,
+    backup_code: Option<String>,
+}
+
+/// Pause or resume every payout broadcast and wallet sweep (admins with 2FA only)
+async fn set_payout_pause(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<PayoutPauseUpdate>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let action = if req.paused { "payouts_pause" } else { "payouts_resume" };
+    let result = match verify_step_up(&state, &claims, req.code.as_deref(), req.backup_code.as_deref(), "pause or resume payouts").await {
+        Ok(()) => state.payout_pause.set(req.paused, req.reason.clone(), &claims.name).await
+            .map_err(|e| (kind_of(&e).status_code(), format!("{:#}", e))),
+        Err(denied) => Err(denied),
+    };
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: action.to_string(),
+        resource: "payments:kill_switch".to_string(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "reason": req.reason }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|(_, message)| message.clone()),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(status) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!(status)))),
+        Err((status, message)) => (status, Json(ApiResponse::error(message))),
+    }
+}
+
+#[derive(Deserialize)]
+struct WalletSweepRequest {
+    /// Fresh TOTP code (step-up 2FA)
+    code: Option<String>,
+    backup_code: Option<String>,
+}
+
+/// Sweep the hot wallet excess to cold storage now (admins with 2FA only)
+async fn sweep_hot_wallet(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<WalletSweepRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let Some(tiers) = state.wallet_tiers.clone() else {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse::error("Wallet tiers are not enabled")));
+    };
+    let result = match verify_step_up(&state, &claims, req.code.as_deref(), req.backup_code.as_deref(), "sweep the hot wallet").await {
+        Ok(()) => tiers.sweep_now().await
+            .map_err(|e| (kind_of(&e).status_code(), format!("{:#}", e))),
+        Err(denied) => Err(denied),
+    };
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "wallet_sweep".to_string(),
+        resource: format!("wallet:{}", tiers.config().cold_address),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "sweep": result.as_ref().ok().and_then(|check| check.sweep.as_ref()) }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|(_, message)| message.clone()),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(check) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!(check)))),
+        Err((status, message)) => (status, Json(ApiResponse::error(message))),
+    }
+}
+
+/// Record a payout approval decision in the audit log
+async fn audit_approval_action(
+    state: &AdminState,
+    claims: &Claims,
+    headers: &HeaderMap,
+    action: &str,
+    approval_id: &str,
+    details: serde_json::Value,
+    error: Option<&anyhow::Error>,
+) {
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: action.to_string(),
+        resource: format!("payout_approval:{}", approval_id),
+        ip_address: extract_client_ip_with_default_config(headers).to_string(),
+        details,
+        success: error.is_none(),
+        error: error.map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+}
+
 /// Get payment configuration
 async fn get_payment_config(State(state): State<AdminState>) -> impl IntoResponse {
     let config = state.payment_manager.get_config().await;
@@ -1782,8 +3040,12 @@ struct PaymentConfigUpdate {
 
 async fn update_payment_config(
     State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
     Json(update): Json<PaymentConfigUpdate>,
 ) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
     let mut config = state.payment_manager.get_config().await;
 
     if let Some(min) = update.min_payout_satoshis {
@@ -1813,12 +3075,12 @@ async fn update_payment_config(
 
     match state.payment_manager.update_config(config).await {
         Ok(_) => {
-            info!("Payment configuration updated");
-            Json(ApiResponse::ok(serde_json::json!({
+            info!("Payment configuration updated by {}", claims.name);
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
                 "message": "Payment configuration updated successfully"
-            })))
+            }))))
         }
-        Err(e) => Json(ApiResponse::<serde_json::Value>::error(format!("Failed to update config: {}", e)))
+        Err(e) => (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to update config: {}", e))))
     }
 }
 
@@ -1855,6 +3117,9 @@ struct LoginRequest2FA {
     pub password: String,
     pub totp_code: Option<String>,
     pub backup_code: Option<String>,
+    /// Solved challenge, once failed logins require one
+    #[serde(default)]
+    pub challenge: Option<ChallengeSolution>,
 }
 
 /// Login response with 2FA support
@@ -1872,20 +3137,30 @@ struct LoginResponse2FA {
 /// Login endpoint with 2FA support
 async fn login_with_2fa(
     State(state): State<AdminState>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest2FA>,
-) -> Result<Json<LoginResponse2FA>, StatusCode> {
+) -> Result<Json<LoginResponse2FA>, Response> {
+    if let Some(refusal) = require_login_challenge(&state, &headers, &req.username, req.challenge.as_ref()).await {
+        return Err(refusal);
+    }
+
     // Step 1: Authenticate username and password
     let user = match state.auth_manager.authenticate(&req.username, &req.password).await {
         Ok(Some(u)) => u,
         Ok(None) => {
             warn!("Failed login attempt for user '{}'", req.username);
-            return Err(StatusCode::UNAUTHORIZED);
+            audit_login(&state, &headers, &req.username, None, None, Some("invalid credentials")).await;
+            record_login_failure(&state, &headers, &req.username).await;
+            return Err(StatusCode::UNAUTHORIZED.into_response());
         }
         Err(e) => {
             error!("Authentication error: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
         }
     };
+    if let Some(challenges) = &state.login_challenges {
+        challenges.record_success(&req.username).await;
+    }
 
     // Step 2: Check if 2FA is enabled for this user
     let two_fa_status = state.two_factor_manager.get_status(&req.username).await;
@@ -1895,10 +3170,11 @@ async fn login_with_2fa(
         // No 2FA required, generate token
         let token = state.auth_manager.generate_token(&user).map_err(|e| {
             error!("Failed to generate token: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })?;
 
         info!("User '{}' logged in successfully (no 2FA)", req.username);
+        audit_login(&state, &headers, &req.username, Some(user.role.as_str()), None, None).await;
 
         return Ok(Json(LoginResponse2FA {
             token: Some(token),
@@ -1914,6 +3190,11 @@ async fn login_with_2fa(
     // Step 3: 2FA is required, verify the code
     let totp_code = req.totp_code.as_deref().unwrap_or("");
     let backup_code = req.backup_code.as_deref();
+    let method = if totp_code.is_empty() && backup_code.is_some() {
+        TwoFactorMethod::BackupCode
+    } else {
+        TwoFactorMethod::Totp
+    };
 
     match state.two_factor_manager.verify_login(
         &req.username,
@@ -1924,10 +3205,11 @@ async fn login_with_2fa(
             // 2FA verification successful
             let token = state.auth_manager.generate_token(&user).map_err(|e| {
                 error!("Failed to generate token: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
             })?;
 
             info!("User '{}' logged in successfully with 2FA", req.username);
+            audit_login(&state, &headers, &req.username, Some(user.role.as_str()), Some(method), None).await;
 
             Ok(Json(LoginResponse2FA {
                 token: Some(token),
@@ -1941,6 +3223,7 @@ async fn login_with_2fa(
         }
         Ok(false) => {
             warn!("Failed 2FA verification for user '{}'", req.username);
+            audit_login(&state, &headers, &req.username, Some(user.role.as_str()), Some(method), Some("invalid 2FA code")).await;
             Ok(Json(LoginResponse2FA {
                 token: None,
                 user_info: None,
@@ -2047,6 +3330,363 @@ async fn two_factor_status(
     Json(ApiResponse::ok(status))
 }
 
+#[derive(Deserialize)]
+struct RotateKeyRequest {
+    /// Fresh TOTP code (step-up 2FA)
+    code: Option<String>,
+    backup_code: Option<String>,
+}
+
+/// Rotate the 2FA encryption key and re-encrypt stored secrets (admins with 2FA only)
+async fn two_factor_rotate_key(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<RotateKeyRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = match verify_step_up(&state, &claims, req.code.as_deref(), req.backup_code.as_deref(), "rotate the 2FA encryption key").await {
+        Ok(()) => state.two_factor_manager.rotate_encryption_key().await
+            .map_err(|e| (kind_of(&e).status_code(), format!("Failed to rotate key: {:#}", e))),
+        Err(denied) => Err(denied),
+    };
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "2fa_rotate_key".to_string(),
+        resource: "2fa:encryption_key".to_string(),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "reencrypted": result.as_ref().ok() }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|(_, message)| message.clone()),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(count) => {
+            info!("Admin '{}' rotated the 2FA encryption key ({} secrets re-encrypted)", claims.name, count);
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+                "message": "2FA encryption key rotated",
+                "reencrypted": count
+            }))))
+        }
+        Err((status, message)) => {
+            error!("2FA encryption key rotation by '{}' failed: {}", claims.name, message);
+            (status, Json(ApiResponse::error(message)))
+        }
+    }
+}
+
+/// List users with failed 2FA attempts or active lockouts
+async fn two_factor_lockouts(
+    State(state): State<AdminState>,
+) -> impl IntoResponse {
+    let lockouts = state.two_factor_manager.list_lockouts().await;
+    Json(ApiResponse::ok(lockouts))
+}
+
+/// Clear a user's 2FA lockout (admins only)
+async fn two_factor_unlock(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.two_factor_manager.unlock(&username).await;
+
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: "2fa_unlock".to_string(),
+        resource: format!("user:{}", username),
+        ip_address: extract_client_ip_with_default_config(&headers).to_string(),
+        details: serde_json::json!({ "cleared": result.as_ref().ok() }),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(cleared) => {
+            info!("Admin '{}' cleared 2FA lockout for user '{}'", claims.name, username);
+            (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+                "username": username,
+                "cleared": cleared,
+                "message": if cleared { "2FA lockout cleared" } else { "User was not locked out" }
+            }))))
+        }
+        Err(e) => {
+            error!("Failed to clear 2FA lockout for user '{}': {}", username, e);
+            (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to unlock: {}", e))))
+        }
+    }
+}
+
+/// Reject user management requests from non-admins
+fn require_admin(claims: &Claims) -> Result<(), (StatusCode, Json<ApiResponse<serde_json::Value>>)> {
+    if claims.role == "admin" {
+        Ok(())
+    } else {
+        warn!("User '{}' with role '{}' denied an admin-only action", claims.name, claims.role);
+        Err((StatusCode::FORBIDDEN, Json(ApiResponse::error("Admin role required"))))
+    }
+}
+
+/// Worker bans and tags are open to operators as well as admins
+fn require_operator(claims: &Claims) -> Result<(), (StatusCode, Json<ApiResponse<serde_json::Value>>)> {
+    if claims.role == "admin" || claims.role == "operator" {
+        Ok(())
+    } else {
+        warn!("User '{}' with role '{}' denied worker moderation", claims.name, claims.role);
+        Err((StatusCode::FORBIDDEN, Json(ApiResponse::error("Admin or operator role required"))))
+    }
+}
+
+/// Record a user management action in the audit log
+async fn audit_user_action(
+    state: &AdminState,
+    claims: &Claims,
+    headers: &HeaderMap,
+    action: &str,
+    username: &str,
+    details: serde_json::Value,
+    error: Option<&anyhow::Error>,
+) {
+    state.audit_logger.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: Utc::now(),
+        username: claims.name.clone(),
+        role: Some(claims.role.clone()),
+        action: action.to_string(),
+        resource: format!("user:{}", username),
+        ip_address: extract_client_ip_with_default_config(headers).to_string(),
+        details,
+        success: error.is_none(),
+        error: error.map(|e| format!("{:#}", e)),
+        request_id: None,
+    }).await;
+}
+
+/// Response for a user management call
+fn user_response<T: Serialize>(result: Result<T>, what: &str) -> (StatusCode, Json<ApiResponse<serde_json::Value>>) {
+    match result.and_then(|value| Ok(serde_json::to_value(value)?)) {
+        Ok(value) => (StatusCode::OK, Json(ApiResponse::ok(value))),
+        Err(e) => {
+            warn!("Failed to {}: {:#}", what, e);
+            (kind_of(&e).status_code(), Json(ApiResponse::error(format!("Failed to {}: {:#}", what, e))))
+        }
+    }
+}
+
+/// List admin users with their roles and last login
+async fn list_users(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let users: Vec<UserSummary> = state.auth_manager.list_users().await;
+    (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({
+        "users": users,
+        "roles": dmpool::auth::ROLES,
+    }))))
+}
+
+#[derive(Deserialize)]
+struct CreateUserRequest {
+    username: String,
+    password: String,
+    role: String,
+}
+
+/// Create an admin user
+async fn create_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<CreateUserRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.create_user(&req.username, &req.password, &req.role).await;
+    audit_user_action(&state, &claims, &headers, "user_create", &req.username,
+        serde_json::json!({ "role": req.role }), result.as_ref().err()).await;
+
+    let result = match result {
+        Ok(()) => state.auth_manager.active_user(&req.username).await
+            .ok_or_else(|| anyhow::anyhow!("User '{}' was not created", req.username)),
+        Err(e) => Err(e),
+    };
+    user_response(result, "create user")
+}
+
+#[derive(Deserialize)]
+struct SetRoleRequest {
+    role: String,
+}
+
+/// Change a user's role
+async fn set_user_role(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+    Json(req): Json<SetRoleRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let previous = state.auth_manager.get_user(&username).await.map(|u| u.role);
+    let result = state.auth_manager.set_role(&username, &req.role).await;
+    audit_user_action(&state, &claims, &headers, "user_role_update", &username,
+        serde_json::json!({ "from": previous, "to": req.role }), result.as_ref().err()).await;
+    user_response(result, "change role")
+}
+
+#[derive(Deserialize)]
+struct PasswordResetRequest {
+    temporary_password: String,
+}
+
+/// Force a password reset: the user logs in with a temporary password and must change it
+async fn reset_user_password(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+    Json(req): Json<PasswordResetRequest>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.reset_password(&username, &req.temporary_password).await;
+    audit_user_action(&state, &claims, &headers, "user_password_reset", &username,
+        serde_json::json!({}), result.as_ref().err()).await;
+    user_response(result, "reset password")
+}
+
+/// Disable a user; their tokens stop working immediately
+async fn disable_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = if username == claims.name {
+        Err(dmpool::error::DmpoolError::InvalidInput("You cannot disable your own account".to_string()).into())
+    } else {
+        state.auth_manager.set_disabled(&username, true).await
+    };
+    audit_user_action(&state, &claims, &headers, "user_disable", &username,
+        serde_json::json!({}), result.as_ref().err()).await;
+    user_response(result, "disable user")
+}
+
+/// Re-enable a disabled user
+async fn enable_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = state.auth_manager.set_disabled(&username, false).await;
+    audit_user_action(&state, &claims, &headers, "user_enable", &username,
+        serde_json::json!({}), result.as_ref().err()).await;
+    user_response(result, "enable user")
+}
+
+/// Delete a user
+async fn delete_user(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> impl IntoResponse {
+    if let Err(denied) = require_admin(&claims) {
+        return denied;
+    }
+    let result = if username == claims.name {
+        Err(dmpool::error::DmpoolError::InvalidInput("You cannot delete your own account".to_string()).into())
+    } else {
+        state.auth_manager.delete_user(&username).await
+    };
+    audit_user_action(&state, &claims, &headers, "user_delete", &username,
+        serde_json::json!({}), result.as_ref().err()).await;
+    user_response(result.map(|()| serde_json::json!({ "deleted": username })), "delete user")
+}
+
+#[derive(Deserialize)]
+struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+/// Change one's own password (required after an admin reset)
+async fn change_password(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Json(req): Json<ChangePasswordRequest>,
+) -> impl IntoResponse {
+    let result = state.auth_manager
+        .change_password(&claims.name, &req.current_password, &req.new_password).await;
+    audit_user_action(&state, &claims, &headers, "password_change", &claims.name,
+        serde_json::json!({}), result.as_ref().err()).await;
+    user_response(result.map(|()| serde_json::json!({ "message": "Password changed" })), "change password")
+}
+
+#[derive(Deserialize)]
+struct LoginHistoryQuery {
+    limit: Option<i64>,
+}
+
+/// A user's login attempts, newest first; users other than admins only see their own
+async fn user_login_history(
+    State(state): State<AdminState>,
+    Extension(claims): Extension<Claims>,
+    Path(username): Path<String>,
+    Query(query): Query<LoginHistoryQuery>,
+) -> impl IntoResponse {
+    if username != claims.name {
+        if let Err(denied) = require_admin(&claims) {
+            return denied;
+        }
+    }
+    let monitor = match &state.login_monitor {
+        Some(monitor) => monitor,
+        None => return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiResponse::error("Login history requires DATABASE_URL")),
+        ),
+    };
+    let last_login = state.auth_manager.get_user(&username).await.and_then(|u| u.last_login);
+    let result = monitor.history(&username, query.limit.unwrap_or(50).clamp(1, 500)).await
+        .map(|attempts| serde_json::json!({
+            "username": username,
+            "last_login": last_login,
+            "attempts": attempts,
+        }));
+    user_response(result, "load login history")
+}
+
 /// Verify 2FA code
 async fn two_factor_verify(
     State(state): State<AdminState>,
@@ -2082,3 +3722,92 @@ async fn two_factor_verify(
 async fn not_found() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "Not Found")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_protected_routes_require_token() {
+        let auth = Arc::new(AuthManager::new("0123456789abcdef0123456789abcdef".to_string()));
+        auth.create_user("viewer1", "Viewer@2026!Strong", "viewer").await.unwrap();
+        let token = auth.generate_token(&auth.get_user("viewer1").await.unwrap()).unwrap();
+        let router = Router::new()
+            .route("/api/payments/create", post(|| async { "created" }))
+            .route_layer(middleware::from_fn_with_state(auth, auth_middleware));
+
+        let call = |authorization: Option<String>| {
+            let router = router.clone();
+            async move {
+                let mut request = Request::builder().method("POST").uri("/api/payments/create");
+                if let Some(authorization) = authorization {
+                    request = request.header(header::AUTHORIZATION, authorization);
+                }
+                router.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+            }
+        };
+        assert_eq!(call(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(Some("Basic dXNlcjpwYXNz".to_string())).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(Some("Bearer invalid".to_string())).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(Some(format!("Bearer {}", token))).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_viewers_cannot_mutate() {
+        type Guard = fn(&Claims) -> Result<(), (StatusCode, Json<ApiResponse<serde_json::Value>>)>;
+        // Each mutating route and the role check its handler starts with
+        let routes: [(&str, Guard); 16] = [
+            ("/api/config", require_admin),
+            ("/api/config/reload", require_admin),
+            ("/api/config/confirmations/x", require_admin),
+            ("/api/config/confirmations/x/apply", require_admin),
+            ("/api/logs/filter", require_admin),
+            ("/api/vardiff/apply", require_admin),
+            ("/api/audit/rotate", require_admin),
+            ("/api/audit/export", require_admin),
+            ("/api/backup/create", require_admin),
+            ("/api/backup/x/delete", require_admin),
+            ("/api/backup/x/restore", require_admin),
+            ("/api/backup/cleanup", require_admin),
+            ("/api/payments/config", require_admin),
+            ("/api/payments/create", require_payout_approver),
+            ("/api/payments/broadcast/x", require_payout_approver),
+            ("/api/workers/x/ban", require_operator),
+        ];
+
+        let auth = Arc::new(AuthManager::new("0123456789abcdef0123456789abcdef".to_string()));
+        let mut tokens = HashMap::new();
+        for role in dmpool::auth::ROLES {
+            let username = format!("{}1", role);
+            auth.create_user(&username, "Viewer@2026!Strong", role).await.unwrap();
+            tokens.insert(role, auth.generate_token(&auth.get_user(&username).await.unwrap()).unwrap());
+        }
+
+        for (path, guard) in routes {
+            let router = Router::new()
+                .route(path, post(move |Extension(claims): Extension<Claims>| async move {
+                    match guard(&claims) {
+                        Ok(()) => (StatusCode::OK, Json(ApiResponse::ok(serde_json::json!({})))),
+                        Err(denied) => denied,
+                    }
+                }))
+                .route_layer(middleware::from_fn_with_state(auth.clone(), auth_middleware));
+            let call = |role: &str| {
+                let request = Request::builder()
+                    .method("POST")
+                    .uri(path)
+                    .header(header::AUTHORIZATION, format!("Bearer {}", tokens[role]))
+                    .body(Body::empty())
+                    .unwrap();
+                router.clone().oneshot(request)
+            };
+            assert_eq!(call("viewer").await.unwrap().status(), StatusCode::FORBIDDEN, "{}", path);
+            assert_eq!(call("admin").await.unwrap().status(), StatusCode::OK, "{}", path);
+        }
+        assert!(require_payout_approver(&auth.verify_token(&tokens["payout"]).unwrap()).is_ok());
+        assert!(require_admin(&auth.verify_token(&tokens["payout"]).unwrap()).is_err());
+        assert!(require_operator(&auth.verify_token(&tokens["operator"]).unwrap()).is_ok());
+    }
+}
@@ -60,14 +60,21 @@ async fn health_handler() -> Json<HealthStatus> {
             shares_per_second: 0.0,
             current_difficulty: 0.0,
             message: "Not initialized".to_string(),
+            jobs: None,
         },
         zmq: ComponentStatus {
             status: "unknown".to_string(),
             message: "Not initialized".to_string(),
             latency_ms: None,
         },
+        zmq_topics: None,
         uptime_seconds: 0,
         memory_mb: None,
+        postgres: None,
+        runtime: None,
+        leadership: None,
+        payout_pause: None,
+        schema: None,
     })
 }
 
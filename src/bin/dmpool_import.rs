@@ -0,0 +1,82 @@
+// DMPool History Import
+// Loads payouts and balances from a previous pool into the payment ledger
+//
+// Stop the admin server (or whichever process owns the payment data
+// directory) first: it keeps the ledger in memory and would overwrite the
+// import on its next save. POST /api/payments/import does the same while
+// the admin server is running.
+
+use anyhow::Result;
+use bitcoin::Network;
+use clap::Parser;
+use dmpool::db::DatabaseManager;
+use dmpool::payment::{ImportFormat, ImportKind, ImportOptions, PaymentConfig, PaymentManager};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[command(about = "Import payout history and balances from another pool")]
+struct Args {
+    /// payouts or balances
+    #[arg(long)]
+    kind: ImportKind,
+
+    /// CSV (with a header row) or JSON array
+    file: PathBuf,
+
+    /// csv or json; guessed from the file extension when omitted
+    #[arg(long)]
+    format: Option<ImportFormat>,
+
+    /// Network the addresses must belong to
+    #[arg(long, default_value = "bitcoin")]
+    network: Network,
+
+    /// Reject the file unless its amounts add up to this many satoshis
+    #[arg(long)]
+    expected_total: Option<u64>,
+
+    /// Validate only
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Payment data directory
+    #[arg(long, default_value = "./data/payments")]
+    data_dir: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let format = match args.format.or_else(|| ImportFormat::from_path(&args.file)) {
+        Some(format) => format,
+        None => return Err(anyhow::anyhow!("Cannot tell the format of {}, pass --format", args.file.display())),
+    };
+    let data = tokio::fs::read(&args.file).await
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.file.display(), e))?;
+
+    // Imported payouts also go to payout history when DATABASE_URL is set
+    let mut payments = PaymentManager::new(args.data_dir, PaymentConfig::default())?;
+    if let Ok(database_url) = std::env::var("DATABASE_URL") {
+        let db = Arc::new(DatabaseManager::new(&database_url)?);
+        db.init_admin_tables().await?;
+        payments = payments.with_recorder(db);
+    }
+    payments.load().await?;
+
+    let report = payments.import_history(&data, &ImportOptions {
+        kind: args.kind,
+        format,
+        network: args.network,
+        expected_total_satoshis: args.expected_total,
+        dry_run: args.dry_run,
+    }).await?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.errors.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
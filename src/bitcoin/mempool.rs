@@ -0,0 +1,196 @@
+// Mempool monitoring from bitcoind ZMQ
+//
+// Transactions announced on rawtx (or hashtx when rawtx isn't subscribed)
+// make up a rolling arrival rate. Payout transactions the pool broadcast are
+// watched from their PayoutBroadcast event until a block includes them; one
+// bitcoind never announced has most likely not propagated and needs a rebroadcast.
+
+use chrono::{DateTime, Utc};
+use crate::bitcoin::zmq::{ZmqConfig, ZmqEvent};
+use crate::events::PoolEvent;
+use crate::runtime_metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+use tracing::warn;
+
+/// Period the arrival rate is measured over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// A watched payout transaction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WatchedTx {
+    pub txid: String,
+    pub payout_id: Option<String>,
+    pub broadcast_at: DateTime<Utc>,
+    /// When bitcoind announced it, if it has
+    pub seen_in_mempool_at: Option<DateTime<Utc>>,
+}
+
+/// Mempool activity at one point in time
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MempoolStatus {
+    pub txs_last_minute: usize,
+    pub txs_total: u64,
+    pub tx_vbytes_total: u64,
+    pub blocks_total: u64,
+    pub last_block_hash: Option<String>,
+    /// Payout transactions not yet in a block
+    pub watched: Vec<WatchedTx>,
+}
+
+impl MempoolStatus {
+    /// Watched payouts bitcoind has not announced
+    pub fn unseen(&self) -> usize {
+        self.watched.iter().filter(|tx| tx.seen_in_mempool_at.is_none()).count()
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        gauge(&mut out, "dmpool_mempool_txs_last_minute", "Transactions announced by bitcoind in the last minute", self.txs_last_minute as f64);
+        counter(&mut out, "dmpool_mempool_txs_total", "Transactions announced by bitcoind", self.txs_total as f64);
+        counter(&mut out, "dmpool_zmq_blocks_total", "Blocks announced by bitcoind", self.blocks_total as f64);
+        gauge(&mut out, "dmpool_payout_txs_unconfirmed", "Broadcast payout transactions not yet in a block", self.watched.len() as f64);
+        gauge(&mut out, "dmpool_payout_txs_unseen", "Broadcast payout transactions bitcoind has not announced", self.unseen() as f64);
+        out
+    }
+}
+
+#[derive(Default)]
+struct MempoolState {
+    arrivals: VecDeque<Instant>,
+    txs_total: u64,
+    tx_vbytes_total: u64,
+    blocks_total: u64,
+    last_block_hash: Option<String>,
+    watched: HashMap<String, WatchedTx>,
+}
+
+/// Mempool arrival rate and payout transaction propagation
+pub struct MempoolMonitor {
+    /// Count hashtx announcements, when there is no rawtx subscription
+    count_hashtx: bool,
+    state: RwLock<MempoolState>,
+}
+
+impl MempoolMonitor {
+    pub fn new(config: &ZmqConfig) -> Self {
+        Self {
+            count_hashtx: config.rawtx.is_none(),
+            state: RwLock::new(MempoolState::default()),
+        }
+    }
+
+    /// Watch a broadcast payout transaction until it is mined
+    ///
+    /// A fee-bumped payout's replacement takes the place of the original.
+    pub async fn watch(&self, txid: &str, payout_id: Option<String>) {
+        let mut state = self.state.write().await;
+        if let Some(id) = &payout_id {
+            state.watched.retain(|watched_txid, tx| watched_txid == txid || tx.payout_id.as_ref() != Some(id));
+        }
+        state.watched.entry(txid.to_string()).or_insert_with(|| WatchedTx {
+            txid: txid.to_string(),
+            payout_id,
+            broadcast_at: Utc::now(),
+            seen_in_mempool_at: None,
+        });
+    }
+
+    pub async fn record(&self, event: &ZmqEvent) {
+        let mut state = self.state.write().await;
+        match event {
+            ZmqEvent::Tx { txid, vsize } => {
+                state.tx_vbytes_total += vsize;
+                Self::arrived(&mut state, txid, true);
+            }
+            ZmqEvent::TxHash { txid } => Self::arrived(&mut state, txid, self.count_hashtx),
+            ZmqEvent::Block { hash, txids } => {
+                state.blocks_total += 1;
+                state.last_block_hash = Some(hash.clone());
+                for txid in txids {
+                    state.watched.remove(txid);
+                }
+            }
+        }
+    }
+
+    fn arrived(state: &mut MempoolState, txid: &str, count: bool) {
+        if count {
+            let now = Instant::now();
+            state.arrivals.push_back(now);
+            while state.arrivals.front().is_some_and(|at| now.duration_since(*at) > RATE_WINDOW) {
+                state.arrivals.pop_front();
+            }
+            state.txs_total += 1;
+        }
+        if let Some(watched) = state.watched.get_mut(txid) {
+            watched.seen_in_mempool_at.get_or_insert_with(Utc::now);
+        }
+    }
+
+    pub async fn status(&self) -> MempoolStatus {
+        let state = self.state.read().await;
+        let mut watched: Vec<WatchedTx> = state.watched.values().cloned().collect();
+        watched.sort_by_key(|tx| tx.broadcast_at);
+        MempoolStatus {
+            txs_last_minute: state.arrivals.iter().filter(|at| at.elapsed() <= RATE_WINDOW).count(),
+            txs_total: state.txs_total,
+            tx_vbytes_total: state.tx_vbytes_total,
+            blocks_total: state.blocks_total,
+            last_block_hash: state.last_block_hash.clone(),
+            watched,
+        }
+    }
+
+    /// Follow ZMQ notifications and payout broadcasts on the event bus
+    pub fn spawn(self: &Arc<Self>, mut zmq: broadcast::Receiver<ZmqEvent>, mut pool_events: broadcast::Receiver<PoolEvent>) {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match zmq.recv().await {
+                    Ok(event) => monitor.record(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => warn!("Mempool monitor skipped {} ZMQ notifications", missed),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match pool_events.recv().await {
+                    Ok(PoolEvent::PayoutBroadcast { payout_id, txid: Some(txid), .. }) => monitor.watch(&txid, Some(payout_id)).await,
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(missed)) => warn!("Mempool monitor skipped {} pool events", missed),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watched_payout_until_mined() {
+        let monitor = MempoolMonitor::new(&ZmqConfig { rawtx: Some("tcp://127.0.0.1:28333".to_string()), ..ZmqConfig::default() });
+        monitor.watch("aa", Some("payout-1".to_string())).await;
+        monitor.watch("bb", None).await;
+
+        monitor.record(&ZmqEvent::Tx { txid: "aa".to_string(), vsize: 141 }).await;
+        // Not counted twice when rawtx is subscribed too
+        monitor.record(&ZmqEvent::TxHash { txid: "aa".to_string() }).await;
+        let status = monitor.status().await;
+        assert_eq!((status.txs_last_minute, status.txs_total, status.unseen()), (1, 1, 1));
+
+        monitor.record(&ZmqEvent::Block { hash: "00".to_string(), txids: vec!["aa".to_string()] }).await;
+        let status = monitor.status().await;
+        assert_eq!(status.watched.len(), 1);
+        assert_eq!(status.watched[0].txid, "bb");
+        assert!(status.to_prometheus().contains("dmpool_payout_txs_unseen 1"));
+    }
+}
@@ -0,0 +1,210 @@
+// Mock Bitcoin RPC server for tests
+//
+// Serves JSON-RPC on a local port like bitcoind: errors come back as HTTP 500
+// (404 for unknown methods) with an `error` object. The methods payouts and
+// health checks use answer with defaults that make a payout succeed; any
+// method can be scripted with a fixed reply, queued one-off replies or
+// injected failures. Every call is recorded with its params.
+
+use super::BitcoinRpcClient;
+use anyhow::{Context, Result};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// bitcoind's code for unknown methods
+pub const RPC_METHOD_NOT_FOUND: i64 = -32601;
+
+pub use super::RPC_INVALID_ADDRESS_OR_KEY;
+
+/// A reply to one call
+#[derive(Clone, Debug)]
+pub enum MockReply {
+    Result(Value),
+    /// JSON-RPC error, sent with HTTP 500 like bitcoind
+    Error { code: i64, message: String },
+    /// Bare HTTP status, e.g. 401 for bad credentials or 503 while warming up
+    Status(u16),
+}
+
+#[derive(Default)]
+struct MockState {
+    fixed: HashMap<String, MockReply>,
+    queued: HashMap<String, VecDeque<MockReply>>,
+    calls: Vec<(String, Vec<Value>)>,
+}
+
+/// Deterministic txid of a raw transaction hex
+pub fn mock_txid(hex: &str) -> String {
+    format!("{:x}", Sha256::digest(hex.as_bytes()))
+}
+
+/// Reply of a method that is not scripted
+fn default_reply(method: &str, params: &[Value]) -> MockReply {
+    let first = params.first().and_then(Value::as_str).unwrap_or_default();
+    match method {
+        "getblockchaininfo" => MockReply::Result(json!({
+            "chain": "regtest",
+            "blocks": 200,
+            "headers": 200,
+            "difficulty": 4.6e-10,
+            "initial_block_download": false,
+        })),
+        "getblockcount" => MockReply::Result(json!(200)),
+        "listunspent" | "listtransactions" => MockReply::Result(json!([])),
+        "createrawtransaction" => MockReply::Result(json!(format!("02000000{}", mock_txid(&params_key(params))))),
+        "signrawtransactionwithwallet" => MockReply::Result(json!({ "hex": first, "complete": true })),
+        "decoderawtransaction" => MockReply::Result(json!({
+            "txid": mock_txid(first),
+            "hash": mock_txid(first),
+            "version": 2,
+            "size": first.len() / 2,
+            "vsize": first.len() / 2,
+            "weight": first.len() * 2,
+            "locktime": 0,
+            "vin": [],
+            "vout": [],
+        })),
+        "sendrawtransaction" => MockReply::Result(json!(mock_txid(first))),
+        "estimatesmartfee" => MockReply::Result(json!({ "feerate": 0.00001, "blocks": 2 })),
+        "gettransaction" => MockReply::Error {
+            code: RPC_INVALID_ADDRESS_OR_KEY,
+            message: "Invalid or non-wallet transaction id".to_string(),
+        },
+        "getrawtransaction" => MockReply::Error {
+            code: RPC_INVALID_ADDRESS_OR_KEY,
+            message: "No such mempool or blockchain transaction".to_string(),
+        },
+        "getmempoolentry" => MockReply::Error {
+            code: RPC_INVALID_ADDRESS_OR_KEY,
+            message: "Transaction not in mempool".to_string(),
+        },
+        _ => MockReply::Error { code: RPC_METHOD_NOT_FOUND, message: "Method not found".to_string() },
+    }
+}
+
+fn params_key(params: &[Value]) -> String {
+    Value::Array(params.to_vec()).to_string()
+}
+
+async fn handle(State(state): State<Arc<Mutex<MockState>>>, Json(request): Json<Value>) -> impl IntoResponse {
+    let method = request["method"].as_str().unwrap_or_default().to_string();
+    let params = request["params"].as_array().cloned().unwrap_or_default();
+    let id = request["id"].clone();
+
+    let reply = {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.calls.push((method.clone(), params.clone()));
+        state.queued.get_mut(&method).and_then(VecDeque::pop_front)
+            .or_else(|| state.fixed.get(&method).cloned())
+            .unwrap_or_else(|| default_reply(&method, &params))
+    };
+
+    match reply {
+        MockReply::Result(result) => (StatusCode::OK, Json(json!({ "result": result, "error": null, "id": id }))).into_response(),
+        MockReply::Error { code, message } => {
+            let status = if code == RPC_METHOD_NOT_FOUND { StatusCode::NOT_FOUND } else { StatusCode::INTERNAL_SERVER_ERROR };
+            (status, Json(json!({ "result": null, "error": { "code": code, "message": message }, "id": id }))).into_response()
+        }
+        MockReply::Status(status) => StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR).into_response(),
+    }
+}
+
+/// A scriptable bitcoind stand-in on a local port; stops when dropped
+pub struct MockRpcServer {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockRpcServer {
+    pub async fn start() -> Result<Self> {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await
+            .context("Failed to bind mock RPC server")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let app = axum::Router::new()
+            .fallback(handle)
+            .with_state(state.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self { url, state, server })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Client for the mock (any credentials are accepted)
+    pub fn client(&self) -> BitcoinRpcClient {
+        BitcoinRpcClient::new(self.url.clone(), "mock".to_string(), "mock".to_string())
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut MockState) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Answer every call of `method` with `result`
+    pub fn respond(&self, method: &str, result: Value) {
+        self.reply(method, MockReply::Result(result));
+    }
+
+    /// Answer every call of `method` with `reply`
+    pub fn reply(&self, method: &str, reply: MockReply) {
+        self.with_state(|state| state.fixed.insert(method.to_string(), reply));
+    }
+
+    /// Answer the next call of `method` with `reply`, before any fixed reply
+    pub fn reply_once(&self, method: &str, reply: MockReply) {
+        self.with_state(|state| state.queued.entry(method.to_string()).or_default().push_back(reply));
+    }
+
+    /// Fail the next `times` calls of `method` with an RPC error
+    pub fn fail_next(&self, method: &str, times: usize, message: &str) {
+        for _ in 0..times {
+            self.reply_once(method, MockReply::Error { code: -1, message: message.to_string() });
+        }
+    }
+
+    /// Params of each call of `method`, oldest first
+    pub fn calls(&self, method: &str) -> Vec<Vec<Value>> {
+        self.with_state(|state| state.calls.iter()
+            .filter(|(called, _)| called == method)
+            .map(|(_, params)| params.clone())
+            .collect())
+    }
+}
+
+impl Drop for MockRpcServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scripted_replies_and_failures() {
+        let mock = MockRpcServer::start().await.unwrap();
+        let client = mock.client();
+        assert_eq!(client.get_blockchain_info().await.unwrap().chain, "regtest");
+
+        mock.respond("listunspent", json!([{ "txid": "aa", "vout": 1, "address": "bcrt1qpool", "amount": 1.5, "confirmations": 6 }]));
+        mock.fail_next("listunspent", 1, "Loading wallet...");
+        let err = client.list_unspent(Some(1), None).await.unwrap_err();
+        assert!(err.to_string().contains("Loading wallet..."));
+        let unspent = client.list_unspent(Some(1), None).await.unwrap();
+        assert_eq!((unspent[0].txid.as_str(), unspent[0].amount), ("aa", 1.5));
+        assert_eq!(mock.calls("listunspent")[1], vec![json!(1), json!(999999)]);
+
+        mock.reply_once("sendrawtransaction", MockReply::Status(503));
+        assert!(client.send_raw_transaction("0200").await.is_err());
+        assert_eq!(client.send_raw_transaction("0200").await.unwrap(), mock_txid("0200"));
+        assert!(client.get_mempool_info().await.unwrap_err().to_string().contains("Method not found"));
+    }
+}
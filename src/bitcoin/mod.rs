@@ -1,12 +1,25 @@
 // Bitcoin RPC Client for DMPool
-// Handles communication with Bitcoin node for transaction creation and broadcasting
+// Handles communication with Bitcoin node for transaction creation and broadcasting,
+// plus ZMQ block and transaction notifications
 
+pub mod mempool;
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock;
+pub mod zmq;
+
+pub use mempool::{MempoolMonitor, MempoolStatus, WatchedTx};
+pub use zmq::{ZmqConfig, ZmqEvent, ZmqSubscriptions, ZmqTopic, ZmqTopicStatus};
+
+use crate::logging::request_id::{current_request_id, REQUEST_ID_HEADER};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::time::Duration;
 use tracing::{error, info, warn};
 
+/// bitcoind's code for unknown transactions and addresses
+pub const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+
 /// Bitcoin RPC client
 pub struct BitcoinRpcClient {
     url: String,
@@ -32,28 +45,39 @@ impl BitcoinRpcClient {
     }
 
     /// Execute a raw RPC call
+    ///
+    /// Calls made while handling an API request carry its correlation ID as
+    /// the JSON-RPC id and in an X-Request-Id header.
     async fn call(&self, method: &str, params: Vec<serde_json::Value>) -> Result<serde_json::Value> {
+        let request_id = current_request_id();
         let request_body = json!({
             "jsonrpc": "1.0",
-            "id": "1",
+            "id": request_id.as_deref().unwrap_or("1"),
             "method": method,
             "params": params
         });
 
-        let response = self.client
+        let mut request = self.client
             .post(&self.url)
-            .basic_auth(&self.username, Some(&self.password))
+            .basic_auth(&self.username, Some(&self.password));
+        if let Some(request_id) = &request_id {
+            request = request.header(REQUEST_ID_HEADER, request_id);
+        }
+
+        let response = request
             .json(&request_body)
             .send()
             .await
             .context("Failed to send RPC request")?;
 
         if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "RPC request failed with status {}: {}",
-                response.status(),
-                response.text().await.unwrap_or_default()
-            ));
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            // bitcoind sends RPC errors with HTTP 500
+            if let Ok(RpcResponse { error: Some(error), .. }) = serde_json::from_str(&body) {
+                return Err(error.into());
+            }
+            return Err(anyhow::anyhow!("RPC request failed with status {}: {}", status, body));
         }
 
         let response_text = response.text().await.context("Failed to read response")?;
@@ -61,7 +85,7 @@ impl BitcoinRpcClient {
             .context("Failed to parse RPC response")?;
 
         if let Some(error) = rpc_response.error {
-            return Err(anyhow::anyhow!("RPC error: {}", error.message));
+            return Err(error.into());
         }
 
         rpc_response.result.ok_or_else(|| anyhow::anyhow!("RPC response missing result"))
@@ -79,6 +103,52 @@ impl BitcoinRpcClient {
         serde_json::from_value(result).context("Failed to parse block count")
     }
 
+    /// Get block hash at a height
+    pub async fn get_block_hash(&self, height: u64) -> Result<String> {
+        let result = self.call("getblockhash", vec![json!(height)]).await?;
+        serde_json::from_value(result).context("Failed to parse block hash")
+    }
+
+    /// Get a block header; `confirmations` is -1 once the block is off the active chain
+    pub async fn get_block_header(&self, block_hash: &str) -> Result<BlockHeaderInfo> {
+        let result = self.call("getblockheader", vec![json!(block_hash), json!(true)]).await?;
+        serde_json::from_value(result).context("Failed to parse block header")
+    }
+
+    /// Get the coinbase transaction of a block
+    pub async fn get_block_coinbase(&self, block_hash: &str) -> Result<CoinbaseInfo> {
+        let block = self.call("getblock", vec![json!(block_hash), json!(2)]).await?;
+        let coinbase = block["tx"].get(0)
+            .ok_or_else(|| anyhow::anyhow!("Block {} has no transactions", block_hash))?;
+
+        let outputs: Vec<CoinbaseVout> = coinbase["vout"].as_array()
+            .map(|outputs| outputs.iter()
+                .filter_map(|o| Some(CoinbaseVout {
+                    script_pubkey_hex: o["scriptPubKey"]["hex"].as_str().unwrap_or_default().to_string(),
+                    satoshis: (o["value"].as_f64()? * 100_000_000.0).round() as u64,
+                }))
+                .collect())
+            .unwrap_or_default();
+
+        Ok(CoinbaseInfo {
+            txid: coinbase["txid"].as_str().unwrap_or_default().to_string(),
+            script_sig_hex: coinbase["vin"][0]["coinbase"].as_str().unwrap_or_default().to_string(),
+            total_output_satoshis: outputs.iter().map(|o| o.satoshis).sum(),
+            outputs,
+            difficulty: block["difficulty"].as_f64().unwrap_or(0.0),
+        })
+    }
+
+    /// Height and coinbase value (subsidy + fees) of the next block template
+    pub async fn get_template_reward(&self) -> Result<(u64, u64)> {
+        let template = self.call("getblocktemplate", vec![json!({ "rules": ["segwit"] })]).await?;
+        let height = template["height"].as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Block template missing height"))?;
+        let value = template["coinbasevalue"].as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Block template missing coinbasevalue"))?;
+        Ok((height, value))
+    }
+
     /// Get network hashps (estimated network hashrate)
     pub async fn get_network_hash_ps(&self, blocks: u32, height: Option<u64>) -> Result<f64> {
         let params = if let Some(h) = height {
@@ -102,6 +172,40 @@ impl BitcoinRpcClient {
         serde_json::from_value(result).context("Failed to parse raw transaction")
     }
 
+    /// Confirmations of a wallet transaction (errors if the wallet doesn't know it)
+    pub async fn get_wallet_transaction_confirmations(&self, txid: &str) -> Result<i64> {
+        let result = self.call("gettransaction", vec![json!(txid)]).await?;
+        result["confirmations"].as_i64()
+            .ok_or_else(|| anyhow::anyhow!("Wallet transaction {} has no confirmations field", txid))
+    }
+
+    /// Mempool entry of a transaction, or None if it isn't in the mempool
+    pub async fn get_mempool_entry(&self, txid: &str) -> Result<Option<MempoolEntry>> {
+        match self.call("getmempoolentry", vec![json!(txid)]).await {
+            Ok(result) => serde_json::from_value(result).map(Some).context("Failed to parse mempool entry"),
+            Err(e) if rpc_error_code(&e) == Some(RPC_INVALID_ADDRESS_OR_KEY) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Wallet view of a transaction: confirmations (negative once a conflict is mined) and conflicts
+    pub async fn get_wallet_transaction(&self, txid: &str) -> Result<WalletTxStatus> {
+        let result = self.call("gettransaction", vec![json!(txid)]).await?;
+        serde_json::from_value(result).context("Failed to parse wallet transaction")
+    }
+
+    /// Replace a wallet transaction with one paying `fee_rate_sat_vb` (BIP 125)
+    pub async fn bump_fee(&self, txid: &str, fee_rate_sat_vb: f64) -> Result<BumpFeeResult> {
+        let result = self.call("bumpfee", vec![json!(txid), json!({ "fee_rate": fee_rate_sat_vb })]).await?;
+        serde_json::from_value(result).context("Failed to parse bumpfee result")
+    }
+
+    /// Verify a message signed with an address's key
+    pub async fn verify_message(&self, address: &str, signature: &str, message: &str) -> Result<bool> {
+        let result = self.call("verifymessage", vec![json!(address), json!(signature), json!(message)]).await?;
+        serde_json::from_value(result).context("Failed to parse message verification")
+    }
+
     /// Decode raw transaction
     pub async fn decode_raw_transaction(&self, hex: &str) -> Result<DecodedTransaction> {
         let result = self.call("decoderawtransaction", vec![json!(hex)]).await?;
@@ -140,6 +244,40 @@ impl BitcoinRpcClient {
         serde_json::from_value(result).context("Failed to broadcast transaction")
     }
 
+    /// Send from the wallet to an address, returning the txid
+    ///
+    /// With `subtract_fee` the network fee comes out of the amount sent.
+    pub async fn send_to_address(&self, address: &str, amount_btc: f64, subtract_fee: bool) -> Result<String> {
+        let result = self.call(
+            "sendtoaddress",
+            vec![json!(address), json!(amount_btc), json!(""), json!(""), json!(subtract_fee)]
+        ).await?;
+        serde_json::from_value(result).context("Failed to parse sendtoaddress result")
+    }
+
+    /// Confirmed balance of an address from the UTXO set (BTC), wallet or not
+    pub async fn scan_address_balance(&self, address: &str) -> Result<f64> {
+        let result = self.call(
+            "scantxoutset",
+            vec![json!("start"), json!([format!("addr({})", address)])]
+        ).await?;
+        result.get("total_amount")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(|| anyhow::anyhow!("scantxoutset result missing total_amount"))
+    }
+
+    /// New receiving address from the wallet
+    pub async fn get_new_address(&self) -> Result<String> {
+        let result = self.call("getnewaddress", vec![]).await?;
+        serde_json::from_value(result).context("Failed to parse new address")
+    }
+
+    /// Mine blocks paying `address` (regtest only), returning their hashes
+    pub async fn generate_to_address(&self, blocks: u32, address: &str) -> Result<Vec<String>> {
+        let result = self.call("generatetoaddress", vec![json!(blocks), json!(address)]).await?;
+        serde_json::from_value(result).context("Failed to parse generated block hashes")
+    }
+
     /// Get wallet info
     pub async fn get_wallet_info(&self) -> Result<WalletInfo> {
         let result = self.call("getwalletinfo", vec![]).await?;
@@ -161,6 +299,15 @@ impl BitcoinRpcClient {
         serde_json::from_value(result).context("Failed to parse unspent outputs")
     }
 
+    /// List wallet transactions, most recent last
+    pub async fn list_transactions(&self, count: u32, skip: u32) -> Result<Vec<WalletTransaction>> {
+        let result = self.call(
+            "listtransactions",
+            vec![json!("*"), json!(count), json!(skip)]
+        ).await?;
+        serde_json::from_value(result).context("Failed to parse wallet transactions")
+    }
+
     /// Estimate smart fee
     pub async fn estimate_smart_fee(&self, conf_target: u32) -> Result<f64> {
         let result = self.call("estimatesmartfee", vec![json!(conf_target)]).await?;
@@ -195,9 +342,18 @@ struct RpcResponse {
     error: Option<RpcError>,
 }
 
-#[derive(Debug, Deserialize)]
-struct RpcError {
-    message: String,
+/// Error returned by bitcoind for a call
+#[derive(Debug, Deserialize, thiserror::Error)]
+#[error("RPC error: {message}")]
+pub struct RpcError {
+    #[serde(default)]
+    pub code: i64,
+    pub message: String,
+}
+
+/// The bitcoind error code of a failed call, if it got that far
+pub fn rpc_error_code(err: &anyhow::Error) -> Option<i64> {
+    err.downcast_ref::<RpcError>().map(|e| e.code)
 }
 
 /// Blockchain info
@@ -210,6 +366,37 @@ pub struct BlockchainInfo {
     pub initial_block_download: bool,
 }
 
+/// Block header
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockHeaderInfo {
+    pub hash: String,
+    pub height: u64,
+    pub confirmations: i64,
+    pub time: u64,
+    pub nonce: u32,
+}
+
+/// Coinbase transaction summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoinbaseInfo {
+    pub txid: String,
+    /// Coinbase input script (hex)
+    pub script_sig_hex: String,
+    /// Sum of coinbase outputs (subsidy + fees)
+    pub total_output_satoshis: u64,
+    #[serde(default)]
+    pub outputs: Vec<CoinbaseVout>,
+    /// Difficulty of the block
+    pub difficulty: f64,
+}
+
+/// A coinbase transaction output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinbaseVout {
+    pub script_pubkey_hex: String,
+    pub satoshis: u64,
+}
+
 /// Mempool info
 #[derive(Debug, Clone, Deserialize)]
 pub struct MempoolInfo {
@@ -219,6 +406,51 @@ pub struct MempoolInfo {
     pub maxmempool: f64,
 }
 
+/// A transaction's mempool entry
+#[derive(Debug, Clone, Deserialize)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    /// Unix time it entered the mempool
+    pub time: i64,
+    pub fees: MempoolEntryFees,
+    #[serde(default, rename = "bip125-replaceable")]
+    pub bip125_replaceable: bool,
+}
+
+/// Fees of a mempool entry (BTC)
+#[derive(Debug, Clone, Deserialize)]
+pub struct MempoolEntryFees {
+    pub base: f64,
+}
+
+impl MempoolEntry {
+    pub fn fee_rate_sat_vb(&self) -> f64 {
+        (self.fees.base * 100_000_000.0).round() / self.vsize.max(1) as f64
+    }
+}
+
+/// Wallet view of a transaction
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalletTxStatus {
+    pub confirmations: i64,
+    /// Wallet transactions spending the same inputs
+    #[serde(default)]
+    pub walletconflicts: Vec<String>,
+    /// Set once the transaction was replaced (BIP 125)
+    #[serde(default)]
+    pub replaced_by_txid: Option<String>,
+}
+
+/// Result of `bumpfee`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BumpFeeResult {
+    pub txid: String,
+    /// BTC
+    pub origfee: f64,
+    /// BTC
+    pub fee: f64,
+}
+
 /// Decoded transaction
 #[derive(Debug, Clone, Deserialize)]
 pub struct DecodedTransaction {
@@ -295,6 +527,24 @@ pub struct WalletInfo {
     pub txcount: u64,
 }
 
+/// Wallet transaction entry (one per output for sends)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletTransaction {
+    pub txid: String,
+    /// "send", "receive", "generate", ...
+    pub category: String,
+    /// BTC, negative for sends
+    pub amount: f64,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub vout: Option<u32>,
+    /// Negative if the transaction conflicts with the chain
+    pub confirmations: i64,
+    /// Unix time
+    pub time: u64,
+}
+
 /// Unspent output
 #[derive(Debug, Clone, Deserialize)]
 pub struct UnspentOutput {
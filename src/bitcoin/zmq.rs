@@ -0,0 +1,453 @@
+// bitcoind ZMQ subscriptions
+//
+// The stratum consumes zmqpubhashblock through p2poolv2's listener only to
+// refetch templates. Payout confirmations and mempool monitoring subscribe
+// here to rawblock, rawtx and hashtx. Topics published on the same endpoint
+// share one SUB socket. A socket that fails or stays silent for
+// `idle_reconnect_secs` is reconnected with exponential backoff. bitcoind
+// numbers each topic's messages, so gaps are counted as missed messages.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::runtime_metrics::labelled;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+/// Decoded notifications buffered for slow subscribers
+const EVENT_CHANNEL_CAPACITY: usize = 4096;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A bitcoind ZMQ notification topic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZmqTopic {
+    RawBlock,
+    RawTx,
+    HashTx,
+}
+
+impl ZmqTopic {
+    pub const ALL: [ZmqTopic; 3] = [ZmqTopic::RawBlock, ZmqTopic::RawTx, ZmqTopic::HashTx];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RawBlock => "rawblock",
+            Self::RawTx => "rawtx",
+            Self::HashTx => "hashtx",
+        }
+    }
+
+    fn parse(name: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|topic| topic.as_str().as_bytes() == name)
+    }
+}
+
+/// The `[dmpool.zmq]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZmqConfig {
+    pub enabled: bool,
+    /// bitcoind `zmqpubrawblock` endpoint
+    pub rawblock: Option<String>,
+    /// bitcoind `zmqpubrawtx` endpoint
+    pub rawtx: Option<String>,
+    /// bitcoind `zmqpubhashtx` endpoint
+    pub hashtx: Option<String>,
+    /// First reconnect delay; doubles up to `reconnect_max_secs`
+    pub reconnect_initial_ms: u64,
+    pub reconnect_max_secs: u64,
+    /// Reconnect a socket that received nothing for this long
+    pub idle_reconnect_secs: u64,
+    /// A block topic silent for this long is reported stale
+    pub block_stale_secs: u64,
+    /// A transaction topic silent for this long is reported stale
+    pub tx_stale_secs: u64,
+}
+
+impl Default for ZmqConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rawblock: None,
+            rawtx: None,
+            hashtx: None,
+            reconnect_initial_ms: 500,
+            reconnect_max_secs: 30,
+            idle_reconnect_secs: 900,
+            block_stale_secs: 3600,
+            tx_stale_secs: 300,
+        }
+    }
+}
+
+impl ZmqConfig {
+    pub fn validate(&self) -> Result<()> {
+        if ZmqTopic::ALL.iter().all(|topic| self.endpoint(*topic).is_none()) {
+            return Err(anyhow::anyhow!("set at least one of rawblock, rawtx or hashtx"));
+        }
+        for topic in ZmqTopic::ALL {
+            if let Some(endpoint) = self.endpoint(topic) {
+                if !endpoint.starts_with("tcp://") && !endpoint.starts_with("ipc://") {
+                    return Err(anyhow::anyhow!("{} endpoint {} must start with tcp:// or ipc://", topic.as_str(), endpoint));
+                }
+            }
+        }
+        if self.reconnect_initial_ms == 0 || self.reconnect_initial_ms > self.reconnect_max_secs * 1000 {
+            return Err(anyhow::anyhow!("reconnect_initial_ms must be between 1 and reconnect_max_secs"));
+        }
+        if self.idle_reconnect_secs == 0 || self.block_stale_secs == 0 || self.tx_stale_secs == 0 {
+            return Err(anyhow::anyhow!("idle_reconnect_secs, block_stale_secs and tx_stale_secs must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Endpoint a topic is subscribed on, if any
+    pub fn endpoint(&self, topic: ZmqTopic) -> Option<&str> {
+        match topic {
+            ZmqTopic::RawBlock => self.rawblock.as_deref(),
+            ZmqTopic::RawTx => self.rawtx.as_deref(),
+            ZmqTopic::HashTx => self.hashtx.as_deref(),
+        }
+    }
+
+    fn stale_after(&self, topic: ZmqTopic) -> Duration {
+        match topic {
+            ZmqTopic::RawBlock => Duration::from_secs(self.block_stale_secs),
+            ZmqTopic::RawTx | ZmqTopic::HashTx => Duration::from_secs(self.tx_stale_secs),
+        }
+    }
+}
+
+/// A decoded notification; hashes and txids in RPC byte order
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZmqEvent {
+    /// From rawblock
+    Block { hash: String, txids: Vec<String> },
+    /// From rawtx
+    Tx { txid: String, vsize: u64 },
+    /// From hashtx
+    TxHash { txid: String },
+}
+
+/// Decode a message body of a topic
+pub fn decode(topic: ZmqTopic, body: &[u8]) -> Result<ZmqEvent> {
+    match topic {
+        ZmqTopic::RawBlock => {
+            let block: ::bitcoin::Block = ::bitcoin::consensus::deserialize(body).context("Invalid rawblock body")?;
+            Ok(ZmqEvent::Block {
+                hash: block.block_hash().to_string(),
+                txids: block.txdata.iter().map(|tx| tx.compute_txid().to_string()).collect(),
+            })
+        }
+        ZmqTopic::RawTx => {
+            let tx: ::bitcoin::Transaction = ::bitcoin::consensus::deserialize(body).context("Invalid rawtx body")?;
+            Ok(ZmqEvent::Tx { txid: tx.compute_txid().to_string(), vsize: tx.vsize() as u64 })
+        }
+        ZmqTopic::HashTx => {
+            if body.len() != 32 {
+                return Err(anyhow::anyhow!("hashtx body is {} bytes, expected 32", body.len()));
+            }
+            // bitcoind already sends the hash byte-reversed
+            Ok(ZmqEvent::TxHash { txid: body.iter().map(|b| format!("{:02x}", b)).collect() })
+        }
+    }
+}
+
+/// Health of one topic subscription
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZmqTopicStatus {
+    pub topic: ZmqTopic,
+    pub endpoint: String,
+    /// healthy, degraded when silent for longer than its stale threshold, unhealthy when disconnected
+    pub status: String,
+    pub connected: bool,
+    pub last_message_at: Option<DateTime<Utc>>,
+    pub seconds_since_message: Option<u64>,
+    pub messages_total: u64,
+    /// Sequence numbers skipped by bitcoind's publisher
+    pub missed_total: u64,
+    pub decode_errors_total: u64,
+    pub reconnects_total: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct TopicState {
+    connected: bool,
+    last_message: Option<(Instant, DateTime<Utc>)>,
+    last_sequence: Option<u32>,
+    messages_total: u64,
+    missed_total: u64,
+    decode_errors_total: u64,
+    reconnects_total: u64,
+    last_error: Option<String>,
+}
+
+/// Subscriptions to bitcoind's ZMQ publishers
+pub struct ZmqSubscriptions {
+    config: ZmqConfig,
+    topics: RwLock<BTreeMap<ZmqTopic, TopicState>>,
+    events: broadcast::Sender<ZmqEvent>,
+}
+
+impl ZmqSubscriptions {
+    pub fn new(config: ZmqConfig) -> Self {
+        let topics = ZmqTopic::ALL.into_iter()
+            .filter(|topic| config.endpoint(*topic).is_some())
+            .map(|topic| (topic, TopicState::default()))
+            .collect();
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { config, topics: RwLock::new(topics), events }
+    }
+
+    pub fn config(&self) -> &ZmqConfig {
+        &self.config
+    }
+
+    /// Receive decoded notifications
+    pub fn subscribe(&self) -> broadcast::Receiver<ZmqEvent> {
+        self.events.subscribe()
+    }
+
+    /// Connect one socket per endpoint, reconnecting until the process exits
+    pub fn spawn(self: &Arc<Self>) {
+        let mut endpoints: BTreeMap<String, Vec<ZmqTopic>> = BTreeMap::new();
+        for topic in ZmqTopic::ALL {
+            if let Some(endpoint) = self.config.endpoint(topic) {
+                endpoints.entry(endpoint.to_string()).or_default().push(topic);
+            }
+        }
+        for (endpoint, topics) in endpoints {
+            let subscriptions = self.clone();
+            tokio::spawn(async move { subscriptions.run(endpoint, topics).await });
+        }
+    }
+
+    async fn run(&self, endpoint: String, topics: Vec<ZmqTopic>) {
+        let initial = Duration::from_millis(self.config.reconnect_initial_ms);
+        let max = Duration::from_secs(self.config.reconnect_max_secs);
+        let mut backoff = initial;
+        loop {
+            let received = self.messages_total(&topics).await;
+            let error = match self.session(&endpoint, &topics).await {
+                Ok(()) => None,
+                Err(e) => Some(format!("{:#}", e)),
+            };
+            match &error {
+                Some(e) => warn!("ZMQ {} disconnected: {}", endpoint, e),
+                None => info!("ZMQ {} silent for {}s, reconnecting", endpoint, self.config.idle_reconnect_secs),
+            }
+            self.set_disconnected(&topics, error).await;
+            // Messages got through, so the endpoint was working: retry quickly
+            if self.messages_total(&topics).await > received {
+                backoff = initial;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(max);
+        }
+    }
+
+    /// Receive from one connection; returns once it has been idle too long
+    async fn session(&self, endpoint: &str, topics: &[ZmqTopic]) -> Result<()> {
+        let mut socket = SubSocket::new();
+        timeout(CONNECT_TIMEOUT, socket.connect(endpoint)).await
+            .with_context(|| format!("Timed out connecting to {}", endpoint))?
+            .with_context(|| format!("Failed to connect to {}", endpoint))?;
+        for topic in topics {
+            socket.subscribe(topic.as_str()).await
+                .with_context(|| format!("Failed to subscribe to {}", topic.as_str()))?;
+        }
+        self.set_connected(topics).await;
+        info!("ZMQ subscribed to {} on {}", topics.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", "), endpoint);
+
+        let idle = Duration::from_secs(self.config.idle_reconnect_secs);
+        loop {
+            let message = match timeout(idle, socket.recv()).await {
+                Ok(message) => message.context("Failed to receive")?,
+                Err(_) => return Ok(()),
+            };
+            let frames = message.into_vec();
+            let frames: Vec<&[u8]> = frames.iter().map(|frame| frame.as_ref()).collect();
+            self.handle_message(&frames).await;
+        }
+    }
+
+    /// Record and publish one multipart message: topic, body, little-endian sequence number
+    pub(crate) async fn handle_message(&self, frames: &[&[u8]]) {
+        let Some(topic) = frames.first().and_then(|name| ZmqTopic::parse(name)) else {
+            debug!("Ignoring ZMQ message on an unknown topic");
+            return;
+        };
+        let sequence = frames.get(2)
+            .and_then(|bytes| <[u8; 4]>::try_from(*bytes).ok())
+            .map(u32::from_le_bytes);
+        let decoded = match frames.get(1) {
+            Some(body) => decode(topic, body),
+            None => Err(anyhow::anyhow!("{} message has no body", topic.as_str())),
+        };
+
+        {
+            let mut topics = self.topics.write().await;
+            let state = topics.entry(topic).or_default();
+            state.messages_total += 1;
+            state.last_message = Some((Instant::now(), Utc::now()));
+            if let (Some(last), Some(sequence)) = (state.last_sequence, sequence) {
+                // A lower number means bitcoind restarted and began counting again
+                let expected = last.wrapping_add(1);
+                if sequence > expected {
+                    state.missed_total += u64::from(sequence - expected);
+                }
+            }
+            if sequence.is_some() {
+                state.last_sequence = sequence;
+            }
+            if let Err(e) = &decoded {
+                state.decode_errors_total += 1;
+                state.last_error = Some(format!("{:#}", e));
+            }
+        }
+
+        match decoded {
+            // No receivers is fine
+            Ok(event) => { let _ = self.events.send(event); }
+            Err(e) => warn!("Undecodable ZMQ {} message: {:#}", topic.as_str(), e),
+        }
+    }
+
+    async fn messages_total(&self, topics: &[ZmqTopic]) -> u64 {
+        let states = self.topics.read().await;
+        topics.iter().filter_map(|topic| states.get(topic)).map(|state| state.messages_total).sum()
+    }
+
+    async fn set_connected(&self, topics: &[ZmqTopic]) {
+        let mut states = self.topics.write().await;
+        for topic in topics {
+            let state = states.entry(*topic).or_default();
+            state.connected = true;
+            // Sequence numbers continue across our reconnects but not bitcoind restarts
+            state.last_sequence = None;
+        }
+    }
+
+    async fn set_disconnected(&self, topics: &[ZmqTopic], error: Option<String>) {
+        let mut states = self.topics.write().await;
+        for topic in topics {
+            let state = states.entry(*topic).or_default();
+            state.connected = false;
+            state.reconnects_total += 1;
+            if error.is_some() {
+                state.last_error = error.clone();
+            }
+        }
+    }
+
+    /// Health of each subscribed topic
+    pub async fn status(&self) -> Vec<ZmqTopicStatus> {
+        let states = self.topics.read().await;
+        states.iter().map(|(topic, state)| {
+            let since = state.last_message.map(|(at, _)| at.elapsed());
+            let status = if !state.connected {
+                "unhealthy"
+            } else if since.is_some_and(|since| since > self.config.stale_after(*topic)) {
+                "degraded"
+            } else {
+                "healthy"
+            };
+            ZmqTopicStatus {
+                topic: *topic,
+                endpoint: self.config.endpoint(*topic).unwrap_or_default().to_string(),
+                status: status.to_string(),
+                connected: state.connected,
+                last_message_at: state.last_message.map(|(_, at)| at),
+                seconds_since_message: since.map(|since| since.as_secs()),
+                messages_total: state.messages_total,
+                missed_total: state.missed_total,
+                decode_errors_total: state.decode_errors_total,
+                reconnects_total: state.reconnects_total,
+                last_error: state.last_error.clone(),
+            }
+        }).collect()
+    }
+
+    /// Per-topic metrics in the Prometheus text format
+    pub async fn to_prometheus(&self) -> String {
+        let status = self.status().await;
+        let samples = |value: fn(&ZmqTopicStatus) -> f64| -> Vec<(&'static str, f64)> {
+            status.iter().map(|topic| (topic.topic.as_str(), value(topic))).collect()
+        };
+        let mut out = String::new();
+        labelled(&mut out, "dmpool_zmq_connected", "gauge", "Whether the ZMQ topic's socket is connected", "topic",
+            &samples(|topic| if topic.connected { 1.0 } else { 0.0 }));
+        labelled(&mut out, "dmpool_zmq_messages_total", "counter", "ZMQ messages received", "topic",
+            &samples(|topic| topic.messages_total as f64));
+        labelled(&mut out, "dmpool_zmq_missed_messages_total", "counter", "ZMQ messages skipped by sequence number", "topic",
+            &samples(|topic| topic.missed_total as f64));
+        labelled(&mut out, "dmpool_zmq_decode_errors_total", "counter", "ZMQ messages that could not be decoded", "topic",
+            &samples(|topic| topic.decode_errors_total as f64));
+        labelled(&mut out, "dmpool_zmq_reconnects_total", "counter", "ZMQ socket reconnects", "topic",
+            &samples(|topic| topic.reconnects_total as f64));
+        let silent: Vec<(&'static str, f64)> = status.iter()
+            .filter_map(|topic| topic.seconds_since_message.map(|secs| (topic.topic.as_str(), secs as f64)))
+            .collect();
+        labelled(&mut out, "dmpool_zmq_seconds_since_message", "gauge", "Time since the last ZMQ message on the topic", "topic", &silent);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscriptions() -> ZmqSubscriptions {
+        ZmqSubscriptions::new(ZmqConfig {
+            enabled: true,
+            rawblock: Some("tcp://127.0.0.1:28333".to_string()),
+            hashtx: Some("tcp://127.0.0.1:28333".to_string()),
+            ..ZmqConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_decodes_rawblock_and_counts_sequence_gaps() {
+        let zmq = subscriptions();
+        let mut events = zmq.subscribe();
+        let genesis = ::bitcoin::blockdata::constants::genesis_block(::bitcoin::Network::Regtest);
+        let body = ::bitcoin::consensus::serialize(&genesis);
+
+        zmq.handle_message(&[b"rawblock", &body, &7u32.to_le_bytes()]).await;
+        zmq.handle_message(&[b"rawblock", &body, &10u32.to_le_bytes()]).await;
+
+        match events.recv().await.unwrap() {
+            ZmqEvent::Block { hash, txids } => {
+                assert_eq!(hash, genesis.block_hash().to_string());
+                assert_eq!(txids.len(), 1);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        let status = zmq.status().await;
+        let rawblock = status.iter().find(|s| s.topic == ZmqTopic::RawBlock).unwrap();
+        assert_eq!((rawblock.messages_total, rawblock.missed_total), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_topic_health() {
+        let zmq = subscriptions();
+        zmq.set_connected(&[ZmqTopic::RawBlock]).await;
+        zmq.handle_message(&[b"hashtx", &[0u8; 31], &0u32.to_le_bytes()]).await;
+
+        let status = zmq.status().await;
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].status, "healthy");
+        // Never connected, and a malformed hash
+        assert_eq!(status[1].status, "unhealthy");
+        assert_eq!(status[1].decode_errors_total, 1);
+        assert!(zmq.to_prometheus().await.contains("dmpool_zmq_connected{topic=\"hashtx\"} 0"));
+    }
+}
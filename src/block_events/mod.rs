@@ -0,0 +1,691 @@
+// Block Announcement Module for DMPool
+// Coordinates everything that happens when the pool finds a block:
+// recording, PPLNS snapshot, alerts, payout crediting and a BlockFound event on the bus.
+// In coinbase payout mode only miners the block's coinbase did not pay are credited.
+// Crediting is idempotent per block hash, so a block replayed after a restart
+// mid-processing is credited if it wasn't yet and never twice.
+// Miners in a loyalty tier get part of the pool fee rebated in the split.
+// Referrers are credited part of the pool fee taken from miners they referred.
+// The snapshotted window is held from share pruning while the block is audited.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::bitcoin::CoinbaseVout;
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::db::{DatabaseManager, NewBlockRecord};
+use crate::events::{EventBus, PoolEvent};
+use crate::explorer::ExplorerLinks;
+use crate::loyalty::{FeeDiscounts, LoyaltyTracker};
+use crate::luck::block_effort_percent;
+use crate::payment::{CoinbasePlanner, PaymentManager};
+use crate::pplns_validator::{RoundingPolicy, distribute_remainder};
+use crate::pplns_window::{PplnsWindow, WindowSnapshot};
+use crate::referrals::{ReferralCredit, ReferralProgram};
+use crate::retention::ShareHolds;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+/// Alert rule used for block announcements
+pub const BLOCK_FOUND_ALERT_RULE: &str = "block_found";
+
+/// A block found by the pool
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockFoundEvent {
+    pub height: u64,
+    pub block_hash: String,
+    pub found_at: DateTime<Utc>,
+    /// Coinbase reward (subsidy + fees)
+    pub reward_satoshis: u64,
+    pub coinbase_txid: Option<String>,
+    /// Miner whose share solved the block (if known)
+    pub finder_address: Option<String>,
+    pub finder_worker: Option<String>,
+    /// Network difficulty of the block, used for its effort
+    #[serde(default)]
+    pub network_difficulty: Option<f64>,
+    /// Coinbase outputs, matched against the coinbase payout plan
+    #[serde(default)]
+    pub coinbase_outputs: Vec<CoinbaseVout>,
+}
+
+/// A miner's credit for a found block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockPayout {
+    pub address: String,
+    pub share_count: u64,
+    pub difficulty: u64,
+    pub amount_satoshis: u64,
+    /// Paid by an output of the block's coinbase rather than credited
+    #[serde(default)]
+    pub in_coinbase: bool,
+}
+
+/// Result of processing a found block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockAnnouncement {
+    pub event: BlockFoundEvent,
+    /// Shares in the PPLNS window at the time of the block
+    pub window_shares: u64,
+    /// Total difficulty in the PPLNS window
+    pub window_difficulty: u64,
+    /// Window difficulty as a percentage of the network difficulty
+    #[serde(default)]
+    pub effort_percent: Option<f64>,
+    /// Per-miner distribution
+    pub payouts: Vec<BlockPayout>,
+    /// Pool fee plus rounding remainder
+    pub pool_fee_satoshis: u64,
+    /// Part of the pool fee credited to referrers
+    #[serde(default)]
+    pub referral_credits: Vec<ReferralCredit>,
+    /// Steps that failed (other steps still ran)
+    pub errors: Vec<String>,
+}
+
+/// Persists found blocks
+#[async_trait]
+pub trait BlockRecorder: Send + Sync {
+    /// Record a block, returning false if it was already recorded
+    async fn record_block(&self, announcement: &BlockAnnouncement) -> Result<bool>;
+}
+
+#[async_trait]
+impl BlockRecorder for DatabaseManager {
+    async fn record_block(&self, announcement: &BlockAnnouncement) -> Result<bool> {
+        let event = &announcement.event;
+        self.record_found_block(&NewBlockRecord {
+            height: event.height as i64,
+            block_hash: event.block_hash.clone(),
+            block_time: event.found_at,
+            reward_sats: event.reward_satoshis as i64,
+            pool_fee_sats: announcement.pool_fee_satoshis as i64,
+            pplns_window_shares: announcement.window_shares.min(i32::MAX as u64) as i32,
+            pplns_total_difficulty: announcement.window_difficulty as i64,
+            network_difficulty: event.network_difficulty,
+            effort_percent: announcement.effort_percent,
+            coinbase_txid: event.coinbase_txid.clone(),
+            payouts: announcement.payouts.iter()
+                .map(|p| (p.address.clone(), p.difficulty as i64, p.amount_satoshis as i64))
+                .collect(),
+        }).await
+    }
+}
+
+/// Split a block reward across the PPLNS window
+///
+/// The pool fee is taken from the whole reward and miners split the rest in
+/// proportion to their difficulty. The rounding remainder is handed out per
+/// `rounding`, with `rotation` (the block height) driving round-robin. Returns
+/// the payouts and what the pool keeps.
+pub fn split_block_reward(
+    snapshot: &WindowSnapshot,
+    reward_satoshis: u64,
+    pool_fee_bps: u32,
+    rounding: RoundingPolicy,
+    rotation: u64,
+) -> (Vec<BlockPayout>, u64) {
+    if snapshot.total_difficulty == 0 {
+        return (Vec::new(), reward_satoshis);
+    }
+
+    let pool_fee = (reward_satoshis as u128 * pool_fee_bps.min(10000) as u128 / 10000) as u64;
+    let distributable = reward_satoshis - pool_fee;
+
+    let mut payouts: Vec<BlockPayout> = snapshot.miners.iter()
+        .map(|(address, totals)| BlockPayout {
+            address: address.clone(),
+            share_count: totals.share_count,
+            difficulty: totals.difficulty,
+            amount_satoshis: (distributable as u128 * totals.difficulty as u128 / snapshot.total_difficulty as u128) as u64,
+            in_coinbase: false,
+        })
+        .collect();
+
+    let paid: u64 = payouts.iter().map(|p| p.amount_satoshis).sum();
+    let contributions: Vec<(&str, u64)> = payouts.iter().map(|p| (p.address.as_str(), p.difficulty)).collect();
+    let credits = distribute_remainder(rounding, distributable - paid, &contributions, rotation);
+    for (payout, credit) in payouts.iter_mut().zip(credits) {
+        payout.amount_satoshis += credit;
+    }
+
+    payouts.retain(|p| p.amount_satoshis > 0);
+    payouts.sort_by(|a, b| b.amount_satoshis.cmp(&a.amount_satoshis).then_with(|| a.address.cmp(&b.address)));
+
+    let paid: u64 = payouts.iter().map(|p| p.amount_satoshis).sum();
+    (payouts, reward_satoshis - paid)
+}
+
+/// Rebate part of the pool fee to miners with a loyalty discount
+///
+/// Each miner gets back its discount (capped at the pool fee) on its gross
+/// share of the reward, out of what the pool keeps. Returns what the pool
+/// keeps after the rebates.
+pub fn apply_fee_discounts(
+    payouts: &mut [BlockPayout],
+    reward_satoshis: u64,
+    total_difficulty: u64,
+    pool_fee_bps: u32,
+    pool_keeps: u64,
+    discounts: &FeeDiscounts,
+) -> u64 {
+    if total_difficulty == 0 {
+        return pool_keeps;
+    }
+    let mut remaining = pool_keeps;
+    for payout in payouts.iter_mut() {
+        let Some(discount) = discounts.get(&payout.address) else {
+            continue;
+        };
+        let gross = reward_satoshis as u128 * payout.difficulty as u128 / total_difficulty as u128;
+        let rebate = (gross * (*discount).min(pool_fee_bps) as u128 / 10000) as u64;
+        let rebate = rebate.min(remaining);
+        payout.amount_satoshis += rebate;
+        remaining -= rebate;
+    }
+    payouts.sort_by(|a, b| b.amount_satoshis.cmp(&a.amount_satoshis).then_with(|| a.address.cmp(&b.address)));
+    remaining
+}
+
+/// Block-found event pipeline
+pub struct BlockAnnouncer {
+    window: Arc<PplnsWindow>,
+    recorder: Option<Arc<dyn BlockRecorder>>,
+    payments: Option<Arc<PaymentManager>>,
+    alerts: Option<Arc<AlertManager>>,
+    events: Option<EventBus>,
+    explorer: Option<Arc<ExplorerLinks>>,
+    coinbase: Option<Arc<CoinbasePlanner>>,
+    loyalty: Option<Arc<LoyaltyTracker>>,
+    referrals: Option<Arc<ReferralProgram>>,
+    holds: Option<Arc<ShareHolds>>,
+    rounding: RoundingPolicy,
+    announced: RwLock<HashSet<u64>>,
+}
+
+impl BlockAnnouncer {
+    /// Create a pipeline that snapshots the given PPLNS window
+    pub fn new(window: Arc<PplnsWindow>) -> Self {
+        Self {
+            window,
+            recorder: None,
+            payments: None,
+            alerts: None,
+            events: None,
+            explorer: None,
+            coinbase: None,
+            loyalty: None,
+            referrals: None,
+            holds: None,
+            rounding: RoundingPolicy::default(),
+            announced: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Record blocks (e.g. in block_details_cache)
+    pub fn with_recorder(mut self, recorder: Arc<dyn BlockRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Credit miner balances from the PPLNS split
+    pub fn with_payments(mut self, payments: Arc<PaymentManager>) -> Self {
+        self.payments = Some(payments);
+        self
+    }
+
+    /// Fire a block-found alert on all channels
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Publish announcements as BlockFound events (webhooks, WebSocket push)
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Include an explorer link in the block-found alert
+    pub fn with_explorer(mut self, explorer: Arc<ExplorerLinks>) -> Self {
+        self.explorer = Some(explorer);
+        self
+    }
+
+    /// Split blocks by the coinbase payout plan issued for their height
+    pub fn with_coinbase(mut self, coinbase: Arc<CoinbasePlanner>) -> Self {
+        self.coinbase = Some(coinbase);
+        self
+    }
+
+    /// Where rounding remainders from the split go (default: the pool)
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Rebate loyalty fee discounts in the split
+    pub fn with_loyalty(mut self, loyalty: Arc<LoyaltyTracker>) -> Self {
+        self.loyalty = Some(loyalty);
+        self
+    }
+
+    /// Credit referrers part of the pool fee from miners they referred
+    pub fn with_referrals(mut self, referrals: Arc<ReferralProgram>) -> Self {
+        self.referrals = Some(referrals);
+        self
+    }
+
+    /// Hold each block's PPLNS window from share pruning for its audit
+    pub fn with_share_holds(mut self, holds: Arc<ShareHolds>) -> Self {
+        self.holds = Some(holds);
+        self
+    }
+
+    /// Hold the window `snapshot` covers for the block at `height`
+    async fn hold_window(&self, height: u64, snapshot: &WindowSnapshot) -> Result<()> {
+        let Some(holds) = &self.holds else {
+            return Ok(());
+        };
+        let start = DateTime::from_timestamp(snapshot.window_start as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid window start {}", snapshot.window_start))?;
+        let end = DateTime::from_timestamp(snapshot.window_end as i64, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid window end {}", snapshot.window_end))?;
+        holds.hold_block(height, start, end).await?;
+        Ok(())
+    }
+
+    async fn fee_discounts(&self) -> FeeDiscounts {
+        match &self.loyalty {
+            Some(loyalty) => loyalty.fee_discounts(Utc::now()).await,
+            None => FeeDiscounts::new(),
+        }
+    }
+
+    /// Process a found block
+    ///
+    /// Returns None if the block height was already announced. A failing
+    /// step is recorded in `errors` and does not stop the remaining steps.
+    pub async fn announce(&self, event: BlockFoundEvent) -> Result<Option<BlockAnnouncement>> {
+        if !self.announced.write().await.insert(event.height) {
+            return Ok(None);
+        }
+
+        info!("Block {} found ({}), reward {} sats", event.height, event.block_hash, event.reward_satoshis);
+
+        // The snapshot must reflect the window at the moment of the block, not a cached one
+        let snapshot = self.window.fresh_snapshot().await?;
+        let hold_error = self.hold_window(event.height, &snapshot).await.err();
+        let discounts = self.fee_discounts().await;
+        let (payouts, pool_fee_satoshis, split_reward, split_difficulty) = match &self.coinbase {
+            Some(planner) => {
+                let plan = match planner.issued(event.height).await {
+                    Some(plan) => plan,
+                    None => {
+                        warn!("No coinbase plan issued for block {}, planning from the current window", event.height);
+                        Arc::new(planner.plan(&snapshot, event.height, event.reward_satoshis, self.window.pool_fee_bps(), &discounts))
+                    }
+                };
+                let (in_coinbase, credit) = plan.settle(&event.coinbase_outputs);
+                let mut payouts: Vec<BlockPayout> = in_coinbase.into_iter()
+                    .map(|p| BlockPayout { in_coinbase: true, ..p })
+                    .chain(credit)
+                    .collect();
+                payouts.sort_by(|a, b| b.amount_satoshis.cmp(&a.amount_satoshis).then_with(|| a.address.cmp(&b.address)));
+                (payouts, plan.pool_fee_satoshis, plan.reward_satoshis, plan.window_difficulty)
+            }
+            None => {
+                let (mut payouts, pool_keeps) = split_block_reward(
+                    &snapshot,
+                    event.reward_satoshis,
+                    self.window.pool_fee_bps(),
+                    self.rounding,
+                    event.height,
+                );
+                let pool_keeps = apply_fee_discounts(
+                    &mut payouts,
+                    event.reward_satoshis,
+                    snapshot.total_difficulty,
+                    self.window.pool_fee_bps(),
+                    pool_keeps,
+                    &discounts,
+                );
+                (payouts, pool_keeps, event.reward_satoshis, snapshot.total_difficulty)
+            }
+        };
+        let referral_credits = match &self.referrals {
+            Some(referrals) => referrals.credits_for(&event.block_hash, event.height, &payouts, split_reward, split_difficulty).await,
+            None => Vec::new(),
+        };
+
+        let effort_percent = event.network_difficulty
+            .and_then(|difficulty| block_effort_percent(snapshot.total_difficulty, difficulty));
+        let mut announcement = BlockAnnouncement {
+            event,
+            window_shares: snapshot.total_shares,
+            window_difficulty: snapshot.total_difficulty,
+            effort_percent,
+            payouts,
+            pool_fee_satoshis,
+            referral_credits,
+            errors: Vec::new(),
+        };
+        if let Some(e) = hold_error {
+            announcement.errors.push(format!("share hold: {:#}", e));
+        }
+
+        let mut already_recorded = false;
+        if let Some(recorder) = &self.recorder {
+            match recorder.record_block(&announcement).await {
+                Ok(true) => {}
+                Ok(false) => already_recorded = true,
+                Err(e) => announcement.errors.push(format!("record: {}", e)),
+            }
+        }
+
+        // A recorded block may still be uncredited if the previous run stopped in between
+        let credited = match self.credit_payouts(&announcement).await {
+            Ok(credited) => credited,
+            Err(e) => {
+                announcement.errors.push(format!("payouts: {}", e));
+                false
+            }
+        };
+        if credited {
+            if let Some(referrals) = &self.referrals {
+                if let Err(e) = referrals.record_credits(&announcement.referral_credits).await {
+                    announcement.errors.push(format!("referrals: {}", e));
+                }
+            }
+        }
+
+        if already_recorded {
+            if !credited {
+                warn!("Block {} already recorded, skipping announcement", announcement.event.height);
+                return Ok(None);
+            }
+            warn!("Block {} was recorded but not credited before, credited on replay", announcement.event.height);
+        }
+
+        if let Err(e) = self.send_alert(&announcement).await {
+            announcement.errors.push(format!("alert: {}", e));
+        }
+
+        if let Some(events) = &self.events {
+            events.publish(PoolEvent::BlockFound(announcement.clone()));
+        }
+
+        if announcement.errors.is_empty() {
+            info!("Block {} announced to {} miners", announcement.event.height, announcement.payouts.len());
+        } else {
+            error!("Block {} announced with errors: {:?}", announcement.event.height, announcement.errors);
+        }
+
+        Ok(Some(announcement))
+    }
+
+    /// Credit each miner's balance, except those paid in the coinbase, and
+    /// each referrer's referral credits
+    ///
+    /// Returns false if there is nothing to credit with or the block hash was
+    /// already credited.
+    async fn credit_payouts(&self, announcement: &BlockAnnouncement) -> Result<bool> {
+        let payments = match &self.payments {
+            Some(payments) => payments,
+            None => return Ok(false),
+        };
+
+        let credits: Vec<(String, u64)> = announcement.payouts.iter()
+            .filter(|p| !p.in_coinbase)
+            .map(|p| (p.address.clone(), p.amount_satoshis))
+            .chain(announcement.referral_credits.iter().map(|c| (c.referrer.clone(), c.credited_satoshis)))
+            .collect();
+        payments.credit_block(&announcement.event.block_hash, announcement.event.height, &credits).await
+    }
+
+    /// Trigger the block-found alert, creating the rule on first use
+    async fn send_alert(&self, announcement: &BlockAnnouncement) -> Result<()> {
+        let alerts = match &self.alerts {
+            Some(alerts) => alerts,
+            None => return Ok(()),
+        };
+
+        if !alerts.get_rules().await.iter().any(|r| r.id == BLOCK_FOUND_ALERT_RULE) {
+            let channels = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                BLOCK_FOUND_ALERT_RULE,
+                "Block found",
+                AlertCondition::Custom { message: "The pool found a block".to_string() },
+                AlertLevel::Info,
+                channels,
+            ).with_cooldown(0)).await;
+        }
+
+        alerts.trigger_alert(BLOCK_FOUND_ALERT_RULE, serde_json::json!({
+            "height": announcement.event.height,
+            "block_hash": announcement.event.block_hash,
+            "reward_satoshis": announcement.event.reward_satoshis,
+            "finder": announcement.event.finder_address,
+            "miners_paid": announcement.payouts.len(),
+            "effort_percent": announcement.effort_percent,
+            "explorer_url": self.explorer.as_ref().and_then(|e| e.block_url(announcement.event.height)),
+        })).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backfill::ShareSource;
+    use crate::payment::PaymentConfig;
+    use crate::pplns_window::MinerWindowTotals;
+    use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    struct VecSource(Vec<SimplePplnsShare>);
+
+    impl ShareSource for VecSource {
+        fn fetch_shares(&self, _start: u64, _end: u64, _limit: usize) -> Vec<SimplePplnsShare> {
+            self.0.clone()
+        }
+    }
+
+    /// Recorder that behaves like the ON CONFLICT insert
+    struct MemoryRecorder {
+        heights: RwLock<HashSet<u64>>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BlockRecorder for MemoryRecorder {
+        async fn record_block(&self, announcement: &BlockAnnouncement) -> Result<bool> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            Ok(self.heights.write().await.insert(announcement.event.height))
+        }
+    }
+
+    fn share(address: &str, difficulty: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some("test-worker".to_string()),
+            user_id: 1,
+            difficulty,
+            n_time: Utc::now().timestamp() as u64,
+            job_id: "job".to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: "00000000".to_string(),
+        }
+    }
+
+    fn event(height: u64) -> BlockFoundEvent {
+        BlockFoundEvent {
+            height,
+            block_hash: format!("{:064x}", height),
+            found_at: Utc::now(),
+            reward_satoshis: 100_000_000,
+            coinbase_txid: None,
+            finder_address: Some("bc1qa".to_string()),
+            finder_worker: None,
+            network_difficulty: Some(8000.0),
+            coinbase_outputs: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_announce_credits_and_records_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let payments = Arc::new(PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap());
+        let window = Arc::new(PplnsWindow::new(
+            Arc::new(VecSource(vec![share("bc1qa", 3000), share("bc1qb", 1000)])),
+            86400,
+            100,
+        ));
+        let recorder = Arc::new(MemoryRecorder { heights: RwLock::new(HashSet::new()), calls: AtomicUsize::new(0) });
+        let bus = EventBus::new(16);
+        let mut events = bus.subscribe();
+        let announcer = BlockAnnouncer::new(window)
+            .with_recorder(recorder.clone())
+            .with_payments(payments.clone())
+            .with_events(bus);
+
+        let announcement = announcer.announce(event(800_000)).await.unwrap().unwrap();
+        assert!(announcement.errors.is_empty());
+        assert_eq!(announcement.window_difficulty, 4000);
+        assert_eq!(announcement.effort_percent, Some(50.0));
+        assert_eq!(announcement.payouts[0].address, "bc1qa");
+        assert_eq!(announcement.payouts[0].amount_satoshis, 74_250_000);
+        assert_eq!(announcement.pool_fee_satoshis, 1_000_000);
+        assert_eq!(payments.get_balance("bc1qb").await.unwrap().balance_satoshis, 24_750_000);
+        match events.recv().await.unwrap() {
+            PoolEvent::BlockFound(published) => assert_eq!(published.event.height, 800_000),
+            other => panic!("unexpected event {}", other.kind()),
+        }
+
+        // Same height again is ignored
+        assert!(announcer.announce(event(800_000)).await.unwrap().is_none());
+        assert_eq!(recorder.calls.load(Ordering::Relaxed), 1);
+        assert_eq!(payments.get_balance("bc1qa").await.unwrap().balance_satoshis, 74_250_000);
+
+        // After a restart the recorded and credited block is not credited again
+        let window = Arc::new(PplnsWindow::new(Arc::new(VecSource(vec![share("bc1qa", 1000)])), 86400, 100));
+        let restarted = BlockAnnouncer::new(window)
+            .with_recorder(recorder.clone())
+            .with_payments(payments.clone());
+        assert!(restarted.announce(event(800_000)).await.unwrap().is_none());
+        assert_eq!(payments.get_balance("bc1qa").await.unwrap().balance_satoshis, 74_250_000);
+
+        // A block recorded before the previous run stopped is still credited
+        recorder.heights.write().await.insert(800_001);
+        let resumed = restarted.announce(event(800_001)).await.unwrap().unwrap();
+        assert!(resumed.errors.is_empty());
+        assert_eq!(payments.get_balance("bc1qa").await.unwrap().balance_satoshis, 74_250_000 + 99_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_coinbase_mode_credits_only_unpaid_miners() {
+        use crate::payment::{CoinbasePayoutConfig, CoinbasePlanner};
+
+        let paid = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+        let temp_dir = TempDir::new().unwrap();
+        let payments = Arc::new(PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap());
+        let window = Arc::new(PplnsWindow::new(
+            Arc::new(VecSource(vec![share(paid, 3000), share("bc1qb", 1000)])),
+            86400,
+            100,
+        ));
+        let planner = Arc::new(CoinbasePlanner::new(CoinbasePayoutConfig {
+            enabled: true,
+            pool_address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            ..CoinbasePayoutConfig::default()
+        }, bitcoin::Network::Bitcoin).unwrap());
+        let plan = planner.plan_window(&window, 800_000, 100_000_000).await.unwrap();
+        let announcer = BlockAnnouncer::new(window)
+            .with_payments(payments.clone())
+            .with_coinbase(planner);
+
+        let mut found = event(800_000);
+        found.coinbase_outputs = plan.outputs.iter()
+            .map(|o| CoinbaseVout { script_pubkey_hex: o.script_pubkey.to_hex_string(), satoshis: o.amount_satoshis })
+            .collect();
+        let announcement = announcer.announce(found).await.unwrap().unwrap();
+
+        // The invalid address is carried into the pool output and credited
+        assert!(announcement.payouts[0].in_coinbase);
+        assert!(payments.get_balance(paid).await.is_none());
+        assert_eq!(payments.get_balance("bc1qb").await.unwrap().balance_satoshis, 24_750_000);
+    }
+
+    #[test]
+    fn test_split_empty_window_goes_to_pool() {
+        let snapshot = WindowSnapshot {
+            computed_at: Utc::now(),
+            window_start: 0,
+            window_end: 0,
+            total_shares: 0,
+            total_difficulty: 0,
+            truncated: false,
+            miners: Default::default(),
+        };
+        let (payouts, pool) = split_block_reward(&snapshot, 5000, 100, RoundingPolicy::RoundRobin, 0);
+        assert!(payouts.is_empty());
+        assert_eq!(pool, 5000);
+    }
+
+    #[test]
+    fn test_split_remainder_policies() {
+        let miners = [("bc1qa", 1), ("bc1qb", 1), ("bc1qc", 1)].into_iter()
+            .map(|(address, difficulty)| (address.to_string(), MinerWindowTotals { share_count: 1, difficulty }))
+            .collect();
+        let snapshot = WindowSnapshot {
+            computed_at: Utc::now(),
+            window_start: 0,
+            window_end: 0,
+            total_shares: 3,
+            total_difficulty: 3,
+            truncated: false,
+            miners,
+        };
+
+        // 1001 sats at 10%: fee 100, 901 over three miners leaves 1 sat
+        let (payouts, pool) = split_block_reward(&snapshot, 1001, 1000, RoundingPolicy::Pool, 0);
+        assert_eq!((payouts.iter().map(|p| p.amount_satoshis).sum::<u64>(), pool), (900, 101));
+
+        for rounding in [RoundingPolicy::LargestContributor, RoundingPolicy::RoundRobin] {
+            let (payouts, pool) = split_block_reward(&snapshot, 1001, 1000, rounding, 800_001);
+            assert_eq!(pool, 100);
+            assert_eq!(payouts.iter().map(|p| p.amount_satoshis).sum::<u64>(), 901);
+        }
+
+        // Round-robin rotates with the block height
+        let (payouts, _) = split_block_reward(&snapshot, 1001, 1000, RoundingPolicy::RoundRobin, 1);
+        assert_eq!((payouts[0].address.as_str(), payouts[0].amount_satoshis), ("bc1qb", 301));
+    }
+
+    #[test]
+    fn test_fee_discounts_rebate_from_pool() {
+        let miners = [("bc1qa", 3), ("bc1qb", 1)].into_iter()
+            .map(|(address, difficulty)| (address.to_string(), MinerWindowTotals { share_count: 1, difficulty }))
+            .collect();
+        let snapshot = WindowSnapshot {
+            computed_at: Utc::now(),
+            window_start: 0,
+            window_end: 0,
+            total_shares: 2,
+            total_difficulty: 4,
+            truncated: false,
+            miners,
+        };
+
+        // 1 BTC at 2%: B's 25M gross gets 0.5% back, A's discount is capped at the fee
+        let (mut payouts, pool) = split_block_reward(&snapshot, 100_000_000, 200, RoundingPolicy::Pool, 0);
+        let discounts = FeeDiscounts::from([("bc1qb".to_string(), 50), ("bc1qa".to_string(), 500)]);
+        let pool = apply_fee_discounts(&mut payouts, 100_000_000, 4, 200, pool, &discounts);
+        assert_eq!(payouts[0].amount_satoshis, 75_000_000);
+        assert_eq!(payouts[1].amount_satoshis, 24_500_000 + 125_000);
+        assert_eq!(pool, 2_000_000 - 1_500_000 - 125_000);
+    }
+}
@@ -0,0 +1,252 @@
+// ClickHouse Module for DMPool
+// Long-term share history in ClickHouse for analytics and hashrate charts
+//
+// Accepted shares are taken off the event bus and inserted in batches through
+// the same buffered exporter as the share firehose, so a slow or unavailable
+// ClickHouse never holds up share processing. The table is created on startup
+// and partitioned by month, ordered by address and time, which keeps per-miner
+// range scans cheap however much history accumulates.
+//
+// ClickHouse is reached over its HTTP interface. When enabled, the Observer
+// API serves hashrate history from ClickHouse instead of PostgreSQL.
+
+use crate::db::{DatabaseManager, HashrateDataPoint};
+use crate::firehose::{BatchSettings, FirehoseSink, ShareRecord};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The `[dmpool.clickhouse]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickHouseConfig {
+    pub enabled: bool,
+    /// HTTP interface, e.g. `http://localhost:8123`
+    pub url: String,
+    pub database: String,
+    pub table: String,
+    pub user: String,
+    pub password: Option<String>,
+    #[serde(flatten)]
+    pub batching: BatchSettings,
+}
+
+impl Default for ClickHouseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://localhost:8123".to_string(),
+            database: "dmpool".to_string(),
+            table: "shares".to_string(),
+            user: "default".to_string(),
+            password: None,
+            batching: BatchSettings::default(),
+        }
+    }
+}
+
+impl ClickHouseConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(anyhow::anyhow!("ClickHouse url must be http(s): {}", self.url));
+        }
+        for (name, value) in [("database", &self.database), ("table", &self.table)] {
+            if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(anyhow::anyhow!("ClickHouse {} must be a plain identifier: {:?}", name, value));
+            }
+        }
+        self.batching.validate()
+    }
+}
+
+/// Serves miner hashrate history
+#[async_trait]
+pub trait HashrateHistorySource: Send + Sync {
+    /// Hourly hashrate of `address` over the last `period_days` days, oldest first
+    async fn hashrate_history(&self, address: &str, period_days: i64) -> Result<Vec<HashrateDataPoint>>;
+}
+
+#[async_trait]
+impl HashrateHistorySource for DatabaseManager {
+    async fn hashrate_history(&self, address: &str, period_days: i64) -> Result<Vec<HashrateDataPoint>> {
+        self.get_miner_hashrate_history(address, period_days).await
+    }
+}
+
+/// Share history stored in ClickHouse
+pub struct ClickHouseStore {
+    http: reqwest::Client,
+    url: String,
+    database: String,
+    table: String,
+    user: String,
+    password: Option<String>,
+}
+
+impl ClickHouseStore {
+    pub fn new(config: &ClickHouseConfig) -> Result<Self> {
+        config.validate()?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .context("Failed to create ClickHouse HTTP client")?;
+        Ok(Self {
+            http,
+            url: config.url.trim_end_matches('/').to_string(),
+            database: config.database.clone(),
+            table: config.table.clone(),
+            user: config.user.clone(),
+            password: config.password.clone(),
+        })
+    }
+
+    /// Create the database and share table if they do not exist
+    pub async fn ensure_schema(&self) -> Result<()> {
+        self.execute(&format!("CREATE DATABASE IF NOT EXISTS {}", self.database), &[], String::new()).await?;
+        self.execute(&self.create_table_sql(), &[], String::new()).await?;
+        Ok(())
+    }
+
+    /// Insert a batch of shares
+    pub async fn insert(&self, batch: &[ShareRecord]) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let mut body = String::new();
+        for record in batch {
+            body.push_str(&serde_json::to_string(record)?);
+            body.push('\n');
+        }
+        let query = format!("INSERT INTO {}.{} FORMAT JSONEachRow", self.database, self.table);
+        self.execute(&query, &[], body).await?;
+        Ok(())
+    }
+
+    /// Delete every share of `address` (applied by ClickHouse in the background)
+    pub async fn delete_address(&self, address: &str) -> Result<()> {
+        let query = format!("ALTER TABLE {}.{} DELETE WHERE address = {{address:String}}", self.database, self.table);
+        self.execute(&query, &[("param_address", address.to_string())], String::new()).await?;
+        Ok(())
+    }
+
+    fn create_table_sql(&self) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {}.{} (\
+                address LowCardinality(String), \
+                worker Nullable(String), \
+                difficulty UInt64, \
+                timestamp DateTime('UTC')\
+            ) ENGINE = MergeTree \
+            PARTITION BY toYYYYMM(timestamp) \
+            ORDER BY (address, timestamp)",
+            self.database, self.table
+        )
+    }
+
+    fn hashrate_history_sql(&self) -> String {
+        // The address and period are bound as query parameters
+        format!(
+            "SELECT toUnixTimestamp(toStartOfHour(timestamp)) AS hour, sum(difficulty) AS total_difficulty \
+            FROM {}.{} \
+            WHERE address = {{address:String}} AND timestamp > now() - INTERVAL {{days:UInt32}} DAY \
+            GROUP BY hour ORDER BY hour \
+            FORMAT JSONEachRow",
+            self.database, self.table
+        )
+    }
+
+    async fn execute(&self, query: &str, params: &[(&str, String)], body: String) -> Result<String> {
+        let mut request = self.http
+            .post(&self.url)
+            .query(&[("query", query), ("output_format_json_quote_64bit_integers", "0")])
+            .query(params)
+            .header("X-ClickHouse-User", &self.user)
+            .body(body);
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let response = request.send().await.context("ClickHouse request failed")?;
+        let status = response.status();
+        let text = response.text().await.context("Failed to read ClickHouse response")?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("ClickHouse returned {}: {}", status, text.trim()));
+        }
+        Ok(text)
+    }
+}
+
+#[async_trait]
+impl FirehoseSink for ClickHouseStore {
+    async fn publish(&self, batch: &[ShareRecord]) -> Result<()> {
+        self.insert(batch).await
+    }
+}
+
+#[async_trait]
+impl HashrateHistorySource for ClickHouseStore {
+    async fn hashrate_history(&self, address: &str, period_days: i64) -> Result<Vec<HashrateDataPoint>> {
+        let params = [
+            ("param_address", address.to_string()),
+            ("param_days", period_days.max(0).to_string()),
+        ];
+        let body = self.execute(&self.hashrate_history_sql(), &params, String::new()).await?;
+        parse_hashrate_rows(&body)
+    }
+}
+
+#[derive(Deserialize)]
+struct HourRow {
+    hour: i64,
+    total_difficulty: u64,
+}
+
+/// Hourly rows as returned by the history query, in the same units as PostgreSQL
+fn parse_hashrate_rows(body: &str) -> Result<Vec<HashrateDataPoint>> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let row: HourRow = serde_json::from_str(line)
+                .with_context(|| format!("Unexpected ClickHouse row: {}", line))?;
+            let hour = DateTime::from_timestamp(row.hour, 0)
+                .ok_or_else(|| anyhow::anyhow!("Invalid hour in ClickHouse row: {}", row.hour))?;
+            Ok(HashrateDataPoint {
+                timestamp: hour.to_rfc3339(),
+                hashrate: (row.total_difficulty as f64 / 3600.0) as u64,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_validation_and_sql() {
+        assert!(ClickHouseConfig::default().validate().is_ok());
+        assert!(ClickHouseConfig { url: "localhost:8123".to_string(), ..Default::default() }.validate().is_err());
+        assert!(ClickHouseConfig { table: "shares; DROP TABLE x".to_string(), ..Default::default() }.validate().is_err());
+
+        let store = ClickHouseStore::new(&ClickHouseConfig { table: "pool_shares".to_string(), ..Default::default() }).unwrap();
+        assert!(store.create_table_sql().starts_with("CREATE TABLE IF NOT EXISTS dmpool.pool_shares ("));
+        let history = store.hashrate_history_sql();
+        assert!(history.contains("FROM dmpool.pool_shares"));
+        assert!(history.contains("address = {address:String}"));
+    }
+
+    #[test]
+    fn test_parse_hashrate_rows() {
+        let body = "{\"hour\":1700000000,\"total_difficulty\":7200}\n{\"hour\":1700003600,\"total_difficulty\":360}\n";
+        let points = parse_hashrate_rows(body).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].timestamp, "2023-11-14T22:13:20+00:00");
+        assert_eq!(points[0].hashrate, 2);
+        assert_eq!(points[1].hashrate, 0);
+
+        assert!(parse_hashrate_rows("").unwrap().is_empty());
+        assert!(parse_hashrate_rows("not json").is_err());
+    }
+}
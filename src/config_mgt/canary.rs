@@ -0,0 +1,343 @@
+// Configuration canary
+//
+// Watches pool health for a window after a config version is applied and
+// rolls back to the parent version if the pool degrades.
+
+use super::{ConfigManager, ConfigVersion};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::health::HealthChecker;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Alert rule used for canary rollbacks
+pub const CANARY_ALERT_RULE: &str = "config_canary_rollback";
+
+/// Canary configuration
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    /// How long to watch after applying (seconds)
+    pub window_secs: u64,
+    /// Interval between health samples (seconds)
+    pub check_interval_secs: u64,
+    /// Consecutive unhealthy samples that trigger a rollback
+    pub max_unhealthy_checks: u32,
+    /// Minimum share rate as a fraction of the pre-apply baseline
+    pub min_share_ratio: f64,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 600,
+            check_interval_secs: 30,
+            max_unhealthy_checks: 3,
+            min_share_ratio: 0.5,
+        }
+    }
+}
+
+/// A single health sample
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CanarySample {
+    /// Overall health status ("healthy", "degraded", "unhealthy")
+    pub status: String,
+    /// Accepted shares per second
+    pub shares_per_second: f64,
+}
+
+/// Source of health samples for the canary
+#[async_trait]
+pub trait CanaryProbe: Send + Sync {
+    async fn sample(&self) -> CanarySample;
+}
+
+#[async_trait]
+impl CanaryProbe for HealthChecker {
+    async fn sample(&self) -> CanarySample {
+        let status = self.check().await;
+        CanarySample {
+            shares_per_second: status.stratum.shares_per_second,
+            status: status.status,
+        }
+    }
+}
+
+/// Outcome of a canary run
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum CanaryOutcome {
+    /// Window elapsed without degradation
+    Passed,
+    /// Rolled back to the parent version
+    RolledBack { reason: String, restored_version: String },
+    /// Degradation detected but rollback was not possible
+    RollbackFailed { reason: String, error: String },
+}
+
+/// Record of a canary run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CanaryReport {
+    pub version_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub baseline_shares_per_second: f64,
+    pub samples: u32,
+    pub outcome: CanaryOutcome,
+}
+
+/// Applies config versions and rolls them back on health degradation
+pub struct ConfigCanary {
+    manager: Arc<ConfigManager>,
+    probe: Arc<dyn CanaryProbe>,
+    alerts: Option<Arc<AlertManager>>,
+    config: CanaryConfig,
+}
+
+impl ConfigCanary {
+    /// Create a new canary
+    pub fn new(manager: Arc<ConfigManager>, probe: Arc<dyn CanaryProbe>, config: CanaryConfig) -> Self {
+        Self {
+            manager,
+            probe,
+            alerts: None,
+            config,
+        }
+    }
+
+    /// Send an alert when a rollback happens
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Apply a configuration and monitor it in the background
+    ///
+    /// The share rate baseline is sampled before the change is applied.
+    pub async fn apply(
+        self: &Arc<Self>,
+        config_data: serde_json::Value,
+        description: String,
+        created_by: String,
+    ) -> Result<(ConfigVersion, tokio::task::JoinHandle<CanaryReport>)> {
+        let baseline = self.probe.sample().await.shares_per_second;
+        let version = self.manager.create_version(config_data, description, created_by).await?;
+
+        let canary = self.clone();
+        let version_id = version.id.clone();
+        let handle = tokio::spawn(async move { canary.monitor(&version_id, baseline).await });
+
+        Ok((version, handle))
+    }
+
+    /// Monitor an applied version against a baseline share rate
+    pub async fn monitor(&self, version_id: &str, baseline_shares_per_second: f64) -> CanaryReport {
+        let started_at = Utc::now();
+        let interval = Duration::from_secs(self.config.check_interval_secs.max(1));
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(self.config.window_secs);
+
+        info!("Canary watching config version {} for {}s", version_id, self.config.window_secs);
+
+        let mut samples = 0;
+        let mut unhealthy_streak = 0;
+        let mut degraded_reason = None;
+
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(interval).await;
+
+            // Stop watching if another version has been applied since
+            if self.manager.current_version.read().await.as_deref() != Some(version_id) {
+                info!("Config version {} superseded, canary stopped", version_id);
+                break;
+            }
+
+            let sample = self.probe.sample().await;
+            samples += 1;
+
+            match self.evaluate(&sample, baseline_shares_per_second) {
+                Some(reason) => {
+                    unhealthy_streak += 1;
+                    warn!("Canary sample {} for {} degraded: {}", samples, version_id, reason);
+                    if unhealthy_streak >= self.config.max_unhealthy_checks {
+                        degraded_reason = Some(reason);
+                        break;
+                    }
+                }
+                None => unhealthy_streak = 0,
+            }
+        }
+
+        let outcome = match degraded_reason {
+            Some(reason) => self.roll_back(version_id, reason).await,
+            None => {
+                info!("Config version {} passed canary", version_id);
+                CanaryOutcome::Passed
+            }
+        };
+
+        CanaryReport {
+            version_id: version_id.to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            baseline_shares_per_second,
+            samples,
+            outcome,
+        }
+    }
+
+    /// Return the degradation reason for a sample, if any
+    fn evaluate(&self, sample: &CanarySample, baseline: f64) -> Option<String> {
+        if sample.status == "unhealthy" {
+            return Some("pool health is unhealthy".to_string());
+        }
+
+        if baseline > 0.0 && sample.shares_per_second < baseline * self.config.min_share_ratio {
+            return Some(format!(
+                "share rate dropped to {:.3}/s from baseline {:.3}/s",
+                sample.shares_per_second, baseline
+            ));
+        }
+
+        None
+    }
+
+    /// Roll back to the parent of a version and raise an alert
+    async fn roll_back(&self, version_id: &str, reason: String) -> CanaryOutcome {
+        let parent_id = self.manager.get_version(version_id).await.and_then(|v| v.parent_id);
+
+        let outcome = match parent_id {
+            Some(parent_id) => {
+                match self.manager.rollback(&parent_id, reason.clone(), "canary".to_string()).await {
+                    Ok(()) => {
+                        warn!("Canary rolled back config {} to {}: {}", version_id, parent_id, reason);
+                        CanaryOutcome::RolledBack { reason, restored_version: parent_id }
+                    }
+                    Err(e) => {
+                        error!("Canary rollback of config {} failed: {}", version_id, e);
+                        CanaryOutcome::RollbackFailed { reason, error: e.to_string() }
+                    }
+                }
+            }
+            None => {
+                error!("Config {} degraded but has no parent version to restore", version_id);
+                CanaryOutcome::RollbackFailed { reason, error: "no parent version".to_string() }
+            }
+        };
+
+        self.send_alert(version_id, &outcome).await;
+        outcome
+    }
+
+    /// Alert operators about a canary rollback
+    async fn send_alert(&self, version_id: &str, outcome: &CanaryOutcome) {
+        let alerts = match &self.alerts {
+            Some(alerts) => alerts,
+            None => return,
+        };
+
+        if !alerts.get_rules().await.iter().any(|r| r.id == CANARY_ALERT_RULE) {
+            let channels = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                CANARY_ALERT_RULE,
+                "Config canary rollback",
+                AlertCondition::Custom {
+                    message: "Configuration change degraded pool health".to_string(),
+                },
+                AlertLevel::Critical,
+                channels,
+            ).with_cooldown(0)).await;
+        }
+
+        let context = serde_json::json!({
+            "version_id": version_id,
+            "outcome": outcome,
+        });
+        if let Err(e) = alerts.trigger_alert(CANARY_ALERT_RULE, context).await {
+            error!("Failed to send canary alert: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tempfile::TempDir;
+
+    /// Probe returning a settable share rate
+    struct FixedProbe {
+        shares_milli: AtomicU64,
+    }
+
+    #[async_trait]
+    impl CanaryProbe for FixedProbe {
+        async fn sample(&self) -> CanarySample {
+            CanarySample {
+                status: "healthy".to_string(),
+                shares_per_second: self.shares_milli.load(Ordering::Relaxed) as f64 / 1000.0,
+            }
+        }
+    }
+
+    fn config(donation: u64) -> serde_json::Value {
+        json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": donation,
+            "pplns_ttl_days": 7
+        })
+    }
+
+    fn fast_config() -> CanaryConfig {
+        CanaryConfig {
+            window_secs: 3,
+            check_interval_secs: 1,
+            max_unhealthy_checks: 2,
+            min_share_ratio: 0.5,
+        }
+    }
+
+    async fn setup(temp_dir: &TempDir) -> (Arc<ConfigManager>, ConfigVersion) {
+        let manager = Arc::new(ConfigManager::new(temp_dir.path().to_path_buf()));
+        manager.initialize().await.unwrap();
+        let base = manager.create_version(config(0), "base".to_string(), "test".to_string()).await.unwrap();
+        // Version IDs have second resolution
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        (manager, base)
+    }
+
+    #[tokio::test]
+    async fn test_rollback_on_share_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, base) = setup(&temp_dir).await;
+        let probe = Arc::new(FixedProbe { shares_milli: AtomicU64::new(10_000) });
+        let canary = Arc::new(ConfigCanary::new(manager.clone(), probe.clone(), fast_config()));
+
+        let (version, handle) = canary.apply(config(100), "raise donation".to_string(), "test".to_string()).await.unwrap();
+        probe.shares_milli.store(1_000, Ordering::Relaxed);
+
+        let report = handle.await.unwrap();
+        assert_eq!(report.version_id, version.id);
+        assert!(matches!(report.outcome, CanaryOutcome::RolledBack { ref restored_version, .. } if *restored_version == base.id));
+        assert_eq!(manager.current_version().await.unwrap().config_data, config(0));
+    }
+
+    #[tokio::test]
+    async fn test_passes_when_healthy() {
+        let temp_dir = TempDir::new().unwrap();
+        let (manager, _) = setup(&temp_dir).await;
+        let probe = Arc::new(FixedProbe { shares_milli: AtomicU64::new(10_000) });
+        let canary = Arc::new(ConfigCanary::new(manager.clone(), probe, fast_config()));
+
+        let (version, handle) = canary.apply(config(100), "raise donation".to_string(), "test".to_string()).await.unwrap();
+
+        let report = handle.await.unwrap();
+        assert_eq!(report.outcome, CanaryOutcome::Passed);
+        assert_eq!(manager.current_version().await.unwrap().id, version.id);
+    }
+}
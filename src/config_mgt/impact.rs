@@ -0,0 +1,327 @@
+// Configuration change impact analysis
+//
+// Dry-runs a candidate configuration against the current one and reports
+// what applying it would do, without touching any state.
+
+use super::{ChangeType, ConfigChange, ConfigManager, ValidationStatus};
+use crate::pplns_validator::PplnsSimulator;
+use anyhow::Result;
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// How a parameter change takes effect
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ApplyMode {
+    /// Picked up by a live reload of the component
+    LiveReload,
+    /// Requires restarting the pool
+    Restart,
+}
+
+/// Impact of a single parameter change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ParameterImpact {
+    /// The underlying change
+    pub change: ConfigChange,
+    /// Component affected by the change
+    pub component: String,
+    /// How the change takes effect
+    pub apply_mode: ApplyMode,
+}
+
+/// Payout change for a single miner
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerPayoutDelta {
+    pub address: String,
+    pub current_satoshis: u64,
+    pub candidate_satoshis: u64,
+    pub delta_satoshis: i64,
+}
+
+/// Estimated payout impact of fee, donation or PPLNS window changes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutImpact {
+    /// Block reward used for the estimate
+    pub block_reward_satoshis: u64,
+    /// Shares in the sample
+    pub sample_shares: u64,
+    /// Total paid to miners under the current config
+    pub current_total_satoshis: u64,
+    /// Total paid to miners under the candidate config
+    pub candidate_total_satoshis: u64,
+    /// Candidate minus current
+    pub delta_satoshis: i64,
+    /// Per-miner changes, largest loss first
+    pub miners: Vec<MinerPayoutDelta>,
+}
+
+/// Result of a configuration dry run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImpactReport {
+    /// Version the candidate is compared against
+    pub current_version: Option<String>,
+    /// Per-parameter impacts
+    pub changes: Vec<ParameterImpact>,
+    /// Components that would be live-reloaded
+    pub reloaded_components: Vec<String>,
+    /// Parameters whose change requires a restart
+    pub restart_required: Vec<String>,
+    /// Payout estimate (if payout-relevant parameters changed)
+    pub payout_impact: Option<PayoutImpact>,
+    /// Validation errors that block applying the candidate
+    pub blocking_errors: Vec<String>,
+    /// Whether the candidate can be applied
+    pub can_apply: bool,
+}
+
+/// Parameters that affect miner payouts
+const PAYOUT_PARAMS: [&str; 3] = ["donation", "pool_fee_bps", "pplns_ttl_days"];
+
+/// Map a parameter to the component it affects and how it is applied
+fn classify_parameter(path: &str) -> (&'static str, ApplyMode) {
+    match path {
+        "stratum.port" | "stratum.hostname" => ("stratum", ApplyMode::Restart),
+        p if p.starts_with("stratum.") => ("stratum", ApplyMode::LiveReload),
+        "pplns_ttl_days" | "ignore_difficulty" => ("accounting", ApplyMode::LiveReload),
+        "donation" | "pool_fee_bps" => ("payments", ApplyMode::LiveReload),
+        p if p.starts_with("api.") => ("api", ApplyMode::Restart),
+        // Unknown parameters are assumed to need a restart
+        _ => ("pool", ApplyMode::Restart),
+    }
+}
+
+/// Compute the per-key changes between two flat configurations
+fn compute_changes(current: &serde_json::Value, candidate: &serde_json::Value) -> Vec<ConfigChange> {
+    let empty = serde_json::Map::new();
+    let old = current.as_object().unwrap_or(&empty);
+    let new = candidate.as_object().unwrap_or(&empty);
+
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter().filter_map(|key| {
+        let (old_value, new_value, change_type) = match (old.get(key), new.get(key)) {
+            (None, Some(n)) => (serde_json::Value::Null, n.clone(), ChangeType::Added),
+            (Some(o), None) => (o.clone(), serde_json::Value::Null, ChangeType::Removed),
+            (Some(o), Some(n)) if o != n => (o.clone(), n.clone(), ChangeType::Modified),
+            _ => return None,
+        };
+        Some(ConfigChange { path: key.clone(), old_value, new_value, change_type })
+    }).collect()
+}
+
+/// Build a simulator for a configuration
+fn simulator_for(config: &serde_json::Value, block_reward_satoshis: u64) -> (PplnsSimulator, u64) {
+    let donation = config.get("donation").and_then(|v| v.as_u64()).unwrap_or(0);
+    let pool_fee = config.get("pool_fee_bps").and_then(|v| v.as_u64()).unwrap_or(0);
+    let window_days = config.get("pplns_ttl_days").and_then(|v| v.as_u64()).unwrap_or(7);
+
+    let deduction_bps = (donation + pool_fee).min(10000) as u16;
+    (PplnsSimulator::new(block_reward_satoshis, deduction_bps, window_days), window_days)
+}
+
+/// Shares within `window_days` of the newest share
+fn shares_in_window(shares: &[SimplePplnsShare], window_days: u64) -> Vec<SimplePplnsShare> {
+    let newest = match shares.iter().map(|s| s.n_time).max() {
+        Some(t) => t,
+        None => return Vec::new(),
+    };
+    let cutoff = newest.saturating_sub(window_days * 86400);
+    shares.iter().filter(|s| s.n_time >= cutoff).cloned().collect()
+}
+
+/// Simulate payouts per miner for a configuration
+fn simulate(config: &serde_json::Value, shares: &[SimplePplnsShare], block_reward_satoshis: u64) -> HashMap<String, u64> {
+    let (simulator, window_days) = simulator_for(config, block_reward_satoshis);
+    let window = shares_in_window(shares, window_days);
+    simulator.simulate_payouts(&window).payouts
+        .into_iter()
+        .map(|p| (p.address, p.final_payout_satoshis))
+        .collect()
+}
+
+/// Estimate payout impact of moving from `current` to `candidate`
+fn estimate_payout_impact(
+    current: &serde_json::Value,
+    candidate: &serde_json::Value,
+    shares: &[SimplePplnsShare],
+    block_reward_satoshis: u64,
+) -> PayoutImpact {
+    let before = simulate(current, shares, block_reward_satoshis);
+    let after = simulate(candidate, shares, block_reward_satoshis);
+
+    let addresses: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut miners: Vec<MinerPayoutDelta> = addresses.into_iter().map(|address| {
+        let current_satoshis = before.get(address).copied().unwrap_or(0);
+        let candidate_satoshis = after.get(address).copied().unwrap_or(0);
+        MinerPayoutDelta {
+            address: address.clone(),
+            current_satoshis,
+            candidate_satoshis,
+            delta_satoshis: candidate_satoshis as i64 - current_satoshis as i64,
+        }
+    }).collect();
+    miners.sort_by_key(|m| m.delta_satoshis);
+
+    let current_total_satoshis: u64 = before.values().sum();
+    let candidate_total_satoshis: u64 = after.values().sum();
+
+    PayoutImpact {
+        block_reward_satoshis,
+        sample_shares: shares.len() as u64,
+        current_total_satoshis,
+        candidate_total_satoshis,
+        delta_satoshis: candidate_total_satoshis as i64 - current_total_satoshis as i64,
+        miners,
+    }
+}
+
+impl ConfigManager {
+    /// Dry-run a candidate configuration against the current version
+    ///
+    /// `shares` is a recent share sample used to estimate payout impact.
+    pub async fn analyze_impact(
+        &self,
+        candidate: &serde_json::Value,
+        shares: &[SimplePplnsShare],
+        block_reward_satoshis: u64,
+    ) -> ImpactReport {
+        let current = self.current_version().await;
+        let current_data = current.as_ref()
+            .map(|v| v.config_data.clone())
+            .unwrap_or_else(|| serde_json::json!({}));
+
+        let changes: Vec<ParameterImpact> = compute_changes(&current_data, candidate)
+            .into_iter()
+            .map(|change| {
+                let (component, apply_mode) = classify_parameter(&change.path);
+                ParameterImpact { change, component: component.to_string(), apply_mode }
+            })
+            .collect();
+
+        let reloaded_components: Vec<String> = changes.iter()
+            .filter(|c| c.apply_mode == ApplyMode::LiveReload)
+            .map(|c| c.component.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let restart_required: Vec<String> = changes.iter()
+            .filter(|c| c.apply_mode == ApplyMode::Restart)
+            .map(|c| c.change.path.clone())
+            .collect();
+
+        let payout_relevant = changes.iter().any(|c| PAYOUT_PARAMS.contains(&c.change.path.as_str()));
+        let payout_impact = if payout_relevant && !shares.is_empty() {
+            Some(estimate_payout_impact(&current_data, candidate, shares, block_reward_satoshis))
+        } else {
+            None
+        };
+
+        let blocking_errors = match self.validate_config(candidate).await {
+            ValidationStatus::Invalid { errors } => errors,
+            _ => Vec::new(),
+        };
+
+        ImpactReport {
+            current_version: current.map(|v| v.id),
+            can_apply: blocking_errors.is_empty(),
+            changes,
+            reloaded_components,
+            restart_required,
+            payout_impact,
+            blocking_errors,
+        }
+    }
+
+    /// Dry-run a stored version (e.g. a rollback target) against the current version
+    pub async fn analyze_version_impact(
+        &self,
+        version_id: &str,
+        shares: &[SimplePplnsShare],
+        block_reward_satoshis: u64,
+    ) -> Result<ImpactReport> {
+        let version = self.get_version(version_id).await
+            .ok_or_else(|| anyhow::anyhow!("Version not found: {}", version_id))?;
+
+        Ok(self.analyze_impact(&version.config_data, shares, block_reward_satoshis).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn share(address: &str, difficulty: u64, time: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some("test-worker".to_string()),
+            user_id: 1,
+            difficulty,
+            n_time: time,
+            job_id: format!("job-{}", time),
+            extranonce2: "00000001".to_string(),
+            nonce: format!("{:08x}", time),
+        }
+    }
+
+    fn base_config() -> serde_json::Value {
+        json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": 0,
+            "pplns_ttl_days": 7
+        })
+    }
+
+    #[tokio::test]
+    async fn test_donation_change_impact() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ConfigManager::new(temp_dir.path().to_path_buf());
+        manager.initialize().await.unwrap();
+        manager.create_version(base_config(), "base".to_string(), "test".to_string()).await.unwrap();
+
+        let mut candidate = base_config();
+        candidate["donation"] = json!(200);
+        candidate["stratum.port"] = json!(3334);
+
+        let shares = vec![share("bc1qa", 1000, 1_000_000), share("bc1qb", 1000, 1_000_100)];
+        let report = manager.analyze_impact(&candidate, &shares, 100_000_000).await;
+
+        assert!(report.can_apply);
+        assert_eq!(report.reloaded_components, vec!["payments".to_string()]);
+        assert_eq!(report.restart_required, vec!["stratum.port".to_string()]);
+
+        let impact = report.payout_impact.unwrap();
+        assert_eq!(impact.current_total_satoshis, 100_000_000);
+        assert_eq!(impact.candidate_total_satoshis, 98_000_000);
+        assert_eq!(impact.delta_satoshis, -2_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_window_change_and_blocking_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ConfigManager::new(temp_dir.path().to_path_buf());
+        manager.initialize().await.unwrap();
+        manager.create_version(base_config(), "base".to_string(), "test".to_string()).await.unwrap();
+
+        // Shrinking the window drops the old miner's shares from the payout
+        let now = 10_000_000;
+        let shares = vec![share("bc1qold", 1000, now - 86400 * 5), share("bc1qnew", 1000, now)];
+        let mut candidate = base_config();
+        candidate["pplns_ttl_days"] = json!(2);
+
+        let report = manager.analyze_impact(&candidate, &shares, 100_000_000).await;
+        let impact = report.payout_impact.unwrap();
+        let old_miner = impact.miners.iter().find(|m| m.address == "bc1qold").unwrap();
+        assert_eq!(old_miner.candidate_satoshis, 0);
+        assert_eq!(impact.miners[0].address, "bc1qold");
+
+        // 100% donation is a blocking validation error
+        candidate["donation"] = json!(10000);
+        let report = manager.analyze_impact(&candidate, &shares, 100_000_000).await;
+        assert!(!report.can_apply);
+        assert!(!report.blocking_errors.is_empty());
+    }
+}
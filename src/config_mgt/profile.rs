@@ -0,0 +1,184 @@
+// Configuration profiles
+//
+// Versions belong to an environment: dev, staging or prod. A version is
+// promoted to the next environment by copying it into a new version of that
+// profile, validated with the target's rules and linked back to its source.
+
+use super::{ConfigManager, ConfigVersion, ValidationStatus};
+use crate::error::DmpoolError;
+use anyhow::Result;
+use bitcoin::Network;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::info;
+
+/// Environment a configuration version belongs to
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigProfile {
+    Dev,
+    Staging,
+    #[default]
+    Prod,
+}
+
+impl ConfigProfile {
+    /// Profile matching a bitcoin network: regtest and signet are dev, testnets staging
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => Self::Prod,
+            Network::Testnet | Network::Testnet4 => Self::Staging,
+            _ => Self::Dev,
+        }
+    }
+
+    /// The profile versions are promoted to from this one
+    pub fn next(self) -> Option<Self> {
+        match self {
+            Self::Dev => Some(Self::Staging),
+            Self::Staging => Some(Self::Prod),
+            Self::Prod => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dev => write!(f, "dev"),
+            Self::Staging => write!(f, "staging"),
+            Self::Prod => write!(f, "prod"),
+        }
+    }
+}
+
+/// Link from a promoted version to its source
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Promotion {
+    pub from_version: String,
+    pub from_profile: ConfigProfile,
+    pub promoted_by: String,
+    pub promoted_at: DateTime<Utc>,
+}
+
+impl ConfigManager {
+    /// Versions of one profile, newest first
+    pub async fn list_profile_versions(&self, profile: ConfigProfile) -> Vec<ConfigVersion> {
+        self.list_versions().await.into_iter().filter(|v| v.profile == profile).collect()
+    }
+
+    /// Promote a version to the next profile
+    ///
+    /// The copy is validated with the target profile's rules and its parent is
+    /// the latest version of that profile. It becomes current only when the
+    /// target is this manager's own profile.
+    pub async fn promote(&self, version_id: &str, target: ConfigProfile, promoted_by: String) -> Result<ConfigVersion> {
+        let source = self.get_version(version_id).await
+            .ok_or_else(|| DmpoolError::NotFound(format!("Version not found: {}", version_id)))?;
+        if source.profile.next() != Some(target) {
+            return Err(DmpoolError::InvalidInput(format!(
+                "Version {} is {}, it can only be promoted to {}",
+                version_id,
+                source.profile,
+                source.profile.next().map(|p| p.to_string()).unwrap_or_else(|| "nothing".to_string())
+            )).into());
+        }
+        if let Err(status) = self.verify(&source) {
+            self.report_signature("promote", version_id, &status).await;
+            return Err(anyhow::anyhow!("Version {} failed signature verification: {:?}", version_id, status));
+        }
+
+        let validation_status = self.validate_for_profile(&source.config_data, target).await;
+        if let ValidationStatus::Invalid { errors } = &validation_status {
+            return Err(DmpoolError::InvalidInput(format!(
+                "Version {} is not valid for {}: {}", version_id, target, errors.join("; ")
+            )).into());
+        }
+
+        let now = Utc::now();
+        let version = ConfigVersion {
+            id: format!("v{}_{}", now.format("%Y%m%d%H%M%S"), target),
+            created_at: now,
+            created_by: promoted_by.clone(),
+            description: format!("Promoted {} from {}: {}", source.id, source.profile, source.description),
+            parent_id: self.list_profile_versions(target).await.first().map(|v| v.id.clone()),
+            config_data: source.config_data.clone(),
+            validation_status,
+            profile: target,
+            promotion: Some(Promotion {
+                from_version: source.id.clone(),
+                from_profile: source.profile,
+                promoted_by,
+                promoted_at: now,
+            }),
+            signature: None,
+        };
+        let version = self.store_version(version, target == self.profile).await?;
+
+        info!("Promoted configuration version {} to {} as {}", source.id, target, version.id);
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn config(donation: i64) -> serde_json::Value {
+        json!({
+            "stratum.port": 3333,
+            "stratum.start_difficulty": 32,
+            "donation": donation,
+            "pplns_ttl_days": 7
+        })
+    }
+
+    #[tokio::test]
+    async fn test_profile_specific_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let dev = ConfigManager::new(temp_dir.path().to_path_buf()).with_profile(ConfigProfile::Dev);
+        dev.initialize().await.unwrap();
+
+        // Regtest pools may donate 100%, production may not
+        assert_eq!(dev.validate_config(&config(10000)).await, ValidationStatus::Valid);
+        assert!(matches!(dev.validate_for_profile(&config(10000), ConfigProfile::Prod).await, ValidationStatus::Invalid { .. }));
+
+        let version = dev.create_version(config(10000), "Regtest".to_string(), "dev".to_string()).await.unwrap();
+        assert_eq!(version.profile, ConfigProfile::Dev);
+        let err = dev.promote(&version.id, ConfigProfile::Staging, "ops".to_string()).await.unwrap_err();
+        assert!(err.to_string().contains("not valid for staging"));
+
+        assert_eq!(ConfigProfile::for_network(Network::Regtest), ConfigProfile::Dev);
+        assert_eq!(ConfigProfile::for_network(Network::Bitcoin), ConfigProfile::Prod);
+    }
+
+    #[tokio::test]
+    async fn test_promotion_links_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = ConfigManager::new(temp_dir.path().to_path_buf()).with_profile(ConfigProfile::Staging);
+        manager.initialize().await.unwrap();
+
+        let source = manager.create_version(config(0), "Staging change".to_string(), "alice".to_string()).await.unwrap();
+        assert!(manager.promote(&source.id, ConfigProfile::Staging, "bob".to_string()).await.is_err());
+
+        let promoted = manager.promote(&source.id, ConfigProfile::Prod, "bob".to_string()).await.unwrap();
+        assert_eq!(promoted.profile, ConfigProfile::Prod);
+        assert_eq!(promoted.config_data, source.config_data);
+        let promotion = promoted.promotion.as_ref().unwrap();
+        assert_eq!(promotion.from_version, source.id);
+        assert_eq!(promotion.promoted_by, "bob");
+
+        // Promoting into another environment leaves this one's current version alone
+        assert_eq!(manager.current_version().await.unwrap().id, source.id);
+        assert_eq!(manager.list_profile_versions(ConfigProfile::Prod).await.len(), 1);
+        assert!(manager.promote(&promoted.id, ConfigProfile::Prod, "bob".to_string()).await.is_err());
+
+        // The link survives a reload
+        let reloaded = ConfigManager::new(temp_dir.path().to_path_buf());
+        reloaded.initialize().await.unwrap();
+        assert_eq!(reloaded.get_version(&promoted.id).await.unwrap().promotion, promoted.promotion);
+    }
+}
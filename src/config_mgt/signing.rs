@@ -0,0 +1,226 @@
+// Configuration version signing
+//
+// Versions are signed with an operator ed25519 key when created and verified
+// against a set of trusted public keys when loaded from disk and before they
+// are applied. The signature covers every field of the version except the
+// signature itself, so editing a version file on disk invalidates it. With
+// enforcement on, unsigned and invalid versions are rejected.
+
+use super::ConfigVersion;
+use crate::keys::{generate_key, KeyBytes, KeyProvider, KEY_ID_CONFIG_SIGNING};
+use anyhow::{Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Alert rule triggered when a version is rejected or its signature is invalid
+pub const CONFIG_SIGNATURE_ALERT_RULE: &str = "config_signature_invalid";
+
+/// Signature attached to a configuration version
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct VersionSignature {
+    /// Signer public key (base64)
+    pub key_id: String,
+    /// ed25519 signature (base64)
+    pub signature: String,
+}
+
+/// Result of verifying a version
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Valid { key_id: String },
+    Unsigned,
+    Invalid { reason: String },
+}
+
+/// Signs versions with the operator key and verifies them against trusted keys
+pub struct ConfigSigner {
+    key: Option<SigningKey>,
+    trusted: Vec<VerifyingKey>,
+    enforce: bool,
+}
+
+impl ConfigSigner {
+    /// Verify against `trusted_keys` (base64 public keys); `enforce` rejects unsigned versions
+    pub fn new(trusted_keys: &[String], enforce: bool) -> Result<Self> {
+        let trusted = trusted_keys.iter()
+            .map(|key| decode_public_key(key))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { key: None, trusted, enforce })
+    }
+
+    /// Sign new versions with this key; its public key is trusted as well
+    pub fn with_signing_key(mut self, seed: &KeyBytes) -> Self {
+        let key = SigningKey::from_bytes(seed);
+        if !self.trusted.contains(&key.verifying_key()) {
+            self.trusted.push(key.verifying_key());
+        }
+        self.key = Some(key);
+        self
+    }
+
+    /// Public key of the signing key (base64)
+    pub fn public_key(&self) -> Option<String> {
+        self.key.as_ref().map(|key| general_purpose::STANDARD.encode(key.verifying_key().as_bytes()))
+    }
+
+    pub fn enforced(&self) -> bool {
+        self.enforce
+    }
+
+    /// Whether a version with this status may be loaded or applied
+    pub fn accepts(&self, status: &SignatureStatus) -> bool {
+        match status {
+            SignatureStatus::Valid { .. } => true,
+            SignatureStatus::Unsigned => !self.enforce,
+            SignatureStatus::Invalid { .. } => !self.enforce,
+        }
+    }
+
+    /// Sign a version in place; versions stay unsigned without a signing key
+    pub fn sign(&self, version: &mut ConfigVersion) -> Result<()> {
+        let key = match &self.key {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let signature = key.sign(&signing_payload(version)?);
+        version.signature = Some(VersionSignature {
+            key_id: general_purpose::STANDARD.encode(key.verifying_key().as_bytes()),
+            signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+        });
+        Ok(())
+    }
+
+    /// Check a version's signature against the trusted keys
+    pub fn verify(&self, version: &ConfigVersion) -> SignatureStatus {
+        let signature = match &version.signature {
+            Some(signature) => signature,
+            None => return SignatureStatus::Unsigned,
+        };
+        match self.check(version, signature) {
+            Ok(()) => SignatureStatus::Valid { key_id: signature.key_id.clone() },
+            Err(e) => SignatureStatus::Invalid { reason: format!("{:#}", e) },
+        }
+    }
+
+    fn check(&self, version: &ConfigVersion, signature: &VersionSignature) -> Result<()> {
+        let key = decode_public_key(&signature.key_id)?;
+        if !self.trusted.contains(&key) {
+            return Err(anyhow::anyhow!("Signed by an untrusted key {}", signature.key_id));
+        }
+        let bytes = general_purpose::STANDARD.decode(&signature.signature)
+            .context("Signature is not valid base64")?;
+        let signature = Signature::from_slice(&bytes)
+            .map_err(|_| anyhow::anyhow!("Signature must be 64 bytes, got {}", bytes.len()))?;
+        key.verify(&signing_payload(version)?, &signature)
+            .map_err(|_| anyhow::anyhow!("Signature does not match the version contents"))
+    }
+}
+
+/// Load the signing key seed from the key provider, creating one on first run
+///
+/// Unlike the 2FA key there is no ephemeral fallback: versions signed with a
+/// key that is lost on restart could never be verified again.
+pub async fn load_signing_key(provider: &dyn KeyProvider) -> Result<KeyBytes> {
+    if let Some(seed) = provider.load_key(KEY_ID_CONFIG_SIGNING).await
+        .with_context(|| format!("Failed to load config signing key from '{}' provider", provider.name()))?
+    {
+        return Ok(seed);
+    }
+
+    let seed = generate_key();
+    provider.store_key(KEY_ID_CONFIG_SIGNING, &seed).await
+        .context("Failed to store a new config signing key")?;
+    info!("Generated new config signing key in '{}' provider", provider.name());
+    Ok(seed)
+}
+
+/// Parse a base64 ed25519 public key
+pub fn decode_public_key(encoded: &str) -> Result<VerifyingKey> {
+    let bytes = general_purpose::STANDARD.decode(encoded.trim())
+        .with_context(|| format!("Public key is not valid base64: {}", encoded))?;
+    let bytes: [u8; 32] = bytes.as_slice().try_into()
+        .map_err(|_| anyhow::anyhow!("Public key must be 32 bytes, got {}", bytes.len()))?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| anyhow::anyhow!("Not an ed25519 public key: {}", encoded))
+}
+
+/// The signed bytes: every field except the signature
+fn signing_payload(version: &ConfigVersion) -> Result<Vec<u8>> {
+    serde_json::to_vec(&serde_json::json!({
+        "id": version.id,
+        "created_at": version.created_at,
+        "created_by": version.created_by,
+        "description": version.description,
+        "parent_id": version.parent_id,
+        "config_data": version.config_data,
+        "validation_status": version.validation_status,
+        "profile": version.profile,
+        "promotion": version.promotion,
+    }))
+    .context("Failed to serialize version for signing")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_mgt::ValidationStatus;
+    use chrono::Utc;
+
+    fn version() -> ConfigVersion {
+        ConfigVersion {
+            id: "v20260101000000".to_string(),
+            created_at: Utc::now(),
+            created_by: "admin".to_string(),
+            description: "Raise start difficulty".to_string(),
+            parent_id: None,
+            config_data: serde_json::json!({"stratum.start_difficulty": 64, "donation": 0}),
+            validation_status: ValidationStatus::Valid,
+            profile: Default::default(),
+            promotion: None,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_and_detect_tampering() {
+        let signer = ConfigSigner::new(&[], true).unwrap().with_signing_key(&[7u8; 32]);
+        let mut version = version();
+        assert_eq!(signer.verify(&version), SignatureStatus::Unsigned);
+        assert!(!signer.accepts(&SignatureStatus::Unsigned));
+
+        signer.sign(&mut version).unwrap();
+        let key_id = signer.public_key().unwrap();
+        assert_eq!(signer.verify(&version), SignatureStatus::Valid { key_id });
+
+        // Signatures survive a round trip through the version file
+        let reloaded: ConfigVersion = serde_json::from_str(&serde_json::to_string_pretty(&version).unwrap()).unwrap();
+        assert!(matches!(signer.verify(&reloaded), SignatureStatus::Valid { .. }));
+
+        version.config_data["donation"] = serde_json::json!(10000);
+        assert!(matches!(signer.verify(&version), SignatureStatus::Invalid { .. }));
+    }
+
+    #[test]
+    fn test_trusted_keys() {
+        let operator = ConfigSigner::new(&[], false).unwrap().with_signing_key(&[1u8; 32]);
+        let mut version = version();
+        operator.sign(&mut version).unwrap();
+
+        // A verifier that only trusts another key rejects the version
+        let other = ConfigSigner::new(&[], true).unwrap().with_signing_key(&[2u8; 32]);
+        let status = other.verify(&version);
+        assert!(matches!(&status, SignatureStatus::Invalid { reason } if reason.contains("untrusted")));
+        assert!(!other.accepts(&status));
+
+        // A verify-only signer configured with the operator's public key accepts it
+        let verifier = ConfigSigner::new(&[operator.public_key().unwrap()], true).unwrap();
+        assert!(verifier.public_key().is_none());
+        assert!(matches!(verifier.verify(&version), SignatureStatus::Valid { .. }));
+
+        assert!(ConfigSigner::new(&["not a key".to_string()], false).is_err());
+        assert!(ConfigSigner::new(&[general_purpose::STANDARD.encode([0u8; 16])], false).is_err());
+    }
+}
@@ -9,6 +9,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+use crate::error::AuthError;
+
 /// Configuration change that requires confirmation
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConfigChangeRequest {
@@ -135,6 +137,17 @@ impl ConfigConfirmation {
             .unwrap_or(RiskLevel::Medium)
     }
 
+    /// Whether a change must be confirmed by a second admin, looking the
+    /// parameter up by full path, else by its last segment
+    fn needs_second_admin(&self, parameter: &str) -> bool {
+        let key = if self.config_meta.contains_key(parameter) {
+            parameter
+        } else {
+            parameter.rsplit('.').next().unwrap_or(parameter)
+        };
+        matches!(self.get_risk_level(key), RiskLevel::High | RiskLevel::Critical)
+    }
+
     /// Create a change request for a configuration parameter
     pub async fn create_change_request(
         &self,
@@ -174,8 +187,9 @@ impl ConfigConfirmation {
         Ok(request)
     }
 
-    /// Confirm a pending change request
-    pub async fn confirm_change(&self, id: &str) -> Result<bool> {
+    /// Confirm a pending change request. High and critical risk changes must
+    /// be confirmed by someone other than the user who proposed them.
+    pub async fn confirm_change(&self, id: &str, confirmer: &str) -> Result<bool> {
         let mut pending = self.pending.write().await;
 
         match pending.get_mut(id) {
@@ -186,6 +200,12 @@ impl ConfigConfirmation {
                     return Ok(false);
                 }
 
+                if request.username == confirmer && self.needs_second_admin(&request.parameter) {
+                    return Err(AuthError::AccessDenied(format!(
+                        "config change {} must be confirmed by a different admin", id
+                    )).into());
+                }
+
                 request.confirmed = true;
                 info!(
                     "Config change confirmed: {} = {:?}",
@@ -388,7 +408,7 @@ mod tests {
         assert!(!request.applied);
 
         // Confirm the change
-        assert!(conf.confirm_change(&request.id).await.unwrap());
+        assert!(conf.confirm_change(&request.id, "bob").await.unwrap());
 
         // Get the request
         let confirmed = conf.get_request(&request.id).await.unwrap();
@@ -401,4 +421,37 @@ mod tests {
         // Request should be removed after application
         assert!(conf.get_request(&request.id).await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_high_risk_change_needs_second_admin() {
+        let conf = ConfigConfirmation::new();
+        let critical = conf
+            .create_change_request(
+                "pplns_ttl_days".to_string(),
+                json!(7),
+                json!(14),
+                "alice".to_string(),
+                "127.0.0.1".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let err = conf.confirm_change(&critical.id, "alice").await.unwrap_err();
+        assert_eq!(crate::error::kind_of(&err), crate::error::ErrorKind::Unauthorized);
+        assert!(!conf.get_request(&critical.id).await.unwrap().confirmed);
+        assert!(conf.confirm_change(&critical.id, "bob").await.unwrap());
+
+        // Medium risk changes may be confirmed by the proposer
+        let medium = conf
+            .create_change_request(
+                "stratum.start_difficulty".to_string(),
+                json!(32),
+                json!(64),
+                "alice".to_string(),
+                "127.0.0.1".to_string(),
+            )
+            .await
+            .unwrap();
+        assert!(conf.confirm_change(&medium.id, "alice").await.unwrap());
+    }
 }
@@ -0,0 +1,360 @@
+// Schema migrations
+//
+// dmpool-owned tables are created and evolved by the numbered SQL files in
+// `migrations/`, compiled into the binary. Every applied file is recorded in
+// `dmpool_schema_migrations` with its SHA-256 checksum, and a file that was
+// edited after it was applied stops the run rather than leaving the schema in
+// an unknown state. Migrations only go up: a fix ships as a new file.
+//
+// Databases set up before the runner existed have no record of 001, which
+// cannot run twice, so it is baselined when its tables are already present.
+// The later files are idempotent and are simply applied and recorded.
+
+use anyhow::{Context, Result};
+use bitcoin::hex::DisplayHex;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use super::DatabaseManager;
+
+/// Advisory lock key held while migrating, so two processes starting
+/// together do not apply the same file twice
+const MIGRATION_LOCK_KEY: i64 = 0x646d_706f_6f6c;
+
+/// One numbered SQL file
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Hex SHA-256 of the SQL
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.sql.as_bytes()).to_lower_hex_string()
+    }
+}
+
+macro_rules! migration {
+    ($version:expr, $name:expr, $file:expr) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../../migrations/", $file)),
+        }
+    };
+}
+
+/// Every migration, in version order
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "admin tables", "001_admin_tables.sql"),
+    migration!(2, "share rollups", "002_share_rollups.sql"),
+    migration!(3, "block payouts", "003_block_payouts.sql"),
+    migration!(4, "share quality", "004_share_quality.sql"),
+    migration!(5, "worker tags", "005_worker_tags.sql"),
+    migration!(6, "fee revenue", "006_fee_revenue.sql"),
+    migration!(7, "payout records", "007_payout_records.sql"),
+    migration!(8, "block luck", "008_block_luck.sql"),
+    migration!(9, "backup catalog", "009_backup_catalog.sql"),
+    migration!(10, "restore drills", "010_restore_drills.sql"),
+    migration!(11, "login history", "011_login_history.sql"),
+    migration!(12, "payout source", "012_payout_source.sql"),
+    migration!(13, "blob store", "013_blob_store.sql"),
+    migration!(14, "payout kill switch", "014_payout_kill_switch.sql"),
+    migration!(15, "search indexes", "015_search_indexes.sql"),
+    migration!(16, "read-only mode", "016_read_only_mode.sql"),
+];
+
+/// A row of `dmpool_schema_migrations`
+#[derive(Clone, Debug, Serialize)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: DateTime<Utc>,
+    /// Recorded without running, for a database that predates the runner
+    pub baselined: bool,
+}
+
+/// Where a migration stands against the database
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has changed since
+    Modified,
+    /// Applied by a newer build this one does not know about
+    Unknown,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MigrationStatus {
+    pub version: i32,
+    pub name: String,
+    pub checksum: String,
+    pub state: MigrationState,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of a migration run
+#[derive(Clone, Debug, Serialize)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    /// Versions recorded as already present
+    pub baselined: Vec<i32>,
+    /// Versions applied, or that would be applied on a dry run
+    pub applied: Vec<i32>,
+    /// Highest applied version afterwards
+    pub schema_version: i32,
+    pub migrations: Vec<MigrationStatus>,
+}
+
+/// Compare the known migrations with the applied ones
+pub fn migration_status(migrations: &[Migration], applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    let mut status: Vec<MigrationStatus> = migrations.iter()
+        .map(|migration| {
+            let checksum = migration.checksum();
+            let record = applied.iter().find(|a| a.version == migration.version);
+            let state = match record {
+                None => MigrationState::Pending,
+                Some(record) if record.checksum != checksum => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                checksum,
+                state,
+                applied_at: record.map(|r| r.applied_at),
+            }
+        })
+        .collect();
+    for record in applied {
+        if !migrations.iter().any(|m| m.version == record.version) {
+            status.push(MigrationStatus {
+                version: record.version,
+                name: record.name.clone(),
+                checksum: record.checksum.clone(),
+                state: MigrationState::Unknown,
+                applied_at: Some(record.applied_at),
+            });
+        }
+    }
+    status.sort_by_key(|s| s.version);
+    status
+}
+
+/// Refuse to run when an applied file has been edited
+pub fn check_status(status: &[MigrationStatus]) -> Result<()> {
+    let modified: Vec<String> = status.iter()
+        .filter(|s| s.state == MigrationState::Modified)
+        .map(|s| format!("{:03} ({})", s.version, s.name))
+        .collect();
+    if !modified.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Migration(s) {} changed after they were applied; add a new migration instead of editing one",
+            modified.join(", ")
+        ));
+    }
+    for unknown in status.iter().filter(|s| s.state == MigrationState::Unknown) {
+        warn!("Database has migration {:03} ({}) that this build does not know about", unknown.version, unknown.name);
+    }
+    Ok(())
+}
+
+impl DatabaseManager {
+    /// Known migrations and whether each has been applied
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        let conn = self.get_conn().await?;
+        let applied = load_applied(&conn).await?;
+        Ok(migration_status(MIGRATIONS, &applied))
+    }
+
+    /// Apply pending migrations in order, each in its own transaction
+    ///
+    /// With `dry_run` nothing is written; the report lists what would run.
+    pub async fn migrate(&self, dry_run: bool) -> Result<MigrationReport> {
+        let mut conn = self.get_conn().await?;
+        conn.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_KEY])
+            .await
+            .context("Failed to take the migration lock")?;
+        let result = run(&mut conn, dry_run).await;
+        if let Err(e) = conn.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_KEY]).await {
+            warn!("Failed to release the migration lock: {}", e);
+        }
+        result
+    }
+}
+
+async fn run(conn: &mut deadpool_postgres::Object, dry_run: bool) -> Result<MigrationReport> {
+    if !dry_run {
+        conn.batch_execute(
+            "CREATE TABLE IF NOT EXISTS dmpool_schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name VARCHAR(255) NOT NULL,
+                checksum VARCHAR(64) NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                execution_ms BIGINT NOT NULL DEFAULT 0,
+                baselined BOOLEAN NOT NULL DEFAULT FALSE
+            )",
+        )
+        .await
+        .context("Failed to create the migrations table")?;
+    }
+
+    let mut applied = load_applied(conn).await?;
+    check_status(&migration_status(MIGRATIONS, &applied))?;
+
+    // A database from before the runner already has 001's tables
+    let mut baselined = Vec::new();
+    if applied.is_empty() {
+        let row = conn.query_one("SELECT to_regclass('public.banned_miners') IS NOT NULL", &[])
+            .await
+            .context("Failed to inspect existing tables")?;
+        if row.get::<_, bool>(0) {
+            let first = &MIGRATIONS[0];
+            if !dry_run {
+                conn.execute(
+                    "INSERT INTO dmpool_schema_migrations (version, name, checksum, baselined) VALUES ($1, $2, $3, TRUE)",
+                    &[&first.version, &first.name, &first.checksum()],
+                )
+                .await
+                .context("Failed to baseline migration 001")?;
+                info!("Baselined migration 001 ({}) for an existing database", first.name);
+            }
+            baselined.push(first.version);
+            applied.push(AppliedMigration {
+                version: first.version,
+                name: first.name.to_string(),
+                checksum: first.checksum(),
+                applied_at: Utc::now(),
+                baselined: true,
+            });
+        }
+    }
+
+    let mut newly_applied = Vec::new();
+    for migration in MIGRATIONS {
+        if applied.iter().any(|a| a.version == migration.version) {
+            continue;
+        }
+        newly_applied.push(migration.version);
+        if dry_run {
+            info!("Would apply migration {:03} ({})", migration.version, migration.name);
+            continue;
+        }
+
+        let started = Instant::now();
+        let tx = conn.transaction().await.context("Failed to start migration transaction")?;
+        tx.batch_execute(migration.sql)
+            .await
+            .with_context(|| format!("Migration {:03} ({}) failed", migration.version, migration.name))?;
+        let execution_ms = started.elapsed().as_millis() as i64;
+        tx.execute(
+            "INSERT INTO dmpool_schema_migrations (version, name, checksum, execution_ms) VALUES ($1, $2, $3, $4)",
+            &[&migration.version, &migration.name, &migration.checksum(), &execution_ms],
+        )
+        .await
+        .context("Failed to record migration")?;
+        tx.commit().await
+            .with_context(|| format!("Failed to commit migration {:03}", migration.version))?;
+        info!("Applied migration {:03} ({}) in {} ms", migration.version, migration.name, execution_ms);
+    }
+
+    let applied = if dry_run { applied } else { load_applied(conn).await? };
+    let mut migrations = migration_status(MIGRATIONS, &applied);
+    if dry_run {
+        // Report the outcome the run would have had
+        for status in migrations.iter_mut().filter(|s| newly_applied.contains(&s.version)) {
+            status.state = MigrationState::Applied;
+        }
+    }
+    let schema_version = migrations.iter()
+        .filter(|s| s.state != MigrationState::Pending)
+        .map(|s| s.version)
+        .max()
+        .unwrap_or(0);
+    Ok(MigrationReport {
+        dry_run,
+        baselined,
+        applied: newly_applied,
+        schema_version,
+        migrations,
+    })
+}
+
+async fn load_applied(conn: &deadpool_postgres::Object) -> Result<Vec<AppliedMigration>> {
+    let exists: bool = conn.query_one("SELECT to_regclass('public.dmpool_schema_migrations') IS NOT NULL", &[])
+        .await
+        .context("Failed to inspect existing tables")?
+        .get(0);
+    if !exists {
+        return Ok(Vec::new());
+    }
+    let rows = conn.query(
+        "SELECT version, name, checksum, applied_at, baselined FROM dmpool_schema_migrations ORDER BY version",
+        &[],
+    )
+    .await
+    .context("Failed to read applied migrations")?;
+    Ok(rows.iter()
+        .map(|row| AppliedMigration {
+            version: row.get(0),
+            name: row.get(1),
+            checksum: row.get(2),
+            applied_at: row.get(3),
+            baselined: row.get(4),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(migration: &Migration, checksum: Option<&str>) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            name: migration.name.to_string(),
+            checksum: checksum.map(str::to_string).unwrap_or_else(|| migration.checksum()),
+            applied_at: Utc::now(),
+            baselined: false,
+        }
+    }
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+            assert_eq!(migration.checksum().len(), 64);
+        }
+        assert_eq!(MIGRATIONS[0].checksum(), MIGRATIONS[0].checksum());
+        assert_ne!(MIGRATIONS[0].checksum(), MIGRATIONS[1].checksum());
+    }
+
+    #[test]
+    fn test_status_detects_pending_modified_and_unknown() {
+        let known = &MIGRATIONS[..3];
+        let mut records = vec![applied(&known[0], None), applied(&known[1], Some("edited"))];
+        records.push(AppliedMigration { version: 99, ..applied(&known[0], None) });
+
+        let status = migration_status(known, &records);
+        let states: Vec<_> = status.iter().map(|s| (s.version, s.state)).collect();
+        assert_eq!(states, vec![
+            (1, MigrationState::Applied),
+            (2, MigrationState::Modified),
+            (3, MigrationState::Pending),
+            (99, MigrationState::Unknown),
+        ]);
+        let err = check_status(&status).unwrap_err();
+        assert!(err.to_string().contains("002"));
+
+        // Unknown newer migrations only warn
+        records.remove(1);
+        assert!(check_status(&migration_status(known, &records)).is_ok());
+    }
+}
@@ -3,14 +3,36 @@
 // This module provides database access for:
 // - Observer API (read-only access to Hydrapool data)
 // - Admin API (full access to admin tables)
+// - Versioned schema migrations for dmpool-owned tables
+// - Schema drift detection against the applied migrations
+// - Connection pool health monitoring and recovery
 
 use anyhow::{Context, Result};
+use crate::loyalty::LoyaltyStatus;
+use crate::luck::{LuckPeriod, LuckStats};
+use crate::payment::{Payout, PayoutStatsBucket, PayoutStatsInterval};
+use crate::revenue::{LedgerEntry, LedgerKind, NewLedgerEntry, RevenueSummary, SummaryPeriod};
+use crate::share_quality::ShareCounts;
+use crate::worker_tags::{TaggedWorker, WorkerFilter, WorkerGroup, WorkerGrouping, WorkerTags};
 use deadpool_postgres::{Config, Pool, Runtime};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use tokio_postgres::NoTls;
 use tracing::{debug, error, info};
 
+mod migrations;
+mod pool_health;
+mod schema;
+mod search;
+
+pub use pool_health::{PoolCounts, PoolHealth, PoolHealthConfig, PoolMonitor, PoolProbe, PoolState};
+pub use search::{like_prefix, SearchMatch, SearchMatchKind, SearchMatchRank, MIN_SEARCH_CHARS};
+pub use schema::{schema_drift, DriftKind, SchemaDrift, SchemaMonitor, SchemaReport, SchemaShape};
+pub use migrations::{
+    check_status, migration_status, AppliedMigration, Migration, MigrationReport, MigrationState, MigrationStatus, MIGRATIONS,
+};
+
 /// Database connection pool manager
 pub struct DatabaseManager {
     pool: Pool,
@@ -61,18 +83,16 @@ impl DatabaseManager {
         Ok(())
     }
 
-    /// Initialize admin tables (run migrations)
+    /// Initialize admin tables (run pending migrations)
     pub async fn init_admin_tables(&self) -> Result<()> {
         info!("Initializing admin tables...");
 
-        let migration_sql = include_str!("../../migrations/001_admin_tables.sql");
-        let conn = self.get_conn().await?;
-
-        conn.batch_execute(migration_sql)
-            .await
-            .context("Failed to execute admin tables migration")?;
-
-        info!("Admin tables initialized successfully");
+        let report = self.migrate(false).await?;
+        info!(
+            "Admin tables initialized successfully (schema version {}, {} migration(s) applied)",
+            report.schema_version,
+            report.applied.len()
+        );
         Ok(())
     }
 }
@@ -84,6 +104,7 @@ impl DatabaseManager {
 /// Pool statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PoolStats {
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub pool_hashrate_3h: u64,
     pub active_miners: i64,
     pub active_workers: i64,
@@ -91,6 +112,7 @@ pub struct PoolStats {
     pub next_block_eta_seconds: i64,
     pub pool_fee_percent: f64,
     pub network_difficulty: u64,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub block_reward: f64,
 }
 
@@ -99,24 +121,35 @@ pub struct PoolStats {
 pub struct MinerStats {
     pub address: String,
     pub shares_in_window: u64,
+    /// Expected earnings (BTC) over the 7-day window at the miner's hashrate, set by the Observer API
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub estimated_reward_window: f64,
+    /// Payout (BTC) if the pool found a block now, set by the Observer API
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub estimated_next_block: f64,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hashrate_3h: u64,
     pub hashrate_avg: HashrateAverage,
     pub workers: Vec<WorkerInfo>,
     pub latest_earnings: Vec<EarningRecord>,
+    /// Address link, set by the Observer API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
+    /// Loyalty tier and streak, set by the Observer API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loyalty: Option<LoyaltyStatus>,
 }
 
 /// Hashrate averages at different time periods
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HashrateAverage {
-    #[serde(rename = "1h")]
+    #[serde(rename = "1h", serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hour_1: u64,
-    #[serde(rename = "6h")]
+    #[serde(rename = "6h", serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hour_6: u64,
-    #[serde(rename = "24h")]
+    #[serde(rename = "24h", serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hour_24: u64,
-    #[serde(rename = "7d")]
+    #[serde(rename = "7d", serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub day_7: u64,
 }
 
@@ -124,10 +157,17 @@ pub struct HashrateAverage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkerInfo {
     pub name: String,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hashrate: u64,
     pub shares: u64,
     pub last_seen: String,
     pub is_online: bool,
+    /// Share acceptance over the last 24 hours
+    pub share_quality: ShareCounts,
+    /// Rejected, stale and duplicate shares as a percentage of all shares
+    pub reject_percent: f64,
+    /// Rig, location and tags parsed from the worker name
+    pub tags: WorkerTags,
 }
 
 /// Earning record (payout)
@@ -135,15 +175,47 @@ pub struct WorkerInfo {
 pub struct EarningRecord {
     pub block_height: i64,
     pub time: String,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub amount_btc: f64,
     pub txid: Option<String>,
     pub confirmations: i32,
 }
 
+/// Block reward credited to an address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressEarning {
+    pub block_height: i64,
+    pub address: String,
+    /// PPLNS difficulty credited for the block
+    pub shares: i64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub reward_sats: i64,
+    pub credited_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Compact miner statistics for bulk requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinerSummary {
+    pub address: String,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate_1h: u64,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate_24h: u64,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate_7d: u64,
+    pub shares_in_window: u64,
+    pub workers_online: i64,
+    pub workers_total: i64,
+    pub last_share_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub balance_sats: i64,
+}
+
 /// Hashrate data point for charts
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HashrateDataPoint {
     pub timestamp: String,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
     pub hashrate: u64,
 }
 
@@ -152,11 +224,17 @@ pub struct HashrateDataPoint {
 pub struct BlockInfo {
     pub height: i64,
     pub time: String,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub reward_btc: f64,
     pub pool_fee_percent: f64,
     pub txid: Option<String>,
     pub confirmations: i32,
     pub payouts_count: i64,
+    /// PPLNS window difficulty as a percentage of the network difficulty
+    pub effort_percent: Option<f64>,
+    /// Block link, set by the Observer API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
 }
 
 /// Block detail with PPLNS distribution
@@ -164,25 +242,49 @@ pub struct BlockInfo {
 pub struct BlockDetail {
     pub height: i64,
     pub time: String,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub reward_btc: f64,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub pool_fee_btc: f64,
     pub network_difficulty: u64,
+    pub effort_percent: Option<f64>,
     pub txid: Option<String>,
     pub confirmations: i32,
     pub pplns_window_shares: i64,
     pub payouts: Vec<PayoutDetail>,
+    /// Block link, set by the Observer API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explorer_url: Option<String>,
 }
 
 /// Payout detail for a block
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayoutDetail {
     pub address: String,
+    #[serde(serialize_with = "crate::observer_api::units::btc")]
     pub amount_btc: f64,
     pub shares: u64,
     #[serde(rename = "share_percent")]
     pub share_percent: f64,
 }
 
+/// Found block to record in block_details_cache and block_payouts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBlockRecord {
+    pub height: i64,
+    pub block_hash: String,
+    pub block_time: chrono::DateTime<chrono::Utc>,
+    pub reward_sats: i64,
+    pub pool_fee_sats: i64,
+    pub pplns_window_shares: i32,
+    pub pplns_total_difficulty: i64,
+    pub network_difficulty: Option<f64>,
+    pub effort_percent: Option<f64>,
+    pub coinbase_txid: Option<String>,
+    /// (miner address, window difficulty, reward sats)
+    pub payouts: Vec<(String, i64, i64)>,
+}
+
 // ============================================================================
 // Query Functions
 // ============================================================================
@@ -283,22 +385,67 @@ impl DatabaseManager {
         // Get latest earnings
         let latest_earnings = self.get_miner_earnings(&conn, address, 10).await?;
 
-        // Calculate estimated rewards
-        let estimated_reward_window = 0.0; // TODO: Calculate based on shares_in_window
-        let estimated_next_block = 0.0; // TODO: Calculate
-
         Ok(Some(MinerStats {
             address: address.to_string(),
             shares_in_window: shares_in_window as u64,
-            estimated_reward_window,
-            estimated_next_block,
+            estimated_reward_window: 0.0,
+            estimated_next_block: 0.0,
             hashrate_3h: hashrate_avg.hour_1,
             hashrate_avg,
             workers,
             latest_earnings,
+            explorer_url: None,
+            loyalty: None,
         })
     }
 
+    /// Compact statistics of known miners among `addresses`, in one query
+    pub async fn get_miner_summaries(&self, addresses: &[String]) -> Result<Vec<MinerSummary>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT m.address, m.balance_sats::BIGINT AS balance_sats,
+                        COALESCE(s.difficulty_1h, 0) AS difficulty_1h,
+                        COALESCE(s.difficulty_24h, 0) AS difficulty_24h,
+                        COALESCE(s.difficulty_7d, 0) AS difficulty_7d,
+                        s.last_share_at,
+                        COALESCE(w.online, 0) AS workers_online,
+                        COALESCE(w.total, 0) AS workers_total
+                 FROM miners m
+                 LEFT JOIN LATERAL (
+                     SELECT SUM(difficulty) FILTER (WHERE created_at > NOW() - INTERVAL '1 hour')::BIGINT AS difficulty_1h,
+                            SUM(difficulty) FILTER (WHERE created_at > NOW() - INTERVAL '24 hours')::BIGINT AS difficulty_24h,
+                            SUM(difficulty)::BIGINT AS difficulty_7d,
+                            MAX(created_at) AS last_share_at
+                     FROM shares
+                     WHERE miner_id = m.id AND created_at > NOW() - INTERVAL '7 days'
+                 ) s ON true
+                 LEFT JOIN LATERAL (
+                     SELECT COUNT(*) FILTER (WHERE is_online) AS online, COUNT(*) AS total
+                     FROM worker_status_cache
+                     WHERE miner_address = m.address
+                 ) w ON true
+                 WHERE m.address = ANY($1)",
+                &[&addresses],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| {
+            let difficulty_7d: i64 = row.get("difficulty_7d");
+            MinerSummary {
+                address: row.get("address"),
+                hashrate_1h: (row.get::<_, i64>("difficulty_1h") as f64 / 3600.0) as u64,
+                hashrate_24h: (row.get::<_, i64>("difficulty_24h") as f64 / 86_400.0) as u64,
+                hashrate_7d: (difficulty_7d as f64 / 604_800.0) as u64,
+                shares_in_window: difficulty_7d as u64,
+                workers_online: row.get("workers_online"),
+                workers_total: row.get("workers_total"),
+                last_share_at: row.get("last_share_at"),
+                balance_sats: row.get("balance_sats"),
+            }
+        }).collect())
+    }
+
     /// Calculate miner hashrate at different time periods
     async fn calculate_miner_hashrate_avg(&self, conn: &deadpool_postgres::Object, address: &str) -> Result<HashrateAverage> {
         let periods = [3600, 21600, 86400, 604800]; // 1h, 6h, 24h, 7d in seconds
@@ -334,10 +481,35 @@ impl DatabaseManager {
             )
             .await?;
 
+        let quality_rows = conn
+            .query(
+                "SELECT worker_name, SUM(accepted)::BIGINT AS accepted, SUM(rejected)::BIGINT AS rejected, SUM(stale)::BIGINT AS stale, SUM(duplicate)::BIGINT AS duplicate
+                 FROM worker_share_quality_hourly
+                 WHERE miner_address = $1 AND hour >= NOW() - INTERVAL '24 hours'
+                 GROUP BY worker_name",
+                &[&address]
+            )
+            .await?;
+
+        let mut quality: HashMap<String, ShareCounts> = HashMap::new();
+        for row in quality_rows {
+            quality.insert(row.get("worker_name"), ShareCounts {
+                accepted: row.get::<_, i64>("accepted") as u64,
+                rejected: row.get::<_, i64>("rejected") as u64,
+                stale: row.get::<_, i64>("stale") as u64,
+                duplicate: row.get::<_, i64>("duplicate") as u64,
+            });
+        }
+
         let mut workers = Vec::new();
         for row in rows {
+            let name: String = row.get("worker_name");
+            let share_quality = quality.remove(&name).unwrap_or_default();
             workers.push(WorkerInfo {
-                name: row.get("worker_name"),
+                reject_percent: share_quality.reject_percent(),
+                share_quality,
+                tags: WorkerTags::parse(&name),
+                name,
                 hashrate: row.get("current_hashrate"),
                 shares: row.get("total_shares"),
                 last_seen: row.get::<_, chrono::DateTime<chrono::Utc>>("last_seen").to_rfc3339(),
@@ -406,7 +578,7 @@ impl DatabaseManager {
 
         let rows = conn
             .query(
-                "SELECT block_height, block_time, reward_sats, pool_fee_sats, coinbase_txid, payout_count FROM block_details_cache ORDER BY block_time DESC LIMIT $1 OFFSET $2",
+                "SELECT block_height, block_time, reward_sats, pool_fee_sats, coinbase_txid, payout_count, effort_percent FROM block_details_cache ORDER BY block_time DESC LIMIT $1 OFFSET $2",
                 &[&limit, &offset]
             )
             .await?;
@@ -424,12 +596,24 @@ impl DatabaseManager {
                 txid: row.get("coinbase_txid"),
                 confirmations: 100, // TODO: Calculate
                 payouts_count: row.get("payout_count"),
+                effort_percent: row.get("effort_percent"),
+                explorer_url: None,
             });
         }
 
         Ok(blocks)
     }
 
+    /// Number of blocks in the block list
+    pub async fn count_blocks(&self) -> Result<i64> {
+        let conn = self.get_conn().await?;
+        let count: i64 = conn
+            .query_one("SELECT COUNT(*) FROM block_details_cache", &[])
+            .await?
+            .get(0);
+        Ok(count)
+    }
+
     /// Get block detail with PPLNS distribution
     pub async fn get_block_detail(&self, height: i64) -> Result<Option<BlockDetail>> {
         let conn = self.get_conn().await?;
@@ -475,11 +659,424 @@ impl DatabaseManager {
             time: block_row.get::<_, chrono::DateTime<chrono::Utc>>("block_time").to_rfc3339(),
             reward_btc: reward_sats as f64 / 100_000_000.0,
             pool_fee_btc: fee_sats as f64 / 100_000_000.0,
-            network_difficulty: 0, // TODO: Get from Bitcoin node
+            network_difficulty: block_row.get::<_, Option<f64>>("network_difficulty").unwrap_or(0.0) as u64,
+            effort_percent: block_row.get("effort_percent"),
             txid: block_row.get("coinbase_txid"),
             confirmations: 100, // TODO: Calculate
             pplns_window_shares: block_row.get("pplns_window_shares"),
             payouts,
+            explorer_url: None,
         }))
     }
+
+    /// Record a found block and its PPLNS distribution
+    ///
+    /// Returns false if the block was already recorded.
+    pub async fn record_found_block(&self, block: &NewBlockRecord) -> Result<bool> {
+        let mut conn = self.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let inserted = tx
+            .execute(
+                "INSERT INTO block_details_cache (block_height, block_hash, block_time, reward_sats, pool_fee_sats, pplns_window_shares, pplns_total_difficulty, payout_count, coinbase_txid, network_difficulty, effort_percent)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                 ON CONFLICT (block_height) DO NOTHING",
+                &[
+                    &(block.height as i32),
+                    &block.block_hash,
+                    &block.block_time,
+                    &block.reward_sats,
+                    &block.pool_fee_sats,
+                    &block.pplns_window_shares,
+                    &block.pplns_total_difficulty,
+                    &(block.payouts.len() as i32),
+                    &block.coinbase_txid,
+                    &block.network_difficulty,
+                    &block.effort_percent,
+                ],
+            )
+            .await
+            .context("Failed to insert block details")?;
+
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        for (address, difficulty, reward_sats) in &block.payouts {
+            tx.execute(
+                "INSERT INTO block_payouts (block_height, miner_address, shares, reward_sats) VALUES ($1, $2, $3, $4)",
+                &[&(block.height as i32), address, difficulty, reward_sats],
+            )
+            .await
+            .context("Failed to insert block payout")?;
+        }
+
+        if block.pool_fee_sats > 0 {
+            tx.execute(
+                "INSERT INTO fee_revenue_ledger (kind, amount_sats, block_height, reference, created_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (kind, reference) DO NOTHING",
+                &[
+                    &LedgerKind::BlockFee.as_str(),
+                    &block.pool_fee_sats,
+                    &(block.height as i32),
+                    &block.height.to_string(),
+                    &block.block_time,
+                ],
+            )
+            .await
+            .context("Failed to record block fee revenue")?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Rolling luck over the last 7 and 30 days and all time
+    pub async fn get_block_luck(&self) -> Result<LuckStats> {
+        let conn = self.get_conn().await?;
+
+        let row = conn
+            .query_one(
+                "SELECT
+                    COUNT(*) FILTER (WHERE block_time > NOW() - INTERVAL '7 days') AS blocks_7d,
+                    COALESCE(SUM(effort_percent) FILTER (WHERE block_time > NOW() - INTERVAL '7 days'), 0) AS effort_7d,
+                    COUNT(*) FILTER (WHERE block_time > NOW() - INTERVAL '30 days') AS blocks_30d,
+                    COALESCE(SUM(effort_percent) FILTER (WHERE block_time > NOW() - INTERVAL '30 days'), 0) AS effort_30d,
+                    COUNT(*) AS blocks_all,
+                    COALESCE(SUM(effort_percent), 0) AS effort_all
+                 FROM block_details_cache
+                 WHERE effort_percent IS NOT NULL",
+                &[]
+            )
+            .await
+            .context("Failed to query block luck")?;
+
+        let period = |blocks: &str, effort: &str| {
+            LuckPeriod::from_totals(row.get::<_, i64>(blocks) as u64, row.get(effort))
+        };
+        Ok(LuckStats {
+            last_7d: period("blocks_7d", "effort_7d"),
+            last_30d: period("blocks_30d", "effort_30d"),
+            all_time: period("blocks_all", "effort_all"),
+        })
+    }
+
+    /// Add share outcome counts to a worker's hourly aggregate
+    pub async fn add_share_quality(
+        &self,
+        address: &str,
+        worker: &str,
+        hour: chrono::DateTime<chrono::Utc>,
+        counts: &ShareCounts,
+    ) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO worker_share_quality_hourly (miner_address, worker_name, hour, accepted, rejected, stale, duplicate)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (miner_address, worker_name, hour) DO UPDATE SET
+                accepted = worker_share_quality_hourly.accepted + EXCLUDED.accepted,
+                rejected = worker_share_quality_hourly.rejected + EXCLUDED.rejected,
+                stale = worker_share_quality_hourly.stale + EXCLUDED.stale,
+                duplicate = worker_share_quality_hourly.duplicate + EXCLUDED.duplicate,
+                updated_at = NOW()",
+            &[
+                &address,
+                &worker,
+                &hour,
+                &(counts.accepted as i64),
+                &(counts.rejected as i64),
+                &(counts.stale as i64),
+                &(counts.duplicate as i64),
+            ],
+        )
+        .await
+        .context("Failed to update share quality")?;
+        Ok(())
+    }
+
+    /// Parse and store tags for workers that are new or renamed
+    ///
+    /// Returns the number of workers tagged.
+    pub async fn sync_worker_tags(&self) -> Result<usize> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT w.miner_address, w.worker_name FROM worker_status_cache w
+                 LEFT JOIN worker_tags t ON t.miner_address = w.miner_address AND t.worker_name = w.worker_name
+                 WHERE t.worker_name IS NULL",
+                &[],
+            )
+            .await?;
+
+        for row in &rows {
+            let address: String = row.get("miner_address");
+            let worker: String = row.get("worker_name");
+            let tags = WorkerTags::parse(&worker);
+            conn.execute(
+                "INSERT INTO worker_tags (miner_address, worker_name, rig, location, tags)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (miner_address, worker_name) DO UPDATE SET
+                    rig = EXCLUDED.rig,
+                    location = EXCLUDED.location,
+                    tags = EXCLUDED.tags,
+                    updated_at = NOW()",
+                &[&address, &worker, &tags.rig, &tags.location, &tags.tags],
+            )
+            .await
+            .context("Failed to store worker tags")?;
+        }
+
+        if !rows.is_empty() {
+            debug!("Tagged {} workers", rows.len());
+        }
+        Ok(rows.len())
+    }
+
+    /// Workers of an address matching a tag filter
+    pub async fn get_tagged_workers(&self, address: &str, filter: &WorkerFilter) -> Result<Vec<TaggedWorker>> {
+        let conn = self.get_conn().await?;
+        let tag = filter.tag.as_deref().map(str::to_lowercase);
+        let location = filter.location.as_deref().map(str::to_lowercase);
+        let rows = conn
+            .query(
+                "SELECT w.miner_address, w.worker_name, t.rig, t.location, t.tags, w.current_hashrate, w.is_online, w.last_seen
+                 FROM worker_status_cache w
+                 JOIN worker_tags t ON t.miner_address = w.miner_address AND t.worker_name = w.worker_name
+                 WHERE w.miner_address = $1
+                   AND ($2::TEXT IS NULL OR $2 = ANY(t.tags))
+                   AND ($3::TEXT IS NULL OR t.location = $3)
+                   AND ($4::TEXT IS NULL OR t.rig = $4)
+                   AND ($5::BOOLEAN IS NULL OR w.is_online = $5)
+                 ORDER BY w.last_seen DESC",
+                &[&address, &tag, &location, &filter.rig, &filter.online],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| TaggedWorker {
+            address: row.get("miner_address"),
+            name: row.get("worker_name"),
+            tags: WorkerTags {
+                rig: row.get("rig"),
+                location: row.get("location"),
+                tags: row.get("tags"),
+            },
+            hashrate: row.get::<_, i64>("current_hashrate").max(0) as u64,
+            is_online: row.get("is_online"),
+            last_seen: row.get::<_, chrono::DateTime<chrono::Utc>>("last_seen").to_rfc3339(),
+        }).collect())
+    }
+
+    /// Worker counts and hashrate per tag or location, for one address or the whole pool
+    pub async fn get_worker_groups(&self, address: Option<&str>, grouping: WorkerGrouping) -> Result<Vec<WorkerGroup>> {
+        let conn = self.get_conn().await?;
+        let key = match grouping {
+            WorkerGrouping::Tag => "UNNEST(t.tags)",
+            WorkerGrouping::Location => "t.location",
+        };
+        let sql = format!(
+            "SELECT g.key, COUNT(*)::BIGINT AS workers,
+                    COUNT(*) FILTER (WHERE g.is_online)::BIGINT AS online,
+                    COALESCE(SUM(g.current_hashrate) FILTER (WHERE g.is_online), 0)::BIGINT AS hashrate
+             FROM (
+                SELECT {} AS key, w.is_online, w.current_hashrate
+                FROM worker_status_cache w
+                JOIN worker_tags t ON t.miner_address = w.miner_address AND t.worker_name = w.worker_name
+                WHERE $1::TEXT IS NULL OR w.miner_address = $1
+             ) g
+             WHERE g.key IS NOT NULL
+             GROUP BY g.key
+             ORDER BY g.key",
+            key
+        );
+        let rows = conn.query(&sql, &[&address]).await?;
+
+        Ok(rows.iter().map(|row| WorkerGroup {
+            key: row.get("key"),
+            workers: row.get::<_, i64>("workers") as u64,
+            online: row.get::<_, i64>("online") as u64,
+            hashrate: row.get::<_, i64>("hashrate").max(0) as u64,
+        }).collect())
+    }
+
+    /// Add a fee revenue ledger entry, returning false if its reference was already recorded
+    pub async fn add_ledger_entry(&self, entry: &NewLedgerEntry) -> Result<bool> {
+        let conn = self.get_conn().await?;
+        let inserted = conn
+            .execute(
+                "INSERT INTO fee_revenue_ledger (kind, amount_sats, block_height, reference, note)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (kind, reference) DO NOTHING",
+                &[
+                    &entry.kind.as_str(),
+                    &entry.amount_satoshis,
+                    &entry.block_height.map(|h| h as i32),
+                    &entry.reference,
+                    &entry.note,
+                ],
+            )
+            .await
+            .context("Failed to add ledger entry")?;
+        Ok(inserted > 0)
+    }
+
+    /// Fee revenue ledger entries in a time range, newest first
+    pub async fn get_ledger_entries(
+        &self,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        limit: i64,
+    ) -> Result<Vec<LedgerEntry>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT id, kind, amount_sats, block_height, reference, note, created_at FROM fee_revenue_ledger
+                 WHERE created_at >= $1 AND created_at < $2
+                 ORDER BY created_at DESC
+                 LIMIT $3",
+                &[&from, &to, &limit],
+            )
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(LedgerEntry {
+                    id: row.get("id"),
+                    kind: row.get::<_, String>("kind").parse()?,
+                    amount_satoshis: row.get("amount_sats"),
+                    block_height: row.get::<_, Option<i32>>("block_height").map(i64::from),
+                    reference: row.get("reference"),
+                    note: row.get("note"),
+                    created_at: row.get("created_at"),
+                })
+            })
+            .collect()
+    }
+
+    /// Fee revenue totals per day or month in a time range
+    pub async fn get_revenue_summary(
+        &self,
+        period: SummaryPeriod,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<RevenueSummary>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT date_trunc($1, created_at) AS period_start,
+                        COUNT(*) FILTER (WHERE kind = 'block_fee')::BIGINT AS blocks,
+                        COALESCE(SUM(amount_sats) FILTER (WHERE kind = 'block_fee'), 0)::BIGINT AS block_fees,
+                        COALESCE(SUM(amount_sats) FILTER (WHERE kind = 'payout_fee'), 0)::BIGINT AS payout_fees,
+                        COALESCE(SUM(amount_sats) FILTER (WHERE kind = 'donation'), 0)::BIGINT AS donations,
+                        COALESCE(SUM(amount_sats) FILTER (WHERE kind = 'referral_credit'), 0)::BIGINT AS referral_credits,
+                        COALESCE(SUM(amount_sats) FILTER (WHERE kind = 'internal_transfer'), 0)::BIGINT AS internal_transfers,
+                        COALESCE(SUM(amount_sats), 0)::BIGINT AS net
+                 FROM fee_revenue_ledger
+                 WHERE created_at >= $2 AND created_at < $3
+                 GROUP BY 1
+                 ORDER BY 1",
+                &[&period.trunc_unit(), &from, &to],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| RevenueSummary {
+            period_start: row.get("period_start"),
+            blocks: row.get("blocks"),
+            block_fees_satoshis: row.get("block_fees"),
+            payout_fees_satoshis: row.get("payout_fees"),
+            donations_satoshis: row.get("donations"),
+            referral_credits_satoshis: row.get("referral_credits"),
+            internal_transfers_satoshis: row.get("internal_transfers"),
+            net_satoshis: row.get("net"),
+        }).collect())
+    }
+
+    /// Insert or update a sent payout
+    pub async fn upsert_payout_record(&self, payout: &Payout) -> Result<()> {
+        let conn = self.get_conn().await?;
+        let fee_sats = payout.intent.as_ref().map_or(0, |i| i.fee_satoshis) as i64;
+        let broadcast_at = payout.broadcast_at.unwrap_or(payout.created_at);
+        conn.execute(
+            "INSERT INTO payout_records (payout_id, miner_address, amount_sats, fee_sats, txid, status, confirmations, broadcast_at, source)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (payout_id) DO UPDATE SET
+                txid = EXCLUDED.txid,
+                status = EXCLUDED.status,
+                confirmations = EXCLUDED.confirmations,
+                fee_sats = GREATEST(payout_records.fee_sats, EXCLUDED.fee_sats),
+                updated_at = NOW()",
+            &[
+                &payout.id,
+                &payout.address,
+                &(payout.amount_satoshis as i64),
+                &fee_sats,
+                &payout.txid,
+                &format!("{:?}", payout.status).to_lowercase(),
+                &(payout.confirmations as i32),
+                &broadcast_at,
+                &payout.source.as_str(),
+            ],
+        )
+        .await
+        .context("Failed to record payout")?;
+        Ok(())
+    }
+
+    /// Payout totals per time bucket, pool-wide or for one address
+    pub async fn get_payout_stats(
+        &self,
+        address: Option<&str>,
+        interval: PayoutStatsInterval,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<PayoutStatsBucket>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT date_trunc($1, broadcast_at) AS bucket_start,
+                        COUNT(*)::BIGINT AS payout_count,
+                        COALESCE(SUM(amount_sats), 0)::BIGINT AS total_paid,
+                        COALESCE(SUM(fee_sats), 0)::BIGINT AS total_fee,
+                        COALESCE(AVG(amount_sats), 0)::BIGINT AS average_payout,
+                        COUNT(DISTINCT miner_address)::BIGINT AS unique_addresses
+                 FROM payout_records
+                 WHERE broadcast_at >= $2 AND broadcast_at < $3
+                   AND ($4::TEXT IS NULL OR miner_address = $4)
+                 GROUP BY 1
+                 ORDER BY 1",
+                &[&interval.trunc_unit(), &from, &to, &address],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| PayoutStatsBucket {
+            bucket_start: row.get("bucket_start"),
+            payout_count: row.get("payout_count"),
+            total_paid_satoshis: row.get("total_paid"),
+            total_fee_satoshis: row.get("total_fee"),
+            average_payout_satoshis: row.get("average_payout"),
+            unique_addresses: row.get("unique_addresses"),
+        }).collect())
+    }
+
+    /// Block earnings of several addresses, newest first
+    pub async fn get_earnings_for_addresses(&self, addresses: &[String], limit: i64) -> Result<Vec<AddressEarning>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT block_height, miner_address, shares, reward_sats, created_at FROM block_payouts
+                 WHERE miner_address = ANY($1)
+                 ORDER BY block_height DESC, miner_address
+                 LIMIT $2",
+                &[&addresses, &limit],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| AddressEarning {
+            block_height: row.get::<_, i32>("block_height") as i64,
+            address: row.get("miner_address"),
+            shares: row.get("shares"),
+            reward_sats: row.get("reward_sats"),
+            credited_at: row.get("created_at"),
+        }).collect())
+    }
 }
@@ -0,0 +1,358 @@
+// PostgreSQL pool health
+//
+// deadpool only notices a dead server when a query fails on a broken
+// connection. The monitor pings the pool on an interval, recycles connections
+// that are too old or idle too long, and when the ping fails drops every idle
+// connection and retries with exponential backoff until the server is back.
+// Its snapshot (state, saturation, failures) is surfaced in the health check
+// so the rest of the pool can run degraded instead of failing blind.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::DatabaseManager;
+
+/// The `[dmpool.database.health]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolHealthConfig {
+    pub enabled: bool,
+    /// Seconds between pings while the database is up
+    pub interval_secs: u64,
+    /// Seconds before a ping counts as failed
+    pub timeout_secs: u64,
+    /// Connections older than this are closed and reopened
+    pub max_lifetime_secs: u64,
+    /// Connections unused for this long are closed
+    pub max_idle_secs: u64,
+    /// Longest wait between reconnect attempts while the database is down
+    pub max_backoff_secs: u64,
+    /// Connections in use, as a percent of the pool, that mark it saturated
+    pub saturation_percent: f64,
+}
+
+impl Default for PoolHealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 15,
+            timeout_secs: 5,
+            max_lifetime_secs: 1800,
+            max_idle_secs: 600,
+            max_backoff_secs: 60,
+            saturation_percent: 90.0,
+        }
+    }
+}
+
+impl PoolHealthConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 || self.timeout_secs == 0 {
+            return Err(anyhow::anyhow!("interval_secs and timeout_secs must be positive"));
+        }
+        if self.timeout_secs > self.interval_secs {
+            return Err(anyhow::anyhow!("timeout_secs must not exceed interval_secs ({})", self.interval_secs));
+        }
+        if self.max_idle_secs == 0 || self.max_lifetime_secs < self.max_idle_secs {
+            return Err(anyhow::anyhow!("max_lifetime_secs must be at least max_idle_secs, which must be positive"));
+        }
+        if self.max_backoff_secs == 0 {
+            return Err(anyhow::anyhow!("max_backoff_secs must be positive"));
+        }
+        if !(1.0..=100.0).contains(&self.saturation_percent) {
+            return Err(anyhow::anyhow!("saturation_percent must be between 1 and 100"));
+        }
+        Ok(())
+    }
+
+    /// Wait before the next check after `failures` consecutive failed pings
+    pub fn next_delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::from_secs(self.interval_secs);
+        }
+        let backoff = 1u64.checked_shl(failures - 1).unwrap_or(u64::MAX);
+        Duration::from_secs(backoff.min(self.max_backoff_secs))
+    }
+}
+
+/// Connection counts of the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolCounts {
+    pub max_size: usize,
+    /// Open connections
+    pub size: usize,
+    /// Open connections not checked out
+    pub available: usize,
+    /// Callers waiting for a connection
+    pub waiting: usize,
+}
+
+impl PoolCounts {
+    /// Connections checked out, as a percent of the pool
+    pub fn saturation_percent(&self) -> f64 {
+        if self.max_size == 0 {
+            return 0.0;
+        }
+        (self.size.saturating_sub(self.available)) as f64 * 100.0 / self.max_size as f64
+    }
+}
+
+/// The pool operations the monitor needs
+#[async_trait]
+pub trait PoolProbe: Send + Sync {
+    /// Run a trivial query on a pooled connection
+    async fn ping(&self) -> Result<()>;
+    fn counts(&self) -> PoolCounts;
+    /// Close connections past the age or idle limits; returns how many
+    fn recycle(&self, max_lifetime: Duration, max_idle: Duration) -> usize;
+    /// Close every idle connection so the next checkout reconnects
+    fn reset(&self) -> usize;
+}
+
+#[async_trait]
+impl PoolProbe for DatabaseManager {
+    async fn ping(&self) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.simple_query("SELECT 1").await
+            .map_err(|e| anyhow::anyhow!("Ping failed: {}", e))?;
+        Ok(())
+    }
+
+    fn counts(&self) -> PoolCounts {
+        let status = self.pool.status();
+        PoolCounts {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
+    }
+
+    fn recycle(&self, max_lifetime: Duration, max_idle: Duration) -> usize {
+        self.pool.retain(|client, metrics| {
+            !client.is_closed() && metrics.age() < max_lifetime && metrics.last_used() < max_idle
+        }).removed.len()
+    }
+
+    fn reset(&self) -> usize {
+        self.pool.retain(|_, _| false).removed.len()
+    }
+}
+
+/// Overall state of the pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolState {
+    /// Not checked yet
+    #[default]
+    Unknown,
+    Healthy,
+    /// Reachable but saturated
+    Degraded,
+    /// Pings fail; reconnecting with backoff
+    Down,
+}
+
+/// Latest pool health snapshot
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PoolHealth {
+    pub state: PoolState,
+    pub counts: PoolCounts,
+    pub saturation_percent: f64,
+    pub ping_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_ok_at: Option<DateTime<Utc>>,
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Times the database came back after being down
+    pub reconnects: u64,
+    /// Connections closed for age or idleness
+    pub recycled_connections: u64,
+    /// Checks that found the pool saturated
+    pub saturated_checks: u64,
+}
+
+impl PoolHealth {
+    /// Health check status: "healthy", "degraded" or "unhealthy"
+    pub fn status(&self) -> &'static str {
+        match self.state {
+            PoolState::Healthy | PoolState::Unknown => "healthy",
+            PoolState::Degraded => "degraded",
+            PoolState::Down => "unhealthy",
+        }
+    }
+}
+
+/// Periodically pings the pool and recovers it after outages
+pub struct PoolMonitor {
+    config: PoolHealthConfig,
+    probe: Arc<dyn PoolProbe>,
+    health: RwLock<PoolHealth>,
+}
+
+impl PoolMonitor {
+    pub fn new(config: PoolHealthConfig, probe: Arc<dyn PoolProbe>) -> Self {
+        Self {
+            config,
+            probe,
+            health: RwLock::new(PoolHealth::default()),
+        }
+    }
+
+    /// Latest snapshot
+    pub async fn health(&self) -> PoolHealth {
+        self.health.read().await.clone()
+    }
+
+    /// Ping the pool once and update the snapshot
+    pub async fn check(&self) -> PoolHealth {
+        let started = Instant::now();
+        let ping = match tokio::time::timeout(Duration::from_secs(self.config.timeout_secs), self.probe.ping()).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("Ping timed out after {}s", self.config.timeout_secs)),
+        };
+
+        let mut health = self.health.write().await;
+        let now = Utc::now();
+        health.checked_at = Some(now);
+        match ping {
+            Ok(()) => {
+                if health.state == PoolState::Down {
+                    health.reconnects += 1;
+                    info!("PostgreSQL is reachable again after {} failed pings", health.consecutive_failures);
+                }
+                health.ping_ms = Some(started.elapsed().as_millis() as u64);
+                health.consecutive_failures = 0;
+                health.last_error = None;
+                health.last_ok_at = Some(now);
+                health.recycled_connections += self.probe.recycle(
+                    Duration::from_secs(self.config.max_lifetime_secs),
+                    Duration::from_secs(self.config.max_idle_secs),
+                ) as u64;
+
+                health.counts = self.probe.counts();
+                health.saturation_percent = health.counts.saturation_percent();
+                let saturated = health.counts.waiting > 0 || health.saturation_percent >= self.config.saturation_percent;
+                if saturated {
+                    health.saturated_checks += 1;
+                    warn!(
+                        "PostgreSQL pool saturated: {}/{} connections in use, {} waiting",
+                        health.counts.size - health.counts.available, health.counts.max_size, health.counts.waiting
+                    );
+                }
+                health.state = if saturated { PoolState::Degraded } else { PoolState::Healthy };
+            }
+            Err(e) => {
+                health.consecutive_failures += 1;
+                health.ping_ms = None;
+                health.last_error = Some(format!("{:#}", e));
+                // Connections to a restarted server are dead; open fresh ones
+                let dropped = self.probe.reset();
+                health.counts = self.probe.counts();
+                health.saturation_percent = health.counts.saturation_percent();
+                if health.state != PoolState::Down {
+                    warn!("PostgreSQL is unreachable, dropped {} idle connections: {:#}", dropped, e);
+                }
+                health.state = PoolState::Down;
+            }
+        }
+        health.clone()
+    }
+
+    /// Check on the configured interval, backing off while the database is down
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let health = self.check().await;
+                tokio::time::sleep(self.config.next_delay(health.consecutive_failures)).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct FakePool {
+        up: AtomicBool,
+        in_use: AtomicUsize,
+        resets: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PoolProbe for FakePool {
+        async fn ping(&self) -> Result<()> {
+            if self.up.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("connection refused"))
+            }
+        }
+
+        fn counts(&self) -> PoolCounts {
+            let in_use = self.in_use.load(Ordering::SeqCst);
+            PoolCounts { max_size: 10, size: in_use.max(2), available: 2usize.saturating_sub(in_use), waiting: 0 }
+        }
+
+        fn recycle(&self, _max_lifetime: Duration, _max_idle: Duration) -> usize {
+            1
+        }
+
+        fn reset(&self) -> usize {
+            self.resets.fetch_add(1, Ordering::SeqCst);
+            2
+        }
+    }
+
+    #[test]
+    fn test_backoff_and_config() {
+        let config = PoolHealthConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.next_delay(0), Duration::from_secs(15));
+        assert_eq!(config.next_delay(1), Duration::from_secs(1));
+        assert_eq!(config.next_delay(4), Duration::from_secs(8));
+        assert_eq!(config.next_delay(20), Duration::from_secs(60));
+        assert_eq!(config.next_delay(80), Duration::from_secs(60));
+        assert!(PoolHealthConfig { timeout_secs: 30, ..Default::default() }.validate().is_err());
+        assert!(PoolHealthConfig { saturation_percent: 0.0, ..Default::default() }.validate().is_err());
+
+        let counts = PoolCounts { max_size: 16, size: 10, available: 2, waiting: 0 };
+        assert_eq!(counts.saturation_percent(), 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_outage_recovery_and_saturation() {
+        let pool = Arc::new(FakePool { up: AtomicBool::new(true), in_use: AtomicUsize::new(0), resets: AtomicUsize::new(0) });
+        let monitor = PoolMonitor::new(PoolHealthConfig::default(), pool.clone());
+
+        let health = monitor.check().await;
+        assert_eq!(health.state, PoolState::Healthy);
+        assert_eq!(health.recycled_connections, 1);
+
+        // Server restarts: pings fail and idle connections are dropped each time
+        pool.up.store(false, Ordering::SeqCst);
+        monitor.check().await;
+        let health = monitor.check().await;
+        assert_eq!((health.state, health.consecutive_failures), (PoolState::Down, 2));
+        assert_eq!(health.status(), "unhealthy");
+        assert_eq!(pool.resets.load(Ordering::SeqCst), 2);
+
+        pool.up.store(true, Ordering::SeqCst);
+        pool.in_use.store(9, Ordering::SeqCst);
+        let health = monitor.check().await;
+        assert_eq!(health.reconnects, 1);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.state, PoolState::Degraded);
+        assert_eq!(health.saturated_checks, 1);
+        assert_eq!(monitor.health().await.status(), "degraded");
+    }
+}
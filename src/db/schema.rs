@@ -0,0 +1,463 @@
+// Schema drift detection
+//
+// The expected schema is read from the migration SQL itself: the tables,
+// columns, indexes and views that the applied migrations create, alter or
+// drop. It is compared with what `information_schema` and `pg_catalog` report
+// for the current schema, so a column dropped or an index removed by hand
+// shows up as a warning instead of as a failing query later. Only
+// dmpool-owned tables are compared; the Hydrapool tables next to them are
+// not ours to check. Indexes backing primary key and unique constraints are
+// left out on both sides since they are named by Postgres.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::migrations::{Migration, MigrationState, MIGRATIONS};
+use super::DatabaseManager;
+
+/// Tables with their columns, indexes with their table, and views
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaShape {
+    pub tables: BTreeMap<String, BTreeSet<String>>,
+    pub indexes: BTreeMap<String, String>,
+    pub views: BTreeSet<String>,
+}
+
+impl SchemaShape {
+    /// Schema left behind by running `migrations` in order
+    pub fn from_migrations(migrations: &[&Migration]) -> Self {
+        let mut shape = Self::default();
+        for migration in migrations {
+            for statement in statements(migration.sql) {
+                shape.apply(&statement);
+            }
+        }
+        shape
+    }
+
+    fn apply(&mut self, tokens: &[String]) {
+        let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+        match words.as_slice() {
+            ["create", "table", rest @ ..] => {
+                let rest = skip(rest, &["if", "not", "exists"]);
+                let Some((name, rest)) = rest.split_first() else {
+                    return;
+                };
+                let columns = rest.strip_prefix(&["("])
+                    .map(|body| items(body).into_iter()
+                        .filter_map(|item| item.first().copied())
+                        .filter(|first| !TABLE_CONSTRAINTS.contains(first))
+                        .map(str::to_string)
+                        .collect())
+                    .unwrap_or_default();
+                self.tables.insert(unqualified(name), columns);
+            }
+            ["alter", "table", rest @ ..] => {
+                let rest = skip(skip(rest, &["if", "exists"]), &["only"]);
+                let Some((name, actions)) = rest.split_first() else {
+                    return;
+                };
+                let table = unqualified(name);
+                for action in items(actions) {
+                    self.alter(&table, &action);
+                }
+            }
+            ["create", rest @ ..] if skip(rest, &["unique"]).first() == Some(&"index") => {
+                let rest = skip(skip(rest, &["unique"]), &["index"]);
+                let rest = skip(skip(rest, &["concurrently"]), &["if", "not", "exists"]);
+                if let [name, "on", "only", table, ..] | [name, "on", table, ..] = rest {
+                    self.indexes.insert(unqualified(name), unqualified(table));
+                }
+            }
+            ["create", rest @ ..] if skip(rest, &["or", "replace"]).first() == Some(&"view") => {
+                let rest = skip(skip(rest, &["or", "replace"]), &["view"]);
+                if let Some(name) = rest.first() {
+                    self.views.insert(unqualified(name));
+                }
+            }
+            ["drop", kind @ ("table" | "index" | "view"), rest @ ..] => {
+                let rest = skip(skip(rest, &["concurrently"]), &["if", "exists"]);
+                for name in items(rest).iter().filter_map(|item| item.first()) {
+                    let name = unqualified(name);
+                    match *kind {
+                        "table" => {
+                            self.tables.remove(&name);
+                            self.indexes.retain(|_, table| *table != name);
+                        }
+                        "index" => {
+                            self.indexes.remove(&name);
+                        }
+                        _ => {
+                            self.views.remove(&name);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn alter(&mut self, table: &str, action: &[&str]) {
+        match action {
+            ["add", "constraint", ..] | ["drop", "constraint", ..] => {}
+            ["add", rest @ ..] => {
+                let rest = skip(skip(rest, &["column"]), &["if", "not", "exists"]);
+                if let (Some(column), Some(columns)) = (rest.first(), self.tables.get_mut(table)) {
+                    columns.insert(unqualified(column));
+                }
+            }
+            ["drop", rest @ ..] => {
+                let rest = skip(skip(rest, &["column"]), &["if", "exists"]);
+                if let (Some(column), Some(columns)) = (rest.first(), self.tables.get_mut(table)) {
+                    columns.remove(&unqualified(column));
+                }
+            }
+            ["rename", "column", from, "to", to] | ["rename", from, "to", to] => {
+                if let Some(columns) = self.tables.get_mut(table) {
+                    columns.remove(&unqualified(from));
+                    columns.insert(unqualified(to));
+                }
+            }
+            ["rename", "to", to] => {
+                if let Some(columns) = self.tables.remove(table) {
+                    let to = unqualified(to);
+                    for index_table in self.indexes.values_mut().filter(|t| t.as_str() == table) {
+                        *index_table = to.clone();
+                    }
+                    self.tables.insert(to, columns);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// First words of table constraints in a CREATE TABLE body
+const TABLE_CONSTRAINTS: &[&str] = &["constraint", "primary", "unique", "check", "foreign", "exclude", "like"];
+
+/// Statements of `sql` as lower-cased tokens, with comments and `$$` bodies removed
+fn statements(sql: &str) -> Vec<Vec<String>> {
+    let mut statements = Vec::new();
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    let mut word = String::new();
+    let flush = |word: &mut String, tokens: &mut Vec<String>| {
+        if !word.is_empty() {
+            tokens.push(std::mem::take(word).to_lowercase());
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                flush(&mut word, &mut tokens);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                flush(&mut word, &mut tokens);
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '$' && c == '$' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '\'' | '"' => {
+                flush(&mut word, &mut tokens);
+                let mut quoted = String::new();
+                for inner in chars.by_ref() {
+                    if inner == c {
+                        break;
+                    }
+                    quoted.push(inner);
+                }
+                // Quoted identifiers keep their case, string literals are only placeholders
+                tokens.push(if c == '"' { quoted } else { "'".to_string() });
+            }
+            '(' | ')' | ',' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(c.to_string());
+            }
+            ';' => {
+                flush(&mut word, &mut tokens);
+                if !tokens.is_empty() {
+                    statements.push(std::mem::take(&mut tokens));
+                }
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens);
+    if !tokens.is_empty() {
+        statements.push(tokens);
+    }
+    statements
+}
+
+/// `tokens` without the leading `words`, if it starts with them
+fn skip<'a, 'b>(tokens: &'b [&'a str], words: &[&'a str]) -> &'b [&'a str] {
+    tokens.strip_prefix(words).unwrap_or(tokens)
+}
+
+/// Comma-separated items at the top level, up to the closing parenthesis
+fn items<'a>(tokens: &[&'a str]) -> Vec<Vec<&'a str>> {
+    let mut items = vec![Vec::new()];
+    let mut depth = 0usize;
+    for &token in tokens {
+        match token {
+            "(" => depth += 1,
+            ")" if depth == 0 => break,
+            ")" => depth -= 1,
+            "," if depth == 0 => {
+                items.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(item) = items.last_mut() {
+            item.push(token);
+        }
+    }
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+fn unqualified(name: &str) -> String {
+    name.rsplit('.').next().unwrap_or(name).to_string()
+}
+
+/// What differs from the expected schema
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    MissingTable,
+    MissingColumn,
+    MissingIndex,
+    MissingView,
+    /// A column on a dmpool table that no migration created
+    UnexpectedColumn,
+    /// An index on a dmpool table that no migration created
+    UnexpectedIndex,
+}
+
+/// One difference between the live and expected schema
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaDrift {
+    pub kind: DriftKind,
+    pub table: String,
+    /// Column, index or view; the table itself for missing tables
+    pub name: String,
+}
+
+impl std::fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            DriftKind::MissingTable => write!(f, "table {} is missing", self.table),
+            DriftKind::MissingColumn => write!(f, "column {}.{} is missing", self.table, self.name),
+            DriftKind::MissingIndex => write!(f, "index {} on {} is missing", self.name, self.table),
+            DriftKind::MissingView => write!(f, "view {} is missing", self.name),
+            DriftKind::UnexpectedColumn => write!(f, "column {}.{} is not created by any migration", self.table, self.name),
+            DriftKind::UnexpectedIndex => write!(f, "index {} on {} is not created by any migration", self.name, self.table),
+        }
+    }
+}
+
+/// Differences of `live` from `expected`, on the tables `expected` knows
+pub fn schema_drift(expected: &SchemaShape, live: &SchemaShape) -> Vec<SchemaDrift> {
+    let drift = |kind, table: &str, name: &str| SchemaDrift { kind, table: table.to_string(), name: name.to_string() };
+    let mut found = Vec::new();
+    for (table, columns) in &expected.tables {
+        let Some(live_columns) = live.tables.get(table) else {
+            found.push(drift(DriftKind::MissingTable, table, table));
+            continue;
+        };
+        found.extend(columns.difference(live_columns).map(|c| drift(DriftKind::MissingColumn, table, c)));
+        found.extend(live_columns.difference(columns).map(|c| drift(DriftKind::UnexpectedColumn, table, c)));
+    }
+    for (index, table) in &expected.indexes {
+        if !live.indexes.contains_key(index) && live.tables.contains_key(table) {
+            found.push(drift(DriftKind::MissingIndex, table, index));
+        }
+    }
+    for (index, table) in &live.indexes {
+        if expected.tables.contains_key(table) && !expected.indexes.contains_key(index) {
+            found.push(drift(DriftKind::UnexpectedIndex, table, index));
+        }
+    }
+    for view in expected.views.difference(&live.views) {
+        found.push(drift(DriftKind::MissingView, view, view));
+    }
+    found
+}
+
+/// Outcome of a schema check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaReport {
+    pub checked_at: DateTime<Utc>,
+    /// Highest applied migration the expectation is built from
+    pub schema_version: i32,
+    pub tables_checked: usize,
+    pub drift: Vec<SchemaDrift>,
+}
+
+impl SchemaReport {
+    pub fn is_clean(&self) -> bool {
+        self.drift.is_empty()
+    }
+}
+
+impl DatabaseManager {
+    /// Tables, constraint-free indexes and views of the current schema
+    pub async fn live_schema(&self) -> Result<SchemaShape> {
+        let conn = self.get_conn().await?;
+        let mut shape = SchemaShape::default();
+        let columns = conn.query(
+            "SELECT c.table_name::text, c.column_name::text FROM information_schema.columns c
+             JOIN information_schema.tables t ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+             WHERE c.table_schema = current_schema() AND t.table_type = 'BASE TABLE'",
+            &[],
+        )
+        .await
+        .context("Failed to read table columns")?;
+        for row in &columns {
+            shape.tables.entry(row.get(0)).or_default().insert(row.get(1));
+        }
+        let indexes = conn.query(
+            "SELECT ic.relname::text, t.relname::text FROM pg_index x
+             JOIN pg_class ic ON ic.oid = x.indexrelid
+             JOIN pg_class t ON t.oid = x.indrelid
+             JOIN pg_namespace n ON n.oid = t.relnamespace
+             WHERE n.nspname = current_schema()
+               AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = x.indexrelid)",
+            &[],
+        )
+        .await
+        .context("Failed to read indexes")?;
+        for row in &indexes {
+            shape.indexes.insert(row.get(0), row.get(1));
+        }
+        let views = conn.query("SELECT table_name::text FROM information_schema.views WHERE table_schema = current_schema()", &[])
+            .await
+            .context("Failed to read views")?;
+        shape.views = views.iter().map(|row| row.get(0)).collect();
+        Ok(shape)
+    }
+
+    /// Compare the live schema with what the applied migrations should have left
+    pub async fn check_schema(&self) -> Result<SchemaReport> {
+        let status = self.migration_status().await?;
+        let applied: Vec<&Migration> = MIGRATIONS.iter()
+            .filter(|m| status.iter().any(|s| s.version == m.version && s.state != MigrationState::Pending))
+            .collect();
+        let expected = SchemaShape::from_migrations(&applied);
+        let live = self.live_schema().await?;
+        Ok(SchemaReport {
+            checked_at: Utc::now(),
+            schema_version: applied.iter().map(|m| m.version).max().unwrap_or(0),
+            tables_checked: expected.tables.len(),
+            drift: schema_drift(&expected, &live),
+        })
+    }
+}
+
+/// Keeps the last schema check for health reports
+pub struct SchemaMonitor {
+    db: Arc<DatabaseManager>,
+    latest: RwLock<Option<SchemaReport>>,
+}
+
+impl SchemaMonitor {
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self { db, latest: RwLock::new(None) }
+    }
+
+    /// Check now, logging each difference as a warning
+    pub async fn check(&self) -> Result<SchemaReport> {
+        let report = self.db.check_schema().await?;
+        if report.is_clean() {
+            info!("Database schema matches migrations up to {:03}", report.schema_version);
+        }
+        for drift in &report.drift {
+            warn!("Schema drift: {}", drift);
+        }
+        *self.latest.write().await = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Result of the last check
+    pub async fn latest(&self) -> Option<SchemaReport> {
+        self.latest.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_schema_follows_migrations() {
+        let all: Vec<&Migration> = MIGRATIONS.iter().collect();
+        let shape = SchemaShape::from_migrations(&all);
+        let banned = &shape.tables["banned_miners"];
+        assert!(banned.contains("address") && banned.contains("id"));
+        assert!(!banned.contains("primary") && !banned.contains("constraint"));
+        // Added by 008 and 012
+        assert!(shape.tables["block_details_cache"].contains("effort_percent"));
+        assert!(shape.tables["payout_records"].contains("source"));
+        assert!(!shape.tables["notification_configs"].contains("constraint"));
+        assert_eq!(shape.indexes["idx_worker_tags_tags"], "worker_tags");
+        assert_eq!(shape.indexes["idx_payout_records_source"], "payout_records");
+        assert!(shape.views.contains("active_miners_24h"));
+        assert!(shape.tables.contains_key("payout_kill_switch"));
+        assert!(shape.tables.contains_key("maintenance_read_only"));
+        assert_eq!(shape.indexes["idx_miners_address_trgm"], "miners");
+        // The trigger function body is not read as statements
+        assert!(!shape.tables.contains_key("update_updated_at_column"));
+    }
+
+    #[test]
+    fn test_drift_reports_missing_and_unexpected_objects() {
+        let migration = Migration {
+            version: 1,
+            name: "test",
+            sql: "CREATE TABLE IF NOT EXISTS t (id SERIAL PRIMARY KEY, a TEXT, CONSTRAINT u UNIQUE (a));
+                  CREATE INDEX IF NOT EXISTS idx_t_a ON t(a);
+                  ALTER TABLE t ADD COLUMN IF NOT EXISTS b INTEGER, DROP COLUMN IF EXISTS old;
+                  ALTER TABLE t RENAME COLUMN a TO c;",
+        };
+        let expected = SchemaShape::from_migrations(&[&migration]);
+        assert_eq!(expected.tables["t"], BTreeSet::from(["b", "c", "id"].map(str::to_string)));
+        assert!(schema_drift(&expected, &expected).is_empty());
+
+        let mut live = expected.clone();
+        live.tables.get_mut("t").unwrap().remove("b");
+        live.tables.get_mut("t").unwrap().insert("hand_added".to_string());
+        live.indexes.remove("idx_t_a");
+        live.indexes.insert("idx_manual".to_string(), "t".to_string());
+        // Other tables in the database are not ours to check
+        live.tables.insert("shares".to_string(), BTreeSet::new());
+        live.indexes.insert("idx_shares".to_string(), "shares".to_string());
+
+        let kinds: Vec<(DriftKind, String)> = schema_drift(&expected, &live).into_iter().map(|d| (d.kind, d.name)).collect();
+        assert_eq!(kinds, vec![
+            (DriftKind::MissingColumn, "b".to_string()),
+            (DriftKind::UnexpectedColumn, "hand_added".to_string()),
+            (DriftKind::MissingIndex, "idx_t_a".to_string()),
+            (DriftKind::UnexpectedIndex, "idx_manual".to_string()),
+        ]);
+
+        live.tables.remove("t");
+        assert_eq!(schema_drift(&expected, &live)[0].to_string(), "table t is missing");
+    }
+}
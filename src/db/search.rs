@@ -0,0 +1,111 @@
+// Miner and worker search
+//
+// Admin autocomplete looks up miner addresses and worker names by prefix,
+// falling back to trigram similarity for typos and fragments from the middle
+// of a name. Both are served by the pg_trgm indexes of migration 015.
+// Matches are ranked exact, then prefix, then similar, and by similarity
+// within each rank.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::DatabaseManager;
+
+/// Shortest query trigram indexes can serve
+pub const MIN_SEARCH_CHARS: usize = 3;
+
+/// What a search result matched
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMatchKind {
+    Address,
+    Worker,
+}
+
+/// How closely a search result matched
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMatchRank {
+    Exact,
+    Prefix,
+    Similar,
+}
+
+/// Miner address or worker matching a search
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub kind: SearchMatchKind,
+    pub address: String,
+    /// Set for worker matches
+    pub worker_name: Option<String>,
+    pub rank: SearchMatchRank,
+    /// Trigram similarity to the query, 0 to 1
+    pub similarity: f32,
+}
+
+/// `LIKE` pattern matching values that start with `query`
+pub fn like_prefix(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 1);
+    for c in query.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+impl DatabaseManager {
+    /// Miner addresses and worker names matching `query`, best first
+    pub async fn search_miners(&self, query: &str, limit: i64) -> Result<Vec<SearchMatch>> {
+        let conn = self.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT kind, address, worker_name, rank, score FROM (
+                     SELECT 'address' AS kind, address::TEXT AS address, NULL::TEXT AS worker_name,
+                            CASE WHEN lower(address) = lower($1) THEN 0 WHEN address ILIKE $2 THEN 1 ELSE 2 END AS rank,
+                            similarity(address, $1) AS score
+                     FROM miners
+                     WHERE address ILIKE $2 OR address % $1
+                     UNION ALL
+                     SELECT 'worker', miner_address::TEXT, worker_name::TEXT,
+                            CASE WHEN lower(worker_name) = lower($1) THEN 0 WHEN worker_name ILIKE $2 THEN 1 ELSE 2 END,
+                            similarity(worker_name, $1)
+                     FROM worker_status_cache
+                     WHERE worker_name ILIKE $2 OR worker_name % $1
+                 ) matches
+                 ORDER BY rank, score DESC, worker_name NULLS FIRST, address
+                 LIMIT $3",
+                &[&query, &like_prefix(query), &limit],
+            )
+            .await?;
+
+        Ok(rows.iter().map(|row| SearchMatch {
+            kind: match row.get::<_, &str>("kind") {
+                "worker" => SearchMatchKind::Worker,
+                _ => SearchMatchKind::Address,
+            },
+            address: row.get("address"),
+            worker_name: row.get("worker_name"),
+            rank: match row.get::<_, i32>("rank") {
+                0 => SearchMatchRank::Exact,
+                1 => SearchMatchRank::Prefix,
+                _ => SearchMatchRank::Similar,
+            },
+            similarity: row.get("score"),
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like_prefix_escapes_wildcards() {
+        assert_eq!(like_prefix("bc1q"), "bc1q%");
+        assert_eq!(like_prefix("rig_01%"), "rig\\_01\\%%");
+        assert_eq!(like_prefix("a\\b"), "a\\\\b%");
+    }
+}
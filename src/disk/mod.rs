@@ -0,0 +1,602 @@
+// Disk Module for DMPool
+// Free space monitoring with alerts and protective actions
+//
+// A full disk corrupts the share store and stops the pool, usually long after
+// the warning signs. The monitor measures free space on the store, backup and
+// log volumes, raises a warning or critical alert per path (cleared when space
+// is back) and, once any path reaches the configured level, takes the enabled
+// protective actions: pausing scheduled backups, rotating and pruning logs and
+// pruning old shares from PostgreSQL. Backups resume when space recovers.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::audit::AuditLogger;
+use crate::backup::BackupManager;
+use crate::retention::{Dataset, RetentionManager};
+
+/// Prefix of archived audit log files
+const AUDIT_ARCHIVE_PREFIX: &str = "audit_";
+
+/// How full a volume is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskLevel {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl DiskLevel {
+    fn alert_level(self) -> AlertLevel {
+        match self {
+            Self::Normal => AlertLevel::Info,
+            Self::Warning => AlertLevel::Warning,
+            Self::Critical => AlertLevel::Critical,
+        }
+    }
+}
+
+/// The `[dmpool.disk]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskConfig {
+    pub enabled: bool,
+    /// Seconds between checks
+    pub interval_secs: u64,
+    /// Free space, in percent, below which a path is at warning level
+    pub warning_free_percent: f64,
+    /// Free space, in percent, below which a path is critical
+    pub critical_free_percent: f64,
+    /// Log directory to watch; defaults to the directory of `[logging] file`
+    pub log_dir: Option<PathBuf>,
+    /// Level at which the protective actions below run
+    pub action_level: DiskLevel,
+    /// Skip scheduled backups and restore drills until space recovers
+    pub pause_backups: bool,
+    /// Rotate the audit log and delete old rotated logs
+    pub rotate_logs: bool,
+    /// Rotated log files kept per log when rotating
+    pub keep_rotated_logs: usize,
+    /// Delete PostgreSQL shares older than this many days; 0 disables
+    pub prune_shares_days: u32,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 300,
+            warning_free_percent: 15.0,
+            critical_free_percent: 5.0,
+            log_dir: None,
+            action_level: DiskLevel::Critical,
+            pause_backups: true,
+            rotate_logs: true,
+            keep_rotated_logs: 5,
+            prune_shares_days: 0,
+        }
+    }
+}
+
+impl DiskConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs < 10 {
+            return Err(anyhow::anyhow!("interval_secs must be at least 10"));
+        }
+        if !(0.0..100.0).contains(&self.critical_free_percent) || self.warning_free_percent >= 100.0 {
+            return Err(anyhow::anyhow!("free space thresholds must be percentages below 100"));
+        }
+        if self.critical_free_percent >= self.warning_free_percent {
+            return Err(anyhow::anyhow!(
+                "critical_free_percent ({}) must be below warning_free_percent ({})",
+                self.critical_free_percent, self.warning_free_percent
+            ));
+        }
+        if self.action_level == DiskLevel::Normal {
+            return Err(anyhow::anyhow!("action_level must be warning or critical"));
+        }
+        Ok(())
+    }
+
+    /// Level for a volume with `free_percent` free
+    pub fn level(&self, free_percent: f64) -> DiskLevel {
+        if free_percent < self.critical_free_percent {
+            DiskLevel::Critical
+        } else if free_percent < self.warning_free_percent {
+            DiskLevel::Warning
+        } else {
+            DiskLevel::Normal
+        }
+    }
+}
+
+/// Size and free space of a volume
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn free_percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 100.0;
+        }
+        self.free_bytes as f64 * 100.0 / self.total_bytes as f64
+    }
+}
+
+/// Usage of the volume holding `path`
+///
+/// Paths that don't exist yet are measured at their nearest existing parent.
+pub fn disk_usage(path: &Path) -> Result<DiskUsage> {
+    let existing = path.ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow::anyhow!("no existing parent directory"))?;
+    statvfs(existing)
+}
+
+/// Free space on the volume holding `path`
+pub fn free_space_bytes(path: &Path) -> Result<u64> {
+    Ok(disk_usage(path)?.free_bytes)
+}
+
+#[cfg(unix)]
+fn statvfs(path: &Path) -> Result<DiskUsage> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let block = stat.f_frsize as u64;
+    Ok(DiskUsage {
+        free_bytes: stat.f_bavail as u64 * block,
+        total_bytes: stat.f_blocks as u64 * block,
+    })
+}
+
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> Result<DiskUsage> {
+    Err(anyhow::anyhow!("disk usage is only measured on unix"))
+}
+
+/// Measures volumes; replaced in tests
+pub trait DiskProbe: Send + Sync {
+    fn usage(&self, path: &Path) -> Result<DiskUsage>;
+}
+
+/// Reads usage from the filesystem
+pub struct StatvfsProbe;
+
+impl DiskProbe for StatvfsProbe {
+    fn usage(&self, path: &Path) -> Result<DiskUsage> {
+        disk_usage(path)
+    }
+}
+
+/// What the monitor can do to free space
+#[async_trait]
+pub trait ProtectiveActions: Send + Sync {
+    /// Pause or resume scheduled backups
+    fn pause_backups(&self, paused: bool);
+    /// Rotate logs, keeping `keep` rotated files per log; returns files deleted
+    async fn rotate_logs(&self, keep: usize) -> Result<usize>;
+    /// Delete shares created before `cutoff`; returns rows deleted
+    async fn prune_shares(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+}
+
+/// Protective action taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskAction {
+    PauseBackups,
+    RotateLogs,
+    PruneShares,
+}
+
+/// Latest measurement of one path
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PathStatus {
+    pub label: String,
+    pub path: PathBuf,
+    pub usage: Option<DiskUsage>,
+    pub free_percent: Option<f64>,
+    pub level: DiskLevel,
+    pub error: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Latest state of every path and the protective actions in force
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DiskStatus {
+    pub level: DiskLevel,
+    pub paths: Vec<PathStatus>,
+    /// Actions taken when the pool last reached the action level
+    pub actions: Vec<DiskAction>,
+    pub protecting_since: Option<DateTime<Utc>>,
+    pub action_errors: Vec<String>,
+}
+
+/// Alert rule for `label` at `level`
+pub fn alert_rule_id(label: &str, level: DiskLevel) -> String {
+    let level = match level {
+        DiskLevel::Critical => "critical",
+        _ => "warning",
+    };
+    format!("disk_space_{}_{}", label, level)
+}
+
+/// Watches free space and protects the pool when it runs low
+pub struct DiskMonitor {
+    config: DiskConfig,
+    paths: Vec<(String, PathBuf)>,
+    probe: Arc<dyn DiskProbe>,
+    alerts: Option<Arc<AlertManager>>,
+    actions: Option<Arc<dyn ProtectiveActions>>,
+    status: RwLock<DiskStatus>,
+}
+
+impl DiskMonitor {
+    pub fn new(config: DiskConfig, probe: Arc<dyn DiskProbe>) -> Self {
+        Self {
+            config,
+            paths: Vec::new(),
+            probe,
+            alerts: None,
+            actions: None,
+            status: RwLock::new(DiskStatus::default()),
+        }
+    }
+
+    /// Watch `path` under a short label such as "store"; duplicates are ignored
+    pub fn with_path(mut self, label: &str, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if !self.paths.iter().any(|(l, p)| l == label || *p == path) {
+            self.paths.push((label.to_string(), path));
+        }
+        self
+    }
+
+    /// Raise and clear disk space alerts
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Take protective actions at the action level
+    pub fn with_actions(mut self, actions: Arc<dyn ProtectiveActions>) -> Self {
+        self.actions = Some(actions);
+        self
+    }
+
+    pub async fn status(&self) -> DiskStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Measure every path, update alerts and start or stop protective actions
+    pub async fn check(&self) -> DiskStatus {
+        let now = Utc::now();
+        let previous = self.status().await;
+        let mut paths = Vec::with_capacity(self.paths.len());
+        for (label, path) in &self.paths {
+            let before = previous.paths.iter()
+                .find(|p| p.label == *label)
+                .map(|p| p.level)
+                .unwrap_or_default();
+            let status = match self.probe.usage(path) {
+                Ok(usage) => {
+                    let free_percent = usage.free_percent();
+                    PathStatus {
+                        label: label.clone(),
+                        path: path.clone(),
+                        usage: Some(usage),
+                        free_percent: Some(free_percent),
+                        level: self.config.level(free_percent),
+                        error: None,
+                        checked_at: Some(now),
+                    }
+                }
+                Err(e) => {
+                    // Keep the last level so an unreadable mount doesn't clear alerts
+                    warn!("Failed to measure free space on {}: {:#}", path.display(), e);
+                    PathStatus {
+                        label: label.clone(),
+                        path: path.clone(),
+                        level: before,
+                        error: Some(format!("{:#}", e)),
+                        checked_at: Some(now),
+                        ..Default::default()
+                    }
+                }
+            };
+            if status.level != before {
+                self.alert_transition(&status, before).await;
+            }
+            paths.push(status);
+        }
+
+        let level = paths.iter().map(|p| p.level).max().unwrap_or_default();
+        let mut status = DiskStatus { level, paths, ..previous };
+        if level >= self.config.action_level && status.protecting_since.is_none() {
+            warn!("Disk space {:?}, taking protective actions", level);
+            status.protecting_since = Some(now);
+            (status.actions, status.action_errors) = self.protect(now).await;
+        } else if level < self.config.action_level && status.protecting_since.is_some() {
+            info!("Disk space recovered, lifting protective actions");
+            if let Some(actions) = &self.actions {
+                if status.actions.contains(&DiskAction::PauseBackups) {
+                    actions.pause_backups(false);
+                }
+            }
+            status.protecting_since = None;
+            status.actions.clear();
+            status.action_errors.clear();
+        }
+        *self.status.write().await = status.clone();
+        status
+    }
+
+    async fn protect(&self, now: DateTime<Utc>) -> (Vec<DiskAction>, Vec<String>) {
+        let mut taken = Vec::new();
+        let mut errors = Vec::new();
+        let Some(actions) = &self.actions else {
+            return (taken, errors);
+        };
+        if self.config.pause_backups {
+            actions.pause_backups(true);
+            taken.push(DiskAction::PauseBackups);
+        }
+        if self.config.rotate_logs {
+            match actions.rotate_logs(self.config.keep_rotated_logs).await {
+                Ok(deleted) => {
+                    info!("Rotated logs, deleted {} old log files", deleted);
+                    taken.push(DiskAction::RotateLogs);
+                }
+                Err(e) => errors.push(format!("rotate_logs: {:#}", e)),
+            }
+        }
+        if self.config.prune_shares_days > 0 {
+            let cutoff = now - ChronoDuration::days(self.config.prune_shares_days as i64);
+            match actions.prune_shares(cutoff).await {
+                Ok(rows) => {
+                    warn!("Pruned {} shares older than {} days to free disk space", rows, self.config.prune_shares_days);
+                    taken.push(DiskAction::PruneShares);
+                }
+                Err(e) => errors.push(format!("prune_shares: {:#}", e)),
+            }
+        }
+        for e in &errors {
+            error!("Disk protective action failed: {}", e);
+        }
+        (taken, errors)
+    }
+
+    async fn alert_transition(&self, status: &PathStatus, before: DiskLevel) {
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+        let context = serde_json::json!({
+            "path": status.path.display().to_string(),
+            "free_percent": status.free_percent.map(|p| (p * 10.0).round() / 10.0),
+            "free_bytes": status.usage.map(|u| u.free_bytes),
+            "warning_free_percent": self.config.warning_free_percent,
+            "critical_free_percent": self.config.critical_free_percent,
+        });
+        // Clear the alert of the level being left
+        if before != DiskLevel::Normal {
+            if let Err(e) = alerts.clear_rule(&alert_rule_id(&status.label, before), context.clone()).await {
+                warn!("Failed to clear disk space alert: {:#}", e);
+            }
+        }
+        if status.level == DiskLevel::Normal {
+            return;
+        }
+
+        let rule_id = alert_rule_id(&status.label, status.level);
+        if !alerts.get_rules().await.iter().any(|r| r.id == rule_id) {
+            let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                &rule_id,
+                &format!("Disk space {} on {}", if status.level == DiskLevel::Critical { "critical" } else { "low" }, status.label),
+                AlertCondition::Custom { message: format!("Free space on {} is running out", status.path.display()) },
+                status.level.alert_level(),
+                channels,
+            ).with_cooldown(60)).await;
+        }
+        if let Err(e) = alerts.trigger_alert(&rule_id, context).await {
+            warn!("Failed to raise disk space alert: {}", e);
+        }
+    }
+
+    /// Check on the configured interval
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.config.interval_secs));
+            loop {
+                interval.tick().await;
+                self.check().await;
+            }
+        })
+    }
+}
+
+/// Delete the oldest files in `dir` named `<prefix>...`, keeping the newest `keep`
+pub fn prune_rotated(dir: &Path, prefix: &str, keep: usize) -> Result<usize> {
+    let mut rotated: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (metadata.modified().unwrap_or(std::time::UNIX_EPOCH), entry.path()))
+        })
+        .collect();
+    rotated.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut deleted = 0;
+    for (_, path) in rotated.into_iter().skip(keep) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) => warn!("Failed to delete rotated log {}: {}", path.display(), e),
+        }
+    }
+    Ok(deleted)
+}
+
+/// Protective actions backed by the pool's managers
+#[derive(Default)]
+pub struct PoolActions {
+    pub backups: Option<Arc<BackupManager>>,
+    pub audit: Option<Arc<AuditLogger>>,
+    pub retention: Option<Arc<RetentionManager>>,
+    /// Node log file (`[logging] file`); its dated rotations are pruned
+    pub log_file: Option<PathBuf>,
+}
+
+#[async_trait]
+impl ProtectiveActions for PoolActions {
+    fn pause_backups(&self, paused: bool) {
+        if let Some(backups) = &self.backups {
+            backups.set_paused(paused);
+        }
+    }
+
+    async fn rotate_logs(&self, keep: usize) -> Result<usize> {
+        let mut deleted = 0;
+        if let Some(audit) = &self.audit {
+            if let Some(audit_file) = audit.log_file_path() {
+                if audit_file.exists() {
+                    audit.rotate_logs().await?;
+                }
+                if let Some(dir) = audit_file.parent() {
+                    deleted += prune_rotated(dir, AUDIT_ARCHIVE_PREFIX, keep)?;
+                }
+            }
+        }
+        if let Some(log_file) = &self.log_file {
+            if let (Some(dir), Some(name)) = (log_file.parent(), log_file.file_name()) {
+                // Rolling appenders write `<name>.<date>` next to the active file
+                deleted += prune_rotated(dir, &format!("{}.", name.to_string_lossy()), keep)?;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn prune_shares(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let retention = self.retention.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("share pruning needs the database"))?;
+        let rows: BTreeMap<String, u64> = retention.purge_dataset(Dataset::Shares, cutoff).await?;
+        Ok(rows.values().sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    struct FakeDisk {
+        free_percent: AtomicU64,
+    }
+
+    impl DiskProbe for FakeDisk {
+        fn usage(&self, _path: &Path) -> Result<DiskUsage> {
+            Ok(DiskUsage { free_bytes: self.free_percent.load(Ordering::SeqCst), total_bytes: 100 })
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingActions {
+        paused: AtomicBool,
+        calls: Mutex<Vec<&'static str>>,
+    }
+
+    #[async_trait]
+    impl ProtectiveActions for RecordingActions {
+        fn pause_backups(&self, paused: bool) {
+            self.paused.store(paused, Ordering::SeqCst);
+        }
+
+        async fn rotate_logs(&self, _keep: usize) -> Result<usize> {
+            self.calls.lock().unwrap().push("rotate");
+            Ok(2)
+        }
+
+        async fn prune_shares(&self, _cutoff: DateTime<Utc>) -> Result<u64> {
+            self.calls.lock().unwrap().push("prune");
+            Err(anyhow::anyhow!("database down"))
+        }
+    }
+
+    #[test]
+    fn test_levels_and_rotated_pruning() {
+        let config = DiskConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.level(50.0), DiskLevel::Normal);
+        assert_eq!(config.level(10.0), DiskLevel::Warning);
+        assert_eq!(config.level(4.9), DiskLevel::Critical);
+        assert!(DiskConfig { critical_free_percent: 20.0, ..Default::default() }.validate().is_err());
+        assert_eq!(alert_rule_id("store", DiskLevel::Critical), "disk_space_store_critical");
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["dmpool.log", "dmpool.log.2026-10-01", "dmpool.log.2026-10-02", "dmpool.log.2026-10-03", "other.txt"] {
+            std::fs::write(dir.path().join(name), "x").unwrap();
+        }
+        assert_eq!(prune_rotated(dir.path(), "dmpool.log.", 1).unwrap(), 2);
+        assert!(dir.path().join("dmpool.log").exists());
+        assert!(dir.path().join("other.txt").exists());
+        assert!(disk_usage(&dir.path().join("missing/dir")).unwrap().total_bytes > 0);
+    }
+
+    #[tokio::test]
+    async fn test_protective_actions_and_recovery() {
+        let disk = Arc::new(FakeDisk { free_percent: AtomicU64::new(50) });
+        let actions = Arc::new(RecordingActions::default());
+        let alerts = Arc::new(AlertManager::default());
+        let monitor = DiskMonitor::new(DiskConfig { prune_shares_days: 3, ..Default::default() }, disk.clone())
+            .with_path("store", "/data/store")
+            .with_path("logs", "/data/store")
+            .with_alerts(alerts.clone())
+            .with_actions(actions.clone());
+
+        assert_eq!(monitor.check().await.level, DiskLevel::Normal);
+        // Both labels point at one path, so only one is watched
+        assert_eq!(monitor.status().await.paths.len(), 1);
+
+        disk.free_percent.store(10, Ordering::SeqCst);
+        let status = monitor.check().await;
+        assert_eq!(status.level, DiskLevel::Warning);
+        assert!(status.actions.is_empty());
+        assert!(alerts.get_rules().await.iter().any(|r| r.id == "disk_space_store_warning"));
+
+        disk.free_percent.store(2, Ordering::SeqCst);
+        let status = monitor.check().await;
+        assert_eq!(status.level, DiskLevel::Critical);
+        assert_eq!(status.actions, vec![DiskAction::PauseBackups, DiskAction::RotateLogs]);
+        assert_eq!(status.action_errors, vec!["prune_shares: database down".to_string()]);
+        assert!(actions.paused.load(Ordering::SeqCst));
+
+        // Still critical: actions are not repeated
+        monitor.check().await;
+        assert_eq!(*actions.calls.lock().unwrap(), vec!["rotate", "prune"]);
+
+        disk.free_percent.store(40, Ordering::SeqCst);
+        let status = monitor.check().await;
+        assert_eq!(status.level, DiskLevel::Normal);
+        assert!(status.protecting_since.is_none());
+        assert!(!actions.paused.load(Ordering::SeqCst));
+        assert_eq!(alerts.get_stats().await.resolved_alerts, 2);
+    }
+}
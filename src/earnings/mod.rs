@@ -0,0 +1,154 @@
+// Earnings Estimator Module for DMPool
+// Projects expected miner earnings from hashrate and current network conditions
+//
+// A hashrate of H finds H * t / (D * 2^32) blocks in t seconds at network
+// difficulty D; under PPLNS a miner earns that many block rewards less the
+// pool fee over time. Projections are also scaled by recent pool luck, since
+// a pool running lucky or unlucky pays out accordingly over short periods.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+/// Hashes per unit of share difficulty
+pub const HASHES_PER_DIFFICULTY: f64 = 4_294_967_296.0;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Expected earnings of `hashrate` (H/s) over `secs` seconds, after the pool fee
+pub fn expected_satoshis(
+    hashrate: f64,
+    secs: u64,
+    network_difficulty: f64,
+    block_reward_satoshis: u64,
+    pool_fee_bps: u32,
+) -> f64 {
+    if hashrate <= 0.0 || network_difficulty <= 0.0 {
+        return 0.0;
+    }
+    let blocks = hashrate * secs as f64 / (network_difficulty * HASHES_PER_DIFFICULTY);
+    let net_reward = block_reward_satoshis as f64 * 10_000u32.saturating_sub(pool_fee_bps) as f64 / 10_000.0;
+    blocks * net_reward
+}
+
+/// Network difficulty and block reward used for projections
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkConditions {
+    pub difficulty: f64,
+    /// Reward of the latest block (subsidy + fees)
+    pub block_reward_satoshis: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Projected earnings for a hashrate
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EarningsProjection {
+    /// Hashrate in H/s
+    #[serde(serialize_with = "crate::observer_api::units::hashrate")]
+    pub hashrate: f64,
+    pub network_difficulty: f64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub block_reward_satoshis: u64,
+    pub pool_fee_percent: f64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub daily_satoshis: u64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub weekly_satoshis: u64,
+    /// Pool luck the adjusted figures are scaled by
+    pub luck_percent: Option<f64>,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub luck_adjusted_daily_satoshis: Option<u64>,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub luck_adjusted_weekly_satoshis: Option<u64>,
+    pub network_updated_at: DateTime<Utc>,
+}
+
+/// Earnings estimator fed with the latest network conditions
+pub struct EarningsEstimator {
+    pool_fee_bps: u32,
+    network: RwLock<Option<NetworkConditions>>,
+}
+
+impl EarningsEstimator {
+    pub fn new(pool_fee_bps: u32) -> Self {
+        Self {
+            pool_fee_bps,
+            network: RwLock::new(None),
+        }
+    }
+
+    /// Update the network difficulty and block reward
+    pub async fn set_network(&self, difficulty: f64, block_reward_satoshis: u64) {
+        *self.network.write().await = Some(NetworkConditions {
+            difficulty,
+            block_reward_satoshis,
+            updated_at: Utc::now(),
+        });
+    }
+
+    /// Current network conditions, if known yet
+    pub async fn network(&self) -> Option<NetworkConditions> {
+        self.network.read().await.clone()
+    }
+
+    /// Expected earnings of `hashrate` (H/s) over `secs` seconds
+    ///
+    /// None until network conditions are known.
+    pub async fn expected_satoshis(&self, hashrate: f64, secs: u64) -> Option<u64> {
+        let network = self.network().await?;
+        Some(expected_satoshis(hashrate, secs, network.difficulty, network.block_reward_satoshis, self.pool_fee_bps).round() as u64)
+    }
+
+    /// Daily and weekly projection for `hashrate` (H/s), optionally luck-adjusted
+    ///
+    /// None until network conditions are known.
+    pub async fn project(&self, hashrate: f64, luck_percent: Option<f64>) -> Option<EarningsProjection> {
+        let network = self.network().await?;
+        let daily = expected_satoshis(hashrate, SECS_PER_DAY, network.difficulty, network.block_reward_satoshis, self.pool_fee_bps);
+        let luck_factor = luck_percent.map(|luck| luck / 100.0);
+
+        Some(EarningsProjection {
+            hashrate,
+            network_difficulty: network.difficulty,
+            block_reward_satoshis: network.block_reward_satoshis,
+            pool_fee_percent: self.pool_fee_bps as f64 / 100.0,
+            daily_satoshis: daily.round() as u64,
+            weekly_satoshis: (daily * 7.0).round() as u64,
+            luck_percent,
+            luck_adjusted_daily_satoshis: luck_factor.map(|f| (daily * f).round() as u64),
+            luck_adjusted_weekly_satoshis: luck_factor.map(|f| (daily * 7.0 * f).round() as u64),
+            network_updated_at: network.updated_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_satoshis() {
+        // Hashrate finding exactly one block a day at difficulty 1
+        let hashrate = HASHES_PER_DIFFICULTY / SECS_PER_DAY as f64;
+        assert_eq!(expected_satoshis(hashrate, SECS_PER_DAY, 1.0, 312_500_000, 0).round(), 312_500_000.0);
+        // 1% fee and two days
+        assert_eq!(expected_satoshis(hashrate, 2 * SECS_PER_DAY, 1.0, 100_000_000, 100).round(), 198_000_000.0);
+        assert_eq!(expected_satoshis(0.0, SECS_PER_DAY, 1.0, 100_000_000, 0), 0.0);
+        assert_eq!(expected_satoshis(hashrate, SECS_PER_DAY, 0.0, 100_000_000, 0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_projection_needs_network_and_scales_by_luck() {
+        let estimator = EarningsEstimator::new(200);
+        let hashrate = 2.0 * HASHES_PER_DIFFICULTY / SECS_PER_DAY as f64;
+        assert!(estimator.project(hashrate, None).await.is_none());
+
+        estimator.set_network(2.0, 100_000_000).await;
+        let projection = estimator.project(hashrate, Some(50.0)).await.unwrap();
+        assert_eq!(projection.daily_satoshis, 98_000_000);
+        assert_eq!(projection.weekly_satoshis, 686_000_000);
+        assert_eq!(projection.luck_adjusted_daily_satoshis, Some(49_000_000));
+        assert_eq!(projection.pool_fee_percent, 2.0);
+        assert!(estimator.project(hashrate, None).await.unwrap().luck_adjusted_weekly_satoshis.is_none());
+    }
+}
@@ -0,0 +1,259 @@
+// Error Module for DMPool
+// Typed error hierarchy shared across the library and its mapping to API error responses
+//
+// Library functions keep returning anyhow::Result; the typed errors below are
+// raised at the source and survive any `.context()` added on the way up, so
+// callers and the API layers can branch on them with `kind_of`.
+
+use axum::http::StatusCode;
+use thiserror::Error;
+
+/// Broad error category used to pick API status codes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    InvalidInput,
+    /// Request conflicts with existing state (e.g. a reused idempotency key)
+    Conflict,
+    Unauthorized,
+    /// A backing service (database, node) is unreachable
+    Unavailable,
+    /// The pool is in read-only maintenance mode
+    ReadOnly,
+    Internal,
+}
+
+impl ErrorKind {
+    /// HTTP status for this kind of error
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::InvalidInput => StatusCode::BAD_REQUEST,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Unavailable | Self::ReadOnly => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Payment and payout errors
+#[derive(Debug, Error)]
+pub enum PaymentError {
+    #[error("No balance found for address {0}")]
+    NoBalance(String),
+    #[error("Insufficient balance: requested {requested}, available {available}")]
+    InsufficientBalance { requested: u64, available: u64 },
+    #[error("Payout {0} not found")]
+    PayoutNotFound(String),
+    #[error("Payout {0} is not pending")]
+    NotPending(String),
+    #[error("Idempotency key {0} was already used for a different payout")]
+    IdempotencyConflict(String),
+    #[error("No unspent outputs available")]
+    NoUnspentOutputs,
+    #[error("Amount too small after fees")]
+    AmountTooSmall,
+    #[error("Transaction signing incomplete")]
+    SigningIncomplete,
+    #[error("Payout {payout_id} needs a second admin's approval (request {approval_id})")]
+    ApprovalRequired { payout_id: String, approval_id: String },
+    #[error("Approval request {0} not found")]
+    ApprovalNotFound(String),
+    #[error("Approval request {0} is not pending")]
+    ApprovalNotPending(String),
+    #[error("Approval request {0} must be approved by a different admin")]
+    SelfApproval(String),
+    #[error("File {0} was already imported")]
+    AlreadyImported(String),
+    #[error("Cannot pay out to this address: {0}")]
+    InvalidAddress(String),
+    #[error("{0}")]
+    PayoutsPaused(String),
+}
+
+impl PaymentError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NoBalance(_) | Self::PayoutNotFound(_) | Self::ApprovalNotFound(_) => ErrorKind::NotFound,
+            Self::InsufficientBalance { .. } | Self::AmountTooSmall | Self::InvalidAddress(_) => ErrorKind::InvalidInput,
+            Self::NotPending(_) | Self::IdempotencyConflict(_) | Self::ApprovalRequired { .. } | Self::ApprovalNotPending(_)
+            | Self::AlreadyImported(_) => ErrorKind::Conflict,
+            Self::SelfApproval(_) => ErrorKind::Unauthorized,
+            Self::PayoutsPaused(_) => ErrorKind::Unavailable,
+            Self::NoUnspentOutputs | Self::SigningIncomplete => ErrorKind::Internal,
+        }
+    }
+}
+
+/// Authentication errors
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Password validation failed: {0}")]
+    WeakPassword(String),
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+    #[error("Failed to hash password: {0}")]
+    PasswordHash(String),
+    #[error("Failed to encode token: {0}")]
+    TokenEncoding(String),
+    #[error("User {0} not found")]
+    UserNotFound(String),
+    #[error("User {0} already exists")]
+    UserExists(String),
+    #[error("Unknown role: {0}")]
+    InvalidRole(String),
+    #[error("At least one enabled admin is required")]
+    LastAdmin,
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+}
+
+impl AuthError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::WeakPassword(_) | Self::InvalidRole(_) => ErrorKind::InvalidInput,
+            Self::InvalidToken(_) | Self::AccessDenied(_) => ErrorKind::Unauthorized,
+            Self::UserNotFound(_) => ErrorKind::NotFound,
+            Self::UserExists(_) | Self::LastAdmin => ErrorKind::Conflict,
+            Self::PasswordHash(_) | Self::TokenEncoding(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+/// Backup and restore errors
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Backup file not found: {0}")]
+    NotFound(String),
+    #[error("Backup checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("{0}")]
+    CommandFailed(String),
+}
+
+impl BackupError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NotFound(_) => ErrorKind::NotFound,
+            Self::InvalidPath(_) => ErrorKind::InvalidInput,
+            Self::ChecksumMismatch { .. } => ErrorKind::Conflict,
+            Self::CommandFailed(_) => ErrorKind::Internal,
+        }
+    }
+}
+
+/// Database errors
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Failed to get database connection: {0}")]
+    Pool(#[from] deadpool_postgres::PoolError),
+    #[error("Database query failed: {0}")]
+    Query(#[from] tokio_postgres::Error),
+    #[error("{0} not found")]
+    NotFound(String),
+}
+
+impl DbError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Pool(_) => ErrorKind::Unavailable,
+            Self::Query(_) => ErrorKind::Internal,
+            Self::NotFound(_) => ErrorKind::NotFound,
+        }
+    }
+}
+
+/// Crate-wide error
+#[derive(Debug, Error)]
+pub enum DmpoolError {
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// Mutation attempted in read-only maintenance mode
+    #[error("{0}")]
+    ReadOnly(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl DmpoolError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Payment(e) => e.kind(),
+            Self::Auth(e) => e.kind(),
+            Self::Backup(e) => e.kind(),
+            Self::Db(e) => e.kind(),
+            Self::InvalidInput(_) => ErrorKind::InvalidInput,
+            Self::NotFound(_) => ErrorKind::NotFound,
+            Self::ReadOnly(_) => ErrorKind::ReadOnly,
+            Self::Other(e) => kind_of(e),
+        }
+    }
+}
+
+/// Classify an error by the first typed error in its chain
+///
+/// Context layers are skipped, so `Err(PaymentError::..).context("..")`
+/// still classifies as a payment error.
+pub fn kind_of(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<DmpoolError>() {
+            return e.kind();
+        }
+        if let Some(e) = cause.downcast_ref::<PaymentError>() {
+            return e.kind();
+        }
+        if let Some(e) = cause.downcast_ref::<AuthError>() {
+            return e.kind();
+        }
+        if let Some(e) = cause.downcast_ref::<BackupError>() {
+            return e.kind();
+        }
+        if let Some(e) = cause.downcast_ref::<DbError>() {
+            return e.kind();
+        }
+        if cause.downcast_ref::<deadpool_postgres::PoolError>().is_some() {
+            return ErrorKind::Unavailable;
+        }
+    }
+    ErrorKind::Internal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_kind_survives_context() {
+        let err = Err::<(), _>(PaymentError::InsufficientBalance { requested: 10, available: 5 })
+            .context("Failed to create payout")
+            .unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::InvalidInput);
+        assert_eq!(format!("{:#}", err), "Failed to create payout: Insufficient balance: requested 10, available 5");
+
+        let err = anyhow::Error::from(BackupError::NotFound("/backups/x".to_string())).context("Restore failed");
+        assert_eq!(kind_of(&err), ErrorKind::NotFound);
+
+        assert_eq!(kind_of(&anyhow::anyhow!("plain")), ErrorKind::Internal);
+    }
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(DmpoolError::from(PaymentError::IdempotencyConflict("k".to_string())).kind().status_code(), StatusCode::CONFLICT);
+        assert_eq!(DmpoolError::from(AuthError::InvalidToken("expired".to_string())).kind().status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(DmpoolError::Other(anyhow::Error::from(DbError::NotFound("Miner".to_string()))).kind(), ErrorKind::NotFound);
+    }
+}
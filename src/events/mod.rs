@@ -0,0 +1,307 @@
+// Event Bus Module for DMPool
+// Typed broadcast bus: subsystems publish pool events and integrations subscribe without touching core logic
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::audit::AuditLogger;
+use crate::block_events::BlockAnnouncement;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Default capacity of the event channel
+///
+/// Share events arrive at stratum rate, so slow subscribers lag rather than block publishers.
+const DEFAULT_CHANNEL_CAPACITY: usize = 4096;
+
+/// Event published on the bus
+///
+/// Serializes as `{"type": "...", "data": {...}}`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum PoolEvent {
+    /// A share was accepted from a miner
+    ShareAccepted {
+        address: String,
+        worker: Option<String>,
+        difficulty: u64,
+        n_time: u64,
+    },
+    /// The pool found a block and split the reward
+    BlockFound(BlockAnnouncement),
+    /// A payout transaction was broadcast
+    PayoutBroadcast {
+        payout_id: String,
+        address: String,
+        amount_satoshis: u64,
+        txid: Option<String>,
+    },
+    /// A payout reached the required confirmations
+    PayoutConfirmed {
+        payout_id: String,
+        address: String,
+        amount_satoshis: u64,
+        txid: Option<String>,
+        confirmations: u32,
+    },
+    /// A configuration change was applied
+    ConfigApplied {
+        parameter: String,
+        old_value: serde_json::Value,
+        new_value: serde_json::Value,
+        applied_by: String,
+        applied_at: DateTime<Utc>,
+    },
+}
+
+impl PoolEvent {
+    /// Event type name, as used in the serialized `type` field
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ShareAccepted { .. } => "share_accepted",
+            Self::BlockFound(_) => "block_found",
+            Self::PayoutBroadcast { .. } => "payout_broadcast",
+            Self::PayoutConfirmed { .. } => "payout_confirmed",
+            Self::ConfigApplied { .. } => "config_applied",
+        }
+    }
+}
+
+/// Subscriber attached to the bus
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &str;
+
+    /// Whether the handler wants events of this kind
+    fn accepts(&self, _kind: &str) -> bool {
+        true
+    }
+
+    /// Handle one event; errors are logged and do not stop the subscription
+    async fn handle(&self, event: &PoolEvent) -> Result<()>;
+}
+
+/// In-process event bus
+///
+/// Cloning is cheap and every clone publishes to the same subscribers.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<PoolEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHANNEL_CAPACITY)
+    }
+}
+
+impl EventBus {
+    /// Create a bus buffering up to `capacity` events per subscriber
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish an event, returning the number of subscribers that received it
+    pub fn publish(&self, event: PoolEvent) -> usize {
+        // No subscribers is fine
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Whether anyone is listening (lets hot paths skip building events)
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Subscribe to all events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<PoolEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Run a handler for every accepted event until the bus is dropped
+    pub fn attach(&self, handler: Arc<dyn EventHandler>) -> JoinHandle<()> {
+        let mut events = self.subscribe();
+        info!("Event handler {} attached", handler.name());
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if !handler.accepts(event.kind()) {
+                            continue;
+                        }
+                        if let Err(e) = handler.handle(&event).await {
+                            warn!("Event handler {} failed on {}: {}", handler.name(), event.kind(), e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event handler {} lagged, skipped {} events", handler.name(), skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+/// Webhooks fed from the event bus (`[dmpool.event_webhooks]`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventWebhookConfig {
+    /// URLs sent every event of the listed kinds
+    pub urls: Vec<String>,
+    /// Event kinds sent to `urls`; empty sends all
+    pub kinds: Vec<String>,
+    /// URLs sent every `block_found` event of the shared pool
+    pub block_urls: Vec<String>,
+}
+
+/// Forwards events to webhook URLs
+pub struct WebhookForwarder {
+    urls: Vec<String>,
+    /// Event kinds to forward; empty forwards everything
+    kinds: Vec<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookForwarder {
+    /// Forward the given kinds (all if empty) to each URL
+    pub fn new(urls: Vec<String>, kinds: Vec<String>) -> Self {
+        Self {
+            urls,
+            kinds,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for WebhookForwarder {
+    fn name(&self) -> &str {
+        "webhooks"
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind)
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        let mut failed = Vec::new();
+        for url in &self.urls {
+            match self.client.post(url).json(event).send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => failed.push(format!("{}: status {}", url, response.status())),
+                Err(e) => failed.push(format!("{}: {}", url, e)),
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!("webhook delivery failed: {}", failed.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for AuditLogger {
+    fn name(&self) -> &str {
+        "audit"
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        matches!(kind, "payout_broadcast" | "config_applied")
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        let (username, resource) = match event {
+            PoolEvent::PayoutBroadcast { payout_id, .. } => ("system".to_string(), format!("payout:{}", payout_id)),
+            PoolEvent::ConfigApplied { parameter, applied_by, .. } => (applied_by.clone(), format!("config:{}", parameter)),
+            _ => return Ok(()),
+        };
+
+        self.entry(username, event.kind().to_string(), resource, "internal".to_string())
+            .details(serde_json::to_value(event)?)
+            .log()
+            .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    struct Collector {
+        kinds: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl EventHandler for Collector {
+        fn name(&self) -> &str {
+            "collector"
+        }
+
+        fn accepts(&self, kind: &str) -> bool {
+            kind != "share_accepted"
+        }
+
+        async fn handle(&self, event: &PoolEvent) -> Result<()> {
+            self.kinds.lock().await.push(event.kind().to_string());
+            Ok(())
+        }
+    }
+
+    fn payout_event(id: &str) -> PoolEvent {
+        PoolEvent::PayoutBroadcast {
+            payout_id: id.to_string(),
+            address: "bc1qa".to_string(),
+            amount_satoshis: 50_000,
+            txid: Some("ab".repeat(32)),
+        }
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let value = serde_json::to_value(payout_event("p1")).unwrap();
+        assert_eq!(value["type"], "payout_broadcast");
+        assert_eq!(value["data"]["payout_id"], "p1");
+
+        let parsed: PoolEvent = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.kind(), "payout_broadcast");
+
+        // Publishing without subscribers is not an error
+        assert_eq!(EventBus::default().publish(payout_event("p2")), 0);
+    }
+
+    #[tokio::test]
+    async fn test_attached_handler_filters_events() {
+        let bus = EventBus::new(16);
+        let collector = Arc::new(Collector { kinds: Mutex::new(Vec::new()) });
+        bus.attach(collector.clone());
+        assert!(bus.has_subscribers());
+
+        bus.publish(PoolEvent::ShareAccepted {
+            address: "bc1qa".to_string(),
+            worker: None,
+            difficulty: 1000,
+            n_time: 1_700_000_000,
+        });
+        bus.publish(payout_event("p1"));
+
+        for _ in 0..50 {
+            if !collector.kinds.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(*collector.kinds.lock().await, vec!["payout_broadcast"]);
+    }
+}
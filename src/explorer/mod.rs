@@ -0,0 +1,177 @@
+// Block Explorer Module for DMPool
+// Builds transaction, address and block links from per-network URL templates
+//
+// Networks without custom templates use the configured provider's public
+// explorer; regtest (and networks the provider does not serve) get no links.
+
+use anyhow::Result;
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Public explorer used for networks without custom templates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplorerProvider {
+    #[default]
+    Mempool,
+    Blockstream,
+    /// No links unless templates are configured
+    None,
+}
+
+/// URL templates; `{txid}`, `{address}` and `{height}` are replaced
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExplorerTemplates {
+    pub tx: String,
+    pub address: String,
+    pub block: String,
+}
+
+impl ExplorerTemplates {
+    /// Templates of a provider for a network (`bitcoin`, `testnet`, `testnet4`, `signet`, `regtest`)
+    pub fn preset(provider: ExplorerProvider, network: &str) -> Option<Self> {
+        let (base, block) = match provider {
+            ExplorerProvider::Mempool => {
+                let base = match network {
+                    "bitcoin" => "https://mempool.space",
+                    "testnet" => "https://mempool.space/testnet",
+                    "testnet4" => "https://mempool.space/testnet4",
+                    "signet" => "https://mempool.space/signet",
+                    _ => return None,
+                };
+                (base, "block/{height}")
+            }
+            ExplorerProvider::Blockstream => {
+                let base = match network {
+                    "bitcoin" => "https://blockstream.info",
+                    "testnet" => "https://blockstream.info/testnet",
+                    _ => return None,
+                };
+                (base, "block-height/{height}")
+            }
+            ExplorerProvider::None => return None,
+        };
+
+        Some(Self {
+            tx: format!("{}/tx/{{txid}}", base),
+            address: format!("{}/address/{{address}}", base),
+            block: format!("{}/{}", base, block),
+        })
+    }
+
+    /// Each template must be an http(s) URL containing its placeholder
+    pub fn validate(&self) -> Result<()> {
+        for (name, template, placeholder) in [
+            ("tx", &self.tx, "{txid}"),
+            ("address", &self.address, "{address}"),
+            ("block", &self.block, "{height}"),
+        ] {
+            if !(template.starts_with("https://") || template.starts_with("http://")) {
+                return Err(anyhow::anyhow!("Explorer {} template must be an http(s) URL: {}", name, template));
+            }
+            if !template.contains(placeholder) {
+                return Err(anyhow::anyhow!("Explorer {} template must contain {}: {}", name, placeholder, template));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `[dmpool.explorer]` settings
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplorerConfig {
+    pub provider: ExplorerProvider,
+    /// Custom templates by network name, taking precedence over the provider
+    pub networks: HashMap<String, ExplorerTemplates>,
+}
+
+impl ExplorerConfig {
+    /// Check every custom template
+    pub fn validate(&self) -> Result<()> {
+        for (network, templates) in &self.networks {
+            if network.parse::<Network>().is_err() {
+                return Err(anyhow::anyhow!("Unknown explorer network: {}", network));
+            }
+            templates.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Explorer links for the pool's network
+#[derive(Clone, Debug, Default)]
+pub struct ExplorerLinks {
+    templates: Option<ExplorerTemplates>,
+}
+
+impl ExplorerLinks {
+    /// Links for `network` from custom templates or the provider preset
+    pub fn new(config: &ExplorerConfig, network: Network) -> Result<Self> {
+        config.validate()?;
+        let name = network.to_string();
+        let templates = config.networks.get(&name).cloned()
+            .or_else(|| ExplorerTemplates::preset(config.provider, &name));
+        Ok(Self { templates })
+    }
+
+    /// Active templates, if links are enabled
+    pub fn templates(&self) -> Option<&ExplorerTemplates> {
+        self.templates.as_ref()
+    }
+
+    pub fn tx_url(&self, txid: &str) -> Option<String> {
+        self.templates.as_ref().map(|t| t.tx.replace("{txid}", txid))
+    }
+
+    pub fn address_url(&self, address: &str) -> Option<String> {
+        self.templates.as_ref().map(|t| t.address.replace("{address}", address))
+    }
+
+    pub fn block_url(&self, height: u64) -> Option<String> {
+        self.templates.as_ref().map(|t| t.block.replace("{height}", &height.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_per_network() {
+        let mempool = ExplorerLinks::new(&ExplorerConfig::default(), Network::Signet).unwrap();
+        assert_eq!(mempool.tx_url("ab").as_deref(), Some("https://mempool.space/signet/tx/ab"));
+        assert_eq!(mempool.block_url(100).as_deref(), Some("https://mempool.space/signet/block/100"));
+
+        let config = ExplorerConfig { provider: ExplorerProvider::Blockstream, ..Default::default() };
+        let blockstream = ExplorerLinks::new(&config, Network::Bitcoin).unwrap();
+        assert_eq!(blockstream.address_url("bc1qa").as_deref(), Some("https://blockstream.info/address/bc1qa"));
+        assert_eq!(blockstream.block_url(840_000).as_deref(), Some("https://blockstream.info/block-height/840000"));
+
+        // Blockstream has no signet explorer and nobody serves regtest
+        assert!(ExplorerLinks::new(&config, Network::Signet).unwrap().tx_url("ab").is_none());
+        assert!(ExplorerLinks::new(&ExplorerConfig::default(), Network::Regtest).unwrap().templates().is_none());
+    }
+
+    #[test]
+    fn test_custom_templates() {
+        let templates = ExplorerTemplates {
+            tx: "http://explorer.lan/tx/{txid}".to_string(),
+            address: "http://explorer.lan/address/{address}".to_string(),
+            block: "http://explorer.lan/height/{height}".to_string(),
+        };
+        let mut config = ExplorerConfig {
+            provider: ExplorerProvider::None,
+            networks: HashMap::from([("regtest".to_string(), templates.clone())]),
+        };
+        let links = ExplorerLinks::new(&config, Network::Regtest).unwrap();
+        assert_eq!(links.block_url(7).as_deref(), Some("http://explorer.lan/height/7"));
+        assert!(ExplorerLinks::new(&config, Network::Bitcoin).unwrap().tx_url("ab").is_none());
+
+        config.networks.insert("regtest".to_string(), ExplorerTemplates { tx: "http://explorer.lan/tx".to_string(), ..templates.clone() });
+        assert!(ExplorerLinks::new(&config, Network::Regtest).is_err());
+        config.networks = HashMap::from([("moonnet".to_string(), templates)]);
+        assert!(config.validate().is_err());
+    }
+}
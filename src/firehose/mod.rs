@@ -0,0 +1,433 @@
+// Share Firehose Module for DMPool
+// Streams accepted shares to Kafka or NATS for operators' own analytics
+//
+// Shares are taken off the event bus into a bounded buffer and published in
+// batches. The stratum pipeline never waits on the broker: when the buffer is
+// full (broker down or too slow) new shares are dropped and counted, and a
+// batch that still fails after its retries is dropped as well.
+//
+// Each share is one JSON message. Kafka messages are keyed by address so a
+// miner's shares stay ordered within a partition. The Kafka and NATS clients
+// are behind the `kafka` and `nats` cargo features.
+
+use crate::events::{EventHandler, PoolEvent};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Broker the firehose publishes to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirehoseBackend {
+    #[default]
+    Kafka,
+    Nats,
+}
+
+/// Batching and buffering of an exporter
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchSettings {
+    /// Shares per published batch
+    pub batch_size: usize,
+    /// Longest a partial batch waits before it is published
+    pub flush_interval_ms: u64,
+    /// Shares buffered while the destination is slow; more are dropped
+    pub buffer_size: usize,
+    /// Attempts after the first before a batch is dropped
+    pub max_retries: u32,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            flush_interval_ms: 1000,
+            buffer_size: 10_000,
+            max_retries: 3,
+        }
+    }
+}
+
+impl BatchSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.batch_size == 0 || self.buffer_size < self.batch_size {
+            return Err(anyhow::anyhow!(
+                "batch_size must be at least 1 and no larger than buffer_size ({})",
+                self.buffer_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The `[dmpool.firehose]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirehoseConfig {
+    pub enabled: bool,
+    pub backend: FirehoseBackend,
+    /// Kafka bootstrap servers (`host:port,...`) or NATS server URL
+    pub servers: String,
+    /// Kafka topic or NATS subject
+    pub topic: String,
+    #[serde(flatten)]
+    pub batching: BatchSettings,
+}
+
+impl Default for FirehoseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: FirehoseBackend::Kafka,
+            servers: "localhost:9092".to_string(),
+            topic: "dmpool.shares".to_string(),
+            batching: BatchSettings::default(),
+        }
+    }
+}
+
+impl FirehoseConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.servers.trim().is_empty() {
+            return Err(anyhow::anyhow!("Firehose servers are required"));
+        }
+        if self.topic.trim().is_empty() {
+            return Err(anyhow::anyhow!("Firehose topic is required"));
+        }
+        self.batching.validate()
+    }
+}
+
+/// One accepted share as published
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareRecord {
+    pub address: String,
+    pub worker: Option<String>,
+    pub difficulty: u64,
+    /// Share time (unix seconds)
+    pub timestamp: u64,
+}
+
+/// Destination of share batches
+#[async_trait]
+pub trait FirehoseSink: Send + Sync {
+    /// Publish a batch; an error retries the whole batch
+    async fn publish(&self, batch: &[ShareRecord]) -> Result<()>;
+}
+
+/// Exporter counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirehoseStats {
+    pub published: u64,
+    /// Dropped because the buffer was full
+    pub dropped: u64,
+    /// Dropped with a batch that failed every retry
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    published: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Buffers accepted shares and publishes them in batches
+pub struct FirehoseExporter {
+    name: String,
+    sender: mpsc::Sender<ShareRecord>,
+    counters: Arc<Counters>,
+}
+
+impl FirehoseExporter {
+    /// Start publishing to `sink`; the task ends once the exporter is dropped
+    pub fn start(name: &str, sink: Arc<dyn FirehoseSink>, batching: &BatchSettings) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(batching.buffer_size.max(1));
+        let counters = Arc::new(Counters::default());
+        let task = tokio::spawn(run(
+            name.to_string(),
+            receiver,
+            sink,
+            batching.batch_size.max(1),
+            Duration::from_millis(batching.flush_interval_ms),
+            batching.max_retries,
+            counters.clone(),
+        ));
+        (Self { name: name.to_string(), sender, counters }, task)
+    }
+
+    /// Queue a share without waiting; false if it was dropped
+    pub fn offer(&self, record: ShareRecord) -> bool {
+        if self.sender.try_send(record).is_ok() {
+            return true;
+        }
+        let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        if dropped == 1 || dropped % 10_000 == 0 {
+            warn!("{} buffer full, {} shares dropped so far", self.name, dropped);
+        }
+        false
+    }
+
+    pub fn stats(&self) -> FirehoseStats {
+        FirehoseStats {
+            published: self.counters.published.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for FirehoseExporter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        kind == "share_accepted"
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        if let PoolEvent::ShareAccepted { address, worker, difficulty, n_time } = event {
+            self.offer(ShareRecord {
+                address: address.clone(),
+                worker: worker.clone(),
+                difficulty: *difficulty,
+                timestamp: *n_time,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Collect batches until the batch is full or the flush interval passes, then publish
+async fn run(
+    name: String,
+    mut receiver: mpsc::Receiver<ShareRecord>,
+    sink: Arc<dyn FirehoseSink>,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_retries: u32,
+    counters: Arc<Counters>,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    while receiver.recv_many(&mut batch, batch_size).await > 0 {
+        let deadline = tokio::time::Instant::now() + flush_interval;
+        while batch.len() < batch_size {
+            let remaining = batch_size - batch.len();
+            match tokio::time::timeout_at(deadline, receiver.recv_many(&mut batch, remaining)).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+
+        let mut attempt = 0;
+        loop {
+            match sink.publish(&batch).await {
+                Ok(()) => {
+                    counters.published.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    break;
+                }
+                Err(e) if attempt < max_retries => {
+                    attempt += 1;
+                    warn!("{} publish failed (attempt {}): {}", name, attempt, e);
+                    tokio::time::sleep(Duration::from_millis(100 << attempt.min(6))).await;
+                }
+                Err(e) => {
+                    counters.failed.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    warn!("{} dropped a batch of {} shares: {}", name, batch.len(), e);
+                    break;
+                }
+            }
+        }
+        batch.clear();
+    }
+    info!("{} stopped", name);
+}
+
+/// Connect the sink for the configured backend
+pub async fn connect(config: &FirehoseConfig) -> Result<Arc<dyn FirehoseSink>> {
+    match config.backend {
+        FirehoseBackend::Kafka => kafka::connect(config),
+        FirehoseBackend::Nats => nats::connect(config).await,
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::{FirehoseConfig, FirehoseSink, ShareRecord};
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use std::sync::Arc;
+
+    /// Publishes shares to a Kafka topic
+    pub struct KafkaSink {
+        producer: FutureProducer,
+        topic: String,
+    }
+
+    pub fn connect(config: &FirehoseConfig) -> Result<Arc<dyn FirehoseSink>> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &config.servers)
+            .set("message.timeout.ms", "10000")
+            .set("linger.ms", "50")
+            .set("compression.type", "lz4")
+            .create()
+            .context("Failed to create Kafka producer")?;
+        Ok(Arc::new(KafkaSink { producer, topic: config.topic.clone() }))
+    }
+
+    #[async_trait]
+    impl FirehoseSink for KafkaSink {
+        async fn publish(&self, batch: &[ShareRecord]) -> Result<()> {
+            // Queue the whole batch first so the producer can pack it, then wait for delivery
+            let mut deliveries = Vec::with_capacity(batch.len());
+            for record in batch {
+                let payload = serde_json::to_vec(record)?;
+                let message = FutureRecord::to(&self.topic).key(record.address.as_str()).payload(&payload);
+                let delivery = self.producer.send_result(message)
+                    .map_err(|(e, _)| anyhow::anyhow!("Failed to queue Kafka message: {}", e))?;
+                deliveries.push(delivery);
+            }
+            for delivery in deliveries {
+                delivery.await
+                    .context("Kafka producer stopped")?
+                    .map_err(|(e, _)| anyhow::anyhow!("Kafka delivery failed: {}", e))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+mod kafka {
+    use super::{FirehoseConfig, FirehoseSink};
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    pub fn connect(_config: &FirehoseConfig) -> Result<Arc<dyn FirehoseSink>> {
+        Err(anyhow::anyhow!("Kafka firehose requires DMPool built with the `kafka` feature"))
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::{FirehoseConfig, FirehoseSink, ShareRecord};
+    use anyhow::{Context, Result};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// Publishes shares to a NATS subject
+    pub struct NatsSink {
+        client: async_nats::Client,
+        subject: String,
+    }
+
+    pub async fn connect(config: &FirehoseConfig) -> Result<Arc<dyn FirehoseSink>> {
+        let client = async_nats::connect(&config.servers).await
+            .with_context(|| format!("Failed to connect to NATS at {}", config.servers))?;
+        Ok(Arc::new(NatsSink { client, subject: config.topic.clone() }))
+    }
+
+    #[async_trait]
+    impl FirehoseSink for NatsSink {
+        async fn publish(&self, batch: &[ShareRecord]) -> Result<()> {
+            for record in batch {
+                self.client.publish(self.subject.clone(), serde_json::to_vec(record)?.into()).await
+                    .context("Failed to publish to NATS")?;
+            }
+            self.client.flush().await.context("Failed to flush NATS client")?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "nats"))]
+mod nats {
+    use super::{FirehoseConfig, FirehoseSink};
+    use anyhow::Result;
+    use std::sync::Arc;
+
+    pub async fn connect(_config: &FirehoseConfig) -> Result<Arc<dyn FirehoseSink>> {
+        Err(anyhow::anyhow!("NATS firehose requires DMPool built with the `nats` feature"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex;
+
+    /// Records batches; fails the first `failures` publishes
+    struct MemorySink {
+        batches: Mutex<Vec<Vec<ShareRecord>>>,
+        failures: AtomicU64,
+    }
+
+    #[async_trait]
+    impl FirehoseSink for MemorySink {
+        async fn publish(&self, batch: &[ShareRecord]) -> Result<()> {
+            if self.failures.load(Ordering::Relaxed) > 0 {
+                self.failures.fetch_sub(1, Ordering::Relaxed);
+                return Err(anyhow::anyhow!("broker unavailable"));
+            }
+            self.batches.lock().await.push(batch.to_vec());
+            Ok(())
+        }
+    }
+
+    fn share(n: u64) -> ShareRecord {
+        ShareRecord { address: "bc1qa".to_string(), worker: Some("rig1".to_string()), difficulty: n, timestamp: n }
+    }
+
+    fn batching(batch_size: usize, buffer_size: usize) -> BatchSettings {
+        BatchSettings { batch_size, buffer_size, flush_interval_ms: 10, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_retries() {
+        let sink = Arc::new(MemorySink { batches: Mutex::new(Vec::new()), failures: AtomicU64::new(1) });
+        let (exporter, task) = FirehoseExporter::start("share_firehose", sink.clone(), &batching(2, 10));
+        for n in 0..5 {
+            assert!(exporter.offer(share(n)));
+        }
+        let counters = exporter.counters.clone();
+        drop(exporter);
+        task.await.unwrap();
+
+        // The first batch failed once and was retried
+        let batches = sink.batches.lock().await;
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(batches[0][0], share(0));
+        assert_eq!(counters.published.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_drops_and_failed_batches_count() {
+        let sink = Arc::new(MemorySink { batches: Mutex::new(Vec::new()), failures: AtomicU64::new(u64::MAX) });
+        let (exporter, _task) = FirehoseExporter::start("share_firehose", sink.clone(), &BatchSettings { max_retries: 1, ..batching(2, 2) });
+
+        // The exporter task has not run yet, so only the buffer's capacity fits
+        assert!(exporter.offer(share(0)));
+        assert!(exporter.offer(share(1)));
+        assert!(!exporter.offer(share(2)));
+
+        let event = PoolEvent::ShareAccepted { address: "bc1qa".to_string(), worker: None, difficulty: 1, n_time: 1 };
+        exporter.handle(&event).await.unwrap();
+        assert_eq!(exporter.stats().dropped, 2);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(exporter.stats(), FirehoseStats { published: 0, dropped: 2, failed: 2 });
+        assert!(sink.batches.lock().await.is_empty());
+        assert!(batching(0, 2).validate().is_err());
+    }
+}
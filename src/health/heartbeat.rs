@@ -0,0 +1,303 @@
+// Outbound heartbeat
+//
+// A dead-man's switch for the whole process: the pool pings an external
+// monitor (healthchecks.io style) every `interval_secs` with its key health
+// indicators as the JSON body. If the process dies or hangs the pings stop and
+// the monitor pages the operator, which the in-process AlertManager cannot do
+// for itself. An unhealthy check pings `<url>/fail` so the monitor alerts
+// straight away instead of waiting for the grace period.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::{HealthChecker, HealthStatus};
+
+/// The `[dmpool.heartbeat]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub enabled: bool,
+    /// Ping URL of the external monitor
+    pub url: String,
+    /// Seconds between pings; the monitor's period should match
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+    /// Ping `<url>/fail` when the health check is unhealthy
+    pub fail_on_unhealthy: bool,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            interval_secs: 60,
+            timeout_secs: 10,
+            fail_on_unhealthy: true,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn validate(&self) -> Result<()> {
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| anyhow::anyhow!("url {:?} is not valid: {}", self.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow::anyhow!("url must be http or https"));
+        }
+        if self.interval_secs < 10 {
+            return Err(anyhow::anyhow!("interval_secs must be at least 10"));
+        }
+        if self.timeout_secs == 0 || self.timeout_secs >= self.interval_secs {
+            return Err(anyhow::anyhow!("timeout_secs must be between 1 and interval_secs ({})", self.interval_secs));
+        }
+        Ok(())
+    }
+}
+
+/// Health indicators sent with each ping
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeartbeatPayload {
+    pub status: String,
+    pub database: String,
+    pub bitcoin_node: String,
+    pub stratum: String,
+    pub zmq: String,
+    pub block_height: u64,
+    pub sync_progress: f64,
+    pub stratum_connections: u32,
+    pub shares_per_second: f64,
+    pub uptime_seconds: u64,
+    pub memory_mb: Option<u64>,
+    /// PostgreSQL pool status, when monitored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postgres: Option<String>,
+    pub version: String,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl HeartbeatPayload {
+    pub fn from_status(status: &HealthStatus) -> Self {
+        Self {
+            status: status.status.clone(),
+            database: status.database.status.clone(),
+            bitcoin_node: status.bitcoin_node.status.clone(),
+            stratum: status.stratum.status.clone(),
+            zmq: status.zmq.status.clone(),
+            block_height: status.bitcoin_node.blockchain.blocks,
+            sync_progress: status.bitcoin_node.sync_progress,
+            stratum_connections: status.stratum.active_connections,
+            shares_per_second: status.stratum.shares_per_second,
+            uptime_seconds: status.uptime_seconds,
+            memory_mb: status.memory_mb,
+            postgres: status.postgres.as_ref().map(|pool| pool.status().to_string()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            sent_at: Utc::now(),
+        }
+    }
+}
+
+/// Where heartbeats get their health indicators
+#[async_trait]
+pub trait HealthSource: Send + Sync {
+    async fn health(&self) -> HealthStatus;
+}
+
+#[async_trait]
+impl HealthSource for HealthChecker {
+    async fn health(&self) -> HealthStatus {
+        self.check().await
+    }
+}
+
+/// Outcome of the latest ping
+#[derive(Clone, Debug, Serialize)]
+pub struct HeartbeatResult {
+    pub url: String,
+    pub status: String,
+    pub delivered: bool,
+    pub error: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Periodically pings the external monitor
+pub struct Heartbeat {
+    config: HeartbeatConfig,
+    health: Arc<dyn HealthSource>,
+    client: reqwest::Client,
+    last: RwLock<Option<HeartbeatResult>>,
+}
+
+impl Heartbeat {
+    pub fn new(config: HeartbeatConfig, health: Arc<dyn HealthSource>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .unwrap_or_default();
+        Self {
+            config,
+            health,
+            client,
+            last: RwLock::new(None),
+        }
+    }
+
+    /// URL to ping for a health status
+    pub fn ping_url(&self, status: &str) -> String {
+        if self.config.fail_on_unhealthy && status == "unhealthy" {
+            format!("{}/fail", self.config.url.trim_end_matches('/'))
+        } else {
+            self.config.url.clone()
+        }
+    }
+
+    /// Run a health check and ping the monitor with it
+    pub async fn beat(&self) -> HeartbeatResult {
+        let payload = HeartbeatPayload::from_status(&self.health.health().await);
+        self.send(&payload).await
+    }
+
+    /// Ping the monitor with a payload
+    pub async fn send(&self, payload: &HeartbeatPayload) -> HeartbeatResult {
+        let url = self.ping_url(&payload.status);
+        let error = self.post(&url, payload).await.err().map(|e| format!("{:#}", e));
+        match &error {
+            Some(e) => warn!("Heartbeat to {} failed: {}", url, e),
+            None => debug!("Heartbeat sent to {} ({})", url, payload.status),
+        }
+        let result = HeartbeatResult {
+            url,
+            status: payload.status.clone(),
+            delivered: error.is_none(),
+            error,
+            sent_at: payload.sent_at,
+        };
+        *self.last.write().await = Some(result.clone());
+        result
+    }
+
+    async fn post(&self, url: &str, payload: &HeartbeatPayload) -> Result<()> {
+        let response = self.client.post(url).json(payload).send().await
+            .context("Failed to reach heartbeat monitor")?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Heartbeat monitor returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Latest ping, if any was sent
+    pub async fn last(&self) -> Option<HeartbeatResult> {
+        self.last.read().await.clone()
+    }
+
+    /// Ping every `interval_secs` until the process exits
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+            loop {
+                interval.tick().await;
+                self.beat().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::{BitcoinNodeStatus, BlockchainInfo, ComponentStatus, NetworkInfo, StratumStatus};
+
+    fn status(overall: &str) -> HealthStatus {
+        HealthStatus {
+            status: overall.to_string(),
+            database: ComponentStatus::healthy(),
+            bitcoin_node: BitcoinNodeStatus {
+                status: "healthy".to_string(),
+                rpc_latency_ms: Some(12),
+                blockchain: BlockchainInfo {
+                    blocks: 850_000,
+                    headers: 850_000,
+                    initial_block_download: false,
+                    verification_progress: 1.0,
+                    block_time_seconds: Some(600),
+                    best_block_hash: "00".repeat(32),
+                },
+                network: NetworkInfo { connections: 8, network_active: true, peer_count: 8 },
+                sync_progress: 1.0,
+                message: "OK".to_string(),
+            },
+            stratum: StratumStatus {
+                status: overall.to_string(),
+                listening: true,
+                active_connections: 42,
+                shares_per_second: 1.5,
+                current_difficulty: 1024.0,
+                message: "OK".to_string(),
+                jobs: None,
+            },
+            zmq: ComponentStatus::healthy(),
+            zmq_topics: None,
+            uptime_seconds: 3600,
+            memory_mb: Some(256),
+            postgres: None,
+            runtime: None,
+            leadership: None,
+            payout_pause: None,
+            schema: None,
+        }
+    }
+
+    fn config(url: &str) -> HeartbeatConfig {
+        HeartbeatConfig { enabled: true, url: url.to_string(), ..Default::default() }
+    }
+
+    struct FixedHealth(&'static str);
+
+    #[async_trait]
+    impl HealthSource for FixedHealth {
+        async fn health(&self) -> HealthStatus {
+            status(self.0)
+        }
+    }
+
+    fn heartbeat(config: HeartbeatConfig) -> Heartbeat {
+        Heartbeat::new(config, Arc::new(FixedHealth("unhealthy")))
+    }
+
+    #[test]
+    fn test_config_and_ping_urls() {
+        assert!(config("https://hc-ping.com/abc").validate().is_ok());
+        assert!(config("ftp://example.com/abc").validate().is_err());
+        assert!(config("not a url").validate().is_err());
+        assert!(HeartbeatConfig { interval_secs: 5, ..config("https://hc-ping.com/abc") }.validate().is_err());
+        assert!(HeartbeatConfig { timeout_secs: 60, ..config("https://hc-ping.com/abc") }.validate().is_err());
+
+        let payload = HeartbeatPayload::from_status(&status("degraded"));
+        assert_eq!((payload.block_height, payload.stratum_connections), (850_000, 42));
+        assert_eq!(payload.stratum, "degraded");
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_pings_fail_url_and_records_errors() {
+        let heartbeat = heartbeat(config("http://127.0.0.1:9/ping/"));
+        assert_eq!(heartbeat.ping_url("healthy"), "http://127.0.0.1:9/ping/");
+        assert_eq!(heartbeat.ping_url("degraded"), "http://127.0.0.1:9/ping/");
+        assert_eq!(heartbeat.ping_url("unhealthy"), "http://127.0.0.1:9/ping/fail");
+
+        // Nothing listens on the discard port
+        let result = heartbeat.beat().await;
+        assert!(!result.delivered);
+        assert!(result.error.is_some());
+        assert_eq!(heartbeat.last().await.unwrap().url, "http://127.0.0.1:9/ping/fail");
+
+        let quiet = self::heartbeat(HeartbeatConfig { fail_on_unhealthy: false, ..config("http://127.0.0.1:9/ping") });
+        assert_eq!(quiet.ping_url("unhealthy"), "http://127.0.0.1:9/ping");
+    }
+}
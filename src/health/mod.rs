@@ -1,7 +1,17 @@
 // Health check module for DMPool
 // Enhanced health monitoring with database/RPC/ZMQ/Bitcoin node integration
 
+pub mod heartbeat;
+
+pub use heartbeat::{Heartbeat, HeartbeatConfig, HeartbeatPayload, HeartbeatResult, HealthSource};
+
 use anyhow::Result;
+use crate::bitcoin::{ZmqSubscriptions, ZmqTopicStatus};
+use crate::db::{PoolHealth, PoolMonitor, SchemaMonitor, SchemaReport};
+use crate::leadership::{Leadership, LeadershipStatus};
+use crate::payment::{PayoutPause, PayoutPauseStatus};
+use crate::runtime_metrics::{resident_memory_bytes, RuntimeMetrics, RuntimeSnapshot};
+use crate::stratum_stats::{JobFreshness, JobFreshnessStatus};
 use p2poolv2_lib::store::Store;
 use p2poolv2_lib::config::Config;
 use serde::{Deserialize, Serialize};
@@ -19,8 +29,27 @@ pub struct HealthStatus {
     pub bitcoin_node: BitcoinNodeStatus,
     pub stratum: StratumStatus,
     pub zmq: ComponentStatus,
+    /// rawblock/rawtx/hashtx subscriptions, when enabled; their health is folded into `zmq`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zmq_topics: Option<Vec<ZmqTopicStatus>>,
     pub uptime_seconds: u64,
     pub memory_mb: Option<u64>,
+    /// PostgreSQL pool health, when monitored; an outage degrades the pool
+    /// (mining continues) rather than making it unhealthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postgres: Option<PoolHealth>,
+    /// Tokio, in-memory cache and channel metrics, when collected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeSnapshot>,
+    /// Active/standby role; a standby is still healthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leadership: Option<LeadershipStatus>,
+    /// Payout kill switch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payout_pause: Option<PayoutPauseStatus>,
+    /// Last schema drift check; differences are warnings and do not change the status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<SchemaReport>,
 }
 
 /// Bitcoin node detailed status
@@ -62,6 +91,9 @@ pub struct StratumStatus {
     pub shares_per_second: f64,
     pub current_difficulty: f64,
     pub message: String,
+    /// Template and job notify freshness, when tracked; stale jobs degrade the stratum
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<JobFreshnessStatus>,
 }
 
 /// Individual component status
@@ -109,6 +141,13 @@ pub struct HealthChecker {
     active_connections: std::sync::Arc<std::sync::atomic::AtomicU32>,
     shares_per_second: std::sync::Arc<std::sync::atomic::AtomicU64>,  // Store as fixed-point (3 decimal places)
     current_difficulty: std::sync::Arc<std::sync::atomic::AtomicU64>,  // Store as fixed-point (2 decimal places)
+    pool_monitor: Option<Arc<PoolMonitor>>,
+    runtime_metrics: Option<Arc<RuntimeMetrics>>,
+    job_freshness: Option<Arc<JobFreshness>>,
+    leadership: Option<Arc<Leadership>>,
+    payout_pause: Option<Arc<PayoutPause>>,
+    schema: Option<Arc<SchemaMonitor>>,
+    zmq: Option<Arc<ZmqSubscriptions>>,
 }
 
 impl HealthChecker {
@@ -121,6 +160,13 @@ impl HealthChecker {
             active_connections: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0)),
             shares_per_second: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
             current_difficulty: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            pool_monitor: None,
+            runtime_metrics: None,
+            job_freshness: None,
+            leadership: None,
+            payout_pause: None,
+            schema: None,
+            zmq: None,
         }
     }
 
@@ -129,6 +175,77 @@ impl HealthChecker {
         self
     }
 
+    /// Report PostgreSQL pool health from this monitor
+    pub fn with_pool_monitor(mut self, monitor: Arc<PoolMonitor>) -> Self {
+        self.pool_monitor = Some(monitor);
+        self
+    }
+
+    /// Report tokio, in-memory cache and channel metrics from this collector
+    pub fn with_runtime_metrics(mut self, metrics: Arc<RuntimeMetrics>) -> Self {
+        self.runtime_metrics = Some(metrics);
+        self
+    }
+
+    /// Report block template and job notify freshness from this tracker
+    pub fn with_job_freshness(mut self, freshness: Arc<JobFreshness>) -> Self {
+        self.job_freshness = Some(freshness);
+        self
+    }
+
+    /// Report this instance's leader/standby role
+    pub fn with_leadership(mut self, leadership: Arc<Leadership>) -> Self {
+        self.leadership = Some(leadership);
+        self
+    }
+
+    /// Report whether payouts are paused
+    pub fn with_payout_pause(mut self, pause: Arc<PayoutPause>) -> Self {
+        self.payout_pause = Some(pause);
+        self
+    }
+
+    /// Report the last schema drift check
+    pub fn with_schema_monitor(mut self, schema: Arc<SchemaMonitor>) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Report per-topic health of these ZMQ subscriptions
+    pub fn with_zmq(mut self, zmq: Arc<ZmqSubscriptions>) -> Self {
+        self.zmq = Some(zmq);
+        self
+    }
+
+    /// ZMQ subscriptions, if enabled
+    pub fn zmq(&self) -> Option<&Arc<ZmqSubscriptions>> {
+        self.zmq.as_ref()
+    }
+
+    /// Latest template freshness, if tracked
+    pub async fn job_freshness(&self) -> Option<JobFreshnessStatus> {
+        match &self.job_freshness {
+            Some(freshness) => Some(freshness.status().await),
+            None => None,
+        }
+    }
+
+    /// Latest runtime metrics, if collected
+    pub async fn runtime_metrics(&self) -> Option<RuntimeSnapshot> {
+        match &self.runtime_metrics {
+            Some(metrics) => Some(metrics.snapshot().await),
+            None => None,
+        }
+    }
+
+    /// Latest PostgreSQL pool health, if monitored
+    pub async fn pool_health(&self) -> Option<PoolHealth> {
+        match &self.pool_monitor {
+            Some(monitor) => Some(monitor.health().await),
+            None => None,
+        }
+    }
+
     pub fn update_block_height(&self, height: u64) {
         self.last_block_height.store(height, std::sync::atomic::Ordering::Relaxed);
     }
@@ -160,7 +277,11 @@ impl HealthChecker {
         let db_status = self.check_database().await;
         let bitcoin_status = self.check_bitcoin_node().await;
         let stratum_status = self.check_stratum().await;
-        let zmq_status = self.check_zmq().await;
+        let zmq_topics = match &self.zmq {
+            Some(zmq) => Some(zmq.status().await),
+            None => None,
+        };
+        let zmq_status = self.check_zmq(zmq_topics.as_deref().unwrap_or_default()).await;
 
         let overall_status = match (
             db_status.status.as_str(),
@@ -172,8 +293,26 @@ impl HealthChecker {
             ("unhealthy", _, _, _) | (_, "unhealthy", _, _) | (_, _, "unhealthy", _) | (_, _, _, "unhealthy") => "unhealthy",
             _ => "degraded",
         };
+        let postgres = self.pool_health().await;
+        let overall_status = match &postgres {
+            Some(pool) if overall_status == "healthy" && pool.status() != "healthy" => "degraded",
+            _ => overall_status,
+        };
 
         let memory_mb = self.get_memory_usage();
+        let runtime = self.runtime_metrics().await;
+        let leadership = match &self.leadership {
+            Some(leadership) => Some(leadership.status().await),
+            None => None,
+        };
+        let payout_pause = match &self.payout_pause {
+            Some(pause) => Some(pause.status().await),
+            None => None,
+        };
+        let schema = match &self.schema {
+            Some(schema) => schema.latest().await,
+            None => None,
+        };
 
         HealthStatus {
             status: overall_status.to_string(),
@@ -181,8 +320,14 @@ impl HealthChecker {
             bitcoin_node: bitcoin_status,
             stratum: stratum_status,
             zmq: zmq_status,
+            zmq_topics,
             uptime_seconds: self.start_time.elapsed().as_secs(),
             memory_mb,
+            postgres,
+            runtime,
+            leadership,
+            payout_pause,
+            schema,
         }
     }
 
@@ -358,19 +503,29 @@ impl HealthChecker {
             _ => false,
         };
 
-        let status = if is_listening {
-            "healthy"
-        } else {
+        let jobs = self.job_freshness().await;
+        let stale = jobs.as_ref().is_some_and(|jobs| jobs.stale);
+
+        let status = if !is_listening {
             "unhealthy"
+        } else if stale {
+            "degraded"
+        } else {
+            "healthy"
         };
 
-        let message = if is_listening {
+        let message = if !is_listening {
+            format!("端口 {} 未监听", self.config.stratum.port)
+        } else if stale {
+            format!("端口 {} 监听中，但 {} 秒内没有新的区块模板",
+                self.config.stratum.port,
+                jobs.as_ref().map_or(0, |jobs| jobs.stale_template_secs)
+            )
+        } else {
             format!("端口 {} 监听中，{} 个活跃连接",
                 self.config.stratum.port,
                 active_connections
             )
-        } else {
-            format!("端口 {} 未监听", self.config.stratum.port)
         };
 
         StratumStatus {
@@ -380,11 +535,12 @@ impl HealthChecker {
             shares_per_second,
             current_difficulty,
             message,
+            jobs,
         }
     }
 
-    /// Check ZMQ endpoint connectivity
-    async fn check_zmq(&self) -> ComponentStatus {
+    /// Check ZMQ endpoint connectivity, degraded while a subscribed topic is disconnected or silent
+    async fn check_zmq(&self, topics: &[ZmqTopicStatus]) -> ComponentStatus {
         let zmq_url = &self.config.stratum.zmqpubhashblock;
         let parts: Vec<&str> = zmq_url.split("://").collect();
 
@@ -394,39 +550,29 @@ impl HealthChecker {
 
         let host_port = parts[1];
 
-        match timeout(Duration::from_secs(2), TcpStream::connect(host_port)).await {
+        let status = match timeout(Duration::from_secs(2), TcpStream::connect(host_port)).await {
             Ok(Ok(_)) => ComponentStatus::healthy()
                 .with_message(format!("ZMQ listening on {}", host_port)),
-            Ok(Err(e)) => ComponentStatus::unhealthy(format!("ZMQ connection failed: {}", e)),
-            Err(_) => ComponentStatus::unhealthy("ZMQ connection timeout (2s)"),
+            Ok(Err(e)) => return ComponentStatus::unhealthy(format!("ZMQ connection failed: {}", e)),
+            Err(_) => return ComponentStatus::unhealthy("ZMQ connection timeout (2s)"),
+        };
+        let failing: Vec<String> = topics.iter()
+            .filter(|topic| topic.status != "healthy")
+            .map(|topic| format!("{} {}", topic.topic.as_str(), topic.status))
+            .collect();
+        if failing.is_empty() {
+            return status;
+        }
+        ComponentStatus {
+            status: "degraded".to_string(),
+            message: format!("ZMQ listening on {}; {}", host_port, failing.join(", ")),
+            latency_ms: None,
         }
     }
 
     /// Get current process memory usage in MB
     fn get_memory_usage(&self) -> Option<u64> {
-        #[cfg(unix)]
-        {
-            use std::fs;
-            match fs::read_to_string("/proc/self/status") {
-                Ok(content) => {
-                    for line in content.lines() {
-                        if line.starts_with("VmRSS:") {
-                            let parts: Vec<&str> = line.split_whitespace().collect();
-                            if parts.len() >= 2 {
-                                let kb: u64 = parts[1].parse().ok()?;
-                                return Some(kb / 1024);
-                            }
-                        }
-                    }
-                    None
-                }
-                Err(_) => None,
-            }
-        }
-        #[cfg(not(unix))]
-        {
-            None
-        }
+        resident_memory_bytes().map(|bytes| bytes / 1024 / 1024)
     }
 }
 
@@ -485,10 +631,17 @@ mod tests {
                 shares_per_second: 0.0,
                 current_difficulty: 32.0,
                 message: "OK".to_string(),
+                jobs: None,
             },
             zmq: ComponentStatus::healthy(),
+            zmq_topics: None,
             uptime_seconds: 3600,
             memory_mb: Some(512),
+            postgres: None,
+            runtime: None,
+            leadership: None,
+            payout_pause: None,
+            schema: None,
         };
 
         let json = serde_json::to_string(&status).unwrap();
@@ -0,0 +1,120 @@
+// HTTP Response Compression Module for DMPool
+//
+// Observer and Admin API responses are compressed with brotli when the
+// request's Accept-Encoding allows it, and with gzip otherwise. Bodies below
+// `min_size_bytes`, images, gRPC and event streams are sent as they are.
+// Compressed responses carry `Vary: Accept-Encoding`.
+
+use anyhow::Result;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use tower_http::compression::predicate::{And, DefaultPredicate, Predicate, SizeAbove};
+use tower_http::compression::{CompressionLayer, CompressionLevel};
+
+/// Highest level any supported algorithm accepts (brotli; gzip stops at 9)
+const MAX_LEVEL: i32 = 11;
+
+/// Responses the compression layer compresses
+pub type CompressionPredicate = And<DefaultPredicate, SizeAbove>;
+
+/// The `[dmpool.http_compression]` settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub gzip: bool,
+    pub brotli: bool,
+    /// Smaller responses are sent uncompressed
+    pub min_size_bytes: u16,
+    /// gzip level (1-9); the algorithm's default when unset
+    pub gzip_level: Option<i32>,
+    /// brotli level (0-11); the algorithm's default when unset
+    pub brotli_level: Option<i32>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gzip: true,
+            brotli: true,
+            min_size_bytes: 1024,
+            gzip_level: None,
+            // Brotli's own default (11) is too slow for dynamic responses
+            brotli_level: Some(4),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.gzip && !self.brotli {
+            return Err(anyhow::anyhow!("at least one of gzip and brotli must be enabled"));
+        }
+        if self.gzip_level.is_some_and(|level| !(1..=9).contains(&level)) {
+            return Err(anyhow::anyhow!("gzip_level must be between 1 and 9"));
+        }
+        if self.brotli_level.is_some_and(|level| !(0..=MAX_LEVEL).contains(&level)) {
+            return Err(anyhow::anyhow!("brotli_level must be between 0 and {}", MAX_LEVEL));
+        }
+        Ok(())
+    }
+
+    fn predicate(&self) -> CompressionPredicate {
+        DefaultPredicate::new().and(SizeAbove::new(self.min_size_bytes))
+    }
+
+    fn quality(level: Option<i32>) -> CompressionLevel {
+        level.map_or(CompressionLevel::Default, CompressionLevel::Precise)
+    }
+
+    /// Layer compressing with one algorithm
+    fn single(&self, gzip: bool, level: Option<i32>) -> CompressionLayer<CompressionPredicate> {
+        CompressionLayer::new()
+            .gzip(gzip)
+            .br(!gzip)
+            .quality(Self::quality(level))
+            .compress_when(self.predicate())
+    }
+
+    /// Compress the responses of `router`
+    pub fn apply<S: Clone + Send + Sync + 'static>(&self, router: Router<S>) -> Router<S> {
+        // One layer per algorithm so each gets its own level. The outer gzip
+        // layer passes through what brotli already encoded, so gzip is only
+        // used when the client does not accept brotli.
+        let router = if self.brotli { router.layer(self.single(false, self.brotli_level)) } else { router };
+        if self.gzip { router.layer(self.single(true, self.gzip_level)) } else { router }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{header, Request}, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_negotiates_encoding_above_min_size() {
+        let config = CompressionConfig { enabled: true, min_size_bytes: 100, ..Default::default() };
+        assert!(config.validate().is_ok());
+        let router = config.apply(Router::new()
+            .route("/large", get(|| async { "x".repeat(4096) }))
+            .route("/small", get(|| async { "x".repeat(10) })));
+
+        let encoding = |uri: &'static str, accept: &'static str| {
+            let router = router.clone();
+            async move {
+                let request = Request::builder().uri(uri).header(header::ACCEPT_ENCODING, accept).body(Body::empty()).unwrap();
+                let response = router.oneshot(request).await.unwrap();
+                response.headers().get(header::CONTENT_ENCODING).map(|h| h.to_str().unwrap().to_string())
+            }
+        };
+        assert_eq!(encoding("/large", "gzip, br").await.as_deref(), Some("br"));
+        assert_eq!(encoding("/large", "gzip").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding("/large", "identity").await, None);
+        assert_eq!(encoding("/small", "gzip, br").await, None);
+
+        let invalid = CompressionConfig { gzip_level: Some(10), ..config };
+        assert!(invalid.validate().is_err());
+    }
+}
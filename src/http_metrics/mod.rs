@@ -0,0 +1,478 @@
+// HTTP Request Metrics Module for DMPool
+// Per-route request counts, status codes, latency histograms and SLO burn rates
+//
+// A middleware on the Observer and Admin API routers records every request
+// under its matched route (`/api/v1/stats/:address`, not the raw path), so
+// the number of series stays bounded. Each configured SLO keeps per-minute
+// totals of the requests it covers; the burn rate of a window is the share
+// of bad requests divided by the error budget (1 - target), so a burn rate
+// of 1 spends the budget exactly over the SLO period and 14 spends a
+// 30-day budget in about two days.
+
+use anyhow::Result;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use crate::runtime_metrics::escape_label;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Route label of requests no route matched
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// API labels used by the routers
+pub const OBSERVER_API: &str = "observer";
+pub const ADMIN_API: &str = "admin";
+
+/// Longest burn rate window
+const MAX_WINDOW_SECS: u64 = 7 * 86_400;
+
+/// Objective for the requests of one API or route
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SloTarget {
+    pub name: String,
+    /// `observer` or `admin`; every API when unset
+    #[serde(default)]
+    pub api: Option<String>,
+    /// Matched route, e.g. `/api/v1/stats/:address`; every route when unset
+    #[serde(default)]
+    pub route: Option<String>,
+    /// Fraction of requests that must not fail with a 5xx status
+    pub availability: f64,
+    /// Requests slower than this count against the latency objective
+    pub latency_threshold_ms: u64,
+    /// Fraction of requests that must finish within the threshold
+    pub latency_target: f64,
+}
+
+impl SloTarget {
+    fn covers(&self, api: &str, route: &str) -> bool {
+        self.api.as_deref().is_none_or(|a| a == api) && self.route.as_deref().is_none_or(|r| r == route)
+    }
+}
+
+/// The `[dmpool.http_metrics]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpMetricsConfig {
+    pub enabled: bool,
+    /// Upper bounds of the latency histogram buckets, ascending
+    pub buckets_ms: Vec<u64>,
+    /// Windows burn rates are computed over
+    pub burn_rate_windows_secs: Vec<u64>,
+    pub slos: Vec<SloTarget>,
+}
+
+impl Default for HttpMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            buckets_ms: vec![5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10_000],
+            burn_rate_windows_secs: vec![300, 3600, 21_600],
+            slos: vec![
+                SloTarget {
+                    name: "observer".to_string(),
+                    api: Some(OBSERVER_API.to_string()),
+                    route: None,
+                    availability: 0.999,
+                    latency_threshold_ms: 500,
+                    latency_target: 0.99,
+                },
+                SloTarget {
+                    name: "admin".to_string(),
+                    api: Some(ADMIN_API.to_string()),
+                    route: None,
+                    availability: 0.99,
+                    latency_threshold_ms: 2000,
+                    latency_target: 0.95,
+                },
+            ],
+        }
+    }
+}
+
+impl HttpMetricsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.buckets_ms.is_empty() || self.buckets_ms[0] == 0 {
+            return Err(anyhow::anyhow!("buckets_ms must hold at least one positive bound"));
+        }
+        if self.buckets_ms.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(anyhow::anyhow!("buckets_ms must be strictly ascending"));
+        }
+        if self.burn_rate_windows_secs.is_empty() {
+            return Err(anyhow::anyhow!("at least one burn rate window is required"));
+        }
+        if let Some(window) = self.burn_rate_windows_secs.iter().find(|&&w| !(60..=MAX_WINDOW_SECS).contains(&w)) {
+            return Err(anyhow::anyhow!("burn rate window {}s must be between 60s and {}s", window, MAX_WINDOW_SECS));
+        }
+        for (i, slo) in self.slos.iter().enumerate() {
+            if slo.name.is_empty() {
+                return Err(anyhow::anyhow!("SLO names must not be empty"));
+            }
+            if self.slos[..i].iter().any(|s| s.name == slo.name) {
+                return Err(anyhow::anyhow!("more than one SLO named {:?}", slo.name));
+            }
+            if slo.api.as_deref().is_some_and(|api| api != OBSERVER_API && api != ADMIN_API) {
+                return Err(anyhow::anyhow!("SLO {:?}: api must be observer or admin", slo.name));
+            }
+            for target in [slo.availability, slo.latency_target] {
+                if !(target > 0.0 && target < 1.0) {
+                    return Err(anyhow::anyhow!("SLO {:?}: targets must be between 0 and 1", slo.name));
+                }
+            }
+            if slo.latency_threshold_ms == 0 {
+                return Err(anyhow::anyhow!("SLO {:?}: latency_threshold_ms must be at least 1", slo.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RouteKey {
+    api: String,
+    route: String,
+    method: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct RouteStats {
+    statuses: BTreeMap<u16, u64>,
+    /// Requests per bucket, the last one past the largest bound
+    buckets: Vec<u64>,
+    count: u64,
+    sum_seconds: f64,
+}
+
+/// Requests an SLO covered in one minute
+#[derive(Clone, Copy, Debug, Default)]
+struct SloMinute {
+    minute: i64,
+    total: u64,
+    errors: u64,
+    slow: u64,
+}
+
+/// Request figures of one route
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RouteSummary {
+    pub api: String,
+    pub method: String,
+    pub route: String,
+    pub requests: u64,
+    pub server_errors: u64,
+    pub statuses: BTreeMap<u16, u64>,
+    pub mean_ms: f64,
+    /// Upper bound of the bucket holding the percentile; none past the largest bucket
+    pub p50_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+}
+
+/// SLO figures over one window
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SloWindow {
+    pub window_secs: u64,
+    pub requests: u64,
+    /// Ratios and burn rates are none without requests
+    pub error_ratio: Option<f64>,
+    pub availability_burn_rate: Option<f64>,
+    pub slow_ratio: Option<f64>,
+    pub latency_burn_rate: Option<f64>,
+}
+
+/// An SLO and its burn rates
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SloStatus {
+    #[serde(flatten)]
+    pub target: SloTarget,
+    pub windows: Vec<SloWindow>,
+}
+
+/// Per-route figures and SLO burn rates
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HttpMetricsSummary {
+    pub routes: Vec<RouteSummary>,
+    pub slos: Vec<SloStatus>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Request metrics shared by the Observer and Admin API routers
+pub struct HttpMetrics {
+    config: HttpMetricsConfig,
+    routes: Mutex<BTreeMap<RouteKey, RouteStats>>,
+    /// Per-minute totals for each SLO, oldest first
+    slo_minutes: Mutex<Vec<VecDeque<SloMinute>>>,
+}
+
+impl HttpMetrics {
+    pub fn new(config: HttpMetricsConfig) -> Self {
+        let slo_minutes = vec![VecDeque::new(); config.slos.len()];
+        Self { config, routes: Mutex::new(BTreeMap::new()), slo_minutes: Mutex::new(slo_minutes) }
+    }
+
+    pub fn config(&self) -> &HttpMetricsConfig {
+        &self.config
+    }
+
+    /// Record a finished request
+    pub fn record(&self, api: &str, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.record_at(api, method, route, status, elapsed, Utc::now());
+    }
+
+    fn record_at(&self, api: &str, method: &str, route: &str, status: u16, elapsed: Duration, now: DateTime<Utc>) {
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+        let bucket = self.config.buckets_ms.iter()
+            .position(|&bound| elapsed_ms <= bound as f64)
+            .unwrap_or(self.config.buckets_ms.len());
+        {
+            let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+            let key = RouteKey { api: api.to_string(), route: route.to_string(), method: method.to_string() };
+            let stats = routes.entry(key).or_default();
+            if stats.buckets.is_empty() {
+                stats.buckets = vec![0; self.config.buckets_ms.len() + 1];
+            }
+            stats.buckets[bucket] += 1;
+            *stats.statuses.entry(status).or_default() += 1;
+            stats.count += 1;
+            stats.sum_seconds += elapsed.as_secs_f64();
+        }
+
+        let minute = now.timestamp().div_euclid(60);
+        let keep_minutes = self.keep_minutes();
+        let mut slo_minutes = self.slo_minutes.lock().unwrap_or_else(|e| e.into_inner());
+        for (slo, minutes) in self.config.slos.iter().zip(slo_minutes.iter_mut()) {
+            if !slo.covers(api, route) {
+                continue;
+            }
+            if minutes.back().is_none_or(|m| m.minute != minute) {
+                minutes.push_back(SloMinute { minute, ..Default::default() });
+            }
+            while minutes.front().is_some_and(|m| m.minute <= minute - keep_minutes) {
+                minutes.pop_front();
+            }
+            if let Some(current) = minutes.back_mut() {
+                current.total += 1;
+                current.errors += u64::from(status >= 500);
+                current.slow += u64::from(elapsed_ms > slo.latency_threshold_ms as f64);
+            }
+        }
+    }
+
+    fn keep_minutes(&self) -> i64 {
+        let longest = self.config.burn_rate_windows_secs.iter().max().copied().unwrap_or(60);
+        longest.div_ceil(60) as i64
+    }
+
+    /// Route figures and SLO burn rates
+    pub fn summary(&self) -> HttpMetricsSummary {
+        self.summary_at(Utc::now())
+    }
+
+    fn summary_at(&self, now: DateTime<Utc>) -> HttpMetricsSummary {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner()).iter()
+            .map(|(key, stats)| RouteSummary {
+                api: key.api.clone(),
+                method: key.method.clone(),
+                route: key.route.clone(),
+                requests: stats.count,
+                server_errors: stats.statuses.range(500..).map(|(_, count)| count).sum(),
+                statuses: stats.statuses.clone(),
+                mean_ms: if stats.count == 0 { 0.0 } else { stats.sum_seconds * 1000.0 / stats.count as f64 },
+                p50_ms: self.percentile(stats, 0.50),
+                p95_ms: self.percentile(stats, 0.95),
+                p99_ms: self.percentile(stats, 0.99),
+            })
+            .collect();
+
+        let minute = now.timestamp().div_euclid(60);
+        let slo_minutes = self.slo_minutes.lock().unwrap_or_else(|e| e.into_inner());
+        let slos = self.config.slos.iter().zip(slo_minutes.iter())
+            .map(|(slo, minutes)| SloStatus {
+                target: slo.clone(),
+                windows: self.config.burn_rate_windows_secs.iter()
+                    .map(|&window_secs| window(slo, minutes, minute, window_secs))
+                    .collect(),
+            })
+            .collect();
+
+        HttpMetricsSummary { routes, slos, generated_at: now }
+    }
+
+    fn percentile(&self, stats: &RouteStats, quantile: f64) -> Option<u64> {
+        let rank = (stats.count as f64 * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in stats.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return self.config.buckets_ms.get(i).copied();
+            }
+        }
+        None
+    }
+
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        {
+            let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = writeln!(out, "# HELP dmpool_http_requests_total HTTP requests by route and status");
+            let _ = writeln!(out, "# TYPE dmpool_http_requests_total counter");
+            for (key, stats) in routes.iter() {
+                for (status, count) in &stats.statuses {
+                    let status = status.to_string();
+                    let _ = writeln!(out, "dmpool_http_requests_total{} {}", labels(&route_labels(key, &[("status", &status)])), count);
+                }
+            }
+            let _ = writeln!(out, "# HELP dmpool_http_request_duration_seconds HTTP request latency by route");
+            let _ = writeln!(out, "# TYPE dmpool_http_request_duration_seconds histogram");
+            for (key, stats) in routes.iter() {
+                let mut cumulative = 0;
+                for (i, count) in stats.buckets.iter().enumerate() {
+                    cumulative += count;
+                    let le = match self.config.buckets_ms.get(i) {
+                        Some(bound) => (*bound as f64 / 1000.0).to_string(),
+                        None => "+Inf".to_string(),
+                    };
+                    let _ = writeln!(out, "dmpool_http_request_duration_seconds_bucket{} {}", labels(&route_labels(key, &[("le", &le)])), cumulative);
+                }
+                let route = labels(&route_labels(key, &[]));
+                let _ = writeln!(out, "dmpool_http_request_duration_seconds_sum{} {}", route, stats.sum_seconds);
+                let _ = writeln!(out, "dmpool_http_request_duration_seconds_count{} {}", route, stats.count);
+            }
+        }
+
+        let summary = self.summary();
+        if summary.slos.is_empty() {
+            return out;
+        }
+        let _ = writeln!(out, "# HELP dmpool_slo_burn_rate Error budget burn rate per SLO, objective and window");
+        let _ = writeln!(out, "# TYPE dmpool_slo_burn_rate gauge");
+        for slo in &summary.slos {
+            for window in &slo.windows {
+                let window_secs = window.window_secs.to_string();
+                let rates = [("availability", window.availability_burn_rate), ("latency", window.latency_burn_rate)];
+                for (objective, rate) in rates {
+                    let Some(rate) = rate else {
+                        continue;
+                    };
+                    let _ = writeln!(out, "dmpool_slo_burn_rate{} {}",
+                        labels(&[("slo", &slo.target.name), ("objective", objective), ("window_secs", &window_secs)]), rate);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// SLO figures over the `window_secs` ending in `minute`
+fn window(slo: &SloTarget, minutes: &VecDeque<SloMinute>, minute: i64, window_secs: u64) -> SloWindow {
+    let first = minute - window_secs.div_ceil(60) as i64;
+    let (requests, errors, slow) = minutes.iter()
+        .filter(|m| m.minute > first && m.minute <= minute)
+        .fold((0, 0, 0), |(t, e, s), m| (t + m.total, e + m.errors, s + m.slow));
+    let ratio = |bad: u64| (requests > 0).then(|| bad as f64 / requests as f64);
+    let error_ratio = ratio(errors);
+    let slow_ratio = ratio(slow);
+    SloWindow {
+        window_secs,
+        requests,
+        error_ratio,
+        availability_burn_rate: error_ratio.map(|r| r / (1.0 - slo.availability)),
+        slow_ratio,
+        latency_burn_rate: slow_ratio.map(|r| r / (1.0 - slo.latency_target)),
+    }
+}
+
+fn route_labels<'a>(key: &'a RouteKey, extra: &[(&'a str, &'a str)]) -> Vec<(&'a str, &'a str)> {
+    let mut pairs = vec![("api", key.api.as_str()), ("method", key.method.as_str()), ("route", key.route.as_str())];
+    pairs.extend_from_slice(extra);
+    pairs
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = pairs.iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Record the route, status and latency of every request
+///
+/// The state pairs the shared metrics with the API label.
+pub async fn http_metrics_middleware(
+    State((metrics, api)): State<(Arc<HttpMetrics>, &'static str)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = req.extensions().get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, |path| path.as_str())
+        .to_string();
+    let method = req.method().clone();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    metrics.record(api, method.as_str(), &route, response.status().as_u16(), started.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes_are_counted_and_rendered() {
+        let metrics = HttpMetrics::new(HttpMetricsConfig::default());
+        for ms in [3, 40, 40, 700] {
+            metrics.record(OBSERVER_API, "GET", "/api/v1/stats/:address", 200, Duration::from_millis(ms));
+        }
+        metrics.record(OBSERVER_API, "GET", "/api/v1/stats/:address", 503, Duration::from_millis(20_000));
+
+        let summary = metrics.summary();
+        let route = &summary.routes[0];
+        assert_eq!((route.requests, route.server_errors), (5, 1));
+        assert_eq!(route.statuses[&200], 4);
+        assert_eq!((route.p50_ms, route.p95_ms), (Some(50), None));
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("dmpool_http_requests_total{api=\"observer\",method=\"GET\",route=\"/api/v1/stats/:address\",status=\"503\"} 1"));
+        assert!(text.contains("dmpool_http_request_duration_seconds_bucket{api=\"observer\",method=\"GET\",route=\"/api/v1/stats/:address\",le=\"0.05\"} 3"));
+        assert!(text.contains("le=\"+Inf\"} 5"));
+        assert!(text.contains("dmpool_slo_burn_rate{slo=\"observer\",objective=\"availability\",window_secs=\"300\"}"));
+        assert!(!text.contains("slo=\"admin\""));
+    }
+
+    #[test]
+    fn test_burn_rates_per_window() {
+        let metrics = HttpMetrics::new(HttpMetricsConfig::default());
+        let now = Utc::now();
+        // An hour ago: 100 good requests; now: 10 requests, one failed and two slow
+        for _ in 0..100 {
+            metrics.record_at(ADMIN_API, "GET", "/api/admin/dashboard", 200, Duration::from_millis(10), now - chrono::Duration::minutes(50));
+        }
+        for i in 0..10 {
+            let status = if i == 0 { 500 } else { 200 };
+            let elapsed = Duration::from_millis(if i < 2 { 3000 } else { 10 });
+            metrics.record_at(ADMIN_API, "POST", "/api/admin/config", status, elapsed, now);
+        }
+
+        let admin = &metrics.summary_at(now).slos[1];
+        let short = &admin.windows[0];
+        assert_eq!(short.requests, 10);
+        assert!((short.availability_burn_rate.unwrap() - 10.0).abs() < 1e-9);
+        assert!((short.latency_burn_rate.unwrap() - 4.0).abs() < 1e-9);
+        let hour = &admin.windows[1];
+        assert_eq!(hour.requests, 110);
+        assert!(hour.availability_burn_rate.unwrap() < 1.0);
+        // The observer SLO saw nothing
+        assert_eq!(metrics.summary_at(now).slos[0].windows[0].error_ratio, None);
+
+        let bad = HttpMetricsConfig { burn_rate_windows_secs: vec![30], ..Default::default() };
+        assert!(bad.validate().is_err());
+    }
+}
@@ -0,0 +1,488 @@
+// Key Management Module for DMPool
+// Provides data-encryption keys from pluggable backends: environment, sealed file, OS keyring and Vault
+
+use anyhow::{Context, Result};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// Key ID for TOTP secret encryption
+pub const KEY_ID_TWO_FACTOR: &str = "two_factor";
+
+/// Key ID reserved for payment signing secrets
+pub const KEY_ID_PAYMENT_SIGNING: &str = "payment_signing";
+
+/// Key ID for the config version signing key (an ed25519 seed)
+pub const KEY_ID_CONFIG_SIGNING: &str = "config_signing";
+
+/// A 256-bit data-encryption key
+pub type KeyBytes = [u8; 32];
+
+/// Source of data-encryption keys
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+    /// Backend name (for logging)
+    fn name(&self) -> &'static str;
+
+    /// Load a key, returning None if it does not exist
+    async fn load_key(&self, key_id: &str) -> Result<Option<KeyBytes>>;
+
+    /// Store (or replace) a key
+    async fn store_key(&self, key_id: &str, key: &KeyBytes) -> Result<()>;
+}
+
+/// Generate a new random key
+pub fn generate_key() -> KeyBytes {
+    Aes256Gcm::generate_key(&mut OsRng).into()
+}
+
+/// Decode a base64 key and check its length
+fn decode_key(encoded: &str) -> Result<KeyBytes> {
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .context("Key is not valid base64")?;
+
+    if bytes.len() != 32 {
+        return Err(anyhow::anyhow!("Key must be 32 bytes (256 bits), got {}", bytes.len()));
+    }
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+/// Validate a key ID so it can be used safely in file names, URLs and CLI arguments
+fn validate_key_id(key_id: &str) -> Result<()> {
+    if key_id.is_empty() || !key_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(anyhow::anyhow!("Invalid key id: {:?}", key_id));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Environment Provider
+// ============================================================================
+
+/// Read-only provider backed by environment variables
+///
+/// Key `two_factor` is read from TWO_FACTOR_ENCRYPTION_KEY for compatibility,
+/// other keys from DMPOOL_KEY_<ID>.
+pub struct EnvKeyProvider;
+
+impl EnvKeyProvider {
+    /// Environment variable name for a key ID
+    pub fn var_name(key_id: &str) -> String {
+        if key_id == KEY_ID_TWO_FACTOR {
+            "TWO_FACTOR_ENCRYPTION_KEY".to_string()
+        } else {
+            format!("DMPOOL_KEY_{}", key_id.to_uppercase().replace('-', "_"))
+        }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for EnvKeyProvider {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn load_key(&self, key_id: &str) -> Result<Option<KeyBytes>> {
+        let var = Self::var_name(key_id);
+        match std::env::var(&var) {
+            Ok(value) => decode_key(&value).with_context(|| format!("Invalid {}", var)).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn store_key(&self, key_id: &str, _key: &KeyBytes) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "Environment key provider is read-only; set {} manually",
+            Self::var_name(key_id)
+        ))
+    }
+}
+
+// ============================================================================
+// Sealed File Provider
+// ============================================================================
+
+/// Key sealed on disk with a passphrase-derived key-encryption key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SealedKey {
+    /// Format version
+    version: u32,
+    /// Argon2 salt (base64)
+    salt: String,
+    /// AES-GCM nonce (base64)
+    nonce: String,
+    /// Encrypted key (base64)
+    ciphertext: String,
+    /// When the key was sealed
+    created_at: DateTime<Utc>,
+}
+
+/// Provider storing keys as sealed files (`<dir>/<key_id>.key.json`)
+pub struct SealedFileKeyProvider {
+    dir: PathBuf,
+    passphrase: String,
+}
+
+impl SealedFileKeyProvider {
+    /// Create a sealed file provider
+    pub fn new(dir: PathBuf, passphrase: String) -> Result<Self> {
+        if passphrase.len() < 16 {
+            return Err(anyhow::anyhow!("Key passphrase must be at least 16 characters"));
+        }
+        Ok(Self { dir, passphrase })
+    }
+
+    fn key_path(&self, key_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.key.json", key_id))
+    }
+
+    /// Derive the key-encryption key from the passphrase
+    fn derive_kek(&self, salt: &[u8]) -> Result<KeyBytes> {
+        let mut kek = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut kek)
+            .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+        Ok(kek)
+    }
+
+    fn seal(&self, key_id: &str, key: &KeyBytes) -> Result<SealedKey> {
+        use rand::RngCore;
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let kek = self.derive_kek(&salt)?;
+        let cipher = Aes256Gcm::new(&kek.into());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: key, aad: key_id.as_bytes() })
+            .map_err(|e| anyhow::anyhow!("Failed to seal key: {}", e))?;
+
+        Ok(SealedKey {
+            version: 1,
+            salt: general_purpose::STANDARD.encode(salt),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+            created_at: Utc::now(),
+        })
+    }
+
+    fn unseal(&self, key_id: &str, sealed: &SealedKey) -> Result<KeyBytes> {
+        let salt = general_purpose::STANDARD.decode(&sealed.salt).context("Invalid salt")?;
+        let nonce = general_purpose::STANDARD.decode(&sealed.nonce).context("Invalid nonce")?;
+        let ciphertext = general_purpose::STANDARD.decode(&sealed.ciphertext).context("Invalid ciphertext")?;
+
+        let kek = self.derive_kek(&salt)?;
+        let cipher = Aes256Gcm::new(&kek.into());
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: key_id.as_bytes() })
+            .map_err(|_| anyhow::anyhow!("Failed to unseal key '{}': wrong passphrase or corrupted file", key_id))?;
+
+        if plaintext.len() != 32 {
+            return Err(anyhow::anyhow!("Sealed key '{}' has invalid length", key_id));
+        }
+
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&plaintext);
+        Ok(key)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for SealedFileKeyProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn load_key(&self, key_id: &str) -> Result<Option<KeyBytes>> {
+        validate_key_id(key_id)?;
+        let path = self.key_path(key_id);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = tokio::fs::read_to_string(&path).await
+            .context("Failed to read sealed key file")?;
+        let sealed: SealedKey = serde_json::from_str(&content)
+            .context("Failed to parse sealed key file")?;
+
+        self.unseal(key_id, &sealed).map(Some)
+    }
+
+    async fn store_key(&self, key_id: &str, key: &KeyBytes) -> Result<()> {
+        validate_key_id(key_id)?;
+        tokio::fs::create_dir_all(&self.dir).await
+            .context("Failed to create key directory")?;
+
+        let sealed = self.seal(key_id, key)?;
+        let json = serde_json::to_string_pretty(&sealed)
+            .context("Failed to serialize sealed key")?;
+
+        // Write to a temp file and rename so a crash never leaves a truncated key
+        let path = self.key_path(key_id);
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json).await
+            .context("Failed to write sealed key file")?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600)).await
+                .context("Failed to restrict sealed key permissions")?;
+        }
+
+        tokio::fs::rename(&tmp_path, &path).await
+            .context("Failed to move sealed key into place")?;
+
+        info!("Stored sealed key '{}' at {:?}", key_id, path);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// OS Keyring Provider
+// ============================================================================
+
+/// Provider backed by the OS keyring through the freedesktop Secret Service (`secret-tool`)
+pub struct KeyringKeyProvider {
+    service: String,
+}
+
+impl KeyringKeyProvider {
+    /// Create a keyring provider using the given service name
+    pub fn new(service: String) -> Self {
+        Self { service }
+    }
+}
+
+#[async_trait]
+impl KeyProvider for KeyringKeyProvider {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    async fn load_key(&self, key_id: &str) -> Result<Option<KeyBytes>> {
+        validate_key_id(key_id)?;
+        let output = tokio::process::Command::new("secret-tool")
+            .args(["lookup", "service", &self.service, "key", key_id])
+            .output()
+            .await
+            .context("Failed to run secret-tool (is libsecret installed?)")?;
+
+        // secret-tool exits non-zero when no matching secret exists
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+
+        let encoded = String::from_utf8(output.stdout).context("Keyring returned invalid UTF-8")?;
+        decode_key(&encoded).map(Some)
+    }
+
+    async fn store_key(&self, key_id: &str, key: &KeyBytes) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+        validate_key_id(key_id)?;
+
+        let mut child = tokio::process::Command::new("secret-tool")
+            .args([
+                "store",
+                &format!("--label=DMPool {}", key_id),
+                "service", &self.service,
+                "key", key_id,
+            ])
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to run secret-tool (is libsecret installed?)")?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(general_purpose::STANDARD.encode(key).as_bytes()).await
+                .context("Failed to pass key to secret-tool")?;
+        }
+
+        let status = child.wait().await.context("secret-tool did not exit")?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("secret-tool store failed with {}", status));
+        }
+
+        info!("Stored key '{}' in OS keyring", key_id);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Vault Provider
+// ============================================================================
+
+/// Provider backed by a HashiCorp Vault KV v2 secrets engine
+pub struct VaultKeyProvider {
+    addr: String,
+    token: String,
+    mount: String,
+    path_prefix: String,
+    client: reqwest::Client,
+}
+
+impl VaultKeyProvider {
+    /// Create a Vault provider
+    pub fn new(addr: String, token: String, mount: String, path_prefix: String) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            addr: addr.trim_end_matches('/').to_string(),
+            token,
+            mount,
+            path_prefix: path_prefix.trim_matches('/').to_string(),
+            client,
+        })
+    }
+
+    fn secret_url(&self, key_id: &str) -> String {
+        format!("{}/v1/{}/data/{}/{}", self.addr, self.mount, self.path_prefix, key_id)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for VaultKeyProvider {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn load_key(&self, key_id: &str) -> Result<Option<KeyBytes>> {
+        validate_key_id(key_id)?;
+        let response = self.client
+            .get(self.secret_url(key_id))
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .context("Failed to reach Vault")?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Vault returned status {}", response.status()));
+        }
+
+        let body: serde_json::Value = response.json().await.context("Invalid Vault response")?;
+        let encoded = body["data"]["data"]["key"].as_str()
+            .ok_or_else(|| anyhow::anyhow!("Vault secret for '{}' has no 'key' field", key_id))?;
+
+        decode_key(encoded).map(Some)
+    }
+
+    async fn store_key(&self, key_id: &str, key: &KeyBytes) -> Result<()> {
+        validate_key_id(key_id)?;
+        let response = self.client
+            .post(self.secret_url(key_id))
+            .header("X-Vault-Token", &self.token)
+            .json(&serde_json::json!({
+                "data": { "key": general_purpose::STANDARD.encode(key) }
+            }))
+            .send()
+            .await
+            .context("Failed to reach Vault")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Vault returned status {}", response.status()));
+        }
+
+        info!("Stored key '{}' in Vault", key_id);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Provider Selection
+// ============================================================================
+
+/// Build a key provider from environment variables
+///
+/// DMPOOL_KEY_PROVIDER selects the backend: env (default), file, keyring or vault.
+pub fn provider_from_env() -> Result<Arc<dyn KeyProvider>> {
+    let backend = std::env::var("DMPOOL_KEY_PROVIDER").unwrap_or_else(|_| "env".to_string());
+
+    let provider: Arc<dyn KeyProvider> = match backend.as_str() {
+        "env" => Arc::new(EnvKeyProvider),
+        "file" => {
+            let dir = std::env::var("DMPOOL_KEY_DIR").unwrap_or_else(|_| "./data/keys".to_string());
+            let passphrase = std::env::var("DMPOOL_KEY_PASSPHRASE")
+                .context("DMPOOL_KEY_PASSPHRASE must be set for the file key provider")?;
+            Arc::new(SealedFileKeyProvider::new(PathBuf::from(dir), passphrase)?)
+        }
+        "keyring" => {
+            let service = std::env::var("DMPOOL_KEYRING_SERVICE").unwrap_or_else(|_| "dmpool".to_string());
+            Arc::new(KeyringKeyProvider::new(service))
+        }
+        "vault" => {
+            let addr = std::env::var("VAULT_ADDR").context("VAULT_ADDR must be set for the vault key provider")?;
+            let token = std::env::var("VAULT_TOKEN").context("VAULT_TOKEN must be set for the vault key provider")?;
+            let mount = std::env::var("DMPOOL_VAULT_MOUNT").unwrap_or_else(|_| "secret".to_string());
+            let prefix = std::env::var("DMPOOL_VAULT_PATH").unwrap_or_else(|_| "dmpool/keys".to_string());
+            Arc::new(VaultKeyProvider::new(addr, token, mount, prefix)?)
+        }
+        other => return Err(anyhow::anyhow!("Unknown key provider: {}", other)),
+    };
+
+    info!("Using '{}' key provider", provider.name());
+    Ok(provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_sealed_file_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = SealedFileKeyProvider::new(
+            temp_dir.path().to_path_buf(),
+            "correct horse battery staple".to_string(),
+        ).unwrap();
+
+        assert!(provider.load_key("two_factor").await.unwrap().is_none());
+
+        let key = generate_key();
+        provider.store_key("two_factor", &key).await.unwrap();
+        assert_eq!(provider.load_key("two_factor").await.unwrap(), Some(key));
+
+        // A different passphrase cannot unseal the key
+        let other = SealedFileKeyProvider::new(
+            temp_dir.path().to_path_buf(),
+            "a completely different passphrase".to_string(),
+        ).unwrap();
+        assert!(other.load_key("two_factor").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key_id_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = SealedFileKeyProvider::new(
+            temp_dir.path().to_path_buf(),
+            "correct horse battery staple".to_string(),
+        ).unwrap();
+
+        assert!(provider.load_key("../escape").await.is_err());
+        assert!(provider.store_key("", &generate_key()).await.is_err());
+    }
+
+    #[test]
+    fn test_env_var_names() {
+        assert_eq!(EnvKeyProvider::var_name(KEY_ID_TWO_FACTOR), "TWO_FACTOR_ENCRYPTION_KEY");
+        assert_eq!(EnvKeyProvider::var_name(KEY_ID_PAYMENT_SIGNING), "DMPOOL_KEY_PAYMENT_SIGNING");
+    }
+}
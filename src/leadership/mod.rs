@@ -0,0 +1,362 @@
+// Leadership Module for DMPool
+// Active/standby coordination between instances sharing one database
+//
+// Every instance competes for a Postgres session advisory lock held on a
+// dedicated connection. The holder is the leader and runs the singleton
+// duties (payout scheduling, scheduled backups and restore drills, alert
+// evaluation); the others stand by and retry every check interval. The lock
+// is released with the leader's session, so a crashed leader is replaced
+// within one interval of its connection closing. A leader that cannot reach
+// the database steps down at once rather than risk a second leader.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio_postgres::{Client, NoTls};
+use tracing::{info, warn};
+
+/// Advisory lock key of the leader lease ("dmpoolL")
+pub const LEADER_LOCK_KEY: i64 = 0x646d_706f_6f6c_4c;
+
+/// `[dmpool.leader]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderConfig {
+    /// Off: this instance is always the leader
+    pub enabled: bool,
+    /// Name reported in health checks (default: $HOSTNAME, else a random id)
+    pub instance_id: Option<String>,
+    /// How often standbys try to take over and the leader confirms its lock
+    pub check_interval_secs: u64,
+}
+
+impl Default for LeaderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            instance_id: None,
+            check_interval_secs: 5,
+        }
+    }
+}
+
+impl LeaderConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be positive"));
+        }
+        if self.instance_id.as_deref().is_some_and(|id| id.trim().is_empty()) {
+            return Err(anyhow::anyhow!("instance_id must not be empty"));
+        }
+        Ok(())
+    }
+
+    fn instance_id(&self) -> String {
+        self.instance_id.clone()
+            .or_else(|| std::env::var("HOSTNAME").ok().filter(|host| !host.is_empty()))
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    }
+}
+
+/// A lease only one instance can hold at a time
+#[async_trait]
+pub trait LeaseBackend: Send + Sync {
+    /// Take the lease, or confirm it is still held; `false` while another instance holds it
+    async fn acquire(&self) -> Result<bool>;
+
+    /// Give the lease up
+    async fn release(&self);
+}
+
+/// Open connection and whether it holds the lock
+struct Session {
+    client: Client,
+    held: bool,
+}
+
+/// Lease backed by a session-level `pg_try_advisory_lock`
+///
+/// Uses its own connection rather than the pool: pooled connections are
+/// recycled, which would silently drop or leak the lock.
+pub struct AdvisoryLockLease {
+    url: String,
+    key: i64,
+    session: Mutex<Option<Session>>,
+}
+
+impl AdvisoryLockLease {
+    pub fn new(url: impl Into<String>, key: i64) -> Self {
+        Self { url: url.into(), key, session: Mutex::new(None) }
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let (client, connection) = tokio_postgres::connect(&self.url, NoTls).await
+            .context("Failed to open leader lock connection")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Leader lock connection closed: {}", e);
+            }
+        });
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl LeaseBackend for AdvisoryLockLease {
+    async fn acquire(&self) -> Result<bool> {
+        let mut slot = self.session.lock().await;
+        let mut session = match slot.take() {
+            Some(session) if !session.client.is_closed() => session,
+            _ => Session { client: self.connect().await?, held: false },
+        };
+
+        let held = if session.held {
+            // The lock lives as long as the session; make sure it still does
+            session.client.simple_query("SELECT 1").await.map(|_| true)
+        } else {
+            session.client.query_one("SELECT pg_try_advisory_lock($1)", &[&self.key]).await
+                .map(|row| row.get::<_, bool>(0))
+        };
+        // On error the session is dropped, which also releases any lock it held
+        session.held = held.context("Leader lock query failed")?;
+        let held = session.held;
+        *slot = Some(session);
+        Ok(held)
+    }
+
+    async fn release(&self) {
+        let Some(session) = self.session.lock().await.take() else {
+            return;
+        };
+        if !session.held {
+            return;
+        }
+        if let Err(e) = session.client.execute("SELECT pg_advisory_unlock($1)", &[&self.key]).await {
+            warn!("Failed to release leader lock: {}", e);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Leader,
+    Standby,
+}
+
+/// Leadership as reported in health checks
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeadershipStatus {
+    /// False when coordination is off and this instance always leads
+    pub enabled: bool,
+    pub instance_id: String,
+    pub role: Role,
+    pub leader_since: Option<DateTime<Utc>>,
+    pub last_check: Option<DateTime<Utc>>,
+    /// Why the last check failed, if it did
+    pub last_error: Option<String>,
+    /// Times this instance became leader or stepped down
+    pub transitions: u64,
+}
+
+/// This instance's leadership, kept current by `spawn`
+pub struct Leadership {
+    lease: Option<Arc<dyn LeaseBackend>>,
+    check_interval: Duration,
+    leader: AtomicBool,
+    status: RwLock<LeadershipStatus>,
+}
+
+impl Leadership {
+    /// Always the leader (coordination off)
+    pub fn standalone(instance_id: impl Into<String>) -> Self {
+        Self {
+            lease: None,
+            check_interval: Duration::from_secs(LeaderConfig::default().check_interval_secs),
+            leader: AtomicBool::new(true),
+            status: RwLock::new(LeadershipStatus {
+                enabled: false,
+                instance_id: instance_id.into(),
+                role: Role::Leader,
+                leader_since: Some(Utc::now()),
+                last_check: None,
+                last_error: None,
+                transitions: 0,
+            }),
+        }
+    }
+
+    /// Compete for `lease`; standby until the first check wins it
+    pub fn new(config: &LeaderConfig, lease: Arc<dyn LeaseBackend>) -> Self {
+        Self {
+            lease: Some(lease),
+            check_interval: Duration::from_secs(config.check_interval_secs.max(1)),
+            leader: AtomicBool::new(false),
+            status: RwLock::new(LeadershipStatus {
+                enabled: true,
+                instance_id: config.instance_id(),
+                role: Role::Standby,
+                leader_since: None,
+                last_check: None,
+                last_error: None,
+                transitions: 0,
+            }),
+        }
+    }
+
+    /// Advisory lock leadership on `database_url` when enabled, standalone otherwise
+    pub fn from_config(config: &LeaderConfig, database_url: &str) -> Self {
+        if config.enabled {
+            Self::new(config, Arc::new(AdvisoryLockLease::new(database_url, LEADER_LOCK_KEY)))
+        } else {
+            Self::standalone(config.instance_id())
+        }
+    }
+
+    /// Whether singleton duties should run on this instance
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::SeqCst)
+    }
+
+    pub async fn status(&self) -> LeadershipStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Try to take or keep the lease once; returns whether this instance leads
+    pub async fn check(&self) -> bool {
+        let Some(lease) = &self.lease else {
+            return true;
+        };
+        let (held, error) = match lease.acquire().await {
+            Ok(held) => (held, None),
+            Err(e) => (false, Some(format!("{:#}", e))),
+        };
+
+        let mut status = self.status.write().await;
+        let was_leader = self.leader.swap(held, Ordering::SeqCst);
+        let now = Utc::now();
+        if held && !was_leader {
+            info!("Instance {} is now the leader", status.instance_id);
+            status.leader_since = Some(now);
+            status.transitions += 1;
+        } else if !held && was_leader {
+            warn!("Instance {} stepped down to standby: {}", status.instance_id, error.as_deref().unwrap_or("lease lost"));
+            status.leader_since = None;
+            status.transitions += 1;
+        }
+        status.role = if held { Role::Leader } else { Role::Standby };
+        status.last_check = Some(now);
+        status.last_error = error;
+        held
+    }
+
+    /// Release the lease so a standby takes over without waiting for the session to time out
+    pub async fn step_down(&self) {
+        if let Some(lease) = &self.lease {
+            self.leader.store(false, Ordering::SeqCst);
+            lease.release().await;
+            let mut status = self.status.write().await;
+            status.role = Role::Standby;
+            status.leader_since = None;
+            info!("Instance {} released leadership", status.instance_id);
+        }
+    }
+
+    /// Check the lease every interval
+    pub fn spawn(self: Arc<Self>) -> Option<JoinHandle<()>> {
+        self.lease.as_ref()?;
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.check_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                self.check().await;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One lock shared by several instances; an instance whose connection is down loses it
+    struct FakeLease {
+        lock: Arc<std::sync::Mutex<Option<u32>>>,
+        id: u32,
+        up: AtomicBool,
+    }
+
+    #[async_trait]
+    impl LeaseBackend for FakeLease {
+        async fn acquire(&self) -> Result<bool> {
+            let mut lock = self.lock.lock().unwrap();
+            if !self.up.load(Ordering::SeqCst) {
+                if *lock == Some(self.id) {
+                    *lock = None;
+                }
+                return Err(anyhow::anyhow!("connection refused"));
+            }
+            Ok(*lock.get_or_insert(self.id) == self.id)
+        }
+
+        async fn release(&self) {
+            let mut lock = self.lock.lock().unwrap();
+            if *lock == Some(self.id) {
+                *lock = None;
+            }
+        }
+    }
+
+    fn instance(lock: &Arc<std::sync::Mutex<Option<u32>>>, id: u32) -> (Arc<FakeLease>, Leadership) {
+        let lease = Arc::new(FakeLease { lock: lock.clone(), id, up: AtomicBool::new(true) });
+        let config = LeaderConfig { enabled: true, instance_id: Some(format!("pool-{}", id)), ..Default::default() };
+        (lease.clone(), Leadership::new(&config, lease))
+    }
+
+    #[tokio::test]
+    async fn test_standby_takes_over_when_leader_fails() {
+        let lock = Arc::new(std::sync::Mutex::new(None));
+        let (lease_a, a) = instance(&lock, 1);
+        let (_, b) = instance(&lock, 2);
+
+        assert!(!a.is_leader());
+        assert!(a.check().await);
+        assert!(!b.check().await);
+        assert_eq!(b.status().await.role, Role::Standby);
+
+        // The leader loses the database: it steps down and its lock goes with the session
+        lease_a.up.store(false, Ordering::SeqCst);
+        assert!(!a.check().await);
+        let status = a.status().await;
+        assert_eq!((status.role, status.transitions), (Role::Standby, 2));
+        assert!(status.last_error.unwrap().contains("connection refused"));
+
+        assert!(b.check().await);
+        let status = b.status().await;
+        assert_eq!((status.instance_id.as_str(), status.role), ("pool-2", Role::Leader));
+        assert!(status.leader_since.is_some());
+
+        // Recovered, the old leader stays on standby
+        lease_a.up.store(true, Ordering::SeqCst);
+        assert!(!a.check().await);
+
+        b.step_down().await;
+        assert!(!b.is_leader());
+        assert!(a.check().await);
+    }
+
+    #[tokio::test]
+    async fn test_standalone_always_leads() {
+        let leadership = Leadership::from_config(&LeaderConfig::default(), "postgresql://unused");
+        assert!(leadership.is_leader());
+        assert!(leadership.check().await);
+        assert!(Arc::new(leadership).spawn().is_none());
+    }
+}
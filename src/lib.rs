@@ -3,35 +3,118 @@
 // This library provides shared functionality for the DMPool Bitcoin mining pool
 // a derivative of Hydrapool by 256 Foundation.
 
+pub mod accounts;
 pub mod alert;
+pub mod announcements;
+pub mod api_tokens;
 pub mod admin_api;
+pub mod app;
 pub mod auth;
 pub mod audit;
+pub mod backfill;
 pub mod backup;
 pub mod bitcoin;
+pub mod block_events;
+pub mod clickhouse;
 pub mod config;
 pub mod config_mgt;
 pub mod confirmation;
 pub mod db;
+pub mod disk;
+pub mod earnings;
+pub mod error;
+pub mod events;
+pub mod explorer;
+pub mod firehose;
 pub mod health;
+pub mod http_compression;
+pub mod http_metrics;
+pub mod keys;
+pub mod leadership;
+pub mod logging;
+pub mod loyalty;
+pub mod luck;
+pub mod maintenance;
+pub mod miner_notify;
 pub mod observer_api;
+pub mod ownership;
+pub mod persist;
 pub mod payment;
 pub mod pplns_validator;
+pub mod pplns_window;
+pub mod preflight;
 pub mod rate_limit;
+pub mod referrals;
+pub mod reconciliation;
+pub mod retention;
+pub mod revenue;
+pub mod runtime_metrics;
+pub mod service_auth;
+pub mod share_quality;
+pub mod share_validation;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+pub mod sla;
+pub mod solo;
+pub mod storage;
+pub mod stratum_stats;
 pub mod two_factor;
+pub mod vardiff;
+pub mod worker_status;
+pub mod worker_tags;
 
-pub use alert::{AlertManager, AlertConfig, AlertRule, AlertChannel, AlertLevel, AlertCondition, Alert};
-pub use auth::{AuthManager, Claims, User, UserInfo, LoginRequest, LoginResponse, PasswordValidation, validate_password_strength};
+pub use accounts::{AccountManager, MinerAccount, AccountStats, OwnershipVerifier};
+pub use alert::{AlertManager, AlertConfig, AlertRule, AlertChannel, AlertLevel, AlertCondition, Alert, HashrateAnomalyDetector, DeliveryQueue, DeliveryPolicy, DeadLetter, DeliveryStats, MessageTemplate, MessageFormat, AuditPattern};
+pub use api_tokens::{MinerTokenManager, MinerApiToken, IssuedToken, TokenChallenge};
+pub use auth::{AuthManager, Claims, User, UserInfo, UserSummary, LoginRequest, LoginResponse, LoginAttempt, LoginMonitor, SuspiciousLogin, OidcClient, OidcSettings, PasswordValidation, validate_password_strength};
+pub use app::{AppContext, AppContextBuilder, DmpoolConfig};
 pub use audit::{AuditLogger, AuditLog, AuditFilter, AuditStats};
-pub use backup::{BackupManager, BackupConfig, BackupMetadata, BackupStats};
-pub use bitcoin::{BitcoinRpcClient, BlockchainInfo, MempoolInfo, DecodedTransaction, TxInput, TxOutput, WalletInfo, UnspentOutput};
-pub use config_mgt::{ConfigManager, ConfigVersion, ConfigDiff, ScheduledChange, ConfigSchema};
+pub use backfill::{BackfillManager, BackfillRequest, BackfillProgress, BackfillStatus, ShareSource};
+pub use backup::{BackupManager, BackupConfig, BackupMetadata, BackupStats, BackupCatalog, BackupLocation, CatalogEntry, DrillResult, RestoreProbe, StoreProbe};
+pub use bitcoin::{BitcoinRpcClient, BlockchainInfo, CoinbaseVout, MempoolInfo, DecodedTransaction, TxInput, TxOutput, WalletInfo, UnspentOutput, WalletTransaction};
+pub use block_events::{BlockAnnouncer, BlockAnnouncement, BlockFoundEvent, BlockPayout, BlockRecorder};
+pub use clickhouse::{ClickHouseConfig, ClickHouseStore, HashrateHistorySource};
+pub use config_mgt::{ConfigManager, ConfigVersion, ConfigDiff, ScheduledChange, ConfigSchema, ImpactReport, PayoutImpact, ConfigCanary, CanaryConfig, CanaryReport, ConfigSigner, SignatureStatus, ConfigProfile, Promotion};
 pub use confirmation::{ConfigConfirmation, ConfigChangeRequest, RiskLevel, ConfigMeta};
-pub use db::{DatabaseManager, PoolStats, MinerStats, BlockInfo, BlockDetail};
+pub use db::{DatabaseManager, PoolStats, MinerStats, BlockInfo, BlockDetail, AddressEarning};
+pub use disk::{DiskConfig, DiskMonitor, DiskStatus, DiskLevel, DiskUsage, DiskProbe, PathStatus, PoolActions, ProtectiveActions};
+pub use earnings::{EarningsEstimator, EarningsProjection, NetworkConditions};
+pub use error::{DmpoolError, ErrorKind, PaymentError, AuthError, BackupError, DbError, kind_of};
+pub use events::{EventBus, EventHandler, PoolEvent, WebhookForwarder};
+pub use explorer::{ExplorerConfig, ExplorerLinks, ExplorerProvider, ExplorerTemplates};
+pub use firehose::{BatchSettings, FirehoseBackend, FirehoseConfig, FirehoseExporter, FirehoseSink, FirehoseStats, ShareRecord};
 pub use health::{HealthChecker, HealthStatus, ComponentStatus};
+pub use http_compression::CompressionConfig;
+pub use http_metrics::{HttpMetrics, HttpMetricsConfig, HttpMetricsSummary, SloTarget};
+pub use keys::{KeyProvider, EnvKeyProvider, SealedFileKeyProvider, KeyringKeyProvider, VaultKeyProvider, provider_from_env};
+pub use leadership::{Leadership, LeaderConfig, LeadershipStatus, LeaseBackend, AdvisoryLockLease, Role};
+pub use logging::{LogControl, LogFilterStatus, LogFormat};
+pub use loyalty::{LoyaltyTracker, LoyaltyConfig, LoyaltyTier, LoyaltyStatus, FeeDiscounts};
+pub use luck::{LuckPeriod, LuckStats};
+pub use maintenance::{MaintenanceConfig, ReadOnlyMode, ReadOnlyStatus, read_only_middleware};
+pub use miner_notify::{BroadcastReport, MinerNotifier, MinerNotifierConfig, NotificationPreferences, PayoutNotice, SmtpConfig};
+pub use announcements::{Announcement, AnnouncementBoard, AnnouncementConfig, AnnouncementSeverity, NewAnnouncement};
 pub use observer_api::{self, ObserverState};
-pub use payment::{PaymentManager, PaymentConfig, Payout, PayoutStatus, MinerBalance, PaymentStats};
-pub use pplns_validator::{PplnsSimulator, PayoutCalculation, PplnsValidationResult, ScenarioResult};
-pub use rate_limit::{RateLimiterState, RateLimitConfig, extract_client_ip};
-pub use two_factor::{TwoFactorManager, TwoFactorSetup, TwoFactorVerify, TwoFactorEnable, TwoFactorStatus, TwoFactorLogin};
+pub use ownership::{Bip322Verifier, OwnershipManager, OwnershipChallenge, VerifiedAddress, SignatureFormat};
+pub use payment::{PaymentManager, PaymentConfig, Payout, PayoutStatus, PayoutIntent, IntentState, MinerBalance, CreditedBlock, PaymentStats, PayoutPlan, plan_payout_run, PayoutRecorder, PayoutStatsBucket, PayoutStatsInterval, WalletTierConfig, WalletTiers, PayoutApprovals, PayoutApprovalConfig, PayoutSource, ImportOptions, ImportReport, PayoutFilter, PayoutPage, PayoutHistory, NetworkParams, CoinbasePayoutConfig, CoinbasePlan, CoinbasePlanner, PayoutPause, PayoutPauseStatus, WalletMonitor, WalletMonitorConfig, WalletCoverage};
+pub use pplns_validator::{PplnsSimulator, PayoutCalculation, PplnsValidationResult, RoundingPolicy, ScenarioResult, SimulationRequest, SimulationReport, SimulationShares, SyntheticMiner};
+pub use pplns_window::{PplnsWindow, WindowSnapshot, MinerContribution};
+pub use preflight::{PreflightConfig, PreflightReport, CheckResult, CheckStatus};
+pub use rate_limit::{RateLimiterState, RateLimitConfig, RateLimitClass, extract_client_ip, StratumScorer, StratumGuardConfig, IpScore};
+pub use reconciliation::{Reconciler, ReconciliationReport, ReconciliationEntry, ReconciliationStatus};
+pub use referrals::{ReferralProgram, ReferralConfig, ReferralCode, ReferralLink, ReferralCredit, ReferralSummary, LinkSource};
+pub use retention::{RetentionManager, RetentionConfig, RetentionPolicy, RetentionRun, Dataset, DatasetPurge, PurgeMode, MinerPurgeRequest, PurgeReport};
+pub use revenue::{RevenueLedger, RevenueRecorder, LedgerEntry, LedgerKind, RevenueSummary, RevenueProjection, SummaryPeriod};
+pub use runtime_metrics::{RuntimeMetrics, RuntimeSnapshot, TokioMetrics, MemoryFootprint, MemoryReporter, ChannelSaturation};
+pub use service_auth::{ServiceAuth, ServiceAuthConfig, ServiceClient, ServiceIdentity, ServiceKey, service_auth_middleware};
+pub use share_quality::{ShareQualityTracker, ShareOutcome, ShareCounts, ConnectionQuality};
+pub use share_validation::{ShareValidator, ShareValidationConfig, ShareSubmission, ShareViolation, ViolationCounts, WorkerViolations, MinerBanStore};
+pub use sla::{SlaTracker, SlaConfig, SlaPeriod, SlaReport, DowntimeWindow};
+pub use solo::{SoloManager, SoloConfig, SoloMinerStats, SoloBlock, SoloStatsSummary};
+pub use storage::{BlobStore, FileStore, MemoryStore, PostgresStore, StorageBackend, StorageConfig};
+pub use stratum_stats::{StratumStats, StratumSample, LiveStats, JobFreshness, JobFreshnessConfig, JobFreshnessStatus};
+pub use two_factor::{TwoFactorManager, TwoFactorSetup, TwoFactorVerify, TwoFactorEnable, TwoFactorStatus, TwoFactorLogin, TwoFactorLockout};
+pub use vardiff::{AdvisorSettings, DifficultyStatus, WorkerDifficultyAdvice, PoolDifficultyAdvice, VardiffReport};
+pub use worker_status::{WorkerStatusTracker, WorkerStatusConfig, WorkerStatusStore, WorkerUpdate, FlushReport};
+pub use worker_tags::{WorkerTags, WorkerFilter, TaggedWorker, WorkerGroup, WorkerGrouping};
 
@@ -0,0 +1,158 @@
+// Logging Control Module for DMPool
+// Log output setup, a runtime-adjustable filter and per-request correlation IDs
+//
+// Filters use the `RUST_LOG` directive syntax, e.g. `info,dmpool::payment=debug`.
+// Changing them takes effect for every subsequent event without a restart.
+// Output is human-readable text or one JSON object per line for log aggregation.
+
+pub mod request_id;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Filter used when nothing else is configured
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Log line format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with span fields (such as `request_id`)
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(anyhow::anyhow!("Unknown log format: {} (expected text or json)", other)),
+        }
+    }
+}
+
+/// Current filter and when it was last changed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogFilterStatus {
+    pub filter: String,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_by: Option<String>,
+}
+
+/// Parse filter directives without installing them
+pub fn parse_filter(directives: &str) -> Result<EnvFilter> {
+    let directives = directives.trim();
+    if directives.is_empty() {
+        return Err(anyhow::anyhow!("Log filter cannot be empty"));
+    }
+    EnvFilter::try_new(directives)
+        .with_context(|| format!("Invalid log filter: {}", directives))
+}
+
+/// Handle for changing the active log filter
+pub struct LogControl {
+    handle: reload::Handle<EnvFilter, Registry>,
+    status: RwLock<LogFilterStatus>,
+}
+
+impl LogControl {
+    /// Create the reloadable filter layer and its control handle
+    ///
+    /// The layer must sit directly on a `Registry`; `init` does this for
+    /// the usual stdout setup.
+    pub fn layer(directives: &str) -> Result<(reload::Layer<EnvFilter, Registry>, Self)> {
+        let filter = parse_filter(directives)?;
+        let (layer, handle) = reload::Layer::new(filter);
+        let control = Self {
+            handle,
+            status: RwLock::new(LogFilterStatus {
+                filter: directives.trim().to_string(),
+                updated_at: None,
+                updated_by: None,
+            }),
+        };
+        Ok((layer, control))
+    }
+
+    /// Install a stdout subscriber with a reloadable filter as the global default
+    pub fn init(directives: &str, format: LogFormat) -> Result<Arc<Self>> {
+        let (layer, control) = Self::layer(directives)?;
+        let text = (format == LogFormat::Text).then(tracing_subscriber::fmt::layer);
+        let json = (format == LogFormat::Json).then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(false)
+        });
+        tracing_subscriber::registry()
+            .with(layer)
+            .with(text)
+            .with(json)
+            .try_init()
+            .context("Failed to install tracing subscriber")?;
+        Ok(Arc::new(control))
+    }
+
+    /// Active filter
+    pub fn status(&self) -> LogFilterStatus {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the active filter, returning the previous one
+    ///
+    /// Invalid directives are rejected and the current filter stays in place.
+    pub fn set_filter(&self, directives: &str, updated_by: &str) -> Result<String> {
+        let filter = parse_filter(directives)?;
+        self.handle.reload(filter)
+            .context("Failed to reload log filter")?;
+
+        let mut status = self.status.write().unwrap_or_else(|e| e.into_inner());
+        let previous = std::mem::replace(&mut status.filter, directives.trim().to_string());
+        status.updated_at = Some(chrono::Utc::now());
+        status.updated_by = Some(updated_by.to_string());
+        Ok(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter() {
+        assert!(parse_filter("info").is_ok());
+        assert!(parse_filter("warn,dmpool::payment=debug").is_ok());
+        assert!(parse_filter("  ").is_err());
+        assert!(parse_filter("dmpool=verbose").is_err());
+
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("logfmt".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_set_filter_reloads() {
+        let (layer, control) = LogControl::layer("warn").unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+
+            assert_eq!(control.set_filter("debug", "admin").unwrap(), "warn");
+            tracing::callsite::rebuild_interest_cache();
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+
+            // A bad filter leaves the current one active
+            assert!(control.set_filter("dmpool=loud", "admin").is_err());
+            assert_eq!(control.status().filter, "debug");
+            assert_eq!(control.status().updated_by.as_deref(), Some("admin"));
+        });
+    }
+}
@@ -0,0 +1,98 @@
+// Request correlation IDs
+//
+// Every API request gets an ID, reused from an incoming X-Request-Id header
+// when it is well-formed and generated otherwise. The ID is recorded on a
+// tracing span around the handler, is available to audit entries and
+// outgoing RPC calls through `current_request_id`, and is returned in the
+// response's X-Request-Id header.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use tracing::Instrument;
+
+/// Header carrying the correlation ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest accepted incoming ID (the admin_audit_logs.request_id column width)
+const MAX_REQUEST_ID_LEN: usize = 100;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Correlation ID of the request being handled, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Accept caller-supplied IDs that are short and header/log safe
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Assign a correlation ID and run the request inside its span
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let request_id = req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(req).instrument(span)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request as HttpRequest, routing::get, Router};
+    use http_body_util::BodyExt;
+    use tower::Service;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(|| async { current_request_id().unwrap_or_default() }))
+            .layer(axum::middleware::from_fn(request_id_middleware))
+    }
+
+    async fn send(request_id: Option<&str>) -> (String, String) {
+        let mut request = HttpRequest::builder().uri("/");
+        if let Some(id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app().call(request.body(Body::empty()).unwrap()).await.unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_generated_id_reaches_handler_and_response() {
+        let (header, seen) = send(None).await;
+        assert_eq!(header, seen);
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[tokio::test]
+    async fn test_incoming_id_reused_when_valid() {
+        assert_eq!(send(Some("lb-7f3a:42")).await, ("lb-7f3a:42".to_string(), "lb-7f3a:42".to_string()));
+
+        let (header, _) = send(Some("bad id; drop table")).await;
+        assert_ne!(header, "bad id; drop table");
+        let (header, _) = send(Some(&"a".repeat(MAX_REQUEST_ID_LEN + 1))).await;
+        assert_eq!(header.len(), 36);
+    }
+}
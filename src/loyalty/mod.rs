@@ -0,0 +1,346 @@
+// Loyalty Module for DMPool
+// Share-based loyalty tiers and pool fee discounts
+//
+// Shares are read from the share store on an interval. Each miner has a
+// streak, the time it has been submitting shares without a gap longer than
+// `max_gap_secs`, and the total difficulty it has contributed. A miner reaches
+// a tier when both its streak and its total difficulty meet the tier's
+// minimums; the tier's discount is rebated from the pool fee when blocks are
+// split. State is kept in `loyalty/miners.json`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::backfill::ShareSource;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Shares read from the store per fetch
+const INGEST_BATCH: usize = 100_000;
+
+/// Fee discount in basis points by miner address
+pub type FeeDiscounts = HashMap<String, u32>;
+
+/// A loyalty tier and what it takes to reach it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoyaltyTier {
+    pub name: String,
+    /// Days of mining without a gap longer than `max_gap_secs`
+    #[serde(default)]
+    pub min_streak_days: u32,
+    /// Total difficulty contributed since the miner was first seen
+    #[serde(default)]
+    pub min_difficulty: u64,
+    /// Taken off the pool fee, capped at the fee itself
+    pub fee_discount_bps: u32,
+}
+
+/// The `[dmpool.loyalty]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoyaltyConfig {
+    pub enabled: bool,
+    /// Seconds between reads of new shares
+    pub update_interval_secs: u64,
+    /// A longer pause between shares restarts the streak
+    pub max_gap_secs: u64,
+    /// From the lowest tier to the highest
+    pub tiers: Vec<LoyaltyTier>,
+}
+
+impl Default for LoyaltyConfig {
+    fn default() -> Self {
+        let tier = |name: &str, min_streak_days, min_difficulty, fee_discount_bps| LoyaltyTier {
+            name: name.to_string(),
+            min_streak_days,
+            min_difficulty,
+            fee_discount_bps,
+        };
+        Self {
+            enabled: false,
+            update_interval_secs: 300,
+            max_gap_secs: 3600,
+            tiers: vec![
+                tier("bronze", 7, 0, 10),
+                tier("silver", 30, 0, 25),
+                tier("gold", 90, 0, 50),
+            ],
+        }
+    }
+}
+
+impl LoyaltyConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.update_interval_secs == 0 {
+            return Err(anyhow::anyhow!("update_interval_secs must be positive"));
+        }
+        if self.max_gap_secs < 60 {
+            return Err(anyhow::anyhow!("max_gap_secs must be at least 60"));
+        }
+        if self.tiers.is_empty() {
+            return Err(anyhow::anyhow!("at least one tier is required"));
+        }
+        let mut names = HashSet::new();
+        for tier in &self.tiers {
+            if tier.name.trim().is_empty() || !names.insert(tier.name.as_str()) {
+                return Err(anyhow::anyhow!("tier names must be non-empty and unique"));
+            }
+            if tier.fee_discount_bps > 10000 {
+                return Err(anyhow::anyhow!("tier {} fee_discount_bps must be <= 10000", tier.name));
+            }
+        }
+        for pair in self.tiers.windows(2) {
+            if pair[1].min_streak_days < pair[0].min_streak_days || pair[1].min_difficulty < pair[0].min_difficulty {
+                return Err(anyhow::anyhow!("tier {} must not require less than tier {}", pair[1].name, pair[0].name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A miner's streak and contribution
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerLoyalty {
+    pub streak_started_at: DateTime<Utc>,
+    pub last_share_at: DateTime<Utc>,
+    pub total_difficulty: u64,
+}
+
+/// A miner's loyalty as shown in its stats
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoyaltyStatus {
+    /// Zero once the miner has been idle longer than the allowed gap
+    pub streak_days: f64,
+    pub streak_started_at: Option<DateTime<Utc>>,
+    pub total_difficulty: u64,
+    pub tier: Option<String>,
+    pub fee_discount_bps: u32,
+    /// The tier above the current one, with its requirements
+    pub next_tier: Option<LoyaltyTier>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct LoyaltyFile {
+    /// Shares up to this n_time have been counted
+    cursor: Option<u64>,
+    miners: HashMap<String, MinerLoyalty>,
+}
+
+/// Tracks miner streaks and contributed difficulty
+pub struct LoyaltyTracker {
+    config: LoyaltyConfig,
+    data_dir: PathBuf,
+    state: RwLock<LoyaltyFile>,
+}
+
+impl LoyaltyTracker {
+    pub fn new(data_dir: PathBuf, config: LoyaltyConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create loyalty data directory")?;
+        Ok(Self {
+            config,
+            data_dir,
+            state: RwLock::new(LoyaltyFile::default()),
+        })
+    }
+
+    pub fn config(&self) -> &LoyaltyConfig {
+        &self.config
+    }
+
+    /// Load miner state from disk
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("miners.json");
+        let Some(state) = persist::read_json::<LoyaltyFile>(&path).await
+            .context("Failed to load loyalty state")?
+        else {
+            return Ok(());
+        };
+        info!("Loaded loyalty state for {} miners", state.miners.len());
+        *self.state.write().await = state;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let state = self.state.read().await;
+        persist::write_json(&self.data_dir.join("miners.json"), &*state).await
+            .context("Failed to write loyalty state")
+    }
+
+    /// Count a share towards a miner's streak and total difficulty
+    pub async fn record_share(&self, address: &str, difficulty: u64, at: DateTime<Utc>) {
+        let mut state = self.state.write().await;
+        self.record(&mut state.miners, address, difficulty, at);
+    }
+
+    fn record(&self, miners: &mut HashMap<String, MinerLoyalty>, address: &str, difficulty: u64, at: DateTime<Utc>) {
+        let miner = miners.entry(address.to_string()).or_insert_with(|| MinerLoyalty {
+            streak_started_at: at,
+            last_share_at: at,
+            total_difficulty: 0,
+        });
+        if (at - miner.last_share_at).num_seconds() > self.config.max_gap_secs as i64 {
+            miner.streak_started_at = at;
+        }
+        miner.last_share_at = miner.last_share_at.max(at);
+        miner.total_difficulty = miner.total_difficulty.saturating_add(difficulty);
+    }
+
+    /// Count shares stored since the last read, returning how many were counted
+    pub async fn ingest(&self, source: Arc<dyn ShareSource>, now: DateTime<Utc>) -> Result<usize> {
+        let end = now.timestamp().max(0) as u64;
+        let mut counted = 0;
+        loop {
+            let start = self.state.read().await.cursor.map_or(0, |cursor| cursor + 1);
+            if start > end {
+                return Ok(counted);
+            }
+            let fetch_source = source.clone();
+            let mut shares = tokio::task::spawn_blocking(move || fetch_source.fetch_shares(start, end, INGEST_BATCH)).await
+                .context("Share fetch task failed")?;
+            let full = shares.len() >= INGEST_BATCH;
+            // A full batch may have cut a second short, so stop before its last second
+            let latest = shares.iter().map(|share| share.n_time).max().unwrap_or(end);
+            let through = if full && latest > start { latest - 1 } else { end };
+            shares.retain(|share| share.n_time <= through);
+            shares.sort_by_key(|share| share.n_time);
+
+            let mut state = self.state.write().await;
+            for share in &shares {
+                let (Some(address), Some(at)) = (&share.btcaddress, DateTime::from_timestamp(share.n_time as i64, 0)) else {
+                    continue;
+                };
+                self.record(&mut state.miners, address, share.difficulty, at);
+                counted += 1;
+            }
+            state.cursor = Some(through);
+            if !full {
+                return Ok(counted);
+            }
+        }
+    }
+
+    /// Highest tier a miner has reached
+    fn tier_of(&self, miner: &MinerLoyalty, now: DateTime<Utc>) -> Option<usize> {
+        let streak_days = self.streak_days(miner, now);
+        self.config.tiers.iter()
+            .rposition(|tier| streak_days >= tier.min_streak_days as f64 && miner.total_difficulty >= tier.min_difficulty)
+    }
+
+    fn streak_days(&self, miner: &MinerLoyalty, now: DateTime<Utc>) -> f64 {
+        if (now - miner.last_share_at).num_seconds() > self.config.max_gap_secs as i64 {
+            return 0.0;
+        }
+        (miner.last_share_at - miner.streak_started_at).num_seconds().max(0) as f64 / 86_400.0
+    }
+
+    /// Loyalty of one miner, None if it has never been seen
+    pub async fn status(&self, address: &str, now: DateTime<Utc>) -> Option<LoyaltyStatus> {
+        let state = self.state.read().await;
+        let miner = state.miners.get(address)?;
+        let streak_days = self.streak_days(miner, now);
+        let tier = self.tier_of(miner, now);
+        let next = tier.map_or(0, |tier| tier + 1);
+        Some(LoyaltyStatus {
+            streak_days,
+            streak_started_at: (streak_days > 0.0).then_some(miner.streak_started_at),
+            total_difficulty: miner.total_difficulty,
+            tier: tier.map(|tier| self.config.tiers[tier].name.clone()),
+            fee_discount_bps: tier.map_or(0, |tier| self.config.tiers[tier].fee_discount_bps),
+            next_tier: self.config.tiers.get(next).cloned(),
+        })
+    }
+
+    /// Fee discounts of all miners that have reached a tier
+    pub async fn fee_discounts(&self, now: DateTime<Utc>) -> FeeDiscounts {
+        let state = self.state.read().await;
+        state.miners.iter()
+            .filter_map(|(address, miner)| {
+                let tier = &self.config.tiers[self.tier_of(miner, now)?];
+                (tier.fee_discount_bps > 0).then(|| (address.clone(), tier.fee_discount_bps))
+            })
+            .collect()
+    }
+
+    /// Read new shares on the configured interval
+    pub fn spawn(self: Arc<Self>, source: Arc<dyn ShareSource>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.config.update_interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.ingest(source.clone(), Utc::now()).await {
+                    warn!("Failed to update loyalty from shares: {:#}", e);
+                    continue;
+                }
+                if let Err(e) = self.save().await {
+                    warn!("Failed to save loyalty state: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use tempfile::TempDir;
+
+    fn tracker(temp_dir: &TempDir) -> LoyaltyTracker {
+        let config = LoyaltyConfig {
+            enabled: true,
+            tiers: vec![
+                LoyaltyTier { name: "bronze".to_string(), min_streak_days: 1, min_difficulty: 100, fee_discount_bps: 20 },
+                LoyaltyTier { name: "gold".to_string(), min_streak_days: 3, min_difficulty: 1000, fee_discount_bps: 50 },
+            ],
+            ..LoyaltyConfig::default()
+        };
+        config.validate().unwrap();
+        LoyaltyTracker::new(temp_dir.path().to_path_buf(), config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streak_and_tiers() {
+        let temp_dir = TempDir::new().unwrap();
+        let loyalty = tracker(&temp_dir);
+        let start = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+
+        // Four days of shares every 30 minutes
+        for step in 0..=(4 * 48) {
+            loyalty.record_share("bc1qa", 10, start + Duration::minutes(30 * step)).await;
+        }
+        // Enough difficulty, but the streak broke after a two-hour gap
+        loyalty.record_share("bc1qb", 5000, start).await;
+        loyalty.record_share("bc1qb", 10, start + Duration::days(2)).await;
+
+        let now = start + Duration::days(4);
+        let a = loyalty.status("bc1qa", now).await.unwrap();
+        assert_eq!(a.tier.as_deref(), Some("gold"));
+        assert_eq!(a.fee_discount_bps, 50);
+        assert!(a.next_tier.is_none());
+        let b = loyalty.status("bc1qb", start + Duration::days(2)).await.unwrap();
+        assert_eq!(b.tier, None);
+        assert_eq!(b.next_tier.unwrap().name, "bronze");
+
+        // Idle miners lose their streak
+        assert_eq!(loyalty.status("bc1qa", now + Duration::hours(2)).await.unwrap().tier, None);
+        assert_eq!(loyalty.fee_discounts(now).await, FeeDiscounts::from([("bc1qa".to_string(), 50)]));
+
+        loyalty.save().await.unwrap();
+        let reloaded = tracker(&temp_dir);
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.status("bc1qa", now).await.unwrap().total_difficulty, 1930);
+    }
+
+    #[test]
+    fn test_validate_tier_order() {
+        let mut config = LoyaltyConfig::default();
+        assert!(config.validate().is_ok());
+        config.tiers.swap(0, 2);
+        assert!(config.validate().is_err());
+    }
+}
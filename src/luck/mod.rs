@@ -0,0 +1,83 @@
+// Block Luck Module for DMPool
+// Per-block effort and rolling luck percentages
+//
+// Effort compares the difficulty in the PPLNS window when a block is found
+// with the network difficulty, i.e. the work expected on average to find one
+// block: 100% is exactly as expected, above 100% is unlucky. Luck over a
+// period is the number of blocks found per expected block, so a period with
+// blocks at 50% and 150% effort has 100% luck.
+
+use serde::{Deserialize, Serialize};
+
+/// Effort of one block as a percentage of the expected work
+///
+/// None when the network difficulty is unknown.
+pub fn block_effort_percent(window_difficulty: u64, network_difficulty: f64) -> Option<f64> {
+    if !network_difficulty.is_finite() || network_difficulty <= 0.0 {
+        return None;
+    }
+    Some(window_difficulty as f64 / network_difficulty * 100.0)
+}
+
+/// Luck of `blocks` blocks whose efforts add up to `effort_percent_sum`
+///
+/// None when no block with a known effort was found.
+pub fn luck_percent(blocks: u64, effort_percent_sum: f64) -> Option<f64> {
+    if blocks == 0 || effort_percent_sum <= 0.0 {
+        return None;
+    }
+    Some(blocks as f64 / effort_percent_sum * 10_000.0)
+}
+
+/// Luck over one period
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LuckPeriod {
+    /// Blocks with a known effort
+    pub blocks: u64,
+    pub average_effort_percent: Option<f64>,
+    pub luck_percent: Option<f64>,
+}
+
+impl LuckPeriod {
+    /// Period from a block count and the sum of their efforts
+    pub fn from_totals(blocks: u64, effort_percent_sum: f64) -> Self {
+        Self {
+            blocks,
+            average_effort_percent: (blocks > 0).then(|| effort_percent_sum / blocks as f64),
+            luck_percent: luck_percent(blocks, effort_percent_sum),
+        }
+    }
+}
+
+/// Rolling pool luck
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LuckStats {
+    pub last_7d: LuckPeriod,
+    pub last_30d: LuckPeriod,
+    pub all_time: LuckPeriod,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_effort() {
+        assert_eq!(block_effort_percent(50, 100.0), Some(50.0));
+        assert_eq!(block_effort_percent(300, 200.0), Some(150.0));
+        assert_eq!(block_effort_percent(300, 0.0), None);
+        assert_eq!(block_effort_percent(300, f64::NAN), None);
+    }
+
+    #[test]
+    fn test_luck_from_efforts() {
+        // One block at 50% and one at 150%: two blocks for two expected
+        let period = LuckPeriod::from_totals(2, 200.0);
+        assert_eq!(period.luck_percent, Some(100.0));
+        assert_eq!(period.average_effort_percent, Some(100.0));
+
+        // Three blocks in the work expected for one
+        assert_eq!(luck_percent(3, 100.0), Some(300.0));
+        assert_eq!(LuckPeriod::from_totals(0, 0.0), LuckPeriod::default());
+    }
+}
@@ -0,0 +1,350 @@
+// Maintenance Module for DMPool
+// Global read-only switch for migrations and restores
+//
+// While the pool is read-only the admin APIs reject every mutating request
+// (payouts, config changes, user management) with a 503 and a READ_ONLY error,
+// and the payment manager refuses to create payouts. Observer endpoints, health
+// checks and monitoring keep working. The switch lives in the database
+// (`maintenance_read_only`, migration 016) so flipping it from either admin API
+// applies to the pool and dmpool-admin alike; `[dmpool.maintenance] read_only`
+// turns it on at startup.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::db::DatabaseManager;
+use crate::error::DmpoolError;
+
+/// Path suffix of the toggle endpoint, which stays writable so the mode can be turned off
+pub const READ_ONLY_TOGGLE_PATH: &str = "/maintenance/read-only";
+
+/// Path suffix of the payout kill switch, which stays writable so payouts can be halted during maintenance
+pub const PAYOUT_KILL_SWITCH_PATH: &str = "/payments/kill-switch";
+
+/// Longest reason shown to blocked requests
+const MAX_REASON_LEN: usize = 200;
+
+/// The `[dmpool.maintenance]` settings
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Start in read-only mode
+    pub read_only: bool,
+    /// Why the pool is read-only, included in the error blocked requests get
+    pub reason: Option<String>,
+}
+
+impl MaintenanceConfig {
+    pub fn validate(&self) -> Result<()> {
+        validate_reason(self.reason.as_deref())
+    }
+}
+
+fn validate_reason(reason: Option<&str>) -> Result<()> {
+    if reason.is_some_and(|r| r.chars().count() > MAX_REASON_LEN) {
+        return Err(anyhow::anyhow!("reason must be at most {} characters", MAX_REASON_LEN));
+    }
+    Ok(())
+}
+
+/// Current read-only state
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadOnlyStatus {
+    pub read_only: bool,
+    pub reason: Option<String>,
+    /// Who last flipped the switch; none when it comes from the config
+    pub changed_by: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+/// Where the switch is kept (maintenance_read_only in production)
+#[async_trait]
+pub trait ReadOnlyStore: Send + Sync {
+    /// The stored switch, `None` if it was never flipped
+    async fn load_read_only(&self) -> Result<Option<ReadOnlyStatus>>;
+
+    async fn save_read_only(&self, status: &ReadOnlyStatus) -> Result<()>;
+}
+
+#[async_trait]
+impl ReadOnlyStore for DatabaseManager {
+    async fn load_read_only(&self) -> Result<Option<ReadOnlyStatus>> {
+        let conn = self.get_conn().await?;
+        let row = conn
+            .query_opt("SELECT read_only, reason, changed_by, changed_at FROM maintenance_read_only WHERE id", &[])
+            .await
+            .context("Failed to read read-only switch")?;
+        Ok(row.map(|row| ReadOnlyStatus {
+            read_only: row.get(0),
+            reason: row.get(1),
+            changed_by: row.get(2),
+            changed_at: row.get(3),
+        }))
+    }
+
+    async fn save_read_only(&self, status: &ReadOnlyStatus) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO maintenance_read_only (id, read_only, reason, changed_by, changed_at) VALUES (TRUE, $1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET read_only = $1, reason = $2, changed_by = $3, changed_at = $4",
+            &[&status.read_only, &status.reason, &status.changed_by, &status.changed_at],
+        )
+        .await
+        .context("Failed to write read-only switch")?;
+        Ok(())
+    }
+}
+
+/// Runtime read-only switch shared by the admin APIs and the payment manager
+pub struct ReadOnlyMode {
+    /// `[dmpool.maintenance]`, applied to the store by `load`
+    config: MaintenanceConfig,
+    store: Option<Arc<dyn ReadOnlyStore>>,
+    /// Last state read from or written to the store
+    status: RwLock<ReadOnlyStatus>,
+}
+
+impl ReadOnlyMode {
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        if config.read_only {
+            warn!("Starting in read-only maintenance mode");
+        }
+        Self {
+            config: config.clone(),
+            store: None,
+            status: RwLock::new(ReadOnlyStatus {
+                read_only: config.read_only,
+                reason: config.reason.clone(),
+                changed_by: None,
+                changed_at: None,
+            }),
+        }
+    }
+
+    /// Keep the switch in `store` so every process using it sees the same state
+    pub fn with_store(mut self, store: Arc<dyn ReadOnlyStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Reconcile with the store at startup: `read_only = true` in the config
+    /// turns the shared switch on, otherwise the stored state is kept
+    pub async fn load(&self) -> Result<ReadOnlyStatus> {
+        let Some(store) = &self.store else {
+            return Ok(self.status.read().await.clone());
+        };
+        if !self.config.read_only {
+            return self.refresh().await;
+        }
+        let status = self.status.read().await.clone();
+        store.save_read_only(&status).await?;
+        Ok(status)
+    }
+
+    /// Read the switch from the store
+    pub async fn refresh(&self) -> Result<ReadOnlyStatus> {
+        let Some(store) = &self.store else {
+            return Ok(self.status.read().await.clone());
+        };
+        let Some(status) = store.load_read_only().await? else {
+            return Ok(self.status.read().await.clone());
+        };
+        *self.status.write().await = status.clone();
+        Ok(status)
+    }
+
+    /// Current state, or the last known one if the store cannot be read
+    pub async fn status(&self) -> ReadOnlyStatus {
+        match self.refresh().await {
+            Ok(status) => status,
+            Err(e) => {
+                warn!("Failed to read read-only switch: {:#}", e);
+                self.status.read().await.clone()
+            }
+        }
+    }
+
+    pub async fn is_read_only(&self) -> bool {
+        self.status().await.read_only
+    }
+
+    /// Turn read-only mode on or off
+    pub async fn set(&self, read_only: bool, reason: Option<String>, changed_by: &str) -> Result<ReadOnlyStatus> {
+        validate_reason(reason.as_deref())
+            .map_err(|e| DmpoolError::InvalidInput(e.to_string()))?;
+        let status = ReadOnlyStatus {
+            read_only,
+            reason: if read_only { reason } else { None },
+            changed_by: Some(changed_by.to_string()),
+            changed_at: Some(Utc::now()),
+        };
+        if let Some(store) = &self.store {
+            store.save_read_only(&status).await?;
+        }
+        *self.status.write().await = status.clone();
+        if read_only {
+            warn!("Read-only maintenance mode enabled by {}", changed_by);
+        } else {
+            info!("Read-only maintenance mode disabled by {}", changed_by);
+        }
+        Ok(status)
+    }
+
+    /// Fail with a read-only error if `operation` may not run now
+    ///
+    /// Fails closed: if the switch cannot be read, nothing is written.
+    pub async fn ensure_writable(&self, operation: &str) -> Result<()> {
+        let status = self.refresh().await.map_err(|e| {
+            DmpoolError::ReadOnly(format!("Cannot {}: read-only switch unavailable ({:#})", operation, e))
+        })?;
+        if !status.read_only {
+            return Ok(());
+        }
+        let message = match &status.reason {
+            Some(reason) => format!("The pool is in read-only maintenance mode ({}); cannot {}", reason, operation),
+            None => format!("The pool is in read-only maintenance mode; cannot {}", operation),
+        };
+        Err(DmpoolError::ReadOnly(message).into())
+    }
+}
+
+/// Whether a request method changes state
+pub fn is_mutating(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Reject mutating requests while read-only, except the toggle itself and the payout kill switch
+pub async fn read_only_middleware(
+    State(mode): State<Arc<ReadOnlyMode>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    let exempt = path.ends_with(READ_ONLY_TOGGLE_PATH) || path.ends_with(PAYOUT_KILL_SWITCH_PATH);
+    if is_mutating(req.method()) && !exempt {
+        let operation = format!("{} {}", req.method(), req.uri().path());
+        if let Err(e) = mode.ensure_writable(&operation).await {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({
+                    "error": "READ_ONLY",
+                    "message": e.to_string(),
+                })),
+            ).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{kind_of, ErrorKind};
+    use axum::{body::Body, routing::{get, put}, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_toggle_and_error() {
+        let mode = ReadOnlyMode::new(&MaintenanceConfig::default());
+        assert!(mode.ensure_writable("create payout").await.is_ok());
+
+        let status = mode.set(true, Some("restoring backup".to_string()), "alice").await.unwrap();
+        assert!(status.read_only);
+        assert_eq!(status.changed_by.as_deref(), Some("alice"));
+        let err = mode.ensure_writable("create payout").await.unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::ReadOnly);
+        assert!(err.to_string().contains("(restoring backup); cannot create payout"));
+
+        // The reason is dropped when the mode is turned off
+        let status = mode.set(false, Some("ignored".to_string()), "alice").await.unwrap();
+        assert_eq!(status.reason, None);
+        assert!(!mode.is_read_only().await);
+        assert!(mode.set(true, Some("x".repeat(201)), "alice").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_blocks_only_writes() {
+        let mode = Arc::new(ReadOnlyMode::new(&MaintenanceConfig { read_only: true, reason: None }));
+        let router = Router::new()
+            .route("/api/payments", get(|| async { "ok" }).post(|| async { "created" }))
+            .route("/api/maintenance/read-only", get(|| async { "on" }).put(|| async { "toggled" }))
+            .route("/api/payments/kill-switch", put(|| async { "paused" }))
+            .layer(axum::middleware::from_fn_with_state(mode.clone(), read_only_middleware));
+
+        let call = |method: Method, uri: &str| {
+            let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+            router.clone().oneshot(request)
+        };
+        assert_eq!(call(Method::GET, "/api/payments").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(call(Method::POST, "/api/payments").await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(call(Method::PUT, "/api/maintenance/read-only").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(call(Method::PUT, "/api/payments/kill-switch").await.unwrap().status(), StatusCode::OK);
+
+        mode.set(false, None, "alice").await.unwrap();
+        assert_eq!(call(Method::POST, "/api/payments").await.unwrap().status(), StatusCode::OK);
+    }
+
+    /// Stands in for the shared database row
+    #[derive(Default)]
+    struct MemorySwitch {
+        row: std::sync::Mutex<Option<ReadOnlyStatus>>,
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl ReadOnlyStore for MemorySwitch {
+        async fn load_read_only(&self) -> Result<Option<ReadOnlyStatus>> {
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("connection refused"));
+            }
+            Ok(self.row.lock().unwrap().clone())
+        }
+
+        async fn save_read_only(&self, status: &ReadOnlyStatus) -> Result<()> {
+            *self.row.lock().unwrap() = Some(status.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_switch_is_shared_and_fails_closed() {
+        let row = Arc::new(MemorySwitch::default());
+        let admin = ReadOnlyMode::new(&MaintenanceConfig::default()).with_store(row.clone());
+        let pool = ReadOnlyMode::new(&MaintenanceConfig::default()).with_store(row.clone());
+        assert!(pool.ensure_writable("create payout").await.is_ok());
+
+        admin.set(true, Some("restoring backup".to_string()), "alice").await.unwrap();
+        let err = pool.ensure_writable("create payout").await.unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::ReadOnly);
+        assert_eq!(pool.status().await.changed_by.as_deref(), Some("alice"));
+
+        admin.set(false, None, "bob").await.unwrap();
+        assert!(pool.ensure_writable("create payout").await.is_ok());
+
+        // A process started with read_only = true turns the shared switch on
+        let config = MaintenanceConfig { read_only: true, reason: Some("migration".to_string()) };
+        ReadOnlyMode::new(&config).with_store(row.clone()).load().await.unwrap();
+        assert!(admin.is_read_only().await);
+        assert_eq!(admin.status().await.reason.as_deref(), Some("migration"));
+        admin.set(false, None, "bob").await.unwrap();
+
+        // An unreadable switch blocks writes but status keeps the last known state
+        row.down.store(true, std::sync::atomic::Ordering::SeqCst);
+        let err = pool.ensure_writable("create payout").await.unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::ReadOnly);
+        assert!(err.to_string().contains("read-only switch unavailable"));
+        assert!(!pool.status().await.read_only);
+    }
+}
@@ -0,0 +1,469 @@
+// Miner Notification Module for DMPool
+// Notifies miners of their own payouts by email, Telegram or webhook
+//
+// Miners store preferences for their address through the Observer API (with a
+// miner API token). The notifier subscribes to payout events on the event bus
+// and sends each affected miner the amount, txid and an explorer link. Pool
+// announcements are pushed to every miner who has not opted out.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::error::DmpoolError;
+use crate::events::{EventHandler, PoolEvent};
+use crate::explorer::ExplorerLinks;
+use crate::persist;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// SMTP server used for email notifications (STARTTLS)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub server: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub from_address: String,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Delivery settings shared by all miners
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MinerNotifierConfig {
+    /// Email is unavailable without SMTP
+    pub smtp: Option<SmtpConfig>,
+    /// Telegram is unavailable without a bot; miners give the chat ID they opened with it
+    pub telegram_bot_token: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where and when a miner wants to be notified
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub telegram_chat_id: Option<String>,
+    /// Must be https
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_true")]
+    pub payout_broadcast: bool,
+    #[serde(default = "default_true")]
+    pub payout_confirmed: bool,
+    /// Operator announcements such as fee changes and maintenance
+    #[serde(default = "default_true")]
+    pub announcements: bool,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NotificationPreferences {
+    /// Check contact details
+    pub fn validate(&self) -> Result<()> {
+        if self.email.is_none() && self.telegram_chat_id.is_none() && self.webhook_url.is_none() {
+            return Err(DmpoolError::InvalidInput("At least one of email, telegram_chat_id or webhook_url is required".to_string()).into());
+        }
+        if let Some(email) = &self.email {
+            if email.parse::<Mailbox>().is_err() {
+                return Err(DmpoolError::InvalidInput(format!("Invalid email address: {}", email)).into());
+            }
+        }
+        if let Some(chat_id) = &self.telegram_chat_id {
+            if chat_id.is_empty() || chat_id.len() > 64 {
+                return Err(DmpoolError::InvalidInput("Telegram chat ID must be 1-64 characters".to_string()).into());
+            }
+        }
+        if let Some(url) = &self.webhook_url {
+            // Plain http to arbitrary hosts would let anyone aim the pool at internal services
+            if !url.starts_with("https://") || url.len() > 2048 {
+                return Err(DmpoolError::InvalidInput("Webhook URL must be an https URL".to_string()).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn wants(&self, kind: &str) -> bool {
+        match kind {
+            "payout_broadcast" => self.payout_broadcast,
+            "payout_confirmed" => self.payout_confirmed,
+            "announcement" => self.announcements,
+            _ => false,
+        }
+    }
+}
+
+/// Payout notification sent to a miner (also the webhook body)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutNotice {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub payout_id: String,
+    pub address: String,
+    pub amount_satoshis: u64,
+    pub txid: Option<String>,
+    pub explorer_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirmations: Option<u32>,
+}
+
+impl PayoutNotice {
+    /// Notice for a payout event, None for other events
+    pub fn from_event(event: &PoolEvent, explorer: Option<&ExplorerLinks>) -> Option<Self> {
+        let (payout_id, address, amount_satoshis, txid, confirmations) = match event {
+            PoolEvent::PayoutBroadcast { payout_id, address, amount_satoshis, txid } => {
+                (payout_id, address, *amount_satoshis, txid, None)
+            }
+            PoolEvent::PayoutConfirmed { payout_id, address, amount_satoshis, txid, confirmations } => {
+                (payout_id, address, *amount_satoshis, txid, Some(*confirmations))
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            kind: event.kind().to_string(),
+            payout_id: payout_id.clone(),
+            address: address.clone(),
+            amount_satoshis,
+            explorer_url: txid.as_deref().zip(explorer).and_then(|(txid, explorer)| explorer.tx_url(txid)),
+            txid: txid.clone(),
+            confirmations,
+        })
+    }
+
+    pub fn subject(&self) -> String {
+        let amount = self.amount_satoshis as f64 / 100_000_000.0;
+        match self.confirmations {
+            Some(_) => format!("DMPool payout of {:.8} BTC confirmed", amount),
+            None => format!("DMPool payout of {:.8} BTC sent", amount),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let amount = self.amount_satoshis as f64 / 100_000_000.0;
+        let mut text = match self.confirmations {
+            Some(confirmations) => format!(
+                "Your payout of {:.8} BTC to {} has {} confirmations.",
+                amount, self.address, confirmations
+            ),
+            None => format!("A payout of {:.8} BTC to {} was broadcast.", amount, self.address),
+        };
+        if let Some(txid) = &self.txid {
+            text.push_str(&format!("\n\nTransaction: {}", txid));
+        }
+        if let Some(url) = &self.explorer_url {
+            text.push_str(&format!("\n{}", url));
+        }
+        text
+    }
+}
+
+/// Outcome of sending a notification to every interested miner
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BroadcastReport {
+    /// Miners at least one channel reached
+    pub delivered: usize,
+    /// Miners no channel reached
+    pub failed: usize,
+}
+
+/// Keeps miner preferences and delivers payout notifications
+pub struct MinerNotifier {
+    config: MinerNotifierConfig,
+    explorer: Option<Arc<ExplorerLinks>>,
+    data_dir: PathBuf,
+    preferences: RwLock<HashMap<String, NotificationPreferences>>,
+    client: reqwest::Client,
+}
+
+impl MinerNotifier {
+    /// Create a notifier persisting preferences to `data_dir`
+    pub fn new(data_dir: PathBuf, config: MinerNotifierConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create miner notification directory")?;
+
+        Ok(Self {
+            config,
+            explorer: None,
+            data_dir,
+            preferences: RwLock::new(HashMap::new()),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Link transactions in notifications
+    pub fn with_explorer(mut self, explorer: Arc<ExplorerLinks>) -> Self {
+        self.explorer = Some(explorer);
+        self
+    }
+
+    /// Load persisted preferences
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("preferences.json");
+        if let Some(preferences) = persist::read_json::<HashMap<String, NotificationPreferences>>(&path).await
+            .context("Failed to load notification preferences file")?
+        {
+            info!("Loaded notification preferences for {} miners", preferences.len());
+            *self.preferences.write().await = preferences;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, preferences: &HashMap<String, NotificationPreferences>) -> Result<()> {
+        persist::write_json(&self.data_dir.join("preferences.json"), preferences).await
+            .context("Failed to write notification preferences file")
+    }
+
+    /// Channels miners can choose from
+    pub fn channels(&self) -> Vec<&'static str> {
+        let mut channels = vec!["webhook"];
+        if self.config.smtp.is_some() {
+            channels.push("email");
+        }
+        if self.config.telegram_bot_token.is_some() {
+            channels.push("telegram");
+        }
+        channels
+    }
+
+    /// Preferences of an address
+    pub async fn get(&self, address: &str) -> Option<NotificationPreferences> {
+        self.preferences.read().await.get(address).cloned()
+    }
+
+    /// Replace the preferences of an address
+    pub async fn set(&self, address: &str, mut preferences: NotificationPreferences) -> Result<NotificationPreferences> {
+        preferences.validate()?;
+        if preferences.email.is_some() && self.config.smtp.is_none() {
+            return Err(DmpoolError::InvalidInput("Email notifications are not available on this pool".to_string()).into());
+        }
+        if preferences.telegram_chat_id.is_some() && self.config.telegram_bot_token.is_none() {
+            return Err(DmpoolError::InvalidInput("Telegram notifications are not available on this pool".to_string()).into());
+        }
+        preferences.updated_at = Utc::now();
+
+        let mut all = self.preferences.write().await;
+        all.insert(address.to_string(), preferences.clone());
+        self.save(&all).await?;
+
+        info!("Updated notification preferences for {}", address);
+        Ok(preferences)
+    }
+
+    /// Stop notifying an address; false if it had no preferences
+    pub async fn remove(&self, address: &str) -> Result<bool> {
+        let mut all = self.preferences.write().await;
+        if all.remove(address).is_none() {
+            return Ok(false);
+        }
+        self.save(&all).await?;
+        Ok(true)
+    }
+
+    /// Send a notification of `kind` to every miner who wants it
+    ///
+    /// `body` is posted to webhooks; email and Telegram get `subject` and `text`.
+    pub async fn broadcast(&self, kind: &str, subject: &str, text: &str, body: &serde_json::Value) -> BroadcastReport {
+        let recipients: Vec<(String, NotificationPreferences)> = self.preferences.read().await.iter()
+            .filter(|(_, preferences)| preferences.wants(kind))
+            .map(|(address, preferences)| (address.clone(), preferences.clone()))
+            .collect();
+
+        let mut report = BroadcastReport::default();
+        for (address, preferences) in recipients {
+            match self.send(&preferences, subject, text, body).await {
+                Ok(()) => report.delivered += 1,
+                Err(e) => {
+                    warn!("Failed to send {} to {}: {:#}", kind, address, e);
+                    report.failed += 1;
+                }
+            }
+        }
+        info!("Sent {} to {} miners ({} failed)", kind, report.delivered, report.failed);
+        report
+    }
+
+    /// Send on every channel in `preferences`, failing if any channel fails
+    async fn send(&self, preferences: &NotificationPreferences, subject: &str, text: &str, body: &serde_json::Value) -> Result<()> {
+        let mut failed = Vec::new();
+        if let Some(email) = &preferences.email {
+            if let Err(e) = self.send_email(email, subject, text).await {
+                failed.push(format!("email: {:#}", e));
+            }
+        }
+        if let Some(chat_id) = &preferences.telegram_chat_id {
+            if let Err(e) = self.send_telegram(chat_id, subject, text).await {
+                failed.push(format!("telegram: {:#}", e));
+            }
+        }
+        if let Some(url) = &preferences.webhook_url {
+            if let Err(e) = self.send_webhook(url, body).await {
+                failed.push(format!("webhook: {:#}", e));
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!(failed.join(", ")));
+        }
+        Ok(())
+    }
+
+    async fn send_email(&self, to: &str, subject: &str, text: &str) -> Result<()> {
+        let smtp = self.config.smtp.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("SMTP is not configured"))?;
+        let message = Message::builder()
+            .from(smtp.from_address.parse::<Mailbox>().context("Invalid SMTP from address")?)
+            .to(to.parse::<Mailbox>().context("Invalid email address")?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(text.to_string())
+            .context("Failed to build email")?;
+
+        let transport: AsyncSmtpTransport<Tokio1Executor> = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.server)
+            .context("Invalid SMTP server")?
+            .port(smtp.port)
+            .credentials(Credentials::new(smtp.username.clone(), smtp.password.clone()))
+            .build();
+        transport.send(message).await.context("Failed to send email")?;
+        Ok(())
+    }
+
+    async fn send_telegram(&self, chat_id: &str, subject: &str, text: &str) -> Result<()> {
+        let bot_token = self.config.telegram_bot_token.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Telegram bot is not configured"))?;
+        let response = self.client
+            .post(format!("https://api.telegram.org/bot{}/sendMessage", bot_token))
+            .json(&serde_json::json!({
+                "chat_id": chat_id,
+                "text": format!("{}\n\n{}", subject, text),
+            }))
+            .send()
+            .await
+            .context("Failed to send Telegram message")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Telegram API error: {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn send_webhook(&self, url: &str, body: &serde_json::Value) -> Result<()> {
+        let response = self.client.post(url).json(body).send().await
+            .context("Failed to send webhook")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Webhook error: {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for MinerNotifier {
+    fn name(&self) -> &str {
+        "miner_notifications"
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        matches!(kind, "payout_broadcast" | "payout_confirmed")
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        let Some(notice) = PayoutNotice::from_event(event, self.explorer.as_deref()) else {
+            return Ok(());
+        };
+        let Some(preferences) = self.get(&notice.address).await.filter(|p| p.wants(&notice.kind)) else {
+            return Ok(());
+        };
+
+        let body = serde_json::to_value(&notice).context("Failed to serialize payout notice")?;
+        self.send(&preferences, &notice.subject(), &notice.text(), &body).await
+            .with_context(|| format!("payout notification for {} failed", notice.address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explorer::ExplorerConfig;
+    use tempfile::TempDir;
+
+    fn preferences() -> NotificationPreferences {
+        NotificationPreferences {
+            email: None,
+            telegram_chat_id: None,
+            webhook_url: Some("https://miner.example/hook".to_string()),
+            payout_broadcast: true,
+            payout_confirmed: false,
+            announcements: true,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_payout_notice() {
+        let event = PoolEvent::PayoutConfirmed {
+            payout_id: "p1".to_string(),
+            address: "bc1qa".to_string(),
+            amount_satoshis: 1_250_000,
+            txid: Some("ab".repeat(32)),
+            confirmations: 6,
+        };
+        let explorer = ExplorerLinks::new(&ExplorerConfig::default(), bitcoin::Network::Testnet).unwrap();
+        let notice = PayoutNotice::from_event(&event, Some(&explorer)).unwrap();
+        assert_eq!(notice.explorer_url.as_deref(), Some(format!("https://mempool.space/testnet/tx/{}", "ab".repeat(32)).as_str()));
+        assert_eq!(notice.subject(), "DMPool payout of 0.01250000 BTC confirmed");
+        assert!(notice.text().contains("has 6 confirmations"));
+        assert_eq!(serde_json::to_value(&notice).unwrap()["type"], "payout_confirmed");
+
+        assert!(!preferences().wants(&notice.kind));
+        assert!(PayoutNotice::from_event(&PoolEvent::ShareAccepted {
+            address: "bc1qa".to_string(),
+            worker: None,
+            difficulty: 1,
+            n_time: 0,
+        }, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preferences_validation_and_persistence() {
+        let temp_dir = TempDir::new().unwrap();
+        let notifier = MinerNotifier::new(temp_dir.path().to_path_buf(), MinerNotifierConfig::default()).unwrap();
+        assert_eq!(notifier.channels(), vec!["webhook"]);
+
+        let mut invalid = preferences();
+        invalid.webhook_url = Some("http://10.0.0.1/admin".to_string());
+        assert!(notifier.set("bc1qa", invalid).await.is_err());
+        // No SMTP configured
+        let mut email = preferences();
+        email.email = Some("miner@example.com".to_string());
+        assert!(notifier.set("bc1qa", email).await.is_err());
+
+        notifier.set("bc1qa", preferences()).await.unwrap();
+        let reloaded = MinerNotifier::new(temp_dir.path().to_path_buf(), MinerNotifierConfig::default()).unwrap();
+        reloaded.load().await.unwrap();
+        assert!(reloaded.get("bc1qa").await.unwrap().payout_broadcast);
+        assert!(reloaded.remove("bc1qa").await.unwrap());
+        assert!(reloaded.get("bc1qa").await.is_none());
+    }
+}
@@ -7,6 +7,8 @@ use axum::{
 };
 use serde_json::json;
 
+use crate::error::{kind_of, DmpoolError, ErrorKind};
+
 /// Observer API error type
 #[derive(Debug)]
 pub enum ObserverError {
@@ -16,6 +18,8 @@ pub enum ObserverError {
     NotFound(String),
     /// Invalid input
     InvalidInput(String),
+    /// Missing or invalid API token
+    Unauthorized(String),
     /// Internal server error
     Internal(String),
 }
@@ -26,6 +30,7 @@ impl std::fmt::Display for ObserverError {
             ObserverError::Database(msg) => write!(f, "Database error: {}", msg),
             ObserverError::NotFound(msg) => write!(f, "Not found: {}", msg),
             ObserverError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            ObserverError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             ObserverError::Internal(msg) => write!(f, "Internal error: {}", msg),
         }
     }
@@ -46,6 +51,9 @@ impl IntoResponse for ObserverError {
             ObserverError::InvalidInput(msg) => {
                 (StatusCode::BAD_REQUEST, msg.as_str(), "INVALID_INPUT")
             }
+            ObserverError::Unauthorized(msg) => {
+                (StatusCode::UNAUTHORIZED, msg.as_str(), "UNAUTHORIZED")
+            }
             ObserverError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", "INTERNAL_ERROR")
@@ -63,7 +71,21 @@ impl IntoResponse for ObserverError {
 
 impl From<anyhow::Error> for ObserverError {
     fn from(err: anyhow::Error) -> Self {
-        ObserverError::Internal(err.to_string())
+        // Keep the whole context chain in the message
+        let message = format!("{:#}", err);
+        match kind_of(&err) {
+            ErrorKind::NotFound => ObserverError::NotFound(message),
+            ErrorKind::InvalidInput | ErrorKind::Conflict => ObserverError::InvalidInput(message),
+            ErrorKind::Unavailable | ErrorKind::ReadOnly => ObserverError::Database(message),
+            ErrorKind::Unauthorized => ObserverError::Unauthorized(message),
+            ErrorKind::Internal => ObserverError::Internal(message),
+        }
+    }
+}
+
+impl From<DmpoolError> for ObserverError {
+    fn from(err: DmpoolError) -> Self {
+        ObserverError::from(anyhow::Error::from(err))
     }
 }
 
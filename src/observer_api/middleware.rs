@@ -0,0 +1,146 @@
+// Observer API Middleware
+//
+// Accepts miner API tokens (`Authorization: Bearer dmp_...`) and applies rate
+// limits: per client IP for anonymous requests, per token (higher) for
+// authenticated ones.
+//
+// Requests for non-public per-miner data can also be recorded in an access
+// log (who, from where, which endpoint and how much was returned).
+
+use axum::{
+    async_trait,
+    body::HttpBody,
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::audit::AuditLog;
+use crate::rate_limit::RateLimitClass;
+
+use super::error::ObserverError;
+use super::versioning::unversioned;
+use super::ObserverState;
+
+/// Miner authenticated by an API token
+#[derive(Clone, Debug)]
+pub struct MinerIdentity {
+    pub address: String,
+    pub token_id: String,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for MinerIdentity {
+    type Rejection = ObserverError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<MinerIdentity>()
+            .cloned()
+            .ok_or_else(|| ObserverError::Unauthorized("A miner API token is required".to_string()))
+    }
+}
+
+/// Resolve API tokens and enforce rate limits
+pub async fn token_middleware(
+    State(state): State<ObserverState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let bearer = req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    match bearer {
+        Some(token) => {
+            let tokens = state.tokens.as_deref()
+                .ok_or_else(|| ObserverError::Unauthorized("API tokens are not enabled".to_string()).into_response())?;
+            let info = tokens.authenticate(&token).await
+                .ok_or_else(|| ObserverError::Unauthorized("Invalid or revoked API token".to_string()).into_response())?;
+            if let Some(limiter) = &state.rate_limiter {
+                // Operator blocks apply to token holders too
+                if let Ok(ip) = limiter.client_ip(req.headers()) {
+                    limiter.check_blocked(ip, RateLimitClass::Token).await.map_err(IntoResponse::into_response)?;
+                }
+                limiter.check_token_rate_limit(&info.id).await.map_err(IntoResponse::into_response)?;
+            }
+            req.extensions_mut().insert(MinerIdentity { address: info.address, token_id: info.id });
+        }
+        None => {
+            if let Some(limiter) = &state.rate_limiter {
+                let ip = limiter.client_ip(req.headers()).map_err(IntoResponse::into_response)?;
+                limiter.check_api_rate_limit(ip).await.map_err(IntoResponse::into_response)?;
+            }
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Audit action of Observer access log entries
+pub const OBSERVER_ACCESS_ACTION: &str = "observer_access";
+
+/// Routes serving non-public per-miner data, in every API version
+const SENSITIVE_ROUTES: &[&str] = &[
+    "/me",
+    "/me/earnings",
+    "/me/notifications",
+    "/me/referrals",
+    "/me/referrals/codes",
+    "/me/referrals/referrer",
+    "/accounts/:name/earnings",
+];
+
+/// Record requests for per-miner data in the access log
+///
+/// Runs inside `token_middleware` so the miner identity is known.
+pub async fn access_audit_middleware(
+    State(state): State<ObserverState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(audit) = state.access_audit.clone() else {
+        return next.run(req).await;
+    };
+    let sensitive = req.extensions().get::<MatchedPath>()
+        .is_some_and(|path| SENSITIVE_ROUTES.contains(&unversioned(path.as_str())));
+    if !sensitive {
+        return next.run(req).await;
+    }
+
+    let identity = req.extensions().get::<MinerIdentity>().cloned();
+    let ip = state.rate_limiter.as_ref()
+        .and_then(|limiter| limiter.client_ip(req.headers()).ok())
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().map(str::to_string);
+
+    let response = next.run(req).await;
+
+    let status = response.status();
+    audit.log(AuditLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now(),
+        username: identity.as_ref().map_or_else(|| "anonymous".to_string(), |id| id.address.clone()),
+        role: None,
+        action: OBSERVER_ACCESS_ACTION.to_string(),
+        resource: format!("observer:{}", path),
+        ip_address: ip,
+        details: serde_json::json!({
+            "method": method,
+            "query": query,
+            "token_id": identity.map(|id| id.token_id),
+            "status": status.as_u16(),
+            "response_bytes": response.body().size_hint().exact(),
+        }),
+        success: status.is_success(),
+        error: None,
+        request_id: None,
+    }).await;
+
+    response
+}
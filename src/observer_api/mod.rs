@@ -2,56 +2,387 @@
 //
 // This module provides public, read-only API endpoints for:
 // - Pool statistics
-// - Miner statistics
-// - Hashrate history
-// - Block information
+// - Miner statistics, with loyalty tiers when enabled
+// - Compact statistics for many miners in one request
+// - Hashrate history (from ClickHouse when enabled)
+// - Block information with per-block effort and rolling luck
+// - Solo mining statistics (when solo mode is enabled)
+// - Per-miner PPLNS window contribution
+// - Farm account aggregates and combined earnings
+// - Worker tag filters and per-tag/location aggregates
+// - Historical payout statistics
+// - Projected earnings for a hashrate or miner
+// - Miner API tokens and per-miner data for token holders
+// - Address ownership verification with signed messages
+// - Payout notification preferences for token holders
+// - Live stratum worker counts and share rates
+// - Operator announcements in effect
+// - Referral codes, referrer and referral credits for token holders
+// - Optional access logging of requests for per-miner data
+//
+// Routes are served under `/api/v1` and, with renamed fields and response
+// envelopes, under `/api/v2`; deprecated routes carry deprecation headers.
+//
+// Pool and miner statistics support ETags and long-polling (`?wait=N`).
+//
+// Hashrates and amounts can be served raw, as display strings or both
+// (`?units=raw|human|both`).
+//
+// Block and miner responses carry block explorer links when configured.
 //
 // These endpoints are accessible without authentication and are
-// designed to be consumed by the observer frontend.
+// designed to be consumed by the observer frontend. Miners can also
+// present an API token for their own data and a higher rate limit.
 
 pub mod routes;
 pub mod error;
+pub mod middleware;
+pub mod poll;
+pub mod units;
+pub mod versioning;
 
 use anyhow::Result;
-use axum::{Router, routing::get};
+use axum::{Router, extract::DefaultBodyLimit, routing::delete, routing::get, routing::post, routing::put};
+use bitcoin::Network;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::db::DatabaseManager;
+use crate::accounts::AccountManager;
+use crate::announcements::AnnouncementBoard;
+use crate::api_tokens::MinerTokenManager;
+use crate::audit::AuditLogger;
+use crate::clickhouse::HashrateHistorySource;
+use crate::db::{DatabaseManager, MinerStats};
+use crate::earnings::{EarningsEstimator, HASHES_PER_DIFFICULTY};
+use crate::events::EventBus;
+use crate::explorer::ExplorerLinks;
+use crate::http_compression::CompressionConfig;
+use crate::http_metrics::{http_metrics_middleware, HttpMetrics, OBSERVER_API};
+use crate::ownership::OwnershipManager;
+use crate::logging::request_id::request_id_middleware;
+use crate::loyalty::LoyaltyTracker;
+use crate::miner_notify::MinerNotifier;
+use crate::payment::NetworkParams;
+use crate::pplns_window::PplnsWindow;
+use crate::rate_limit::RateLimiterState;
+use crate::referrals::ReferralProgram;
+use crate::solo::SoloManager;
+use crate::stratum_stats::StratumStats;
+use routes::miners::{BulkStatsConfig, BULK_BODY_LIMIT_BYTES};
+use units::UnitsConfig;
+use versioning::VersioningConfig;
 
 /// Application state for Observer API
 #[derive(Clone)]
 pub struct ObserverState {
     pub db: Arc<DatabaseManager>,
+    pub solo: Option<Arc<SoloManager>>,
+    pub pplns_window: Option<Arc<PplnsWindow>>,
+    pub accounts: Option<Arc<AccountManager>>,
+    pub tokens: Option<Arc<MinerTokenManager>>,
+    pub ownership: Option<Arc<OwnershipManager>>,
+    pub notifications: Option<Arc<MinerNotifier>>,
+    pub announcements: Option<Arc<AnnouncementBoard>>,
+    pub loyalty: Option<Arc<LoyaltyTracker>>,
+    pub referrals: Option<Arc<ReferralProgram>>,
+    pub explorer: Option<Arc<ExplorerLinks>>,
+    pub stratum_stats: Option<Arc<StratumStats>>,
+    pub earnings: Option<Arc<EarningsEstimator>>,
+    /// Hashrate history comes from the database unless set
+    pub hashrate_history: Option<Arc<dyn HashrateHistorySource>>,
+    /// Rate limits are only enforced when set
+    pub rate_limiter: Option<Arc<RateLimiterState>>,
+    /// Representation of hashrates and amounts without a `units` parameter
+    pub units: UnitsConfig,
+    /// Long-polling waits for changes on the bus when set
+    pub events: Option<EventBus>,
+    /// Miner addresses must belong to this network
+    pub network: NetworkParams,
+    /// Requests for per-miner data are logged here when set
+    pub access_audit: Option<Arc<AuditLogger>>,
+    /// Per-route request metrics are recorded when set
+    pub http_metrics: Option<Arc<HttpMetrics>>,
+    /// Deprecated routes of each API version
+    pub versioning: Arc<VersioningConfig>,
+    /// Responses are compressed when set
+    pub compression: Option<CompressionConfig>,
+    /// Limits of bulk miner requests
+    pub bulk: BulkStatsConfig,
+}
+
+impl ObserverState {
+    /// Create state backed only by the database
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self { db, solo: None, pplns_window: None, accounts: None, tokens: None, ownership: None, notifications: None, announcements: None, loyalty: None, referrals: None, explorer: None, stratum_stats: None, earnings: None, hashrate_history: None, rate_limiter: None, units: UnitsConfig::default(), events: None, network: NetworkParams::for_network(Network::Bitcoin), access_audit: None, http_metrics: None, versioning: Arc::new(VersioningConfig::default()), compression: None, bulk: BulkStatsConfig::default() }
+    }
+
+    /// Attach the solo mining manager
+    pub fn with_solo(mut self, solo: Arc<SoloManager>) -> Self {
+        self.solo = Some(solo);
+        self
+    }
+
+    /// Attach the live PPLNS window
+    pub fn with_pplns_window(mut self, window: Arc<PplnsWindow>) -> Self {
+        self.pplns_window = Some(window);
+        self
+    }
+
+    /// Attach miner accounts
+    pub fn with_accounts(mut self, accounts: Arc<AccountManager>) -> Self {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    /// Attach miner API tokens
+    pub fn with_tokens(mut self, tokens: Arc<MinerTokenManager>) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Attach address ownership verification
+    pub fn with_ownership(mut self, ownership: Arc<OwnershipManager>) -> Self {
+        self.ownership = Some(ownership);
+        self
+    }
+
+    /// Attach per-miner payout notifications
+    pub fn with_notifications(mut self, notifications: Arc<MinerNotifier>) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    /// Add explorer links to block and miner responses
+    pub fn with_explorer(mut self, explorer: Arc<ExplorerLinks>) -> Self {
+        self.explorer = Some(explorer);
+        self
+    }
+
+    /// Serve operator announcements
+    pub fn with_announcements(mut self, announcements: Arc<AnnouncementBoard>) -> Self {
+        self.announcements = Some(announcements);
+        self
+    }
+
+    /// Show loyalty tiers in miner stats
+    pub fn with_loyalty(mut self, loyalty: Arc<LoyaltyTracker>) -> Self {
+        self.loyalty = Some(loyalty);
+        self
+    }
+
+    /// Serve the referral program to token holders
+    pub fn with_referrals(mut self, referrals: Arc<ReferralProgram>) -> Self {
+        self.referrals = Some(referrals);
+        self
+    }
+
+    /// Attach live stratum statistics
+    pub fn with_stratum_stats(mut self, stats: Arc<StratumStats>) -> Self {
+        self.stratum_stats = Some(stats);
+        self
+    }
+
+    /// Attach the earnings estimator for projections and miner estimates
+    pub fn with_earnings(mut self, earnings: Arc<EarningsEstimator>) -> Self {
+        self.earnings = Some(earnings);
+        self
+    }
+
+    /// Serve hashrate history from another store, such as ClickHouse
+    pub fn with_hashrate_history(mut self, source: Arc<dyn HashrateHistorySource>) -> Self {
+        self.hashrate_history = Some(source);
+        self
+    }
+
+    /// Fill a miner's estimated rewards from the earnings estimator and PPLNS window
+    pub async fn fill_estimates(&self, stats: &mut MinerStats) {
+        if let Some(earnings) = &self.earnings {
+            // Miner hashrates are stored as share difficulty per second
+            let hashrate = stats.hashrate_avg.day_7 as f64 * HASHES_PER_DIFFICULTY;
+            if let Some(satoshis) = earnings.expected_satoshis(hashrate, 7 * 86_400).await {
+                stats.estimated_reward_window = satoshis as f64 / 100_000_000.0;
+            }
+        }
+        if let Some(window) = &self.pplns_window {
+            match window.contribution(&stats.address).await {
+                Ok(contribution) => {
+                    stats.estimated_next_block = contribution.projected_payout_satoshis as f64 / 100_000_000.0;
+                }
+                Err(e) => warn!("Failed to estimate next block payout for {}: {}", stats.address, e),
+            }
+        }
+    }
+
+    /// Fill a miner's loyalty tier and streak
+    pub async fn fill_loyalty(&self, stats: &mut MinerStats) {
+        if let Some(loyalty) = &self.loyalty {
+            stats.loyalty = loyalty.status(&stats.address, chrono::Utc::now()).await;
+        }
+    }
+
+    /// Explorer link for a block height
+    pub fn block_url(&self, height: i64) -> Option<String> {
+        let height = u64::try_from(height).ok()?;
+        self.explorer.as_ref()?.block_url(height)
+    }
+
+    /// Explorer link for an address
+    pub fn address_url(&self, address: &str) -> Option<String> {
+        self.explorer.as_ref()?.address_url(address)
+    }
+
+    /// Rate limit anonymous requests per IP and token requests per token
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiterState>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Let long-polling requests wake on pool events
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Set the default representation of hashrates and amounts
+    pub fn with_units(mut self, units: UnitsConfig) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// Network miner addresses are validated against (default mainnet)
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = NetworkParams::for_network(network);
+        self
+    }
+
+    /// Log requests for non-public per-miner data to `audit`
+    pub fn with_access_audit(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.access_audit = Some(audit);
+        self
+    }
+
+    /// Record per-route request counts and latencies
+    pub fn with_http_metrics(mut self, http_metrics: Arc<HttpMetrics>) -> Self {
+        self.http_metrics = Some(http_metrics);
+        self
+    }
+
+    /// Set the limits of bulk miner requests
+    pub fn with_bulk_stats(mut self, bulk: BulkStatsConfig) -> Self {
+        self.bulk = bulk;
+        self
+    }
+
+    /// Compress responses with gzip or brotli
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Mark routes deprecated
+    pub fn with_versioning(mut self, versioning: VersioningConfig) -> Self {
+        self.versioning = Arc::new(versioning);
+        self
+    }
+
+    /// Whether `address` is a valid address on the pool's network
+    pub fn is_valid_address(&self, address: &str) -> bool {
+        self.network.is_valid_address(address)
+    }
 }
 
 /// Create the Observer API router
 pub fn create_router(db: Arc<DatabaseManager>) -> Router {
-    let state = ObserverState { db };
+    create_router_with_state(ObserverState::new(db))
+}
 
+/// Routes of one API version, relative to its prefix
+fn versioned_routes() -> Router<ObserverState> {
     Router::new()
         // Pool statistics
-        .route("/api/v1/stats", get(routes::get_pool_stats))
+        .route("/stats", get(routes::get_pool_stats))
+        .route("/pool/live", get(routes::pool::get_live_stats))
+        .route("/announcements", get(routes::announcements::get_announcements))
 
         // Miner statistics
-        .route("/api/v1/stats/:address", get(routes::get_miner_stats))
-        .route("/api/v1/stats/:address/hashrate", get(routes::get_miner_hashrate_history))
+        .route("/stats/:address", get(routes::get_miner_stats))
+        .route("/stats/:address/hashrate", get(routes::get_miner_hashrate_history))
+        .route("/stats/:address/pplns", get(routes::get_miner_pplns_contribution))
+        .route("/stats/:address/payouts", get(routes::get_miner_payout_stats))
+        .route("/stats/:address/workers", get(routes::get_miner_workers))
+        .route("/stats/:address/workers/groups", get(routes::get_miner_worker_groups))
+        .route("/miners/stats", post(routes::miners::get_bulk_miner_stats)
+            .layer(DefaultBodyLimit::max(BULK_BODY_LIMIT_BYTES)))
+        .route("/workers/groups", get(routes::get_pool_worker_groups))
+        .route("/payouts/stats", get(routes::get_pool_payout_stats))
+        .route("/earnings/projection", get(routes::earnings::get_earnings_projection))
 
         // Block information
-        .route("/api/v1/blocks", get(routes::get_blocks))
-        .route("/api/v1/blocks/:height", get(routes::get_block_detail))
+        .route("/blocks", get(routes::get_blocks))
+        .route("/blocks/luck", get(routes::get_block_luck))
+        .route("/blocks/:height", get(routes::get_block_detail))
+
+        // Farm accounts
+        .route("/accounts/:name", get(routes::get_account_stats))
+        .route("/accounts/:name/earnings", get(routes::get_account_earnings))
+
+        // Solo mining
+        .route("/solo/blocks", get(routes::get_solo_blocks))
+        .route("/solo/:address", get(routes::get_solo_stats))
+
+        // Miner API tokens
+        .route("/tokens/challenge", post(routes::tokens::create_token_challenge))
+        .route("/tokens", get(routes::tokens::list_tokens).post(routes::tokens::issue_token))
+        .route("/tokens/:id", delete(routes::tokens::revoke_token))
+        .route("/me", get(routes::tokens::get_my_stats))
+        .route("/me/earnings", get(routes::tokens::get_my_earnings))
+        .route("/me/notifications", get(routes::notifications::get_notifications)
+            .put(routes::notifications::update_notifications)
+            .delete(routes::notifications::delete_notifications))
+        .route("/me/referrals", get(routes::referrals::get_referrals))
+        .route("/me/referrals/codes", post(routes::referrals::create_code))
+        .route("/me/referrals/referrer", put(routes::referrals::set_referrer))
+
+        // Address ownership
+        .route("/ownership/challenge", post(routes::ownership::create_ownership_challenge))
+        .route("/ownership/verify", post(routes::ownership::verify_ownership))
+        .route("/ownership/:address", get(routes::ownership::get_ownership))
+}
+
+/// Create the Observer API router from a prepared state
+pub fn create_router_with_state(state: ObserverState) -> Router {
+    let http_metrics = state.http_metrics.clone();
+    let compression = state.compression;
+    let router = Router::new()
+        .nest(versioning::V1_PREFIX, versioned_routes())
+        .nest(versioning::V2_PREFIX, versioned_routes())
+        .layer(axum::middleware::from_fn_with_state(state.clone(), units::units_middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::access_audit_middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), middleware::token_middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), versioning::deprecation_middleware))
+        // Outermost so token and rate limit errors get the v2 shape too
+        .layer(axum::middleware::from_fn(versioning::v2_middleware))
+        .with_state(state);
 
-        .with_state(state)
+    let router = match http_metrics {
+        Some(metrics) => router.layer(axum::middleware::from_fn_with_state((metrics, OBSERVER_API), http_metrics_middleware)),
+        None => router,
+    };
+    let router = match compression {
+        Some(compression) => compression.apply(router),
+        None => router,
+    };
+    router.layer(axum::middleware::from_fn(request_id_middleware))
 }
 
 /// Start the Observer API server
 pub async fn start_observer_api(
-    db: Arc<DatabaseManager>,
+    state: ObserverState,
     host: String,
     port: u16,
 ) -> Result<tokio::task::JoinHandle<()>> {
-    let app = create_router(db.clone());
+    let app = create_router_with_state(state);
     let addr = format!("{}:{}", host, port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
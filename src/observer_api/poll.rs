@@ -0,0 +1,203 @@
+// Conditional requests and long-polling
+//
+// Pollable endpoints send an ETag derived from the response body and answer
+// `If-None-Match` with 304 Not Modified when nothing changed. With `?wait=N`
+// (and a matching ETag) the request is held for up to N seconds: every
+// relevant event on the bus re-runs the query, at most once per
+// `RECHECK_INTERVAL`, and the new body is returned as soon as it differs.
+
+use bitcoin::hex::DisplayHex;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use super::error::ObserverError;
+use crate::events::{EventBus, PoolEvent};
+
+/// Longest a request may be held
+pub const MAX_WAIT_SECS: u64 = 60;
+
+/// Minimum time between re-running the query while waiting
+const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Query parameters for long-polling
+#[derive(Debug, Default, Deserialize)]
+pub struct PollQuery {
+    /// Seconds to wait for a change when the ETag still matches
+    pub wait: Option<u64>,
+}
+
+/// Strong ETag for a response body
+pub fn etag_for(body: &[u8]) -> String {
+    format!("\"{}\"", Sha256::digest(body)[..16].to_lower_hex_string())
+}
+
+/// Whether an `If-None-Match` header value matches the ETag (weak comparison)
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',')
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Serve `fetch` with ETag handling, holding the request while it is unchanged
+///
+/// `relevant` picks the events that may change the response; without an
+/// event bus the request is answered straight away.
+pub async fn respond<T, F, Fut>(
+    bus: Option<&EventBus>,
+    headers: &HeaderMap,
+    query: &PollQuery,
+    relevant: impl Fn(&PoolEvent) -> bool,
+    mut fetch: F,
+) -> Result<Response, ObserverError>
+where
+    T: Serialize,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ObserverError>>,
+{
+    let if_none_match = headers.get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let wait = Duration::from_secs(query.wait.unwrap_or(0).min(MAX_WAIT_SECS));
+    let deadline = Instant::now() + wait;
+
+    // Subscribe before the first query so no change slips in between
+    let mut events = match (&if_none_match, bus) {
+        (Some(_), Some(bus)) if !wait.is_zero() => Some(bus.subscribe()),
+        _ => None,
+    };
+
+    loop {
+        let body = serde_json::to_vec(&fetch().await?)
+            .map_err(|e| ObserverError::Internal(format!("Failed to serialize response: {}", e)))?;
+        let etag = etag_for(&body);
+
+        let unchanged = if_none_match.as_deref().is_some_and(|inm| etag_matches(inm, &etag));
+        if !unchanged {
+            return Ok(build(StatusCode::OK, &etag, Body::from(body)));
+        }
+
+        let Some(receiver) = events.as_mut() else {
+            return Ok(build(StatusCode::NOT_MODIFIED, &etag, Body::empty()));
+        };
+        let changed = tokio::time::timeout_at(deadline, next_relevant(receiver, &relevant)).await;
+        if !matches!(changed, Ok(true)) {
+            return Ok(build(StatusCode::NOT_MODIFIED, &etag, Body::empty()));
+        }
+        // Coalesce bursts (shares arrive at stratum rate) into one query
+        tokio::time::sleep_until((Instant::now() + RECHECK_INTERVAL).min(deadline)).await;
+    }
+}
+
+/// Wait for an event `relevant` accepts; false once the bus is gone
+async fn next_relevant(receiver: &mut broadcast::Receiver<PoolEvent>, relevant: impl Fn(&PoolEvent) -> bool) -> bool {
+    loop {
+        match receiver.recv().await {
+            Ok(event) if relevant(&event) => return true,
+            Ok(_) => {}
+            // Missed events may have been relevant
+            Err(broadcast::error::RecvError::Lagged(_)) => return true,
+            Err(broadcast::error::RecvError::Closed) => return false,
+        }
+    }
+}
+
+fn build(status: StatusCode, etag: &str, body: Body) -> Response {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, value);
+    }
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if status == StatusCode::OK {
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    response
+}
+
+/// Events that can change pool-wide statistics
+pub fn pool_event(_event: &PoolEvent) -> bool {
+    true
+}
+
+/// Events that can change one miner's statistics
+pub fn miner_event(address: &str) -> impl Fn(&PoolEvent) -> bool + '_ {
+    move |event| match event {
+        PoolEvent::ShareAccepted { address: a, .. }
+        | PoolEvent::PayoutBroadcast { address: a, .. }
+        | PoolEvent::PayoutConfirmed { address: a, .. } => a == address,
+        PoolEvent::BlockFound(_) => true,
+        PoolEvent::ConfigApplied { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn share(address: &str) -> PoolEvent {
+        PoolEvent::ShareAccepted { address: address.to_string(), worker: None, difficulty: 1, n_time: 0 }
+    }
+
+    #[test]
+    fn test_etag_matching() {
+        let etag = etag_for(b"{\"a\":1}");
+        assert_eq!(etag, etag_for(b"{\"a\":1}"));
+        assert_ne!(etag, etag_for(b"{\"a\":2}"));
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"other\", W/{}", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+
+        assert!(miner_event("bc1qa")(&share("bc1qa")));
+        assert!(!miner_event("bc1qa")(&share("bc1qb")));
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_returns_on_change() {
+        let events = EventBus::default();
+        let counter = Arc::new(AtomicU64::new(0));
+        let fetch = || {
+            let counter = counter.clone();
+            async move { Ok::<_, ObserverError>(counter.load(Ordering::SeqCst)) }
+        };
+
+        let first = respond(Some(&events), &HeaderMap::new(), &PollQuery::default(), pool_event, fetch).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, first.headers()[header::ETAG].clone());
+
+        // Unchanged and not waiting: 304 straight away
+        let response = respond(Some(&events), &headers, &PollQuery::default(), pool_event, fetch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // Nothing happens within the wait
+        let response = respond(Some(&events), &headers, &PollQuery { wait: Some(1) }, pool_event, fetch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A share changes the value while the request waits
+        let publisher = {
+            let (events, counter) = (events.clone(), counter.clone());
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                events.publish(share("bc1qa"));
+            })
+        };
+        let response = respond(Some(&events), &headers, &PollQuery { wait: Some(10) }, pool_event, fetch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], headers[header::IF_NONE_MATCH]);
+        publisher.await.unwrap();
+    }
+}
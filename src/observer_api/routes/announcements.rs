@@ -0,0 +1,46 @@
+// Announcement endpoints
+//
+// Operator notices currently in effect (fee changes, maintenance)
+
+use super::super::error::ObserverError;
+use super::super::ObserverState;
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::announcements::{Announcement, AnnouncementSeverity};
+
+/// Announcement as shown to miners
+#[derive(Debug, Serialize)]
+pub struct AnnouncementView {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub severity: AnnouncementSeverity,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl From<Announcement> for AnnouncementView {
+    fn from(announcement: Announcement) -> Self {
+        Self {
+            id: announcement.id,
+            title: announcement.title,
+            body: announcement.body,
+            severity: announcement.severity,
+            starts_at: announcement.starts_at,
+            ends_at: announcement.ends_at,
+        }
+    }
+}
+
+/// GET /api/v1/announcements
+///
+/// Returns announcements in effect now, most severe first
+pub async fn get_announcements(
+    State(state): State<ObserverState>,
+) -> Result<Json<Vec<AnnouncementView>>, ObserverError> {
+    let board = state.announcements.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Announcements are not enabled".to_string()))?;
+    Ok(Json(board.active_at(Utc::now()).await.into_iter().map(AnnouncementView::from).collect()))
+}
@@ -0,0 +1,54 @@
+// Earnings projection endpoint
+//
+// Expected daily/weekly earnings for a hashrate or an existing miner, from the
+// current network difficulty, block reward, pool fee and 30-day pool luck
+
+use super::super::error::ObserverError;
+use super::super::ObserverState;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::earnings::{EarningsProjection, HASHES_PER_DIFFICULTY};
+
+/// Query parameters for an earnings projection; one of the two is required
+#[derive(Debug, Deserialize)]
+pub struct ProjectionQuery {
+    /// Hashrate in H/s
+    pub hashrate: Option<f64>,
+    /// Project from this miner's 24h hashrate
+    pub address: Option<String>,
+}
+
+/// GET /api/v1/earnings/projection?hashrate=1e14 or ?address=bc1...
+///
+/// Returns expected earnings with and without the recent pool luck
+pub async fn get_earnings_projection(
+    State(state): State<ObserverState>,
+    Query(query): Query<ProjectionQuery>,
+) -> Result<Json<EarningsProjection>, ObserverError> {
+    let estimator = state.earnings.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Earnings projections are not enabled".to_string()))?;
+
+    let hashrate = match (query.hashrate, query.address) {
+        (Some(hashrate), None) if hashrate.is_finite() && hashrate > 0.0 => hashrate,
+        (Some(_), None) => return Err(ObserverError::InvalidInput("Hashrate must be positive".to_string())),
+        (None, Some(address)) => {
+            if !state.is_valid_address(&address) {
+                return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+            }
+            let stats = state.db.get_miner_stats(&address).await?
+                .ok_or_else(|| ObserverError::NotFound(format!("Miner not found: {}", address)))?;
+            // Miner hashrates are stored as share difficulty per second
+            stats.hashrate_avg.hour_24 as f64 * HASHES_PER_DIFFICULTY
+        }
+        _ => return Err(ObserverError::InvalidInput("Provide either hashrate or address".to_string())),
+    };
+
+    let luck = state.db.get_block_luck().await?.last_30d.luck_percent;
+    let projection = estimator.project(hashrate, luck).await
+        .ok_or_else(|| ObserverError::NotFound("Network conditions are not available yet".to_string()))?;
+    Ok(Json(projection))
+}
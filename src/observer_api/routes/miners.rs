@@ -1 +1,116 @@
-// Placeholder - will be implemented
+// Bulk miner endpoints
+//
+// Farm dashboards tracking many addresses fetch compact statistics for all
+// of them in one request instead of one `/stats/:address` call each.
+
+use super::super::error::ObserverError;
+use super::super::ObserverState;
+use anyhow::Result;
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::db::MinerSummary;
+
+/// Highest `max_addresses` the config accepts
+pub const MAX_BULK_ADDRESSES: usize = 500;
+
+/// Request bodies above this size are rejected before parsing
+pub const BULK_BODY_LIMIT_BYTES: usize = 64 * 1024;
+
+/// The `[dmpool.observer_bulk]` settings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkStatsConfig {
+    /// Most addresses one request may ask for
+    pub max_addresses: usize,
+}
+
+impl Default for BulkStatsConfig {
+    fn default() -> Self {
+        Self { max_addresses: 50 }
+    }
+}
+
+impl BulkStatsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_BULK_ADDRESSES).contains(&self.max_addresses) {
+            return Err(anyhow::anyhow!("max_addresses must be between 1 and {}", MAX_BULK_ADDRESSES));
+        }
+        Ok(())
+    }
+}
+
+/// Body of a bulk stats request
+#[derive(Debug, Deserialize)]
+pub struct BulkStatsRequest {
+    pub addresses: Vec<String>,
+}
+
+/// Bulk stats in request order, with the addresses the pool has not seen
+#[derive(Debug, Serialize)]
+pub struct BulkStatsResponse {
+    pub miners: Vec<MinerSummary>,
+    pub not_found: Vec<String>,
+}
+
+/// Trim and deduplicate `addresses`, keeping their order
+fn normalize_addresses(
+    addresses: Vec<String>,
+    max: usize,
+    is_valid: impl Fn(&str) -> bool,
+) -> Result<Vec<String>, ObserverError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(addresses.len().min(max));
+    for address in addresses {
+        let address = address.trim();
+        if normalized.iter().any(|a| a == address) {
+            continue;
+        }
+        if !is_valid(address) {
+            return Err(ObserverError::InvalidInput(format!("Invalid Bitcoin address: {}", address)));
+        }
+        if normalized.len() == max {
+            return Err(ObserverError::InvalidInput(format!("At most {} addresses per request", max)));
+        }
+        normalized.push(address.to_string());
+    }
+    if normalized.is_empty() {
+        return Err(ObserverError::InvalidInput("No addresses given".to_string()));
+    }
+    Ok(normalized)
+}
+
+/// POST /api/v1/miners/stats
+///
+/// Returns compact statistics for up to `max_addresses` miners
+pub async fn get_bulk_miner_stats(
+    State(state): State<ObserverState>,
+    Json(request): Json<BulkStatsRequest>,
+) -> Result<Json<BulkStatsResponse>, ObserverError> {
+    let addresses = normalize_addresses(request.addresses, state.bulk.max_addresses, |a| state.is_valid_address(a))?;
+    let mut found = state.db.get_miner_summaries(&addresses).await?;
+
+    let mut miners = Vec::with_capacity(found.len());
+    let mut not_found = Vec::new();
+    for address in addresses {
+        match found.iter().position(|m| m.address == address) {
+            Some(i) => miners.push(found.swap_remove(i)),
+            None => not_found.push(address),
+        }
+    }
+    Ok(Json(BulkStatsResponse { miners, not_found }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_addresses() {
+        let is_valid = |a: &str| a.starts_with("bc1");
+        let addresses = vec![" bc1qa ".to_string(), "bc1qb".to_string(), "bc1qa".to_string()];
+        assert_eq!(normalize_addresses(addresses.clone(), 2, is_valid).unwrap(), vec!["bc1qa", "bc1qb"]);
+        assert!(normalize_addresses(addresses, 1, is_valid).is_err());
+        assert!(normalize_addresses(vec!["1abc".to_string()], 5, is_valid).is_err());
+        assert!(normalize_addresses(Vec::new(), 5, is_valid).is_err());
+    }
+}
@@ -3,62 +3,111 @@
 // Public, read-only endpoints for pool and miner statistics
 
 use super::error::ObserverError;
+use super::poll::{self, PollQuery};
 use axum::{
     extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
+use crate::accounts::{earnings_csv, AccountManager, AccountStats};
 use crate::db::{DatabaseManager, BlockInfo, BlockDetail, HashrateDataPoint};
+use crate::luck::LuckStats;
+use crate::payment::{PayoutStatsBucket, PayoutStatsInterval};
+use crate::pplns_window::MinerContribution;
+use crate::solo::{SoloBlock, SoloStatsSummary};
+use crate::worker_tags::{TaggedWorker, WorkerFilter, WorkerGroup, WorkerGrouping};
+
+/// Page size of paginated lists without a `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+/// Largest page size of paginated lists
+pub const MAX_PAGE_SIZE: i64 = 100;
 
 /// Query parameters for pagination
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct PaginationQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
 
+impl PaginationQuery {
+    /// Limit clamped to 1..=MAX_PAGE_SIZE and a non-negative offset
+    pub fn page(&self) -> (i64, i64) {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        (limit, self.offset.unwrap_or(0).max(0))
+    }
+}
+
 /// Query parameters for hashrate history
 #[derive(Debug, Deserialize)]
 pub struct HashrateQuery {
     pub period: Option<String>, // "7d", "1m", "3m", etc.
 }
 
+/// Query parameters for payout statistics
+#[derive(Debug, Deserialize)]
+pub struct PayoutStatsQuery {
+    pub interval: Option<PayoutStatsInterval>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Query parameters for worker aggregation
+#[derive(Debug, Deserialize)]
+pub struct WorkerGroupQuery {
+    pub by: Option<WorkerGrouping>,
+}
+
 // ============================================================================
 // Pool Statistics Endpoints
 // ============================================================================
 
-/// GET /api/v1/stats
+/// GET /api/v1/stats?wait=30
 ///
-/// Returns pool-wide statistics
+/// Returns pool-wide statistics; honours If-None-Match and long-polls with `wait`
 pub async fn get_pool_stats(
     State(state): State<super::ObserverState>,
-) -> Result<Json<crate::db::PoolStats>, ObserverError> {
-    let stats = state.db.get_pool_stats().await?;
-    Ok(Json(stats))
+    headers: HeaderMap,
+    Query(poll): Query<PollQuery>,
+) -> Result<Response, ObserverError> {
+    poll::respond(state.events.as_ref(), &headers, &poll, poll::pool_event, || async {
+        Ok(state.db.get_pool_stats().await?)
+    }).await
 }
 
 // ============================================================================
 // Miner Statistics Endpoints
 // ============================================================================
 
-/// GET /api/v1/stats/:address
+/// GET /api/v1/stats/:address?wait=30
 ///
-/// Returns detailed statistics for a specific miner
+/// Returns detailed statistics for a specific miner; honours If-None-Match and
+/// long-polls with `wait`
 pub async fn get_miner_stats(
     State(state): State<super::ObserverState>,
     Path(address): Path<String>,
-) -> Result<Json<crate::db::MinerStats>, ObserverError> {
+    headers: HeaderMap,
+    Query(poll): Query<PollQuery>,
+) -> Result<Response, ObserverError> {
     // Validate Bitcoin address
-    if !is_valid_bitcoin_address(&address) {
+    if !state.is_valid_address(&address) {
         return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
     }
 
-    match state.db.get_miner_stats(&address).await? {
-        Some(stats) => Ok(Json(stats)),
-        None => Err(ObserverError::NotFound(format!("Miner not found: {}", address))),
-    }
+    poll::respond(state.events.as_ref(), &headers, &poll, poll::miner_event(&address), || async {
+        match state.db.get_miner_stats(&address).await? {
+            Some(mut stats) => {
+                stats.explorer_url = state.address_url(&address);
+                state.fill_estimates(&mut stats).await;
+                state.fill_loyalty(&mut stats).await;
+                Ok(stats)
+            }
+            None => Err(ObserverError::NotFound(format!("Miner not found: {}", address))),
+        }
+    }).await
 }
 
 /// GET /api/v1/stats/:address/hashrate?period=7d
@@ -70,14 +119,17 @@ pub async fn get_miner_hashrate_history(
     Query(query): Query<HashrateQuery>,
 ) -> Result<Json<HashrateHistoryResponse>, ObserverError> {
     // Validate Bitcoin address
-    if !is_valid_bitcoin_address(&address) {
+    if !state.is_valid_address(&address) {
         return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
     }
 
     // Parse period (default: 7 days)
     let period_days = parse_period(query.period.as_deref()).unwrap_or(7);
 
-    let data_points = state.db.get_miner_hashrate_history(&address, period_days).await?;
+    let data_points = match &state.hashrate_history {
+        Some(source) => source.hashrate_history(&address, period_days).await?,
+        None => state.db.get_miner_hashrate_history(&address, period_days).await?,
+    };
 
     Ok(Json(HashrateHistoryResponse {
         address,
@@ -87,6 +139,105 @@ pub async fn get_miner_hashrate_history(
     }))
 }
 
+/// GET /api/v1/stats/:address/pplns
+///
+/// Returns a miner's share of the current PPLNS window and projected payout
+/// if a block were found now
+pub async fn get_miner_pplns_contribution(
+    State(state): State<super::ObserverState>,
+    Path(address): Path<String>,
+) -> Result<Json<MinerContribution>, ObserverError> {
+    let window = state.pplns_window.as_ref()
+        .ok_or_else(|| ObserverError::NotFound("PPLNS window is not available".to_string()))?;
+
+    if !state.is_valid_address(&address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+
+    Ok(Json(window.contribution(&address).await?))
+}
+
+/// GET /api/v1/stats/:address/workers?tag=&location=&rig=&online=
+///
+/// Returns a miner's workers with their parsed tags, optionally filtered
+pub async fn get_miner_workers(
+    State(state): State<super::ObserverState>,
+    Path(address): Path<String>,
+    Query(filter): Query<WorkerFilter>,
+) -> Result<Json<Vec<TaggedWorker>>, ObserverError> {
+    if !state.is_valid_address(&address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+
+    Ok(Json(state.db.get_tagged_workers(&address, &filter).await?))
+}
+
+/// GET /api/v1/stats/:address/workers/groups?by=tag|location
+///
+/// Returns a miner's worker counts and hashrate per tag or location
+pub async fn get_miner_worker_groups(
+    State(state): State<super::ObserverState>,
+    Path(address): Path<String>,
+    Query(query): Query<WorkerGroupQuery>,
+) -> Result<Json<Vec<WorkerGroup>>, ObserverError> {
+    if !state.is_valid_address(&address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+
+    Ok(Json(state.db.get_worker_groups(Some(&address), query.by.unwrap_or_default()).await?))
+}
+
+/// GET /api/v1/workers/groups?by=tag|location
+///
+/// Returns pool-wide worker counts and hashrate per tag or location
+pub async fn get_pool_worker_groups(
+    State(state): State<super::ObserverState>,
+    Query(query): Query<WorkerGroupQuery>,
+) -> Result<Json<Vec<WorkerGroup>>, ObserverError> {
+    Ok(Json(state.db.get_worker_groups(None, query.by.unwrap_or_default()).await?))
+}
+
+/// GET /api/v1/stats/:address/payouts?interval=day|week|month&from=&to=
+///
+/// Returns a miner's payout totals per time bucket for charting
+pub async fn get_miner_payout_stats(
+    State(state): State<super::ObserverState>,
+    Path(address): Path<String>,
+    Query(query): Query<PayoutStatsQuery>,
+) -> Result<Json<Vec<PayoutStatsBucket>>, ObserverError> {
+    if !state.is_valid_address(&address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+
+    payout_stats(&state, Some(&address), query).await
+}
+
+/// GET /api/v1/payouts/stats?interval=day|week|month&from=&to=
+///
+/// Returns pool-wide payout totals, fees and averages per time bucket
+pub async fn get_pool_payout_stats(
+    State(state): State<super::ObserverState>,
+    Query(query): Query<PayoutStatsQuery>,
+) -> Result<Json<Vec<PayoutStatsBucket>>, ObserverError> {
+    payout_stats(&state, None, query).await
+}
+
+/// Shared range handling for the payout stats endpoints
+async fn payout_stats(
+    state: &super::ObserverState,
+    address: Option<&str>,
+    query: PayoutStatsQuery,
+) -> Result<Json<Vec<PayoutStatsBucket>>, ObserverError> {
+    let to = query.to.unwrap_or_else(chrono::Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(90));
+    if from >= to || to - from > chrono::Duration::days(730) {
+        return Err(ObserverError::InvalidInput("Range must be positive and at most two years".to_string()));
+    }
+
+    let stats = state.db.get_payout_stats(address, query.interval.unwrap_or_default(), from, to).await?;
+    Ok(Json(stats))
+}
+
 /// Response for hashrate history
 #[derive(Debug, Serialize)]
 pub struct HashrateHistoryResponse {
@@ -102,19 +253,22 @@ pub struct HashrateHistoryResponse {
 
 /// GET /api/v1/blocks?limit=20&offset=0
 ///
-/// Returns list of blocks found by the pool
+/// Returns list of blocks found by the pool with their effort and rolling luck
 pub async fn get_blocks(
     State(state): State<super::ObserverState>,
     Query(query): Query<PaginationQuery>,
 ) -> Result<Json<BlocksResponse>, ObserverError> {
-    let limit = query.limit.unwrap_or(20).min(100); // Max 100
-    let offset = query.offset.unwrap_or(0);
+    let (limit, offset) = query.page();
 
-    let blocks = state.db.get_blocks(limit, offset).await?;
+    let mut blocks = state.db.get_blocks(limit, offset).await?;
+    for block in &mut blocks {
+        block.explorer_url = state.block_url(block.height);
+    }
 
     Ok(Json(BlocksResponse {
-        total: blocks.len() as i64, // TODO: Get actual count
+        total: state.db.count_blocks().await?,
         blocks,
+        luck: state.db.get_block_luck().await?,
     }))
 }
 
@@ -123,6 +277,16 @@ pub async fn get_blocks(
 pub struct BlocksResponse {
     pub total: i64,
     pub blocks: Vec<BlockInfo>,
+    pub luck: LuckStats,
+}
+
+/// GET /api/v1/blocks/luck
+///
+/// Returns pool luck over the last 7 and 30 days and all time
+pub async fn get_block_luck(
+    State(state): State<super::ObserverState>,
+) -> Result<Json<LuckStats>, ObserverError> {
+    Ok(Json(state.db.get_block_luck().await?))
 }
 
 /// GET /api/v1/blocks/:height
@@ -133,22 +297,126 @@ pub async fn get_block_detail(
     Path(height): Path<i64>,
 ) -> Result<Json<BlockDetail>, ObserverError> {
     match state.db.get_block_detail(height).await? {
-        Some(detail) => Ok(Json(detail)),
+        Some(mut detail) => {
+            detail.explorer_url = state.block_url(height);
+            Ok(Json(detail))
+        }
         None => Err(ObserverError::NotFound(format!("Block not found: {}", height))),
     }
 }
 
 // ============================================================================
-// Helper Functions
+// Farm Account Endpoints
 // ============================================================================
 
-/// Validate Bitcoin address (basic check)
-fn is_valid_bitcoin_address(address: &str) -> bool {
-    // Basic validation - should use proper Bitcoin address validation
-    // Prefixes: bc1 (Bech32), 1 (P2PKH), 3 (P2SH)
-    address.starts_with("bc1") || address.starts_with("1") || address.starts_with("3")
+/// Query parameters for account earnings export
+#[derive(Debug, Deserialize)]
+pub struct EarningsQuery {
+    pub limit: Option<i64>,
+    /// "json" (default) or "csv"
+    pub format: Option<String>,
+}
+
+/// Get the account manager or fail if accounts are not enabled
+fn account_manager(state: &super::ObserverState) -> Result<&AccountManager, ObserverError> {
+    state.accounts.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Miner accounts are not enabled".to_string()))
 }
 
+/// GET /api/v1/accounts/:name
+///
+/// Returns statistics aggregated across all addresses of a farm account
+pub async fn get_account_stats(
+    State(state): State<super::ObserverState>,
+    Path(name): Path<String>,
+) -> Result<Json<AccountStats>, ObserverError> {
+    let accounts = account_manager(&state)?;
+    if accounts.get_account(&name).await.is_none() {
+        return Err(ObserverError::NotFound(format!("Account not found: {}", name)));
+    }
+
+    Ok(Json(accounts.aggregate_stats(&name, &state.db).await?))
+}
+
+/// GET /api/v1/accounts/:name/earnings?limit=500&format=csv
+///
+/// Returns combined block earnings of all addresses of a farm account
+pub async fn get_account_earnings(
+    State(state): State<super::ObserverState>,
+    Path(name): Path<String>,
+    Query(query): Query<EarningsQuery>,
+) -> Result<Response, ObserverError> {
+    let accounts = account_manager(&state)?;
+    if accounts.get_account(&name).await.is_none() {
+        return Err(ObserverError::NotFound(format!("Account not found: {}", name)));
+    }
+
+    let limit = query.limit.unwrap_or(500).clamp(1, 10_000);
+    let earnings = accounts.combined_earnings(&name, &state.db, limit).await?;
+
+    if query.format.as_deref() == Some("csv") {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}_earnings.csv\"", name)),
+            ],
+            earnings_csv(&earnings),
+        ).into_response());
+    }
+
+    Ok(Json(earnings).into_response())
+}
+
+// ============================================================================
+// Solo Mining Endpoints
+// ============================================================================
+
+/// GET /api/v1/solo/:address
+///
+/// Returns solo mining statistics for a miner (best share, expected time to block)
+pub async fn get_solo_stats(
+    State(state): State<super::ObserverState>,
+    Path(address): Path<String>,
+) -> Result<Json<SoloStatsResponse>, ObserverError> {
+    let solo = state.solo.as_ref()
+        .ok_or_else(|| ObserverError::NotFound("Solo mining mode is not enabled".to_string()))?;
+
+    if !state.is_valid_address(&address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+
+    let stats = solo.get_summary(&address).await
+        .ok_or_else(|| ObserverError::NotFound(format!("Solo miner not found: {}", address)))?;
+    let blocks = solo.get_blocks_for_address(&address).await;
+
+    Ok(Json(SoloStatsResponse { stats, blocks }))
+}
+
+/// Response for solo miner statistics
+#[derive(Debug, Serialize)]
+pub struct SoloStatsResponse {
+    pub stats: SoloStatsSummary,
+    pub blocks: Vec<SoloBlock>,
+}
+
+/// GET /api/v1/solo/blocks?limit=20&offset=0
+///
+/// Returns recent blocks found in solo mode with their attributed miner
+pub async fn get_solo_blocks(
+    State(state): State<super::ObserverState>,
+    Query(query): Query<PaginationQuery>,
+) -> Result<Json<Vec<SoloBlock>>, ObserverError> {
+    let solo = state.solo.as_ref()
+        .ok_or_else(|| ObserverError::NotFound("Solo mining mode is not enabled".to_string()))?;
+
+    let (limit, offset) = query.page();
+    Ok(Json(solo.get_blocks(limit as usize, offset as usize).await))
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
 /// Parse period string to days
 fn parse_period(period: &str) -> Option<i64> {
     match period {
@@ -167,6 +435,12 @@ fn parse_period(period: &str) -> Option<i64> {
 // Module Re-exports
 // ============================================================================
 
+pub mod announcements;
 pub mod blocks;
+pub mod earnings;
 pub mod miners;
+pub mod notifications;
+pub mod ownership;
 pub mod pool;
+pub mod referrals;
+pub mod tokens;
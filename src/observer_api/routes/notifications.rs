@@ -0,0 +1,52 @@
+// Miner notification endpoints
+//
+// Payout notification preferences for the address of the caller's API token
+
+use super::super::error::ObserverError;
+use super::super::middleware::MinerIdentity;
+use super::super::ObserverState;
+use axum::{extract::State, Json};
+
+use crate::miner_notify::{MinerNotifier, NotificationPreferences};
+
+/// Get the notifier or fail if miner notifications are not enabled
+fn notifier(state: &ObserverState) -> Result<&MinerNotifier, ObserverError> {
+    state.notifications.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Miner notifications are not enabled".to_string()))
+}
+
+/// GET /api/v1/me/notifications (token required)
+///
+/// Returns the caller's preferences and the channels the pool offers
+pub async fn get_notifications(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+) -> Result<Json<serde_json::Value>, ObserverError> {
+    let notifier = notifier(&state)?;
+    Ok(Json(serde_json::json!({
+        "channels": notifier.channels(),
+        "preferences": notifier.get(&miner.address).await,
+    })))
+}
+
+/// PUT /api/v1/me/notifications (token required)
+///
+/// Replaces the caller's preferences
+pub async fn update_notifications(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+    Json(preferences): Json<NotificationPreferences>,
+) -> Result<Json<NotificationPreferences>, ObserverError> {
+    Ok(Json(notifier(&state)?.set(&miner.address, preferences).await?))
+}
+
+/// DELETE /api/v1/me/notifications (token required)
+///
+/// Stops payout notifications for the caller
+pub async fn delete_notifications(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+) -> Result<Json<serde_json::Value>, ObserverError> {
+    let removed = notifier(&state)?.remove(&miner.address).await?;
+    Ok(Json(serde_json::json!({ "success": removed })))
+}
@@ -0,0 +1,67 @@
+// Address ownership endpoints
+//
+// Signed-message challenges (BIP-322 or legacy signmessage) that record an
+// address as controlled by the caller
+
+use super::super::error::ObserverError;
+use super::super::ObserverState;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::ownership::{OwnershipChallenge, OwnershipManager, VerifiedAddress};
+
+#[derive(Debug, Deserialize)]
+pub struct OwnershipChallengeRequest {
+    pub address: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyOwnershipRequest {
+    pub address: String,
+    /// Base64 BIP-322 or legacy signature of the challenge message
+    pub signature: String,
+}
+
+/// Get the ownership manager or fail if verification is not enabled
+fn ownership_manager(state: &ObserverState) -> Result<&OwnershipManager, ObserverError> {
+    state.ownership.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Address verification is not enabled".to_string()))
+}
+
+/// POST /api/v1/ownership/challenge
+///
+/// Returns a single-use message to sign with the address key
+pub async fn create_ownership_challenge(
+    State(state): State<ObserverState>,
+    Json(req): Json<OwnershipChallengeRequest>,
+) -> Result<Json<OwnershipChallenge>, ObserverError> {
+    if !state.is_valid_address(&req.address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+    Ok(Json(ownership_manager(&state)?.challenge(&req.address).await))
+}
+
+/// POST /api/v1/ownership/verify
+///
+/// Checks the signed challenge and records the address as verified
+pub async fn verify_ownership(
+    State(state): State<ObserverState>,
+    Json(req): Json<VerifyOwnershipRequest>,
+) -> Result<Json<VerifiedAddress>, ObserverError> {
+    Ok(Json(ownership_manager(&state)?.verify(&req.address, &req.signature).await?))
+}
+
+/// GET /api/v1/ownership/:address
+///
+/// Returns when the address was verified
+pub async fn get_ownership(
+    State(state): State<ObserverState>,
+    Path(address): Path<String>,
+) -> Result<Json<VerifiedAddress>, ObserverError> {
+    ownership_manager(&state)?.get(&address).await
+        .map(Json)
+        .ok_or_else(|| ObserverError::NotFound(format!("Address not verified: {}", address)))
+}
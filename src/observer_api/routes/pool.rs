@@ -1 +1,20 @@
-// Placeholder - will be implemented
+// Live pool endpoints
+//
+// Current stratum activity bridged from the p2poolv2 metrics
+
+use super::super::error::ObserverError;
+use super::super::ObserverState;
+use axum::{extract::State, Json};
+
+use crate::stratum_stats::LiveStats;
+
+/// GET /api/v1/pool/live
+///
+/// Returns current worker counts and share rates
+pub async fn get_live_stats(
+    State(state): State<ObserverState>,
+) -> Result<Json<LiveStats>, ObserverError> {
+    let stats = state.stratum_stats.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Live stratum statistics are not available".to_string()))?;
+    Ok(Json(stats.current().await))
+}
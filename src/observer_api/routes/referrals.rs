@@ -0,0 +1,65 @@
+// Miner referral endpoints
+//
+// Referral codes, referrer and referral credits for the address of the
+// caller's API token
+
+use super::super::error::ObserverError;
+use super::super::middleware::MinerIdentity;
+use super::super::ObserverState;
+use axum::{extract::State, Json};
+use serde::Deserialize;
+
+use crate::referrals::{LinkSource, ReferralCode, ReferralLink, ReferralProgram, ReferralSummary};
+
+/// Credits listed in a miner's referral summary
+const SUMMARY_CREDITS: usize = 100;
+
+/// Get the referral program or fail if it is not enabled
+fn referrals(state: &ObserverState) -> Result<&ReferralProgram, ObserverError> {
+    state.referrals.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("Referral program is not enabled".to_string()))
+}
+
+/// GET /api/v1/me/referrals (token required)
+///
+/// Returns the caller's codes, referrer, referred miners and latest credits
+pub async fn get_referrals(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+) -> Result<Json<ReferralSummary>, ObserverError> {
+    Ok(Json(referrals(&state)?.summary(&miner.address, SUMMARY_CREDITS).await))
+}
+
+#[derive(Deserialize)]
+pub struct CreateCodeRequest {
+    /// Random when not given
+    pub code: Option<String>,
+}
+
+/// POST /api/v1/me/referrals/codes (token required)
+pub async fn create_code(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+    Json(req): Json<CreateCodeRequest>,
+) -> Result<Json<ReferralCode>, ObserverError> {
+    Ok(Json(referrals(&state)?.create_code(&miner.address, req.code.as_deref()).await?))
+}
+
+#[derive(Deserialize)]
+pub struct SetReferrerRequest {
+    pub code: String,
+}
+
+/// PUT /api/v1/me/referrals/referrer (token required)
+///
+/// Links the caller to the owner of a code, within the self-service period
+pub async fn set_referrer(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+    Json(req): Json<SetReferrerRequest>,
+) -> Result<Json<ReferralLink>, ObserverError> {
+    let link = referrals(&state)?
+        .link(&miner.address, &req.code, LinkSource::SelfService, chrono::Utc::now())
+        .await?;
+    Ok(Json(link))
+}
@@ -0,0 +1,131 @@
+// Miner API token endpoints
+//
+// Token issuance after a signed ownership challenge, token management, and
+// per-miner data for the token's address
+
+use super::super::error::ObserverError;
+use super::super::middleware::MinerIdentity;
+use super::super::ObserverState;
+use super::EarningsQuery;
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::accounts::earnings_csv;
+use crate::api_tokens::{IssuedToken, MinerApiToken, MinerTokenManager, TokenChallenge};
+use crate::db::MinerStats;
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeRequest {
+    pub address: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueTokenRequest {
+    pub address: String,
+    /// Signature of the challenge message by the address key
+    pub signature: String,
+    pub label: Option<String>,
+}
+
+/// Get the token manager or fail if tokens are not enabled
+fn token_manager(state: &ObserverState) -> Result<&MinerTokenManager, ObserverError> {
+    state.tokens.as_deref()
+        .ok_or_else(|| ObserverError::NotFound("API tokens are not enabled".to_string()))
+}
+
+/// POST /api/v1/tokens/challenge
+///
+/// Returns a single-use message to sign with the address key
+pub async fn create_token_challenge(
+    State(state): State<ObserverState>,
+    Json(req): Json<ChallengeRequest>,
+) -> Result<Json<TokenChallenge>, ObserverError> {
+    if !state.is_valid_address(&req.address) {
+        return Err(ObserverError::InvalidInput("Invalid Bitcoin address".to_string()));
+    }
+    Ok(Json(token_manager(&state)?.challenge(&req.address).await))
+}
+
+/// POST /api/v1/tokens
+///
+/// Exchanges a signed challenge for a token; the token is only shown here
+pub async fn issue_token(
+    State(state): State<ObserverState>,
+    Json(req): Json<IssueTokenRequest>,
+) -> Result<Json<IssuedToken>, ObserverError> {
+    if req.label.as_ref().is_some_and(|label| label.len() > 64) {
+        return Err(ObserverError::InvalidInput("Token label must be at most 64 characters".to_string()));
+    }
+    Ok(Json(token_manager(&state)?.issue(&req.address, &req.signature, req.label).await?))
+}
+
+/// GET /api/v1/tokens (token required)
+///
+/// Returns the tokens of the caller's address
+pub async fn list_tokens(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+) -> Result<Json<Vec<MinerApiToken>>, ObserverError> {
+    Ok(Json(token_manager(&state)?.list(&miner.address).await))
+}
+
+/// DELETE /api/v1/tokens/:id (token required)
+///
+/// Revokes one of the caller's tokens, including the one in use
+pub async fn revoke_token(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ObserverError> {
+    if !token_manager(&state)?.revoke(&miner.address, &id).await? {
+        return Err(ObserverError::NotFound(format!("Active token not found: {}", id)));
+    }
+    Ok(Json(serde_json::json!({ "success": true, "id": id })))
+}
+
+/// GET /api/v1/me (token required)
+///
+/// Returns statistics for the token's address
+pub async fn get_my_stats(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+) -> Result<Json<MinerStats>, ObserverError> {
+    match state.db.get_miner_stats(&miner.address).await? {
+        Some(mut stats) => {
+            stats.explorer_url = state.address_url(&miner.address);
+            state.fill_estimates(&mut stats).await;
+            state.fill_loyalty(&mut stats).await;
+            Ok(Json(stats))
+        }
+        None => Err(ObserverError::NotFound(format!("Miner not found: {}", miner.address))),
+    }
+}
+
+/// GET /api/v1/me/earnings?limit=500&format=csv (token required)
+///
+/// Returns per-block earnings of the token's address
+pub async fn get_my_earnings(
+    State(state): State<ObserverState>,
+    miner: MinerIdentity,
+    Query(query): Query<EarningsQuery>,
+) -> Result<Response, ObserverError> {
+    let limit = query.limit.unwrap_or(500).clamp(1, 10_000);
+    let earnings = state.db.get_earnings_for_addresses(&[miner.address.clone()], limit).await?;
+
+    if query.format.as_deref() == Some("csv") {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}_earnings.csv\"", miner.address)),
+            ],
+            earnings_csv(&earnings),
+        ).into_response());
+    }
+
+    Ok(Json(earnings).into_response())
+}
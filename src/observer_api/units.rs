@@ -0,0 +1,277 @@
+// Observer API unit formatting
+//
+// Hashrates and amounts are served as raw numbers by default. With
+// `?units=human` they become display strings ("1.23 TH/s", "0.00123456 BTC")
+// and with `?units=both` an object carrying the raw value and the display
+// string. Response structs opt fields in with the serializer helpers below;
+// the representation for the current request is set by `units_middleware`,
+// so the same structs still serialize as plain numbers everywhere else.
+
+use axum::{
+    extract::{Query, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::error::ObserverError;
+use super::ObserverState;
+use crate::earnings::HASHES_PER_DIFFICULTY;
+
+const HASHRATE_UNITS: [&str; 7] = ["H/s", "KH/s", "MH/s", "GH/s", "TH/s", "PH/s", "EH/s"];
+
+/// How hashrate and amount fields are represented
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitStyle {
+    /// Plain numbers, as stored
+    #[default]
+    Raw,
+    /// Display strings
+    Human,
+    /// `{"raw": .., "display": ..}`
+    Both,
+}
+
+/// Unit human-readable hashrates are shown in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashrateUnit {
+    /// Largest unit that keeps the value at or above 1
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "H/s")]
+    Hashes,
+    #[serde(rename = "KH/s")]
+    Kilo,
+    #[serde(rename = "MH/s")]
+    Mega,
+    #[serde(rename = "GH/s")]
+    Giga,
+    #[serde(rename = "TH/s")]
+    Tera,
+    #[serde(rename = "PH/s")]
+    Peta,
+    #[serde(rename = "EH/s")]
+    Exa,
+}
+
+impl HashrateUnit {
+    /// Power of 1000 of a fixed unit
+    fn exponent(self) -> Option<usize> {
+        match self {
+            Self::Auto => None,
+            Self::Hashes => Some(0),
+            Self::Kilo => Some(1),
+            Self::Mega => Some(2),
+            Self::Giga => Some(3),
+            Self::Tera => Some(4),
+            Self::Peta => Some(5),
+            Self::Exa => Some(6),
+        }
+    }
+}
+
+/// Unit human-readable amounts are shown in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountUnit {
+    #[default]
+    Btc,
+    Sat,
+}
+
+/// The `[dmpool.observer_units]` settings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnitsConfig {
+    /// Representation when a request has no `units` parameter
+    pub style: UnitStyle,
+    pub hashrate_unit: HashrateUnit,
+    pub amount_unit: AmountUnit,
+}
+
+tokio::task_local! {
+    static UNITS: UnitsConfig;
+}
+
+/// Units of the request being served, or raw numbers outside a request
+fn current() -> UnitsConfig {
+    UNITS.try_with(|units| *units).unwrap_or_default()
+}
+
+/// Query parameters selecting the representation
+#[derive(Debug, Deserialize)]
+pub struct UnitsQuery {
+    pub units: Option<UnitStyle>,
+    pub hashrate_unit: Option<HashrateUnit>,
+    pub amount_unit: Option<AmountUnit>,
+}
+
+/// Apply `?units=raw|human|both` (and optional unit overrides) to the response
+pub async fn units_middleware(
+    State(state): State<ObserverState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ObserverError> {
+    let Query(query) = Query::<UnitsQuery>::try_from_uri(req.uri())
+        .map_err(|e| ObserverError::InvalidInput(format!("Invalid unit selection: {}", e.body_text())))?;
+    let units = UnitsConfig {
+        style: query.units.unwrap_or(state.units.style),
+        hashrate_unit: query.hashrate_unit.unwrap_or(state.units.hashrate_unit),
+        amount_unit: query.amount_unit.unwrap_or(state.units.amount_unit),
+    };
+    Ok(UNITS.scope(units, next.run(req)).await)
+}
+
+/// Format a hashrate in H/s
+pub fn format_hashrate(hashes_per_second: f64, unit: HashrateUnit) -> String {
+    let exponent = unit.exponent().unwrap_or_else(|| {
+        let mut exponent = 0;
+        let mut value = hashes_per_second.abs();
+        while value >= 1000.0 && exponent < HASHRATE_UNITS.len() - 1 {
+            value /= 1000.0;
+            exponent += 1;
+        }
+        exponent
+    });
+    let value = hashes_per_second / 1000f64.powi(exponent as i32);
+    format!("{:.2} {}", value, HASHRATE_UNITS[exponent])
+}
+
+/// Format an amount in satoshis
+pub fn format_satoshis(satoshis: f64, unit: AmountUnit) -> String {
+    match unit {
+        AmountUnit::Btc => format!("{:.8} BTC", satoshis / 100_000_000.0),
+        AmountUnit::Sat => format!("{} sat", group_thousands(satoshis.round() as i64)),
+    }
+}
+
+fn group_thousands(value: i64) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    if value < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Numeric fields the serializer helpers accept
+pub trait UnitValue: Serialize {
+    /// None for missing values, which stay null
+    fn as_f64(&self) -> Option<f64>;
+}
+
+macro_rules! unit_value {
+    ($($t:ty),*) => {
+        $(impl UnitValue for $t {
+            fn as_f64(&self) -> Option<f64> {
+                Some(*self as f64)
+            }
+        })*
+    };
+}
+
+unit_value!(u32, u64, i64, f64);
+
+impl<T: UnitValue> UnitValue for Option<T> {
+    fn as_f64(&self) -> Option<f64> {
+        self.as_ref().and_then(UnitValue::as_f64)
+    }
+}
+
+fn serialize_unit<T, S>(value: &T, serializer: S, display: impl FnOnce(f64, &UnitsConfig) -> String) -> Result<S::Ok, S::Error>
+where
+    T: UnitValue,
+    S: Serializer,
+{
+    let units = current();
+    let Some(number) = value.as_f64() else {
+        return value.serialize(serializer);
+    };
+    match units.style {
+        UnitStyle::Raw => value.serialize(serializer),
+        UnitStyle::Human => serializer.serialize_str(&display(number, &units)),
+        UnitStyle::Both => {
+            let mut state = serializer.serialize_struct("Unit", 2)?;
+            state.serialize_field("raw", value)?;
+            state.serialize_field("display", &display(number, &units))?;
+            state.end()
+        }
+    }
+}
+
+/// `serialize_with` helper for hashrates in H/s
+pub fn hashrate<T: UnitValue, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_unit(value, serializer, |h, units| format_hashrate(h, units.hashrate_unit))
+}
+
+/// `serialize_with` helper for hashrates stored as share difficulty per second
+pub fn difficulty_rate<T: UnitValue, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_unit(value, serializer, |d, units| format_hashrate(d * HASHES_PER_DIFFICULTY, units.hashrate_unit))
+}
+
+/// `serialize_with` helper for amounts in satoshis
+pub fn satoshis<T: UnitValue, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_unit(value, serializer, |sats, units| format_satoshis(sats, units.amount_unit))
+}
+
+/// `serialize_with` helper for amounts in BTC
+pub fn btc<T: UnitValue, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serialize_unit(value, serializer, |btc, units| format_satoshis(btc * 100_000_000.0, units.amount_unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        #[serde(serialize_with = "hashrate")]
+        hashrate: f64,
+        #[serde(serialize_with = "satoshis")]
+        paid: Option<u64>,
+        #[serde(serialize_with = "btc")]
+        reward: f64,
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_hashrate(1_234_000_000_000.0, HashrateUnit::Auto), "1.23 TH/s");
+        assert_eq!(format_hashrate(950.0, HashrateUnit::Auto), "950.00 H/s");
+        assert_eq!(format_hashrate(1_234_000_000_000.0, HashrateUnit::Giga), "1234.00 GH/s");
+        assert_eq!(format_satoshis(123_456.0, AmountUnit::Btc), "0.00123456 BTC");
+        assert_eq!(format_satoshis(12_345_678.0, AmountUnit::Sat), "12,345,678 sat");
+        assert_eq!(format_satoshis(-1_000.0, AmountUnit::Sat), "-1,000 sat");
+    }
+
+    #[tokio::test]
+    async fn test_style_follows_request_scope() {
+        let sample = Sample { hashrate: 2_500_000.0, paid: Some(5_000), reward: 3.125 };
+
+        // Outside a request everything stays raw
+        assert_eq!(
+            serde_json::to_value(&sample).unwrap(),
+            serde_json::json!({"hashrate": 2_500_000.0, "paid": 5_000, "reward": 3.125}),
+        );
+
+        let human = UnitsConfig { style: UnitStyle::Human, ..Default::default() };
+        let value = UNITS.scope(human, async { serde_json::to_value(&sample).unwrap() }).await;
+        assert_eq!(value["hashrate"], "2.50 MH/s");
+        assert_eq!(value["paid"], "0.00005000 BTC");
+        assert_eq!(value["reward"], "3.12500000 BTC");
+
+        let both = UnitsConfig { style: UnitStyle::Both, amount_unit: AmountUnit::Sat, ..Default::default() };
+        let missing = Sample { paid: None, ..sample };
+        let value = UNITS.scope(both, async { serde_json::to_value(&missing).unwrap() }).await;
+        assert_eq!(value["reward"], serde_json::json!({"raw": 3.125, "display": "312,500,000 sat"}));
+        assert!(value["paid"].is_null());
+    }
+}
@@ -0,0 +1,326 @@
+// Observer API versions
+//
+// Every route is served under `/api/v1` with its original response shape and
+// under `/api/v2`, where `v2_middleware` rewrites the same response: fields
+// named after a unit or an internal window get clearer names, bodies are
+// wrapped in a `{"data": ..}` envelope, paginated lists carry a `pagination`
+// object and errors become `{"error": {"code": .., "message": ..}}`.
+//
+// Routes of either version can be marked deprecated in the config, which adds
+// `Deprecation`, `Sunset` and `Link` headers to their responses.
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::error::ObserverError;
+use super::routes::PaginationQuery;
+use super::ObserverState;
+
+pub const V1_PREFIX: &str = "/api/v1";
+pub const V2_PREFIX: &str = "/api/v2";
+
+/// Largest response body the v2 envelope is applied to
+const MAX_REWRITE_BYTES: usize = 16 * 1024 * 1024;
+
+/// v1 field names and their v2 replacements
+///
+/// Amounts follow `?units=`, so their names no longer carry a unit.
+pub const V2_FIELD_NAMES: &[(&str, &str)] = &[
+    ("reward_btc", "reward"),
+    ("pool_fee_btc", "pool_fee"),
+    ("amount_btc", "amount"),
+    ("pool_hashrate_3h", "hashrate_3h"),
+    ("estimated_reward_window", "estimated_reward_7d"),
+    ("shares_in_window", "window_shares"),
+    ("payouts_count", "payout_count"),
+    ("balance_sats", "balance"),
+    ("next_block_eta_seconds", "next_block_eta_secs"),
+];
+
+/// Paginated routes (without version prefix) and the field holding their items
+///
+/// `None` means the v1 response is the bare list.
+const PAGINATED_ROUTES: &[(&str, Option<&str>)] = &[
+    ("/blocks", Some("blocks")),
+    ("/solo/blocks", None),
+];
+
+/// A deprecated route or group of routes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteDeprecation {
+    /// Route pattern such as "/api/v1/blocks/:height", or a prefix ending in "/*"
+    pub route: String,
+    /// When the route was deprecated; `Deprecation: true` without it
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// When the route stops being served
+    #[serde(default)]
+    pub sunset: Option<DateTime<Utc>>,
+    /// Page describing the deprecation and how to migrate
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+impl RouteDeprecation {
+    fn matches(&self, route: &str) -> bool {
+        match self.route.strip_suffix("/*") {
+            Some(prefix) => route == prefix || route.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/')),
+            None => route == self.route,
+        }
+    }
+}
+
+/// The `[dmpool.observer_versioning]` settings
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersioningConfig {
+    pub deprecations: Vec<RouteDeprecation>,
+}
+
+impl VersioningConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (i, deprecation) in self.deprecations.iter().enumerate() {
+            let route = deprecation.route.as_str();
+            if version_of(route).is_none() {
+                return Err(anyhow::anyhow!("deprecations[{}].route must start with {} or {}", i, V1_PREFIX, V2_PREFIX));
+            }
+            if self.deprecations[..i].iter().any(|d| d.route == route) {
+                return Err(anyhow::anyhow!("deprecations[{}].route {} is listed twice", i, route));
+            }
+            if let (Some(since), Some(sunset)) = (deprecation.since, deprecation.sunset) {
+                if sunset <= since {
+                    return Err(anyhow::anyhow!("deprecations[{}].sunset must be after since", i));
+                }
+            }
+            let link = deprecation.link.as_deref().unwrap_or("https://");
+            if !(link.starts_with("https://") || link.starts_with("http://") || link.starts_with('/')) {
+                return Err(anyhow::anyhow!("deprecations[{}].link must be an http(s) URL or an absolute path", i));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deprecation of a matched route, exact entries before prefixes
+    pub fn deprecation_for(&self, route: &str) -> Option<&RouteDeprecation> {
+        self.deprecations.iter().find(|d| d.route == route)
+            .or_else(|| self.deprecations.iter().find(|d| d.matches(route)))
+    }
+}
+
+/// Version prefix of a path, if any
+pub fn version_of(path: &str) -> Option<&'static str> {
+    [V1_PREFIX, V2_PREFIX].into_iter().find(|prefix| {
+        path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Path without its version prefix
+pub fn unversioned(path: &str) -> &str {
+    version_of(path).map_or(path, |prefix| &path[prefix.len()..])
+}
+
+/// Add deprecation headers to responses of deprecated routes
+pub async fn deprecation_middleware(
+    State(state): State<ObserverState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let deprecation = req.extensions().get::<MatchedPath>()
+        .and_then(|route| state.versioning.deprecation_for(route.as_str()))
+        .cloned();
+    let Some(deprecation) = deprecation else {
+        return next.run(req).await;
+    };
+    let path = req.uri().path().to_string();
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    let deprecated = deprecation.since.map_or_else(|| "true".to_string(), |since| format!("@{}", since.timestamp()));
+    if let Ok(value) = HeaderValue::from_str(&deprecated) {
+        headers.insert("deprecation", value);
+    }
+    if let Some(sunset) = deprecation.sunset {
+        if let Ok(value) = HeaderValue::from_str(&sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string()) {
+            headers.insert("sunset", value);
+        }
+    }
+    let mut links = Vec::new();
+    if let Some(link) = &deprecation.link {
+        links.push(format!("<{}>; rel=\"deprecation\"", link));
+    }
+    if version_of(&path) == Some(V1_PREFIX) {
+        links.push(format!("<{}{}>; rel=\"successor-version\"", V2_PREFIX, unversioned(&path)));
+    }
+    for link in links {
+        if let Ok(value) = HeaderValue::from_str(&link) {
+            headers.append(header::LINK, value);
+        }
+    }
+    response
+}
+
+/// Rewrite `/api/v2` JSON responses into the v2 shape
+pub async fn v2_middleware(req: Request, next: Next) -> Response {
+    if version_of(req.uri().path()) != Some(V2_PREFIX) {
+        return next.run(req).await;
+    }
+    let paginated = req.extensions().get::<MatchedPath>()
+        .and_then(|route| PAGINATED_ROUTES.iter().find(|(path, _)| *path == unversioned(route.as_str())))
+        .map(|(_, items)| *items);
+    let page = Query::<PaginationQuery>::try_from_uri(req.uri())
+        .map(|Query(query)| query.page())
+        .unwrap_or_else(|_| PaginationQuery::default().page());
+
+    let response = next.run(req).await;
+    let is_json = response.headers().get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_REWRITE_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => return ObserverError::Internal(format!("Failed to read response body: {}", e)).into_response(),
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let body = if parts.status.is_client_error() || parts.status.is_server_error() {
+        error_envelope(parts.status, value)
+    } else {
+        let value = rename_fields(value);
+        match paginated {
+            Some(items) => paginate(value, items, page),
+            None => json!({ "data": value }),
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+/// Rename v1 fields to their v2 names at every depth
+pub fn rename_fields(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(fields.into_iter()
+            .map(|(key, value)| {
+                let key = V2_FIELD_NAMES.iter()
+                    .find(|(v1, _)| *v1 == key)
+                    .map_or(key, |(_, v2)| v2.to_string());
+                (key, rename_fields(value))
+            })
+            .collect()),
+        Value::Array(items) => Value::Array(items.into_iter().map(rename_fields).collect()),
+        other => other,
+    }
+}
+
+/// `{"data": [..], "pagination": {..}, "meta": {..}}` for a page of a list
+fn paginate(value: Value, items: Option<&str>, (limit, offset): (i64, i64)) -> Value {
+    let (data, total, meta) = match (items, value) {
+        (Some(items), Value::Object(mut fields)) => {
+            let data = fields.remove(items).unwrap_or_else(|| Value::Array(Vec::new()));
+            let total = fields.remove("total").and_then(|total| total.as_i64());
+            (data, total, fields)
+        }
+        (_, value) => (value, None, Map::new()),
+    };
+    let count = data.as_array().map_or(0, Vec::len) as i64;
+    // Without a total a full page may be followed by more
+    let more = total.map_or(count == limit, |total| offset + count < total);
+    let mut envelope = json!({
+        "data": data,
+        "pagination": {
+            "limit": limit,
+            "offset": offset,
+            "total": total,
+            "next_offset": more.then_some(offset + count),
+        },
+    });
+    if !meta.is_empty() {
+        envelope["meta"] = Value::Object(meta);
+    }
+    envelope
+}
+
+/// `{"error": {"code": .., "message": .., ..}}` from a v1 error body
+fn error_envelope(status: StatusCode, value: Value) -> Value {
+    let mut fields = match value {
+        Value::Object(fields) => fields,
+        other => Map::from_iter([("message".to_string(), other)]),
+    };
+    // Rate limit errors carry `"status": "error"` instead of a code
+    fields.remove("status");
+    let code = match fields.remove("error") {
+        Some(Value::String(code)) => code,
+        _ => status.canonical_reason().unwrap_or("ERROR").to_uppercase().replace(' ', "_"),
+    };
+    let mut error = Map::from_iter([("code".to_string(), Value::String(code))]);
+    error.extend(fields);
+    json!({ "error": error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v2_shapes() {
+        let blocks = json!({
+            "total": 3,
+            "blocks": [{ "height": 1, "reward_btc": 3.125, "payouts_count": 4 }],
+            "luck": { "luck_7d": 98.0 },
+        });
+        assert_eq!(paginate(rename_fields(blocks), Some("blocks"), (1, 1)), json!({
+            "data": [{ "height": 1, "reward": 3.125, "payout_count": 4 }],
+            "pagination": { "limit": 1, "offset": 1, "total": 3, "next_offset": 2 },
+            "meta": { "luck": { "luck_7d": 98.0 } },
+        }));
+        // A short bare list is the last page
+        assert_eq!(paginate(json!([1, 2]), None, (20, 0))["pagination"]["next_offset"], Value::Null);
+
+        let limited = json!({ "status": "error", "message": "Too many requests", "retry_after": 60 });
+        assert_eq!(error_envelope(StatusCode::TOO_MANY_REQUESTS, limited), json!({
+            "error": { "code": "TOO_MANY_REQUESTS", "message": "Too many requests", "retry_after": 60 },
+        }));
+        let not_found = json!({ "error": "NOT_FOUND", "message": "Block not found: 5" });
+        assert_eq!(error_envelope(StatusCode::NOT_FOUND, not_found)["error"]["code"], "NOT_FOUND");
+    }
+
+    #[test]
+    fn test_deprecations() {
+        let config = VersioningConfig {
+            deprecations: vec![
+                RouteDeprecation { route: "/api/v1/*".to_string(), since: None, sunset: None, link: None },
+                RouteDeprecation {
+                    route: "/api/v1/blocks".to_string(),
+                    since: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+                    sunset: Some("2026-12-31T00:00:00Z".parse().unwrap()),
+                    link: Some("https://pool.example/docs/v2".to_string()),
+                },
+            ],
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.deprecation_for("/api/v1/blocks").unwrap().link.is_some());
+        assert_eq!(config.deprecation_for("/api/v1/stats/:address").unwrap().route, "/api/v1/*");
+        assert!(config.deprecation_for("/api/v2/blocks").is_none());
+        assert_eq!(unversioned("/api/v2/me/earnings"), "/me/earnings");
+        assert_eq!(version_of("/api/v10/stats"), None);
+
+        let mut invalid = config.clone();
+        invalid.deprecations[1].sunset = invalid.deprecations[1].since;
+        assert!(invalid.validate().is_err());
+        invalid.deprecations[1].route = "/blocks".to_string();
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,251 @@
+// BIP-322 Message Verification
+//
+// Checks "simple" BIP-322 signatures (a base64 witness stack) for P2WPKH and
+// P2TR key-path addresses, and legacy `signmessage` signatures for P2PKH and
+// P2WPKH addresses, without a round trip to the node.
+
+use crate::error::DmpoolError;
+use anyhow::Result;
+use base64::{Engine as _, engine::general_purpose};
+use bitcoin::{
+    absolute::LockTime,
+    consensus,
+    ecdsa,
+    hashes::{sha256, Hash, HashEngine},
+    key::CompressedPublicKey,
+    opcodes::all::OP_RETURN,
+    script::Builder,
+    secp256k1::{Message, Secp256k1, XOnlyPublicKey},
+    sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType},
+    sign_message::{signed_msg_hash, MessageSignature},
+    taproot,
+    transaction::Version,
+    Address, AddressType, Amount, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use std::str::FromStr;
+
+/// Signature format that was accepted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    Bip322,
+    Legacy,
+}
+
+/// BIP-322 tagged hash of a message
+fn message_hash(message: &str) -> [u8; 32] {
+    let tag = sha256::Hash::hash(b"BIP0322-signed-message");
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Virtual transaction committing to the message and the address
+fn to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    let script_sig = Builder::new()
+        .push_int(0)
+        .push_slice(message_hash(message))
+        .into_script();
+
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: Txid::all_zeros(), vout: 0xFFFFFFFF },
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: script_pubkey.to_owned() }],
+    }
+}
+
+/// Virtual transaction spending `to_spend` with the signature as its witness
+fn to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: to_spend.compute_txid(), vout: 0 },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+fn verify_p2wpkh(script_pubkey: &Script, message: &str, witness: Witness) -> bool {
+    if witness.len() != 2 {
+        return false;
+    }
+    let (Some(sig), Some(pubkey)) = (witness.nth(0), witness.nth(1)) else {
+        return false;
+    };
+    let Ok(sig) = ecdsa::Signature::from_slice(sig) else {
+        return false;
+    };
+    let Ok(pubkey) = CompressedPublicKey::from_slice(pubkey) else {
+        return false;
+    };
+    if sig.sighash_type != EcdsaSighashType::All
+        || ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != *script_pubkey
+    {
+        return false;
+    }
+
+    let to_spend = to_spend(script_pubkey, message);
+    let to_sign = to_sign(&to_spend, witness);
+    let Ok(sighash) = SighashCache::new(&to_sign)
+        .p2wpkh_signature_hash(0, script_pubkey, Amount::ZERO, EcdsaSighashType::All)
+    else {
+        return false;
+    };
+
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(sighash.to_byte_array()), &sig.signature, &pubkey.0)
+        .is_ok()
+}
+
+fn verify_p2tr(address: &Address, message: &str, witness: Witness) -> bool {
+    if witness.len() != 1 {
+        return false;
+    }
+    let Some(Ok(sig)) = witness.nth(0).map(taproot::Signature::from_slice) else {
+        return false;
+    };
+    if !matches!(sig.sighash_type, TapSighashType::Default | TapSighashType::All) {
+        return false;
+    }
+    let Some(Ok(output_key)) = address.witness_program()
+        .map(|program| XOnlyPublicKey::from_slice(program.program().as_bytes()))
+    else {
+        return false;
+    };
+
+    let to_spend = to_spend(&address.script_pubkey(), message);
+    let to_sign = to_sign(&to_spend, witness);
+    let Ok(sighash) = SighashCache::new(&to_sign)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(&to_spend.output), sig.sighash_type)
+    else {
+        return false;
+    };
+
+    Secp256k1::verification_only()
+        .verify_schnorr(&sig.signature, &Message::from_digest(sighash.to_byte_array()), &output_key)
+        .is_ok()
+}
+
+/// Legacy `signmessage` signature: 65-byte recoverable ECDSA over the
+/// "Bitcoin Signed Message" hash, accepted for P2PKH and (as most wallets
+/// produce it) P2WPKH addresses
+fn verify_legacy(address: &Address, message: &str, bytes: &[u8]) -> bool {
+    let Ok(sig) = MessageSignature::from_slice(bytes) else {
+        return false;
+    };
+    let Ok(pubkey) = sig.recover_pubkey(&Secp256k1::verification_only(), signed_msg_hash(message)) else {
+        return false;
+    };
+    match address.address_type() {
+        Some(AddressType::P2pkh) => address.pubkey_hash() == Some(pubkey.pubkey_hash()),
+        Some(AddressType::P2wpkh) => pubkey.compressed
+            && pubkey.wpubkey_hash().is_ok_and(|hash| ScriptBuf::new_p2wpkh(&hash) == address.script_pubkey()),
+        _ => false,
+    }
+}
+
+/// Verify a base64 signature of `message` by `address`
+///
+/// Returns the accepted format, or None if the signature does not match.
+/// Malformed addresses, addresses for another network and unsupported
+/// address types are input errors.
+pub fn verify_signature(address: &str, message: &str, signature: &str, network: Network) -> Result<Option<SignatureFormat>> {
+    let address = Address::from_str(address)
+        .map_err(|e| DmpoolError::InvalidInput(format!("Invalid address {}: {}", address, e)))?
+        .require_network(network)
+        .map_err(|e| DmpoolError::InvalidInput(format!("Address {} is not for {}: {}", address, network, e)))?;
+    let address_type = address.address_type();
+    if !matches!(address_type, Some(AddressType::P2pkh | AddressType::P2wpkh | AddressType::P2tr)) {
+        return Err(DmpoolError::InvalidInput(format!(
+            "Message signing is only supported for P2PKH, P2WPKH and P2TR addresses, not {}", address
+        )).into());
+    }
+
+    let Ok(bytes) = general_purpose::STANDARD.decode(signature.trim()) else {
+        return Ok(None);
+    };
+
+    // A 65-byte legacy signature cannot also be a valid witness stack for these types
+    if bytes.len() == 65 && verify_legacy(&address, message, &bytes) {
+        return Ok(Some(SignatureFormat::Legacy));
+    }
+
+    let Ok(witness) = consensus::deserialize::<Witness>(&bytes) else {
+        return Ok(None);
+    };
+    let valid = match address_type {
+        Some(AddressType::P2wpkh) => verify_p2wpkh(&address.script_pubkey(), message, witness),
+        Some(AddressType::P2tr) => verify_p2tr(&address, message, witness),
+        _ => false,
+    };
+    Ok(valid.then_some(SignatureFormat::Bip322))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::hex::DisplayHex;
+    use bitcoin::PublicKey;
+
+    // Test vectors from BIP-322
+    const P2WPKH_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const P2TR_ADDRESS: &str = "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3";
+
+    #[test]
+    fn test_bip322_vectors() {
+        assert_eq!(
+            message_hash("Hello World").to_lower_hex_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let empty = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        let hello = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        assert_eq!(verify_signature(P2WPKH_ADDRESS, "", empty, Network::Bitcoin).unwrap(), Some(SignatureFormat::Bip322));
+        assert_eq!(verify_signature(P2WPKH_ADDRESS, "Hello World", hello, Network::Bitcoin).unwrap(), Some(SignatureFormat::Bip322));
+        assert_eq!(verify_signature(P2WPKH_ADDRESS, "Hello World", empty, Network::Bitcoin).unwrap(), None);
+
+        let taproot = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+        assert_eq!(verify_signature(P2TR_ADDRESS, "Hello World", taproot, Network::Bitcoin).unwrap(), Some(SignatureFormat::Bip322));
+        assert_eq!(verify_signature(P2TR_ADDRESS, "Hello", taproot, Network::Bitcoin).unwrap(), None);
+
+        // Wrong network and unsupported types are rejected outright
+        assert!(verify_signature(P2WPKH_ADDRESS, "", empty, Network::Testnet).is_err());
+        assert!(verify_signature("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", "", empty, Network::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_legacy_signmessage() {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::new(&mut rand::thread_rng());
+        let pubkey = PublicKey::new(secret.public_key(&secp));
+        let sign = |message: &str| {
+            let msg = Message::from_digest(signed_msg_hash(message).to_byte_array());
+            let sig = MessageSignature::new(secp.sign_ecdsa_recoverable(&msg, &secret), true);
+            general_purpose::STANDARD.encode(sig.serialize())
+        };
+
+        let p2pkh = Address::p2pkh(pubkey, Network::Regtest).to_string();
+        let p2wpkh = Address::p2wpkh(&CompressedPublicKey(pubkey.inner), Network::Regtest).to_string();
+        let signature = sign("prove it");
+        assert_eq!(verify_signature(&p2pkh, "prove it", &signature, Network::Regtest).unwrap(), Some(SignatureFormat::Legacy));
+        assert_eq!(verify_signature(&p2wpkh, "prove it", &signature, Network::Regtest).unwrap(), Some(SignatureFormat::Legacy));
+        assert_eq!(verify_signature(&p2pkh, "prove it!", &signature, Network::Regtest).unwrap(), None);
+        assert_eq!(verify_signature(&p2pkh, "prove it", "not base64", Network::Regtest).unwrap(), None);
+    }
+}
@@ -0,0 +1,208 @@
+// Address Ownership Module for DMPool
+// Proves control of payout addresses with signed messages and records verified links
+//
+// A miner requests a challenge for an address, signs it with a BIP-322 or
+// legacy `signmessage` signature and submits it. Challenges are single-use and
+// expire; verified links are persisted so self-service features can check them
+// without asking for a new signature.
+
+pub mod bip322;
+
+use crate::accounts::OwnershipVerifier;
+use crate::error::DmpoolError;
+use crate::persist;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bitcoin::Network;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+pub use bip322::{verify_signature, SignatureFormat};
+
+/// Minutes a challenge stays valid
+const CHALLENGE_TTL_MINUTES: i64 = 10;
+
+/// Verifies signatures locally for the pool's network
+pub struct Bip322Verifier {
+    network: Network,
+}
+
+impl Bip322Verifier {
+    pub fn new(network: Network) -> Self {
+        Self { network }
+    }
+}
+
+#[async_trait]
+impl OwnershipVerifier for Bip322Verifier {
+    async fn verify(&self, address: &str, message: &str, signature: &str) -> Result<bool> {
+        Ok(verify_signature(address, message, signature, self.network)?.is_some())
+    }
+}
+
+/// Message to sign to prove control of an address
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OwnershipChallenge {
+    pub address: String,
+    pub message: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Address whose owner has signed a challenge
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerifiedAddress {
+    pub address: String,
+    pub verified_at: DateTime<Utc>,
+}
+
+/// Issues ownership challenges and keeps verified addresses
+pub struct OwnershipManager {
+    verifier: Arc<dyn OwnershipVerifier>,
+    data_dir: PathBuf,
+    /// Outstanding challenges by address
+    challenges: RwLock<HashMap<String, OwnershipChallenge>>,
+    verified: RwLock<HashMap<String, VerifiedAddress>>,
+}
+
+impl OwnershipManager {
+    /// Create an ownership manager persisting to `data_dir`
+    pub fn new(data_dir: PathBuf, verifier: Arc<dyn OwnershipVerifier>) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create ownership directory")?;
+
+        Ok(Self {
+            verifier,
+            data_dir,
+            challenges: RwLock::new(HashMap::new()),
+            verified: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Load persisted verified addresses
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("verified_addresses.json");
+        if let Some(verified) = persist::read_json::<HashMap<String, VerifiedAddress>>(&path).await
+            .context("Failed to load verified addresses file")?
+        {
+            info!("Loaded {} verified addresses", verified.len());
+            *self.verified.write().await = verified;
+        }
+        Ok(())
+    }
+
+    async fn save(&self, verified: &HashMap<String, VerifiedAddress>) -> Result<()> {
+        persist::write_json(&self.data_dir.join("verified_addresses.json"), verified).await
+            .context("Failed to write verified addresses file")
+    }
+
+    /// Start verification of an address, replacing any earlier challenge
+    pub async fn challenge(&self, address: &str) -> OwnershipChallenge {
+        let challenge = OwnershipChallenge {
+            address: address.to_string(),
+            message: format!("DMPool ownership of {} ({})", address, uuid::Uuid::new_v4().simple()),
+            expires_at: Utc::now() + Duration::minutes(CHALLENGE_TTL_MINUTES),
+        };
+        self.challenges.write().await.insert(address.to_string(), challenge.clone());
+        challenge
+    }
+
+    /// Check a signed challenge and record the address as verified
+    ///
+    /// The challenge is consumed whether or not the signature checks out.
+    pub async fn verify(&self, address: &str, signature: &str) -> Result<VerifiedAddress> {
+        let challenge = self.challenges.write().await.remove(address)
+            .filter(|c| c.expires_at > Utc::now())
+            .ok_or_else(|| DmpoolError::InvalidInput(format!("No pending challenge for {}", address)))?;
+
+        if !self.verifier.verify(address, &challenge.message, signature).await? {
+            return Err(DmpoolError::InvalidInput(format!("Invalid ownership signature for {}", address)).into());
+        }
+
+        let verified = VerifiedAddress {
+            address: address.to_string(),
+            verified_at: Utc::now(),
+        };
+        let mut all = self.verified.write().await;
+        all.insert(address.to_string(), verified.clone());
+        self.save(&all).await?;
+
+        info!("Verified ownership of {}", address);
+        Ok(verified)
+    }
+
+    /// Verification record of an address
+    pub async fn get(&self, address: &str) -> Option<VerifiedAddress> {
+        self.verified.read().await.get(address).cloned()
+    }
+
+    /// Whether the owner of an address has proven control
+    pub async fn is_verified(&self, address: &str) -> bool {
+        self.verified.read().await.contains_key(address)
+    }
+
+    /// Drop a verification record; false if the address was not verified
+    pub async fn remove(&self, address: &str) -> Result<bool> {
+        let mut all = self.verified.write().await;
+        if all.remove(address).is_none() {
+            return Ok(false);
+        }
+        self.save(&all).await?;
+
+        info!("Removed ownership verification of {}", address);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Accepts signatures of the form "sig:<address>:<message>"
+    struct FakeVerifier;
+
+    #[async_trait]
+    impl OwnershipVerifier for FakeVerifier {
+        async fn verify(&self, address: &str, message: &str, signature: &str) -> Result<bool> {
+            Ok(signature == format!("sig:{}:{}", address, message))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_consumes_challenge() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = OwnershipManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+
+        assert!(manager.verify("bc1qa", "sig").await.is_err());
+
+        let challenge = manager.challenge("bc1qa").await;
+        let signature = format!("sig:bc1qa:{}", challenge.message);
+        assert!(manager.verify("bc1qa", "sig:bc1qa:wrong").await.is_err());
+        assert!(manager.verify("bc1qa", &signature).await.is_err());
+        assert!(!manager.is_verified("bc1qa").await);
+
+        let challenge = manager.challenge("bc1qa").await;
+        manager.verify("bc1qa", &format!("sig:bc1qa:{}", challenge.message)).await.unwrap();
+        assert!(manager.is_verified("bc1qa").await);
+    }
+
+    #[tokio::test]
+    async fn test_verified_addresses_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = OwnershipManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+        let challenge = manager.challenge("bc1qa").await;
+        manager.verify("bc1qa", &format!("sig:bc1qa:{}", challenge.message)).await.unwrap();
+
+        let reloaded = OwnershipManager::new(temp_dir.path().to_path_buf(), Arc::new(FakeVerifier)).unwrap();
+        reloaded.load().await.unwrap();
+        assert!(reloaded.get("bc1qa").await.is_some());
+        assert!(reloaded.remove("bc1qa").await.unwrap());
+        assert!(!reloaded.remove("bc1qa").await.unwrap());
+        assert!(!reloaded.is_verified("bc1qa").await);
+    }
+}
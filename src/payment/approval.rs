@@ -0,0 +1,364 @@
+// Payout approvals
+//
+// Payouts above `payout_threshold_satoshis`, and payout runs whose total is
+// above `run_threshold_satoshis`, are held as pending approval requests instead
+// of being broadcast. A different admin from the one who asked for the
+// broadcast must approve before the transaction is built; requests expire
+// after `expiry_secs`. Requests are kept in a `BlobStore` so approvals survive
+// a restart between approval and broadcast, and so the pool's automatic runs
+// see decisions made in dmpool-admin. Every change re-reads the store first.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use crate::error::PaymentError;
+use crate::storage::{BlobStore, FileStore};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Decided requests kept in approvals.json
+const MAX_DECIDED_REQUESTS: usize = 1000;
+
+const APPROVALS_KEY: &str = "approvals.json";
+
+/// Approval thresholds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PayoutApprovalConfig {
+    pub enabled: bool,
+    /// Single payouts above this need a second admin
+    pub payout_threshold_satoshis: u64,
+    /// Payout runs totalling more than this need a second admin
+    pub run_threshold_satoshis: u64,
+    /// Seconds a request stays open
+    pub expiry_secs: u64,
+    /// Approvers confirm with a fresh 2FA code
+    pub require_two_factor: bool,
+}
+
+impl Default for PayoutApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            payout_threshold_satoshis: 10_000_000, // 0.1 BTC
+            run_threshold_satoshis: 100_000_000,   // 1 BTC
+            expiry_secs: 86400,
+            require_two_factor: true,
+        }
+    }
+}
+
+impl PayoutApprovalConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.expiry_secs == 0 {
+            return Err(anyhow::anyhow!("expiry_secs must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// What an approval covers
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApprovalSubject {
+    Payout { payout_id: String },
+    PayoutRun { payout_ids: Vec<String> },
+}
+
+impl ApprovalSubject {
+    pub fn covers(&self, payout_id: &str) -> bool {
+        match self {
+            Self::Payout { payout_id: id } => id == payout_id,
+            Self::PayoutRun { payout_ids } => payout_ids.iter().any(|id| id == payout_id),
+        }
+    }
+
+    pub fn payout_ids(&self) -> Vec<String> {
+        match self {
+            Self::Payout { payout_id } => vec![payout_id.clone()],
+            Self::PayoutRun { payout_ids } => payout_ids.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Expired,
+}
+
+/// A payout or payout run waiting for (or given) a second admin's decision
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub subject: ApprovalSubject,
+    pub amount_satoshis: u64,
+    pub requested_by: String,
+    pub requested_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub status: ApprovalStatus,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+}
+
+/// Pending and decided payout approval requests
+pub struct PayoutApprovals {
+    config: PayoutApprovalConfig,
+    store: Arc<dyn BlobStore>,
+    /// Last requests read from or written to the store
+    requests: RwLock<Vec<ApprovalRequest>>,
+}
+
+impl PayoutApprovals {
+    pub fn new(data_dir: PathBuf, config: PayoutApprovalConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create payout approval data directory")?;
+        Ok(Self {
+            config,
+            store: Arc::new(FileStore::new(data_dir)),
+            requests: RwLock::new(Vec::new()),
+        })
+    }
+
+    /// Keep requests in `store`, shared by every process that approves or holds payouts
+    pub fn with_store(mut self, store: Arc<dyn BlobStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn config(&self) -> &PayoutApprovalConfig {
+        &self.config
+    }
+
+    /// Load requests from the store
+    pub async fn load(&self) -> Result<()> {
+        let mut requests = self.requests.write().await;
+        self.reload(&mut requests).await?;
+        info!("Loaded {} payout approval requests", requests.len());
+        Ok(())
+    }
+
+    /// Replace `requests` with the stored ones, if any were written
+    async fn reload(&self, requests: &mut Vec<ApprovalRequest>) -> Result<()> {
+        if let Some(stored) = self.store.get_json::<Vec<ApprovalRequest>>(APPROVALS_KEY).await
+            .context("Failed to load approvals")?
+        {
+            *requests = stored;
+        }
+        Ok(())
+    }
+
+    /// Pick up requests and decisions made by other processes
+    pub async fn refresh(&self) -> Result<()> {
+        let mut requests = self.requests.write().await;
+        self.reload(&mut requests).await
+    }
+
+    /// `refresh`, keeping the last known requests if the store cannot be read
+    async fn refresh_or_warn(&self) {
+        if let Err(e) = self.refresh().await {
+            warn!("Failed to refresh payout approvals: {:#}", e);
+        }
+    }
+
+    async fn save(&self, requests: &[ApprovalRequest]) -> Result<()> {
+        self.store.put_json(APPROVALS_KEY, requests).await
+            .context("Failed to write approvals")
+    }
+
+    /// Whether a single payout of this amount needs approval
+    pub fn payout_needs_approval(&self, amount_satoshis: u64) -> bool {
+        self.config.enabled && amount_satoshis > self.config.payout_threshold_satoshis
+    }
+
+    /// Whether a payout run of this total needs approval
+    pub fn run_needs_approval(&self, total_satoshis: u64) -> bool {
+        self.config.enabled && total_satoshis > self.config.run_threshold_satoshis
+    }
+
+    /// Whether an approved request covers this payout
+    pub async fn is_approved(&self, payout_id: &str) -> bool {
+        self.requests.read().await.iter()
+            .any(|r| r.status == ApprovalStatus::Approved && r.subject.covers(payout_id))
+    }
+
+    /// The open request covering this payout, if any
+    pub async fn pending_for(&self, payout_id: &str) -> Option<ApprovalRequest> {
+        let now = Utc::now();
+        self.requests.read().await.iter()
+            .find(|r| r.status == ApprovalStatus::Pending && r.expires_at > now && r.subject.covers(payout_id))
+            .cloned()
+    }
+
+    /// Open a request, or return the open one already covering the same payouts
+    pub async fn request(&self, subject: ApprovalSubject, amount_satoshis: u64, requested_by: &str) -> Result<ApprovalRequest> {
+        let mut requests = self.requests.write().await;
+        self.reload(&mut requests).await?;
+        Self::expire(&mut requests, Utc::now());
+        if let Some(existing) = requests.iter().find(|r| r.status == ApprovalStatus::Pending && r.subject == subject) {
+            return Ok(existing.clone());
+        }
+
+        let now = Utc::now();
+        let request = ApprovalRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            subject,
+            amount_satoshis,
+            requested_by: requested_by.to_string(),
+            requested_at: now,
+            expires_at: now + Duration::seconds(self.config.expiry_secs as i64),
+            status: ApprovalStatus::Pending,
+            decided_by: None,
+            decided_at: None,
+            reason: None,
+        };
+        info!("Payout approval {} requested by {} for {} satoshis", request.id, requested_by, amount_satoshis);
+        requests.push(request.clone());
+        self.save(&requests).await?;
+        Ok(request)
+    }
+
+    /// Approve a pending request; the requester cannot approve their own
+    pub async fn approve(&self, id: &str, approver: &str) -> Result<ApprovalRequest> {
+        self.decide(id, approver, ApprovalStatus::Approved, None).await
+    }
+
+    /// Reject a pending request
+    pub async fn reject(&self, id: &str, approver: &str, reason: Option<String>) -> Result<ApprovalRequest> {
+        self.decide(id, approver, ApprovalStatus::Rejected, reason).await
+    }
+
+    async fn decide(&self, id: &str, approver: &str, status: ApprovalStatus, reason: Option<String>) -> Result<ApprovalRequest> {
+        let mut requests = self.requests.write().await;
+        self.reload(&mut requests).await?;
+        Self::expire(&mut requests, Utc::now());
+        let request = requests.iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| PaymentError::ApprovalNotFound(id.to_string()))?;
+        if request.status != ApprovalStatus::Pending {
+            return Err(PaymentError::ApprovalNotPending(id.to_string()).into());
+        }
+        if status == ApprovalStatus::Approved && request.requested_by == approver {
+            return Err(PaymentError::SelfApproval(id.to_string()).into());
+        }
+
+        request.status = status;
+        request.decided_by = Some(approver.to_string());
+        request.decided_at = Some(Utc::now());
+        request.reason = reason;
+        let request = request.clone();
+        info!("Payout approval {} {:?} by {}", id, status, approver);
+
+        let decided = requests.iter().filter(|r| r.status != ApprovalStatus::Pending).count();
+        if decided > MAX_DECIDED_REQUESTS {
+            let mut excess = decided - MAX_DECIDED_REQUESTS;
+            requests.retain(|r| {
+                let drop = excess > 0 && r.status != ApprovalStatus::Pending && r.id != request.id;
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+        }
+        self.save(&requests).await?;
+        Ok(request)
+    }
+
+    fn expire(requests: &mut [ApprovalRequest], now: DateTime<Utc>) {
+        for request in requests.iter_mut().filter(|r| r.status == ApprovalStatus::Pending && r.expires_at <= now) {
+            request.status = ApprovalStatus::Expired;
+        }
+    }
+
+    /// Requests still waiting for a decision
+    pub async fn pending(&self) -> Vec<ApprovalRequest> {
+        self.refresh_or_warn().await;
+        let now = Utc::now();
+        self.requests.read().await.iter()
+            .filter(|r| r.status == ApprovalStatus::Pending && r.expires_at > now)
+            .cloned()
+            .collect()
+    }
+
+    /// All requests, newest first
+    pub async fn list(&self, limit: usize) -> Vec<ApprovalRequest> {
+        self.refresh_or_warn().await;
+        self.requests.read().await.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub async fn get(&self, id: &str) -> Option<ApprovalRequest> {
+        self.refresh_or_warn().await;
+        self.requests.read().await.iter().find(|r| r.id == id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::kind_of;
+    use crate::error::ErrorKind;
+    use tempfile::TempDir;
+
+    fn approvals(temp_dir: &TempDir) -> PayoutApprovals {
+        PayoutApprovals::new(
+            temp_dir.path().to_path_buf(),
+            PayoutApprovalConfig { enabled: true, ..Default::default() },
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_second_admin_must_approve() {
+        let temp_dir = TempDir::new().unwrap();
+        let approvals = approvals(&temp_dir);
+        assert!(!approvals.payout_needs_approval(10_000_000));
+        assert!(approvals.payout_needs_approval(10_000_001));
+
+        let subject = ApprovalSubject::Payout { payout_id: "p1".to_string() };
+        let request = approvals.request(subject.clone(), 20_000_000, "alice").await.unwrap();
+        // Asking again reuses the open request
+        assert_eq!(approvals.request(subject, 20_000_000, "alice").await.unwrap().id, request.id);
+        assert!(!approvals.is_approved("p1").await);
+
+        let err = approvals.approve(&request.id, "alice").await.unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::Unauthorized);
+
+        let approved = approvals.approve(&request.id, "bob").await.unwrap();
+        assert_eq!(approved.decided_by.as_deref(), Some("bob"));
+        assert!(approvals.is_approved("p1").await);
+        assert_eq!(kind_of(&approvals.reject(&request.id, "carol", None).await.unwrap_err()), ErrorKind::Conflict);
+
+        // Decisions survive a restart
+        let reloaded = PayoutApprovals::new(temp_dir.path().to_path_buf(), approvals.config().clone()).unwrap();
+        reloaded.load().await.unwrap();
+        assert!(reloaded.is_approved("p1").await);
+    }
+
+    #[tokio::test]
+    async fn test_runs_and_expiry() {
+        let temp_dir = TempDir::new().unwrap();
+        let approvals = approvals(&temp_dir);
+        assert!(approvals.run_needs_approval(150_000_000));
+
+        let subject = ApprovalSubject::PayoutRun { payout_ids: vec!["p1".to_string(), "p2".to_string()] };
+        let run = approvals.request(subject, 150_000_000, "auto_payout").await.unwrap();
+        approvals.reject(&run.id, "bob", Some("unexpected total".to_string())).await.unwrap();
+        assert!(!approvals.is_approved("p2").await);
+        assert!(approvals.pending().await.is_empty());
+
+        let subject = ApprovalSubject::PayoutRun { payout_ids: vec!["p3".to_string()] };
+        let run = approvals.request(subject, 150_000_000, "auto_payout").await.unwrap();
+        {
+            let mut requests = approvals.requests.write().await;
+            requests.last_mut().unwrap().expires_at = Utc::now() - Duration::seconds(1);
+            approvals.save(&requests).await.unwrap();
+        }
+        assert_eq!(kind_of(&approvals.approve(&run.id, "bob").await.unwrap_err()), ErrorKind::Conflict);
+        assert_eq!(approvals.get(&run.id).await.unwrap().status, ApprovalStatus::Expired);
+    }
+}
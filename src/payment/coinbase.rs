@@ -0,0 +1,353 @@
+// Coinbase payouts
+//
+// Instead of crediting balances that are paid out later, miners can be paid
+// by outputs of the block's own coinbase transaction. The planner turns the
+// PPLNS window into the outputs a block template should carry: the largest
+// miners get an output each, up to `max_outputs` including the pool's own.
+// Miners below `min_output_satoshis`, or who do not fit, are folded into the
+// pool's output and credited as balances once the block is found, so the
+// regular payout runs pay them when they reach the threshold.
+
+use anyhow::{Context, Result};
+use bitcoin::address::{Address, NetworkUnchecked};
+use bitcoin::{Amount, Network, ScriptBuf, TxOut};
+use chrono::{DateTime, Utc};
+use crate::bitcoin::CoinbaseVout;
+use crate::block_events::{apply_fee_discounts, split_block_reward, BlockPayout};
+use crate::loyalty::{FeeDiscounts, LoyaltyTracker};
+use crate::pplns_validator::RoundingPolicy;
+use crate::pplns_window::{PplnsWindow, WindowSnapshot};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::{NetworkParams, DUST_LIMIT_SATOSHIS};
+
+/// Largest coinbase output count accepted; each P2WPKH output adds 31 vbytes to the block
+pub const MAX_COINBASE_OUTPUTS: usize = 500;
+
+/// Plans kept for matching found blocks against the template they came from
+const PLANS_KEPT: usize = 8;
+
+/// The `[dmpool.coinbase_payouts]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoinbasePayoutConfig {
+    pub enabled: bool,
+    /// Receives the pool fee, rounding and carried balances
+    pub pool_address: String,
+    /// Coinbase outputs including the pool's
+    pub max_outputs: usize,
+    /// Smaller miner shares are carried as balances
+    pub min_output_satoshis: u64,
+}
+
+impl Default for CoinbasePayoutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pool_address: String::new(),
+            max_outputs: 100,
+            min_output_satoshis: 100_000,  // 0.001 BTC
+        }
+    }
+}
+
+impl CoinbasePayoutConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.pool_address.trim().is_empty() {
+            return Err(anyhow::anyhow!("pool_address is required"));
+        }
+        self.pool_address.trim().parse::<Address<NetworkUnchecked>>()
+            .map_err(|e| anyhow::anyhow!("pool_address {} is not a valid address: {}", self.pool_address, e))?;
+        if self.max_outputs < 2 || self.max_outputs > MAX_COINBASE_OUTPUTS {
+            return Err(anyhow::anyhow!("max_outputs must be between 2 and {}", MAX_COINBASE_OUTPUTS));
+        }
+        if self.min_output_satoshis < DUST_LIMIT_SATOSHIS {
+            return Err(anyhow::anyhow!("min_output_satoshis must be at least the dust limit ({})", DUST_LIMIT_SATOSHIS));
+        }
+        Ok(())
+    }
+}
+
+/// An output for the block template's coinbase
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CoinbaseOutput {
+    pub address: String,
+    pub script_pubkey: ScriptBuf,
+    pub amount_satoshis: u64,
+}
+
+/// Coinbase outputs for one block height
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CoinbasePlan {
+    pub height: u64,
+    /// Coinbase value the plan splits (subsidy + fees)
+    pub reward_satoshis: u64,
+    /// Pool output first, then miners by amount
+    pub outputs: Vec<CoinbaseOutput>,
+    /// Miners with their own output
+    pub paid: Vec<BlockPayout>,
+    /// Miners credited as balances when the block is found
+    pub carried: Vec<BlockPayout>,
+    /// Pool fee plus rounding remainder
+    pub pool_fee_satoshis: u64,
+    pub window_shares: u64,
+    pub window_difficulty: u64,
+    pub computed_at: DateTime<Utc>,
+}
+
+impl CoinbasePlan {
+    pub fn carried_satoshis(&self) -> u64 {
+        self.carried.iter().map(|p| p.amount_satoshis).sum()
+    }
+
+    /// Outputs in the form the template builder adds to the coinbase
+    pub fn tx_outputs(&self) -> Vec<TxOut> {
+        self.outputs.iter()
+            .map(|o| TxOut { value: Amount::from_sat(o.amount_satoshis), script_pubkey: o.script_pubkey.clone() })
+            .collect()
+    }
+
+    /// Split the plan by what the found block's coinbase actually paid
+    ///
+    /// Returns the miners whose output is in the coinbase and those to
+    /// credit as balances: the carried miners plus any planned output
+    /// the block does not contain.
+    pub fn settle(&self, coinbase: &[CoinbaseVout]) -> (Vec<BlockPayout>, Vec<BlockPayout>) {
+        let mut unmatched: Vec<&CoinbaseVout> = coinbase.iter().collect();
+        let mut in_coinbase = Vec::new();
+        let mut credit = self.carried.clone();
+        for payout in &self.paid {
+            let output = self.outputs.iter().find(|o| o.address == payout.address);
+            let matched = output.and_then(|output| {
+                let script = output.script_pubkey.to_hex_string();
+                unmatched.iter().position(|vout| vout.script_pubkey_hex == script && vout.satoshis == output.amount_satoshis)
+            });
+            match matched {
+                Some(index) => {
+                    unmatched.swap_remove(index);
+                    in_coinbase.push(payout.clone());
+                }
+                None => credit.push(payout.clone()),
+            }
+        }
+        (in_coinbase, credit)
+    }
+}
+
+/// Plans coinbase outputs from the PPLNS window
+pub struct CoinbasePlanner {
+    config: CoinbasePayoutConfig,
+    network: NetworkParams,
+    pool_script: ScriptBuf,
+    rounding: RoundingPolicy,
+    loyalty: Option<Arc<LoyaltyTracker>>,
+    plans: RwLock<VecDeque<Arc<CoinbasePlan>>>,
+}
+
+impl CoinbasePlanner {
+    pub fn new(config: CoinbasePayoutConfig, network: Network) -> Result<Self> {
+        config.validate()?;
+        let network = NetworkParams::for_network(network);
+        let pool_script = network.parse_address(config.pool_address.trim())
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Invalid coinbase pool_address")?
+            .script_pubkey();
+        Ok(Self {
+            config,
+            network,
+            pool_script,
+            rounding: RoundingPolicy::default(),
+            loyalty: None,
+            plans: RwLock::new(VecDeque::new()),
+        })
+    }
+
+    /// Where rounding remainders from the split go (default: the pool)
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Rebate loyalty fee discounts in planned payouts
+    pub fn with_loyalty(mut self, loyalty: Arc<LoyaltyTracker>) -> Self {
+        self.loyalty = Some(loyalty);
+        self
+    }
+
+    pub fn config(&self) -> &CoinbasePayoutConfig {
+        &self.config
+    }
+
+    /// Plan outputs for a block at `height` from a window snapshot
+    pub fn plan(
+        &self,
+        snapshot: &WindowSnapshot,
+        height: u64,
+        reward_satoshis: u64,
+        pool_fee_bps: u32,
+        discounts: &FeeDiscounts,
+    ) -> CoinbasePlan {
+        let (mut payouts, pool_keeps) = split_block_reward(snapshot, reward_satoshis, pool_fee_bps, self.rounding, height);
+        let pool_fee_satoshis = apply_fee_discounts(
+            &mut payouts,
+            reward_satoshis,
+            snapshot.total_difficulty,
+            pool_fee_bps,
+            pool_keeps,
+            discounts,
+        );
+        let min_output = self.config.min_output_satoshis.max(DUST_LIMIT_SATOSHIS);
+
+        let mut outputs = Vec::new();
+        let mut paid = Vec::new();
+        let mut carried = Vec::new();
+        // Payouts come largest first, so the miners that fit are the biggest
+        for payout in payouts {
+            let script = match self.network.parse_address(&payout.address) {
+                Ok(address) => Some(address.script_pubkey()),
+                Err(e) => {
+                    warn!("Carrying coinbase payout as a balance: {}", e);
+                    None
+                }
+            };
+            match script {
+                Some(script_pubkey) if payout.amount_satoshis >= min_output && outputs.len() + 1 < self.config.max_outputs => {
+                    outputs.push(CoinbaseOutput {
+                        address: payout.address.clone(),
+                        script_pubkey,
+                        amount_satoshis: payout.amount_satoshis,
+                    });
+                    paid.push(payout);
+                }
+                _ => carried.push(payout),
+            }
+        }
+
+        let pool_amount = pool_fee_satoshis + carried.iter().map(|p| p.amount_satoshis).sum::<u64>();
+        if pool_amount > 0 {
+            outputs.insert(0, CoinbaseOutput {
+                address: self.config.pool_address.trim().to_string(),
+                script_pubkey: self.pool_script.clone(),
+                amount_satoshis: pool_amount,
+            });
+        }
+
+        CoinbasePlan {
+            height,
+            reward_satoshis,
+            outputs,
+            paid,
+            carried,
+            pool_fee_satoshis,
+            window_shares: snapshot.total_shares,
+            window_difficulty: snapshot.total_difficulty,
+            computed_at: Utc::now(),
+        }
+    }
+
+    /// Plan from the live window and remember the plan for when the block is found
+    pub async fn plan_window(&self, window: &PplnsWindow, height: u64, reward_satoshis: u64) -> Result<Arc<CoinbasePlan>> {
+        let snapshot = window.snapshot().await?;
+        let discounts = match &self.loyalty {
+            Some(loyalty) => loyalty.fee_discounts(Utc::now()).await,
+            None => FeeDiscounts::new(),
+        };
+        let plan = Arc::new(self.plan(&snapshot, height, reward_satoshis, window.pool_fee_bps(), &discounts));
+
+        let mut plans = self.plans.write().await;
+        plans.retain(|p| p.height != height);
+        plans.push_back(plan.clone());
+        while plans.len() > PLANS_KEPT {
+            plans.pop_front();
+        }
+        Ok(plan)
+    }
+
+    /// Latest plan issued for a height
+    pub async fn issued(&self, height: u64) -> Option<Arc<CoinbasePlan>> {
+        self.plans.read().await.iter().rev().find(|p| p.height == height).cloned()
+    }
+
+    /// Most recently issued plan
+    pub async fn latest(&self) -> Option<Arc<CoinbasePlan>> {
+        self.plans.read().await.back().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pplns_window::MinerWindowTotals;
+
+    const POOL: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    const MINER_A: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const MINER_B: &str = "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3";
+
+    fn snapshot(miners: &[(&str, u64)]) -> WindowSnapshot {
+        WindowSnapshot {
+            computed_at: Utc::now(),
+            window_start: 0,
+            window_end: 0,
+            total_shares: miners.len() as u64,
+            total_difficulty: miners.iter().map(|(_, d)| d).sum(),
+            truncated: false,
+            miners: miners.iter()
+                .map(|(address, difficulty)| (address.to_string(), MinerWindowTotals { share_count: 1, difficulty: *difficulty }))
+                .collect(),
+        }
+    }
+
+    fn planner(max_outputs: usize) -> CoinbasePlanner {
+        CoinbasePlanner::new(CoinbasePayoutConfig {
+            enabled: true,
+            pool_address: POOL.to_string(),
+            max_outputs,
+            min_output_satoshis: 100_000,
+        }, Network::Bitcoin).unwrap()
+    }
+
+    #[test]
+    fn test_plan_caps_outputs_and_carries_small_miners() {
+        // 1 BTC at 1%: A gets 59.4M, B 39.6M, the tiny and testnet miners are carried
+        let window = snapshot(&[(MINER_A, 6000), (MINER_B, 3999), ("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", 1)]);
+        let plan = planner(3).plan(&window, 800_000, 100_000_000, 100, &FeeDiscounts::new());
+
+        assert_eq!(plan.outputs.len(), 3);
+        assert_eq!(plan.outputs[0].address, POOL);
+        assert_eq!(plan.outputs[1].address, MINER_A);
+        assert_eq!(plan.carried.len(), 1);
+        assert_eq!(plan.outputs[0].amount_satoshis, plan.pool_fee_satoshis + plan.carried_satoshis());
+        assert_eq!(plan.tx_outputs().iter().map(|o| o.value.to_sat()).sum::<u64>(), 100_000_000);
+
+        // With room for only one miner, B is carried too
+        let plan = planner(2).plan(&window, 800_000, 100_000_000, 100, &FeeDiscounts::new());
+        assert_eq!(plan.paid.len(), 1);
+        assert_eq!(plan.carried.len(), 2);
+
+        assert!(CoinbasePlanner::new(CoinbasePayoutConfig {
+            pool_address: "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string(),
+            ..CoinbasePayoutConfig::default()
+        }, Network::Bitcoin).is_err());
+    }
+
+    #[test]
+    fn test_settle_credits_outputs_missing_from_block() {
+        let plan = planner(10).plan(&snapshot(&[(MINER_A, 1), (MINER_B, 1)]), 800_000, 100_000_000, 100, &FeeDiscounts::new());
+        let vout = |o: &CoinbaseOutput| CoinbaseVout {
+            script_pubkey_hex: o.script_pubkey.to_hex_string(),
+            satoshis: o.amount_satoshis,
+        };
+
+        let all: Vec<CoinbaseVout> = plan.outputs.iter().map(vout).collect();
+        let (in_coinbase, credit) = plan.settle(&all);
+        assert_eq!((in_coinbase.len(), credit.len()), (2, 0));
+
+        // A block built from another template only paid the pool
+        let (in_coinbase, credit) = plan.settle(&all[..1]);
+        assert_eq!((in_coinbase.len(), credit.len()), (0, 2));
+    }
+}
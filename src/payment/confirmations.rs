@@ -0,0 +1,106 @@
+// Payout confirmation tracking
+//
+// Broadcast payouts are rechecked whenever bitcoind announces a block on
+// ZMQ instead of on a timer. Each one's confirmations are read from the
+// wallet and recorded until `required_confirmations` marks it confirmed.
+
+use anyhow::{Context, Result};
+use crate::bitcoin::ZmqEvent;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use super::{PaymentManager, PayoutStatus};
+
+impl PaymentManager {
+    /// Re-read the confirmations of broadcast payouts; returns how many became confirmed
+    pub async fn refresh_confirmations(&self) -> Result<usize> {
+        let awaiting: Vec<(String, String, u32)> = self.payouts.read().await.iter()
+            .filter(|p| matches!(p.status, PayoutStatus::Broadcast | PayoutStatus::Stuck | PayoutStatus::Evicted))
+            .filter_map(|p| p.txid.clone().map(|txid| (p.id.clone(), txid, p.confirmations)))
+            .collect();
+        if awaiting.is_empty() {
+            return Ok(0);
+        }
+
+        let tip = self.bitcoin_client.get_blockchain_info().await
+            .context("Failed to read the chain tip")?
+            .blocks;
+        let required = self.config.read().await.required_confirmations;
+        let mut confirmed = 0;
+        for (payout_id, txid, previous) in awaiting {
+            let confirmations = match self.bitcoin_client.get_wallet_transaction_confirmations(&txid).await {
+                Ok(confirmations) => confirmations,
+                Err(e) => {
+                    warn!("Failed to read confirmations of payout {} ({}): {:#}", payout_id, txid, e);
+                    continue;
+                }
+            };
+            // Unmined, or conflicted (negative)
+            if confirmations <= 0 || confirmations as u32 == previous {
+                continue;
+            }
+            let height = (tip + 1).saturating_sub(confirmations as u64);
+            self.confirm_payout(&payout_id, txid, height, confirmations as u32).await?;
+            if confirmations as u32 >= required {
+                confirmed += 1;
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Refresh confirmations on every block bitcoind announces
+    pub fn track_confirmations(self: &Arc<Self>, mut zmq: broadcast::Receiver<ZmqEvent>) {
+        let payments = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match zmq.recv().await {
+                    Ok(ZmqEvent::Block { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        match payments.refresh_confirmations().await {
+                            Ok(0) => {}
+                            Ok(confirmed) => info!("{} payouts reached the required confirmations", confirmed),
+                            Err(e) => error!("Payout confirmation refresh failed: {:#}", e),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PaymentConfig;
+    use super::*;
+    use crate::bitcoin::mock::MockRpcServer;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    const ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    #[tokio::test]
+    async fn test_refresh_confirmations_until_required() {
+        let mock = MockRpcServer::start().await.unwrap();
+        mock.respond("listunspent", json!([
+            { "txid": "aa", "vout": 0, "address": ADDRESS, "amount": 0.1, "confirmations": 10 }
+        ]));
+        let temp_dir = TempDir::new().unwrap();
+        let config = PaymentConfig { bitcoin_rpc_url: mock.url().to_string(), ..PaymentConfig::default() };
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), config).unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 5_000_000, 123).await.unwrap();
+        let payout = manager.create_payout(ADDRESS.to_string(), 2_000_000).await.unwrap();
+        manager.broadcast_payout(&payout.id).await.unwrap();
+
+        mock.respond("gettransaction", json!({ "confirmations": 2 }));
+        assert_eq!(manager.refresh_confirmations().await.unwrap(), 0);
+        let payout = manager.get_payout(&payout.id).await.unwrap().unwrap();
+        assert_eq!((payout.confirmations, payout.block_height, payout.status.clone()), (2, Some(199), PayoutStatus::Broadcast));
+
+        mock.respond("gettransaction", json!({ "confirmations": 6 }));
+        assert_eq!(manager.refresh_confirmations().await.unwrap(), 1);
+        assert_eq!(manager.get_payout(&payout.id).await.unwrap().unwrap().status, PayoutStatus::Confirmed);
+        assert_eq!(manager.refresh_confirmations().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,373 @@
+// UTXO consolidation
+//
+// Coinbase and change outputs pile up in the pool wallet as many small UTXOs,
+// and every one a payout spends adds an input's weight to its fee. When the
+// `conf_target` fee estimate drops to `max_fee_rate_sat_vb` or below, up to
+// `max_inputs` of the smallest UTXOs are merged into one output at a fresh
+// wallet address. The network fee is recorded in the revenue ledger as an
+// internal transfer. Consolidation waits while the kill switch is engaged or
+// a payout transaction is signed but not yet sent, since it could spend the
+// same coins.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::bitcoin::{TxInput, TxOutput, UnspentOutput};
+use crate::error::PaymentError;
+use crate::revenue::{LedgerKind, NewLedgerEntry};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::outputs::input_vbytes;
+use super::{sat_per_vb, IntentState, PaymentManager, PayoutStatus, DUST_LIMIT_SATOSHIS, TX_OUTPUT_VBYTES, TX_OVERHEAD_VBYTES};
+
+/// The `[dmpool.utxo_consolidation]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsolidationConfig {
+    pub enabled: bool,
+    /// Seconds between fee checks
+    pub check_interval_secs: u64,
+    /// Blocks the fee estimate is for
+    pub conf_target: u32,
+    /// Consolidate only when the estimate is at or below this
+    pub max_fee_rate_sat_vb: f64,
+    /// Fewer small UTXOs than this are left alone
+    pub min_utxos: usize,
+    /// Inputs per consolidation transaction
+    pub max_inputs: usize,
+    /// Only UTXOs below this are consolidated
+    pub small_utxo_max_satoshis: u64,
+    pub min_confirmations: u32,
+    /// Most a single consolidation may pay in fees
+    pub max_fee_satoshis: u64,
+}
+
+impl Default for ConsolidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval_secs: 3600,
+            conf_target: 6,
+            max_fee_rate_sat_vb: 3.0,
+            min_utxos: 50,
+            max_inputs: 200,
+            small_utxo_max_satoshis: 10_000_000, // 0.1 BTC
+            min_confirmations: 6,
+            max_fee_satoshis: 50_000,
+        }
+    }
+}
+
+impl ConsolidationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be at least 1"));
+        }
+        if !(1..=1008).contains(&self.conf_target) {
+            return Err(anyhow::anyhow!("conf_target must be between 1 and 1008"));
+        }
+        if self.max_fee_rate_sat_vb < 1.0 {
+            return Err(anyhow::anyhow!("max_fee_rate_sat_vb must be at least 1"));
+        }
+        if self.min_utxos < 2 {
+            return Err(anyhow::anyhow!("min_utxos must be at least 2"));
+        }
+        // Standard transactions are limited to 100 kvB
+        if !(self.min_utxos..=1400).contains(&self.max_inputs) {
+            return Err(anyhow::anyhow!("max_inputs must be between min_utxos ({}) and 1400", self.min_utxos));
+        }
+        if self.small_utxo_max_satoshis <= DUST_LIMIT_SATOSHIS {
+            return Err(anyhow::anyhow!("small_utxo_max_satoshis must be above the dust limit ({})", DUST_LIMIT_SATOSHIS));
+        }
+        Ok(())
+    }
+}
+
+/// Inputs and fee of a consolidation transaction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConsolidationPlan {
+    pub inputs: Vec<(String, u32)>,
+    pub input_total_satoshis: u64,
+    pub fee_rate_sat_vb: f64,
+    pub estimated_vsize: u64,
+    pub fee_satoshis: u64,
+}
+
+/// A broadcast consolidation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Consolidation {
+    pub txid: String,
+    pub address: String,
+    pub inputs: usize,
+    pub input_total_satoshis: u64,
+    pub fee_satoshis: u64,
+    pub fee_rate_sat_vb: f64,
+    /// Whether an admin asked for it rather than the scheduler
+    pub manual: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of a consolidation check
+#[derive(Clone, Debug, Serialize)]
+pub struct ConsolidationCheck {
+    pub fee_rate_sat_vb: f64,
+    /// Whether the estimate is within `max_fee_rate_sat_vb`
+    pub low_fee: bool,
+    /// Confirmed UTXOs below `small_utxo_max_satoshis` worth spending
+    pub small_utxos: usize,
+    pub plan: Option<ConsolidationPlan>,
+    pub consolidation: Option<Consolidation>,
+    /// Why nothing was sent
+    pub skipped: Option<String>,
+}
+
+/// Pick the smallest UTXOs worth spending at `fee_rate_sat_vb`, within the config's limits
+///
+/// UTXOs whose value would not cover their own input's fee, sized by
+/// address type, are skipped.
+/// Returns None when fewer than `min_utxos` qualify.
+pub fn plan_consolidation(utxos: &[UnspentOutput], fee_rate_sat_vb: f64, config: &ConsolidationConfig) -> Option<ConsolidationPlan> {
+    let fee = |vbytes: u64| (vbytes as f64 * fee_rate_sat_vb).ceil() as u64;
+    let mut candidates: Vec<(&UnspentOutput, u64, u64)> = small_utxos(utxos, config)
+        .map(|(utxo, amount)| (utxo, amount, input_vbytes(utxo.address.as_deref())))
+        .filter(|(_, amount, vbytes)| *amount > fee(*vbytes))
+        .collect();
+    candidates.sort_by_key(|(_, amount, _)| *amount);
+
+    // The output goes to a fresh wallet address, sized as P2WPKH
+    let mut estimated_vsize = TX_OVERHEAD_VBYTES + TX_OUTPUT_VBYTES;
+    let mut selected = Vec::new();
+    for (utxo, amount, vbytes) in candidates {
+        // Fewer inputs when the fee cap would be exceeded
+        if selected.len() == config.max_inputs || fee(estimated_vsize + vbytes) > config.max_fee_satoshis {
+            break;
+        }
+        estimated_vsize += vbytes;
+        selected.push((utxo, amount));
+    }
+    if selected.len() < config.min_utxos {
+        return None;
+    }
+
+    Some(ConsolidationPlan {
+        inputs: selected.iter().map(|(utxo, _)| (utxo.txid.clone(), utxo.vout)).collect(),
+        input_total_satoshis: selected.iter().map(|(_, amount)| amount).sum(),
+        fee_rate_sat_vb,
+        estimated_vsize,
+        fee_satoshis: fee(estimated_vsize),
+    })
+}
+
+fn small_utxos<'a>(utxos: &'a [UnspentOutput], config: &'a ConsolidationConfig) -> impl Iterator<Item = (&'a UnspentOutput, u64)> {
+    utxos.iter()
+        .filter(|utxo| utxo.confirmations >= config.min_confirmations)
+        .map(|utxo| (utxo, (utxo.amount * 100_000_000.0).round() as u64))
+        .filter(|(_, amount)| *amount < config.small_utxo_max_satoshis)
+}
+
+impl PaymentManager {
+    /// Consolidate small wallet UTXOs if fees are low
+    ///
+    /// With `force` the fee window is ignored; the fee cap and input limits still apply.
+    pub async fn consolidate_utxos(&self, config: &ConsolidationConfig, force: bool) -> Result<ConsolidationCheck> {
+        let mut check = self.consolidation_check(config).await?;
+        if check.plan.is_none() || (!check.low_fee && !force) {
+            return Ok(check);
+        }
+
+        self.ensure_writable("consolidate wallet UTXOs").await?;
+        // Payouts pick their inputs from the same wallet
+        let _guard = self.payout_lock.lock().await;
+        if let Err(e) = self.ensure_payouts_running("consolidate wallet UTXOs").await {
+            if force {
+                return Err(e);
+            }
+            check.skipped = Some(format!("{:#}", e));
+            return Ok(check);
+        }
+        let unsent = self.payouts.read().await.iter()
+            .filter(|p| p.status == PayoutStatus::Pending)
+            .filter(|p| p.intent.as_ref().is_some_and(|i| i.state != IntentState::Broadcast))
+            .count();
+        if unsent > 0 {
+            check.skipped = Some(format!("{} payout transactions are signed but not yet sent", unsent));
+            return Ok(check);
+        }
+
+        let Some(plan) = check.plan.clone() else {
+            return Ok(check);
+        };
+        let address = self.bitcoin_client.get_new_address().await
+            .context("Failed to get a consolidation address")?;
+        let inputs = plan.inputs.iter()
+            .map(|(txid, vout)| TxInput { txid: txid.clone(), vout: *vout, sequence: Some(0xffff_fffd) })
+            .collect();
+        let outputs = vec![TxOutput {
+            address: address.clone(),
+            amount: (plan.input_total_satoshis - plan.fee_satoshis) as f64 / 100_000_000.0,
+        }];
+        let raw_tx = self.bitcoin_client.create_raw_transaction(inputs, outputs, None).await
+            .context("Failed to create consolidation transaction")?;
+        let signed = self.bitcoin_client.sign_raw_transaction_with_wallet(&raw_tx).await
+            .context("Failed to sign consolidation transaction")?;
+        if !signed.complete {
+            return Err(PaymentError::SigningIncomplete.into());
+        }
+        let txid = self.bitcoin_client.send_raw_transaction(&signed.hex).await
+            .context("Failed to broadcast consolidation transaction")?;
+        info!("Consolidated {} UTXOs ({} satoshis) into {} for {} satoshis in fees (txid {})",
+            plan.inputs.len(), plan.input_total_satoshis, address, plan.fee_satoshis, txid);
+
+        if let Some(revenue) = &self.revenue {
+            let entry = NewLedgerEntry {
+                kind: LedgerKind::InternalTransfer,
+                amount_satoshis: -(plan.fee_satoshis as i64),
+                block_height: None,
+                reference: Some(format!("consolidation:{}", txid)),
+                note: Some(format!("Consolidated {} UTXOs, {} satoshis", plan.inputs.len(), plan.input_total_satoshis)),
+            };
+            if let Err(e) = revenue.record_revenue(&entry).await {
+                error!("Failed to record consolidation {} in the ledger: {:#}", txid, e);
+            }
+        }
+        check.consolidation = Some(Consolidation {
+            txid,
+            address,
+            inputs: plan.inputs.len(),
+            input_total_satoshis: plan.input_total_satoshis,
+            fee_satoshis: plan.fee_satoshis,
+            fee_rate_sat_vb: plan.fee_rate_sat_vb,
+            manual: force,
+            created_at: Utc::now(),
+        });
+        Ok(check)
+    }
+
+    /// Current fee window and what a consolidation would spend, without sending anything
+    pub async fn consolidation_check(&self, config: &ConsolidationConfig) -> Result<ConsolidationCheck> {
+        let fee_rate_sat_vb = sat_per_vb(self.bitcoin_client.estimate_smart_fee(config.conf_target).await
+            .context("Failed to estimate fee")?).max(1.0);
+        let unspent = self.bitcoin_client.list_unspent(Some(config.min_confirmations), Some(999999)).await
+            .context("Failed to get unspent outputs")?;
+
+        let plan = plan_consolidation(&unspent, fee_rate_sat_vb, config);
+        let low_fee = fee_rate_sat_vb <= config.max_fee_rate_sat_vb;
+        let skipped = match (&plan, low_fee) {
+            (None, _) => Some(format!("Fewer than {} small UTXOs to consolidate", config.min_utxos)),
+            (Some(_), false) => Some(format!("Fee rate {:.1} sat/vB is above {:.1}", fee_rate_sat_vb, config.max_fee_rate_sat_vb)),
+            (Some(_), true) => None,
+        };
+        Ok(ConsolidationCheck {
+            fee_rate_sat_vb,
+            low_fee,
+            small_utxos: small_utxos(&unspent, config).count(),
+            plan,
+            consolidation: None,
+            skipped,
+        })
+    }
+
+    /// Check for a low-fee window every `check_interval_secs`
+    pub fn schedule_consolidation(self: &Arc<Self>, config: ConsolidationConfig) {
+        let payments = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(config.check_interval_secs));
+            loop {
+                interval.tick().await;
+                match payments.consolidate_utxos(&config, false).await {
+                    Ok(check) if check.consolidation.is_none() => {
+                        if let Some(reason) = check.skipped {
+                            info!("No UTXO consolidation: {}", reason);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("UTXO consolidation failed: {:#}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PaymentConfig;
+    use super::*;
+    use crate::bitcoin::mock::MockRpcServer;
+    use crate::revenue::RevenueRecorder;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use tokio::sync::Mutex;
+
+    struct MemoryLedger(Mutex<Vec<NewLedgerEntry>>);
+
+    #[async_trait::async_trait]
+    impl RevenueRecorder for MemoryLedger {
+        async fn record_revenue(&self, entry: &NewLedgerEntry) -> Result<bool> {
+            self.0.lock().await.push(entry.clone());
+            Ok(true)
+        }
+    }
+
+    fn utxo(n: u32, satoshis: u64) -> UnspentOutput {
+        UnspentOutput {
+            txid: format!("{:064x}", n),
+            vout: 0,
+            address: None,
+            amount: satoshis as f64 / 100_000_000.0,
+            confirmations: 100,
+        }
+    }
+
+    #[test]
+    fn test_plan_takes_smallest_within_limits() {
+        let config = ConsolidationConfig { min_utxos: 3, max_inputs: 4, ..ConsolidationConfig::default() };
+        let mut utxos: Vec<UnspentOutput> = (1..=6).map(|n| utxo(n, n as u64 * 100_000)).collect();
+        // Too large, and not worth its input fee at 2 sat/vB
+        utxos.push(utxo(7, 20_000_000));
+        utxos.push(utxo(8, 100));
+
+        let plan = plan_consolidation(&utxos, 2.0, &config).unwrap();
+        assert_eq!(plan.inputs.len(), 4);
+        assert_eq!(plan.input_total_satoshis, 1_000_000);
+        // 11 + 4 * 68 + 31 vbytes
+        assert_eq!((plan.estimated_vsize, plan.fee_satoshis), (314, 628));
+
+        // The fee cap leaves room for 2 inputs, below min_utxos
+        assert!(plan_consolidation(&utxos, 2.0, &ConsolidationConfig { max_fee_satoshis: 400, ..config }).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_consolidates_in_low_fee_window_and_records_fee() {
+        let mock = MockRpcServer::start().await.unwrap();
+        let utxos: Vec<_> = (1..=3u32).map(|n| json!({
+            "txid": format!("{:064x}", n), "vout": 0, "amount": 0.001, "confirmations": 10
+        })).collect();
+        mock.respond("listunspent", json!(utxos));
+        mock.respond("getnewaddress", json!("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"));
+        let temp_dir = TempDir::new().unwrap();
+        let ledger = Arc::new(MemoryLedger(Mutex::new(Vec::new())));
+        let config = PaymentConfig { bitcoin_rpc_url: mock.url().to_string(), ..PaymentConfig::default() };
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), config).unwrap()
+            .with_revenue(ledger.clone());
+        let consolidation = ConsolidationConfig { enabled: true, min_utxos: 3, ..ConsolidationConfig::default() };
+
+        // 20 sat/vB is no low-fee window
+        mock.respond("estimatesmartfee", json!({ "feerate": 0.0002, "blocks": 6 }));
+        let check = manager.consolidate_utxos(&consolidation, false).await.unwrap();
+        assert!(!check.low_fee && check.consolidation.is_none());
+        assert!(mock.calls("sendrawtransaction").is_empty());
+
+        mock.respond("estimatesmartfee", json!({ "feerate": 0.00001, "blocks": 6 }));
+        let check = manager.consolidate_utxos(&consolidation, false).await.unwrap();
+        let sent = check.consolidation.unwrap();
+        // 11 + 3 * 68 + 31 vbytes at 1 sat/vB
+        assert_eq!((sent.inputs, sent.input_total_satoshis, sent.fee_satoshis), (3, 300_000, 246));
+        assert_eq!(mock.calls("createrawtransaction")[0][1], json!([{ "address": "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq", "amount": 0.00299754 }]));
+
+        let entries = ledger.0.lock().await;
+        assert_eq!((entries[0].kind, entries[0].amount_satoshis), (LedgerKind::InternalTransfer, -246));
+        assert_eq!(entries[0].reference, Some(format!("consolidation:{}", sent.txid)));
+    }
+}
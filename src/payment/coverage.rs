@@ -0,0 +1,279 @@
+// Wallet coverage monitoring
+//
+// Compares the hot wallet balance with what it still has to fund: unsent
+// payouts, balances due for payout and a fee allowance per payout. The
+// coverage ratio (balance / required) is kept for metrics, and each
+// configured threshold the ratio drops below raises its own alert rule, so
+// coverage falling from a warning to a critical level pages again at once.
+// Rules of thresholds the ratio is back above are cleared.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::runtime_metrics::gauge;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::{PaymentManager, TierWallet};
+
+/// Alert rule prefix; the threshold's level is appended (`wallet_coverage_critical`)
+pub const WALLET_COVERAGE_ALERT_RULE: &str = "wallet_coverage";
+
+/// Coverage ratio below which an alert of `level` is raised
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoverageThreshold {
+    pub ratio: f64,
+    pub level: AlertLevel,
+}
+
+/// The `[dmpool.wallet_monitor]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalletMonitorConfig {
+    pub enabled: bool,
+    /// Seconds between balance checks
+    pub check_interval_secs: u64,
+    /// Network fee allowance added to the requirement for each owed payout
+    pub fee_headroom_per_payout_satoshis: u64,
+    /// At most one threshold per level; the lowest ratio crossed wins
+    pub thresholds: Vec<CoverageThreshold>,
+}
+
+impl Default for WalletMonitorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval_secs: 300,
+            fee_headroom_per_payout_satoshis: 5_000,
+            thresholds: vec![
+                CoverageThreshold { ratio: 1.5, level: AlertLevel::Warning },
+                CoverageThreshold { ratio: 1.0, level: AlertLevel::Critical },
+            ],
+        }
+    }
+}
+
+impl WalletMonitorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be at least 1"));
+        }
+        if self.thresholds.is_empty() {
+            return Err(anyhow::anyhow!("at least one threshold is required"));
+        }
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            if !threshold.ratio.is_finite() || threshold.ratio <= 0.0 {
+                return Err(anyhow::anyhow!("threshold ratio {} must be positive", threshold.ratio));
+            }
+            if self.thresholds[..i].iter().any(|t| t.level == threshold.level) {
+                return Err(anyhow::anyhow!("more than one {:?} threshold", threshold.level));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of one coverage check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalletCoverage {
+    pub wallet_balance_satoshis: u64,
+    /// Unsent payouts plus balances due for payout
+    pub owed_payouts: usize,
+    pub owed_satoshis: u64,
+    pub fee_headroom_satoshis: u64,
+    /// Owed plus fee headroom
+    pub required_satoshis: u64,
+    /// Balance over requirement; none when nothing is owed
+    pub ratio: Option<f64>,
+    /// Level of the lowest threshold crossed
+    pub level: Option<AlertLevel>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl WalletCoverage {
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        gauge(&mut out, "dmpool_wallet_balance_satoshis", "Hot wallet balance", self.wallet_balance_satoshis as f64);
+        gauge(&mut out, "dmpool_wallet_owed_satoshis", "Unsent payouts and balances due for payout", self.owed_satoshis as f64);
+        gauge(&mut out, "dmpool_wallet_required_satoshis", "Owed payouts plus fee headroom", self.required_satoshis as f64);
+        if let Some(ratio) = self.ratio {
+            gauge(&mut out, "dmpool_wallet_coverage_ratio", "Hot wallet balance over required funds", ratio);
+        }
+        out
+    }
+}
+
+/// Level of the lowest threshold `ratio` is below
+fn coverage_level(thresholds: &[CoverageThreshold], ratio: Option<f64>) -> Option<AlertLevel> {
+    let ratio = ratio?;
+    thresholds.iter()
+        .filter(|t| ratio < t.ratio)
+        .min_by(|a, b| a.ratio.total_cmp(&b.ratio))
+        .map(|t| t.level)
+}
+
+fn rule_id(level: AlertLevel) -> String {
+    let level = match level {
+        AlertLevel::Info => "info",
+        AlertLevel::Warning => "warning",
+        AlertLevel::Critical => "critical",
+    };
+    format!("{}_{}", WALLET_COVERAGE_ALERT_RULE, level)
+}
+
+/// Checks that the hot wallet can fund what miners are owed
+pub struct WalletMonitor {
+    config: WalletMonitorConfig,
+    wallet: Arc<dyn TierWallet>,
+    payments: Arc<PaymentManager>,
+    alerts: Option<Arc<AlertManager>>,
+    latest: RwLock<Option<WalletCoverage>>,
+}
+
+impl WalletMonitor {
+    pub fn new(config: WalletMonitorConfig, wallet: Arc<dyn TierWallet>, payments: Arc<PaymentManager>) -> Self {
+        Self { config, wallet, payments, alerts: None, latest: RwLock::new(None) }
+    }
+
+    /// Raise an alert for each threshold coverage drops below
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn config(&self) -> &WalletMonitorConfig {
+        &self.config
+    }
+
+    /// Result of the last check
+    pub async fn latest(&self) -> Option<WalletCoverage> {
+        self.latest.read().await.clone()
+    }
+
+    /// Compare the wallet balance with what is owed and alert on low coverage
+    pub async fn check(&self) -> Result<WalletCoverage> {
+        let balance = self.wallet.hot_balance_satoshis().await
+            .context("Failed to read hot wallet balance")?;
+        let (owed_payouts, owed_satoshis) = self.payments.owed_payouts().await;
+        let fee_headroom = self.config.fee_headroom_per_payout_satoshis.saturating_mul(owed_payouts as u64);
+        let required = owed_satoshis.saturating_add(fee_headroom);
+        let ratio = (required > 0).then(|| balance as f64 / required as f64);
+        let coverage = WalletCoverage {
+            wallet_balance_satoshis: balance,
+            owed_payouts,
+            owed_satoshis,
+            fee_headroom_satoshis: fee_headroom,
+            required_satoshis: required,
+            ratio,
+            level: coverage_level(&self.config.thresholds, ratio),
+            checked_at: Utc::now(),
+        };
+        if let Some(level) = coverage.level {
+            warn!("Hot wallet covers {:.2}x of the {} satoshis owed ({:?})", ratio.unwrap_or_default(), required, level);
+        }
+        self.alert(&coverage).await;
+        *self.latest.write().await = Some(coverage.clone());
+        Ok(coverage)
+    }
+
+    async fn alert(&self, coverage: &WalletCoverage) {
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+        let context = serde_json::json!(coverage);
+        for threshold in &self.config.thresholds {
+            let rule_id = rule_id(threshold.level);
+            if coverage.level != Some(threshold.level) {
+                if let Err(e) = alerts.clear_rule(&rule_id, context.clone()).await {
+                    warn!("Failed to clear wallet coverage alert: {:#}", e);
+                }
+                continue;
+            }
+            if !alerts.get_rules().await.iter().any(|r| r.id == rule_id) {
+                let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+                alerts.add_rule(AlertRule::new(
+                    &rule_id,
+                    &format!("Hot wallet coverage below {}x", threshold.ratio),
+                    AlertCondition::Custom { message: "The hot wallet cannot comfortably fund pending payouts; top it up".to_string() },
+                    threshold.level,
+                    channels,
+                )).await;
+            }
+            if let Err(e) = alerts.trigger_alert(&rule_id, context.clone()).await {
+                warn!("Failed to raise wallet coverage alert: {:#}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertConfig;
+    use crate::payment::PaymentConfig;
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    struct FixedWallet(std::sync::Mutex<u64>);
+
+    #[async_trait]
+    impl TierWallet for FixedWallet {
+        async fn hot_balance_satoshis(&self) -> Result<u64> {
+            Ok(*self.0.lock().unwrap())
+        }
+
+        async fn address_balance_satoshis(&self, _address: &str) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn sweep_to(&self, _address: &str, _amount_satoshis: u64) -> Result<String> {
+            Err(anyhow::anyhow!("not used"))
+        }
+    }
+
+    #[test]
+    fn test_lowest_crossed_threshold_wins() {
+        let thresholds = WalletMonitorConfig::default().thresholds;
+        assert_eq!(coverage_level(&thresholds, None), None);
+        assert_eq!(coverage_level(&thresholds, Some(2.0)), None);
+        assert_eq!(coverage_level(&thresholds, Some(1.2)), Some(AlertLevel::Warning));
+        assert_eq!(coverage_level(&thresholds, Some(0.4)), Some(AlertLevel::Critical));
+
+        let duplicate = WalletMonitorConfig {
+            thresholds: vec![thresholds[0], CoverageThreshold { ratio: 2.0, level: AlertLevel::Warning }],
+            ..Default::default()
+        };
+        assert!(duplicate.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_alerts_escalate_and_clear() {
+        let temp_dir = TempDir::new().unwrap();
+        let payments = Arc::new(PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap());
+        payments.add_earnings("bc1qminer".to_string(), 10_000_000, 100).await.unwrap();
+        let wallet = Arc::new(FixedWallet(std::sync::Mutex::new(30_000_000)));
+        let alerts = Arc::new(AlertManager::new(AlertConfig { enabled: true, ..Default::default() }));
+        let monitor = WalletMonitor::new(WalletMonitorConfig::default(), wallet.clone(), payments)
+            .with_alerts(alerts.clone());
+
+        let coverage = monitor.check().await.unwrap();
+        assert_eq!((coverage.owed_payouts, coverage.required_satoshis), (1, 10_005_000));
+        assert_eq!(coverage.level, None);
+        assert!(coverage.to_prometheus().contains("dmpool_wallet_coverage_ratio 2.99"));
+
+        *wallet.0.lock().unwrap() = 12_000_000;
+        assert_eq!(monitor.check().await.unwrap().level, Some(AlertLevel::Warning));
+        *wallet.0.lock().unwrap() = 5_000_000;
+        assert_eq!(monitor.check().await.unwrap().level, Some(AlertLevel::Critical));
+        let fired: Vec<_> = alerts.get_history(Some(10)).await.iter().map(|a| a.rule_id.clone()).collect();
+        assert!(fired.contains(&"wallet_coverage_warning".to_string()));
+        assert!(fired.contains(&"wallet_coverage_critical".to_string()));
+
+        *wallet.0.lock().unwrap() = 50_000_000;
+        assert_eq!(monitor.check().await.unwrap().level, None);
+        assert_eq!(monitor.latest().await.unwrap().wallet_balance_satoshis, 50_000_000);
+    }
+}
@@ -0,0 +1,466 @@
+// Payout history for the payment manager
+//
+// Recent payouts live in memory, ordered by creation time and indexed by id
+// and address. Once more than the configured number are held, the oldest
+// settled (confirmed or failed) payouts are appended to a JSONL archive next
+// to payouts.json and dropped from memory, so memory stays bounded however
+// old the pool is. Pending and broadcast payouts are never archived because
+// they are still being updated. Queries page newest-first through memory and
+// then the archive.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+
+use super::{Payout, PayoutStatus};
+
+/// Default number of payouts kept in memory
+pub const DEFAULT_PAYOUTS_IN_MEMORY: usize = 10_000;
+
+/// Position of a payout: creation time, then insertion order
+type Key = (DateTime<Utc>, u64);
+
+/// Which payouts a history query returns
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PayoutFilter {
+    pub address: Option<String>,
+    pub status: Option<PayoutStatus>,
+    /// Only payouts created at or after this time
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only payouts created before this time
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl PayoutFilter {
+    /// Payouts to one address
+    pub fn address(address: impl Into<String>) -> Self {
+        Self { address: Some(address.into()), ..Default::default() }
+    }
+
+    pub fn matches(&self, payout: &Payout) -> bool {
+        self.address.as_ref().is_none_or(|a| *a == payout.address)
+            && self.status.as_ref().is_none_or(|s| *s == payout.status)
+            && self.created_after.is_none_or(|t| payout.created_at >= t)
+            && self.created_before.is_none_or(|t| payout.created_at < t)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.address.is_none() && self.status.is_none() && self.created_after.is_none() && self.created_before.is_none()
+    }
+}
+
+/// One page of payouts, newest first
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PayoutPage {
+    pub payouts: Vec<Payout>,
+    /// Matching payouts across memory and the archive
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Totals of the archived payouts, kept so statistics need no archive scan
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveSummary {
+    pub records: usize,
+    pub confirmed: usize,
+    pub confirmed_satoshis: u64,
+}
+
+impl ArchiveSummary {
+    fn add(&mut self, payout: &Payout) {
+        self.records += 1;
+        if payout.status == PayoutStatus::Confirmed {
+            self.confirmed += 1;
+            self.confirmed_satoshis += payout.amount_satoshis;
+        }
+    }
+}
+
+/// In-memory payouts with id and address indexes
+pub struct PayoutHistory {
+    records: BTreeMap<Key, Payout>,
+    by_id: HashMap<String, Key>,
+    by_address: HashMap<String, BTreeSet<Key>>,
+    next_seq: u64,
+    max_in_memory: usize,
+    archive_path: PathBuf,
+    archived: ArchiveSummary,
+}
+
+impl PayoutHistory {
+    pub fn new(archive_path: PathBuf, max_in_memory: usize) -> Self {
+        Self {
+            records: BTreeMap::new(),
+            by_id: HashMap::new(),
+            by_address: HashMap::new(),
+            next_seq: 0,
+            max_in_memory,
+            archive_path,
+            archived: ArchiveSummary::default(),
+        }
+    }
+
+    pub fn set_max_in_memory(&mut self, max_in_memory: usize) {
+        self.max_in_memory = max_in_memory;
+    }
+
+    /// Replace the in-memory payouts, e.g. from payouts.json
+    pub fn replace(&mut self, payouts: Vec<Payout>) {
+        self.records.clear();
+        self.by_id.clear();
+        self.by_address.clear();
+        for payout in payouts {
+            self.insert(payout);
+        }
+    }
+
+    /// Add a payout, replacing any payout with the same id
+    pub fn insert(&mut self, payout: Payout) {
+        self.remove(&payout.id);
+        let key = (payout.created_at, self.next_seq);
+        self.next_seq += 1;
+        self.by_id.insert(payout.id.clone(), key);
+        self.by_address.entry(payout.address.clone()).or_default().insert(key);
+        self.records.insert(key, payout);
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Payout> {
+        let key = self.by_id.remove(id)?;
+        let payout = self.records.remove(&key)?;
+        if let Some(keys) = self.by_address.get_mut(&payout.address) {
+            keys.remove(&key);
+            if keys.is_empty() {
+                self.by_address.remove(&payout.address);
+            }
+        }
+        Some(payout)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Payout> {
+        self.by_id.get(id).and_then(|key| self.records.get(key))
+    }
+
+    /// Mutable access for status updates; the id and address must not change
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Payout> {
+        let key = self.by_id.get(id)?;
+        self.records.get_mut(key)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.by_id.contains_key(id)
+    }
+
+    /// In-memory payouts, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Payout> {
+        self.records.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn archived(&self) -> ArchiveSummary {
+        self.archived
+    }
+
+    pub fn archive_path(&self) -> &Path {
+        &self.archive_path
+    }
+
+    /// In-memory payouts matching `filter`, newest first
+    fn matching<'a>(&'a self, filter: &'a PayoutFilter) -> Box<dyn Iterator<Item = &'a Payout> + 'a> {
+        match &filter.address {
+            Some(address) => Box::new(
+                self.by_address.get(address).into_iter()
+                    .flat_map(|keys| keys.iter().rev())
+                    .filter_map(|key| self.records.get(key))
+                    .filter(move |p| filter.matches(p)),
+            ),
+            None => Box::new(self.records.values().rev().filter(move |p| filter.matches(p))),
+        }
+    }
+
+    /// A page of the in-memory payouts only
+    pub fn page_in_memory(&self, filter: &PayoutFilter, offset: usize, limit: usize) -> PayoutPage {
+        PayoutPage {
+            payouts: self.matching(filter).skip(offset).take(limit).cloned().collect(),
+            total: self.matching(filter).count(),
+            offset,
+            limit,
+        }
+    }
+
+    /// A page of payouts, newest first, continuing into the archive
+    pub async fn page(&self, filter: &PayoutFilter, offset: usize, limit: usize) -> Result<PayoutPage> {
+        let mut page = self.page_in_memory(filter, offset, limit);
+        let in_memory = page.total;
+
+        let wanted_from = offset.saturating_sub(in_memory);
+        let wanted_to = offset.saturating_add(limit).saturating_sub(in_memory);
+        let needs_rows = wanted_to > wanted_from;
+        let known_total = if filter.is_empty() {
+            Some(self.archived.records)
+        } else if filter.address.is_none() && filter.status == Some(PayoutStatus::Confirmed)
+            && filter.created_after.is_none() && filter.created_before.is_none()
+        {
+            Some(self.archived.confirmed)
+        } else {
+            None
+        };
+        if self.archived.records == 0 || (!needs_rows && known_total.is_some()) {
+            page.total += known_total.unwrap_or(0);
+            return Ok(page);
+        }
+
+        // The archive is oldest first: count the matches, then take the requested slice from the end
+        let archived_total = match known_total {
+            Some(total) => total,
+            None => {
+                let mut total = 0;
+                let mut reader = ArchiveReader::open(&self.archive_path).await?;
+                while let Some(payout) = reader.next().await? {
+                    if filter.matches(&payout) {
+                        total += 1;
+                    }
+                }
+                total
+            }
+        };
+        if needs_rows && wanted_from < archived_total {
+            let first = archived_total.saturating_sub(wanted_to);
+            let last = archived_total - wanted_from;
+            let mut slice = Vec::with_capacity(last - first);
+            let mut position = 0;
+            let mut reader = ArchiveReader::open(&self.archive_path).await?;
+            while let Some(payout) = reader.next().await? {
+                if !filter.matches(&payout) {
+                    continue;
+                }
+                if position >= first && position < last {
+                    slice.push(payout);
+                }
+                position += 1;
+                if position >= last {
+                    break;
+                }
+            }
+            slice.reverse();
+            page.payouts.extend(slice);
+        }
+        page.total = in_memory + archived_total;
+        Ok(page)
+    }
+
+    /// Find a payout in memory or, failing that, in the archive
+    pub async fn find(&self, predicate: impl Fn(&Payout) -> bool) -> Result<Option<Payout>> {
+        if let Some(payout) = self.records.values().rev().find(|p| predicate(p)) {
+            return Ok(Some(payout.clone()));
+        }
+        if self.archived.records == 0 {
+            return Ok(None);
+        }
+        let mut reader = ArchiveReader::open(&self.archive_path).await?;
+        while let Some(payout) = reader.next().await? {
+            if predicate(&payout) {
+                return Ok(Some(payout));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Which of `ids` are in the archive
+    pub async fn archived_ids(&self, ids: &HashSet<String>) -> Result<HashSet<String>> {
+        let mut found = HashSet::new();
+        if self.archived.records == 0 || ids.is_empty() {
+            return Ok(found);
+        }
+        let mut reader = ArchiveReader::open(&self.archive_path).await?;
+        while let Some(payout) = reader.next().await? {
+            if ids.contains(&payout.id) {
+                found.insert(payout.id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Read the archive totals and drop in-memory copies of archived payouts
+    ///
+    /// A crash between archiving and saving payouts.json leaves a payout in
+    /// both places; the archived copy wins. Returns the number dropped.
+    pub async fn load_archive(&mut self) -> Result<usize> {
+        self.archived = ArchiveSummary::default();
+        let mut duplicates = Vec::new();
+        let mut reader = ArchiveReader::open(&self.archive_path).await?;
+        while let Some(payout) = reader.next().await? {
+            if self.contains(&payout.id) {
+                duplicates.push(payout.id.clone());
+            }
+            self.archived.add(&payout);
+        }
+        for id in &duplicates {
+            self.remove(id);
+        }
+        Ok(duplicates.len())
+    }
+
+    /// Move the oldest settled payouts beyond the in-memory cap to the archive
+    ///
+    /// Payouts are appended to the archive before they leave memory. Returns
+    /// the number archived.
+    pub async fn archive_overflow(&mut self) -> Result<usize> {
+        let excess = self.records.len().saturating_sub(self.max_in_memory);
+        if excess == 0 {
+            return Ok(0);
+        }
+        let settled: Vec<String> = self.records.values()
+            .filter(|p| matches!(p.status, PayoutStatus::Confirmed | PayoutStatus::Failed))
+            .take(excess)
+            .map(|p| p.id.clone())
+            .collect();
+        if settled.is_empty() {
+            return Ok(0);
+        }
+
+        let mut lines = Vec::new();
+        for id in &settled {
+            if let Some(payout) = self.get(id) {
+                lines.extend(serde_json::to_vec(payout).context("Failed to serialize archived payout")?);
+                lines.push(b'\n');
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.archive_path)
+            .await
+            .context("Failed to open payout archive")?;
+        file.write_all(&lines).await.context("Failed to append to payout archive")?;
+        file.sync_data().await.context("Failed to sync payout archive")?;
+
+        for id in &settled {
+            if let Some(payout) = self.remove(id) {
+                self.archived.add(&payout);
+            }
+        }
+        Ok(settled.len())
+    }
+}
+
+/// Streams payouts from the JSONL archive, oldest first
+pub struct ArchiveReader {
+    lines: Option<Lines<BufReader<File>>>,
+}
+
+impl ArchiveReader {
+    /// Open the archive; a missing archive reads as empty
+    pub async fn open(path: &Path) -> Result<Self> {
+        let lines = match File::open(path).await {
+            Ok(file) => Some(BufReader::new(file).lines()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("Failed to open payout archive"),
+        };
+        Ok(Self { lines })
+    }
+
+    pub async fn next(&mut self) -> Result<Option<Payout>> {
+        let Some(lines) = self.lines.as_mut() else {
+            return Ok(None);
+        };
+        while let Some(line) = lines.next_line().await.context("Failed to read payout archive")? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let payout = serde_json::from_str(&line).context("Failed to parse archived payout")?;
+            return Ok(Some(payout));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::PayoutSource;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn payout(id: usize, address: &str, status: PayoutStatus) -> Payout {
+        Payout {
+            id: format!("p{}", id),
+            address: address.to_string(),
+            amount_satoshis: 1000 * id as u64,
+            txid: None,
+            block_height: None,
+            status,
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(id as i64),
+            broadcast_at: None,
+            confirmations: 0,
+            error: None,
+            idempotency_key: None,
+            intent: None,
+            source: PayoutSource::Pool,
+        }
+    }
+
+    fn ids(page: &PayoutPage) -> Vec<String> {
+        page.payouts.iter().map(|p| p.id.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_overflow_archives_oldest_settled() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut history = PayoutHistory::new(temp_dir.path().join("archive.jsonl"), 3);
+        history.insert(payout(1, "a", PayoutStatus::Pending));
+        for id in 2..=5 {
+            history.insert(payout(id, if id % 2 == 0 { "a" } else { "b" }, PayoutStatus::Confirmed));
+        }
+
+        assert_eq!(history.archive_overflow().await.unwrap(), 2);
+        assert_eq!(history.len(), 3);
+        // The pending payout stays in memory even though it is the oldest
+        assert!(history.contains("p1"));
+        assert!(!history.contains("p2") && !history.contains("p3"));
+        assert_eq!(history.archived(), ArchiveSummary { records: 2, confirmed: 2, confirmed_satoshis: 5000 });
+
+        // Pages run newest first from memory into the archive
+        let page = history.page(&PayoutFilter::default(), 0, 10).await.unwrap();
+        assert_eq!(ids(&page), vec!["p5", "p4", "p1", "p3", "p2"]);
+        assert_eq!(page.total, 5);
+        let page = history.page(&PayoutFilter::default(), 2, 2).await.unwrap();
+        assert_eq!(ids(&page), vec!["p1", "p3"]);
+
+        let page = history.page(&PayoutFilter::address("a"), 1, 10).await.unwrap();
+        assert_eq!(ids(&page), vec!["p1", "p2"]);
+        assert_eq!(page.total, 3);
+
+        let found = history.find(|p| p.id == "p3").await.unwrap().unwrap();
+        assert_eq!(found.address, "b");
+    }
+
+    #[tokio::test]
+    async fn test_load_archive_drops_duplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = temp_dir.path().join("archive.jsonl");
+        let mut history = PayoutHistory::new(archive.clone(), 1);
+        history.insert(payout(1, "a", PayoutStatus::Confirmed));
+        history.insert(payout(2, "a", PayoutStatus::Failed));
+        history.archive_overflow().await.unwrap();
+
+        // Simulate a crash before payouts.json was rewritten
+        let mut reloaded = PayoutHistory::new(archive, 1);
+        reloaded.replace(vec![payout(1, "a", PayoutStatus::Confirmed), payout(2, "a", PayoutStatus::Failed)]);
+        assert_eq!(reloaded.load_archive().await.unwrap(), 1);
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded.archived().records, 1);
+        assert_eq!(reloaded.page(&PayoutFilter::default(), 0, 10).await.unwrap().total, 2);
+    }
+}
@@ -0,0 +1,481 @@
+// Transaction history import
+//
+// Loads payouts and balances from a pool migrating to dmpool. Files are CSV
+// (with a header row) or a JSON array, one record per payout or per miner:
+//
+//   payouts:  id?, address, amount_satoshis, fee_satoshis?, txid?, paid_at, block_height?
+//   balances: address, balance_satoshis, total_earned_satoshis?, total_paid_satoshis?
+//
+// Every row is validated before anything is loaded; one bad row rejects the
+// file. Imported payouts become confirmed records tagged `imported`, so they
+// show in payout history and statistics but are left out of wallet
+// reconciliation. Balances are added to the ledger. A file is recorded by its
+// SHA-256 once loaded and importing it again is refused.
+
+use anyhow::{Context, Result};
+use bitcoin::Network;
+use chrono::{DateTime, Utc};
+use crate::error::PaymentError;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::str::FromStr;
+use tracing::info;
+
+use super::{IntentState, MinerBalance, NetworkParams, PaymentManager, Payout, PayoutIntent, PayoutSource, PayoutStatus};
+
+/// Validation errors reported per import
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// File format of an import
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Json,
+}
+
+impl ImportFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => Err(anyhow::anyhow!("Unknown import format: {} (expected csv or json)", other)),
+        }
+    }
+}
+
+/// What a file contains
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportKind {
+    Payouts,
+    Balances,
+}
+
+impl FromStr for ImportKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "payouts" => Ok(Self::Payouts),
+            "balances" => Ok(Self::Balances),
+            other => Err(anyhow::anyhow!("Unknown import kind: {} (expected payouts or balances)", other)),
+        }
+    }
+}
+
+/// A payout made by the previous pool
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportedPayout {
+    /// The previous system's payout id, if it had one
+    #[serde(default)]
+    pub id: Option<String>,
+    pub address: String,
+    pub amount_satoshis: u64,
+    #[serde(default)]
+    pub fee_satoshis: Option<u64>,
+    #[serde(default)]
+    pub txid: Option<String>,
+    pub paid_at: DateTime<Utc>,
+    #[serde(default)]
+    pub block_height: Option<u64>,
+}
+
+/// A miner's standing with the previous pool
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportedBalance {
+    pub address: String,
+    /// Unpaid balance carried over
+    pub balance_satoshis: u64,
+    #[serde(default)]
+    pub total_earned_satoshis: Option<u64>,
+    #[serde(default)]
+    pub total_paid_satoshis: Option<u64>,
+}
+
+/// How to import a file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportOptions {
+    pub kind: ImportKind,
+    pub format: ImportFormat,
+    /// Addresses must belong to this network
+    pub network: Network,
+    /// Reject the file unless its amounts add up to this
+    #[serde(default)]
+    pub expected_total_satoshis: Option<u64>,
+    /// Validate without loading anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A row that failed validation (rows count from 1, excluding any CSV header)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+/// Outcome of an import
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub kind: ImportKind,
+    /// SHA-256 of the file
+    pub file_hash: String,
+    pub rows: usize,
+    pub total_satoshis: u64,
+    /// Rows loaded (0 for a dry run or a rejected file)
+    pub imported: usize,
+    /// Payouts already present under the same id
+    pub skipped: usize,
+    pub dry_run: bool,
+    pub errors: Vec<RowError>,
+}
+
+/// A file that has been loaded
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImportRecord {
+    pub file_hash: String,
+    pub kind: ImportKind,
+    pub rows: usize,
+    pub total_satoshis: u64,
+    pub imported_at: DateTime<Utc>,
+}
+
+fn parse_rows<T: serde::de::DeserializeOwned>(data: &[u8], format: ImportFormat) -> Result<Vec<T>> {
+    match format {
+        ImportFormat::Json => serde_json::from_slice(data).context("Failed to parse JSON import (expected an array of records)"),
+        ImportFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(data)
+            .deserialize()
+            .enumerate()
+            .map(|(i, row)| row.with_context(|| format!("Failed to parse CSV row {}", i + 1)))
+            .collect(),
+    }
+}
+
+fn check_address(address: &str, network: Network) -> Result<(), String> {
+    NetworkParams::for_network(network).parse_address(address).map(|_| ())
+}
+
+fn check_total(errors: &mut Vec<RowError>, total: u64, expected: Option<u64>) {
+    if let Some(expected) = expected.filter(|expected| *expected != total) {
+        errors.push(RowError {
+            row: 0,
+            message: format!("amounts add up to {} satoshis, expected {}", total, expected),
+        });
+    }
+}
+
+/// Validate payouts, returning every problem found
+pub fn validate_payouts(payouts: &[ImportedPayout], network: Network, expected_total: Option<u64>) -> Vec<RowError> {
+    let mut errors = Vec::new();
+    let mut ids = HashSet::new();
+    let mut txid_outputs = HashSet::new();
+    let now = Utc::now();
+    let mut total: u64 = 0;
+
+    for (i, payout) in payouts.iter().enumerate() {
+        let mut fail = |message: String| errors.push(RowError { row: i + 1, message });
+        if let Err(message) = check_address(&payout.address, network) {
+            fail(message);
+        }
+        if payout.amount_satoshis == 0 {
+            fail("amount_satoshis must be positive".to_string());
+        }
+        if let Some(txid) = &payout.txid {
+            if txid.len() != 64 || !txid.chars().all(|c| c.is_ascii_hexdigit()) {
+                fail(format!("txid {} is not 64 hex characters", txid));
+            } else if !txid_outputs.insert((txid.to_ascii_lowercase(), payout.address.clone())) {
+                fail(format!("payout to {} in {} appears twice", payout.address, txid));
+            }
+        }
+        if payout.paid_at > now {
+            fail(format!("paid_at {} is in the future", payout.paid_at));
+        }
+        if let Some(id) = &payout.id {
+            if !ids.insert(id.clone()) {
+                fail(format!("duplicate id {}", id));
+            }
+        }
+        total = total.saturating_add(payout.amount_satoshis);
+    }
+
+    check_total(&mut errors, total, expected_total);
+    errors
+}
+
+/// Validate balances, returning every problem found
+pub fn validate_balances(balances: &[ImportedBalance], network: Network, expected_total: Option<u64>) -> Vec<RowError> {
+    let mut errors = Vec::new();
+    let mut addresses = HashSet::new();
+    let mut total: u64 = 0;
+
+    for (i, balance) in balances.iter().enumerate() {
+        let mut fail = |message: String| errors.push(RowError { row: i + 1, message });
+        if let Err(message) = check_address(&balance.address, network) {
+            fail(message);
+        }
+        if !addresses.insert(balance.address.clone()) {
+            fail(format!("duplicate address {}", balance.address));
+        }
+        if let (Some(earned), Some(paid)) = (balance.total_earned_satoshis, balance.total_paid_satoshis) {
+            if earned.checked_sub(paid) != Some(balance.balance_satoshis) {
+                fail(format!(
+                    "total_earned_satoshis ({}) - total_paid_satoshis ({}) does not equal balance_satoshis ({})",
+                    earned, paid, balance.balance_satoshis
+                ));
+            }
+        }
+        total = total.saturating_add(balance.balance_satoshis);
+    }
+
+    check_total(&mut errors, total, expected_total);
+    errors
+}
+
+/// Stable id for an imported payout, so the same payout never loads twice
+fn imported_payout_id(payout: &ImportedPayout) -> String {
+    let key = match &payout.id {
+        Some(id) => id.clone(),
+        None => format!("{}:{}:{}:{}", payout.address, payout.amount_satoshis,
+            payout.txid.as_deref().unwrap_or(""), payout.paid_at.timestamp()),
+    };
+    let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+    format!("imported-{}", &digest[..32])
+}
+
+impl PaymentManager {
+    /// Validate and load a file of historical payouts or balances
+    ///
+    /// Returns a report with the validation errors instead of failing when
+    /// rows are invalid; nothing is loaded in that case.
+    pub async fn import_history(&self, data: &[u8], options: &ImportOptions) -> Result<ImportReport> {
+        let file_hash = format!("{:x}", Sha256::digest(data));
+        let mut report = ImportReport {
+            kind: options.kind,
+            file_hash: file_hash.clone(),
+            rows: 0,
+            total_satoshis: 0,
+            imported: 0,
+            skipped: 0,
+            dry_run: options.dry_run,
+            errors: Vec::new(),
+        };
+
+        let _guard = self.payout_lock.lock().await;
+        if self.load_imports().await?.iter().any(|r| r.file_hash == file_hash) {
+            return Err(PaymentError::AlreadyImported(file_hash).into());
+        }
+
+        match options.kind {
+            ImportKind::Payouts => {
+                let payouts: Vec<ImportedPayout> = parse_rows(data, options.format)?;
+                report.rows = payouts.len();
+                report.total_satoshis = payouts.iter().map(|p| p.amount_satoshis).sum();
+                report.errors = validate_payouts(&payouts, options.network, options.expected_total_satoshis);
+                if report.errors.is_empty() && !options.dry_run {
+                    let (imported, skipped) = self.load_imported_payouts(&payouts).await?;
+                    report.imported = imported;
+                    report.skipped = skipped;
+                }
+            }
+            ImportKind::Balances => {
+                let balances: Vec<ImportedBalance> = parse_rows(data, options.format)?;
+                report.rows = balances.len();
+                report.total_satoshis = balances.iter().map(|b| b.balance_satoshis).sum();
+                report.errors = validate_balances(&balances, options.network, options.expected_total_satoshis);
+                if report.errors.is_empty() && !options.dry_run {
+                    report.imported = self.load_imported_balances(&balances).await;
+                }
+            }
+        }
+        report.errors.truncate(MAX_REPORTED_ERRORS);
+
+        if report.errors.is_empty() && !options.dry_run {
+            self.save().await?;
+            self.record_import(ImportRecord {
+                file_hash,
+                kind: options.kind,
+                rows: report.rows,
+                total_satoshis: report.total_satoshis,
+                imported_at: Utc::now(),
+            }).await?;
+            info!("Imported {} {:?} ({} satoshis, {} already present) from file {}",
+                report.imported, options.kind, report.total_satoshis, report.skipped, report.file_hash);
+        }
+        Ok(report)
+    }
+
+    async fn load_imported_payouts(&self, imported: &[ImportedPayout]) -> Result<(usize, usize)> {
+        let required_confirmations = self.config.read().await.required_confirmations;
+        let mut added = Vec::new();
+        {
+            let mut payouts = self.payouts.write().await;
+            let ids: HashSet<String> = imported.iter().map(imported_payout_id).collect();
+            let archived = payouts.archived_ids(&ids).await?;
+            for row in imported {
+                let id = imported_payout_id(row);
+                if payouts.contains(&id) || archived.contains(&id) {
+                    continue;
+                }
+                let payout = Payout {
+                    id,
+                    address: row.address.clone(),
+                    amount_satoshis: row.amount_satoshis,
+                    txid: row.txid.clone(),
+                    block_height: row.block_height,
+                    status: PayoutStatus::Confirmed,
+                    created_at: row.paid_at,
+                    broadcast_at: Some(row.paid_at),
+                    confirmations: required_confirmations,
+                    error: None,
+                    idempotency_key: None,
+                    // Carries the fee into payout statistics
+                    intent: row.fee_satoshis.map(|fee_satoshis| PayoutIntent {
+                        state: IntentState::Broadcast,
+                        preimage_hash: String::new(),
+                        txid: row.txid.clone().unwrap_or_default(),
+                        signed_tx_hex: String::new(),
+                        attempts: 0,
+                        fee_satoshis,
+                        updated_at: row.paid_at,
+                    }),
+                    source: PayoutSource::Imported,
+                };
+                added.push(payout.clone());
+                // Kept in creation order, so saving archives the oldest first when over the cap
+                payouts.insert(payout);
+            }
+        }
+
+        if let Some(recorder) = &self.recorder {
+            for payout in &added {
+                recorder.record_payout(payout).await
+                    .with_context(|| format!("Failed to record imported payout {}", payout.id))?;
+            }
+        }
+        Ok((added.len(), imported.len() - added.len()))
+    }
+
+    async fn load_imported_balances(&self, imported: &[ImportedBalance]) -> usize {
+        let mut balances = self.balances.write().await;
+        for row in imported {
+            let paid = row.total_paid_satoshis.unwrap_or(0);
+            let earned = row.total_earned_satoshis.unwrap_or(row.balance_satoshis + paid);
+            let balance = balances.entry(row.address.clone()).or_insert_with(|| MinerBalance {
+                address: row.address.clone(),
+                balance_satoshis: 0,
+                total_earned_satoshis: 0,
+                total_paid_satoshis: 0,
+                updated_at: Utc::now(),
+            });
+            balance.balance_satoshis += row.balance_satoshis;
+            balance.total_earned_satoshis += earned;
+            balance.total_paid_satoshis += paid;
+            balance.updated_at = Utc::now();
+        }
+        imported.len()
+    }
+
+    /// Files imported so far
+    pub async fn load_imports(&self) -> Result<Vec<ImportRecord>> {
+        let path = self.data_dir.join("imports.json");
+        let imports = persist::read_json(&path).await
+            .context("Failed to load imports file")?;
+        Ok(imports.unwrap_or_default())
+    }
+
+    async fn record_import(&self, record: ImportRecord) -> Result<()> {
+        let mut imports = self.load_imports().await?;
+        imports.push(record);
+        persist::write_json(&self.data_dir.join("imports.json"), &imports).await
+            .context("Failed to write imports file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::PaymentConfig;
+    use tempfile::TempDir;
+
+    const ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    fn options(kind: ImportKind, format: ImportFormat) -> ImportOptions {
+        ImportOptions { kind, format, network: Network::Bitcoin, expected_total_satoshis: None, dry_run: false }
+    }
+
+    #[tokio::test]
+    async fn test_import_payouts_csv() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap();
+        let txid = "a".repeat(64);
+        let csv = format!(
+            "id,address,amount_satoshis,fee_satoshis,txid,paid_at,block_height\n\
+             old-1,{ADDRESS},1500000,300,{txid},2025-06-01T00:00:00Z,900000\n\
+             old-2,{ADDRESS},2500000,,,2025-07-01T00:00:00Z,\n"
+        );
+
+        // Wrong expected total: rejected, nothing loaded
+        let mut opts = options(ImportKind::Payouts, ImportFormat::Csv);
+        opts.expected_total_satoshis = Some(1);
+        let report = manager.import_history(csv.as_bytes(), &opts).await.unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(manager.list_payouts(&Default::default(), 0, 10).await.unwrap().total, 0);
+
+        opts.expected_total_satoshis = Some(4_000_000);
+        let report = manager.import_history(csv.as_bytes(), &opts).await.unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.imported, 2);
+
+        let history = manager.get_payout_history(ADDRESS, 10).await;
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|p| p.source == PayoutSource::Imported && p.status == PayoutStatus::Confirmed));
+        let first = history.iter().find(|p| p.amount_satoshis == 1_500_000).unwrap();
+        assert_eq!(first.intent.as_ref().unwrap().fee_satoshis, 300);
+
+        // The same file is refused the second time
+        let err = manager.import_history(csv.as_bytes(), &opts).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<PaymentError>(), Some(PaymentError::AlreadyImported(_))));
+    }
+
+    #[tokio::test]
+    async fn test_import_balances_json_validates() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap();
+
+        let bad = serde_json::json!([
+            { "address": ADDRESS, "balance_satoshis": 100, "total_earned_satoshis": 500, "total_paid_satoshis": 300 },
+            { "address": "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", "balance_satoshis": 100 },
+            { "address": ADDRESS, "balance_satoshis": 100 },
+        ]).to_string();
+        let report = manager.import_history(bad.as_bytes(), &options(ImportKind::Balances, ImportFormat::Json)).await.unwrap();
+        assert_eq!(report.errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(manager.get_balance(ADDRESS).await.is_none());
+
+        let good = serde_json::json!([
+            { "address": ADDRESS, "balance_satoshis": 200, "total_earned_satoshis": 500, "total_paid_satoshis": 300 },
+        ]).to_string();
+        let mut opts = options(ImportKind::Balances, ImportFormat::Json);
+        opts.dry_run = true;
+        assert!(manager.import_history(good.as_bytes(), &opts).await.unwrap().errors.is_empty());
+        assert!(manager.get_balance(ADDRESS).await.is_none());
+
+        opts.dry_run = false;
+        assert_eq!(manager.import_history(good.as_bytes(), &opts).await.unwrap().imported, 1);
+        let balance = manager.get_balance(ADDRESS).await.unwrap();
+        assert_eq!((balance.balance_satoshis, balance.total_earned_satoshis, balance.total_paid_satoshis), (200, 500, 300));
+    }
+}
@@ -0,0 +1,304 @@
+// In-flight payout monitoring
+//
+// Between broadcast and confirmation each payout transaction is checked
+// against the node's mempool: its fee rate against the `conf_target`
+// estimate, whether it is still there and whether a conflicting spend
+// replaced it. A payout below the target for `stuck_after_minutes` is marked
+// Stuck and can be fee-bumped (BIP 125). One that left the mempool unmined,
+// or whose inputs another transaction spent, is marked Evicted and can be
+// rebroadcast unless the conflict was mined.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::error::{DmpoolError, PaymentError};
+use crate::events::PoolEvent;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::{sat_per_vb, IntentState, PaymentManager, Payout, PayoutStatus};
+
+/// The `[dmpool.payout_inflight]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InflightConfig {
+    pub enabled: bool,
+    /// Seconds between mempool checks of broadcast payouts
+    pub check_interval_secs: u64,
+    /// Blocks the fee rate should confirm within
+    pub conf_target: u32,
+    /// Below the target fee rate for this long makes a payout Stuck
+    pub stuck_after_minutes: u64,
+    /// Highest fee rate a bump may use
+    pub max_bump_fee_rate_sat_vb: f64,
+}
+
+impl Default for InflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            check_interval_secs: 300,
+            conf_target: 6,
+            stuck_after_minutes: 60,
+            max_bump_fee_rate_sat_vb: 200.0,
+        }
+    }
+}
+
+impl InflightConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be at least 1"));
+        }
+        if !(1..=1008).contains(&self.conf_target) {
+            return Err(anyhow::anyhow!("conf_target must be between 1 and 1008"));
+        }
+        if self.max_bump_fee_rate_sat_vb < 1.0 {
+            return Err(anyhow::anyhow!("max_bump_fee_rate_sat_vb must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Mempool position of a broadcast payout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflightPayout {
+    pub payout_id: String,
+    pub address: String,
+    pub txid: String,
+    pub status: PayoutStatus,
+    pub broadcast_at: Option<DateTime<Utc>>,
+    pub in_mempool: bool,
+    pub fee_rate_sat_vb: Option<f64>,
+    pub target_fee_rate_sat_vb: f64,
+    /// Fee rate over the target; below 1 it is unlikely to confirm within `conf_target` blocks
+    pub fee_position: Option<f64>,
+    /// Wallet transactions spending the same inputs
+    pub conflicts: Vec<String>,
+    /// Mined conflicting spend, when the payout was double-spent
+    pub double_spent: bool,
+    /// Fee rate to bump a Stuck payout to
+    pub suggested_fee_rate_sat_vb: Option<f64>,
+}
+
+/// BIP 125 needs a higher fee rate than the original, by at least the 1 sat/vB incremental relay fee
+fn suggested_fee_rate(current: f64, target: f64, max: f64) -> f64 {
+    target.max(current + 1.0).ceil().min(max)
+}
+
+impl PaymentManager {
+    /// Check broadcast payouts against the mempool, marking them Stuck or Evicted
+    pub async fn check_inflight(&self, config: &InflightConfig) -> Result<Vec<InflightPayout>> {
+        let inflight: Vec<Payout> = self.payouts.read().await.iter()
+            .filter(|p| matches!(p.status, PayoutStatus::Broadcast | PayoutStatus::Stuck | PayoutStatus::Evicted))
+            .filter(|p| p.txid.is_some())
+            .cloned()
+            .collect();
+        if inflight.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let target = sat_per_vb(self.bitcoin_client.estimate_smart_fee(config.conf_target).await
+            .context("Failed to estimate the target fee rate")?);
+        let stuck_after = chrono::Duration::minutes(config.stuck_after_minutes as i64);
+        let mut report = Vec::new();
+        for mut payout in inflight {
+            let txid = payout.txid.clone().unwrap_or_default();
+            let wallet = match self.bitcoin_client.get_wallet_transaction(&txid).await {
+                Ok(wallet) => wallet,
+                Err(e) => {
+                    warn!("Failed to read wallet transaction {} of payout {}: {:#}", txid, payout.id, e);
+                    continue;
+                }
+            };
+            // Mined; the confirmation tracker takes it from here
+            if wallet.confirmations > 0 {
+                continue;
+            }
+            let entry = self.bitcoin_client.get_mempool_entry(&txid).await
+                .with_context(|| format!("Failed to read the mempool entry of {}", txid))?;
+
+            let mut position = InflightPayout {
+                payout_id: payout.id.clone(),
+                address: payout.address.clone(),
+                txid: txid.clone(),
+                status: payout.status.clone(),
+                broadcast_at: payout.broadcast_at,
+                in_mempool: entry.is_some(),
+                fee_rate_sat_vb: entry.as_ref().map(|e| e.fee_rate_sat_vb()),
+                target_fee_rate_sat_vb: target,
+                fee_position: entry.as_ref().map(|e| e.fee_rate_sat_vb() / target.max(1.0)),
+                conflicts: wallet.walletconflicts.clone(),
+                double_spent: wallet.confirmations < 0,
+                suggested_fee_rate_sat_vb: None,
+            };
+
+            let status = match (&entry, wallet.confirmations < 0) {
+                (_, true) | (None, false) => PayoutStatus::Evicted,
+                (Some(entry), false) => {
+                    let since = payout.broadcast_at.unwrap_or_else(|| DateTime::from_timestamp(entry.time, 0).unwrap_or_else(Utc::now));
+                    if entry.fee_rate_sat_vb() < target && Utc::now() - since >= stuck_after {
+                        position.suggested_fee_rate_sat_vb = Some(suggested_fee_rate(entry.fee_rate_sat_vb(), target, config.max_bump_fee_rate_sat_vb));
+                        PayoutStatus::Stuck
+                    } else {
+                        PayoutStatus::Broadcast
+                    }
+                }
+            };
+            if status != payout.status {
+                match status {
+                    PayoutStatus::Evicted if position.double_spent => warn!("Payout {} was double-spent by {:?}", payout.id, wallet.walletconflicts),
+                    PayoutStatus::Evicted => warn!("Payout {} ({}) left the mempool unmined", payout.id, txid),
+                    PayoutStatus::Stuck => warn!("Payout {} ({}) is stuck at {:.1} sat/vB, target {:.1}", payout.id, txid,
+                        position.fee_rate_sat_vb.unwrap_or_default(), target),
+                    _ => info!("Payout {} ({}) is back in the mempool", payout.id, txid),
+                }
+                payout.status = status.clone();
+                payout.error = match status {
+                    PayoutStatus::Evicted if position.double_spent => Some(format!("Inputs double-spent by {}", wallet.walletconflicts.join(", "))),
+                    PayoutStatus::Evicted => Some(match &wallet.replaced_by_txid {
+                        Some(replacement) => format!("Replaced by {}", replacement),
+                        None => "Left the mempool unmined".to_string(),
+                    }),
+                    _ => None,
+                };
+                self.update_payout(&payout).await?;
+                self.record_payout(&payout).await;
+            }
+            position.status = status;
+            report.push(position);
+        }
+        Ok(report)
+    }
+
+    /// Replace a Broadcast or Stuck payout's transaction with a higher-fee one
+    ///
+    /// Without `fee_rate_sat_vb` the suggested rate is used: the `conf_target`
+    /// estimate, and at least 1 sat/vB above the current rate.
+    pub async fn bump_payout_fee(&self, payout_id: &str, fee_rate_sat_vb: Option<f64>, requested_by: &str, config: &InflightConfig) -> Result<Payout> {
+        self.ensure_writable("bump payout fees").await?;
+        let _guard = self.payout_lock.lock().await;
+        self.ensure_payouts_running("bump payout fees").await?;
+
+        let mut payout = self.payouts.read().await
+            .find(|p| p.id == payout_id)
+            .await?
+            .ok_or_else(|| PaymentError::PayoutNotFound(payout_id.to_string()))?;
+        let txid = match (&payout.status, &payout.txid) {
+            (PayoutStatus::Broadcast | PayoutStatus::Stuck, Some(txid)) => txid.clone(),
+            _ => return Err(PaymentError::NotPending(payout_id.to_string()).into()),
+        };
+
+        let fee_rate = match fee_rate_sat_vb {
+            Some(rate) => rate,
+            None => {
+                let entry = self.bitcoin_client.get_mempool_entry(&txid).await?
+                    .ok_or_else(|| PaymentError::NotPending(payout_id.to_string()))?;
+                let target = sat_per_vb(self.bitcoin_client.estimate_smart_fee(config.conf_target).await?);
+                suggested_fee_rate(entry.fee_rate_sat_vb(), target, config.max_bump_fee_rate_sat_vb)
+            }
+        };
+        if !(1.0..=config.max_bump_fee_rate_sat_vb).contains(&fee_rate) {
+            return Err(DmpoolError::InvalidInput(format!(
+                "Fee rate must be between 1 and {} sat/vB", config.max_bump_fee_rate_sat_vb
+            )).into());
+        }
+
+        let bumped = self.bitcoin_client.bump_fee(&txid, fee_rate).await
+            .with_context(|| format!("Failed to bump the fee of {}", txid))?;
+        info!("{} bumped payout {} from {} to {} at {} sat/vB", requested_by, payout.id, txid, bumped.txid, fee_rate);
+
+        let signed_tx_hex = match self.bitcoin_client.get_raw_transaction(&bumped.txid).await {
+            Ok(hex) => hex,
+            Err(e) => {
+                warn!("Failed to fetch replacement {} of payout {}: {:#}", bumped.txid, payout.id, e);
+                String::new()
+            }
+        };
+        if let Some(intent) = payout.intent.as_mut() {
+            intent.state = IntentState::Broadcast;
+            intent.txid = bumped.txid.clone();
+            intent.signed_tx_hex = signed_tx_hex;
+            intent.fee_satoshis = (bumped.fee * 100_000_000.0).round() as u64;
+            intent.updated_at = Utc::now();
+        }
+        payout.txid = Some(bumped.txid);
+        payout.status = PayoutStatus::Broadcast;
+        payout.error = None;
+        self.update_payout(&payout).await?;
+        self.record_payout(&payout).await;
+        if let Some(events) = &self.events {
+            events.publish(PoolEvent::PayoutBroadcast {
+                payout_id: payout.id.clone(),
+                address: payout.address.clone(),
+                amount_satoshis: payout.amount_satoshis,
+                txid: payout.txid.clone(),
+            });
+        }
+        Ok(payout)
+    }
+
+    /// Check broadcast payouts every `check_interval_secs`
+    pub fn monitor_inflight(self: &Arc<Self>, config: InflightConfig) {
+        let payments = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(config.check_interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = payments.check_inflight(&config).await {
+                    error!("In-flight payout check failed: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PaymentConfig;
+    use super::*;
+    use crate::bitcoin::mock::MockRpcServer;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    const ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
+    #[tokio::test]
+    async fn test_stuck_payout_bumped_and_evicted_payout_detected() {
+        let mock = MockRpcServer::start().await.unwrap();
+        mock.respond("listunspent", json!([
+            { "txid": "aa", "vout": 0, "address": ADDRESS, "amount": 0.1, "confirmations": 10 }
+        ]));
+        let temp_dir = TempDir::new().unwrap();
+        let config = PaymentConfig { bitcoin_rpc_url: mock.url().to_string(), ..PaymentConfig::default() };
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), config).unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 5_000_000, 123).await.unwrap();
+        let payout = manager.create_payout(ADDRESS.to_string(), 2_000_000).await.unwrap();
+        manager.broadcast_payout(&payout.id).await.unwrap();
+        let inflight = InflightConfig { enabled: true, stuck_after_minutes: 0, ..InflightConfig::default() };
+
+        // 2 sat/vB against a 10 sat/vB target
+        mock.respond("gettransaction", json!({ "confirmations": 0, "walletconflicts": [] }));
+        mock.respond("estimatesmartfee", json!({ "feerate": 0.0001, "blocks": 6 }));
+        mock.respond("getmempoolentry", json!({ "vsize": 150, "time": 0, "fees": { "base": 0.000003 } }));
+        let report = manager.check_inflight(&inflight).await.unwrap();
+        assert_eq!(report[0].status, PayoutStatus::Stuck);
+        assert_eq!((report[0].fee_position, report[0].suggested_fee_rate_sat_vb), (Some(0.2), Some(10.0)));
+
+        mock.respond("bumpfee", json!({ "txid": "bb".repeat(32), "origfee": 0.000003, "fee": 0.000015 }));
+        let bumped = manager.bump_payout_fee(&payout.id, None, "alice", &inflight).await.unwrap();
+        assert_eq!((bumped.status.clone(), bumped.txid.clone()), (PayoutStatus::Broadcast, Some("bb".repeat(32))));
+        assert_eq!(mock.calls("bumpfee")[0][1], json!({ "fee_rate": 10.0 }));
+
+        // The replacement disappeared from the mempool
+        mock.reply("getmempoolentry", crate::bitcoin::mock::MockReply::Error {
+            code: crate::bitcoin::RPC_INVALID_ADDRESS_OR_KEY,
+            message: "Transaction not in mempool".to_string(),
+        });
+        let report = manager.check_inflight(&inflight).await.unwrap();
+        assert_eq!(report[0].status, PayoutStatus::Evicted);
+        assert!(!report[0].in_mempool);
+        assert!(manager.bump_payout_fee(&payout.id, Some(20.0), "alice", &inflight).await.is_err());
+    }
+}
@@ -1,18 +1,81 @@
 // Payment System Module for DMPool
-// Handles miner balance tracking, payout calculations, and Bitcoin transactions
+// Handles miner balance tracking, payout calculations, and Bitcoin transactions,
+// with hot/cold wallet tiers for the funds behind them, coverage alerts when
+// the hot wallet cannot fund what is owed, second-admin approval for large
+// payouts and imports of another pool's history. Payout
+// history is bounded in memory, with older settled payouts archived to disk.
+// Miners can instead be paid directly by coinbase outputs of found blocks.
+// Found blocks are credited once per block hash, so a replayed block-found
+// handler never credits miners twice. A global kill switch halts every
+// broadcast and sweep. Broadcast payouts are confirmed as blocks arrive and
+// watched in the mempool until then, so stuck ones can be fee-bumped. Small
+// wallet UTXOs are consolidated while fees are low.
+
+pub mod approval;
+pub mod coinbase;
+pub mod confirmations;
+pub mod consolidation;
+pub mod coverage;
+pub mod history;
+pub mod import;
+pub mod inflight;
+pub mod network;
+pub mod outputs;
+pub mod pause;
+pub mod wallet;
+
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bitcoin::Network;
 use chrono::{DateTime, Utc};
-use crate::bitcoin::BitcoinRpcClient;
+use crate::bitcoin::{BitcoinRpcClient, UnspentOutput};
+use crate::db::DatabaseManager;
+use crate::error::PaymentError;
+use crate::events::{EventBus, PoolEvent};
+use crate::maintenance::ReadOnlyMode;
+use crate::storage::{BlobStore, FileStore};
+use crate::revenue::{payout_run_entry, RevenueRecorder};
+use crate::runtime_metrics::{string_bytes, MemoryFootprint, MemoryReporter};
+use history::ArchiveReader;
+use outputs::{dust_threshold, input_vbytes, output_vbytes};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{error, info, warn};
 
+pub use approval::{ApprovalRequest, ApprovalStatus, ApprovalSubject, PayoutApprovalConfig, PayoutApprovals};
+pub use coinbase::{CoinbaseOutput, CoinbasePayoutConfig, CoinbasePlan, CoinbasePlanner};
+pub use consolidation::{Consolidation, ConsolidationCheck, ConsolidationConfig, ConsolidationPlan};
+pub use coverage::{CoverageThreshold, WalletCoverage, WalletMonitor, WalletMonitorConfig};
+pub use history::{ArchiveSummary, PayoutFilter, PayoutHistory, PayoutPage, DEFAULT_PAYOUTS_IN_MEMORY};
+pub use import::{ImportFormat, ImportKind, ImportOptions, ImportReport};
+pub use inflight::{InflightConfig, InflightPayout};
+pub use network::{parse_network, NetworkParams};
+pub use pause::{PayoutPause, PayoutPauseStatus, PayoutPauseStore};
+pub use wallet::{TierCheck, TierWallet, WalletTierConfig, WalletTierStatus, WalletTiers};
+
+/// Confirmation target used to estimate the fee rate for payout previews
+const PREVIEW_FEE_CONF_TARGET: u32 = 6;
+
+/// Outputs below this are not worth creating, whatever the address type (P2PKH's threshold)
+///
+/// `outputs::dust_threshold` gives the lower threshold of a specific address.
+const DUST_LIMIT_SATOSHIS: u64 = 546;
+
+/// Size estimates for a P2WPKH transaction (vbytes); `outputs` sizes other types
+const TX_OVERHEAD_VBYTES: u64 = 11;
+const TX_INPUT_VBYTES: u64 = 68;
+const TX_OUTPUT_VBYTES: u64 = 31;
+
+/// `estimatesmartfee` BTC/kvB as sat/vB, to a thousandth
+fn sat_per_vb(btc_per_kvb: f64) -> f64 {
+    (btc_per_kvb * 100_000_000.0).round() / 1000.0
+}
+
 /// Payout record representing a single payment to a miner
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Payout {
@@ -36,6 +99,65 @@ pub struct Payout {
     pub confirmations: u32,
     /// Error message if failed
     pub error: Option<String>,
+    /// Client-supplied key that makes payout creation idempotent
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// Broadcast progress, persisted before each step so retries never build a second transaction
+    #[serde(default)]
+    pub intent: Option<PayoutIntent>,
+    /// Where the payout was made
+    #[serde(default)]
+    pub source: PayoutSource,
+}
+
+/// Origin of a payout record
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutSource {
+    /// Paid by this pool's wallet
+    #[default]
+    Pool,
+    /// Loaded from a previous pool's records
+    Imported,
+}
+
+impl PayoutSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pool => "pool",
+            Self::Imported => "imported",
+        }
+    }
+}
+
+/// Step reached by a payout's transaction
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentState {
+    /// Transaction built and signed, not yet sent
+    Signed,
+    /// Send attempted; outcome unknown until seen in the wallet or mempool
+    Broadcasting,
+    /// Accepted by the node
+    Broadcast,
+}
+
+/// Persisted payout transaction intent
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutIntent {
+    pub state: IntentState,
+    /// SHA-256 of the unsigned transaction hex
+    pub preimage_hash: String,
+    /// Txid of the signed transaction
+    pub txid: String,
+    /// Signed transaction, re-sent as-is on retry
+    pub signed_tx_hex: String,
+    /// Number of send attempts
+    pub attempts: u32,
+    /// Network fee paid by the transaction
+    #[serde(default)]
+    pub fee_satoshis: u64,
+    pub updated_at: DateTime<Utc>,
 }
 
 /// Payout status
@@ -49,6 +171,10 @@ pub enum PayoutStatus {
     Confirmed,
     /// Failed - transaction failed
     Failed,
+    /// Stuck - in the mempool too long below the target fee rate; can be fee-bumped
+    Stuck,
+    /// Evicted - left the mempool unmined or was double-spent; can be rebroadcast
+    Evicted,
 }
 
 /// Miner balance record
@@ -66,6 +192,36 @@ pub struct MinerBalance {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A found block whose payouts were credited to balances
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreditedBlock {
+    pub block_hash: String,
+    pub height: u64,
+    /// Miners credited
+    pub miners: usize,
+    pub total_satoshis: u64,
+    pub credited_at: DateTime<Utc>,
+}
+
+/// Contents of balances.json
+///
+/// Credited blocks are saved in the same file as the balances they changed, so
+/// both are written in one atomic replace.
+#[derive(Debug, Default, Deserialize)]
+struct BalancesFile {
+    balances: HashMap<String, MinerBalance>,
+    #[serde(default)]
+    credited_blocks: HashMap<String, CreditedBlock>,
+}
+
+/// balances.json as saved now or before credited blocks were tracked
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredBalances {
+    Current(BalancesFile),
+    Legacy(HashMap<String, MinerBalance>),
+}
+
 /// Payment configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaymentConfig {
@@ -89,6 +245,28 @@ pub struct PaymentConfig {
     pub bitcoin_rpc_url: String,
     pub bitcoin_rpc_user: String,
     pub bitcoin_rpc_pass: String,
+    /// Network payouts are made on; payout addresses must belong to it
+    #[serde(default = "default_network")]
+    pub network: Network,
+}
+
+fn default_network() -> Network {
+    Network::Bitcoin
+}
+
+impl PaymentConfig {
+    /// Defaults for a network, with payout thresholds that suit it
+    pub fn for_network(network: Network) -> Self {
+        Self {
+            min_payout_satoshis: NetworkParams::for_network(network).default_min_payout_satoshis,
+            network,
+            ..Self::default()
+        }
+    }
+
+    pub fn network_params(&self) -> NetworkParams {
+        NetworkParams::for_network(self.network)
+    }
 }
 
 impl Default for PaymentConfig {
@@ -105,24 +283,42 @@ impl Default for PaymentConfig {
             bitcoin_rpc_url: "http://127.0.0.1:8332".to_string(),
             bitcoin_rpc_user: "bitcoin".to_string(),
             bitcoin_rpc_pass: String::new(),
+            network: Network::Bitcoin,
         }
     }
 }
 
+const BALANCES_KEY: &str = "balances.json";
+const PAYOUTS_KEY: &str = "payouts.json";
+
 /// Payment manager
 pub struct PaymentManager {
     /// Miner balances (address -> balance)
     balances: Arc<RwLock<HashMap<String, MinerBalance>>>,
-    /// Payout history
-    payouts: Arc<RwLock<Vec<Payout>>>,
+    /// Found blocks already credited (block hash -> record); locked after `balances`
+    credited_blocks: RwLock<HashMap<String, CreditedBlock>>,
+    /// Recent payouts, indexed by id and address; older ones are archived
+    payouts: Arc<RwLock<PayoutHistory>>,
     /// Configuration
     config: Arc<RwLock<PaymentConfig>>,
     /// Bitcoin RPC client
     bitcoin_client: Arc<BitcoinRpcClient>,
-    /// Data directory for persistence
-    data_dir: PathBuf,
-    /// Maximum payouts to keep in memory
-    max_payouts: usize,
+    /// Where balances and payouts are persisted
+    store: Arc<dyn BlobStore>,
+    /// Serializes payout creation and broadcasting
+    payout_lock: Mutex<()>,
+    /// Fee revenue ledger for payout run network fees
+    revenue: Option<Arc<dyn RevenueRecorder>>,
+    /// Historical payout store
+    recorder: Option<Arc<dyn PayoutRecorder>>,
+    /// Bus for PayoutBroadcast and PayoutConfirmed events
+    events: Option<EventBus>,
+    /// Holds large payouts and runs for a second admin
+    approvals: Option<Arc<PayoutApprovals>>,
+    /// Blocks payout creation and broadcasting during maintenance
+    read_only: Option<Arc<ReadOnlyMode>>,
+    /// Kill switch checked before every broadcast
+    pause: Option<Arc<PayoutPause>>,
 }
 
 impl PaymentManager {
@@ -141,95 +337,273 @@ impl PaymentManager {
 
         Ok(Self {
             balances: Arc::new(RwLock::new(HashMap::new())),
-            payouts: Arc::new(RwLock::new(Vec::new())),
+            credited_blocks: RwLock::new(HashMap::new()),
+            payouts: Arc::new(RwLock::new(PayoutHistory::new(
+                data_dir.join("payouts_archive.jsonl"),
+                DEFAULT_PAYOUTS_IN_MEMORY,
+            ))),
             config: Arc::new(RwLock::new(config)),
             bitcoin_client,
-            data_dir,
-            max_payouts: 10000,
+            store: Arc::new(FileStore::new(data_dir)),
+            payout_lock: Mutex::new(()),
+            revenue: None,
+            recorder: None,
+            events: None,
+            approvals: None,
+            read_only: None,
+            pause: None,
         })
     }
 
-    /// Load persisted data from disk
+    /// Keep at most this many payouts in memory before archiving settled ones
+    pub fn with_max_payouts(mut self, max_payouts: usize) -> Self {
+        if let Some(payouts) = Arc::get_mut(&mut self.payouts) {
+            payouts.get_mut().set_max_in_memory(max_payouts.max(1));
+        }
+        self
+    }
+
+    /// Persist balances and payouts in `store` instead of the data directory
+    ///
+    /// The payout archive stays in the data directory.
+    pub fn with_store(mut self, store: Arc<dyn BlobStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Record payout run network fees in the revenue ledger
+    pub fn with_revenue(mut self, revenue: Arc<dyn RevenueRecorder>) -> Self {
+        self.revenue = Some(revenue);
+        self
+    }
+
+    /// Persist sent payouts for historical statistics
+    pub fn with_recorder(mut self, recorder: Arc<dyn PayoutRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Publish payout broadcasts and confirmations on the event bus
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Hold payouts and runs above the approval thresholds until a second admin approves
+    pub fn with_approvals(mut self, approvals: Arc<PayoutApprovals>) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    /// Refuse to create or broadcast payouts while the pool is read-only
+    pub fn with_read_only(mut self, read_only: Arc<ReadOnlyMode>) -> Self {
+        self.read_only = Some(read_only);
+        self
+    }
+
+    /// Refuse to broadcast payouts while the kill switch is engaged
+    pub fn with_payout_pause(mut self, pause: Arc<PayoutPause>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    async fn ensure_writable(&self, operation: &str) -> Result<()> {
+        match &self.read_only {
+            Some(mode) => mode.ensure_writable(operation).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Fail with `PaymentError::PayoutsPaused` while the kill switch is engaged
+    pub async fn ensure_payouts_running(&self, operation: &str) -> Result<()> {
+        match &self.pause {
+            Some(pause) => pause.ensure_running(operation).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Push all sent payouts to the recorder, e.g. after loading history
+    ///
+    /// Returns the number of payouts recorded.
+    pub async fn sync_recorder(&self) -> Result<usize> {
+        let recorder = match &self.recorder {
+            Some(recorder) => recorder,
+            None => return Ok(0),
+        };
+
+        let payouts = self.payouts.read().await;
+        let is_sent = |p: &Payout| matches!(p.status, PayoutStatus::Broadcast | PayoutStatus::Confirmed);
+        let mut recorded = 0;
+        let mut archive = ArchiveReader::open(payouts.archive_path()).await?;
+        while let Some(payout) = archive.next().await? {
+            if is_sent(&payout) {
+                recorder.record_payout(&payout).await?;
+                recorded += 1;
+            }
+        }
+        for payout in payouts.iter().filter(|p| is_sent(p)) {
+            recorder.record_payout(payout).await?;
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+
+    /// Send a payout to the recorder, logging failures
+    async fn record_payout(&self, payout: &Payout) {
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record_payout(payout).await {
+                error!("Failed to record payout {} history: {}", payout.id, e);
+            }
+        }
+    }
+
+    /// Load persisted data from the store
     pub async fn load(&self) -> Result<()> {
         // Load balances
-        let balances_path = self.data_dir.join("balances.json");
-        if balances_path.exists() {
-            let mut file = File::open(&balances_path).await
-                .context("Failed to open balances file")?;
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).await?;
-            let balances: HashMap<String, MinerBalance> = serde_json::from_slice(&contents)
-                .context("Failed to parse balances file")?;
-            let count = balances.len();
-            *self.balances.write().await = balances;
-            info!("Loaded {} miner balances", count);
+        if let Some(stored) = self.store.get_json::<StoredBalances>(BALANCES_KEY).await
+            .context("Failed to load balances file")?
+        {
+            let file = match stored {
+                StoredBalances::Current(file) => file,
+                StoredBalances::Legacy(balances) => BalancesFile { balances, ..Default::default() },
+            };
+            info!("Loaded {} miner balances and {} credited blocks", file.balances.len(), file.credited_blocks.len());
+            let mut balances = self.balances.write().await;
+            *balances = file.balances;
+            *self.credited_blocks.write().await = file.credited_blocks;
         }
 
         // Load payouts
-        let payouts_path = self.data_dir.join("payouts.json");
-        if payouts_path.exists() {
-            let mut file = File::open(&payouts_path).await
-                .context("Failed to open payouts file")?;
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).await?;
-            let payouts: Vec<Payout> = serde_json::from_slice(&contents)
-                .context("Failed to parse payouts file")?;
+        if let Some(payouts) = self.store.get_json::<Vec<Payout>>(PAYOUTS_KEY).await
+            .context("Failed to load payouts file")?
+        {
             let count = payouts.len();
-            *self.payouts.write().await = payouts;
+            self.payouts.write().await.replace(payouts);
             info!("Loaded {} payout records", count);
         }
 
-        Ok(())
+        let (duplicates, archived) = {
+            let mut payouts = self.payouts.write().await;
+            (payouts.load_archive().await?, payouts.archived())
+        };
+        if archived.records > 0 {
+            info!("{} older payout records are archived", archived.records);
+        }
+        if duplicates > 0 {
+            warn!("Dropped {} payout records that were already archived", duplicates);
+        }
+        // Histories saved before archiving existed may be over the cap
+        self.save().await
     }
 
-    /// Save data to disk
+    /// Save data to the store
     pub async fn save(&self) -> Result<()> {
         // Save balances
-        let balances_path = self.data_dir.join("balances.json");
         let balances = self.balances.read().await;
-        let balances_json = serde_json::to_vec_pretty(&*balances)
-            .context("Failed to serialize balances")?;
+        let credited_blocks = self.credited_blocks.read().await;
+        let balances_json = balances_json(&balances, &credited_blocks)?;
+        drop(credited_blocks);
         drop(balances);
-        {
-            let mut file = File::create(&balances_path).await
-                .context("Failed to create balances file")?;
-            file.write_all(&balances_json).await?;
-        }
+        self.store.put(BALANCES_KEY, &balances_json).await
+            .context("Failed to write balances file")?;
 
-        // Save payouts
-        let payouts_path = self.data_dir.join("payouts.json");
-        let payouts = self.payouts.read().await;
-        let payouts_json = serde_json::to_vec_pretty(&*payouts)
+        // Archive settled payouts over the cap, then save the rest
+        let mut payouts = self.payouts.write().await;
+        let archived = payouts.archive_overflow().await?;
+        if archived > 0 {
+            info!("Archived {} settled payout records", archived);
+        }
+        let in_memory: Vec<&Payout> = payouts.iter().collect();
+        let payouts_json = serde_json::to_vec_pretty(&in_memory)
             .context("Failed to serialize payouts")?;
         drop(payouts);
-        {
-            let mut file = File::create(&payouts_path).await
-                .context("Failed to create payouts file")?;
-            file.write_all(&payouts_json).await?;
-        }
-
-        Ok(())
+        self.store.put(PAYOUTS_KEY, &payouts_json).await
+            .context("Failed to write payouts file")
     }
 
     /// Add earnings to a miner's balance (call when block is found)
     pub async fn add_earnings(&self, address: String, amount_satoshis: u64, block_height: u64) -> Result<()> {
         let mut balances = self.balances.write().await;
-        let balance = balances.entry(address.clone()).or_insert_with(|| MinerBalance {
-            address: address.clone(),
-            balance_satoshis: 0,
-            total_earned_satoshis: 0,
-            total_paid_satoshis: 0,
-            updated_at: Utc::now(),
+        credit_balance(&mut balances, &address, amount_satoshis, block_height);
+        Ok(())
+    }
+
+    /// Credit a found block's payouts exactly once, keyed by block hash
+    ///
+    /// All credits and the credited-block record are saved together before
+    /// they are applied, so a failed save leaves memory untouched and a retry
+    /// credits the block. Returns false, leaving balances untouched, if the
+    /// block was already credited (e.g. the handler replayed it after a restart).
+    pub async fn credit_block(&self, block_hash: &str, height: u64, credits: &[(String, u64)]) -> Result<bool> {
+        let mut balances = self.balances.write().await;
+        let mut credited_blocks = self.credited_blocks.write().await;
+        if let Some(previous) = credited_blocks.get(block_hash) {
+            warn!("Block {} ({}) was already credited at {}, skipping", height, block_hash, previous.credited_at);
+            return Ok(false);
+        }
+
+        let mut new_balances = balances.clone();
+        for (address, amount_satoshis) in credits {
+            credit_balance(&mut new_balances, address, *amount_satoshis, height);
+        }
+        let mut new_credited_blocks = credited_blocks.clone();
+        new_credited_blocks.insert(block_hash.to_string(), CreditedBlock {
+            block_hash: block_hash.to_string(),
+            height,
+            miners: credits.len(),
+            total_satoshis: credits.iter().map(|(_, amount)| amount).sum(),
+            credited_at: Utc::now(),
         });
 
-        balance.balance_satoshis += amount_satoshis;
-        balance.total_earned_satoshis += amount_satoshis;
-        balance.updated_at = Utc::now();
+        // Hold both locks until the write lands so nothing interleaves with it
+        let json = balances_json(&new_balances, &new_credited_blocks)?;
+        self.store.put(BALANCES_KEY, &json).await
+            .context("Failed to save credited block")?;
+        *balances = new_balances;
+        *credited_blocks = new_credited_blocks;
+        Ok(true)
+    }
 
-        info!("Added {} satoshis to {} (block {}), new balance: {}",
-            amount_satoshis, address, block_height, balance.balance_satoshis);
+    /// Credit record for a block, if its payouts were credited
+    pub async fn credited_block(&self, block_hash: &str) -> Option<CreditedBlock> {
+        self.credited_blocks.read().await.get(block_hash).cloned()
+    }
 
-        Ok(())
+    /// Move the whole unpaid balance of one address to another
+    ///
+    /// Lifetime earnings stay with the original address. Returns the amount moved.
+    pub async fn transfer_balance(&self, from: &str, to: &str) -> Result<u64> {
+        if from == to {
+            return Ok(0);
+        }
+
+        let amount = {
+            let mut balances = self.balances.write().await;
+            let amount = match balances.get_mut(from) {
+                Some(balance) if balance.balance_satoshis > 0 => {
+                    let amount = balance.balance_satoshis;
+                    balance.balance_satoshis = 0;
+                    balance.updated_at = Utc::now();
+                    amount
+                }
+                _ => return Ok(0),
+            };
+
+            let target = balances.entry(to.to_string()).or_insert_with(|| MinerBalance {
+                address: to.to_string(),
+                balance_satoshis: 0,
+                total_earned_satoshis: 0,
+                total_paid_satoshis: 0,
+                updated_at: Utc::now(),
+            });
+            target.balance_satoshis += amount;
+            target.updated_at = Utc::now();
+            amount
+        };
+
+        self.save().await?;
+        info!("Transferred {} satoshis from {} to {}", amount, from, to);
+        Ok(amount)
     }
 
     /// Get miner balance
@@ -257,19 +631,53 @@ impl PaymentManager {
 
     /// Create a payout record (doesn't broadcast)
     pub async fn create_payout(&self, address: String, amount_satoshis: u64) -> Result<Payout> {
+        self.create_payout_idempotent(address, amount_satoshis, None).await
+    }
+
+    /// Create a payout record, returning the existing one if the idempotency key was already used
+    ///
+    /// Reusing a key for a different address or amount is an error.
+    pub async fn create_payout_idempotent(
+        &self,
+        address: String,
+        amount_satoshis: u64,
+        idempotency_key: Option<String>,
+    ) -> Result<Payout> {
+        self.ensure_writable("create payouts").await?;
+        let _guard = self.payout_lock.lock().await;
+
+        self.config.read().await.network_params().parse_address(&address)
+            .map_err(PaymentError::InvalidAddress)?;
+        if amount_satoshis < dust_threshold(&address) {
+            return Err(PaymentError::AmountTooSmall.into());
+        }
+
+        if let Some(key) = &idempotency_key {
+            let existing = self.payouts.read().await
+                .find(|p| p.idempotency_key.as_ref() == Some(key))
+                .await?;
+            if let Some(existing) = existing {
+                if existing.address != address || existing.amount_satoshis != amount_satoshis {
+                    return Err(PaymentError::IdempotencyConflict(key.clone()).into());
+                }
+                info!("Idempotency key {} matches existing payout {}", key, existing.id);
+                return Ok(existing);
+            }
+        }
+
         // Check if miner has enough balance
         let balance = {
             let balances = self.balances.read().await;
             balances.get(&address).cloned()
         };
 
-        let balance = balance.ok_or_else(|| anyhow::anyhow!("No balance found for address {}", address))?;
+        let balance = balance.ok_or_else(|| PaymentError::NoBalance(address.clone()))?;
 
         if balance.balance_satoshis < amount_satoshis {
-            return Err(anyhow::anyhow!(
-                "Insufficient balance: requested {}, available {}",
-                amount_satoshis, balance.balance_satoshis
-            ));
+            return Err(PaymentError::InsufficientBalance {
+                requested: amount_satoshis,
+                available: balance.balance_satoshis,
+            }.into());
         }
 
         // Create payout record
@@ -284,6 +692,9 @@ impl PaymentManager {
             broadcast_at: None,
             confirmations: 0,
             error: None,
+            idempotency_key,
+            intent: None,
+            source: PayoutSource::Pool,
         };
 
         // Deduct from balance (marked as pending until confirmed)
@@ -295,17 +706,8 @@ impl PaymentManager {
             }
         }
 
-        // Add to payouts
-        {
-            let mut payouts = self.payouts.write().await;
-            payouts.push(payout.clone());
-
-            // Trim if exceeded max
-            if payouts.len() > self.max_payouts {
-                let remove_count = payouts.len() - self.max_payouts;
-                payouts.drain(0..remove_count);
-            }
-        }
+        // Add to payouts; save archives any overflow
+        self.payouts.write().await.insert(payout.clone());
 
         // Save to disk
         self.save().await?;
@@ -316,22 +718,125 @@ impl PaymentManager {
     }
 
     /// Broadcast a payout (build and send Bitcoin transaction)
+    ///
+    /// Safe to retry: a payout that was already broadcast is returned as-is,
+    /// and once a transaction has been signed only that same transaction is
+    /// ever re-sent, so a retry after a timeout cannot pay twice.
     pub async fn broadcast_payout(&self, payout_id: &str) -> Result<Payout> {
-        let config = self.config.read().await;
+        self.broadcast_payout_as(payout_id, "system").await
+    }
+
+    /// Broadcast a payout on behalf of an admin
+    ///
+    /// A payout that needs approval opens a request in `requested_by`'s name
+    /// and fails with `PaymentError::ApprovalRequired` until another admin
+    /// approves it.
+    pub async fn broadcast_payout_as(&self, payout_id: &str, requested_by: &str) -> Result<Payout> {
+        self.ensure_writable("broadcast payouts").await?;
+        let _guard = self.payout_lock.lock().await;
+        self.ensure_payouts_running("broadcast payouts").await?;
 
         // Find the payout
-        let mut payout = {
-            let payouts = self.payouts.read().await;
-            payouts.iter()
-                .find(|p| p.id == payout_id)
-                .cloned()
-                .ok_or_else(|| anyhow::anyhow!("Payout {} not found", payout_id))?
+        let payout = self.payouts.read().await
+            .find(|p| p.id == payout_id)
+            .await?
+            .ok_or_else(|| PaymentError::PayoutNotFound(payout_id.to_string()))?;
+
+        match payout.status {
+            PayoutStatus::Broadcast | PayoutStatus::Stuck | PayoutStatus::Confirmed => {
+                info!("Payout {} already broadcast (txid: {:?})", payout.id, payout.txid);
+                return Ok(payout);
+            }
+            PayoutStatus::Evicted => {
+                // Send the same signed transaction again; fails if its inputs were double-spent
+                return match payout.intent.clone() {
+                    Some(intent) if !intent.signed_tx_hex.is_empty() => {
+                        warn!("Rebroadcasting evicted payout {} (txid {})", payout.id, intent.txid);
+                        self.send_intent(payout, intent).await
+                    }
+                    _ => Err(PaymentError::NotPending(payout_id.to_string()).into()),
+                };
+            }
+            PayoutStatus::Failed => return Err(PaymentError::NotPending(payout_id.to_string()).into()),
+            PayoutStatus::Pending => {}
+        }
+
+        // A previous attempt got as far as signing: resume it instead of building a new transaction
+        if let Some(intent) = payout.intent.clone() {
+            warn!("Resuming payout {} at {:?} (txid {})", payout.id, intent.state, intent.txid);
+            if self.transaction_known(&intent.txid).await {
+                return self.mark_broadcast(payout, intent).await;
+            }
+            return self.send_intent(payout, intent).await;
+        }
+
+        self.check_approval(&payout, requested_by).await?;
+
+        // The wallet may already hold a matching send (e.g. made before intents were persisted)
+        if let Some(txid) = self.find_existing_send(&payout).await {
+            warn!("Payout {} already paid by wallet transaction {}", payout.id, txid);
+            let intent = PayoutIntent {
+                state: IntentState::Broadcast,
+                preimage_hash: String::new(),
+                txid,
+                signed_tx_hex: String::new(),
+                attempts: 0,
+                fee_satoshis: 0,
+                updated_at: Utc::now(),
+            };
+            return self.mark_broadcast(payout, intent).await;
+        }
+
+        let intent = self.build_intent(payout.clone()).await?;
+        self.send_intent(payout, intent).await
+    }
+
+    /// Fail with ApprovalRequired unless the payout is cleared to be sent
+    async fn check_approval(&self, payout: &Payout, requested_by: &str) -> Result<()> {
+        let Some(approvals) = &self.approvals else {
+            return Ok(());
         };
+        // Decisions may have been made by another process; fail closed if they cannot be read
+        approvals.refresh().await?;
+        // Part of a run still waiting for its decision
+        if let Some(request) = approvals.pending_for(&payout.id).await {
+            return Err(PaymentError::ApprovalRequired { payout_id: payout.id.clone(), approval_id: request.id }.into());
+        }
+        if !approvals.payout_needs_approval(payout.amount_satoshis) || approvals.is_approved(&payout.id).await {
+            return Ok(());
+        }
+        let subject = ApprovalSubject::Payout { payout_id: payout.id.clone() };
+        let request = approvals.request(subject, payout.amount_satoshis, requested_by).await?;
+        warn!("Payout {} of {} satoshis held for approval ({})", payout.id, payout.amount_satoshis, request.id);
+        Err(PaymentError::ApprovalRequired { payout_id: payout.id.clone(), approval_id: request.id }.into())
+    }
 
-        if payout.status != PayoutStatus::Pending {
-            return Err(anyhow::anyhow!("Payout {} is not pending", payout_id));
+    /// Broadcast every payout covered by an approved request
+    pub async fn broadcast_approved(&self, request: &ApprovalRequest, approved_by: &str) -> Result<Vec<Payout>> {
+        if request.status != ApprovalStatus::Approved {
+            return Err(PaymentError::ApprovalNotPending(request.id.clone()).into());
+        }
+        let mut broadcast = Vec::new();
+        for payout_id in request.subject.payout_ids() {
+            match self.broadcast_payout_as(&payout_id, approved_by).await {
+                Ok(payout) => broadcast.push(payout),
+                Err(e) => error!("Failed to broadcast approved payout {}: {:#}", payout_id, e),
+            }
         }
+        self.record_run_fees(&broadcast).await;
+        Ok(broadcast)
+    }
 
+    async fn record_run_fees(&self, broadcast: &[Payout]) {
+        if let (Some(revenue), Some(entry)) = (&self.revenue, payout_run_entry(broadcast)) {
+            if let Err(e) = revenue.record_revenue(&entry).await {
+                error!("Failed to record payout run fees: {}", e);
+            }
+        }
+    }
+
+    /// Build and sign the payout transaction and persist it as a Signed intent
+    async fn build_intent(&self, mut payout: Payout) -> Result<PayoutIntent> {
         info!("Building transaction for payout {} to {} ({} satoshis)",
             payout.id, payout.address, payout.amount_satoshis);
 
@@ -346,31 +851,29 @@ impl PaymentManager {
             let error_msg = "No unspent outputs available in wallet".to_string();
             payout.status = PayoutStatus::Failed;
             payout.error = Some(error_msg.clone());
+            self.update_payout(&payout).await?;
 
-            // Update payouts
-            {
-                let mut payouts = self.payouts.write().await;
-                if let Some(p) = payouts.iter_mut().find(|p| p.id == payout_id) {
-                    *p = payout.clone();
-                }
-            }
-            self.save().await?;
-
-            return Err(anyhow::anyhow!("No unspent outputs available"));
+            return Err(PaymentError::NoUnspentOutputs.into());
         }
 
         // Select inputs (simple implementation - use first available utxo)
         // In production, you'd want to implement proper coin selection
         let utxo = &unspent[0];
-        let total_input = (utxo.amount * 100_000_000.0) as u64; // Convert BTC to satoshis
+        let total_input = (utxo.amount * 100_000_000.0).round() as u64; // Convert BTC to satoshis
 
-        // Calculate change
-        let change_satoshis = total_input - payout.amount_satoshis;
-        let fee_estimate = config.donation_bps as u64; // Use a reasonable fee estimate
-        let actual_change = change_satoshis.saturating_sub(fee_estimate);
+        // Return change to the input's address
+        // In production, this should be configured separately
+        let change_address = utxo.address.clone().unwrap_or_else(|| utxo.txid.clone());
+        let fee_rate_sat_vb = sat_per_vb(self.bitcoin_client.estimate_smart_fee(PREVIEW_FEE_CONF_TARGET).await
+            .context("Failed to estimate fee")?).max(1.0);
+        let vsize = TX_OVERHEAD_VBYTES + input_vbytes(utxo.address.as_deref())
+            + output_vbytes(&payout.address) + output_vbytes(&change_address);
+        let fee_estimate = (vsize as f64 * fee_rate_sat_vb).ceil() as u64;
 
-        if actual_change < 546 { // Dust limit
-            return Err(anyhow::anyhow!("Amount too small after fees"));
+        // Calculate change
+        let actual_change = total_input.saturating_sub(payout.amount_satoshis).saturating_sub(fee_estimate);
+        if actual_change < dust_threshold(&change_address) {
+            return Err(PaymentError::AmountTooSmall.into());
         }
 
         let change_btc = actual_change as f64 / 100_000_000.0;
@@ -382,9 +885,7 @@ impl PaymentManager {
                 amount: amount_btc,
             },
             crate::bitcoin::TxOutput {
-                // Return change to the pool's address
-                // In production, this should be configured separately
-                address: utxo.address.clone().unwrap_or_else(|| utxo.txid.clone()), // Fallback to input address
+                address: change_address,
                 amount: change_btc,
             },
         ];
@@ -394,7 +895,8 @@ impl PaymentManager {
             crate::bitcoin::TxInput {
                 txid: utxo.txid.clone(),
                 vout: utxo.vout,
-                sequence: None,
+                // Signal replaceability (BIP 125) so a stuck payout can be fee-bumped
+                sequence: Some(0xffff_fffd),
             }
         ];
 
@@ -409,61 +911,163 @@ impl PaymentManager {
             .context("Failed to sign transaction")?;
 
         if !signed_tx.complete {
-            return Err(anyhow::anyhow!("Transaction signing incomplete"));
+            return Err(PaymentError::SigningIncomplete.into());
         }
 
-        info!("Signed transaction: {}", signed_tx.hex);
+        let decoded = self.bitcoin_client.decode_raw_transaction(&signed_tx.hex).await
+            .context("Failed to decode signed transaction")?;
 
-        // Broadcast transaction
-        let txid = self.bitcoin_client.send_raw_transaction(&signed_tx.hex).await
-            .context("Failed to broadcast transaction")?;
+        info!("Signed transaction {} for payout {}", decoded.txid, payout.id);
 
-        info!("Broadcast transaction {} for payout {}", txid, payout.id);
+        // Persist before sending so a crash or timeout can only ever re-send this transaction
+        let intent = PayoutIntent {
+            state: IntentState::Signed,
+            preimage_hash: format!("{:x}", Sha256::digest(raw_tx.as_bytes())),
+            txid: decoded.txid,
+            signed_tx_hex: signed_tx.hex,
+            attempts: 0,
+            fee_satoshis: fee_estimate,
+            updated_at: Utc::now(),
+        };
+        payout.intent = Some(intent.clone());
+        self.update_payout(&payout).await?;
+
+        Ok(intent)
+    }
+
+    /// Send a signed intent's transaction
+    async fn send_intent(&self, mut payout: Payout, mut intent: PayoutIntent) -> Result<Payout> {
+        intent.state = IntentState::Broadcasting;
+        intent.attempts += 1;
+        intent.updated_at = Utc::now();
+        payout.intent = Some(intent.clone());
+        self.update_payout(&payout).await?;
+
+        match self.bitcoin_client.send_raw_transaction(&intent.signed_tx_hex).await {
+            Ok(txid) => {
+                if txid != intent.txid {
+                    warn!("Node returned txid {} for payout {}, expected {}", txid, payout.id, intent.txid);
+                }
+                info!("Broadcast transaction {} for payout {}", txid, payout.id);
+                self.mark_broadcast(payout, intent).await
+            }
+            Err(e) => {
+                // e.g. "already in mempool" after a timed-out earlier attempt
+                if self.transaction_known(&intent.txid).await {
+                    info!("Transaction {} for payout {} already known to the node", intent.txid, payout.id);
+                    return self.mark_broadcast(payout, intent).await;
+                }
+                Err(e).context("Failed to broadcast transaction")
+            }
+        }
+    }
 
-        // Update payout
-        payout.txid = Some(txid.clone());
+    /// Record a payout as broadcast
+    async fn mark_broadcast(&self, mut payout: Payout, mut intent: PayoutIntent) -> Result<Payout> {
+        intent.state = IntentState::Broadcast;
+        intent.updated_at = Utc::now();
+        payout.txid = Some(intent.txid.clone());
         payout.status = PayoutStatus::Broadcast;
         payout.broadcast_at = Some(Utc::now());
+        payout.intent = Some(intent);
+        self.update_payout(&payout).await?;
+
+        info!("Successfully broadcast payout {} to {} for {} satoshis (txid: {:?})",
+            payout.id, payout.address, payout.amount_satoshis, payout.txid);
+
+        self.record_payout(&payout).await;
+        if let Some(events) = &self.events {
+            events.publish(PoolEvent::PayoutBroadcast {
+                payout_id: payout.id.clone(),
+                address: payout.address.clone(),
+                amount_satoshis: payout.amount_satoshis,
+                txid: payout.txid.clone(),
+            });
+        }
+        Ok(payout)
+    }
+
+    /// Whether the wallet or mempool already has a transaction
+    async fn transaction_known(&self, txid: &str) -> bool {
+        self.bitcoin_client.get_wallet_transaction_confirmations(txid).await.is_ok()
+            || self.bitcoin_client.get_raw_transaction(txid).await.is_ok()
+    }
+
+    /// Find a recent wallet send that pays this payout and isn't claimed by another payout
+    async fn find_existing_send(&self, payout: &Payout) -> Option<String> {
+        let transactions = match self.bitcoin_client.list_transactions(100, 0).await {
+            Ok(transactions) => transactions,
+            Err(e) => {
+                warn!("Failed to list wallet transactions for payout {}: {}", payout.id, e);
+                return None;
+            }
+        };
 
-        // Update payouts
+        // Sends claimed by archived payouts are older than the wallet's recent transactions
+        let payouts = self.payouts.read().await;
+        transactions.into_iter()
+            .filter(|tx| tx.category == "send" && tx.confirmations >= 0)
+            .filter(|tx| tx.address.as_deref() == Some(payout.address.as_str()))
+            .filter(|tx| (tx.amount.abs() * 100_000_000.0).round() as u64 == payout.amount_satoshis)
+            .filter(|tx| tx.time as i64 >= payout.created_at.timestamp())
+            .find(|tx| !payouts.iter().any(|p| p.id != payout.id && p.txid.as_deref() == Some(tx.txid.as_str())))
+            .map(|tx| tx.txid)
+    }
+
+    /// Replace a payout record and persist
+    async fn update_payout(&self, payout: &Payout) -> Result<()> {
         {
             let mut payouts = self.payouts.write().await;
-            if let Some(p) = payouts.iter_mut().find(|p| p.id == payout_id) {
+            if let Some(p) = payouts.get_mut(&payout.id) {
                 *p = payout.clone();
             }
         }
-
-        self.save().await?;
-
-        info!("Successfully broadcast payout {} to {} for {} satoshis (txid: {})",
-            payout.id, payout.address, payout.amount_satoshis, txid);
-
-        Ok(payout)
+        self.save().await
     }
 
-    /// Get payout history for an address
+    /// Get payout history for an address, newest first
     pub async fn get_payout_history(&self, address: &str, limit: usize) -> Vec<Payout> {
+        let filter = PayoutFilter::address(address);
         let payouts = self.payouts.read().await;
-        payouts.iter()
-            .filter(|p| p.address == address)
-            .rev()
-            .take(limit)
-            .cloned()
-            .collect()
+        match payouts.page(&filter, 0, limit).await {
+            Ok(page) => page.payouts,
+            Err(e) => {
+                error!("Failed to read archived payouts for {}: {:#}", address, e);
+                payouts.page_in_memory(&filter, 0, limit).payouts
+            }
+        }
+    }
+
+    /// Get a payout by id, including archived payouts
+    pub async fn get_payout(&self, payout_id: &str) -> Result<Option<Payout>> {
+        self.payouts.read().await.find(|p| p.id == payout_id).await
+    }
+
+    /// Page through payouts matching `filter`, newest first
+    pub async fn list_payouts(&self, filter: &PayoutFilter, offset: usize, limit: usize) -> Result<PayoutPage> {
+        self.payouts.read().await.page(filter, offset, limit).await
     }
 
     /// Get all pending payouts
     pub async fn get_pending_payout_records(&self) -> Vec<Payout> {
         let payouts = self.payouts.read().await;
         payouts.iter()
-            .filter(|p| p.status == PayoutStatus::Pending || p.status == PayoutStatus::Broadcast)
+            .filter(|p| matches!(p.status, PayoutStatus::Pending | PayoutStatus::Broadcast | PayoutStatus::Stuck | PayoutStatus::Evicted))
             .cloned()
             .collect()
     }
 
-    /// Get all payouts
-    pub async fn get_all_payouts(&self) -> Vec<Payout> {
-        self.payouts.read().await.clone()
+    /// What the wallet still has to fund: unsent payouts plus balances due for payout, as (count, satoshis)
+    pub async fn owed_payouts(&self) -> (usize, u64) {
+        let unsent: Vec<u64> = self.payouts.read().await.iter()
+            .filter(|p| p.status == PayoutStatus::Pending)
+            .map(|p| p.amount_satoshis)
+            .collect();
+        let due = self.get_pending_payouts().await;
+        (
+            unsent.len() + due.len(),
+            unsent.iter().sum::<u64>() + due.iter().map(|(_, amount)| amount).sum::<u64>(),
+        )
     }
 
     /// Confirm a payout (called when transaction gets confirmations)
@@ -473,11 +1077,12 @@ impl PaymentManager {
         drop(config);
 
         let mut payouts = self.payouts.write().await;
-        if let Some(payout) = payouts.iter_mut().find(|p| p.id == payout_id) {
+        if let Some(payout) = payouts.get_mut(payout_id) {
             payout.txid = Some(txid.clone());
             payout.block_height = Some(block_height);
             payout.confirmations = confirmations;
 
+            let newly_confirmed = confirmations >= required && payout.status != PayoutStatus::Confirmed;
             if confirmations >= required {
                 payout.status = PayoutStatus::Confirmed;
 
@@ -488,9 +1093,25 @@ impl PaymentManager {
                 }
 
                 info!("Payout {} confirmed with {} confirmations", payout_id, confirmations);
+            } else if matches!(payout.status, PayoutStatus::Stuck | PayoutStatus::Evicted) {
+                // Mined after all
+                payout.status = PayoutStatus::Broadcast;
             }
 
+            let sent = payout.clone();
+            drop(payouts);
             self.save().await?;
+            self.record_payout(&sent).await;
+
+            if let (true, Some(events)) = (newly_confirmed, &self.events) {
+                events.publish(PoolEvent::PayoutConfirmed {
+                    payout_id: sent.id,
+                    address: sent.address,
+                    amount_satoshis: sent.amount_satoshis,
+                    txid: sent.txid,
+                    confirmations,
+                });
+            }
         }
 
         Ok(())
@@ -500,11 +1121,12 @@ impl PaymentManager {
     pub async fn get_stats(&self) -> PaymentStats {
         let payouts = self.payouts.read().await;
         let balances = self.balances.read().await;
+        let archived = payouts.archived();
 
         let total_paid: u64 = payouts.iter()
             .filter(|p| p.status == PayoutStatus::Confirmed)
             .map(|p| p.amount_satoshis)
-            .sum();
+            .sum::<u64>() + archived.confirmed_satoshis;
 
         let pending_amount: u64 = payouts.iter()
             .filter(|p| p.status == PayoutStatus::Pending || p.status == PayoutStatus::Broadcast)
@@ -520,7 +1142,7 @@ impl PaymentManager {
             total_balance_satoshis: total_balance,
             total_paid_satoshis: total_paid,
             pending_payouts_satoshis: pending_amount,
-            confirmed_payouts: payouts.iter().filter(|p| p.status == PayoutStatus::Confirmed).count(),
+            confirmed_payouts: payouts.iter().filter(|p| p.status == PayoutStatus::Confirmed).count() + archived.confirmed,
             pending_payouts: payouts.iter().filter(|p| p.status == PayoutStatus::Pending || p.status == PayoutStatus::Broadcast).count(),
         }
     }
@@ -537,6 +1159,25 @@ impl PaymentManager {
         self.config.read().await.clone()
     }
 
+    /// Preview the next payout run without creating records or transactions
+    pub async fn preview_payout_run(&self) -> Result<PayoutPlan> {
+        let mut pending = self.get_pending_payouts().await;
+        pending.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let outputs = pending.into_iter()
+            .map(|(address, amount_satoshis)| PlannedOutput { address, amount_satoshis })
+            .collect();
+
+        let unspent = self.bitcoin_client.list_unspent(Some(1), Some(999999)).await
+            .context("Failed to get unspent outputs")?;
+
+        // estimatesmartfee returns BTC/kvB
+        let fee_rate_btc_kvb = self.bitcoin_client.estimate_smart_fee(PREVIEW_FEE_CONF_TARGET).await
+            .context("Failed to estimate fee")?;
+        let fee_rate_sat_vb = (fee_rate_btc_kvb * 100_000_000.0 / 1000.0).max(1.0);
+
+        Ok(plan_payout_run(outputs, &unspent, fee_rate_sat_vb))
+    }
+
     /// Process automatic payouts (call periodically)
     pub async fn process_auto_payouts(&self) -> Result<Vec<Payout>> {
         let config = self.config.read().await;
@@ -544,6 +1185,14 @@ impl PaymentManager {
             return Ok(Vec::new());
         }
         drop(config);
+        if self.ensure_writable("create payouts").await.is_err() {
+            info!("Skipping automatic payouts in read-only maintenance mode");
+            return Ok(Vec::new());
+        }
+        if let Err(e) = self.ensure_payouts_running("create automatic payouts").await {
+            info!("Skipping automatic payouts: {:#}", e);
+            return Ok(Vec::new());
+        }
 
         let pending = self.get_pending_payouts().await;
         let mut created = Vec::new();
@@ -559,17 +1208,185 @@ impl PaymentManager {
             }
         }
 
+        // Large runs wait for a second admin as a whole
+        if let Some(approvals) = &self.approvals {
+            let total: u64 = created.iter().map(|p| p.amount_satoshis).sum();
+            if approvals.run_needs_approval(total) {
+                let subject = ApprovalSubject::PayoutRun { payout_ids: created.iter().map(|p| p.id.clone()).collect() };
+                let request = approvals.request(subject, total, "auto_payout").await?;
+                warn!("Payout run of {} payouts ({} satoshis) held for approval ({})", created.len(), total, request.id);
+                return Ok(created);
+            }
+        }
+
         // Broadcast all created payouts
+        let mut broadcast = Vec::new();
         for payout in &created {
-            if let Err(e) = self.broadcast_payout(&payout.id).await {
-                error!("Failed to broadcast payout {}: {}", payout.id, e);
+            match self.broadcast_payout(&payout.id).await {
+                Ok(payout) => broadcast.push(payout),
+                Err(e) => error!("Failed to broadcast payout {}: {}", payout.id, e),
             }
         }
+        self.record_run_fees(&broadcast).await;
 
         Ok(created)
     }
 }
 
+/// Add an amount to a miner's balance, creating the balance if needed
+/// Contents of balances.json for the given state
+fn balances_json(balances: &HashMap<String, MinerBalance>, credited_blocks: &HashMap<String, CreditedBlock>) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(&serde_json::json!({
+        "balances": balances,
+        "credited_blocks": credited_blocks,
+    }))
+    .context("Failed to serialize balances")
+}
+
+fn credit_balance(balances: &mut HashMap<String, MinerBalance>, address: &str, amount_satoshis: u64, block_height: u64) {
+    let balance = balances.entry(address.to_string()).or_insert_with(|| MinerBalance {
+        address: address.to_string(),
+        balance_satoshis: 0,
+        total_earned_satoshis: 0,
+        total_paid_satoshis: 0,
+        updated_at: Utc::now(),
+    });
+
+    balance.balance_satoshis += amount_satoshis;
+    balance.total_earned_satoshis += amount_satoshis;
+    balance.updated_at = Utc::now();
+
+    info!("Added {} satoshis to {} (block {}), new balance: {}",
+        amount_satoshis, address, block_height, balance.balance_satoshis);
+}
+
+/// An output of a planned payout run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedOutput {
+    pub address: String,
+    pub amount_satoshis: u64,
+}
+
+/// A wallet UTXO selected to fund a payout run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SelectedUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub amount_satoshis: u64,
+}
+
+/// Dry-run plan for paying every balance above the threshold in one transaction
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutPlan {
+    /// Payments to miners
+    pub outputs: Vec<PlannedOutput>,
+    /// Wallet UTXOs that would be spent (largest first)
+    pub selected_utxos: Vec<SelectedUtxo>,
+    pub input_total_satoshis: u64,
+    pub output_total_satoshis: u64,
+    /// Fee rate used for the estimate (sat/vB)
+    pub fee_rate_sat_vb: f64,
+    pub estimated_vsize: u64,
+    pub fee_satoshis: u64,
+    /// Change returned to the wallet (0 if it would be dust)
+    pub change_satoshis: u64,
+    pub change_address: Option<String>,
+    /// Whether the wallet can fund the run
+    pub feasible: bool,
+    pub error: Option<String>,
+}
+
+/// Plan a payout run from wallet UTXOs without touching any state
+///
+/// UTXOs are selected largest first until they cover the outputs plus the
+/// fee; change below its address's dust threshold is added to the fee. Input
+/// and output sizes depend on their address types.
+pub fn plan_payout_run(outputs: Vec<PlannedOutput>, utxos: &[UnspentOutput], fee_rate_sat_vb: f64) -> PayoutPlan {
+    let output_total: u64 = outputs.iter().map(|o| o.amount_satoshis).sum();
+    let payment_vbytes: u64 = outputs.iter().map(|o| output_vbytes(&o.address)).sum();
+    let fee_for = |inputs_vbytes: u64, change_vbytes: u64| -> (u64, u64) {
+        let vsize = TX_OVERHEAD_VBYTES + inputs_vbytes + payment_vbytes + change_vbytes;
+        (vsize, (vsize as f64 * fee_rate_sat_vb).ceil() as u64)
+    };
+    let change_vbytes = |address: &Option<String>| address.as_deref().map_or(TX_OUTPUT_VBYTES, output_vbytes);
+
+    let mut candidates: Vec<&UnspentOutput> = utxos.iter().collect();
+    candidates.sort_by(|a, b| b.amount.partial_cmp(&a.amount).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut plan = PayoutPlan {
+        outputs,
+        selected_utxos: Vec::new(),
+        input_total_satoshis: 0,
+        output_total_satoshis: output_total,
+        fee_rate_sat_vb,
+        estimated_vsize: 0,
+        fee_satoshis: 0,
+        change_satoshis: 0,
+        change_address: None,
+        feasible: false,
+        error: None,
+    };
+
+    if plan.outputs.is_empty() {
+        plan.error = Some("No balances above the payout threshold".to_string());
+        return plan;
+    }
+    if let Some(dust) = plan.outputs.iter().find(|o| o.amount_satoshis < dust_threshold(&o.address)) {
+        plan.error = Some(format!(
+            "Payment of {} satoshis to {} is below its dust threshold ({})",
+            dust.amount_satoshis, dust.address, dust_threshold(&dust.address)
+        ));
+        return plan;
+    }
+
+    let mut inputs_vbytes = 0;
+    for utxo in candidates {
+        // Round to whole satoshis; list_unspent reports BTC as f64
+        let amount_satoshis = (utxo.amount * 100_000_000.0).round() as u64;
+        plan.selected_utxos.push(SelectedUtxo {
+            txid: utxo.txid.clone(),
+            vout: utxo.vout,
+            amount_satoshis,
+        });
+        plan.input_total_satoshis += amount_satoshis;
+        inputs_vbytes += input_vbytes(utxo.address.as_deref());
+        if plan.change_address.is_none() {
+            plan.change_address = utxo.address.clone();
+        }
+
+        let (vsize, fee) = fee_for(inputs_vbytes, change_vbytes(&plan.change_address));
+        if plan.input_total_satoshis < output_total + fee {
+            continue;
+        }
+
+        let change = plan.input_total_satoshis - output_total - fee;
+        let change_dust = plan.change_address.as_deref().map_or(DUST_LIMIT_SATOSHIS, dust_threshold);
+        if change >= change_dust {
+            plan.estimated_vsize = vsize;
+            plan.fee_satoshis = fee;
+            plan.change_satoshis = change;
+        } else {
+            // No change output; the leftover goes to the fee
+            let (vsize, _) = fee_for(inputs_vbytes, 0);
+            plan.estimated_vsize = vsize;
+            plan.fee_satoshis = plan.input_total_satoshis - output_total;
+            plan.change_address = None;
+        }
+        plan.feasible = true;
+        return plan;
+    }
+
+    let (vsize, fee) = fee_for(inputs_vbytes, change_vbytes(&plan.change_address));
+    plan.estimated_vsize = vsize;
+    plan.fee_satoshis = fee;
+    plan.error = Some(format!(
+        "Insufficient wallet funds: need {} satoshis, have {}",
+        output_total + fee,
+        plan.input_total_satoshis
+    ));
+    plan
+}
+
 /// Payment statistics
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PaymentStats {
@@ -581,24 +1398,197 @@ pub struct PaymentStats {
     pub pending_payouts: usize,
 }
 
+/// Persists sent payouts for historical statistics
+#[async_trait]
+pub trait PayoutRecorder: Send + Sync {
+    /// Insert or update a broadcast or confirmed payout
+    async fn record_payout(&self, payout: &Payout) -> Result<()>;
+}
+
+#[async_trait]
+impl PayoutRecorder for DatabaseManager {
+    async fn record_payout(&self, payout: &Payout) -> Result<()> {
+        self.upsert_payout_record(payout).await
+    }
+}
+
+#[async_trait]
+impl MemoryReporter for PaymentManager {
+    async fn footprints(&self) -> Vec<MemoryFootprint> {
+        let payouts = self.payouts.read().await;
+        let payout_bytes: u64 = payouts.iter()
+            .map(|p| {
+                let intent = p.intent.as_ref().map_or(0, |i| {
+                    (i.preimage_hash.capacity() + i.txid.capacity() + i.signed_tx_hex.capacity()) as u64
+                });
+                std::mem::size_of::<Payout>() as u64
+                    + (p.id.capacity() + p.address.capacity()) as u64
+                    + string_bytes(p.txid.as_ref())
+                    + string_bytes(p.error.as_ref())
+                    + string_bytes(p.idempotency_key.as_ref())
+                    + intent
+            })
+            .sum();
+        let payout_entries = payouts.len();
+        drop(payouts);
+
+        let balances = self.balances.read().await;
+        // The address is held twice: as the map key and in the balance
+        let balance_bytes: u64 = balances.values()
+            .map(|b| (std::mem::size_of::<(String, MinerBalance)>() + 2 * b.address.capacity()) as u64)
+            .sum();
+
+        vec![
+            MemoryFootprint { name: "payouts".to_string(), entries: payout_entries, estimated_bytes: payout_bytes },
+            MemoryFootprint { name: "balances".to_string(), entries: balances.len(), estimated_bytes: balance_bytes },
+        ]
+    }
+}
+
+/// Bucket size for historical payout statistics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayoutStatsInterval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl PayoutStatsInterval {
+    /// Unit for Postgres date_trunc
+    pub fn trunc_unit(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// Payout totals for one time bucket
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutStatsBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub payout_count: i64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub total_paid_satoshis: i64,
+    /// Network fees spent on payout transactions
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub total_fee_satoshis: i64,
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub average_payout_satoshis: i64,
+    pub unique_addresses: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitcoin::mock::{mock_txid, MockRpcServer};
     use tempfile::TempDir;
 
+    const ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+
     #[tokio::test]
     async fn test_add_earnings() {
         let temp_dir = TempDir::new().unwrap();
         let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
             .unwrap();
 
-        manager.add_earnings("bc1qtest".to_string(), 500_000, 123).await.unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 500_000, 123).await.unwrap();
 
-        let balance = manager.get_balance("bc1qtest").await;
+        let balance = manager.get_balance(ADDRESS).await;
         assert!(balance.is_some());
         assert_eq!(balance.unwrap().balance_satoshis, 500_000);
     }
 
+    #[tokio::test]
+    async fn test_credit_block_once_across_restarts() {
+        let temp_dir = TempDir::new().unwrap();
+        // balances.json from before credited blocks were tracked
+        std::fs::write(temp_dir.path().join("balances.json"), serde_json::to_vec(&serde_json::json!({
+            ADDRESS: {
+                "address": ADDRESS,
+                "balance_satoshis": 100,
+                "total_earned_satoshis": 100,
+                "total_paid_satoshis": 0,
+                "updated_at": Utc::now(),
+            }
+        })).unwrap()).unwrap();
+
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap();
+        manager.load().await.unwrap();
+        let credits = vec![(ADDRESS.to_string(), 500_000), ("bc1qother".to_string(), 250_000)];
+        assert!(manager.credit_block("00ab", 123, &credits).await.unwrap());
+        assert!(!manager.credit_block("00ab", 123, &credits).await.unwrap());
+        assert_eq!(manager.get_balance(ADDRESS).await.unwrap().balance_satoshis, 500_100);
+
+        // A restart mid-processing replays the block against the saved state
+        let restarted = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default()).unwrap();
+        restarted.load().await.unwrap();
+        assert!(!restarted.credit_block("00ab", 123, &credits).await.unwrap());
+        assert_eq!(restarted.get_balance(ADDRESS).await.unwrap().balance_satoshis, 500_100);
+        assert_eq!(restarted.credited_block("00ab").await.unwrap().total_satoshis, 750_000);
+    }
+
+    /// Refuses to write balances.json while `fail_balances` is set
+    #[derive(Default)]
+    struct FlakyStore {
+        inner: crate::storage::MemoryStore,
+        fail_balances: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl BlobStore for FlakyStore {
+        fn backend(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            self.inner.get(key).await
+        }
+
+        async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+            if key == BALANCES_KEY && self.fail_balances.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("disk full"));
+            }
+            self.inner.put(key, value).await
+        }
+
+        async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+            self.inner.append(key, value).await
+        }
+
+        async fn delete(&self, key: &str) -> Result<()> {
+            self.inner.delete(key).await
+        }
+
+        async fn list(&self) -> Result<Vec<String>> {
+            self.inner.list().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credit_block_retried_after_failed_save() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Arc::new(FlakyStore::default());
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap()
+            .with_store(store.clone());
+        let credits = vec![(ADDRESS.to_string(), 500_000)];
+
+        store.fail_balances.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(manager.credit_block("00ab", 123, &credits).await.is_err());
+        assert!(manager.get_balance(ADDRESS).await.is_none());
+        assert!(manager.credited_block("00ab").await.is_none());
+
+        // The block handler's retry credits it once the store recovers
+        store.fail_balances.store(false, std::sync::atomic::Ordering::SeqCst);
+        assert!(manager.credit_block("00ab", 123, &credits).await.unwrap());
+        assert_eq!(manager.get_balance(ADDRESS).await.unwrap().balance_satoshis, 500_000);
+        assert!(!manager.credit_block("00ab", 123, &credits).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_create_payout() {
         let temp_dir = TempDir::new().unwrap();
@@ -608,15 +1598,15 @@ mod tests {
             .unwrap();
 
         // Add earnings
-        manager.add_earnings("bc1qtest".to_string(), 500_000, 123).await.unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 500_000, 123).await.unwrap();
 
         // Create payout
-        let payout = manager.create_payout("bc1qtest".to_string(), 100_000).await.unwrap();
+        let payout = manager.create_payout(ADDRESS.to_string(), 100_000).await.unwrap();
         assert_eq!(payout.amount_satoshis, 100_000);
         assert_eq!(payout.status, PayoutStatus::Pending);
 
         // Balance should be reduced
-        let balance = manager.get_balance("bc1qtest").await.unwrap();
+        let balance = manager.get_balance(ADDRESS).await.unwrap();
         assert_eq!(balance.balance_satoshis, 400_000);
     }
 
@@ -626,10 +1616,191 @@ mod tests {
         let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
             .unwrap();
 
-        manager.add_earnings("bc1qtest".to_string(), 50_000, 123).await.unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 50_000, 123).await.unwrap();
+
+        let result = manager.create_payout(ADDRESS.to_string(), 100_000).await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<PaymentError>(),
+            Some(PaymentError::InsufficientBalance { requested: 100_000, available: 50_000 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_payout_address_must_match_network() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::for_network(Network::Signet))
+            .unwrap();
+        assert_eq!(manager.get_config().await.min_payout_satoshis, 100_000);
+
+        manager.add_earnings(ADDRESS.to_string(), 500_000, 123).await.unwrap();
+        let result = manager.create_payout(ADDRESS.to_string(), 100_000).await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<PaymentError>(),
+            Some(PaymentError::InvalidAddress(_))
+        ));
+    }
+
+    fn utxo(txid: &str, amount_btc: f64) -> UnspentOutput {
+        UnspentOutput {
+            txid: txid.to_string(),
+            vout: 0,
+            address: Some(format!("bc1q{}", txid)),
+            amount: amount_btc,
+            confirmations: 10,
+        }
+    }
 
-        let result = manager.create_payout("bc1qtest".to_string(), 100_000).await;
-        assert!(result.is_err());
+    #[test]
+    fn test_plan_payout_run() {
+        let outputs = vec![
+            PlannedOutput { address: "bc1qa".to_string(), amount_satoshis: 3_000_000 },
+            PlannedOutput { address: "bc1qb".to_string(), amount_satoshis: 1_500_000 },
+        ];
+        let utxos = vec![utxo("small", 0.001), utxo("large", 0.04), utxo("medium", 0.02)];
+
+        let plan = plan_payout_run(outputs.clone(), &utxos, 10.0);
+        assert!(plan.feasible);
+        // Largest first: 4M is not enough for 4.5M, so the 2M UTXO is added
+        assert_eq!(plan.selected_utxos.iter().map(|u| u.txid.as_str()).collect::<Vec<_>>(), vec!["large", "medium"]);
+        // 11 + 2 * 68 + 3 * 31 vbytes
+        assert_eq!(plan.estimated_vsize, 240);
+        assert_eq!(plan.fee_satoshis, 2400);
+        assert_eq!(plan.change_satoshis, 6_000_000 - 4_500_000 - 2400);
+        assert_eq!(plan.change_address.as_deref(), Some("bc1qlarge"));
+
+        let plan = plan_payout_run(outputs, &[utxo("small", 0.001)], 10.0);
+        assert!(!plan.feasible);
+        assert!(plan.error.unwrap().contains("Insufficient"));
+    }
+
+    #[test]
+    fn test_plan_payout_run_sizes_taproot() {
+        let taproot = "bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297";
+        let wallet_utxo = UnspentOutput { address: Some(taproot.to_string()), ..utxo("tr", 0.02) };
+        let plan = plan_payout_run(vec![PlannedOutput { address: taproot.to_string(), amount_satoshis: 1_000_000 }], &[wallet_utxo.clone()], 10.0);
+        // 11 + 58 (key path input) + 2 * 43 vbytes
+        assert_eq!((plan.estimated_vsize, plan.fee_satoshis), (155, 1550));
+        assert_eq!(plan.change_satoshis, 2_000_000 - 1_000_000 - 1550);
+
+        // 300 satoshis is above the P2WPKH dust threshold (294) but below P2TR's (330)
+        let dust = plan_payout_run(vec![PlannedOutput { address: taproot.to_string(), amount_satoshis: 300 }], &[wallet_utxo], 10.0);
+        assert!(!dust.feasible);
+        assert!(dust.error.unwrap().contains("dust threshold (330)"));
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_create_and_broadcast() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 5_000_000, 123).await.unwrap();
+
+        let key = Some("retry-1".to_string());
+        let first = manager.create_payout_idempotent(ADDRESS.to_string(), 2_000_000, key.clone()).await.unwrap();
+        let retry = manager.create_payout_idempotent(ADDRESS.to_string(), 2_000_000, key.clone()).await.unwrap();
+        assert_eq!(first.id, retry.id);
+        assert_eq!(manager.get_balance(ADDRESS).await.unwrap().balance_satoshis, 3_000_000);
+
+        // Same key, different payout
+        assert!(manager.create_payout_idempotent(ADDRESS.to_string(), 1_000_000, key).await.is_err());
+
+        // Already broadcast payouts are returned without touching the node
+        {
+            let mut payouts = manager.payouts.write().await;
+            let payout = payouts.get_mut(&first.id).unwrap();
+            payout.status = PayoutStatus::Broadcast;
+            payout.txid = Some("abcd".to_string());
+        }
+        let payout = manager.broadcast_payout(&first.id).await.unwrap();
+        assert_eq!(payout.txid.as_deref(), Some("abcd"));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_retry_resends_signed_transaction() {
+        let mock = MockRpcServer::start().await.unwrap();
+        mock.respond("listunspent", serde_json::json!([
+            { "txid": "aa", "vout": 0, "address": ADDRESS, "amount": 0.1, "confirmations": 10 }
+        ]));
+        mock.fail_next("sendrawtransaction", 1, "connection reset");
+        let temp_dir = TempDir::new().unwrap();
+        let config = PaymentConfig { bitcoin_rpc_url: mock.url().to_string(), ..PaymentConfig::default() };
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), config).unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 5_000_000, 123).await.unwrap();
+        let payout = manager.create_payout(ADDRESS.to_string(), 2_000_000).await.unwrap();
+
+        assert!(manager.broadcast_payout(&payout.id).await.is_err());
+        let sent = manager.broadcast_payout(&payout.id).await.unwrap();
+        assert_eq!(sent.status, PayoutStatus::Broadcast);
+
+        // The retry sends the transaction signed by the first attempt
+        assert_eq!(mock.calls("createrawtransaction").len(), 1);
+        let sends = mock.calls("sendrawtransaction");
+        assert_eq!(sends.len(), 2);
+        assert_eq!(sends[0], sends[1]);
+        assert_eq!(sent.txid, Some(mock_txid(sends[0][0].as_str().unwrap())));
+    }
+
+    #[tokio::test]
+    async fn test_large_payout_held_for_approval() {
+        let temp_dir = TempDir::new().unwrap();
+        let approvals = Arc::new(PayoutApprovals::new(
+            temp_dir.path().join("approvals"),
+            PayoutApprovalConfig { enabled: true, ..Default::default() },
+        ).unwrap());
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap()
+            .with_approvals(approvals.clone());
+
+        manager.add_earnings(ADDRESS.to_string(), 50_000_000, 123).await.unwrap();
+        let payout = manager.create_payout(ADDRESS.to_string(), 20_000_000).await.unwrap();
+
+        // Held before the node is ever asked to build a transaction
+        let err = manager.broadcast_payout_as(&payout.id, "alice").await.unwrap_err();
+        let request = match err.downcast_ref::<PaymentError>() {
+            Some(PaymentError::ApprovalRequired { approval_id, .. }) => approvals.get(approval_id).await.unwrap(),
+            other => panic!("expected ApprovalRequired, got {:?}", other),
+        };
+        assert_eq!(request.requested_by, "alice");
+        assert_eq!(request.amount_satoshis, 20_000_000);
+        assert_eq!(approvals.pending().await.len(), 1);
+        assert_eq!(manager.get_payout_history(ADDRESS, 10).await[0].status, PayoutStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_auto_payout_run_held_until_approved() {
+        let mock = MockRpcServer::start().await.unwrap();
+        mock.respond("listunspent", serde_json::json!([
+            { "txid": "aa", "vout": 0, "address": ADDRESS, "amount": 1.0, "confirmations": 10 }
+        ]));
+        let temp_dir = TempDir::new().unwrap();
+        let approval_config = PayoutApprovalConfig { enabled: true, run_threshold_satoshis: 10_000_000, ..Default::default() };
+        // The pool and dmpool-admin each hold their own view of the shared store
+        let pool_approvals = Arc::new(PayoutApprovals::new(temp_dir.path().join("approvals"), approval_config.clone()).unwrap());
+        let admin_approvals = PayoutApprovals::new(temp_dir.path().join("approvals"), approval_config).unwrap();
+        let config = PaymentConfig {
+            bitcoin_rpc_url: mock.url().to_string(),
+            auto_payout_enabled: true,
+            ..PaymentConfig::default()
+        };
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), config)
+            .unwrap()
+            .with_approvals(pool_approvals);
+        manager.add_earnings(ADDRESS.to_string(), 20_000_000, 123).await.unwrap();
+
+        let created = manager.process_auto_payouts().await.unwrap();
+        assert_eq!(created.len(), 1);
+        assert!(mock.calls("sendrawtransaction").is_empty());
+        let request = admin_approvals.pending().await.pop().unwrap();
+        assert_eq!((request.amount_satoshis, request.requested_by.as_str()), (20_000_000, "auto_payout"));
+
+        // Still held when retried before a decision
+        assert!(manager.broadcast_payout(&created[0].id).await.is_err());
+        assert!(mock.calls("sendrawtransaction").is_empty());
+
+        admin_approvals.approve(&request.id, "bob").await.unwrap();
+        let sent = manager.broadcast_payout(&created[0].id).await.unwrap();
+        assert_eq!(sent.status, PayoutStatus::Broadcast);
+        assert_eq!(mock.calls("sendrawtransaction").len(), 1);
     }
 
     #[tokio::test]
@@ -638,7 +1809,7 @@ mod tests {
         let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
             .unwrap();
 
-        manager.add_earnings("bc1qtest".to_string(), 500_000, 123).await.unwrap();
+        manager.add_earnings(ADDRESS.to_string(), 500_000, 123).await.unwrap();
         manager.save().await.unwrap();
 
         // Create new manager and load
@@ -646,8 +1817,81 @@ mod tests {
             .unwrap();
         manager2.load().await.unwrap();
 
-        let balance = manager2.get_balance("bc1qtest").await;
+        let balance = manager2.get_balance(ADDRESS).await;
         assert!(balance.is_some());
         assert_eq!(balance.unwrap().balance_satoshis, 500_000);
     }
+
+    #[tokio::test]
+    async fn test_sync_recorder_only_sent_payouts() {
+        struct MemoryRecorder(Mutex<Vec<String>>);
+
+        #[async_trait]
+        impl PayoutRecorder for MemoryRecorder {
+            async fn record_payout(&self, payout: &Payout) -> Result<()> {
+                self.0.lock().await.push(payout.id.clone());
+                Ok(())
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let recorder = Arc::new(MemoryRecorder(Mutex::new(Vec::new())));
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap()
+            .with_recorder(recorder.clone());
+
+        manager.add_earnings(ADDRESS.to_string(), 5_000_000, 123).await.unwrap();
+        let sent = manager.create_payout(ADDRESS.to_string(), 1_000_000).await.unwrap();
+        manager.create_payout(ADDRESS.to_string(), 1_000_000).await.unwrap();
+        {
+            let mut payouts = manager.payouts.write().await;
+            let payout = payouts.get_mut(&sent.id).unwrap();
+            payout.status = PayoutStatus::Broadcast;
+        }
+
+        assert_eq!(manager.sync_recorder().await.unwrap(), 1);
+        assert_eq!(*recorder.0.lock().await, vec![sent.id]);
+    }
+
+    #[tokio::test]
+    async fn test_settled_payouts_archived_over_cap() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap()
+            .with_max_payouts(2);
+        manager.add_earnings(ADDRESS.to_string(), 10_000_000, 123).await.unwrap();
+
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            let payout = manager.create_payout(ADDRESS.to_string(), 1_000_000).await.unwrap();
+            manager.confirm_payout(&payout.id, "ab".repeat(32), 124, 6).await.unwrap();
+            ids.push(payout.id);
+        }
+        let pending = manager.create_payout(ADDRESS.to_string(), 1_000_000).await.unwrap();
+
+        // Two in memory (the pending payout and the newest confirmed one), three archived
+        assert_eq!(manager.payouts.read().await.len(), 2);
+        assert_eq!(manager.payouts.read().await.archived().records, 3);
+
+        let history = manager.get_payout_history(ADDRESS, 10).await;
+        let mut expected = vec![pending.id.clone()];
+        expected.extend(ids.iter().rev().cloned());
+        assert_eq!(history.iter().map(|p| p.id.clone()).collect::<Vec<_>>(), expected);
+
+        let page = manager.list_payouts(&PayoutFilter::default(), 2, 2).await.unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(page.payouts.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec![ids[2].as_str(), ids[1].as_str()]);
+        assert_eq!(manager.get_payout(&ids[0]).await.unwrap().unwrap().status, PayoutStatus::Confirmed);
+
+        let stats = manager.get_stats().await;
+        assert_eq!((stats.confirmed_payouts, stats.total_paid_satoshis), (4, 4_000_000));
+
+        // Archived payouts are not reloaded into memory
+        let reloaded = PaymentManager::new(temp_dir.path().to_path_buf(), PaymentConfig::default())
+            .unwrap()
+            .with_max_payouts(2);
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.payouts.read().await.len(), 2);
+        assert_eq!(reloaded.list_payouts(&PayoutFilter::default(), 0, 10).await.unwrap().total, 5);
+    }
 }
@@ -0,0 +1,143 @@
+// Network parameters for payouts
+// Values that differ between mainnet, testnet4, signet and regtest: subsidy
+// schedule, address format and the payout settings that make sense there
+
+use bitcoin::address::{Address, NetworkUnchecked};
+use bitcoin::Network;
+
+use super::DUST_LIMIT_SATOSHIS;
+
+/// Initial block subsidy in satoshis (50 BTC)
+const INITIAL_SUBSIDY_SATOSHIS: u64 = 5_000_000_000;
+
+/// Consensus and policy values for the network the pool mines on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkParams {
+    pub network: Network,
+    /// Blocks between subsidy halvings
+    pub halving_interval: u64,
+    /// Smallest minimum payout allowed; small mainnet payouts cost miners more in fees than they are worth
+    pub min_payout_floor_satoshis: u64,
+    /// Default minimum payout
+    pub default_min_payout_satoshis: u64,
+    /// Fewest confirmations allowed before a payout counts as complete
+    pub min_confirmations: u32,
+}
+
+impl NetworkParams {
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Bitcoin => Self {
+                network,
+                halving_interval: 210_000,
+                min_payout_floor_satoshis: 10_000,
+                default_min_payout_satoshis: 1_000_000,
+                min_confirmations: 3,
+            },
+            Network::Regtest => Self {
+                network,
+                halving_interval: 150,
+                min_payout_floor_satoshis: DUST_LIMIT_SATOSHIS,
+                default_min_payout_satoshis: 10_000,
+                min_confirmations: 1,
+            },
+            // testnet, testnet4 and signet
+            _ => Self {
+                network,
+                halving_interval: 210_000,
+                min_payout_floor_satoshis: DUST_LIMIT_SATOSHIS,
+                default_min_payout_satoshis: 100_000,
+                min_confirmations: 1,
+            },
+        }
+    }
+
+    /// Block subsidy in satoshis at the given height
+    pub fn block_subsidy_satoshis(&self, height: u64) -> u64 {
+        let halvings = height / self.halving_interval;
+        if halvings >= 64 {
+            return 0;
+        }
+        INITIAL_SUBSIDY_SATOSHIS >> halvings
+    }
+
+    /// Parse an address, rejecting addresses for other networks
+    pub fn parse_address(&self, address: &str) -> Result<Address, String> {
+        address.parse::<Address<NetworkUnchecked>>()
+            .map_err(|e| format!("invalid address {}: {}", address, e))?
+            .require_network(self.network)
+            .map_err(|_| format!("address {} is not for {}", address, self.network))
+    }
+
+    pub fn is_valid_address(&self, address: &str) -> bool {
+        self.parse_address(address).is_ok()
+    }
+
+    /// Problems with payment settings on this network; unset settings are not checked
+    pub fn check_payment(&self, min_payout_satoshis: Option<u64>, required_confirmations: Option<u64>) -> Vec<String> {
+        let mut errors = Vec::new();
+        if min_payout_satoshis.is_some_and(|min| min < self.min_payout_floor_satoshis) {
+            errors.push(format!(
+                "minimum payout must be at least {} satoshis on {}",
+                self.min_payout_floor_satoshis, self.network
+            ));
+        }
+        if required_confirmations.is_some_and(|n| n < self.min_confirmations as u64) {
+            errors.push(format!(
+                "at least {} confirmations are required on {}",
+                self.min_confirmations, self.network
+            ));
+        }
+        errors
+    }
+}
+
+/// Parse a network name, accepting `mainnet` for `bitcoin`
+pub fn parse_network(name: &str) -> Option<Network> {
+    match name {
+        "mainnet" => Some(Network::Bitcoin),
+        name => name.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsidy_schedule() {
+        let mainnet = NetworkParams::for_network(Network::Bitcoin);
+        assert_eq!(mainnet.block_subsidy_satoshis(0), 5_000_000_000);
+        assert_eq!(mainnet.block_subsidy_satoshis(840_000), 312_500_000);
+        assert_eq!(NetworkParams::for_network(Network::Testnet4).block_subsidy_satoshis(840_000), 312_500_000);
+
+        // Regtest halves every 150 blocks
+        let regtest = NetworkParams::for_network(Network::Regtest);
+        assert_eq!(regtest.block_subsidy_satoshis(149), 5_000_000_000);
+        assert_eq!(regtest.block_subsidy_satoshis(300), 1_250_000_000);
+        assert_eq!(regtest.block_subsidy_satoshis(150 * 64), 0);
+    }
+
+    #[test]
+    fn test_addresses_and_bounds() {
+        let mainnet = NetworkParams::for_network(Network::Bitcoin);
+        let signet = NetworkParams::for_network(Network::Signet);
+        let mainnet_address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+        let test_address = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+        assert!(mainnet.is_valid_address(mainnet_address));
+        assert!(!mainnet.is_valid_address(test_address));
+        assert!(signet.is_valid_address(test_address));
+        assert!(!signet.is_valid_address(mainnet_address));
+        assert!(!signet.is_valid_address("not an address"));
+
+        assert!(mainnet.check_payment(Some(1_000_000), Some(6)).is_empty());
+        assert!(mainnet.check_payment(None, None).is_empty());
+        assert_eq!(mainnet.check_payment(Some(1_000), Some(1)).len(), 2);
+        assert!(NetworkParams::for_network(Network::Regtest).check_payment(Some(1_000), Some(1)).is_empty());
+
+        assert_eq!(parse_network("mainnet"), Some(Network::Bitcoin));
+        assert_eq!(parse_network("testnet4"), Some(Network::Testnet4));
+        assert_eq!(parse_network("moonnet"), None);
+    }
+}
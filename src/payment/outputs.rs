@@ -0,0 +1,79 @@
+// Output type sizes and dust thresholds
+//
+// Fee estimates and dust checks depend on the script being paid or spent. A
+// P2TR (bech32m) output is 12 vbytes larger than a P2WPKH one but cheaper to
+// spend, and its dust threshold is 330 satoshis rather than 294. Thresholds
+// follow Bitcoin Core's policy at the default 3 sat/vB dust relay fee.
+// Addresses that do not parse are sized as P2WPKH.
+
+use bitcoin::address::{Address, AddressType, NetworkUnchecked};
+use bitcoin::{Script, ScriptBuf};
+
+use super::{TX_INPUT_VBYTES, TX_OUTPUT_VBYTES};
+
+/// Bitcoin Core's default `-dustrelayfee`
+const DUST_RELAY_FEE_SAT_VB: u64 = 3;
+
+/// Size of spending a witness program / a legacy output, as Core assumes for dust
+const WITNESS_SPEND_VBYTES: u64 = 67;
+const LEGACY_SPEND_VBYTES: u64 = 148;
+
+fn script_pubkey(address: &str) -> Option<ScriptBuf> {
+    address.trim().parse::<Address<NetworkUnchecked>>().ok()
+        .map(|address| address.assume_checked().script_pubkey())
+}
+
+fn script_output_vbytes(script: &Script) -> u64 {
+    let len = script.len() as u64;
+    // Value, script length prefix, script
+    8 + if len < 0xfd { 1 } else { 3 } + len
+}
+
+fn script_dust_threshold(script: &Script) -> u64 {
+    let spend = if script.is_witness_program() { WITNESS_SPEND_VBYTES } else { LEGACY_SPEND_VBYTES };
+    (script_output_vbytes(script) + spend) * DUST_RELAY_FEE_SAT_VB
+}
+
+/// Vbytes an output paying `address` adds to a transaction
+pub fn output_vbytes(address: &str) -> u64 {
+    script_pubkey(address).map_or(TX_OUTPUT_VBYTES, |script| script_output_vbytes(&script))
+}
+
+/// Smallest standard output to `address`: 546 for P2PKH, 294 for P2WPKH, 330 for P2WSH and P2TR
+pub fn dust_threshold(address: &str) -> u64 {
+    script_pubkey(address).map_or(294, |script| script_dust_threshold(&script))
+}
+
+/// Vbytes spending a wallet output at `address` adds, assuming single-key scripts
+pub fn input_vbytes(address: Option<&str>) -> u64 {
+    let address_type = address
+        .and_then(|a| a.trim().parse::<Address<NetworkUnchecked>>().ok())
+        .and_then(|a| a.assume_checked().address_type());
+    match address_type {
+        Some(AddressType::P2pkh) => 148,
+        // P2SH-wrapped P2WPKH
+        Some(AddressType::P2sh) => 91,
+        // Key path spend with a 64-byte signature
+        Some(AddressType::P2tr) => 58,
+        _ => TX_INPUT_VBYTES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P2PKH: &str = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+    const P2WPKH: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    const P2TR: &str = "bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297";
+
+    #[test]
+    fn test_sizes_and_dust_per_output_type() {
+        assert_eq!((output_vbytes(P2PKH), dust_threshold(P2PKH), input_vbytes(Some(P2PKH))), (34, 546, 148));
+        assert_eq!((output_vbytes(P2WPKH), dust_threshold(P2WPKH), input_vbytes(Some(P2WPKH))), (31, 294, 68));
+        assert_eq!((output_vbytes(P2TR), dust_threshold(P2TR), input_vbytes(Some(P2TR))), (43, 330, 58));
+        // Testnet bech32m
+        assert_eq!(dust_threshold("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c"), 330);
+        assert_eq!((output_vbytes("bc1qa"), input_vbytes(None)), (31, 68));
+    }
+}
@@ -0,0 +1,236 @@
+// Payout kill switch
+//
+// Halts every payout broadcast and hot wallet sweep at once, e.g. on a
+// suspected wallet compromise. The switch lives in the database
+// (`payout_kill_switch`, migration 014) so flipping it from dmpool-admin also
+// stops the pool process: each broadcast reads it right before sending and
+// refuses to send if it cannot be read. Pausing and resuming raise alerts.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::db::DatabaseManager;
+use crate::error::{DmpoolError, PaymentError};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Alert rule raised when payouts are paused
+pub const PAYOUTS_PAUSED_ALERT_RULE: &str = "payouts_paused";
+
+/// Alert rule raised when payouts are resumed
+pub const PAYOUTS_RESUMED_ALERT_RULE: &str = "payouts_resumed";
+
+/// Longest reason kept with the switch
+const MAX_REASON_LEN: usize = 200;
+
+/// State of the kill switch
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PayoutPauseStatus {
+    pub paused: bool,
+    pub reason: Option<String>,
+    pub changed_by: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+/// Where the switch is kept (payout_kill_switch in production)
+#[async_trait]
+pub trait PayoutPauseStore: Send + Sync {
+    /// The stored switch, `None` if it was never flipped
+    async fn load_payout_pause(&self) -> Result<Option<PayoutPauseStatus>>;
+
+    async fn save_payout_pause(&self, status: &PayoutPauseStatus) -> Result<()>;
+}
+
+#[async_trait]
+impl PayoutPauseStore for DatabaseManager {
+    async fn load_payout_pause(&self) -> Result<Option<PayoutPauseStatus>> {
+        let conn = self.get_conn().await?;
+        let row = conn
+            .query_opt("SELECT paused, reason, changed_by, changed_at FROM payout_kill_switch WHERE id", &[])
+            .await
+            .context("Failed to read payout kill switch")?;
+        Ok(row.map(|row| PayoutPauseStatus {
+            paused: row.get(0),
+            reason: row.get(1),
+            changed_by: row.get(2),
+            changed_at: row.get(3),
+        }))
+    }
+
+    async fn save_payout_pause(&self, status: &PayoutPauseStatus) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO payout_kill_switch (id, paused, reason, changed_by, changed_at) VALUES (TRUE, $1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET paused = $1, reason = $2, changed_by = $3, changed_at = $4",
+            &[&status.paused, &status.reason, &status.changed_by, &status.changed_at],
+        )
+        .await
+        .context("Failed to write payout kill switch")?;
+        Ok(())
+    }
+}
+
+/// Global payout kill switch, shared by the payment manager, wallet tiers and health checks
+#[derive(Default)]
+pub struct PayoutPause {
+    store: Option<Arc<dyn PayoutPauseStore>>,
+    alerts: Option<Arc<AlertManager>>,
+    /// Last state read from or written to the store
+    status: RwLock<PayoutPauseStatus>,
+}
+
+impl PayoutPause {
+    /// Keep the switch in `store` so every process using it sees the same state
+    pub fn with_store(mut self, store: Arc<dyn PayoutPauseStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Alert when payouts are paused or resumed
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Read the switch from the store
+    pub async fn refresh(&self) -> Result<PayoutPauseStatus> {
+        let Some(store) = &self.store else {
+            return Ok(self.status.read().await.clone());
+        };
+        let status = store.load_payout_pause().await?.unwrap_or_default();
+        *self.status.write().await = status.clone();
+        Ok(status)
+    }
+
+    /// Current state, or the last known one if the store cannot be read
+    pub async fn status(&self) -> PayoutPauseStatus {
+        match self.refresh().await {
+            Ok(status) => status,
+            Err(e) => {
+                warn!("Failed to read payout kill switch: {:#}", e);
+                self.status.read().await.clone()
+            }
+        }
+    }
+
+    /// Pause or resume payouts
+    pub async fn set(&self, paused: bool, reason: Option<String>, changed_by: &str) -> Result<PayoutPauseStatus> {
+        if reason.as_ref().is_some_and(|r| r.chars().count() > MAX_REASON_LEN) {
+            return Err(DmpoolError::InvalidInput(format!("reason must be at most {} characters", MAX_REASON_LEN)).into());
+        }
+        let status = PayoutPauseStatus {
+            paused,
+            reason: if paused { reason } else { None },
+            changed_by: Some(changed_by.to_string()),
+            changed_at: Some(Utc::now()),
+        };
+        if let Some(store) = &self.store {
+            store.save_payout_pause(&status).await?;
+        }
+        *self.status.write().await = status.clone();
+
+        if paused {
+            warn!("Payouts paused by {} ({})", changed_by, status.reason.as_deref().unwrap_or("no reason given"));
+        } else {
+            info!("Payouts resumed by {}", changed_by);
+        }
+        self.alert(&status).await;
+        Ok(status)
+    }
+
+    /// Fail with `PaymentError::PayoutsPaused` unless `operation` may send funds now
+    ///
+    /// Fails closed: if the switch cannot be read, nothing is sent.
+    pub async fn ensure_running(&self, operation: &str) -> Result<()> {
+        let status = self.refresh().await.map_err(|e| {
+            PaymentError::PayoutsPaused(format!("Cannot {}: payout kill switch unavailable ({:#})", operation, e))
+        })?;
+        if !status.paused {
+            return Ok(());
+        }
+        let message = match &status.reason {
+            Some(reason) => format!("Payouts are paused ({}); cannot {}", reason, operation),
+            None => format!("Payouts are paused; cannot {}", operation),
+        };
+        Err(PaymentError::PayoutsPaused(message).into())
+    }
+
+    async fn alert(&self, status: &PayoutPauseStatus) {
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+        let (rule_id, name, message, level) = if status.paused {
+            (PAYOUTS_PAUSED_ALERT_RULE, "Payouts paused", "The payout kill switch was engaged; no payouts or sweeps are sent", AlertLevel::Critical)
+        } else {
+            (PAYOUTS_RESUMED_ALERT_RULE, "Payouts resumed", "The payout kill switch was released", AlertLevel::Warning)
+        };
+        if !alerts.get_rules().await.iter().any(|r| r.id == rule_id) {
+            let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                rule_id,
+                name,
+                AlertCondition::Custom { message: message.to_string() },
+                level,
+                channels,
+            ).with_cooldown(0)).await;
+        }
+        if let Err(e) = alerts.trigger_alert(rule_id, serde_json::json!(status)).await {
+            warn!("Failed to raise payout kill switch alert: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{kind_of, ErrorKind};
+    use std::sync::Mutex;
+
+    /// Stands in for the shared database row
+    #[derive(Default)]
+    struct MemorySwitch {
+        row: Mutex<Option<PayoutPauseStatus>>,
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl PayoutPauseStore for MemorySwitch {
+        async fn load_payout_pause(&self) -> Result<Option<PayoutPauseStatus>> {
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("connection refused"));
+            }
+            Ok(self.row.lock().unwrap().clone())
+        }
+
+        async fn save_payout_pause(&self, status: &PayoutPauseStatus) -> Result<()> {
+            *self.row.lock().unwrap() = Some(status.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_switch_is_shared_and_fails_closed() {
+        let row = Arc::new(MemorySwitch::default());
+        let admin = PayoutPause::default().with_store(row.clone());
+        let pool = PayoutPause::default().with_store(row.clone());
+        assert!(pool.ensure_running("broadcast payouts").await.is_ok());
+
+        admin.set(true, Some("wallet compromise".to_string()), "alice").await.unwrap();
+        let err = pool.ensure_running("broadcast payouts").await.unwrap_err();
+        assert_eq!(kind_of(&err), ErrorKind::Unavailable);
+        assert!(err.to_string().contains("(wallet compromise); cannot broadcast payouts"));
+        assert_eq!(pool.status().await.changed_by.as_deref(), Some("alice"));
+
+        admin.set(false, Some("ignored".to_string()), "bob").await.unwrap();
+        assert!(pool.ensure_running("broadcast payouts").await.is_ok());
+        assert_eq!(pool.status().await.reason, None);
+
+        // An unreadable switch blocks sends but status keeps the last known state
+        row.down.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(pool.ensure_running("broadcast payouts").await.unwrap_err().to_string().contains("kill switch unavailable"));
+        assert!(!pool.status().await.paused);
+    }
+}
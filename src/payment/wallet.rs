@@ -0,0 +1,483 @@
+// Hot/cold wallet tiers
+//
+// Payouts are funded from the node's RPC wallet (the hot wallet). Its balance
+// is held near `hot_max_satoshis`: income above that is swept to a cold
+// address that the pool never holds keys for on this host. What miners are
+// owed (pending payouts and balances over the payout threshold) always stays
+// hot, even above the cap, and a low hot balance raises an alert so the
+// operator can top it up from cold storage. Sweeps stop while the payout
+// kill switch is engaged.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bitcoin::address::{Address, NetworkUnchecked};
+use chrono::{DateTime, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::bitcoin::BitcoinRpcClient;
+use crate::error::PaymentError;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+use super::{PaymentManager, PayoutPause, DUST_LIMIT_SATOSHIS};
+
+/// Alert rule raised when the hot wallet runs low
+pub const HOT_WALLET_LOW_ALERT_RULE: &str = "hot_wallet_low";
+
+/// Sweeps kept in sweeps.json
+const MAX_SWEEP_HISTORY: usize = 1000;
+
+/// The `[dmpool.wallet_tiers]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalletTierConfig {
+    pub enabled: bool,
+    /// Address excess hot wallet income is swept to
+    pub cold_address: String,
+    /// Hot wallet balance kept for payouts; anything above is swept
+    pub hot_max_satoshis: u64,
+    /// Alert when the hot wallet falls below this
+    pub hot_low_satoshis: u64,
+    /// Smaller excesses wait for the next check, saving fees
+    pub sweep_min_satoshis: u64,
+    /// Seconds between balance checks
+    pub check_interval_secs: u64,
+}
+
+impl Default for WalletTierConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cold_address: String::new(),
+            hot_max_satoshis: 50_000_000,   // 0.5 BTC
+            hot_low_satoshis: 5_000_000,    // 0.05 BTC
+            sweep_min_satoshis: 1_000_000,  // 0.01 BTC
+            check_interval_secs: 3600,
+        }
+    }
+}
+
+impl WalletTierConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.cold_address.trim().is_empty() {
+            return Err(anyhow::anyhow!("cold_address is required"));
+        }
+        self.cold_address.trim().parse::<Address<NetworkUnchecked>>()
+            .map_err(|e| anyhow::anyhow!("cold_address {} is not a valid address: {}", self.cold_address, e))?;
+        if self.hot_low_satoshis >= self.hot_max_satoshis {
+            return Err(anyhow::anyhow!("hot_low_satoshis must be below hot_max_satoshis ({})", self.hot_max_satoshis));
+        }
+        if self.sweep_min_satoshis <= DUST_LIMIT_SATOSHIS {
+            return Err(anyhow::anyhow!("sweep_min_satoshis must be above the dust limit ({})", DUST_LIMIT_SATOSHIS));
+        }
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Wallet operations the tiers need
+#[async_trait]
+pub trait TierWallet: Send + Sync {
+    /// Confirmed balance of the hot (RPC) wallet
+    async fn hot_balance_satoshis(&self) -> Result<u64>;
+
+    /// Confirmed balance of an address outside the wallet
+    async fn address_balance_satoshis(&self, address: &str) -> Result<u64>;
+
+    /// Send from the hot wallet, fee deducted from the amount; returns the txid
+    async fn sweep_to(&self, address: &str, amount_satoshis: u64) -> Result<String>;
+}
+
+#[async_trait]
+impl TierWallet for BitcoinRpcClient {
+    async fn hot_balance_satoshis(&self) -> Result<u64> {
+        let info = self.get_wallet_info().await?;
+        Ok(btc_to_satoshis(info.balance))
+    }
+
+    async fn address_balance_satoshis(&self, address: &str) -> Result<u64> {
+        Ok(btc_to_satoshis(self.scan_address_balance(address).await?))
+    }
+
+    async fn sweep_to(&self, address: &str, amount_satoshis: u64) -> Result<String> {
+        self.send_to_address(address, amount_satoshis as f64 / 100_000_000.0, true).await
+    }
+}
+
+fn btc_to_satoshis(btc: f64) -> u64 {
+    (btc * 100_000_000.0).round().max(0.0) as u64
+}
+
+/// A transfer from the hot wallet to the cold address
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sweep {
+    pub txid: String,
+    pub amount_satoshis: u64,
+    pub hot_balance_before: u64,
+    /// Whether an admin asked for it rather than the periodic check
+    pub manual: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of a balance check
+#[derive(Clone, Debug, Serialize)]
+pub struct TierCheck {
+    pub hot_balance_satoshis: u64,
+    /// Owed to miners, never swept
+    pub reserved_satoshis: u64,
+    pub low: bool,
+    pub sweep: Option<Sweep>,
+}
+
+/// Hot and cold balances for the admin API
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletTierStatus {
+    pub hot_balance_satoshis: u64,
+    /// None when the UTXO set scan fails
+    pub cold_balance_satoshis: Option<u64>,
+    pub cold_address: String,
+    pub hot_max_satoshis: u64,
+    pub hot_low_satoshis: u64,
+    pub reserved_satoshis: u64,
+    pub low: bool,
+    pub swept_total_satoshis: u64,
+    pub recent_sweeps: Vec<Sweep>,
+}
+
+/// Amount to sweep so the hot wallet ends at `target`, if worth sending
+pub fn sweep_amount(hot_balance: u64, target: u64, sweep_min: u64) -> Option<u64> {
+    let excess = hot_balance.saturating_sub(target);
+    (excess >= sweep_min).then_some(excess)
+}
+
+/// Keeps the hot wallet between its low-balance alert and its cap
+pub struct WalletTiers {
+    config: WalletTierConfig,
+    wallet: Arc<dyn TierWallet>,
+    data_dir: PathBuf,
+    payments: Option<Arc<PaymentManager>>,
+    /// Kill switch checked before every sweep; sweeps are refused without one
+    pause: Option<Arc<PayoutPause>>,
+    alerts: Option<Arc<AlertManager>>,
+    sweeps: RwLock<Vec<Sweep>>,
+    /// Serializes checks so two sweeps never race on the same balance
+    check_lock: Mutex<()>,
+}
+
+impl WalletTiers {
+    pub fn new(data_dir: PathBuf, config: WalletTierConfig, wallet: Arc<dyn TierWallet>) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create wallet tier data directory")?;
+        Ok(Self {
+            config,
+            wallet,
+            data_dir,
+            payments: None,
+            pause: None,
+            alerts: None,
+            sweeps: RwLock::new(Vec::new()),
+            check_lock: Mutex::new(()),
+        })
+    }
+
+    /// Keep what miners are owed in the hot wallet
+    pub fn with_payments(mut self, payments: Arc<PaymentManager>) -> Self {
+        self.payments = Some(payments);
+        self
+    }
+
+    /// Stop sweeps while payouts are paused
+    pub fn with_payout_pause(mut self, pause: Arc<PayoutPause>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// Raise an alert when the hot wallet runs low
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn config(&self) -> &WalletTierConfig {
+        &self.config
+    }
+
+    /// Load sweep history from disk
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("sweeps.json");
+        let Some(sweeps) = persist::read_json::<Vec<Sweep>>(&path).await
+            .context("Failed to load sweeps file")?
+        else {
+            return Ok(());
+        };
+        info!("Loaded {} wallet sweeps", sweeps.len());
+        *self.sweeps.write().await = sweeps;
+        Ok(())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&*self.sweeps.read().await)
+            .context("Failed to serialize sweeps")?;
+        persist::write_atomic(&self.data_dir.join("sweeps.json"), &json).await
+            .context("Failed to write sweeps file")
+    }
+
+    /// Satoshis owed to miners: unsent payouts plus balances due for payout
+    pub async fn reserved_satoshis(&self) -> u64 {
+        match &self.payments {
+            Some(payments) => payments.owed_payouts().await.1,
+            None => 0,
+        }
+    }
+
+    /// Check the hot balance, alert if low and sweep any excess to cold storage
+    pub async fn check(&self) -> Result<TierCheck> {
+        self.run(false, self.config.sweep_min_satoshis).await
+    }
+
+    /// Sweep now, whatever the excess, as long as it is above dust
+    pub async fn sweep_now(&self) -> Result<TierCheck> {
+        self.run(true, DUST_LIMIT_SATOSHIS + 1).await
+    }
+
+    /// Fails closed: without a kill switch to check, nothing is swept
+    async fn ensure_sweeps_running(&self) -> Result<()> {
+        match &self.pause {
+            Some(pause) => pause.ensure_running("sweep the hot wallet").await,
+            None => Err(PaymentError::PayoutsPaused("Cannot sweep the hot wallet: no payout kill switch configured".to_string()).into()),
+        }
+    }
+
+    async fn run(&self, manual: bool, sweep_min: u64) -> Result<TierCheck> {
+        let _guard = self.check_lock.lock().await;
+
+        let hot = self.wallet.hot_balance_satoshis().await
+            .context("Failed to read hot wallet balance")?;
+        let reserved = self.reserved_satoshis().await;
+        let low = hot < self.config.hot_low_satoshis;
+        if low {
+            self.alert_low(hot, reserved).await;
+        } else if let Some(alerts) = &self.alerts {
+            // Topped up: resolve the low balance alert
+            let context = serde_json::json!({ "hot_balance_satoshis": hot });
+            if let Err(e) = alerts.clear_rule(HOT_WALLET_LOW_ALERT_RULE, context).await {
+                warn!("Failed to clear hot wallet alert: {:#}", e);
+            }
+        }
+
+        let target = self.config.hot_max_satoshis.max(reserved);
+        let amount = match sweep_amount(hot, target, sweep_min) {
+            Some(amount) => match self.ensure_sweeps_running().await {
+                Ok(()) => Some(amount),
+                Err(e) if manual => return Err(e),
+                Err(e) => {
+                    info!("Skipping hot wallet sweep: {:#}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let sweep = match amount {
+            Some(amount) => {
+                let txid = self.wallet.sweep_to(&self.config.cold_address, amount).await
+                    .with_context(|| format!("Failed to sweep {} satoshis to cold storage", amount))?;
+                info!("Swept {} satoshis from the hot wallet to {} (txid {})", amount, self.config.cold_address, txid);
+                let sweep = Sweep {
+                    txid,
+                    amount_satoshis: amount,
+                    hot_balance_before: hot,
+                    manual,
+                    created_at: Utc::now(),
+                };
+                {
+                    let mut sweeps = self.sweeps.write().await;
+                    sweeps.push(sweep.clone());
+                    let excess = sweeps.len().saturating_sub(MAX_SWEEP_HISTORY);
+                    sweeps.drain(..excess);
+                }
+                if let Err(e) = self.save().await {
+                    warn!("Failed to save sweep history: {:#}", e);
+                }
+                Some(sweep)
+            }
+            None => None,
+        };
+
+        Ok(TierCheck {
+            hot_balance_satoshis: hot,
+            reserved_satoshis: reserved,
+            low,
+            sweep,
+        })
+    }
+
+    async fn alert_low(&self, hot: u64, reserved: u64) {
+        warn!("Hot wallet balance {} satoshis is below {}", hot, self.config.hot_low_satoshis);
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+        if !alerts.get_rules().await.iter().any(|r| r.id == HOT_WALLET_LOW_ALERT_RULE) {
+            let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                HOT_WALLET_LOW_ALERT_RULE,
+                "Hot wallet balance low",
+                AlertCondition::Custom { message: "The hot wallet needs topping up from cold storage".to_string() },
+                AlertLevel::Warning,
+                channels,
+            ).with_cooldown(21600)).await;
+        }
+        let context = serde_json::json!({
+            "hot_balance_satoshis": hot,
+            "hot_low_satoshis": self.config.hot_low_satoshis,
+            "reserved_satoshis": reserved,
+        });
+        if let Err(e) = alerts.trigger_alert(HOT_WALLET_LOW_ALERT_RULE, context).await {
+            warn!("Failed to raise hot wallet alert: {}", e);
+        }
+    }
+
+    /// Current hot and cold balances with recent sweeps
+    pub async fn status(&self, recent: usize) -> Result<WalletTierStatus> {
+        let hot = self.wallet.hot_balance_satoshis().await
+            .context("Failed to read hot wallet balance")?;
+        let cold = match self.wallet.address_balance_satoshis(&self.config.cold_address).await {
+            Ok(balance) => Some(balance),
+            Err(e) => {
+                warn!("Failed to read cold wallet balance: {:#}", e);
+                None
+            }
+        };
+        let sweeps = self.sweeps.read().await;
+        Ok(WalletTierStatus {
+            hot_balance_satoshis: hot,
+            cold_balance_satoshis: cold,
+            cold_address: self.config.cold_address.clone(),
+            hot_max_satoshis: self.config.hot_max_satoshis,
+            hot_low_satoshis: self.config.hot_low_satoshis,
+            reserved_satoshis: self.reserved_satoshis().await,
+            low: hot < self.config.hot_low_satoshis,
+            swept_total_satoshis: sweeps.iter().map(|s| s.amount_satoshis).sum(),
+            recent_sweeps: sweeps.iter().rev().take(recent).cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::PaymentConfig;
+    use tempfile::TempDir;
+
+    struct MemoryWallet {
+        hot: Mutex<u64>,
+        sent: Mutex<Vec<(String, u64)>>,
+    }
+
+    #[async_trait]
+    impl TierWallet for MemoryWallet {
+        async fn hot_balance_satoshis(&self) -> Result<u64> {
+            Ok(*self.hot.lock().await)
+        }
+
+        async fn address_balance_satoshis(&self, _address: &str) -> Result<u64> {
+            Ok(self.sent.lock().await.iter().map(|(_, amount)| amount).sum())
+        }
+
+        async fn sweep_to(&self, address: &str, amount_satoshis: u64) -> Result<String> {
+            *self.hot.lock().await -= amount_satoshis;
+            let mut sent = self.sent.lock().await;
+            sent.push((address.to_string(), amount_satoshis));
+            Ok(format!("tx{}", sent.len()))
+        }
+    }
+
+    fn config() -> WalletTierConfig {
+        WalletTierConfig {
+            enabled: true,
+            cold_address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweeps_excess_above_cap_and_reserve() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = Arc::new(MemoryWallet { hot: Mutex::new(80_000_000), sent: Mutex::new(Vec::new()) });
+        let payments = Arc::new(PaymentManager::new(temp_dir.path().join("payment"), PaymentConfig::default()).unwrap());
+        let tiers = WalletTiers::new(temp_dir.path().join("payment"), config(), wallet.clone())
+            .unwrap()
+            .with_payments(payments.clone())
+            .with_payout_pause(Arc::new(PayoutPause::default()));
+
+        let check = tiers.check().await.unwrap();
+        assert_eq!(check.sweep.unwrap().amount_satoshis, 30_000_000);
+        assert_eq!(*wallet.hot.lock().await, 50_000_000);
+
+        // Below the sweep minimum: nothing sent
+        *wallet.hot.lock().await += 500_000;
+        assert!(tiers.check().await.unwrap().sweep.is_none());
+
+        // Miners are owed more than the cap, so it all stays hot
+        payments.add_earnings("bc1qminer".to_string(), 70_000_000, 1).await.unwrap();
+        *wallet.hot.lock().await = 75_000_000;
+        let check = tiers.check().await.unwrap();
+        assert_eq!(check.reserved_satoshis, 70_000_000);
+        assert_eq!(check.sweep.unwrap().amount_satoshis, 5_000_000);
+
+        let status = tiers.status(10).await.unwrap();
+        assert_eq!(status.cold_balance_satoshis, Some(35_000_000));
+        assert_eq!(status.swept_total_satoshis, 35_000_000);
+        assert_eq!(status.recent_sweeps[0].txid, "tx2");
+
+        // History survives a restart
+        let reloaded = WalletTiers::new(temp_dir.path().join("payment"), config(), wallet).unwrap();
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.status(10).await.unwrap().recent_sweeps.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_sweep_while_paused_or_without_kill_switch() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = Arc::new(MemoryWallet { hot: Mutex::new(80_000_000), sent: Mutex::new(Vec::new()) });
+
+        // No kill switch to check: refused
+        let tiers = WalletTiers::new(temp_dir.path().to_path_buf(), config(), wallet.clone()).unwrap();
+        assert!(tiers.check().await.unwrap().sweep.is_none());
+        assert!(tiers.sweep_now().await.is_err());
+
+        let pause = Arc::new(PayoutPause::default());
+        let tiers = tiers.with_payout_pause(pause.clone());
+        pause.set(true, Some("incident".to_string()), "alice").await.unwrap();
+        assert!(tiers.check().await.unwrap().sweep.is_none());
+        assert!(tiers.sweep_now().await.is_err());
+        assert!(wallet.sent.lock().await.is_empty());
+
+        pause.set(false, None, "alice").await.unwrap();
+        assert_eq!(tiers.sweep_now().await.unwrap().sweep.unwrap().amount_satoshis, 30_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_low_balance_alerts_without_sweeping() {
+        let temp_dir = TempDir::new().unwrap();
+        let wallet = Arc::new(MemoryWallet { hot: Mutex::new(1_000_000), sent: Mutex::new(Vec::new()) });
+        let alerts = Arc::new(AlertManager::default());
+        let tiers = WalletTiers::new(temp_dir.path().to_path_buf(), config(), wallet.clone())
+            .unwrap()
+            .with_alerts(alerts.clone());
+
+        let check = tiers.check().await.unwrap();
+        assert!(check.low);
+        assert!(check.sweep.is_none());
+        assert!(wallet.sent.lock().await.is_empty());
+        assert_eq!(alerts.get_history(None).await.len(), 1);
+
+        assert!(WalletTierConfig { hot_low_satoshis: 60_000_000, ..config() }.validate().is_err());
+        assert!(WalletTierConfig { cold_address: "not-an-address".to_string(), ..config() }.validate().is_err());
+        assert!(config().validate().is_ok());
+    }
+}
@@ -0,0 +1,282 @@
+// Persistence Module for DMPool
+// Crash-safe JSON state files shared by the file-persisting managers
+//
+// Writing a state file in place leaves it truncated if the process dies
+// mid-write. Files written here go to a temp file first, are fsynced and then
+// renamed over the original, so a reader sees either the old or the new
+// contents. Each file gets a `.sha256` sidecar and the previous good version
+// is kept as `.bak`; a file that is missing or fails its checksum on read is
+// recovered from the `.bak` copy (the damaged file is kept as `.corrupt`).
+// Files written before checksums existed have no sidecar and are trusted.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tracing::{error, warn};
+
+/// Per-file locks so concurrent saves don't interleave their renames
+static FILE_LOCKS: LazyLock<std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+async fn lock(path: &Path) -> OwnedMutexGuard<()> {
+    let file_lock = FILE_LOCKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+    file_lock.lock_owned().await
+}
+
+/// `path` with `suffix` appended to the file name
+pub(crate) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub(crate) fn checksum_path(path: &Path) -> PathBuf {
+    sibling(path, ".sha256")
+}
+
+/// Last good version of `path`
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+fn checksum(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+async fn exists(path: &Path) -> Result<bool> {
+    fs::try_exists(path).await.with_context(|| format!("Failed to check {}", path.display()))
+}
+
+/// Write a file and fsync it
+async fn write_synced(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(path).await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents).await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.sync_all().await
+        .with_context(|| format!("Failed to sync {}", path.display()))
+}
+
+/// Persist the renames in `path`'s directory
+async fn sync_dir(path: &Path) {
+    let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+        return;
+    };
+    // Directories can only be opened for syncing on unix
+    if !cfg!(unix) {
+        return;
+    }
+    let synced = match fs::File::open(dir).await {
+        Ok(handle) => handle.sync_all().await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = synced {
+        warn!("Failed to sync directory {}: {}", dir.display(), e);
+    }
+}
+
+/// Replace `path` with `contents` atomically
+///
+/// The current file and its checksum become the `.bak` copy, then the new
+/// checksum and the new contents (written to a temp file and fsynced) are
+/// renamed into place. A crash at any point leaves `path` either complete or
+/// missing, and a missing file is recovered from `.bak` on read.
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let _guard = lock(path).await;
+    let temp = sibling(path, ".tmp");
+    write_synced(&temp, contents).await?;
+
+    let sum = checksum_path(path);
+    if exists(path).await? {
+        // Checksum first: if we stop in between, the current file has no
+        // sidecar and is still trusted as a legacy file
+        let backup = backup_path(path);
+        let backup_sum = checksum_path(&backup);
+        if exists(&sum).await? {
+            fs::rename(&sum, &backup_sum).await
+                .with_context(|| format!("Failed to keep checksum of {}", path.display()))?;
+        } else if exists(&backup_sum).await? {
+            // A legacy file has no checksum; don't pair it with an older one
+            fs::remove_file(&backup_sum).await
+                .with_context(|| format!("Failed to remove stale checksum of {}", backup.display()))?;
+        }
+        fs::rename(path, &backup).await
+            .with_context(|| format!("Failed to keep {} as backup", path.display()))?;
+    }
+
+    let sum_temp = sibling(&sum, ".tmp");
+    write_synced(&sum_temp, checksum(contents).as_bytes()).await?;
+    fs::rename(&sum_temp, &sum).await
+        .with_context(|| format!("Failed to write checksum of {}", path.display()))?;
+    fs::rename(&temp, path).await
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    sync_dir(path).await;
+    Ok(())
+}
+
+/// Contents of `path` if it exists and matches its checksum
+///
+/// `Err` means the file is there but damaged.
+async fn read_checked(path: &Path) -> Result<Option<Vec<u8>>> {
+    let contents = match fs::read(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    match fs::read_to_string(checksum_path(path)).await {
+        Ok(expected) if expected.trim() != checksum(&contents) => {
+            Err(anyhow::anyhow!("{} does not match its checksum", path.display()))
+        }
+        Ok(_) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Some(contents)),
+        Err(e) => Err(e).with_context(|| format!("Failed to read checksum of {}", path.display())),
+    }
+}
+
+/// Read `path`, recovering from the last good copy if it is missing or damaged
+///
+/// Returns `None` when neither the file nor its backup exist.
+pub async fn read_verified(path: &Path) -> Result<Option<Vec<u8>>> {
+    let _guard = lock(path).await;
+    let damage = match read_checked(path).await {
+        Ok(Some(contents)) => return Ok(Some(contents)),
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    let backup = backup_path(path);
+    let recovered = match read_checked(&backup).await {
+        Ok(recovered) => recovered,
+        Err(backup_err) => {
+            return Err(damage.unwrap_or(backup_err))
+                .with_context(|| format!("No intact copy of {}", path.display()));
+        }
+    };
+    let Some(recovered) = recovered else {
+        return match damage {
+            Some(e) => Err(e).with_context(|| format!("No backup of {} to recover from", path.display())),
+            None => Ok(None),
+        };
+    };
+
+    match &damage {
+        Some(e) => error!("{:#}; recovering from {}", e, backup.display()),
+        None => warn!("{} is missing; recovering from {}", path.display(), backup.display()),
+    }
+    if let Err(e) = restore(path, &backup, damage.is_some()).await {
+        error!("Failed to restore {} from backup: {:#}", path.display(), e);
+    }
+    Ok(Some(recovered))
+}
+
+/// Put the backup back in place so the next write doesn't rotate a damaged file into `.bak`
+async fn restore(path: &Path, backup: &Path, damaged: bool) -> Result<()> {
+    if damaged {
+        fs::rename(path, sibling(path, ".corrupt")).await
+            .with_context(|| format!("Failed to set aside {}", path.display()))?;
+    }
+    let sum = checksum_path(path);
+    if exists(&sum).await? {
+        fs::remove_file(&sum).await?;
+    }
+    let temp = sibling(path, ".tmp");
+    fs::copy(backup, &temp).await
+        .with_context(|| format!("Failed to copy {}", backup.display()))?;
+    let backup_sum = checksum_path(backup);
+    if exists(&backup_sum).await? {
+        fs::copy(&backup_sum, &sum).await?;
+    }
+    fs::rename(&temp, path).await?;
+    sync_dir(path).await;
+    Ok(())
+}
+
+/// Serialize `value` as pretty JSON and write it atomically
+pub async fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    let json = serde_json::to_vec_pretty(value)
+        .with_context(|| format!("Failed to serialize {}", path.display()))?;
+    write_atomic(path, &json).await
+}
+
+/// Read and parse a JSON file written by `write_json`, with recovery
+///
+/// Returns `None` when neither the file nor its backup exist.
+pub async fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match read_verified(path).await? {
+        Some(contents) => serde_json::from_slice(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_write_keeps_backup_and_checksum() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+
+        write_json(&path, &vec![1, 2]).await.unwrap();
+        write_json(&path, &vec![3]).await.unwrap();
+
+        assert_eq!(read_json::<Vec<u32>>(&path).await.unwrap(), Some(vec![3]));
+        assert_eq!(read_json::<Vec<u32>>(&backup_path(&path)).await.unwrap(), Some(vec![1, 2]));
+        assert!(checksum_path(&path).exists());
+        assert!(!sibling(&path, ".tmp").exists());
+        assert_eq!(read_json::<Vec<u32>>(&dir.path().join("missing.json")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_recovers_damaged_or_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        write_json(&path, &vec![1]).await.unwrap();
+        write_json(&path, &vec![2]).await.unwrap();
+
+        // Torn write: contents no longer match the checksum
+        std::fs::write(&path, b"[2").unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).await.unwrap(), Some(vec![1]));
+        assert!(sibling(&path, ".corrupt").exists());
+        // Restored in place, so the next write keeps a good backup
+        assert_eq!(read_json::<Vec<u32>>(&path).await.unwrap(), Some(vec![1]));
+        write_json(&path, &vec![3]).await.unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&backup_path(&path)).await.unwrap(), Some(vec![1]));
+
+        // Crash between rotating to .bak and renaming the new file into place
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).await.unwrap(), Some(vec![1]));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_file_without_checksum_is_trusted() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, b"[7]").unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path).await.unwrap(), Some(vec![7]));
+
+        write_json(&path, &vec![8]).await.unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&backup_path(&path)).await.unwrap(), Some(vec![7]));
+
+        // Damaged with no backup to fall back on
+        std::fs::remove_file(backup_path(&path)).unwrap();
+        std::fs::write(&path, b"[9]").unwrap();
+        assert!(read_json::<Vec<u32>>(&path).await.is_err());
+    }
+}
@@ -2,7 +2,9 @@
 // Validates the correctness of PPLNS payout calculations
 
 use anyhow::Result;
+use bitcoin::Network;
 use chrono::{DateTime, Utc};
+use crate::payment::NetworkParams;
 use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -26,6 +28,9 @@ pub struct PayoutCalculation {
     pub block_reward_satoshis: u64,
     /// Pool fee/deduction (satoshi)
     pub pool_fee_satoshis: u64,
+    /// Rounding remainder credited by the rounding policy (included in the final payout)
+    #[serde(default)]
+    pub remainder_satoshis: u64,
     /// Final payout amount
     pub final_payout_satoshis: u64,
 }
@@ -51,6 +56,65 @@ pub struct PplnsValidationResult {
     pub validated_at: DateTime<Utc>,
 }
 
+/// Where the satoshis lost to integer division go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingPolicy {
+    /// Keep the remainder as pool income
+    #[default]
+    Pool,
+    /// Credit the whole remainder to the miner with the most difficulty
+    LargestContributor,
+    /// One satoshi per miner in contribution order, starting at a rotating offset
+    RoundRobin,
+}
+
+impl std::str::FromStr for RoundingPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pool" => Ok(Self::Pool),
+            "largest_contributor" => Ok(Self::LargestContributor),
+            "round_robin" => Ok(Self::RoundRobin),
+            other => Err(anyhow::anyhow!("Unknown rounding policy: {}", other)),
+        }
+    }
+}
+
+/// Split `remainder` satoshis among contributors according to `policy`
+///
+/// `contributions` are (address, difficulty) pairs and the result is aligned
+/// with them. Contributors are ordered by difficulty, then address, so the
+/// split does not depend on input order; `rotation` (e.g. the block height)
+/// picks where round-robin starts so the same miners are not always favoured.
+pub fn distribute_remainder(policy: RoundingPolicy, remainder: u64, contributions: &[(&str, u64)], rotation: u64) -> Vec<u64> {
+    let mut credits = vec![0u64; contributions.len()];
+    if remainder == 0 || contributions.is_empty() {
+        return credits;
+    }
+
+    let mut order: Vec<usize> = (0..contributions.len()).collect();
+    order.sort_by(|&a, &b| {
+        contributions[b].1.cmp(&contributions[a].1)
+            .then_with(|| contributions[a].0.cmp(contributions[b].0))
+    });
+
+    match policy {
+        RoundingPolicy::Pool => {}
+        RoundingPolicy::LargestContributor => credits[order[0]] = remainder,
+        RoundingPolicy::RoundRobin => {
+            let n = order.len() as u64;
+            let start = (rotation % n) as usize;
+            for (i, &index) in order.iter().cycle().skip(start).take(order.len()).enumerate() {
+                credits[index] = remainder / n + u64::from((i as u64) < remainder % n);
+            }
+        }
+    }
+
+    credits
+}
+
 /// PPLNS payment simulator for testing
 pub struct PplnsSimulator {
     /// Block reward in satoshis (for mainnet, this is variable)
@@ -59,6 +123,8 @@ pub struct PplnsSimulator {
     pool_fee_bps: u16,
     /// PPLNS window time window (days)
     pplns_window_days: u64,
+    /// Where rounding remainders go
+    rounding: RoundingPolicy,
 }
 
 impl PplnsSimulator {
@@ -68,9 +134,26 @@ impl PplnsSimulator {
             block_reward_satoshis,
             pool_fee_bps,
             pplns_window_days,
+            rounding: RoundingPolicy::default(),
         }
     }
 
+    /// Set the rounding remainder policy
+    pub fn with_rounding(mut self, rounding: RoundingPolicy) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Pool fee taken from the whole block reward
+    pub fn pool_fee_satoshis(&self) -> u64 {
+        (self.block_reward_satoshis as u128 * self.pool_fee_bps as u128 / 10000).min(u64::MAX as u128) as u64
+    }
+
+    /// Reward left for miners after the pool fee
+    pub fn distributable_satoshis(&self) -> u64 {
+        self.block_reward_satoshis.saturating_sub(self.pool_fee_satoshis())
+    }
+
     /// Default simulator (using mainnet values)
     pub fn default() -> Self {
         Self::new(
@@ -80,6 +163,16 @@ impl PplnsSimulator {
         )
     }
 
+    /// Simulator paying the block subsidy of `network` at `height`, with default fee and window
+    pub fn for_network(network: Network, height: u64) -> Self {
+        let default = Self::default();
+        Self::new(
+            NetworkParams::for_network(network).block_subsidy_satoshis(height),
+            default.pool_fee_bps,
+            default.pplns_window_days,
+        )
+    }
+
     /// Calculate payout for a single miner based on their shares
     pub fn calculate_payout(
         &self,
@@ -100,11 +193,11 @@ impl PplnsSimulator {
             return None;
         }
 
-        // Calculate total difficulty of miner's shares
-        let total_difficulty: u64 = miner_shares.iter().map(|s| s.difficulty).sum();
+        // Sum difficulties in u128: a window of large share difficulties can overflow u64
+        let total_difficulty: u128 = miner_shares.iter().map(|s| s.difficulty as u128).sum();
 
         // Calculate total difficulty of all shares in PPLNS window
-        let window_difficulty: u64 = shares.iter().map(|s| s.difficulty).sum();
+        let window_difficulty: u128 = shares.iter().map(|s| s.difficulty as u128).sum();
 
         if window_difficulty == 0 {
             return None;
@@ -113,18 +206,19 @@ impl PplnsSimulator {
         // Calculate proportional payout using u128 to prevent overflow
         // (block_reward_satoshis * total_difficulty) could be very large
         let proportional_payout: u128 = (self.block_reward_satoshis as u128)
-            * (total_difficulty as u128)
-            / (window_difficulty as u128);
+            * total_difficulty
+            / window_difficulty;
 
-        // Calculate pool fee using u128 to prevent overflow
-        let pool_fee: u128 = (proportional_payout
-            * (self.pool_fee_bps as u128))
-            / 10000u128;
+        // Miners split the reward net of the pool fee, which is taken once from
+        // the whole reward; per-miner fees rounded down could add up to less
+        // than the pool's cut and pay out more than the reward net of fees
+        let net_payout: u128 = (self.distributable_satoshis() as u128)
+            * total_difficulty
+            / window_difficulty;
+        let pool_fee: u128 = proportional_payout - net_payout;
 
-        // Final payout (ensure no negative values)
-        let final_payout = proportional_payout
-            .saturating_sub(pool_fee)
-            .min(u64::MAX as u128) as u64;
+        // Final payout before any rounding remainder is credited
+        let final_payout = net_payout.min(u64::MAX as u128) as u64;
 
         // Convert pool_fee back to u64 for storage
         let pool_fee_u64 = pool_fee.min(u64::MAX as u128) as u64;
@@ -136,11 +230,12 @@ impl PplnsSimulator {
                 .and_then(|s| s.workername.clone())
                 .unwrap_or_else(|| "unknown".to_string()),
             share_count: miner_shares.len() as u64,
-            total_difficulty,
+            total_difficulty: total_difficulty.min(u64::MAX as u128) as u64,
             payout_satoshis: proportional_payout.min(u64::MAX as u128) as u64,
             pplns_window_size: shares.len() as u64,
             block_reward_satoshis: self.block_reward_satoshis,
             pool_fee_satoshis: pool_fee_u64,
+            remainder_satoshis: 0,
             final_payout_satoshis: final_payout,
         })
     }
@@ -163,16 +258,27 @@ impl PplnsSimulator {
         // Calculate payout for each miner
         for miner_addr in unique_miners.iter() {
             if let Some(payout) = self.calculate_payout(shares, miner_addr) {
-                total_payout += payout.final_payout_satoshis;
                 payouts.push(payout);
             }
         }
 
-        // Validate calculations
-        let _total_difficulty: u64 = shares.iter().map(|s| s.difficulty).sum();
-        let expected_total_payout = self.block_reward_satoshis.saturating_sub(
-            (self.block_reward_satoshis * self.pool_fee_bps as u64) / 10000
+        // Credit the rounding remainder
+        let expected_total_payout = self.distributable_satoshis();
+        let base_payout: u64 = payouts.iter().map(|p| p.final_payout_satoshis).sum();
+        let contributions: Vec<(&str, u64)> = payouts.iter()
+            .map(|p| (p.address.as_str(), p.total_difficulty))
+            .collect();
+        let credits = distribute_remainder(
+            self.rounding,
+            expected_total_payout.saturating_sub(base_payout),
+            &contributions,
+            0,
         );
+        for (payout, credit) in payouts.iter_mut().zip(credits) {
+            payout.remainder_satoshis = credit;
+            payout.final_payout_satoshis += credit;
+            total_payout += payout.final_payout_satoshis;
+        }
 
         // Check if payouts exceed block reward
         if total_payout > expected_total_payout {
@@ -182,6 +288,14 @@ impl PplnsSimulator {
             ));
         }
 
+        // Any policy other than Pool must pay out exactly the reward net of fees
+        if self.rounding != RoundingPolicy::Pool && !payouts.is_empty() && total_payout != expected_total_payout {
+            errors.push(format!(
+                "Total payouts ({}) do not match distributable reward ({})",
+                total_payout, expected_total_payout
+            ));
+        }
+
         // Check for negative payouts
         for payout in &payouts {
             if payout.final_payout_satoshis == 0 && payout.share_count > 0 {
@@ -242,7 +356,7 @@ impl PplnsSimulator {
             let time_span = newest.n_time.saturating_sub(oldest.n_time);
             let time_span_days = time_span / 86400; // Convert seconds to days
 
-            if time_span_days > expected_window_days * 2 {
+            if time_span_days > expected_window_days.saturating_mul(2) {
                 return Err(format!(
                     "PPLNS window spans {} days, expected around {} days",
                     time_span_days, expected_window_days
@@ -302,9 +416,145 @@ pub struct ScenarioResult {
     pub details: PplnsValidationResult,
 }
 
+/// Upper bound on generated synthetic shares per simulation
+const MAX_SYNTHETIC_SHARES: u64 = 100_000;
+
+/// A synthetic miner in a simulation request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SyntheticMiner {
+    pub address: String,
+    /// Shares submitted within the window
+    pub share_count: u64,
+    /// Difficulty of each share
+    pub difficulty: u64,
+}
+
+/// Where simulation shares come from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SimulationShares {
+    /// Generated shares spread evenly over the window
+    Synthetic { miners: Vec<SyntheticMiner> },
+    /// The pool's real shares from the last `window_days`
+    Window { limit: Option<usize> },
+}
+
+/// Simulation parameters
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimulationRequest {
+    pub block_reward_satoshis: u64,
+    pub pool_fee_bps: u16,
+    pub window_days: u64,
+    pub shares: SimulationShares,
+    /// Fee to compare against, e.g. the current one
+    pub baseline_fee_bps: Option<u16>,
+    #[serde(default)]
+    pub rounding: RoundingPolicy,
+}
+
+impl SimulationRequest {
+    /// Check parameters are within sane bounds
+    pub fn validate(&self) -> Result<()> {
+        if self.block_reward_satoshis == 0 || self.block_reward_satoshis > 21_000_000 * 100_000_000 {
+            return Err(anyhow::anyhow!("block_reward_satoshis out of range"));
+        }
+        if self.pool_fee_bps > 10_000 || self.baseline_fee_bps.is_some_and(|bps| bps > 10_000) {
+            return Err(anyhow::anyhow!("Fee must be at most 10000 bps"));
+        }
+        if self.window_days == 0 || self.window_days > 365 {
+            return Err(anyhow::anyhow!("window_days must be between 1 and 365"));
+        }
+        if let SimulationShares::Synthetic { miners } = &self.shares {
+            if miners.is_empty() {
+                return Err(anyhow::anyhow!("Synthetic share source needs at least one miner"));
+            }
+            let total: u64 = miners.iter().map(|m| m.share_count).sum();
+            if total > MAX_SYNTHETIC_SHARES {
+                return Err(anyhow::anyhow!("At most {} synthetic shares are allowed", MAX_SYNTHETIC_SHARES));
+            }
+        }
+        Ok(())
+    }
+
+    /// Simulator for the requested parameters
+    pub fn simulator(&self) -> PplnsSimulator {
+        PplnsSimulator::new(self.block_reward_satoshis, self.pool_fee_bps, self.window_days)
+            .with_rounding(self.rounding)
+    }
+}
+
+/// Generate shares for synthetic miners, spread evenly over the window ending at `end_time`
+pub fn synthetic_shares(miners: &[SyntheticMiner], window_days: u64, end_time: u64) -> Vec<SimplePplnsShare> {
+    let span = window_days * 86400;
+    let mut shares = Vec::new();
+
+    for (user_id, miner) in miners.iter().enumerate() {
+        let step = span / miner.share_count.max(1);
+        for i in 0..miner.share_count {
+            let n_time = end_time.saturating_sub(span) + i * step;
+            shares.push(SimplePplnsShare {
+                btcaddress: Some(miner.address.clone()),
+                workername: Some("simulated".to_string()),
+                user_id: user_id as u64,
+                difficulty: miner.difficulty,
+                n_time,
+                job_id: format!("sim-{}-{}", user_id, i),
+                extranonce2: "00000000".to_string(),
+                nonce: format!("{:08x}", i),
+            });
+        }
+    }
+
+    shares
+}
+
+/// Result of a simulation request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub request: SimulationRequest,
+    pub result: PplnsValidationResult,
+    pub scenarios: Vec<ScenarioResult>,
+    /// Same shares at the baseline fee, if requested
+    pub baseline: Option<PplnsValidationResult>,
+    /// Total miner payout change versus the baseline
+    pub delta_vs_baseline_satoshis: Option<i64>,
+}
+
+/// Run a simulation request against a share set
+pub async fn run_simulation(request: SimulationRequest, shares: &[SimplePplnsShare]) -> SimulationReport {
+    let simulator = request.simulator();
+    let mut result = simulator.simulate_payouts(shares);
+
+    // Sanity checks don't fail the run but are worth surfacing
+    if let Err(e) = simulator.validate_difficulty_bounds(shares) {
+        result.warnings.push(e);
+    }
+    if let Err(e) = simulator.validate_window_size(shares, request.window_days) {
+        result.warnings.push(e);
+    }
+
+    let scenarios = simulator.run_scenarios(shares).await;
+    let baseline = request.baseline_fee_bps.map(|bps| {
+        PplnsSimulator::new(request.block_reward_satoshis, bps, request.window_days)
+            .with_rounding(request.rounding)
+            .simulate_payouts(shares)
+    });
+    let delta_vs_baseline_satoshis = baseline.as_ref()
+        .map(|b| result.total_payout_satoshis as i64 - b.total_payout_satoshis as i64);
+
+    SimulationReport {
+        request,
+        result,
+        scenarios,
+        baseline,
+        delta_vs_baseline_satoshis,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     fn create_test_share(address: &str, difficulty: u64, time: u64) -> SimplePplnsShare {
         SimplePplnsShare {
@@ -350,11 +600,44 @@ mod tests {
         // Calculate expected payout
         // Total: 100M satoshis (1 BTC)
         // bc1qtest1: 100M * 3000/5000 = 60M satoshis
-        // Pool fee: 60M * 100/10000 = 0.6M satoshis
-        // Final: 60M - 0.6M = 59.4M satoshis
+        // Pool fee: 100M * 100/10000 = 1M satoshis, leaving 99M for miners
+        // Final: 99M * 3000/5000 = 59.4M satoshis (0.6M of it fee)
         assert_eq!(test1_payout.final_payout_satoshis, 59400000);
     }
 
+    #[test]
+    fn test_rounding_remainder_policies() {
+        // 100 sats over three equal miners leaves 1 sat of remainder
+        let shares = vec![
+            create_test_share("bc1qc", 1000, 1000),
+            create_test_share("bc1qa", 1000, 2000),
+            create_test_share("bc1qb", 1000, 3000),
+        ];
+        let final_of = |rounding| -> HashMap<String, u64> {
+            PplnsSimulator::new(100, 0, 7).with_rounding(rounding).simulate_payouts(&shares)
+                .payouts.into_iter()
+                .map(|p| (p.address, p.final_payout_satoshis))
+                .collect()
+        };
+
+        assert_eq!(final_of(RoundingPolicy::Pool).values().sum::<u64>(), 99);
+        let largest = final_of(RoundingPolicy::LargestContributor);
+        assert_eq!((largest["bc1qa"], largest["bc1qb"], largest["bc1qc"]), (34, 33, 33));
+
+        // Round-robin starts at the rotation offset in contribution order
+        let contributions = [("bc1qc", 1000), ("bc1qa", 1000), ("bc1qb", 1000), ("bc1qd", 10)];
+        assert_eq!(distribute_remainder(RoundingPolicy::RoundRobin, 2, &contributions, 1), vec![1, 0, 1, 0]);
+        assert_eq!(distribute_remainder(RoundingPolicy::RoundRobin, 5, &contributions, 3), vec![1, 1, 1, 2]);
+        assert_eq!(distribute_remainder(RoundingPolicy::Pool, 5, &contributions, 0), vec![0; 4]);
+    }
+
+    #[test]
+    fn test_network_defaults() {
+        assert_eq!(PplnsSimulator::for_network(Network::Bitcoin, 840_000).distributable_satoshis(), 312_500_000);
+        assert_eq!(PplnsSimulator::for_network(Network::Regtest, 840_000).distributable_satoshis(), 0);
+        assert_eq!(PplnsSimulator::for_network(Network::Regtest, 200).distributable_satoshis(), 2_500_000_000);
+    }
+
     #[test]
     fn test_difficulty_validation() {
         let simulator = PplnsSimulator::default();
@@ -402,4 +685,143 @@ mod tests {
 
         assert!(simulator.validate_window_size(&wide_shares, 7).is_err());
     }
+
+    #[tokio::test]
+    async fn test_synthetic_simulation_with_baseline() {
+        let request = SimulationRequest {
+            block_reward_satoshis: 100_000_000,
+            pool_fee_bps: 200,
+            window_days: 7,
+            shares: SimulationShares::Synthetic {
+                miners: vec![
+                    SyntheticMiner { address: "bc1qtest1".to_string(), share_count: 30, difficulty: 1000 },
+                    SyntheticMiner { address: "bc1qtest2".to_string(), share_count: 10, difficulty: 1000 },
+                ],
+            },
+            baseline_fee_bps: Some(100),
+            rounding: RoundingPolicy::RoundRobin,
+        };
+        assert!(request.validate().is_ok());
+
+        let miners = match &request.shares {
+            SimulationShares::Synthetic { miners } => miners.clone(),
+            _ => unreachable!(),
+        };
+        let shares = synthetic_shares(&miners, request.window_days, 1_000_000_000);
+        assert_eq!(shares.len(), 40);
+        assert!(PplnsSimulator::default().validate_window_size(&shares, 7).is_ok());
+
+        let report = run_simulation(request, &shares).await;
+        assert!(report.result.valid);
+        assert_eq!(report.result.total_payout_satoshis, 98_000_000);
+        assert_eq!(report.baseline.unwrap().total_payout_satoshis, 99_000_000);
+        assert_eq!(report.delta_vs_baseline_satoshis, Some(-1_000_000));
+        assert_eq!(report.scenarios.len(), 2);
+    }
+
+    #[test]
+    fn test_simulation_request_validation() {
+        let mut request = SimulationRequest {
+            block_reward_satoshis: 312_500_000,
+            pool_fee_bps: 100,
+            window_days: 7,
+            shares: SimulationShares::Window { limit: None },
+            baseline_fee_bps: None,
+            rounding: RoundingPolicy::default(),
+        };
+        assert!(request.validate().is_ok());
+
+        request.pool_fee_bps = 10_001;
+        assert!(request.validate().is_err());
+
+        request.pool_fee_bps = 100;
+        request.shares = SimulationShares::Synthetic { miners: Vec::new() };
+        assert!(request.validate().is_err());
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Random PPLNS window: (miner index, difficulty) per share
+        fn window() -> impl Strategy<Value = Vec<(u8, u64)>> {
+            prop::collection::vec(
+                (0u8..16, prop_oneof![1u64..1_000_000, 1u64..=u64::MAX]),
+                1..200,
+            )
+        }
+
+        fn shares(window: &[(u8, u64)]) -> Vec<SimplePplnsShare> {
+            window.iter().enumerate()
+                .map(|(i, (miner, difficulty))| create_test_share(&format!("bc1qminer{}", miner), *difficulty, i as u64))
+                .collect()
+        }
+
+        proptest! {
+            #[test]
+            fn payouts_never_exceed_reward(
+                window in window(),
+                reward in 0u64..=21_000_000 * 100_000_000,
+                fee_bps in 0u16..=10_000,
+            ) {
+                let result = PplnsSimulator::new(reward, fee_bps, 7).simulate_payouts(&shares(&window));
+                let fee = (reward as u128 * fee_bps as u128 / 10_000) as u64;
+
+                prop_assert!(result.valid, "{:?}", result.errors);
+                prop_assert!(result.total_payout_satoshis <= reward - fee);
+                let gross: u64 = result.payouts.iter().map(|p| p.payout_satoshis).sum();
+                prop_assert!(gross <= reward);
+            }
+
+            #[test]
+            fn payouts_are_proportional(window in window(), reward in 1u64..=u64::MAX, fee_bps in 0u16..=10_000) {
+                let shares = shares(&window);
+                let result = PplnsSimulator::new(reward, fee_bps, 7).simulate_payouts(&shares);
+                let window_difficulty: u128 = shares.iter().map(|s| s.difficulty as u128).sum();
+
+                for payout in &result.payouts {
+                    let difficulty: u128 = shares.iter()
+                        .filter(|s| s.btcaddress.as_deref() == Some(payout.address.as_str()))
+                        .map(|s| s.difficulty as u128)
+                        .sum();
+                    // Floor of the exact share, fee taken from that
+                    let expected = reward as u128 * difficulty / window_difficulty;
+                    prop_assert_eq!(payout.payout_satoshis as u128, expected);
+                    prop_assert_eq!(payout.final_payout_satoshis, payout.payout_satoshis - payout.pool_fee_satoshis + payout.remainder_satoshis);
+                }
+
+                // Rounding loses less than one satoshi per miner
+                let gross: u128 = result.payouts.iter().map(|p| p.payout_satoshis as u128).sum();
+                prop_assert!(reward as u128 - gross < result.payouts.len().max(1) as u128);
+            }
+
+            #[test]
+            fn remainder_policies_pay_exact_total(
+                window in window(),
+                reward in 0u64..=21_000_000 * 100_000_000,
+                fee_bps in 0u16..=10_000,
+            ) {
+                let shares = shares(&window);
+                let fee = (reward as u128 * fee_bps as u128 / 10_000) as u64;
+
+                for rounding in [RoundingPolicy::LargestContributor, RoundingPolicy::RoundRobin] {
+                    let result = PplnsSimulator::new(reward, fee_bps, 7).with_rounding(rounding).simulate_payouts(&shares);
+                    prop_assert!(result.valid, "{:?}", result.errors);
+                    prop_assert_eq!(result.total_payout_satoshis, reward - fee);
+                }
+            }
+
+            #[test]
+            fn more_work_never_pays_less(window in window(), reward in 1u64..=u64::MAX) {
+                let result = PplnsSimulator::new(reward, 0, 7).simulate_payouts(&shares(&window));
+                let mut payouts: Vec<_> = result.payouts.iter()
+                    .map(|p| (p.total_difficulty, p.payout_satoshis))
+                    .collect();
+                payouts.sort();
+                for pair in payouts.windows(2) {
+                    prop_assert!(pair[0].1 <= pair[1].1);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,291 @@
+// PPLNS Window Module for DMPool
+// Aggregates the live PPLNS share window per miner with short-TTL caching
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crate::backfill::ShareSource;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Default cache TTL for window snapshots
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Maximum shares read when building a snapshot
+const MAX_WINDOW_SHARES: usize = 2_000_000;
+
+/// Default block reward used for projections (3.125 BTC subsidy)
+const DEFAULT_BLOCK_REWARD_SATOSHIS: u64 = 312_500_000;
+
+/// Per-miner totals within the window
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MinerWindowTotals {
+    pub share_count: u64,
+    pub difficulty: u64,
+}
+
+/// Aggregated PPLNS window at a point in time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    /// When the snapshot was computed
+    pub computed_at: DateTime<Utc>,
+    /// Window start (unix seconds)
+    pub window_start: u64,
+    /// Window end (unix seconds)
+    pub window_end: u64,
+    /// Shares in the window
+    pub total_shares: u64,
+    /// Total difficulty in the window
+    pub total_difficulty: u64,
+    /// Whether the share read hit MAX_WINDOW_SHARES
+    pub truncated: bool,
+    /// Totals per miner address
+    pub miners: HashMap<String, MinerWindowTotals>,
+}
+
+/// A miner's contribution to the current window
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerContribution {
+    pub address: String,
+    /// Miner's shares in the window
+    pub share_count: u64,
+    /// Miner's difficulty in the window
+    pub difficulty: u64,
+    /// Total difficulty of all shares in the window
+    pub window_difficulty: u64,
+    /// Total shares in the window
+    pub window_shares: u64,
+    /// Miner's share of the window (0-100)
+    pub percentage: f64,
+    /// Payout if a block were found now, after pool fee
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub projected_payout_satoshis: u64,
+    /// Block reward used for the projection
+    #[serde(serialize_with = "crate::observer_api::units::satoshis")]
+    pub block_reward_satoshis: u64,
+    /// PPLNS window length (seconds)
+    pub window_secs: u64,
+    /// When the underlying snapshot was computed
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Cached view of the live PPLNS window
+pub struct PplnsWindow {
+    source: Arc<dyn ShareSource>,
+    window_secs: u64,
+    pool_fee_bps: u32,
+    block_reward_satoshis: AtomicU64,
+    cache_ttl: Duration,
+    cached: RwLock<Option<(Instant, Arc<WindowSnapshot>)>>,
+}
+
+impl PplnsWindow {
+    /// Create a window over the last `window_secs` seconds of shares
+    pub fn new(source: Arc<dyn ShareSource>, window_secs: u64, pool_fee_bps: u32) -> Self {
+        Self {
+            source,
+            window_secs,
+            pool_fee_bps,
+            block_reward_satoshis: AtomicU64::new(DEFAULT_BLOCK_REWARD_SATOSHIS),
+            cache_ttl: DEFAULT_CACHE_TTL,
+            cached: RwLock::new(None),
+        }
+    }
+
+    /// Override the cache TTL
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Set the block reward used for payout projections
+    pub fn set_block_reward(&self, satoshis: u64) {
+        self.block_reward_satoshis.store(satoshis, Ordering::Relaxed);
+    }
+
+    /// Current snapshot, recomputed if the cache has expired
+    pub async fn snapshot(&self) -> Result<Arc<WindowSnapshot>> {
+        if let Some((at, snapshot)) = self.cached.read().await.as_ref() {
+            if at.elapsed() < self.cache_ttl {
+                return Ok(snapshot.clone());
+            }
+        }
+
+        let mut cached = self.cached.write().await;
+        // Another request may have refreshed while we waited for the lock
+        if let Some((at, snapshot)) = cached.as_ref() {
+            if at.elapsed() < self.cache_ttl {
+                return Ok(snapshot.clone());
+            }
+        }
+
+        let source = self.source.clone();
+        let window_secs = self.window_secs;
+        let snapshot = tokio::task::spawn_blocking(move || Self::compute(source.as_ref(), window_secs))
+            .await
+            .context("PPLNS window computation panicked")?;
+
+        if snapshot.truncated {
+            warn!("PPLNS window truncated at {} shares", MAX_WINDOW_SHARES);
+        }
+        debug!("Computed PPLNS window: {} shares, {} miners", snapshot.total_shares, snapshot.miners.len());
+
+        let snapshot = Arc::new(snapshot);
+        *cached = Some((Instant::now(), snapshot.clone()));
+        Ok(snapshot)
+    }
+
+    /// Snapshot computed now, bypassing the cache
+    pub async fn fresh_snapshot(&self) -> Result<Arc<WindowSnapshot>> {
+        *self.cached.write().await = None;
+        self.snapshot().await
+    }
+
+    /// Pool fee applied to projections (basis points)
+    pub fn pool_fee_bps(&self) -> u32 {
+        self.pool_fee_bps
+    }
+
+    /// Contribution of a single miner to the current window
+    pub async fn contribution(&self, address: &str) -> Result<MinerContribution> {
+        let snapshot = self.snapshot().await?;
+        let totals = snapshot.miners.get(address).cloned().unwrap_or_default();
+        let block_reward_satoshis = self.block_reward_satoshis.load(Ordering::Relaxed);
+
+        let (percentage, projected_payout_satoshis) = if snapshot.total_difficulty > 0 {
+            // u128 to avoid overflow, matching PplnsSimulator
+            let gross = block_reward_satoshis as u128 * totals.difficulty as u128
+                / snapshot.total_difficulty as u128;
+            let fee = gross * self.pool_fee_bps as u128 / 10000;
+            (
+                totals.difficulty as f64 / snapshot.total_difficulty as f64 * 100.0,
+                (gross - fee).min(u64::MAX as u128) as u64,
+            )
+        } else {
+            (0.0, 0)
+        };
+
+        Ok(MinerContribution {
+            address: address.to_string(),
+            share_count: totals.share_count,
+            difficulty: totals.difficulty,
+            window_difficulty: snapshot.total_difficulty,
+            window_shares: snapshot.total_shares,
+            percentage,
+            projected_payout_satoshis,
+            block_reward_satoshis,
+            window_secs: self.window_secs,
+            computed_at: snapshot.computed_at,
+        })
+    }
+
+    /// Aggregate shares in the window ending now
+    fn compute(source: &dyn ShareSource, window_secs: u64) -> WindowSnapshot {
+        let window_end = Utc::now().timestamp().max(0) as u64;
+        let window_start = window_end.saturating_sub(window_secs);
+        let shares = source.fetch_shares(window_start, window_end, MAX_WINDOW_SHARES);
+
+        let mut miners: HashMap<String, MinerWindowTotals> = HashMap::new();
+        let mut total_difficulty = 0u64;
+        for share in &shares {
+            total_difficulty = total_difficulty.saturating_add(share.difficulty);
+            if let Some(address) = &share.btcaddress {
+                let totals = miners.entry(address.clone()).or_default();
+                totals.share_count += 1;
+                totals.difficulty = totals.difficulty.saturating_add(share.difficulty);
+            }
+        }
+
+        WindowSnapshot {
+            computed_at: Utc::now(),
+            window_start,
+            window_end,
+            total_shares: shares.len() as u64,
+            total_difficulty,
+            truncated: shares.len() >= MAX_WINDOW_SHARES,
+            miners,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+    use std::sync::atomic::AtomicUsize;
+
+    struct VecSource {
+        shares: Vec<SimplePplnsShare>,
+        fetches: AtomicUsize,
+    }
+
+    impl ShareSource for VecSource {
+        fn fetch_shares(&self, start: u64, end: u64, limit: usize) -> Vec<SimplePplnsShare> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            self.shares.iter()
+                .filter(|s| s.n_time >= start && s.n_time <= end)
+                .take(limit)
+                .cloned()
+                .collect()
+        }
+    }
+
+    fn share(address: &str, difficulty: u64, time: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some("test-worker".to_string()),
+            user_id: 1,
+            difficulty,
+            n_time: time,
+            job_id: format!("job-{}", time),
+            extranonce2: "00000001".to_string(),
+            nonce: format!("{:08x}", time),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_contribution_and_projection() {
+        let now = Utc::now().timestamp() as u64;
+        let source = Arc::new(VecSource {
+            shares: vec![
+                share("bc1qa", 3000, now - 60),
+                share("bc1qb", 1000, now - 30),
+                // Outside the one-hour window
+                share("bc1qa", 5000, now - 7200),
+            ],
+            fetches: AtomicUsize::new(0),
+        });
+        let window = PplnsWindow::new(source, 3600, 100);
+        window.set_block_reward(100_000_000);
+
+        let a = window.contribution("bc1qa").await.unwrap();
+        assert_eq!(a.share_count, 1);
+        assert_eq!(a.window_difficulty, 4000);
+        assert!((a.percentage - 75.0).abs() < 1e-9);
+        // 75M gross less 1% fee
+        assert_eq!(a.projected_payout_satoshis, 74_250_000);
+
+        let unknown = window.contribution("bc1qunknown").await.unwrap();
+        assert_eq!(unknown.share_count, 0);
+        assert_eq!(unknown.projected_payout_satoshis, 0);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_cached() {
+        let source = Arc::new(VecSource { shares: Vec::new(), fetches: AtomicUsize::new(0) });
+        let window = PplnsWindow::new(source.clone(), 3600, 0);
+
+        window.snapshot().await.unwrap();
+        window.snapshot().await.unwrap();
+        assert_eq!(source.fetches.load(Ordering::Relaxed), 1);
+
+        let window = PplnsWindow::new(source.clone(), 3600, 0).with_cache_ttl(Duration::ZERO);
+        window.snapshot().await.unwrap();
+        window.snapshot().await.unwrap();
+        assert_eq!(source.fetches.load(Ordering::Relaxed), 3);
+    }
+}
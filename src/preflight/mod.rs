@@ -0,0 +1,386 @@
+// Preflight Module for DMPool
+// Startup checks that report every problem at once, before services start
+//
+// Without these the pool fails halfway through startup on the first broken
+// dependency, and fixing it only reveals the next one. Preflight checks config
+// consistency, PostgreSQL and its schema version, bitcoind RPC credentials,
+// network and wallet, the ZMQ endpoint and free disk space, and each failed
+// check says what to fix. Run it with `dmpool --config config.toml preflight`;
+// startup runs it too unless `[dmpool.preflight] enabled = false`.
+
+use anyhow::Result;
+use bitcoin::Network;
+use p2poolv2_lib::config::Config;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+use crate::app::DmpoolConfig;
+use crate::bitcoin::BitcoinRpcClient;
+use crate::config_mgt::{ConfigManager, ConfigProfile};
+use crate::db::{check_status, DatabaseManager, MigrationState};
+use crate::disk::free_space_bytes;
+
+/// The `[dmpool.preflight]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreflightConfig {
+    /// Run the checks at startup and refuse to start if any fail
+    pub enabled: bool,
+    /// Seconds each network check may take
+    pub timeout_secs: u64,
+    /// Free space required on the store, data and backup filesystems
+    pub min_free_disk_mb: u64,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_secs: 5,
+            min_free_disk_mb: 1024,
+        }
+    }
+}
+
+impl PreflightConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout_secs == 0 || self.timeout_secs > 60 {
+            return Err(anyhow::anyhow!("timeout_secs must be between 1 and 60"));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// Startup continues, but something needs attention
+    Warn,
+    /// Startup would fail or misbehave
+    Fail,
+}
+
+/// One preflight check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    pub fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    pub fn warn(name: &str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    pub fn fail(name: &str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.to_string(), status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+/// Every check, in the order they were run
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    /// No check failed (warnings are allowed)
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Warn)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let label = match check.status {
+                CheckStatus::Pass => "ok",
+                CheckStatus::Warn => "WARN",
+                CheckStatus::Fail => "FAIL",
+            };
+            writeln!(f, "[{:>4}] {}: {}", label, check.name, check.detail)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "       -> {}", hint)?;
+            }
+        }
+        write!(f, "{} failed, {} warnings", self.failures().count(), self.warnings().count())
+    }
+}
+
+/// Run every check against the node and pool configs
+pub async fn run(config: &Config, dmpool: &DmpoolConfig) -> PreflightReport {
+    let timeout = Duration::from_secs(dmpool.preflight.timeout_secs);
+    let rpc = BitcoinRpcClient::new(
+        format!("http://{}", config.bitcoinrpc.url),
+        config.bitcoinrpc.username.clone(),
+        config.bitcoinrpc.password.clone(),
+    );
+    // Payouts and wallet tiers spend from the node's wallet
+    let wallet_required = dmpool.payment.auto_payout_enabled || dmpool.wallet_tiers.enabled;
+
+    let mut checks = vec![check_config(config, dmpool).await];
+    let (database, bitcoind, zmq) = tokio::join!(
+        check_database(&dmpool.database.url, timeout),
+        check_bitcoind(&rpc, config.stratum.network, wallet_required, timeout),
+        check_zmq(&config.stratum.zmqpubhashblock, timeout),
+    );
+    checks.push(database);
+    checks.extend(bitcoind);
+    checks.push(zmq);
+    for (label, path) in disk_paths(config, dmpool) {
+        checks.push(check_disk(&label, &path, dmpool.preflight.min_free_disk_mb));
+    }
+    PreflightReport { checks }
+}
+
+/// Schema validation plus settings that must agree between the node and pool configs
+pub async fn check_config(config: &Config, dmpool: &DmpoolConfig) -> CheckResult {
+    const NAME: &str = "config";
+    let data_dir = dmpool.data_dir.clone().unwrap_or_else(|| PathBuf::from(&config.store.path));
+    let profile = dmpool.config_versions.profile.unwrap_or_else(|| ConfigProfile::for_network(config.stratum.network));
+    let schema = ConfigManager::new(data_dir.join("config_versions")).with_profile(profile);
+    if let Err(e) = dmpool.validate(&schema).await {
+        return CheckResult::fail(NAME, format!("{:#}", e), "Fix the [dmpool] section of the config file");
+    }
+
+    let listeners = [
+        ("[stratum]", config.stratum.hostname.as_str(), u32::from(config.stratum.port)),
+        ("[api]", config.api.hostname.as_str(), u32::from(config.api.port)),
+        ("[dmpool.observer_api]", dmpool.observer_api.host.as_str(), u32::from(dmpool.observer_api.port)),
+        ("[dmpool.admin_api]", dmpool.admin_api.host.as_str(), u32::from(dmpool.admin_api.port)),
+    ];
+    for (i, (section, host, port)) in listeners.iter().enumerate() {
+        let clash = listeners[i + 1..].iter()
+            .find(|(_, other_host, other_port)| other_port == port && hosts_overlap(host, other_host));
+        if let Some((other, _, _)) = clash {
+            return CheckResult::fail(
+                NAME,
+                format!("{} and {} both listen on port {}", section, other, port),
+                "Give every listener its own port",
+            );
+        }
+    }
+    if config.store.path.trim().is_empty() {
+        return CheckResult::fail(NAME, "[store] path is empty", "Set [store] path to the node's data directory");
+    }
+    CheckResult::pass(NAME, format!("valid for {}", config.stratum.network))
+}
+
+/// Two listen addresses would collide on the same port
+fn hosts_overlap(a: &str, b: &str) -> bool {
+    let wildcard = |host: &str| matches!(host, "0.0.0.0" | "::" | "[::]" | "");
+    a == b || wildcard(a) || wildcard(b)
+}
+
+/// Run a check with a deadline
+async fn within<T>(timeout: Duration, check: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(timeout, check)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {}s", timeout.as_secs())))
+}
+
+/// PostgreSQL is reachable and its migrations match this build
+pub async fn check_database(url: &str, timeout: Duration) -> CheckResult {
+    const NAME: &str = "database";
+    let hint = "Check [dmpool.database] url (or DATABASE_URL) and that PostgreSQL accepts connections from this host";
+    let db = match DatabaseManager::new(url) {
+        Ok(db) => db,
+        Err(e) => return CheckResult::fail(NAME, format!("invalid database URL: {:#}", e), hint),
+    };
+    let status = match within(timeout, db.migration_status()).await {
+        Ok(status) => status,
+        Err(e) => return CheckResult::fail(NAME, format!("cannot connect: {:#}", e), hint),
+    };
+    if let Err(e) = check_status(&status) {
+        return CheckResult::fail(NAME, format!("{:#}", e), "Restore the original migration files and add a new migration for the change");
+    }
+
+    let count = |state| status.iter().filter(|s| s.state == state).count();
+    let version = status.iter()
+        .filter(|s| s.state == MigrationState::Applied)
+        .map(|s| s.version)
+        .max()
+        .unwrap_or(0);
+    let (pending, unknown) = (count(MigrationState::Pending), count(MigrationState::Unknown));
+    if unknown > 0 {
+        return CheckResult::warn(
+            NAME,
+            format!("schema has {} migration(s) newer than this build", unknown),
+            "The database was migrated by a newer dmpool; upgrade this binary before relying on it",
+        );
+    }
+    if pending > 0 {
+        return CheckResult::warn(
+            NAME,
+            format!("schema version {}, {} migration(s) pending", version, pending),
+            "They are applied at startup; preview them with `dmpool --config <file> migrate --dry-run`",
+        );
+    }
+    CheckResult::pass(NAME, format!("schema version {}", version))
+}
+
+/// bitcoind accepts the RPC credentials, is on the configured network and has a wallet loaded
+pub async fn check_bitcoind(rpc: &BitcoinRpcClient, network: Network, wallet_required: bool, timeout: Duration) -> Vec<CheckResult> {
+    const NAME: &str = "bitcoind";
+    let info = match within(timeout, rpc.get_blockchain_info()).await {
+        Ok(info) => info,
+        Err(e) => {
+            let detail = format!("{:#}", e);
+            let hint = if detail.contains("401") {
+                "The RPC credentials were rejected; set [bitcoinrpc] username and password to bitcoin.conf's rpcuser/rpcpassword"
+            } else {
+                "Check that bitcoind is running, [bitcoinrpc] url is right and bitcoin.conf's rpcbind/rpcallowip admit this host"
+            };
+            return vec![CheckResult::fail(NAME, detail, hint)];
+        }
+    };
+
+    let expected = network.to_core_arg();
+    let node = if info.chain != expected {
+        CheckResult::fail(
+            NAME,
+            format!("node is on {} but [stratum] network is {}", info.chain, network),
+            "Point [bitcoinrpc] url at a node for the configured network, or fix [stratum] network",
+        )
+    } else if info.initial_block_download {
+        CheckResult::warn(
+            NAME,
+            format!("{} node still syncing ({} of {} blocks)", info.chain, info.blocks, info.headers),
+            "Templates and payouts are unreliable until the initial block download finishes",
+        )
+    } else {
+        CheckResult::pass(NAME, format!("{} node at height {}", info.chain, info.blocks))
+    };
+
+    const WALLET: &str = "bitcoind wallet";
+    let wallet = match within(timeout, rpc.get_wallet_info()).await {
+        Ok(wallet) => CheckResult::pass(WALLET, format!("wallet '{}' loaded", wallet.wallet_name)),
+        Err(e) => {
+            let hint = "Load the payout wallet with `bitcoin-cli loadwallet <name>` (add wallet=<name> to bitcoin.conf to load it on start)";
+            if wallet_required {
+                CheckResult::fail(WALLET, format!("no usable wallet: {:#}", e), hint)
+            } else {
+                CheckResult::warn(WALLET, format!("no usable wallet: {:#}", e), hint)
+            }
+        }
+    };
+    vec![node, wallet]
+}
+
+/// The `tcp://host:port` ZMQ endpoint accepts connections
+pub async fn check_zmq(url: &str, timeout: Duration) -> CheckResult {
+    const NAME: &str = "zmq";
+    let hint = "Set zmqpubhashblock in bitcoin.conf to the endpoint in [stratum] zmqpubhashblock";
+    let Some(address) = url.strip_prefix("tcp://").filter(|a| !a.is_empty()) else {
+        return CheckResult::fail(NAME, format!("invalid endpoint '{}'", url), "Use the form tcp://host:port");
+    };
+    let connect = async { TcpStream::connect(address).await.map_err(anyhow::Error::from) };
+    match within(timeout, connect).await {
+        Ok(_) => CheckResult::pass(NAME, format!("{} reachable", address)),
+        Err(e) => CheckResult::fail(NAME, format!("cannot connect to {}: {:#}", address, e), hint),
+    }
+}
+
+/// Directories the pool writes to, without duplicates
+fn disk_paths(config: &Config, dmpool: &DmpoolConfig) -> Vec<(String, PathBuf)> {
+    let store = PathBuf::from(&config.store.path);
+    let data_dir = dmpool.data_dir.clone().unwrap_or_else(|| store.clone());
+    let mut paths = vec![("store".to_string(), store)];
+    if !paths.iter().any(|(_, p)| *p == data_dir) {
+        paths.push(("data_dir".to_string(), data_dir.clone()));
+    }
+    if dmpool.backup.enabled {
+        let backups = dmpool.backup.backup_dir.clone().unwrap_or_else(|| data_dir.join("backups"));
+        paths.push(("backups".to_string(), backups));
+    }
+    paths
+}
+
+/// At least `min_free_mb` is free where `path` lives
+pub fn check_disk(label: &str, path: &Path, min_free_mb: u64) -> CheckResult {
+    let name = format!("disk ({})", label);
+    match free_space_bytes(path) {
+        Ok(free) => {
+            let free_mb = free / (1024 * 1024);
+            if free_mb < min_free_mb {
+                CheckResult::fail(
+                    &name,
+                    format!("{} has {} MB free, below {} MB", path.display(), free_mb, min_free_mb),
+                    "Free up space or move the directory to a larger volume",
+                )
+            } else {
+                CheckResult::pass(&name, format!("{} has {} MB free", path.display(), free_mb))
+            }
+        }
+        Err(e) => CheckResult::warn(
+            &name,
+            format!("cannot measure free space for {}: {:#}", path.display(), e),
+            "Check that the directory is on a mounted, readable volume",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_check_and_report() {
+        let dir = tempfile::tempdir().unwrap();
+        // Missing directories are measured at their existing parent
+        let missing = dir.path().join("not/created/yet");
+        assert!(free_space_bytes(&missing).unwrap() > 0);
+        assert_eq!(check_disk("store", &missing, 0).status, CheckStatus::Pass);
+        assert_eq!(check_disk("store", &missing, u64::MAX / (2 * 1024 * 1024)).status, CheckStatus::Fail);
+
+        let report = PreflightReport {
+            checks: vec![
+                CheckResult::pass("config", "valid for signet"),
+                CheckResult::warn("database", "2 migration(s) pending", "They are applied at startup"),
+                CheckResult::fail("zmq", "cannot connect", "Set zmqpubhashblock"),
+            ],
+        };
+        assert!(!report.passed());
+        let text = report.to_string();
+        assert!(text.contains("[FAIL] zmq: cannot connect\n       -> Set zmqpubhashblock"));
+        assert!(text.ends_with("1 failed, 1 warnings"));
+    }
+
+    #[tokio::test]
+    async fn test_zmq_endpoint() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        assert_eq!(check_zmq(&url, Duration::from_secs(1)).await.status, CheckStatus::Pass);
+
+        let invalid = check_zmq("ipc:///tmp/zmq", Duration::from_secs(1)).await;
+        assert_eq!(invalid.status, CheckStatus::Fail);
+        assert_eq!(invalid.hint.as_deref(), Some("Use the form tcp://host:port"));
+
+        drop(listener);
+        let closed = check_zmq(&url, Duration::from_secs(1)).await;
+        assert_eq!(closed.status, CheckStatus::Fail);
+        assert!(closed.hint.unwrap().contains("bitcoin.conf"));
+    }
+}
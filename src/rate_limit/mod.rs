@@ -1,7 +1,17 @@
 // Rate limiting module for DMPool Admin API
-// Prevents brute force attacks and API abuse
+// Prevents brute force attacks and API abuse, and scores stratum clients per IP
+//
+// Counts allowed and rejected requests per class, remembers which clients
+// were rejected most and lets operators block an IP for a while.
+
+pub mod stratum;
+
+pub use stratum::{IpScore, StratumActivity, StratumGuardConfig, StratumScorer, start_stratum_guard};
 
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use crate::error::DmpoolError;
+use crate::runtime_metrics::gauge;
 use axum::{
     extract::{Request, State},
     http::{StatusCode, HeaderMap},
@@ -9,12 +19,13 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
 use std::net::{IpAddr, Ipv4Addr};
 use std::num::NonZeroU32;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{warn, debug, error};
+use tracing::{warn, debug, error, info};
 
 /// Rate limiter configuration
 #[derive(Clone)]
@@ -23,6 +34,8 @@ pub struct RateLimitConfig {
     pub api_rpm: NonZeroU32,
     /// Requests per minute for login endpoint (stricter)
     pub login_rpm: NonZeroU32,
+    /// Requests per minute per miner API token (more generous than per-IP)
+    pub token_rpm: NonZeroU32,
     /// Burst size
     pub burst: NonZeroU32,
     /// Trusted proxy IPs that can set X-Forwarded-For
@@ -39,6 +52,8 @@ impl Default for RateLimitConfig {
             api_rpm: NonZeroU32::new(60).unwrap(),
             // 10 requests per minute for login (anti-brute-force)
             login_rpm: NonZeroU32::new(10).unwrap(),
+            // 600 requests per minute for miner API tokens
+            token_rpm: NonZeroU32::new(600).unwrap(),
             // Allow burst of 10 requests
             burst: NonZeroU32::new(10).unwrap(),
             // No trusted proxies by default (safer)
@@ -71,14 +86,97 @@ impl RateLimitConfig {
     }
 }
 
+/// Requests limited separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitClass {
+    /// General API requests per IP
+    Api,
+    /// Login attempts per IP
+    Login,
+    /// Requests per miner API token
+    Token,
+}
+
+impl RateLimitClass {
+    pub const ALL: [RateLimitClass; 3] = [RateLimitClass::Api, RateLimitClass::Login, RateLimitClass::Token];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RateLimitClass::Api => "api",
+            RateLimitClass::Login => "login",
+            RateLimitClass::Token => "token",
+        }
+    }
+}
+
+/// Longest manual block
+pub const MAX_BLOCK_MINUTES: u64 = 7 * 24 * 60;
+
+/// Clients whose rejections are remembered; the least recently rejected are dropped
+const MAX_TRACKED_OFFENDERS: usize = 10_000;
+
+/// Requests seen for one class since start
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ClassCounts {
+    pub allowed: u64,
+    /// Over the limit
+    pub rejected: u64,
+    /// From a blocked IP
+    pub blocked: u64,
+}
+
+/// IP refused by an operator until `expires_at`
+#[derive(Clone, Debug, Serialize)]
+pub struct IpBlock {
+    pub ip: IpAddr,
+    pub reason: Option<String>,
+    pub blocked_by: String,
+    pub blocked_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Client rejected for going over a limit
+#[derive(Clone, Debug, Serialize)]
+pub struct Offender {
+    /// IP, or token ID for the token class
+    pub client: String,
+    pub class: RateLimitClass,
+    pub rejected: u64,
+    /// Requests counted in the current minute
+    pub requests_in_window: u32,
+    pub last_rejected_at: DateTime<Utc>,
+}
+
+/// Counters, top offenders and active blocks
+#[derive(Clone, Debug, Serialize)]
+pub struct RateLimitSummary {
+    pub classes: BTreeMap<RateLimitClass, ClassCounts>,
+    pub offenders: Vec<Offender>,
+    pub blocks: Vec<IpBlock>,
+}
+
+/// Rejections of one client
+#[derive(Clone, Copy)]
+struct Rejections {
+    count: u64,
+    last_at: DateTime<Utc>,
+}
+
+type RequestTimes = Arc<RwLock<HashMap<String, Vec<std::time::Instant>>>>;
+
 /// Rate limiter state - stores rate limit information per IP
 #[derive(Clone)]
 pub struct RateLimiterState {
     /// Rate limit configuration
     config: RateLimitConfig,
     /// Store last request time per IP (simple in-memory tracking)
-    api_request_times: Arc<RwLock<std::collections::HashMap<String, Vec<std::time::Instant>>>>,
-    login_request_times: Arc<RwLock<std::collections::HashMap<String, Vec<std::time::Instant>>>>,
+    api_request_times: RequestTimes,
+    login_request_times: RequestTimes,
+    token_request_times: RequestTimes,
+    counts: Arc<RwLock<BTreeMap<RateLimitClass, ClassCounts>>>,
+    rejections: Arc<RwLock<HashMap<(RateLimitClass, String), Rejections>>>,
+    blocks: Arc<RwLock<HashMap<IpAddr, IpBlock>>>,
 }
 
 impl RateLimiterState {
@@ -86,8 +184,12 @@ impl RateLimiterState {
     pub fn new(config: RateLimitConfig) -> Self {
         Self {
             config,
-            api_request_times: Arc::new(RwLock::new(std::collections::HashMap::new())),
-            login_request_times: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            api_request_times: Arc::new(RwLock::new(HashMap::new())),
+            login_request_times: Arc::new(RwLock::new(HashMap::new())),
+            token_request_times: Arc::new(RwLock::new(HashMap::new())),
+            counts: Arc::new(RwLock::new(BTreeMap::new())),
+            rejections: Arc::new(RwLock::new(HashMap::new())),
+            blocks: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -97,56 +199,117 @@ impl RateLimiterState {
         times.retain(|t| now.duration_since(*t) < window);
     }
 
-    /// Check if the given IP is rate limited for API requests
-    pub async fn check_api_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
-        let ip_str = ip.to_string();
-        let mut times = self.api_request_times.write().await;
-        let requests = times.entry(ip_str.clone()).or_insert_with(Vec::new);
+    fn request_times(&self, class: RateLimitClass) -> &RequestTimes {
+        match class {
+            RateLimitClass::Api => &self.api_request_times,
+            RateLimitClass::Login => &self.login_request_times,
+            RateLimitClass::Token => &self.token_request_times,
+        }
+    }
+
+    fn limit(&self, class: RateLimitClass) -> u32 {
+        match class {
+            RateLimitClass::Api => self.config.api_rpm.get(),
+            RateLimitClass::Login => self.config.login_rpm.get(),
+            RateLimitClass::Token => self.config.token_rpm.get(),
+        }
+    }
+
+    /// Count a request of `key` against the per-minute limit of `class`
+    async fn check(&self, class: RateLimitClass, key: &str) -> Result<(), RateLimitError> {
+        let allowed = {
+            let mut times = self.request_times(class).write().await;
+            let requests = times.entry(key.to_string()).or_default();
+
+            // Clean up old requests
+            Self::cleanup_old_requests(requests, std::time::Duration::from_secs(60));
+
+            let allowed = requests.len() < self.limit(class) as usize;
+            if allowed {
+                requests.push(std::time::Instant::now());
+            }
+            allowed
+        };
 
-        // Clean up old requests
-        Self::cleanup_old_requests(requests, std::time::Duration::from_secs(60));
+        {
+            let mut counts = self.counts.write().await;
+            let counts = counts.entry(class).or_default();
+            if allowed {
+                counts.allowed += 1;
+            } else {
+                counts.rejected += 1;
+            }
+        }
+        if allowed {
+            debug!("{} request allowed for: {}", class.as_str(), key);
+            return Ok(());
+        }
+        warn!("Rate limit exceeded for {}: {}", class.as_str(), key);
+        self.record_rejection(class, key).await;
+        Err(RateLimitError::TooManyRequests)
+    }
 
-        // Check rate limit
-        if requests.len() >= self.config.api_rpm.get() as usize {
-            warn!("Rate limit exceeded for API: {}", ip_str);
-            return Err(RateLimitError::TooManyRequests);
+    async fn record_rejection(&self, class: RateLimitClass, key: &str) {
+        let now = Utc::now();
+        let mut rejections = self.rejections.write().await;
+        let entry = rejections.entry((class, key.to_string())).or_insert(Rejections { count: 0, last_at: now });
+        entry.count += 1;
+        entry.last_at = now;
+        if rejections.len() > MAX_TRACKED_OFFENDERS {
+            let oldest = rejections.iter().min_by_key(|(_, r)| r.last_at).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                rejections.remove(&oldest);
+            }
         }
+    }
 
-        // Add current request timestamp
-        requests.push(std::time::Instant::now());
-        debug!("API request allowed for: {} (total: {})", ip_str, requests.len());
-        Ok(())
+    /// Refuse requests from an IP an operator blocked
+    pub async fn check_blocked(&self, ip: IpAddr, class: RateLimitClass) -> Result<(), RateLimitError> {
+        let remaining = match self.blocks.read().await.get(&ip) {
+            Some(block) => (block.expires_at - Utc::now()).num_seconds(),
+            None => return Ok(()),
+        };
+        if remaining <= 0 {
+            return Ok(());
+        }
+        self.counts.write().await.entry(class).or_default().blocked += 1;
+        debug!("Refused {} request from blocked IP {}", class.as_str(), ip);
+        Err(RateLimitError::Blocked(remaining))
+    }
+
+    /// Check if the given IP is rate limited for API requests
+    pub async fn check_api_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
+        self.check_blocked(ip, RateLimitClass::Api).await?;
+        self.check(RateLimitClass::Api, &ip.to_string()).await
     }
 
     /// Check if the given IP is rate limited for login attempts
     pub async fn check_login_rate_limit(&self, ip: IpAddr) -> Result<(), RateLimitError> {
-        let ip_str = ip.to_string();
-        let mut times = self.login_request_times.write().await;
-        let requests = times.entry(ip_str.clone()).or_insert_with(Vec::new);
-
-        // Clean up old requests
-        Self::cleanup_old_requests(requests, std::time::Duration::from_secs(60));
+        self.check_blocked(ip, RateLimitClass::Login).await?;
+        self.check(RateLimitClass::Login, &ip.to_string()).await
+    }
 
-        // Check rate limit (stricter for login)
-        if requests.len() >= self.config.login_rpm.get() as usize {
-            warn!("Rate limit exceeded for login: {}", ip_str);
-            return Err(RateLimitError::TooManyRequests);
-        }
+    /// Check if the given miner API token is rate limited
+    pub async fn check_token_rate_limit(&self, token_id: &str) -> Result<(), RateLimitError> {
+        self.check(RateLimitClass::Token, token_id).await
+    }
 
-        // Add current request timestamp
-        requests.push(std::time::Instant::now());
-        debug!("Login attempt allowed for: {} (total: {})", ip_str, requests.len());
-        Ok(())
+    /// Client IP of a request, honouring trusted proxies
+    pub fn client_ip(&self, headers: &HeaderMap) -> Result<IpAddr, RateLimitError> {
+        extract_client_ip(headers, &self.config)
     }
 
     /// Get current rate limit status for an IP
     pub async fn get_rate_limit_status(&self, ip: IpAddr) -> RateLimitStatus {
         let ip_str = ip.to_string();
-        let api_times = self.api_request_times.read().await;
-        let login_times = self.login_request_times.read().await;
-
-        let api_count = api_times.get(&ip_str).map_or(0, |v| v.len()) as u32;
-        let login_count = login_times.get(&ip_str).map_or(0, |v| v.len()) as u32;
+        let api_count = self.requests_in_window(RateLimitClass::Api, &ip_str).await;
+        let login_count = self.requests_in_window(RateLimitClass::Login, &ip_str).await;
+        let rejections = self.rejections.read().await;
+        let rejected = [RateLimitClass::Api, RateLimitClass::Login].iter()
+            .filter_map(|class| rejections.get(&(*class, ip_str.clone())))
+            .map(|r| r.count)
+            .sum();
+        let blocked = self.blocks.read().await.get(&ip).filter(|b| b.expires_at > Utc::now()).cloned();
 
         RateLimitStatus {
             ip: ip_str,
@@ -154,8 +317,101 @@ impl RateLimiterState {
             login_requests_remaining: self.config.login_rpm.get().saturating_sub(login_count),
             api_limit: self.config.api_rpm.get(),
             login_limit: self.config.login_rpm.get(),
+            rejected,
+            blocked,
+        }
+    }
+
+    /// Requests of `key` counted in the current minute
+    async fn requests_in_window(&self, class: RateLimitClass, key: &str) -> u32 {
+        let now = std::time::Instant::now();
+        self.request_times(class).read().await.get(key).map_or(0, |times| {
+            times.iter().filter(|t| now.duration_since(**t) < std::time::Duration::from_secs(60)).count() as u32
+        })
+    }
+
+    /// Refuse every request from `ip` for `minutes`, replacing an existing block
+    pub async fn block(&self, ip: IpAddr, minutes: u64, reason: Option<String>, blocked_by: &str) -> Result<IpBlock> {
+        if minutes == 0 || minutes > MAX_BLOCK_MINUTES {
+            return Err(DmpoolError::InvalidInput(format!("minutes must be 1 to {}", MAX_BLOCK_MINUTES)).into());
+        }
+        let blocked_at = Utc::now();
+        let block = IpBlock {
+            ip,
+            reason,
+            blocked_by: blocked_by.to_string(),
+            blocked_at,
+            expires_at: blocked_at + chrono::Duration::minutes(minutes as i64),
+        };
+        let mut blocks = self.blocks.write().await;
+        blocks.retain(|_, b| b.expires_at > blocked_at);
+        blocks.insert(ip, block.clone());
+        warn!("Blocked {} for {} minutes by {}", ip, minutes, blocked_by);
+        Ok(block)
+    }
+
+    /// Lift a block; false if the IP was not blocked
+    pub async fn unblock(&self, ip: IpAddr) -> bool {
+        let now = Utc::now();
+        let removed = self.blocks.write().await.remove(&ip).is_some_and(|b| b.expires_at > now);
+        if removed {
+            info!("Unblocked {}", ip);
+        }
+        removed
+    }
+
+    /// Active blocks, soonest to expire first
+    pub async fn blocks(&self) -> Vec<IpBlock> {
+        let now = Utc::now();
+        let mut blocks: Vec<IpBlock> = self.blocks.read().await.values().filter(|b| b.expires_at > now).cloned().collect();
+        blocks.sort_by_key(|b| b.expires_at);
+        blocks
+    }
+
+    /// Clients rejected most often, at most `limit`
+    pub async fn top_offenders(&self, limit: usize) -> Vec<Offender> {
+        let mut offenders: Vec<Offender> = self.rejections.read().await.iter()
+            .map(|((class, client), r)| Offender {
+                client: client.clone(),
+                class: *class,
+                rejected: r.count,
+                requests_in_window: 0,
+                last_rejected_at: r.last_at,
+            })
+            .collect();
+        offenders.sort_by(|a, b| b.rejected.cmp(&a.rejected).then_with(|| b.last_rejected_at.cmp(&a.last_rejected_at)));
+        offenders.truncate(limit);
+        for offender in &mut offenders {
+            offender.requests_in_window = self.requests_in_window(offender.class, &offender.client).await;
+        }
+        offenders
+    }
+
+    /// Counters per class, the top `limit` offenders and active blocks
+    pub async fn summary(&self, limit: usize) -> RateLimitSummary {
+        let counts = self.counts.read().await.clone();
+        RateLimitSummary {
+            classes: RateLimitClass::ALL.iter().map(|c| (*c, counts.get(c).copied().unwrap_or_default())).collect(),
+            offenders: self.top_offenders(limit).await,
+            blocks: self.blocks().await,
         }
     }
+
+    /// Render in the Prometheus text exposition format
+    pub async fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let counts = self.counts.read().await.clone();
+        let _ = writeln!(out, "# HELP dmpool_rate_limit_requests_total Rate limited requests by class and result");
+        let _ = writeln!(out, "# TYPE dmpool_rate_limit_requests_total counter");
+        for class in RateLimitClass::ALL {
+            let c = counts.get(&class).copied().unwrap_or_default();
+            for (result, value) in [("allowed", c.allowed), ("rejected", c.rejected), ("blocked", c.blocked)] {
+                let _ = writeln!(out, "dmpool_rate_limit_requests_total{{class=\"{}\",result=\"{}\"}} {}", class.as_str(), result, value);
+            }
+        }
+        gauge(&mut out, "dmpool_rate_limit_blocked_ips", "IPs blocked by an operator", self.blocks().await.len() as f64);
+        out
+    }
 }
 
 /// Rate limit status for an IP
@@ -166,6 +422,10 @@ pub struct RateLimitStatus {
     pub login_requests_remaining: u32,
     pub api_limit: u32,
     pub login_limit: u32,
+    /// API and login requests rejected since start
+    pub rejected: u64,
+    /// Active manual block
+    pub blocked: Option<IpBlock>,
 }
 
 /// Rate limit errors
@@ -173,25 +433,34 @@ pub struct RateLimitStatus {
 pub enum RateLimitError {
     TooManyRequests,
     InvalidIp(String),
+    /// Blocked by an operator for this many more seconds
+    Blocked(i64),
 }
 
 impl IntoResponse for RateLimitError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
+        let (status, message, retry_after) = match self {
             RateLimitError::TooManyRequests => (
                 StatusCode::TOO_MANY_REQUESTS,
-                "Too many requests. Please try again later.",
+                "Too many requests. Please try again later.".to_string(),
+                60,
+            ),
+            RateLimitError::InvalidIp(msg) => (
+                StatusCode::FORBIDDEN,
+                msg,
+                60,
             ),
-            RateLimitError::InvalidIp(ref msg) => (
+            RateLimitError::Blocked(seconds) => (
                 StatusCode::FORBIDDEN,
-                msg.as_str(),
+                "This address is temporarily blocked.".to_string(),
+                seconds,
             ),
         };
 
         let body = serde_json::json!({
             "status": "error",
             "message": message,
-            "retry_after": 60
+            "retry_after": retry_after
         });
 
         (status, axum::Json(body)).into_response()
@@ -392,6 +661,7 @@ mod tests {
         let config = RateLimitConfig {
             api_rpm: NonZeroU32::new(5).unwrap(),
             login_rpm: NonZeroU32::new(2).unwrap(),
+            token_rpm: NonZeroU32::new(3).unwrap(),
             burst: NonZeroU32::new(2).unwrap(),
             trusted_proxies: HashSet::new(),
             require_valid_ip: false, // Allow localhost in tests
@@ -412,5 +682,48 @@ mod tests {
         assert!(limiter.check_login_rate_limit(ip2).await.is_ok());
         assert!(limiter.check_login_rate_limit(ip2).await.is_ok());
         assert!(limiter.check_login_rate_limit(ip2).await.is_err());
+
+        // Token limits are tracked per token, independently of the IP limits
+        for _ in 0..3 {
+            assert!(limiter.check_token_rate_limit("token-a").await.is_ok());
+        }
+        assert!(limiter.check_token_rate_limit("token-a").await.is_err());
+        assert!(limiter.check_token_rate_limit("token-b").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_offenders_blocks_and_metrics() {
+        let config = RateLimitConfig {
+            api_rpm: NonZeroU32::new(2).unwrap(),
+            require_valid_ip: false,
+            ..Default::default()
+        };
+        let limiter = RateLimiterState::new(config);
+        let noisy: IpAddr = "203.0.113.7".parse().unwrap();
+        let quiet: IpAddr = "203.0.113.8".parse().unwrap();
+        for _ in 0..5 {
+            let _ = limiter.check_api_rate_limit(noisy).await;
+        }
+        let _ = limiter.check_api_rate_limit(quiet).await;
+
+        let summary = limiter.summary(10).await;
+        assert_eq!(summary.offenders.len(), 1);
+        assert_eq!((summary.offenders[0].client.as_str(), summary.offenders[0].rejected), ("203.0.113.7", 3));
+        assert_eq!(summary.offenders[0].requests_in_window, 2);
+        assert_eq!(summary.classes[&RateLimitClass::Api].allowed, 3);
+        assert_eq!(limiter.get_rate_limit_status(noisy).await.rejected, 3);
+
+        assert!(limiter.block(quiet, 0, None, "admin").await.is_err());
+        limiter.block(quiet, 10, Some("scraping".to_string()), "admin").await.unwrap();
+        assert!(matches!(limiter.check_api_rate_limit(quiet).await, Err(RateLimitError::Blocked(s)) if s > 500));
+        assert!(limiter.get_rate_limit_status(quiet).await.blocked.is_some());
+        let text = limiter.to_prometheus().await;
+        assert!(text.contains("dmpool_rate_limit_requests_total{class=\"api\",result=\"rejected\"} 3"));
+        assert!(text.contains("dmpool_rate_limit_requests_total{class=\"api\",result=\"blocked\"} 1"));
+        assert!(text.contains("dmpool_rate_limit_blocked_ips 1"));
+
+        assert!(limiter.unblock(quiet).await);
+        assert!(!limiter.unblock(quiet).await);
+        assert!(limiter.check_api_rate_limit(quiet).await.is_ok());
     }
 }
@@ -0,0 +1,421 @@
+// Stratum abuse scoring
+//
+// Scores stratum clients per source IP. Connections, short-lived connections
+// (churn), mining.subscribe requests and rejected submissions add points;
+// rejected submissions count more at low difficulty, where flooding is
+// cheapest for the client and costliest for the pool. Scores halve every
+// `half_life_secs`. An IP reaching `ban_score` is refused for `ban_secs`,
+// doubling for each further ban while its score has not decayed away.
+//
+// The p2poolv2 stratum server does not report client addresses, so the
+// scores come from a guard that listens on the public stratum port and
+// relays each connection to the stratum server, watching the JSON-RPC lines
+// in both directions.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Longest JSON-RPC line relayed; longer lines close the connection
+const MAX_LINE_BYTES: u64 = 16 * 1024;
+
+/// Scores below this are forgotten once any ban has expired
+const FORGET_BELOW: f64 = 0.01;
+
+/// The `[dmpool.stratum_guard]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StratumGuardConfig {
+    pub enabled: bool,
+    /// Public address miners connect to
+    pub listen: String,
+    /// Address of the stratum server, moved off the public port
+    pub upstream: String,
+    pub connect_points: f64,
+    /// Extra points for connections closed within `short_connection_secs`
+    pub churn_points: f64,
+    pub short_connection_secs: u64,
+    pub subscribe_points: f64,
+    /// Points for a rejected submission at or above `reference_difficulty`
+    pub invalid_points: f64,
+    pub reference_difficulty: f64,
+    /// Largest multiplier for rejected submissions at low difficulty
+    pub max_difficulty_weight: f64,
+    pub half_life_secs: u64,
+    pub ban_score: f64,
+    /// First ban length; repeat bans double up to `max_ban_secs`
+    pub ban_secs: u64,
+    pub max_ban_secs: u64,
+}
+
+impl Default for StratumGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "0.0.0.0:3333".to_string(),
+            upstream: "127.0.0.1:3334".to_string(),
+            connect_points: 1.0,
+            churn_points: 4.0,
+            short_connection_secs: 10,
+            subscribe_points: 2.0,
+            invalid_points: 1.0,
+            reference_difficulty: 1024.0,
+            max_difficulty_weight: 16.0,
+            half_life_secs: 300,
+            ban_score: 100.0,
+            ban_secs: 900,
+            max_ban_secs: 86_400,
+        }
+    }
+}
+
+impl StratumGuardConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.listen == self.upstream {
+            return Err(anyhow::anyhow!("listen and upstream must differ ({})", self.listen));
+        }
+        if self.half_life_secs == 0 || self.ban_secs == 0 {
+            return Err(anyhow::anyhow!("half_life_secs and ban_secs must be at least 1"));
+        }
+        if self.ban_score <= 0.0 || self.reference_difficulty <= 0.0 || self.max_difficulty_weight < 1.0 {
+            return Err(anyhow::anyhow!("ban_score and reference_difficulty must be positive and max_difficulty_weight at least 1"));
+        }
+        if self.max_ban_secs < self.ban_secs {
+            return Err(anyhow::anyhow!("max_ban_secs must be at least ban_secs ({})", self.ban_secs));
+        }
+        Ok(())
+    }
+}
+
+/// Something a stratum client did
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StratumActivity {
+    Connected,
+    Disconnected { connected_secs: u64 },
+    Subscribed,
+    /// A submission the stratum server rejected, at the connection's difficulty
+    RejectedSubmission { difficulty: f64 },
+}
+
+/// Current score of one IP
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IpScore {
+    pub ip: IpAddr,
+    pub score: f64,
+    pub banned_until: Option<DateTime<Utc>>,
+    /// Bans since the score last decayed away
+    pub bans: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct IpState {
+    score: f64,
+    updated_at: DateTime<Utc>,
+    banned_until: Option<DateTime<Utc>>,
+    bans: u32,
+}
+
+impl IpState {
+    fn decay(&mut self, now: DateTime<Utc>, half_life_secs: u64) {
+        let elapsed = (now - self.updated_at).num_milliseconds().max(0) as f64 / 1000.0;
+        self.score *= 0.5f64.powf(elapsed / half_life_secs as f64);
+        self.updated_at = now;
+    }
+}
+
+/// Per-IP abuse scores and temporary bans
+pub struct StratumScorer {
+    config: StratumGuardConfig,
+    ips: Mutex<HashMap<IpAddr, IpState>>,
+}
+
+impl StratumScorer {
+    pub fn new(config: StratumGuardConfig) -> Self {
+        Self {
+            config,
+            ips: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &StratumGuardConfig {
+        &self.config
+    }
+
+    /// Points an activity adds to its IP's score
+    pub fn points(&self, activity: StratumActivity) -> f64 {
+        match activity {
+            StratumActivity::Connected => self.config.connect_points,
+            StratumActivity::Disconnected { connected_secs } if connected_secs < self.config.short_connection_secs => self.config.churn_points,
+            StratumActivity::Disconnected { .. } => 0.0,
+            StratumActivity::Subscribed => self.config.subscribe_points,
+            StratumActivity::RejectedSubmission { difficulty } => {
+                let weight = (self.config.reference_difficulty / difficulty.max(f64::MIN_POSITIVE))
+                    .clamp(1.0, self.config.max_difficulty_weight);
+                self.config.invalid_points * weight
+            }
+        }
+    }
+
+    /// Score an activity, returning the ban expiry when it bans the IP
+    pub async fn record(&self, ip: IpAddr, activity: StratumActivity, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let points = self.points(activity);
+        let mut ips = self.ips.lock().await;
+        let state = ips.entry(ip).or_insert_with(|| IpState { score: 0.0, updated_at: now, banned_until: None, bans: 0 });
+        state.decay(now, self.config.half_life_secs);
+        state.score += points;
+
+        let banned = state.banned_until.is_some_and(|until| until > now);
+        if banned || state.score < self.config.ban_score {
+            return None;
+        }
+        let secs = self.config.ban_secs
+            .saturating_mul(1u64 << state.bans.min(32))
+            .min(self.config.max_ban_secs);
+        let until = now + Duration::seconds(secs as i64);
+        state.banned_until = Some(until);
+        state.bans += 1;
+        warn!("Banning stratum client {} for {}s (score {:.1}, ban {})", ip, secs, state.score, state.bans);
+        Some(until)
+    }
+
+    /// When the IP's ban ends, if it is banned
+    pub async fn banned_until(&self, ip: IpAddr, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ips.lock().await.get(&ip)
+            .and_then(|state| state.banned_until)
+            .filter(|until| *until > now)
+    }
+
+    /// Lift a ban and reset the IP's score, returning whether it was tracked
+    pub async fn unban(&self, ip: IpAddr) -> bool {
+        self.ips.lock().await.remove(&ip).is_some()
+    }
+
+    /// All tracked IPs, highest score first
+    pub async fn scores(&self, now: DateTime<Utc>) -> Vec<IpScore> {
+        let mut ips = self.ips.lock().await;
+        let mut scores: Vec<IpScore> = ips.iter_mut()
+            .map(|(ip, state)| {
+                state.decay(now, self.config.half_life_secs);
+                IpScore {
+                    ip: *ip,
+                    score: state.score,
+                    banned_until: state.banned_until.filter(|until| *until > now),
+                    bans: state.bans,
+                    updated_at: state.updated_at,
+                }
+            })
+            .collect();
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scores
+    }
+
+    /// Forget IPs whose score has decayed away and whose ban has ended
+    pub async fn prune(&self, now: DateTime<Utc>) -> usize {
+        let mut ips = self.ips.lock().await;
+        let before = ips.len();
+        ips.retain(|_, state| {
+            state.decay(now, self.config.half_life_secs);
+            state.score >= FORGET_BELOW || state.banned_until.is_some_and(|until| until > now)
+        });
+        before - ips.len()
+    }
+}
+
+/// Listen on the public stratum port and relay clients to the stratum server
+pub async fn start_stratum_guard(scorer: Arc<StratumScorer>) -> anyhow::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(&scorer.config().listen).await
+        .map_err(|e| anyhow::anyhow!("Failed to bind stratum guard on {}: {}", scorer.config().listen, e))?;
+    info!("Stratum guard listening on {}, relaying to {}", scorer.config().listen, scorer.config().upstream);
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let (client, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Stratum guard accept failed: {}", e);
+                    continue;
+                }
+            };
+            let scorer = scorer.clone();
+            tokio::spawn(async move {
+                guard_connection(scorer, client, peer.ip()).await;
+            });
+        }
+    }))
+}
+
+/// What the relay tracks for one connection
+#[derive(Default)]
+struct Session {
+    difficulty: f64,
+    /// Ids of submissions awaiting a response
+    pending_submits: HashSet<String>,
+}
+
+async fn guard_connection(scorer: Arc<StratumScorer>, client: TcpStream, ip: IpAddr) {
+    let now = Utc::now();
+    if scorer.banned_until(ip, now).await.is_some() {
+        debug!("Refused banned stratum client {}", ip);
+        return;
+    }
+    if scorer.record(ip, StratumActivity::Connected, now).await.is_some() {
+        return;
+    }
+    let upstream = match TcpStream::connect(&scorer.config().upstream).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            warn!("Stratum guard cannot reach {}: {}", scorer.config().upstream, e);
+            return;
+        }
+    };
+
+    let session = Arc::new(std::sync::Mutex::new(Session {
+        difficulty: scorer.config().reference_difficulty,
+        ..Default::default()
+    }));
+    let (client_read, client_write) = client.into_split();
+    let (upstream_read, upstream_write) = upstream.into_split();
+    tokio::select! {
+        _ = relay(client_read, upstream_write, |line| client_line(&scorer, &session, ip, line)) => {}
+        _ = relay(upstream_read, client_write, |line| server_line(&scorer, &session, ip, line)) => {}
+    }
+
+    let connected_secs = (Utc::now() - now).num_seconds().max(0) as u64;
+    scorer.record(ip, StratumActivity::Disconnected { connected_secs }, Utc::now()).await;
+}
+
+/// Copy lines until either side closes or `inspect` returns false
+async fn relay<R, W, F, Fut>(reader: R, mut writer: W, inspect: F)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match (&mut reader).take(MAX_LINE_BYTES).read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if !line.ends_with('\n') => return,
+            Ok(_) => {}
+        }
+        if !inspect(line.clone()).await || writer.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Inspect a client request; false closes the connection
+async fn client_line(scorer: &StratumScorer, session: &std::sync::Mutex<Session>, ip: IpAddr, line: String) -> bool {
+    let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+        return true;
+    };
+    match message.get("method").and_then(|m| m.as_str()) {
+        Some("mining.subscribe") => scorer.record(ip, StratumActivity::Subscribed, Utc::now()).await.is_none(),
+        Some("mining.submit") => {
+            if let Some(id) = message.get("id") {
+                session.lock().unwrap().pending_submits.insert(id.to_string());
+            }
+            true
+        }
+        _ => true,
+    }
+}
+
+/// Inspect a server message; false closes the connection
+async fn server_line(scorer: &StratumScorer, session: &std::sync::Mutex<Session>, ip: IpAddr, line: String) -> bool {
+    let Ok(message) = serde_json::from_str::<serde_json::Value>(&line) else {
+        return true;
+    };
+    let rejected_at = {
+        let mut session = session.lock().unwrap();
+        if message.get("method").and_then(|m| m.as_str()) == Some("mining.set_difficulty") {
+            if let Some(difficulty) = message["params"].get(0).and_then(|d| d.as_f64()) {
+                session.difficulty = difficulty;
+            }
+            None
+        } else {
+            let id = message.get("id").map(|id| id.to_string());
+            let submit = id.is_some_and(|id| session.pending_submits.remove(&id));
+            let accepted = message.get("result").and_then(|r| r.as_bool()) == Some(true);
+            (submit && !accepted).then_some(session.difficulty)
+        }
+    };
+    match rejected_at {
+        Some(difficulty) => scorer.record(ip, StratumActivity::RejectedSubmission { difficulty }, Utc::now()).await.is_none(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_scores_decay_and_bans_escalate() {
+        let scorer = StratumScorer::new(StratumGuardConfig::default());
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+
+        // Low difficulty rejects weigh more, up to the cap
+        assert_eq!(scorer.points(StratumActivity::RejectedSubmission { difficulty: 4096.0 }), 1.0);
+        assert_eq!(scorer.points(StratumActivity::RejectedSubmission { difficulty: 256.0 }), 4.0);
+        assert_eq!(scorer.points(StratumActivity::RejectedSubmission { difficulty: 1.0 }), 16.0);
+        assert_eq!(scorer.points(StratumActivity::Disconnected { connected_secs: 3600 }), 0.0);
+
+        // 19 short connections score 95, which halves in one half-life
+        for _ in 0..19 {
+            assert!(scorer.record(ip, StratumActivity::Connected, start).await.is_none());
+            assert!(scorer.record(ip, StratumActivity::Disconnected { connected_secs: 1 }, start).await.is_none());
+        }
+        let later = start + Duration::seconds(300);
+        let score = scorer.scores(later).await[0].score;
+        assert!((score - 47.5).abs() < 1e-6, "{}", score);
+
+        let reject = StratumActivity::RejectedSubmission { difficulty: 1.0 };
+        for _ in 0..3 {
+            assert!(scorer.record(ip, reject, later).await.is_none());
+        }
+        let until = scorer.record(ip, reject, later).await.unwrap();
+        assert_eq!(until, later + Duration::seconds(900));
+        assert_eq!(scorer.banned_until(ip, later).await, Some(until));
+        assert_eq!(scorer.banned_until(ip, until).await, None);
+
+        // A second ban before the score decays away lasts twice as long
+        for _ in 0..5 {
+            assert!(scorer.record(ip, reject, until).await.is_none());
+        }
+        assert_eq!(scorer.record(ip, reject, until).await, Some(until + Duration::seconds(1800)));
+
+        assert!(scorer.unban(ip).await);
+        assert_eq!(scorer.banned_until(ip, until).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_prune_and_config() {
+        let scorer = StratumScorer::new(StratumGuardConfig::default());
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+        scorer.record("198.51.100.1".parse().unwrap(), StratumActivity::Subscribed, start).await;
+        scorer.record("198.51.100.2".parse().unwrap(), StratumActivity::Connected, start).await;
+
+        assert_eq!(scorer.prune(start + Duration::seconds(60)).await, 0);
+        let scores = scorer.scores(start + Duration::seconds(60)).await;
+        assert_eq!(scores[0].ip, "198.51.100.1".parse::<IpAddr>().unwrap());
+        // Ten half-lives later both have decayed away
+        assert_eq!(scorer.prune(start + Duration::seconds(3000)).await, 2);
+
+        assert!(StratumGuardConfig::default().validate().is_ok());
+        let config = StratumGuardConfig { upstream: "0.0.0.0:3333".to_string(), ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,335 @@
+// Reconciliation Module for DMPool
+// Cross-checks confirmed payouts against wallet transactions and exports reports
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::bitcoin::{BitcoinRpcClient, WalletTransaction};
+use crate::payment::{PaymentManager, Payout, PayoutFilter, PayoutSource, PayoutStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Wallet transactions fetched per listtransactions call
+const WALLET_PAGE_SIZE: u32 = 1000;
+
+/// Upper bound on wallet pages read for one report
+const MAX_WALLET_PAGES: u32 = 100;
+
+/// Result of matching one payout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconciliationStatus {
+    /// Wallet send matches address and amount
+    Matched,
+    /// Payout is confirmed but has no txid
+    MissingTxid,
+    /// Txid not found among wallet sends
+    NotInWallet,
+    /// Transaction found but it doesn't pay the payout address
+    AddressMismatch,
+    /// Output to the payout address has a different amount
+    AmountMismatch,
+    /// Wallet reports the transaction as conflicted
+    Conflicted,
+}
+
+impl ReconciliationStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Matched => "matched",
+            Self::MissingTxid => "missing_txid",
+            Self::NotInWallet => "not_in_wallet",
+            Self::AddressMismatch => "address_mismatch",
+            Self::AmountMismatch => "amount_mismatch",
+            Self::Conflicted => "conflicted",
+        }
+    }
+}
+
+/// One reconciled payout
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReconciliationEntry {
+    pub payout_id: String,
+    pub address: String,
+    pub amount_satoshis: u64,
+    pub txid: Option<String>,
+    pub paid_at: DateTime<Utc>,
+    pub status: ReconciliationStatus,
+    /// Amount the wallet sent to the address, if found
+    pub wallet_amount_satoshis: Option<u64>,
+    pub wallet_confirmations: Option<i64>,
+}
+
+/// Reconciliation report for a time range
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    pub generated_at: DateTime<Utc>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total_payouts: usize,
+    pub matched: usize,
+    pub flagged: usize,
+    /// Sum of confirmed payout amounts in the range
+    pub total_paid_satoshis: u64,
+    /// Sum the wallet actually sent for those payouts
+    pub wallet_sent_satoshis: u64,
+    pub entries: Vec<ReconciliationEntry>,
+}
+
+impl ReconciliationReport {
+    /// Entries that did not match
+    pub fn flagged_entries(&self) -> impl Iterator<Item = &ReconciliationEntry> {
+        self.entries.iter().filter(|e| e.status != ReconciliationStatus::Matched)
+    }
+
+    /// Render the entries as CSV
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "payout_id,address,amount_satoshis,txid,paid_at,status,wallet_amount_satoshis,wallet_confirmations\n",
+        );
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                csv_field(&entry.payout_id),
+                csv_field(&entry.address),
+                entry.amount_satoshis,
+                csv_field(entry.txid.as_deref().unwrap_or("")),
+                entry.paid_at.to_rfc3339(),
+                entry.status.as_str(),
+                entry.wallet_amount_satoshis.map(|a| a.to_string()).unwrap_or_default(),
+                entry.wallet_confirmations.map(|c| c.to_string()).unwrap_or_default(),
+            ));
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// When a payout left the pool
+fn paid_at(payout: &Payout) -> DateTime<Utc> {
+    payout.broadcast_at.unwrap_or(payout.created_at)
+}
+
+/// Match confirmed payouts in `[from, to)` against wallet send entries
+pub fn reconcile(
+    payouts: &[Payout],
+    wallet_txs: &[WalletTransaction],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> ReconciliationReport {
+    let mut sends: HashMap<&str, Vec<&WalletTransaction>> = HashMap::new();
+    for tx in wallet_txs.iter().filter(|tx| tx.category == "send") {
+        sends.entry(tx.txid.as_str()).or_default().push(tx);
+    }
+
+    let mut entries: Vec<ReconciliationEntry> = payouts.iter()
+        .filter(|p| p.status == PayoutStatus::Confirmed)
+        // Imported payouts were sent from the previous pool's wallet
+        .filter(|p| p.source == PayoutSource::Pool)
+        .filter(|p| paid_at(p) >= from && paid_at(p) < to)
+        .map(|payout| {
+            let mut entry = ReconciliationEntry {
+                payout_id: payout.id.clone(),
+                address: payout.address.clone(),
+                amount_satoshis: payout.amount_satoshis,
+                txid: payout.txid.clone(),
+                paid_at: paid_at(payout),
+                status: ReconciliationStatus::Matched,
+                wallet_amount_satoshis: None,
+                wallet_confirmations: None,
+            };
+
+            let outputs = match payout.txid.as_deref() {
+                None => {
+                    entry.status = ReconciliationStatus::MissingTxid;
+                    return entry;
+                }
+                Some(txid) => match sends.get(txid) {
+                    Some(outputs) => outputs,
+                    None => {
+                        entry.status = ReconciliationStatus::NotInWallet;
+                        return entry;
+                    }
+                },
+            };
+
+            entry.wallet_confirmations = outputs.first().map(|tx| tx.confirmations);
+            let sent: Vec<u64> = outputs.iter()
+                .filter(|tx| tx.address.as_deref() == Some(payout.address.as_str()))
+                .map(|tx| (tx.amount.abs() * 100_000_000.0).round() as u64)
+                .collect();
+
+            entry.status = if outputs.iter().any(|tx| tx.confirmations < 0) {
+                ReconciliationStatus::Conflicted
+            } else if sent.is_empty() {
+                ReconciliationStatus::AddressMismatch
+            } else if sent.contains(&payout.amount_satoshis) {
+                ReconciliationStatus::Matched
+            } else {
+                ReconciliationStatus::AmountMismatch
+            };
+            entry.wallet_amount_satoshis = match entry.status {
+                ReconciliationStatus::Matched => Some(payout.amount_satoshis),
+                _ if !sent.is_empty() => Some(sent.iter().sum()),
+                _ => None,
+            };
+            entry
+        })
+        .collect();
+    entries.sort_by_key(|e| e.paid_at);
+
+    let matched = entries.iter().filter(|e| e.status == ReconciliationStatus::Matched).count();
+    ReconciliationReport {
+        generated_at: Utc::now(),
+        from,
+        to,
+        total_payouts: entries.len(),
+        matched,
+        flagged: entries.len() - matched,
+        total_paid_satoshis: entries.iter().map(|e| e.amount_satoshis).sum(),
+        wallet_sent_satoshis: entries.iter().filter_map(|e| e.wallet_amount_satoshis).sum(),
+        entries,
+    }
+}
+
+/// Builds reconciliation reports from the payment manager and wallet
+pub struct Reconciler {
+    payments: Arc<PaymentManager>,
+    bitcoin: Arc<BitcoinRpcClient>,
+}
+
+impl Reconciler {
+    /// Create a reconciler
+    pub fn new(payments: Arc<PaymentManager>, bitcoin: Arc<BitcoinRpcClient>) -> Self {
+        Self { payments, bitcoin }
+    }
+
+    /// Reconcile confirmed payouts paid within `[from, to)`
+    pub async fn report(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<ReconciliationReport> {
+        // Payouts are paid after they are created, so later ones cannot fall in the window
+        let filter = PayoutFilter {
+            status: Some(PayoutStatus::Confirmed),
+            created_before: Some(to),
+            ..Default::default()
+        };
+        let payouts = self.payments.list_payouts(&filter, 0, usize::MAX).await?.payouts;
+        let wallet_txs = self.wallet_transactions_since(from).await?;
+
+        let report = reconcile(&payouts, &wallet_txs, from, to);
+        if report.flagged > 0 {
+            warn!("Reconciliation {} - {}: {} of {} payouts flagged", from, to, report.flagged, report.total_payouts);
+        } else {
+            info!("Reconciliation {} - {}: all {} payouts matched", from, to, report.total_payouts);
+        }
+        Ok(report)
+    }
+
+    /// Page back through the wallet until transactions predate `since`
+    async fn wallet_transactions_since(&self, since: DateTime<Utc>) -> Result<Vec<WalletTransaction>> {
+        let since = since.timestamp().max(0) as u64;
+        let mut transactions = Vec::new();
+
+        for page in 0..MAX_WALLET_PAGES {
+            let batch = self.bitcoin.list_transactions(WALLET_PAGE_SIZE, page * WALLET_PAGE_SIZE).await?;
+            let done = batch.len() < WALLET_PAGE_SIZE as usize || batch.iter().all(|tx| tx.time < since);
+            transactions.extend(batch);
+            if done {
+                return Ok(transactions);
+            }
+        }
+
+        warn!("Reconciliation stopped after {} wallet pages", MAX_WALLET_PAGES);
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn payout(id: &str, address: &str, amount: u64, txid: Option<&str>, at: DateTime<Utc>) -> Payout {
+        Payout {
+            id: id.to_string(),
+            address: address.to_string(),
+            amount_satoshis: amount,
+            txid: txid.map(str::to_string),
+            block_height: Some(800_000),
+            status: PayoutStatus::Confirmed,
+            created_at: at,
+            broadcast_at: Some(at),
+            confirmations: 6,
+            error: None,
+            idempotency_key: None,
+            intent: None,
+            source: PayoutSource::Pool,
+        }
+    }
+
+    fn send(txid: &str, address: &str, btc: f64) -> WalletTransaction {
+        WalletTransaction {
+            txid: txid.to_string(),
+            category: "send".to_string(),
+            amount: -btc,
+            address: Some(address.to_string()),
+            vout: Some(0),
+            confirmations: 10,
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn test_reconcile_flags_mismatches() {
+        let now = Utc::now();
+        let payouts = vec![
+            payout("p1", "bc1qa", 1_000_000, Some("tx1"), now),
+            payout("p2", "bc1qb", 2_000_000, Some("tx1"), now),
+            payout("p3", "bc1qc", 3_000_000, Some("tx2"), now),
+            payout("p4", "bc1qd", 4_000_000, Some("tx3"), now),
+            payout("p5", "bc1qe", 5_000_000, None, now),
+            // Outside the range
+            payout("p6", "bc1qf", 6_000_000, Some("tx9"), now - Duration::days(30)),
+        ];
+        let wallet = vec![
+            send("tx1", "bc1qa", 0.01),
+            send("tx1", "bc1qb", 0.02),
+            send("tx2", "bc1qc", 0.025),
+            send("tx3", "bc1qother", 0.04),
+        ];
+
+        let report = reconcile(&payouts, &wallet, now - Duration::days(1), now + Duration::days(1));
+        let statuses: HashMap<_, _> = report.entries.iter().map(|e| (e.payout_id.as_str(), e.status)).collect();
+
+        assert_eq!(report.total_payouts, 5);
+        assert_eq!(report.matched, 2);
+        assert_eq!(statuses["p3"], ReconciliationStatus::AmountMismatch);
+        assert_eq!(statuses["p4"], ReconciliationStatus::AddressMismatch);
+        assert_eq!(statuses["p5"], ReconciliationStatus::MissingTxid);
+        assert_eq!(report.flagged_entries().count(), 3);
+        assert_eq!(report.wallet_sent_satoshis, 5_500_000);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let now = Utc::now();
+        let payouts = vec![payout("p1", "bc1qa", 1_000_000, Some("tx1"), now)];
+        let report = reconcile(&payouts, &[], now - Duration::hours(1), now + Duration::hours(1));
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("payout_id,address"));
+        assert!(lines[1].starts_with("p1,bc1qa,1000000,tx1,"));
+        assert!(lines[1].ends_with(",not_in_wallet,,"));
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,522 @@
+// Referral Module for DMPool
+// Referral codes, referred miner links and referral credits
+//
+// Miners create referral codes. A new miner is linked to a referrer when its
+// first share carries a code as a worker name suffix (`rig1+CODE`), or later
+// through the Observer API within `self_service_days` of its first share.
+// When a block is credited, `fee_share_bps` of the pool fee taken from each
+// referred miner is credited to its referrer. Every credit is kept in the
+// referral ledger and recorded as a `referral_credit` revenue entry.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use crate::backfill::ShareSource;
+use crate::block_events::BlockPayout;
+use crate::error::DmpoolError;
+use crate::persist;
+use crate::revenue::{LedgerKind, NewLedgerEntry, RevenueRecorder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Shares read from the store per fetch
+const INGEST_BATCH: usize = 100_000;
+
+/// Separates a referral code from the worker name in a miner's first share
+pub const WORKER_CODE_SEPARATOR: char = '+';
+
+/// The `[dmpool.referrals]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReferralConfig {
+    pub enabled: bool,
+    /// Part of a referred miner's pool fee credited to its referrer
+    pub fee_share_bps: u32,
+    /// Days after its first share a miner can still enter a referral code
+    pub self_service_days: u32,
+    pub max_codes_per_miner: usize,
+    /// Seconds between reads of new shares for first connections
+    pub update_interval_secs: u64,
+}
+
+impl Default for ReferralConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fee_share_bps: 2000,
+            self_service_days: 30,
+            max_codes_per_miner: 5,
+            update_interval_secs: 60,
+        }
+    }
+}
+
+impl ReferralConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.fee_share_bps == 0 || self.fee_share_bps > 10000 {
+            return Err(anyhow::anyhow!("fee_share_bps must be between 1 and 10000"));
+        }
+        if self.max_codes_per_miner == 0 {
+            return Err(anyhow::anyhow!("max_codes_per_miner must be positive"));
+        }
+        if self.update_interval_secs == 0 {
+            return Err(anyhow::anyhow!("update_interval_secs must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// How a miner was linked to its referrer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSource {
+    /// Code in the worker name of the first share
+    Connection,
+    /// Entered by the miner through the Observer API
+    SelfService,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferralCode {
+    pub code: String,
+    pub owner: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferralLink {
+    pub referred: String,
+    pub referrer: String,
+    pub code: String,
+    pub source: LinkSource,
+    pub linked_at: DateTime<Utc>,
+}
+
+/// A referrer's credit from one referred miner's fee in one block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferralCredit {
+    pub block_hash: String,
+    pub height: u64,
+    pub referrer: String,
+    pub referred: String,
+    pub code: String,
+    /// Pool fee taken from the referred miner's share of the block
+    pub referred_fee_satoshis: u64,
+    pub credited_satoshis: u64,
+    pub credited_at: DateTime<Utc>,
+}
+
+/// A miner's codes, referrer, referred miners and credits
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReferralSummary {
+    pub address: String,
+    pub codes: Vec<ReferralCode>,
+    pub referred_by: Option<ReferralLink>,
+    pub referrals: Vec<ReferralLink>,
+    pub credited_satoshis: u64,
+    /// Newest first
+    pub credits: Vec<ReferralCredit>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ReferralState {
+    /// Shares up to this n_time have been checked for first connections
+    cursor: Option<u64>,
+    codes: HashMap<String, ReferralCode>,
+    /// By referred address
+    links: HashMap<String, ReferralLink>,
+    first_seen: HashMap<String, DateTime<Utc>>,
+    credits: Vec<ReferralCredit>,
+}
+
+/// Referral codes must be 4-32 letters, digits, `-` or `_`; compared case-insensitively
+fn normalize_code(code: &str) -> Result<String> {
+    let code = code.trim();
+    if code.len() < 4 || code.len() > 32 || !code.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        return Err(DmpoolError::InvalidInput("Referral codes are 4-32 letters, digits, '-' or '_'".to_string()).into());
+    }
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Referral program state and crediting
+pub struct ReferralProgram {
+    config: ReferralConfig,
+    data_dir: PathBuf,
+    state: RwLock<ReferralState>,
+    revenue: Option<Arc<dyn RevenueRecorder>>,
+}
+
+impl ReferralProgram {
+    pub fn new(data_dir: PathBuf, config: ReferralConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create referral data directory")?;
+        Ok(Self {
+            config,
+            data_dir,
+            state: RwLock::new(ReferralState::default()),
+            revenue: None,
+        })
+    }
+
+    /// Record referral credits in the revenue ledger
+    pub fn with_revenue(mut self, revenue: Arc<dyn RevenueRecorder>) -> Self {
+        self.revenue = Some(revenue);
+        self
+    }
+
+    pub fn config(&self) -> &ReferralConfig {
+        &self.config
+    }
+
+    /// Load codes, links and credits from disk
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("referrals.json");
+        let Some(state) = persist::read_json::<ReferralState>(&path).await
+            .context("Failed to load referrals")?
+        else {
+            return Ok(());
+        };
+        info!("Loaded {} referral codes and {} referred miners", state.codes.len(), state.links.len());
+        *self.state.write().await = state;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let state = self.state.read().await;
+        persist::write_json(&self.data_dir.join("referrals.json"), &*state).await
+            .context("Failed to write referrals")
+    }
+
+    /// Create a referral code for `owner`, random unless `code` is given
+    pub async fn create_code(&self, owner: &str, code: Option<&str>) -> Result<ReferralCode> {
+        let code = match code {
+            Some(code) => normalize_code(code)?,
+            None => uuid::Uuid::new_v4().simple().to_string()[..8].to_ascii_uppercase(),
+        };
+        let created = {
+            let mut state = self.state.write().await;
+            if state.codes.values().filter(|c| c.owner == owner).count() >= self.config.max_codes_per_miner {
+                return Err(DmpoolError::InvalidInput(format!(
+                    "At most {} referral codes per miner", self.config.max_codes_per_miner
+                )).into());
+            }
+            if state.codes.contains_key(&code) {
+                return Err(DmpoolError::InvalidInput(format!("Referral code {} is taken", code)).into());
+            }
+            let created = ReferralCode { code: code.clone(), owner: owner.to_string(), created_at: Utc::now() };
+            state.codes.insert(code, created.clone());
+            created
+        };
+        self.save().await?;
+        info!("Referral code {} created for {}", created.code, owner);
+        Ok(created)
+    }
+
+    /// Link `referred` to the owner of `code`
+    ///
+    /// Self-service links are only accepted within `self_service_days` of the
+    /// miner's first share. A miner's referrer never changes once linked.
+    pub async fn link(&self, referred: &str, code: &str, source: LinkSource, now: DateTime<Utc>) -> Result<ReferralLink> {
+        let code = normalize_code(code)?;
+        let link = {
+            let mut state = self.state.write().await;
+            if let Some(existing) = state.links.get(referred) {
+                return Err(DmpoolError::InvalidInput(format!("Already referred by code {}", existing.code)).into());
+            }
+            if source == LinkSource::SelfService {
+                if let Some(first_seen) = state.first_seen.get(referred) {
+                    if now - *first_seen > Duration::days(self.config.self_service_days as i64) {
+                        return Err(DmpoolError::InvalidInput(format!(
+                            "Referral codes must be entered within {} days of the first share", self.config.self_service_days
+                        )).into());
+                    }
+                }
+            }
+            let link = Self::link_code(&state, referred, &code, source, now)?;
+            state.links.insert(referred.to_string(), link.clone());
+            link
+        };
+        self.save().await?;
+        info!("{} linked to referrer {} with code {}", referred, link.referrer, link.code);
+        Ok(link)
+    }
+
+    fn link_code(state: &ReferralState, referred: &str, code: &str, source: LinkSource, now: DateTime<Utc>) -> Result<ReferralLink> {
+        let owner = &state.codes.get(code)
+            .ok_or_else(|| DmpoolError::NotFound(format!("Referral code {} not found", code)))?
+            .owner;
+        if owner == referred {
+            return Err(DmpoolError::InvalidInput("Miners cannot refer themselves".to_string()).into());
+        }
+        Ok(ReferralLink {
+            referred: referred.to_string(),
+            referrer: owner.clone(),
+            code: code.to_string(),
+            source,
+            linked_at: now,
+        })
+    }
+
+    /// Note a share; the first share of a new miner links it by its worker name code
+    fn observe(state: &mut ReferralState, address: &str, worker: Option<&str>, at: DateTime<Utc>) {
+        if state.first_seen.contains_key(address) {
+            return;
+        }
+        state.first_seen.insert(address.to_string(), at);
+        let Some((_, code)) = worker.and_then(|worker| worker.rsplit_once(WORKER_CODE_SEPARATOR)) else {
+            return;
+        };
+        let linked = normalize_code(code)
+            .and_then(|code| Self::link_code(state, address, &code, LinkSource::Connection, at));
+        match linked {
+            Ok(link) => {
+                info!("{} linked to referrer {} on first connection", address, link.referrer);
+                state.links.insert(address.to_string(), link);
+            }
+            Err(e) => warn!("Ignoring referral code in first share of {}: {:#}", address, e),
+        }
+    }
+
+    /// Check shares stored since the last read for first connections
+    pub async fn ingest(&self, source: Arc<dyn ShareSource>, now: DateTime<Utc>) -> Result<usize> {
+        let end = now.timestamp().max(0) as u64;
+        let mut checked = 0;
+        loop {
+            let start = self.state.read().await.cursor.map_or(0, |cursor| cursor + 1);
+            if start > end {
+                return Ok(checked);
+            }
+            let fetch_source = source.clone();
+            let mut shares = tokio::task::spawn_blocking(move || fetch_source.fetch_shares(start, end, INGEST_BATCH)).await
+                .context("Share fetch task failed")?;
+            let full = shares.len() >= INGEST_BATCH;
+            // A full batch may have cut a second short, so stop before its last second
+            let latest = shares.iter().map(|share| share.n_time).max().unwrap_or(end);
+            let through = if full && latest > start { latest - 1 } else { end };
+            shares.retain(|share| share.n_time <= through);
+            shares.sort_by_key(|share| share.n_time);
+
+            let mut state = self.state.write().await;
+            for share in &shares {
+                let (Some(address), Some(at)) = (&share.btcaddress, DateTime::from_timestamp(share.n_time as i64, 0)) else {
+                    continue;
+                };
+                Self::observe(&mut state, address, share.workername.as_deref(), at);
+                checked += 1;
+            }
+            state.cursor = Some(through);
+            if !full {
+                return Ok(checked);
+            }
+        }
+    }
+
+    /// Referral credits for a block's payouts
+    ///
+    /// A miner's fee is its gross share of the reward (by difficulty over
+    /// `total_difficulty`) less what it was paid.
+    pub async fn credits_for(
+        &self,
+        block_hash: &str,
+        height: u64,
+        payouts: &[BlockPayout],
+        reward_satoshis: u64,
+        total_difficulty: u64,
+    ) -> Vec<ReferralCredit> {
+        if total_difficulty == 0 {
+            return Vec::new();
+        }
+        let state = self.state.read().await;
+        payouts.iter()
+            .filter_map(|payout| {
+                let link = state.links.get(&payout.address)?;
+                let gross = (reward_satoshis as u128 * payout.difficulty as u128 / total_difficulty as u128) as u64;
+                let fee = gross.saturating_sub(payout.amount_satoshis);
+                let credited = (fee as u128 * self.config.fee_share_bps as u128 / 10000) as u64;
+                (credited > 0).then(|| ReferralCredit {
+                    block_hash: block_hash.to_string(),
+                    height,
+                    referrer: link.referrer.clone(),
+                    referred: payout.address.clone(),
+                    code: link.code.clone(),
+                    referred_fee_satoshis: fee,
+                    credited_satoshis: credited,
+                    credited_at: Utc::now(),
+                })
+            })
+            .collect()
+    }
+
+    /// Add credited referral credits to the ledger, once per block
+    pub async fn record_credits(&self, credits: &[ReferralCredit]) -> Result<()> {
+        let Some(block_hash) = credits.first().map(|credit| credit.block_hash.clone()) else {
+            return Ok(());
+        };
+        {
+            let mut state = self.state.write().await;
+            if state.credits.iter().any(|credit| credit.block_hash == block_hash) {
+                return Ok(());
+            }
+            state.credits.extend(credits.iter().cloned());
+        }
+        self.save().await?;
+
+        if let Some(revenue) = &self.revenue {
+            for credit in credits {
+                revenue.record_revenue(&NewLedgerEntry {
+                    kind: LedgerKind::ReferralCredit,
+                    amount_satoshis: -(credit.credited_satoshis as i64),
+                    block_height: Some(credit.height as i64),
+                    reference: Some(format!("{}:{}", credit.block_hash, credit.referred)),
+                    note: Some(format!("{} referred {} ({})", credit.referrer, credit.referred, credit.code)),
+                }).await.context("Failed to record referral credit revenue")?;
+            }
+        }
+        info!("Recorded {} referral credits for block {}", credits.len(), credits[0].height);
+        Ok(())
+    }
+
+    /// A miner's codes, referrer, referred miners and latest credits
+    pub async fn summary(&self, address: &str, limit: usize) -> ReferralSummary {
+        let state = self.state.read().await;
+        let mut codes: Vec<ReferralCode> = state.codes.values().filter(|c| c.owner == address).cloned().collect();
+        codes.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        let mut referrals: Vec<ReferralLink> = state.links.values().filter(|l| l.referrer == address).cloned().collect();
+        referrals.sort_by(|a, b| a.linked_at.cmp(&b.linked_at));
+        let credits: Vec<&ReferralCredit> = state.credits.iter().filter(|c| c.referrer == address).collect();
+        ReferralSummary {
+            address: address.to_string(),
+            codes,
+            referred_by: state.links.get(address).cloned(),
+            referrals,
+            credited_satoshis: credits.iter().map(|c| c.credited_satoshis).sum(),
+            credits: credits.into_iter().rev().take(limit).cloned().collect(),
+        }
+    }
+
+    /// All links, oldest first
+    pub async fn links(&self) -> Vec<ReferralLink> {
+        let mut links: Vec<ReferralLink> = self.state.read().await.links.values().cloned().collect();
+        links.sort_by(|a, b| a.linked_at.cmp(&b.linked_at));
+        links
+    }
+
+    /// Referral ledger, newest first, optionally for one referrer
+    pub async fn ledger(&self, referrer: Option<&str>, limit: usize) -> Vec<ReferralCredit> {
+        self.state.read().await.credits.iter().rev()
+            .filter(|credit| referrer.is_none_or(|referrer| credit.referrer == referrer))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Check new shares for first connections on the configured interval
+    pub fn spawn(self: Arc<Self>, source: Arc<dyn ShareSource>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.config.update_interval_secs));
+            loop {
+                interval.tick().await;
+                match self.ingest(source.clone(), Utc::now()).await {
+                    Ok(0) => continue,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Failed to check shares for referrals: {:#}", e);
+                        continue;
+                    }
+                }
+                if let Err(e) = self.save().await {
+                    warn!("Failed to save referrals: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+    use tempfile::TempDir;
+
+    struct VecSource(Vec<SimplePplnsShare>);
+
+    impl ShareSource for VecSource {
+        fn fetch_shares(&self, start: u64, end: u64, limit: usize) -> Vec<SimplePplnsShare> {
+            self.0.iter().filter(|s| s.n_time >= start && s.n_time <= end).take(limit).cloned().collect()
+        }
+    }
+
+    fn share(address: &str, worker: &str, n_time: u64) -> SimplePplnsShare {
+        SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some(worker.to_string()),
+            user_id: 1,
+            difficulty: 1,
+            n_time,
+            job_id: "job".to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: "00000000".to_string(),
+        }
+    }
+
+    fn payout(address: &str, difficulty: u64, amount_satoshis: u64) -> BlockPayout {
+        BlockPayout { address: address.to_string(), share_count: 1, difficulty, amount_satoshis, in_coinbase: false }
+    }
+
+    #[tokio::test]
+    async fn test_link_on_first_connection_and_self_service() {
+        let temp_dir = TempDir::new().unwrap();
+        let referrals = ReferralProgram::new(temp_dir.path().to_path_buf(), ReferralConfig::default()).unwrap();
+        referrals.create_code("bc1qowner", Some("friends")).await.unwrap();
+        assert!(referrals.create_code("bc1qother", Some("FRIENDS")).await.is_err());
+
+        let now = Utc::now();
+        let t = now.timestamp() as u64;
+        let source = Arc::new(VecSource(vec![
+            share("bc1qnew", "rig1+friends", t - 100),
+            // Only the first share counts
+            share("bc1qlate", "rig1", t - 90),
+            share("bc1qlate", "rig1+FRIENDS", t - 80),
+            share("bc1qowner", "rig1+FRIENDS", t - 70),
+        ]));
+        assert_eq!(referrals.ingest(source, now).await.unwrap(), 4);
+
+        let links = referrals.links().await;
+        assert_eq!(links.len(), 1);
+        assert_eq!((links[0].referred.as_str(), links[0].source), ("bc1qnew", LinkSource::Connection));
+
+        referrals.link("bc1qlate", "friends", LinkSource::SelfService, now).await.unwrap();
+        assert!(referrals.link("bc1qlate", "friends", LinkSource::SelfService, now).await.is_err());
+        assert!(referrals.link("bc1qowner", "friends", LinkSource::SelfService, now).await.is_err());
+        // Too late for a miner first seen long ago
+        let later = now + Duration::days(31);
+        referrals.state.write().await.first_seen.insert("bc1qold".to_string(), now);
+        assert!(referrals.link("bc1qold", "friends", LinkSource::SelfService, later).await.is_err());
+        assert_eq!(referrals.summary("bc1qowner", 10).await.referrals.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_credits_from_referred_fee() {
+        let temp_dir = TempDir::new().unwrap();
+        let referrals = ReferralProgram::new(temp_dir.path().to_path_buf(), ReferralConfig::default()).unwrap();
+        referrals.create_code("bc1qowner", Some("FRIENDS")).await.unwrap();
+        referrals.link("bc1qnew", "FRIENDS", LinkSource::SelfService, Utc::now()).await.unwrap();
+
+        // 1 BTC at 2%, the referred miner has half the window: 1M fee, 20% to the referrer
+        let payouts = vec![payout("bc1qnew", 1, 49_000_000), payout("bc1qsolo", 1, 49_000_000)];
+        let credits = referrals.credits_for("hash", 900_000, &payouts, 100_000_000, 2).await;
+        assert_eq!(credits.len(), 1);
+        assert_eq!((credits[0].referred_fee_satoshis, credits[0].credited_satoshis), (1_000_000, 200_000));
+
+        referrals.record_credits(&credits).await.unwrap();
+        referrals.record_credits(&credits).await.unwrap();
+        let reloaded = ReferralProgram::new(temp_dir.path().to_path_buf(), ReferralConfig::default()).unwrap();
+        reloaded.load().await.unwrap();
+        let summary = reloaded.summary("bc1qowner", 10).await;
+        assert_eq!((summary.credited_satoshis, summary.credits.len()), (200_000, 1));
+        assert_eq!(reloaded.ledger(Some("bc1qother"), 10).await.len(), 0);
+    }
+}
@@ -0,0 +1,302 @@
+// Share pruning holds
+//
+// Shares behind a pending block audit must outlive their retention. The block
+// announcer holds the PPLNS window it snapshotted for each found block, and
+// admins can place and release holds by hand. Share purges never delete
+// shares at or after the start of an active hold: the cutoff is moved back to
+// the earliest held window. Holds live in a blob store so every instance
+// sees them; if they cannot be read, shares are not purged.
+//
+// The share store prunes past the PPLNS TTL on its own schedule, so it is
+// given `max_hold_hours` of extra TTL to keep what a hold may still need.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use crate::error::DmpoolError;
+use crate::runtime_metrics::{counter, gauge, labelled};
+use crate::storage::BlobStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::info;
+
+/// Blob holding the active holds
+const HOLDS_KEY: &str = "share_holds.json";
+
+/// Longest reason kept with a hold
+const MAX_REASON_LEN: usize = 200;
+
+/// The `[dmpool.share_holds]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShareHoldConfig {
+    pub enabled: bool,
+    /// Hours a found block's window is held for its audit (0 places no automatic holds)
+    pub block_hold_hours: u64,
+    /// Longest any hold may last; also added to the share store TTL
+    pub max_hold_hours: u64,
+}
+
+impl Default for ShareHoldConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            block_hold_hours: 72,
+            max_hold_hours: 168,
+        }
+    }
+}
+
+impl ShareHoldConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_hold_hours == 0 {
+            return Err(anyhow::anyhow!("max_hold_hours must be at least 1"));
+        }
+        if self.block_hold_hours > self.max_hold_hours {
+            return Err(anyhow::anyhow!("block_hold_hours must not exceed max_hold_hours"));
+        }
+        Ok(())
+    }
+
+    /// Share store TTL that keeps shares for as long as a hold can last
+    pub fn store_ttl(&self, ttl: std::time::Duration) -> std::time::Duration {
+        if !self.enabled {
+            return ttl;
+        }
+        ttl + std::time::Duration::from_secs(self.max_hold_hours.saturating_mul(3600))
+    }
+}
+
+/// Shares created in `[window_start, window_end]` kept until `expires_at`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShareHold {
+    pub id: String,
+    pub reason: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Admin request to hold a window
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaceHoldRequest {
+    pub reason: String,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    /// Hours to hold for, `max_hold_hours` if not given
+    #[serde(default)]
+    pub hours: Option<u64>,
+}
+
+/// Share purges seen so far
+#[derive(Clone, Debug, Default)]
+struct PruneStats {
+    runs: u64,
+    /// Runs whose cutoff a hold moved back
+    held_runs: u64,
+    /// Rows deleted per table
+    rows: BTreeMap<String, u64>,
+    /// How far the last cutoff was moved back
+    last_held_back_secs: i64,
+}
+
+/// Holds on share windows, consulted before shares are purged
+pub struct ShareHolds {
+    config: ShareHoldConfig,
+    store: Arc<dyn BlobStore>,
+    /// Serializes read-modify-write of the holds blob
+    write_lock: Mutex<()>,
+    stats: RwLock<PruneStats>,
+}
+
+impl ShareHolds {
+    pub fn new(config: ShareHoldConfig, store: Arc<dyn BlobStore>) -> Self {
+        Self { config, store, write_lock: Mutex::new(()), stats: RwLock::new(PruneStats::default()) }
+    }
+
+    pub fn config(&self) -> &ShareHoldConfig {
+        &self.config
+    }
+
+    async fn load(&self) -> Result<Vec<ShareHold>> {
+        let holds: Option<Vec<ShareHold>> = self.store.get_json(HOLDS_KEY).await
+            .context("Failed to read share holds")?;
+        Ok(holds.unwrap_or_default())
+    }
+
+    /// Holds that have not expired, earliest window first
+    pub async fn list(&self) -> Result<Vec<ShareHold>> {
+        let now = Utc::now();
+        let mut holds: Vec<ShareHold> = self.load().await?.into_iter().filter(|h| h.expires_at > now).collect();
+        holds.sort_by_key(|h| (h.window_start, h.placed_at));
+        Ok(holds)
+    }
+
+    /// Store `hold`, replacing one with the same id and dropping expired ones
+    async fn save(&self, hold: ShareHold) -> Result<ShareHold> {
+        let _guard = self.write_lock.lock().await;
+        let now = Utc::now();
+        let mut holds: Vec<ShareHold> = self.load().await?.into_iter()
+            .filter(|h| h.expires_at > now && h.id != hold.id)
+            .collect();
+        holds.push(hold.clone());
+        self.store.put_json(HOLDS_KEY, &holds).await.context("Failed to write share holds")?;
+        Ok(hold)
+    }
+
+    /// Hold a window on behalf of `placed_by`
+    pub async fn place(&self, request: &PlaceHoldRequest, placed_by: &str) -> Result<ShareHold> {
+        let reason = request.reason.trim();
+        if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+            return Err(DmpoolError::InvalidInput(format!("reason must be 1 to {} characters", MAX_REASON_LEN)).into());
+        }
+        if request.window_start >= request.window_end {
+            return Err(DmpoolError::InvalidInput("window_start must be before window_end".to_string()).into());
+        }
+        let hours = request.hours.unwrap_or(self.config.max_hold_hours);
+        if hours == 0 || hours > self.config.max_hold_hours {
+            return Err(DmpoolError::InvalidInput(format!("hours must be 1 to {}", self.config.max_hold_hours)).into());
+        }
+        let placed_at = Utc::now();
+        let hold = self.save(ShareHold {
+            id: uuid::Uuid::new_v4().simple().to_string()[..12].to_string(),
+            reason: reason.to_string(),
+            window_start: request.window_start,
+            window_end: request.window_end,
+            placed_by: placed_by.to_string(),
+            placed_at,
+            expires_at: placed_at + Duration::hours(hours as i64),
+        }).await?;
+        info!("Share hold {} placed by {} on {} to {}", hold.id, placed_by, hold.window_start, hold.window_end);
+        Ok(hold)
+    }
+
+    /// Hold the window snapshotted for block `height` for its audit
+    ///
+    /// Placing it again (a replayed block) only extends it.
+    pub async fn hold_block(&self, height: u64, window_start: DateTime<Utc>, window_end: DateTime<Utc>) -> Result<Option<ShareHold>> {
+        if self.config.block_hold_hours == 0 {
+            return Ok(None);
+        }
+        let placed_at = Utc::now();
+        let hold = self.save(ShareHold {
+            id: format!("block-{}", height),
+            reason: format!("Audit of block {}", height),
+            window_start,
+            window_end,
+            placed_by: "block_announcer".to_string(),
+            placed_at,
+            expires_at: placed_at + Duration::hours(self.config.block_hold_hours as i64),
+        }).await?;
+        Ok(Some(hold))
+    }
+
+    /// Remove a hold; false if there was none
+    pub async fn release(&self, id: &str) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        let mut holds = self.load().await?;
+        let count = holds.len();
+        holds.retain(|h| h.id != id);
+        if holds.len() == count {
+            return Ok(false);
+        }
+        self.store.put_json(HOLDS_KEY, &holds).await.context("Failed to write share holds")?;
+        info!("Share hold {} released", id);
+        Ok(true)
+    }
+
+    /// Latest cutoff a share purge may use instead of `cutoff`
+    pub async fn prune_cutoff(&self, cutoff: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let earliest = self.list().await?.into_iter().map(|h| h.window_start).min();
+        Ok(earliest.map_or(cutoff, |start| start.min(cutoff)))
+    }
+
+    /// Count a share purge that used `effective` instead of `requested`
+    pub async fn record_prune(&self, requested: DateTime<Utc>, effective: DateTime<Utc>, rows: &BTreeMap<String, u64>) {
+        let mut stats = self.stats.write().await;
+        stats.runs += 1;
+        let held_back = (requested - effective).num_seconds();
+        if held_back > 0 {
+            stats.held_runs += 1;
+        }
+        stats.last_held_back_secs = held_back;
+        for (table, deleted) in rows {
+            *stats.rows.entry(table.clone()).or_insert(0) += deleted;
+        }
+    }
+
+    /// Render in the Prometheus text exposition format
+    pub async fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let stats = self.stats.read().await.clone();
+        let rows: Vec<(&str, f64)> = stats.rows.iter().map(|(table, rows)| (table.as_str(), *rows as f64)).collect();
+        labelled(&mut out, "dmpool_shares_pruned_total", "counter", "Share rows deleted by retention purges", "table", &rows);
+        counter(&mut out, "dmpool_share_prune_runs_total", "Share purges run", stats.runs as f64);
+        counter(&mut out, "dmpool_share_prune_held_runs_total", "Share purges whose cutoff a hold moved back", stats.held_runs as f64);
+        gauge(&mut out, "dmpool_share_prune_held_back_seconds", "How far holds moved back the last share purge cutoff", stats.last_held_back_secs as f64);
+        if let Ok(holds) = self.list().await {
+            gauge(&mut out, "dmpool_share_holds_active", "Active share holds", holds.len() as f64);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    #[tokio::test]
+    async fn test_holds_move_cutoff_back_until_released() {
+        let holds = ShareHolds::new(ShareHoldConfig { enabled: true, ..Default::default() }, Arc::new(MemoryStore::default()));
+        let now = Utc::now();
+        let cutoff = now - Duration::days(30);
+        assert_eq!(holds.prune_cutoff(cutoff).await.unwrap(), cutoff);
+
+        let start = now - Duration::days(40);
+        let block = holds.hold_block(900_000, start, now - Duration::days(33)).await.unwrap().unwrap();
+        assert_eq!(block.id, "block-900000");
+        holds.hold_block(900_000, start, now - Duration::days(33)).await.unwrap();
+        let manual = holds.place(&PlaceHoldRequest {
+            reason: "dispute".to_string(),
+            window_start: now - Duration::days(35),
+            window_end: now - Duration::days(34),
+            hours: Some(1),
+        }, "admin").await.unwrap();
+        assert_eq!(holds.list().await.unwrap().len(), 2);
+        assert_eq!(holds.prune_cutoff(cutoff).await.unwrap(), start);
+
+        assert!(holds.release("block-900000").await.unwrap());
+        assert!(!holds.release("block-900000").await.unwrap());
+        assert_eq!(holds.prune_cutoff(cutoff).await.unwrap(), manual.window_start);
+
+        let rows = BTreeMap::from([("shares".to_string(), 12u64)]);
+        holds.record_prune(cutoff, manual.window_start, &rows).await;
+        let text = holds.to_prometheus().await;
+        assert!(text.contains("dmpool_shares_pruned_total{table=\"shares\"} 12"));
+        assert!(text.contains("dmpool_share_prune_held_runs_total 1"));
+        assert!(text.contains("dmpool_share_holds_active 1"));
+    }
+
+    #[tokio::test]
+    async fn test_place_validates_window_and_hours() {
+        let config = ShareHoldConfig { enabled: true, max_hold_hours: 24, ..Default::default() };
+        assert!(config.validate().is_err());
+        let holds = ShareHolds::new(ShareHoldConfig { block_hold_hours: 0, ..config }, Arc::new(MemoryStore::default()));
+        let now = Utc::now();
+        let request = |start: DateTime<Utc>, hours: Option<u64>| PlaceHoldRequest {
+            reason: "audit".to_string(),
+            window_start: start,
+            window_end: now,
+            hours,
+        };
+        assert!(holds.place(&request(now, None), "admin").await.is_err());
+        assert!(holds.place(&request(now - Duration::hours(1), Some(25)), "admin").await.is_err());
+        let hold = holds.place(&request(now - Duration::hours(1), None), "admin").await.unwrap();
+        assert_eq!(hold.expires_at - hold.placed_at, Duration::hours(24));
+        assert!(holds.hold_block(1, now, now).await.unwrap().is_none());
+    }
+}
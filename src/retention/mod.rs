@@ -0,0 +1,479 @@
+// Data Retention Module for DMPool
+// Per-dataset retention policies and erasure of a miner's data on request
+//
+// Scheduled purges delete rows older than each dataset's policy. A policy of
+// 0 days keeps the dataset forever; only settled payouts are ever expired.
+//
+// A miner purge either deletes everything stored for an address or
+// anonymizes it: history the pool needs for its own accounting (share
+// rollups, block payouts, payout records) is kept under a pseudonym, while
+// settings and contact details are always deleted. Admin audit entries about
+// the address are pseudonymized rather than deleted, so the trail of what was
+// done survives the purge. A miner with an unpaid balance cannot be purged.
+// Share purges stop short of windows held for block audits (see `holds`).
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use crate::api_tokens::MinerTokenManager;
+use crate::clickhouse::ClickHouseStore;
+use crate::db::DatabaseManager;
+use crate::error::DmpoolError;
+use crate::miner_notify::MinerNotifier;
+use crate::ownership::OwnershipManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+mod holds;
+pub use holds::{PlaceHoldRequest, ShareHold, ShareHoldConfig, ShareHolds};
+
+/// Shortest share retention, so 30 day charts and estimates stay complete
+pub const MIN_SHARE_RETENTION_DAYS: u32 = 30;
+
+/// The `[dmpool.retention]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Run scheduled purges; miner purges are always available
+    pub enabled: bool,
+    pub interval_hours: u64,
+    /// Days of shares, share rollups and share quality kept (0 keeps forever)
+    pub shares_days: u32,
+    /// Days of admin audit entries kept (0 keeps forever)
+    pub audit_days: u32,
+    /// Days of notification history kept (0 keeps forever)
+    pub alerts_days: u32,
+    /// Days of settled payout records kept (0 keeps forever)
+    pub payouts_days: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 24,
+            shares_days: 180,
+            audit_days: 365,
+            alerts_days: 90,
+            payouts_days: 0,
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_hours == 0 {
+            return Err(anyhow::anyhow!("Retention interval_hours must be at least 1"));
+        }
+        if self.shares_days != 0 && self.shares_days < MIN_SHARE_RETENTION_DAYS {
+            return Err(anyhow::anyhow!(
+                "Retention shares_days must be 0 (keep) or at least {}",
+                MIN_SHARE_RETENTION_DAYS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Policies that expire data
+    pub fn policies(&self) -> Vec<RetentionPolicy> {
+        [
+            (Dataset::Shares, self.shares_days),
+            (Dataset::AuditLogs, self.audit_days),
+            (Dataset::Alerts, self.alerts_days),
+            (Dataset::Payouts, self.payouts_days),
+        ]
+        .into_iter()
+        .filter(|(_, days)| *days > 0)
+        .map(|(dataset, days)| RetentionPolicy { dataset, days })
+        .collect()
+    }
+}
+
+/// Data with a retention policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dataset {
+    Shares,
+    AuditLogs,
+    Alerts,
+    Payouts,
+}
+
+impl Dataset {
+    /// Statements deleting rows older than `$1`
+    fn purge_statements(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Dataset::Shares => &[
+                ("shares", "DELETE FROM shares WHERE created_at < $1"),
+                ("share_rollups_hourly", "DELETE FROM share_rollups_hourly WHERE hour < $1"),
+                ("worker_share_quality_hourly", "DELETE FROM worker_share_quality_hourly WHERE hour < $1"),
+            ],
+            Dataset::AuditLogs => &[("admin_audit_logs", "DELETE FROM admin_audit_logs WHERE created_at < $1")],
+            Dataset::Alerts => &[("notification_history", "DELETE FROM notification_history WHERE created_at < $1")],
+            Dataset::Payouts => &[(
+                "payout_records",
+                "DELETE FROM payout_records WHERE broadcast_at < $1 AND status IN ('confirmed', 'failed')",
+            )],
+        }
+    }
+}
+
+/// How long a dataset is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub dataset: Dataset,
+    pub days: u32,
+}
+
+/// Rows deleted from one dataset
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DatasetPurge {
+    pub dataset: Dataset,
+    pub cutoff: DateTime<Utc>,
+    /// Rows deleted per table
+    pub rows: BTreeMap<String, u64>,
+}
+
+/// Result of a retention run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RetentionRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub purged: Vec<DatasetPurge>,
+    /// Datasets that failed, with the error
+    pub errors: Vec<String>,
+}
+
+/// How a miner's data is erased
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeMode {
+    /// Keep accounting history under a pseudonym
+    #[default]
+    Anonymize,
+    /// Delete every row for the address
+    Delete,
+}
+
+/// Admin request to erase a miner's data
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MinerPurgeRequest {
+    #[serde(default)]
+    pub mode: PurgeMode,
+    /// Must repeat the address being purged
+    pub confirm: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// What a miner purge removed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub address: String,
+    /// Replaces the address in kept history and in the audit trail
+    pub pseudonym: String,
+    pub mode: PurgeMode,
+    /// Rows deleted or pseudonymized per table
+    pub rows: BTreeMap<String, u64>,
+    /// Records removed outside the database
+    pub removed: Vec<String>,
+    pub purged_at: DateTime<Utc>,
+}
+
+/// Stable pseudonym for an address
+pub fn pseudonym(address: &str) -> String {
+    let digest = Sha256::digest(address.as_bytes());
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("anon-{}", hex)
+}
+
+/// Address columns a miner purge touches
+///
+/// History tables are pseudonymized under [`PurgeMode::Anonymize`]; the rest
+/// are always deleted.
+const ADDRESS_COLUMNS: &[(&str, &str, bool)] = &[
+    ("share_rollups_hourly", "miner_address", true),
+    ("worker_share_quality_hourly", "miner_address", true),
+    ("block_payouts", "miner_address", true),
+    ("payout_records", "miner_address", true),
+    ("banned_miners", "address", true),
+    ("worker_status_cache", "miner_address", false),
+    ("worker_tags", "miner_address", false),
+    ("custom_thresholds", "address", false),
+];
+
+/// Statements erasing an address (`$1`), with its pseudonym as `$2` where used
+fn miner_purge_statements(mode: PurgeMode) -> Vec<(String, String)> {
+    let mut statements = vec![(
+        "notification_history".to_string(),
+        "DELETE FROM notification_history WHERE config_id IN (SELECT id FROM notification_configs WHERE address = $1)".to_string(),
+    ), (
+        "notification_configs".to_string(),
+        "DELETE FROM notification_configs WHERE address = $1".to_string(),
+    )];
+
+    for (table, column, history) in ADDRESS_COLUMNS {
+        let sql = if *history && mode == PurgeMode::Anonymize {
+            format!("UPDATE {} SET {} = $2 WHERE {} = $1", table, column, column)
+        } else {
+            format!("DELETE FROM {} WHERE {} = $1", table, column)
+        };
+        statements.push((table.to_string(), sql));
+    }
+
+    match mode {
+        PurgeMode::Anonymize => {
+            statements.push(("miners".to_string(), "UPDATE miners SET address = $2 WHERE address = $1".to_string()));
+        }
+        PurgeMode::Delete => {
+            statements.push(("shares".to_string(), "DELETE FROM shares WHERE miner_id IN (SELECT id FROM miners WHERE address = $1)".to_string()));
+            statements.push(("miners".to_string(), "DELETE FROM miners WHERE address = $1".to_string()));
+        }
+    }
+
+    // The audit trail is kept in both modes
+    statements.push((
+        "admin_audit_logs".to_string(),
+        "UPDATE admin_audit_logs SET target_id = $2 WHERE target_id = $1".to_string(),
+    ));
+    statements
+}
+
+/// Runs retention policies and miner purges
+pub struct RetentionManager {
+    db: Arc<DatabaseManager>,
+    config: RetentionConfig,
+    notifications: Option<Arc<MinerNotifier>>,
+    ownership: Option<Arc<OwnershipManager>>,
+    tokens: Option<Arc<MinerTokenManager>>,
+    clickhouse: Option<Arc<ClickHouseStore>>,
+    holds: Option<Arc<ShareHolds>>,
+    last_run: RwLock<Option<RetentionRun>>,
+}
+
+impl RetentionManager {
+    pub fn new(db: Arc<DatabaseManager>, config: RetentionConfig) -> Self {
+        Self {
+            db,
+            config,
+            notifications: None,
+            ownership: None,
+            tokens: None,
+            clickhouse: None,
+            holds: None,
+            last_run: RwLock::new(None),
+        }
+    }
+
+    /// Also remove payout notification preferences on purge
+    pub fn with_notifications(mut self, notifications: Arc<MinerNotifier>) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    /// Also remove address ownership verifications on purge
+    pub fn with_ownership(mut self, ownership: Arc<OwnershipManager>) -> Self {
+        self.ownership = Some(ownership);
+        self
+    }
+
+    /// Also revoke miner API tokens on purge
+    pub fn with_tokens(mut self, tokens: Arc<MinerTokenManager>) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Also delete ClickHouse share history on purge
+    pub fn with_clickhouse(mut self, clickhouse: Arc<ClickHouseStore>) -> Self {
+        self.clickhouse = Some(clickhouse);
+        self
+    }
+
+    /// Keep shares in held windows when purging shares
+    pub fn with_holds(mut self, holds: Arc<ShareHolds>) -> Self {
+        self.holds = Some(holds);
+        self
+    }
+
+    pub fn config(&self) -> &RetentionConfig {
+        &self.config
+    }
+
+    pub fn holds(&self) -> Option<&Arc<ShareHolds>> {
+        self.holds.as_ref()
+    }
+
+    /// Last scheduled or manual run
+    pub async fn last_run(&self) -> Option<RetentionRun> {
+        self.last_run.read().await.clone()
+    }
+
+    /// Apply every policy now
+    ///
+    /// A failing dataset is recorded and the others still run.
+    pub async fn run(&self) -> RetentionRun {
+        let started_at = Utc::now();
+        let mut purged = Vec::new();
+        let mut errors = Vec::new();
+
+        for policy in self.config.policies() {
+            let cutoff = started_at - Duration::days(policy.days as i64);
+            match self.purge_dataset(policy.dataset, cutoff).await {
+                Ok(rows) => purged.push(DatasetPurge { dataset: policy.dataset, cutoff, rows }),
+                Err(e) => {
+                    warn!("Retention purge of {:?} failed: {:#}", policy.dataset, e);
+                    errors.push(format!("{:?}: {:#}", policy.dataset, e));
+                }
+            }
+        }
+
+        let run = RetentionRun { started_at, finished_at: Utc::now(), purged, errors };
+        let deleted: u64 = run.purged.iter().flat_map(|p| p.rows.values()).sum();
+        if deleted > 0 {
+            info!("Retention run deleted {} rows", deleted);
+        }
+        *self.last_run.write().await = Some(run.clone());
+        run
+    }
+
+    /// Delete rows of `dataset` older than `cutoff`, whatever its retention policy
+    ///
+    /// Shares are only deleted before the earliest held window.
+    pub async fn purge_dataset(&self, dataset: Dataset, cutoff: DateTime<Utc>) -> Result<BTreeMap<String, u64>> {
+        let holds = self.holds.as_ref().filter(|_| dataset == Dataset::Shares);
+        let effective = match holds {
+            Some(holds) => holds.prune_cutoff(cutoff).await?,
+            None => cutoff,
+        };
+        if effective < cutoff {
+            info!("Share purge held back to {} (requested {})", effective, cutoff);
+        }
+
+        let conn = self.db.get_conn().await?;
+        let mut rows = BTreeMap::new();
+        for (table, sql) in dataset.purge_statements() {
+            let deleted = conn.execute(*sql, &[&effective]).await
+                .with_context(|| format!("Failed to purge {}", table))?;
+            rows.insert(table.to_string(), deleted);
+        }
+        if let Some(holds) = holds {
+            holds.record_prune(cutoff, effective, &rows).await;
+        }
+        Ok(rows)
+    }
+
+    /// Erase everything stored for `address`
+    pub async fn purge_miner(&self, address: &str, request: &MinerPurgeRequest) -> Result<PurgeReport> {
+        if request.confirm != address {
+            return Err(DmpoolError::InvalidInput("confirm must repeat the address being purged".to_string()).into());
+        }
+
+        let pseudonym = pseudonym(address);
+        let mut conn = self.db.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let balance: Option<i64> = tx
+            .query_opt("SELECT balance_sats FROM miners WHERE address = $1", &[&address])
+            .await?
+            .map(|row| row.get("balance_sats"));
+        if let Some(balance) = balance.filter(|b| *b > 0) {
+            return Err(DmpoolError::InvalidInput(format!(
+                "{} has an unpaid balance of {} sats; pay it out before purging",
+                address, balance
+            )).into());
+        }
+
+        let mut rows = BTreeMap::new();
+        for (table, sql) in miner_purge_statements(request.mode) {
+            let changed = if sql.contains("$2") {
+                tx.execute(sql.as_str(), &[&address, &pseudonym]).await
+            } else {
+                tx.execute(sql.as_str(), &[&address]).await
+            }
+            .with_context(|| format!("Failed to purge {} from {}", address, table))?;
+            *rows.entry(table).or_insert(0) += changed;
+        }
+        tx.commit().await.context("Failed to commit miner purge")?;
+
+        // Stores outside the database; the database purge already stands
+        let mut removed = Vec::new();
+        if let Some(notifications) = &self.notifications {
+            if notifications.remove(address).await? {
+                removed.push("notification_preferences".to_string());
+            }
+        }
+        if let Some(ownership) = &self.ownership {
+            if ownership.remove(address).await? {
+                removed.push("ownership_verification".to_string());
+            }
+        }
+        if let Some(tokens) = &self.tokens {
+            for token in tokens.list(address).await {
+                if tokens.revoke(address, &token.id).await? {
+                    removed.push(format!("api_token:{}", token.id));
+                }
+            }
+        }
+        if let Some(clickhouse) = &self.clickhouse {
+            clickhouse.delete_address(address).await?;
+            removed.push("clickhouse_shares".to_string());
+        }
+
+        info!("Purged miner data ({:?}) as {}", request.mode, pseudonym);
+        Ok(PurgeReport {
+            address: address.to_string(),
+            pseudonym,
+            mode: request.mode,
+            rows,
+            removed,
+            purged_at: Utc::now(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_and_validation() {
+        let config = RetentionConfig::default();
+        assert!(config.validate().is_ok());
+        let datasets: Vec<_> = config.policies().iter().map(|p| p.dataset).collect();
+        assert_eq!(datasets, vec![Dataset::Shares, Dataset::AuditLogs, Dataset::Alerts]);
+
+        assert!(RetentionConfig { shares_days: 7, ..Default::default() }.validate().is_err());
+        assert!(RetentionConfig { shares_days: 0, ..Default::default() }.validate().is_ok());
+        assert!(RetentionConfig { interval_hours: 0, ..Default::default() }.validate().is_err());
+
+        let payouts = Dataset::Payouts.purge_statements();
+        assert!(payouts[0].1.contains("status IN ('confirmed', 'failed')"));
+    }
+
+    #[test]
+    fn test_miner_purge_statements() {
+        let anonymize = miner_purge_statements(PurgeMode::Anonymize);
+        let sql = |statements: &[(String, String)], table: &str| {
+            statements.iter().find(|(t, _)| t == table).map(|(_, sql)| sql.clone()).unwrap()
+        };
+        assert_eq!(sql(&anonymize, "payout_records"), "UPDATE payout_records SET miner_address = $2 WHERE miner_address = $1");
+        assert!(sql(&anonymize, "worker_tags").starts_with("DELETE"));
+        assert!(sql(&anonymize, "miners").starts_with("UPDATE"));
+        assert!(!anonymize.iter().any(|(t, _)| t == "shares"));
+
+        let delete = miner_purge_statements(PurgeMode::Delete);
+        assert!(sql(&delete, "payout_records").starts_with("DELETE"));
+        assert!(sql(&delete, "shares").starts_with("DELETE"));
+        // Shares go before the miner row they reference, the audit trail is kept
+        let position = |table: &str| delete.iter().position(|(t, _)| t == table).unwrap();
+        assert!(position("shares") < position("miners"));
+        assert!(sql(&delete, "admin_audit_logs").starts_with("UPDATE"));
+
+        assert_eq!(pseudonym("bc1qexample"), pseudonym("bc1qexample"));
+        assert_ne!(pseudonym("bc1qexample"), pseudonym("bc1qother"));
+        assert_eq!(pseudonym("bc1qexample").len(), "anon-".len() + 16);
+    }
+}
@@ -0,0 +1,318 @@
+// Revenue Module for DMPool
+// Fee revenue ledger: pool cut per block, payout run network fees, operational donations,
+// referral credits and the fees of internal wallet transfers
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bitcoin::Network;
+use chrono::{DateTime, Utc};
+use crate::bitcoin::BitcoinRpcClient;
+use crate::db::DatabaseManager;
+use crate::payment::{NetworkParams, Payout};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::info;
+
+/// Average days per month used for monthly projections
+const DAYS_PER_MONTH: f64 = 30.44;
+
+/// Kind of ledger entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerKind {
+    /// Pool fee kept from a found block
+    BlockFee,
+    /// Network fee the pool paid for a payout run
+    PayoutFee,
+    /// Operational donation paid out of pool revenue
+    Donation,
+    /// Part of a referred miner's pool fee credited to its referrer
+    ReferralCredit,
+    /// Network fee of moving funds between the pool's own outputs, e.g. a UTXO consolidation
+    InternalTransfer,
+}
+
+impl LedgerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BlockFee => "block_fee",
+            Self::PayoutFee => "payout_fee",
+            Self::Donation => "donation",
+            Self::ReferralCredit => "referral_credit",
+            Self::InternalTransfer => "internal_transfer",
+        }
+    }
+}
+
+impl std::str::FromStr for LedgerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block_fee" => Ok(Self::BlockFee),
+            "payout_fee" => Ok(Self::PayoutFee),
+            "donation" => Ok(Self::Donation),
+            "referral_credit" => Ok(Self::ReferralCredit),
+            "internal_transfer" => Ok(Self::InternalTransfer),
+            other => Err(anyhow::anyhow!("Unknown ledger kind: {}", other)),
+        }
+    }
+}
+
+/// Entry to add to the ledger
+///
+/// Income is positive, expenses negative. Entries with the same kind and
+/// reference are recorded once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NewLedgerEntry {
+    pub kind: LedgerKind,
+    pub amount_satoshis: i64,
+    pub block_height: Option<i64>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+}
+
+/// Recorded ledger entry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub kind: LedgerKind,
+    pub amount_satoshis: i64,
+    pub block_height: Option<i64>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Summary bucket size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryPeriod {
+    #[default]
+    Daily,
+    Monthly,
+}
+
+impl SummaryPeriod {
+    /// Unit for Postgres date_trunc
+    pub fn trunc_unit(&self) -> &'static str {
+        match self {
+            Self::Daily => "day",
+            Self::Monthly => "month",
+        }
+    }
+}
+
+/// Revenue totals for one day or month
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RevenueSummary {
+    pub period_start: DateTime<Utc>,
+    pub blocks: i64,
+    pub block_fees_satoshis: i64,
+    /// Network fees paid for payout runs (negative)
+    pub payout_fees_satoshis: i64,
+    /// Donations paid (negative)
+    pub donations_satoshis: i64,
+    /// Pool fee credited to referrers (negative)
+    #[serde(default)]
+    pub referral_credits_satoshis: i64,
+    /// Network fees of internal transfers (negative)
+    #[serde(default)]
+    pub internal_transfers_satoshis: i64,
+    pub net_satoshis: i64,
+}
+
+/// Expected pool fee income at the current hashrate
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RevenueProjection {
+    /// Share difficulty accepted per second over the last 3 hours
+    pub pool_hashrate: u64,
+    pub network_difficulty: f64,
+    pub pool_fee_bps: u32,
+    pub block_reward_satoshis: u64,
+    pub expected_blocks_per_day: f64,
+    pub daily_satoshis: u64,
+    pub monthly_satoshis: u64,
+}
+
+/// Project fee income from the pool's share rate
+///
+/// `pool_hashrate` is share difficulty per second, so blocks per day is
+/// simply a day's worth of difficulty over the network difficulty.
+pub fn project_revenue(pool_hashrate: u64, network_difficulty: f64, block_reward_satoshis: u64, pool_fee_bps: u32) -> RevenueProjection {
+    let expected_blocks_per_day = if network_difficulty > 0.0 {
+        pool_hashrate as f64 * 86_400.0 / network_difficulty
+    } else {
+        0.0
+    };
+    let fee_per_block = block_reward_satoshis as f64 * pool_fee_bps as f64 / 10_000.0;
+    let daily = expected_blocks_per_day * fee_per_block;
+
+    RevenueProjection {
+        pool_hashrate,
+        network_difficulty,
+        pool_fee_bps,
+        block_reward_satoshis,
+        expected_blocks_per_day,
+        daily_satoshis: daily.round() as u64,
+        monthly_satoshis: (daily * DAYS_PER_MONTH).round() as u64,
+    }
+}
+
+/// Ledger entry for the network fees of a payout run
+///
+/// The reference is derived from the payout ids, so recording the same run
+/// twice is a no-op. Returns None if no payout has a known fee.
+pub fn payout_run_entry(payouts: &[Payout]) -> Option<NewLedgerEntry> {
+    let fees: Vec<(&str, u64)> = payouts.iter()
+        .filter_map(|p| p.intent.as_ref().map(|i| (p.id.as_str(), i.fee_satoshis)))
+        .filter(|(_, fee)| *fee > 0)
+        .collect();
+    if fees.is_empty() {
+        return None;
+    }
+
+    let mut ids: Vec<&str> = fees.iter().map(|(id, _)| *id).collect();
+    ids.sort_unstable();
+    let total: u64 = fees.iter().map(|(_, fee)| fee).sum();
+
+    Some(NewLedgerEntry {
+        kind: LedgerKind::PayoutFee,
+        amount_satoshis: -(total as i64),
+        block_height: None,
+        reference: Some(format!("run:{:x}", Sha256::digest(ids.join(",").as_bytes()))),
+        note: Some(format!("{} payouts", ids.len())),
+    })
+}
+
+/// Stores ledger entries
+#[async_trait]
+pub trait RevenueRecorder: Send + Sync {
+    /// Record an entry, returning false if it was already recorded
+    async fn record_revenue(&self, entry: &NewLedgerEntry) -> Result<bool>;
+}
+
+#[async_trait]
+impl RevenueRecorder for DatabaseManager {
+    async fn record_revenue(&self, entry: &NewLedgerEntry) -> Result<bool> {
+        self.add_ledger_entry(entry).await
+    }
+}
+
+/// Fee revenue reporting for the Admin API
+pub struct RevenueLedger {
+    db: Arc<DatabaseManager>,
+    bitcoin: Arc<BitcoinRpcClient>,
+    network: NetworkParams,
+}
+
+impl RevenueLedger {
+    /// Create a ledger backed by the database and node
+    pub fn new(db: Arc<DatabaseManager>, bitcoin: Arc<BitcoinRpcClient>) -> Self {
+        Self { db, bitcoin, network: NetworkParams::for_network(Network::Bitcoin) }
+    }
+
+    /// Network whose subsidy schedule projections use (default mainnet)
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = NetworkParams::for_network(network);
+        self
+    }
+
+    /// Record an operational donation paid from pool revenue
+    pub async fn record_donation(&self, amount_satoshis: u64, note: Option<String>) -> Result<()> {
+        if amount_satoshis == 0 {
+            return Err(anyhow::anyhow!("Donation amount must be positive"));
+        }
+        self.db.add_ledger_entry(&NewLedgerEntry {
+            kind: LedgerKind::Donation,
+            amount_satoshis: -(amount_satoshis as i64),
+            block_height: None,
+            reference: None,
+            note,
+        }).await?;
+        info!("Recorded donation of {} satoshis", amount_satoshis);
+        Ok(())
+    }
+
+    /// Ledger entries in `[from, to)`, newest first
+    pub async fn entries(&self, from: DateTime<Utc>, to: DateTime<Utc>, limit: i64) -> Result<Vec<LedgerEntry>> {
+        self.db.get_ledger_entries(from, to, limit).await
+    }
+
+    /// Daily or monthly totals in `[from, to)`
+    pub async fn summary(&self, period: SummaryPeriod, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<RevenueSummary>> {
+        self.db.get_revenue_summary(period, from, to).await
+    }
+
+    /// Projected income from the current pool hashrate and network difficulty
+    pub async fn projection(&self) -> Result<RevenueProjection> {
+        let stats = self.db.get_pool_stats().await?;
+        let chain = self.bitcoin.get_blockchain_info().await
+            .context("Failed to get network difficulty")?;
+
+        Ok(project_revenue(
+            stats.pool_hashrate_3h,
+            chain.difficulty,
+            self.network.block_subsidy_satoshis(chain.blocks + 1),
+            (stats.pool_fee_percent * 100.0).round() as u32,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment::{IntentState, PayoutIntent, PayoutSource, PayoutStatus};
+
+    fn payout(id: &str, fee: Option<u64>) -> Payout {
+        Payout {
+            id: id.to_string(),
+            address: "bc1qa".to_string(),
+            amount_satoshis: 1_000_000,
+            txid: None,
+            block_height: None,
+            status: PayoutStatus::Broadcast,
+            created_at: Utc::now(),
+            broadcast_at: None,
+            confirmations: 0,
+            error: None,
+            idempotency_key: None,
+            intent: fee.map(|fee_satoshis| PayoutIntent {
+                state: IntentState::Broadcast,
+                preimage_hash: String::new(),
+                txid: format!("tx-{}", id),
+                signed_tx_hex: String::new(),
+                attempts: 1,
+                fee_satoshis,
+                updated_at: Utc::now(),
+            }),
+            source: PayoutSource::Pool,
+        }
+    }
+
+    #[test]
+    fn test_project_revenue() {
+        // Half a network difficulty per day: one block every two days
+        let projection = project_revenue(50, 8_640_000.0, 312_500_000, 100);
+        assert!((projection.expected_blocks_per_day - 0.5).abs() < 1e-9);
+        assert_eq!(projection.daily_satoshis, 1_562_500);
+        assert_eq!(projection.monthly_satoshis, 47_562_500);
+
+        assert_eq!(project_revenue(50, 0.0, 312_500_000, 100).daily_satoshis, 0);
+    }
+
+    #[test]
+    fn test_payout_run_entry() {
+        let run = vec![payout("b", Some(1_500)), payout("a", Some(2_500)), payout("c", None)];
+        let entry = payout_run_entry(&run).unwrap();
+        assert_eq!(entry.kind, LedgerKind::PayoutFee);
+        assert_eq!(entry.amount_satoshis, -4_000);
+
+        // Same run in another order gets the same reference
+        let reordered = vec![payout("a", Some(2_500)), payout("b", Some(1_500))];
+        assert_eq!(payout_run_entry(&reordered).unwrap().reference, entry.reference);
+
+        assert!(payout_run_entry(&[payout("d", None)]).is_none());
+    }
+}
@@ -0,0 +1,349 @@
+// Runtime Metrics Module for DMPool
+// Tokio runtime, in-memory cache and channel saturation metrics
+//
+// VmRSS says the process is growing, not why. These metrics break it down:
+// tokio task counts, queue depths and poll times, the entries and estimated
+// heap use of the managers that keep data in memory (audit log cache, payout
+// history, balances) and how full the stratum notify and share emission
+// channels are. Snapshots are reported in HealthStatus and rendered in the
+// Prometheus text format for the admin metrics endpoint.
+//
+// Poll times, local queue depths and the blocking queue are only available
+// when the pool is built with `RUSTFLAGS="--cfg tokio_unstable"`.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+/// Entries held and estimated heap use of one in-memory collection
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryFootprint {
+    pub name: String,
+    pub entries: usize,
+    pub estimated_bytes: u64,
+}
+
+/// A manager that keeps data in memory
+#[async_trait]
+pub trait MemoryReporter: Send + Sync {
+    /// One footprint per in-memory collection
+    async fn footprints(&self) -> Vec<MemoryFootprint>;
+}
+
+/// Fill level of a bounded channel
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSaturation {
+    pub name: String,
+    /// Messages waiting for the receiver
+    pub queued: usize,
+    pub capacity: usize,
+    pub saturation_percent: f64,
+    /// Every sender has been dropped
+    pub closed: bool,
+}
+
+/// Tokio runtime counters
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokioMetrics {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    /// Tasks waiting in the shared injection queue
+    pub global_queue_depth: usize,
+    /// Time spent polling tasks since start, summed over workers
+    pub busy_seconds_total: f64,
+    /// Tasks waiting in worker-local run queues
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_queue_depth: Option<usize>,
+    /// Tasks waiting for a blocking thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocking_queue_depth: Option<usize>,
+    /// Task polls since start, summed over workers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polls_total: Option<u64>,
+    /// Mean task poll time across workers, in microseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean_poll_time_us: Option<f64>,
+}
+
+impl TokioMetrics {
+    /// Sample the runtime behind `handle`
+    pub fn sample(handle: &Handle) -> Self {
+        let metrics = handle.metrics();
+        let workers = metrics.num_workers();
+        let busy: Duration = (0..workers).map(|w| metrics.worker_total_busy_duration(w)).sum();
+        let mut sample = Self {
+            workers,
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            busy_seconds_total: busy.as_secs_f64(),
+            ..Default::default()
+        };
+        sample.add_unstable(&metrics);
+        sample
+    }
+
+    #[cfg(tokio_unstable)]
+    fn add_unstable(&mut self, metrics: &tokio::runtime::RuntimeMetrics) {
+        let workers = self.workers;
+        self.local_queue_depth = Some((0..workers).map(|w| metrics.worker_local_queue_depth(w)).sum());
+        self.blocking_queue_depth = Some(metrics.blocking_queue_depth());
+        self.polls_total = Some((0..workers).map(|w| metrics.worker_poll_count(w)).sum());
+        if workers > 0 {
+            let total: Duration = (0..workers).map(|w| metrics.worker_mean_poll_time(w)).sum();
+            self.mean_poll_time_us = Some(total.as_secs_f64() * 1_000_000.0 / workers as f64);
+        }
+    }
+
+    #[cfg(not(tokio_unstable))]
+    fn add_unstable(&mut self, _metrics: &tokio::runtime::RuntimeMetrics) {}
+}
+
+/// Runtime metrics at one point in time
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeSnapshot {
+    /// Absent when sampled outside a tokio runtime
+    pub tokio: Option<TokioMetrics>,
+    pub resident_memory_bytes: Option<u64>,
+    pub managers: Vec<MemoryFootprint>,
+    pub channels: Vec<ChannelSaturation>,
+}
+
+impl RuntimeSnapshot {
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        if let Some(tokio) = &self.tokio {
+            gauge(&mut out, "dmpool_tokio_workers", "Tokio worker threads", tokio.workers as f64);
+            gauge(&mut out, "dmpool_tokio_alive_tasks", "Tasks spawned and not yet completed", tokio.alive_tasks as f64);
+            gauge(&mut out, "dmpool_tokio_global_queue_depth", "Tasks waiting in the global run queue", tokio.global_queue_depth as f64);
+            counter(&mut out, "dmpool_tokio_worker_busy_seconds_total", "Time workers spent polling tasks", tokio.busy_seconds_total);
+            if let Some(depth) = tokio.local_queue_depth {
+                gauge(&mut out, "dmpool_tokio_local_queue_depth", "Tasks waiting in worker-local run queues", depth as f64);
+            }
+            if let Some(depth) = tokio.blocking_queue_depth {
+                gauge(&mut out, "dmpool_tokio_blocking_queue_depth", "Tasks waiting for a blocking thread", depth as f64);
+            }
+            if let Some(polls) = tokio.polls_total {
+                counter(&mut out, "dmpool_tokio_polls_total", "Task polls", polls as f64);
+            }
+            if let Some(mean) = tokio.mean_poll_time_us {
+                gauge(&mut out, "dmpool_tokio_mean_poll_time_seconds", "Mean task poll time", mean / 1_000_000.0);
+            }
+        }
+        if let Some(rss) = self.resident_memory_bytes {
+            gauge(&mut out, "dmpool_process_resident_memory_bytes", "Resident set size", rss as f64);
+        }
+
+        let managers = |value: fn(&MemoryFootprint) -> f64| {
+            self.managers.iter().map(move |m| (m.name.as_str(), value(m))).collect::<Vec<_>>()
+        };
+        labelled(&mut out, "dmpool_manager_entries", "gauge", "Entries held in memory", "collection",
+            &managers(|m| m.entries as f64));
+        labelled(&mut out, "dmpool_manager_memory_bytes", "gauge", "Estimated heap use of in-memory collections", "collection",
+            &managers(|m| m.estimated_bytes as f64));
+
+        let channels = |value: fn(&ChannelSaturation) -> f64| {
+            self.channels.iter().map(move |c| (c.name.as_str(), value(c))).collect::<Vec<_>>()
+        };
+        labelled(&mut out, "dmpool_channel_queued", "gauge", "Messages waiting in the channel", "channel",
+            &channels(|c| c.queued as f64));
+        labelled(&mut out, "dmpool_channel_capacity", "gauge", "Channel capacity", "channel",
+            &channels(|c| c.capacity as f64));
+        labelled(&mut out, "dmpool_channel_saturation_ratio", "gauge", "Fraction of the channel capacity in use", "channel",
+            &channels(|c| c.saturation_percent / 100.0));
+        labelled(&mut out, "dmpool_channel_open", "gauge", "Whether the channel still has senders", "channel",
+            &channels(|c| if c.closed { 0.0 } else { 1.0 }));
+        out
+    }
+}
+
+pub(crate) fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    labelled(out, name, "gauge", help, "", &[("", value)]);
+}
+
+pub(crate) fn counter(out: &mut String, name: &str, help: &str, value: f64) {
+    labelled(out, name, "counter", help, "", &[("", value)]);
+}
+
+/// Write one metric family; samples with an empty label are written unlabelled
+pub(crate) fn labelled(out: &mut String, name: &str, kind: &str, help: &str, label: &str, samples: &[(&str, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (value_label, value) in samples {
+        if value_label.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape_label(value_label), value);
+        }
+    }
+}
+
+pub(crate) fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+type ChannelProbe = Box<dyn Fn() -> ChannelSaturation + Send + Sync>;
+
+/// Collects runtime, manager memory and channel metrics
+pub struct RuntimeMetrics {
+    handle: Option<Handle>,
+    reporters: Vec<Arc<dyn MemoryReporter>>,
+    channels: Vec<ChannelProbe>,
+}
+
+impl RuntimeMetrics {
+    /// Sample the tokio runtime this is created on, if any
+    pub fn new() -> Self {
+        Self {
+            handle: Handle::try_current().ok(),
+            reporters: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Report the in-memory collections of a manager
+    pub fn with_reporter(mut self, reporter: Arc<dyn MemoryReporter>) -> Self {
+        self.reporters.push(reporter);
+        self
+    }
+
+    /// Report how full the channel behind `sender` is
+    ///
+    /// Only a weak sender is kept, so watching never keeps a channel open.
+    pub fn with_channel<T: Send + 'static>(mut self, name: impl Into<String>, sender: &mpsc::Sender<T>) -> Self {
+        let name = name.into();
+        let weak = sender.downgrade();
+        self.channels.push(Box::new(move || match weak.upgrade() {
+            Some(sender) => {
+                let capacity = sender.max_capacity();
+                let queued = capacity - sender.capacity();
+                ChannelSaturation {
+                    name: name.clone(),
+                    queued,
+                    capacity,
+                    saturation_percent: queued as f64 * 100.0 / capacity as f64,
+                    closed: false,
+                }
+            }
+            None => ChannelSaturation { name: name.clone(), closed: true, ..Default::default() },
+        }));
+        self
+    }
+
+    /// Sample every metric
+    pub async fn snapshot(&self) -> RuntimeSnapshot {
+        let mut managers = Vec::new();
+        for reporter in &self.reporters {
+            managers.extend(reporter.footprints().await);
+        }
+        RuntimeSnapshot {
+            tokio: self.handle.as_ref().map(TokioMetrics::sample),
+            resident_memory_bytes: resident_memory_bytes(),
+            managers,
+            channels: self.channels.iter().map(|probe| probe()).collect(),
+        }
+    }
+}
+
+impl Default for RuntimeMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resident set size of this process (VmRSS)
+pub fn resident_memory_bytes() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        let content = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = content.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Estimated heap bytes of an optional string
+pub fn string_bytes(value: Option<&String>) -> u64 {
+    value.map_or(0, |s| s.capacity() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedReporter;
+
+    #[async_trait]
+    impl MemoryReporter for FixedReporter {
+        async fn footprints(&self) -> Vec<MemoryFootprint> {
+            vec![MemoryFootprint { name: "payouts".to_string(), entries: 3, estimated_bytes: 960 }]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channel_saturation_and_close() {
+        let (tx, mut rx) = mpsc::channel::<u32>(4);
+        let metrics = RuntimeMetrics::new().with_channel("notify", &tx);
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        tx.send(3).await.unwrap();
+
+        let channel = &metrics.snapshot().await.channels[0];
+        assert_eq!(channel.queued, 3);
+        assert_eq!(channel.capacity, 4);
+        assert_eq!(channel.saturation_percent, 75.0);
+        assert!(!channel.closed);
+
+        // The watched sender does not hold the channel open
+        drop(tx);
+        while rx.recv().await.is_some() {}
+        assert!(metrics.snapshot().await.channels[0].closed);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_collects_runtime_and_managers() {
+        let metrics = RuntimeMetrics::new().with_reporter(Arc::new(FixedReporter));
+        let snapshot = metrics.snapshot().await;
+        let tokio = snapshot.tokio.expect("sampled inside a runtime");
+        assert_eq!(tokio.workers, 1);
+        assert!(tokio.alive_tasks <= 1);
+        assert_eq!(snapshot.managers.len(), 1);
+        assert_eq!(snapshot.managers[0].entries, 3);
+    }
+
+    #[test]
+    fn test_prometheus_format() {
+        let snapshot = RuntimeSnapshot {
+            tokio: Some(TokioMetrics { workers: 4, alive_tasks: 12, busy_seconds_total: 1.5, ..Default::default() }),
+            resident_memory_bytes: Some(1024),
+            managers: vec![MemoryFootprint { name: "audit_log".to_string(), entries: 10, estimated_bytes: 4096 }],
+            channels: vec![ChannelSaturation {
+                name: "share\"emissions".to_string(),
+                queued: 250,
+                capacity: 1000,
+                saturation_percent: 25.0,
+                closed: false,
+            }],
+        };
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("# TYPE dmpool_tokio_workers gauge\ndmpool_tokio_workers 4\n"));
+        assert!(text.contains("# TYPE dmpool_tokio_worker_busy_seconds_total counter\n"));
+        assert!(text.contains("dmpool_process_resident_memory_bytes 1024\n"));
+        assert!(text.contains("dmpool_manager_memory_bytes{collection=\"audit_log\"} 4096\n"));
+        assert!(text.contains("dmpool_channel_saturation_ratio{channel=\"share\\\"emissions\"} 0.25\n"));
+        assert!(text.contains("dmpool_channel_open{channel=\"share\\\"emissions\"} 1\n"));
+        // Unsampled poll times are left out
+        assert!(!text.contains("dmpool_tokio_mean_poll_time_seconds"));
+    }
+}
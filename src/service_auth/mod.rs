@@ -0,0 +1,389 @@
+// Service Auth Module for DMPool
+//
+// Authenticates internal HTTP calls between dmpool services with
+// HMAC-SHA256 signed requests. Each request carries the calling service,
+// the key id, a timestamp and a nonce; the signature covers those, the
+// method, path and query, and a hash of the body. Several keys can be
+// trusted at once so they can be rotated without downtime: add the new key
+// everywhere, switch `signing_key`, then drop the old one.
+
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose};
+use bitcoin::hex::DisplayHex;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SERVICE_HEADER: &str = "x-dmpool-service";
+pub const KEY_ID_HEADER: &str = "x-dmpool-key-id";
+pub const TIMESTAMP_HEADER: &str = "x-dmpool-timestamp";
+pub const NONCE_HEADER: &str = "x-dmpool-nonce";
+pub const SIGNATURE_HEADER: &str = "x-dmpool-signature";
+
+/// Shortest accepted key
+const MIN_KEY_BYTES: usize = 32;
+
+/// Largest body the middleware buffers to check a signature
+const MAX_SIGNED_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// A shared key
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServiceKey {
+    pub id: String,
+    /// Base64, at least 32 bytes
+    pub secret: String,
+}
+
+/// The `[dmpool.service_auth]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceAuthConfig {
+    /// Require signed requests on the Admin API
+    pub enabled: bool,
+    /// Name this service signs its own requests with
+    pub service: String,
+    /// Key used to sign outgoing requests; every key in `keys` is accepted
+    pub signing_key: Option<String>,
+    /// Overridden by SERVICE_AUTH_KEYS (`id=base64,id=base64`)
+    pub keys: Vec<ServiceKey>,
+    /// Services allowed to call in; empty allows any holder of a key
+    pub allowed_services: Vec<String>,
+    /// Accepted clock difference, which is also how long nonces are remembered
+    pub max_skew_secs: u64,
+}
+
+impl Default for ServiceAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            service: "dmpool".to_string(),
+            signing_key: None,
+            keys: Vec::new(),
+            allowed_services: Vec::new(),
+            max_skew_secs: 300,
+        }
+    }
+}
+
+impl ServiceAuthConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.keys.is_empty() {
+            return Err(anyhow::anyhow!("service_auth needs at least one key"));
+        }
+        for key in &self.keys {
+            decode_key(key)?;
+        }
+        if let Some(id) = &self.signing_key {
+            if !self.keys.iter().any(|k| &k.id == id) {
+                return Err(anyhow::anyhow!("signing_key {} is not one of the keys", id));
+            }
+        }
+        if self.max_skew_secs == 0 {
+            return Err(anyhow::anyhow!("max_skew_secs must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Parse SERVICE_AUTH_KEYS: `id=base64,id=base64`
+pub fn parse_keys(value: &str) -> Result<Vec<ServiceKey>> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (id, secret) = pair.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Service key '{}' must look like id=base64", pair.split('=').next().unwrap_or_default()))?;
+            Ok(ServiceKey { id: id.trim().to_string(), secret: secret.trim().to_string() })
+        })
+        .collect()
+}
+
+fn decode_key(key: &ServiceKey) -> Result<Vec<u8>> {
+    let secret = general_purpose::STANDARD.decode(key.secret.trim())
+        .with_context(|| format!("Service key {} is not valid base64", key.id))?;
+    if secret.len() < MIN_KEY_BYTES {
+        return Err(anyhow::anyhow!("Service key {} must be at least {} bytes, got {}", key.id, MIN_KEY_BYTES, secret.len()));
+    }
+    Ok(secret)
+}
+
+/// Service that signed a verified request, added to request extensions
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceIdentity {
+    pub service: String,
+    pub key_id: String,
+}
+
+/// Signs outgoing and verifies incoming service requests
+pub struct ServiceAuth {
+    service: String,
+    signing_key: Option<String>,
+    keys: HashMap<String, Vec<u8>>,
+    allowed_services: Vec<String>,
+    max_skew_secs: i64,
+    /// Nonces seen within the skew window, with their timestamps
+    nonces: Mutex<HashMap<String, i64>>,
+}
+
+impl ServiceAuth {
+    pub fn new(config: &ServiceAuthConfig) -> Result<Self> {
+        config.validate()?;
+        let keys = config.keys.iter()
+            .map(|key| Ok((key.id.clone(), decode_key(key)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(Self {
+            service: config.service.clone(),
+            signing_key: config.signing_key.clone(),
+            keys,
+            allowed_services: config.allowed_services.clone(),
+            max_skew_secs: config.max_skew_secs as i64,
+            nonces: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Headers authenticating a request from this service
+    pub fn sign(&self, method: &str, path_and_query: &str, body: &[u8]) -> Result<HeaderMap> {
+        let key_id = self.signing_key.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No service signing_key configured"))?;
+        let timestamp = Utc::now().timestamp();
+        let nonce = uuid::Uuid::new_v4().to_string();
+        let mac = self.mac(key_id, &canonical_request(method, path_and_query, timestamp, &nonce, &self.service, body))?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(SERVICE_HEADER, self.service.parse()?);
+        headers.insert(KEY_ID_HEADER, key_id.parse()?);
+        headers.insert(TIMESTAMP_HEADER, timestamp.to_string().parse()?);
+        headers.insert(NONCE_HEADER, nonce.parse()?);
+        headers.insert(SIGNATURE_HEADER, general_purpose::STANDARD.encode(mac.finalize().into_bytes()).parse()?);
+        Ok(headers)
+    }
+
+    /// Check a request's signature, freshness and nonce
+    pub async fn verify(&self, method: &str, path_and_query: &str, headers: &HeaderMap, body: &[u8]) -> Result<ServiceIdentity> {
+        let header = |name: &str| -> Result<&str> {
+            headers.get(name).and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow::anyhow!("Missing {} header", name))
+        };
+        let service = header(SERVICE_HEADER)?;
+        let key_id = header(KEY_ID_HEADER)?;
+        let nonce = header(NONCE_HEADER)?;
+        let timestamp: i64 = header(TIMESTAMP_HEADER)?.parse().context("Invalid timestamp")?;
+        let signature = general_purpose::STANDARD.decode(header(SIGNATURE_HEADER)?)
+            .context("Signature is not valid base64")?;
+
+        if !self.allowed_services.is_empty() && !self.allowed_services.iter().any(|s| s == service) {
+            return Err(anyhow::anyhow!("Service {} is not allowed", service));
+        }
+        let now = Utc::now().timestamp();
+        if (now - timestamp).abs() > self.max_skew_secs {
+            return Err(anyhow::anyhow!("Request timestamp is {}s off", now - timestamp));
+        }
+        self.mac(key_id, &canonical_request(method, path_and_query, timestamp, nonce, service, body))?
+            .verify_slice(&signature)
+            .map_err(|_| anyhow::anyhow!("Signature does not match"))?;
+
+        // Only signed requests reach the nonce cache, so it can't be flooded
+        let mut nonces = self.nonces.lock().await;
+        nonces.retain(|_, seen| now - *seen <= self.max_skew_secs);
+        if nonces.insert(nonce.to_string(), timestamp).is_some() {
+            return Err(anyhow::anyhow!("Nonce {} was already used", nonce));
+        }
+
+        Ok(ServiceIdentity { service: service.to_string(), key_id: key_id.to_string() })
+    }
+
+    fn mac(&self, key_id: &str, message: &[u8]) -> Result<HmacSha256> {
+        let key = self.keys.get(key_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown service key {}", key_id))?;
+        let mut mac = HmacSha256::new_from_slice(key).context("Invalid service key")?;
+        mac.update(message);
+        Ok(mac)
+    }
+}
+
+/// The signed string: method, path and query, timestamp, nonce, service and body hash
+fn canonical_request(method: &str, path_and_query: &str, timestamp: i64, nonce: &str, service: &str, body: &[u8]) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path_and_query,
+        timestamp,
+        nonce,
+        service,
+        Sha256::digest(body).as_slice().to_lower_hex_string(),
+    ).into_bytes()
+}
+
+/// Middleware rejecting requests without a valid service signature
+pub async fn service_auth_middleware(
+    State(auth): State<Arc<ServiceAuth>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_SIGNED_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let path_and_query = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    match auth.verify(parts.method.as_str(), path_and_query, &parts.headers, &body).await {
+        Ok(identity) => {
+            debug!("Service request from {} ({} {})", identity.service, parts.method, path_and_query);
+            parts.extensions.insert(identity);
+            next.run(Request::from_parts(parts, Body::from(body))).await
+        }
+        Err(e) => {
+            warn!("Rejected service request {} {}: {:#}", parts.method, path_and_query, e);
+            (StatusCode::UNAUTHORIZED, "Service authentication failed").into_response()
+        }
+    }
+}
+
+/// HTTP client for another dmpool service, signing every request
+pub struct ServiceClient {
+    base_url: String,
+    auth: Arc<ServiceAuth>,
+    http: reqwest::Client,
+}
+
+impl ServiceClient {
+    pub fn new(base_url: impl Into<String>, auth: Arc<ServiceAuth>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .context("Failed to build service HTTP client")?;
+        Ok(Self { base_url: base_url.into().trim_end_matches('/').to_string(), auth, http })
+    }
+
+    pub async fn get(&self, path_and_query: &str) -> Result<reqwest::Response> {
+        self.send(reqwest::Method::GET, path_and_query, Vec::new()).await
+    }
+
+    /// Send a JSON body with any method
+    pub async fn send_json<T: Serialize>(&self, method: reqwest::Method, path_and_query: &str, body: &T) -> Result<reqwest::Response> {
+        let body = serde_json::to_vec(body).context("Failed to serialize request body")?;
+        self.send(method, path_and_query, body).await
+    }
+
+    async fn send(&self, method: reqwest::Method, path_and_query: &str, body: Vec<u8>) -> Result<reqwest::Response> {
+        let headers = self.auth.sign(method.as_str(), path_and_query, &body)?;
+        let mut request = self.http.request(method, format!("{}{}", self.base_url, path_and_query))
+            .headers(headers);
+        if !body.is_empty() {
+            request = request.header(reqwest::header::CONTENT_TYPE, "application/json").body(body);
+        }
+        request.send().await
+            .with_context(|| format!("Failed to reach {}{}", self.base_url, path_and_query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Extension, Router};
+    use tower::Service;
+
+    fn key(id: &str, byte: u8) -> ServiceKey {
+        ServiceKey { id: id.to_string(), secret: general_purpose::STANDARD.encode([byte; 32]) }
+    }
+
+    fn auth(service: &str, signing_key: &str, keys: Vec<ServiceKey>) -> ServiceAuth {
+        ServiceAuth::new(&ServiceAuthConfig {
+            enabled: true,
+            service: service.to_string(),
+            signing_key: Some(signing_key.to_string()),
+            keys,
+            ..Default::default()
+        }).unwrap()
+    }
+
+    fn auth_config() -> ServiceAuthConfig {
+        ServiceAuthConfig {
+            enabled: true,
+            service: "admin".to_string(),
+            signing_key: Some("k1".to_string()),
+            keys: vec![key("k1", 9)],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sign_verify_and_rotation() {
+        let observer = auth("observer", "2026-09", vec![key("2026-09", 1)]);
+        let admin = auth("admin", "2026-10", vec![key("2026-09", 1), key("2026-10", 2)]);
+
+        let headers = observer.sign("post", "/api/admin/backups?limit=5", b"{}").unwrap();
+        // Tampered path, method or body fail
+        assert!(admin.verify("POST", "/api/admin/backups?limit=50", &headers, b"{}").await.is_err());
+        assert!(admin.verify("GET", "/api/admin/backups?limit=5", &headers, b"{}").await.is_err());
+        assert!(admin.verify("POST", "/api/admin/backups?limit=5", &headers, b"{ }").await.is_err());
+
+        // The old key is still accepted during rotation, but only once per nonce
+        let identity = admin.verify("POST", "/api/admin/backups?limit=5", &headers, b"{}").await.unwrap();
+        assert_eq!(identity, ServiceIdentity { service: "observer".to_string(), key_id: "2026-09".to_string() });
+        assert!(admin.verify("POST", "/api/admin/backups?limit=5", &headers, b"{}").await.is_err());
+
+        // A service that dropped the old key rejects it
+        let headers = admin.sign("GET", "/", b"").unwrap();
+        assert!(observer.verify("GET", "/", &headers, b"").await.is_err());
+
+        let mut stale = observer.sign("GET", "/", b"").unwrap();
+        stale.insert(TIMESTAMP_HEADER, (Utc::now().timestamp() - 3600).to_string().parse().unwrap());
+        assert!(admin.verify("GET", "/", &stale, b"").await.unwrap_err().to_string().contains("off"));
+
+        let short = ServiceKey { id: "short".to_string(), secret: general_purpose::STANDARD.encode([1u8; 8]) };
+        assert!(ServiceAuthConfig { keys: vec![short], ..Default::default() }.validate().is_err());
+        assert!(ServiceAuthConfig { keys: vec![key("k1", 1)], signing_key: Some("k2".to_string()), ..Default::default() }.validate().is_err());
+        assert_eq!(parse_keys("a=AAAA, b=BBBB").unwrap().len(), 2);
+        assert!(parse_keys("a").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_middleware() {
+        let server = Arc::new(ServiceAuth::new(&ServiceAuthConfig {
+            allowed_services: vec!["observer".to_string()],
+            ..auth_config()
+        }).unwrap());
+        let mut app = Router::new()
+            .route("/echo", post(|Extension(identity): Extension<ServiceIdentity>, body: String| async move {
+                format!("{}:{}", identity.service, body)
+            }))
+            .layer(axum::middleware::from_fn_with_state(server, service_auth_middleware));
+
+        let observer = auth("observer", "k1", vec![key("k1", 9)]);
+        let mut request = axum::http::Request::post("/echo");
+        for (name, value) in observer.sign("POST", "/echo", b"ping").unwrap().iter() {
+            request = request.header(name, value);
+        }
+        let response = app.call(request.body(Body::from("ping")).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"observer:ping");
+
+        // Unsigned requests and services that aren't allowed are rejected
+        let response = app.call(axum::http::Request::post("/echo").body(Body::from("ping")).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let worker = auth("worker", "k1", vec![key("k1", 9)]);
+        let mut request = axum::http::Request::post("/echo");
+        for (name, value) in worker.sign("POST", "/echo", b"ping").unwrap().iter() {
+            request = request.header(name, value);
+        }
+        let response = app.call(request.body(Body::from("ping")).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
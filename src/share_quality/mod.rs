@@ -0,0 +1,338 @@
+// Share Quality Module for DMPool
+// Tracks accepted/rejected/stale/duplicate share ratios per worker connection
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use crate::alert::{AlertCondition, AlertManager};
+use crate::db::DatabaseManager;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, warn};
+
+/// Default rolling window for per-connection ratios (minutes)
+const DEFAULT_WINDOW_MINUTES: i64 = 60;
+
+/// Outcome of a submitted share
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareOutcome {
+    Accepted,
+    Rejected,
+    Stale,
+    Duplicate,
+}
+
+/// Share counts by outcome
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareCounts {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub stale: u64,
+    pub duplicate: u64,
+}
+
+impl ShareCounts {
+    /// Count one share
+    pub fn add(&mut self, outcome: ShareOutcome) {
+        match outcome {
+            ShareOutcome::Accepted => self.accepted += 1,
+            ShareOutcome::Rejected => self.rejected += 1,
+            ShareOutcome::Stale => self.stale += 1,
+            ShareOutcome::Duplicate => self.duplicate += 1,
+        }
+    }
+
+    /// Add another set of counts
+    pub fn merge(&mut self, other: &ShareCounts) {
+        self.accepted += other.accepted;
+        self.rejected += other.rejected;
+        self.stale += other.stale;
+        self.duplicate += other.duplicate;
+    }
+
+    /// All submitted shares
+    pub fn total(&self) -> u64 {
+        self.accepted + self.rejected + self.stale + self.duplicate
+    }
+
+    /// Rejected, stale and duplicate shares as a percentage of all shares
+    pub fn reject_percent(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => (total - self.accepted) as f64 / total as f64 * 100.0,
+        }
+    }
+}
+
+/// Rolling share quality for one connection
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionQuality {
+    pub connection_id: u64,
+    pub address: String,
+    pub worker: String,
+    /// Counts within the rolling window
+    pub counts: ShareCounts,
+    pub reject_percent: f64,
+    pub last_share: DateTime<Utc>,
+}
+
+/// Per-connection state
+struct ConnectionState {
+    address: String,
+    worker: String,
+    /// Per-minute counts, oldest first
+    minutes: VecDeque<(DateTime<Utc>, ShareCounts)>,
+    last_share: DateTime<Utc>,
+}
+
+impl ConnectionState {
+    fn prune(&mut self, cutoff: DateTime<Utc>) {
+        while self.minutes.front().is_some_and(|(minute, _)| *minute < cutoff) {
+            self.minutes.pop_front();
+        }
+    }
+
+    fn counts(&self) -> ShareCounts {
+        let mut counts = ShareCounts::default();
+        for (_, minute) in &self.minutes {
+            counts.merge(minute);
+        }
+        counts
+    }
+}
+
+/// Tracks share acceptance quality per worker connection
+pub struct ShareQualityTracker {
+    window: Duration,
+    connections: RwLock<HashMap<u64, ConnectionState>>,
+    /// Hourly counts not yet written to the database
+    pending: RwLock<HashMap<(String, String, DateTime<Utc>), ShareCounts>>,
+}
+
+impl Default for ShareQualityTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW_MINUTES)
+    }
+}
+
+impl ShareQualityTracker {
+    /// Create a tracker with a rolling window of `window_minutes`
+    pub fn new(window_minutes: i64) -> Self {
+        Self {
+            window: Duration::minutes(window_minutes.max(1)),
+            connections: RwLock::new(HashMap::new()),
+            pending: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a share submitted on a connection
+    pub async fn record(&self, connection_id: u64, address: &str, worker: &str, outcome: ShareOutcome, at: DateTime<Utc>) {
+        let minute = at.duration_trunc(Duration::minutes(1)).unwrap_or(at);
+        let hour = at.duration_trunc(Duration::hours(1)).unwrap_or(at);
+
+        {
+            let mut connections = self.connections.write().await;
+            let state = connections.entry(connection_id).or_insert_with(|| ConnectionState {
+                address: address.to_string(),
+                worker: worker.to_string(),
+                minutes: VecDeque::new(),
+                last_share: at,
+            });
+
+            match state.minutes.back_mut() {
+                Some((last, counts)) if *last == minute => counts.add(outcome),
+                _ => {
+                    let mut counts = ShareCounts::default();
+                    counts.add(outcome);
+                    state.minutes.push_back((minute, counts));
+                }
+            }
+            state.last_share = state.last_share.max(at);
+            state.prune(at - self.window);
+        }
+
+        self.pending.write().await
+            .entry((address.to_string(), worker.to_string(), hour))
+            .or_default()
+            .add(outcome);
+    }
+
+    /// Forget a closed connection
+    pub async fn disconnect(&self, connection_id: u64) {
+        self.connections.write().await.remove(&connection_id);
+    }
+
+    /// Rolling quality for every open connection
+    pub async fn connections(&self) -> Vec<ConnectionQuality> {
+        let cutoff = Utc::now() - self.window;
+        let mut connections = self.connections.write().await;
+
+        let mut result: Vec<ConnectionQuality> = connections.iter_mut()
+            .map(|(id, state)| {
+                state.prune(cutoff);
+                let counts = state.counts();
+                ConnectionQuality {
+                    connection_id: *id,
+                    address: state.address.clone(),
+                    worker: state.worker.clone(),
+                    reject_percent: counts.reject_percent(),
+                    counts,
+                    last_share: state.last_share,
+                }
+            })
+            .collect();
+        result.sort_by_key(|c| c.connection_id);
+        result
+    }
+
+    /// Write pending hourly aggregates to the database
+    ///
+    /// Counts that fail to write are kept for the next flush.
+    pub async fn flush(&self, db: &DatabaseManager) -> Result<usize> {
+        let pending = std::mem::take(&mut *self.pending.write().await);
+        let mut written = 0;
+        let mut failed = HashMap::new();
+
+        for ((address, worker, hour), counts) in pending {
+            match db.add_share_quality(&address, &worker, hour, &counts).await {
+                Ok(()) => written += 1,
+                Err(e) => {
+                    error!("Failed to write share quality for {}.{}: {}", address, worker, e);
+                    failed.insert((address, worker, hour), counts);
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            let mut pending = self.pending.write().await;
+            for (key, counts) in failed {
+                pending.entry(key).or_default().merge(&counts);
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Trigger RejectRateAbove rules for connections over their threshold
+    ///
+    /// Returns the ids of the connections that alerted.
+    pub async fn check_alerts(&self, alerts: &AlertManager) -> Result<Vec<u64>> {
+        let rules: Vec<_> = alerts.get_rules().await.into_iter()
+            .filter(|r| r.enabled)
+            .filter_map(|r| match r.condition {
+                AlertCondition::RejectRateAbove { threshold_percent, min_shares } => {
+                    Some((r.id, threshold_percent, min_shares))
+                }
+                _ => None,
+            })
+            .collect();
+        if rules.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut alerted = Vec::new();
+        for connection in self.connections().await {
+            for (rule_id, threshold_percent, min_shares) in &rules {
+                if connection.counts.total() < *min_shares || connection.reject_percent <= *threshold_percent {
+                    continue;
+                }
+
+                warn!(
+                    "Worker {}.{} reject rate {:.1}% over {} shares",
+                    connection.address, connection.worker, connection.reject_percent, connection.counts.total()
+                );
+                alerts.trigger_alert(rule_id, serde_json::to_value(&connection)?).await?;
+                if !alerted.contains(&connection.connection_id) {
+                    alerted.push(connection.connection_id);
+                }
+            }
+        }
+
+        Ok(alerted)
+    }
+
+    /// Periodically flush aggregates and evaluate reject rate alerts
+    pub async fn run(self: Arc<Self>, db: Arc<DatabaseManager>, alerts: Option<Arc<AlertManager>>, interval: std::time::Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.flush(&db).await {
+                error!("Share quality flush failed: {}", e);
+            }
+            if let Some(alerts) = &alerts {
+                if let Err(e) = self.check_alerts(alerts).await {
+                    error!("Share quality alert check failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{AlertLevel, AlertRule};
+
+    #[tokio::test]
+    async fn test_rolling_ratios() {
+        let tracker = ShareQualityTracker::new(60);
+        let now = Utc::now();
+
+        // Old rejects fall out of the window
+        for _ in 0..5 {
+            tracker.record(1, "bc1qa", "rig1", ShareOutcome::Rejected, now - Duration::minutes(90)).await;
+        }
+        for _ in 0..8 {
+            tracker.record(1, "bc1qa", "rig1", ShareOutcome::Accepted, now).await;
+        }
+        tracker.record(1, "bc1qa", "rig1", ShareOutcome::Stale, now).await;
+        tracker.record(1, "bc1qa", "rig1", ShareOutcome::Duplicate, now).await;
+        tracker.record(2, "bc1qb", "rig1", ShareOutcome::Accepted, now).await;
+
+        let connections = tracker.connections().await;
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].counts, ShareCounts { accepted: 8, rejected: 0, stale: 1, duplicate: 1 });
+        assert!((connections[0].reject_percent - 20.0).abs() < 1e-9);
+
+        // Pending hourly aggregates keep everything for the database
+        let pending = tracker.pending.read().await;
+        let rejected: u64 = pending.values().map(|c| c.rejected).sum();
+        assert_eq!(rejected, 5);
+        drop(pending);
+
+        tracker.disconnect(2).await;
+        assert_eq!(tracker.connections().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reject_rate_alert() {
+        let alerts = AlertManager::default();
+        alerts.add_rule(AlertRule::new(
+            "reject_rate",
+            "High reject rate",
+            AlertCondition::RejectRateAbove { threshold_percent: 10.0, min_shares: 10 },
+            AlertLevel::Warning,
+            Vec::new(),
+        ).with_cooldown(0)).await;
+
+        let tracker = ShareQualityTracker::default();
+        let now = Utc::now();
+        for i in 0..10 {
+            let outcome = if i < 3 { ShareOutcome::Rejected } else { ShareOutcome::Accepted };
+            tracker.record(1, "bc1qa", "rig1", outcome, now).await;
+        }
+        // Too few shares to judge
+        for _ in 0..3 {
+            tracker.record(2, "bc1qb", "rig1", ShareOutcome::Rejected, now).await;
+        }
+        // Healthy
+        for _ in 0..20 {
+            tracker.record(3, "bc1qc", "rig1", ShareOutcome::Accepted, now).await;
+        }
+
+        assert_eq!(tracker.check_alerts(&alerts).await.unwrap(), vec![1]);
+        assert_eq!(alerts.get_history(None).await.len(), 1);
+    }
+}
@@ -0,0 +1,464 @@
+// Share Validation Module for DMPool
+// Duplicate, time-warp and stale-job checks on accepted shares, with automatic bans
+//
+// Shares are checked on their way from the stratum server to the node. A
+// violation is counted against the worker; once a worker reaches
+// `ban_threshold` violations within `violation_window_secs`, its address is
+// written to banned_miners and its shares are refused until the ban expires.
+//
+// Jobs are learned from the shares that reference them: a job is stale once
+// it was first seen more than `stale_job_secs` ago, or when the pool finds a
+// block after it was first seen.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::db::DatabaseManager;
+use crate::events::{EventHandler, PoolEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, error, warn};
+
+/// Alert rule raised when a miner is banned automatically
+pub const SHARE_BAN_ALERT_RULE: &str = "share_validation_ban";
+
+/// How long job ids are remembered, so late shares for old jobs are still stale
+const JOB_MEMORY_HOURS: i64 = 24;
+
+/// The `[dmpool.share_validation]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShareValidationConfig {
+    pub enabled: bool,
+    /// Drop invalid shares instead of only counting them
+    pub reject_invalid: bool,
+    /// Seconds nTime may be ahead of the pool's clock
+    pub max_future_secs: u64,
+    /// Seconds nTime may be behind the pool's clock
+    pub max_past_secs: u64,
+    /// Seconds after a job is first seen before its shares are stale
+    pub stale_job_secs: u64,
+    /// Seconds violations are counted over
+    pub violation_window_secs: u64,
+    /// Violations within the window that ban the worker's address; 0 never bans
+    pub ban_threshold: u32,
+    /// Days an automatic ban lasts; 0 bans permanently
+    pub ban_days: u32,
+}
+
+impl Default for ShareValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reject_invalid: true,
+            max_future_secs: 7200,
+            max_past_secs: 3600,
+            stale_job_secs: 300,
+            violation_window_secs: 3600,
+            ban_threshold: 100,
+            ban_days: 1,
+        }
+    }
+}
+
+impl ShareValidationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.stale_job_secs == 0 {
+            return Err(anyhow::anyhow!("stale_job_secs must be at least 1"));
+        }
+        if self.stale_job_secs > self.max_past_secs {
+            return Err(anyhow::anyhow!("stale_job_secs cannot exceed max_past_secs ({})", self.max_past_secs));
+        }
+        if self.ban_threshold > 0 && self.violation_window_secs == 0 {
+            return Err(anyhow::anyhow!("violation_window_secs must be at least 1 when bans are enabled"));
+        }
+        Ok(())
+    }
+}
+
+/// A share as submitted by a worker
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShareSubmission {
+    pub address: String,
+    pub worker: Option<String>,
+    pub job_id: String,
+    pub extranonce2: String,
+    pub nonce: String,
+    /// Block header time chosen by the miner (unix seconds)
+    pub n_time: u64,
+    pub difficulty: u64,
+}
+
+/// Why a share was refused
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShareViolation {
+    /// Same job, extranonce2, nonce and nTime as an earlier share
+    Duplicate,
+    /// nTime is this many seconds away from the pool's clock (positive is ahead)
+    TimeWarp { offset_secs: i64 },
+    /// The job was superseded
+    StaleJob,
+    /// The address is banned
+    Banned,
+}
+
+/// Violations per kind
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViolationCounts {
+    pub duplicate: u64,
+    pub time_warp: u64,
+    pub stale_job: u64,
+}
+
+impl ViolationCounts {
+    fn add(&mut self, violation: &ShareViolation) {
+        match violation {
+            ShareViolation::Duplicate => self.duplicate += 1,
+            ShareViolation::TimeWarp { .. } => self.time_warp += 1,
+            ShareViolation::StaleJob => self.stale_job += 1,
+            ShareViolation::Banned => {}
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.duplicate + self.time_warp + self.stale_job
+    }
+}
+
+/// A worker's violation history
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerViolations {
+    pub address: String,
+    pub worker: String,
+    /// Counts since the validator started
+    pub counts: ViolationCounts,
+    /// Violations within the window
+    pub recent: usize,
+    pub last_violation: DateTime<Utc>,
+}
+
+/// Where automatic bans are written (banned_miners in production)
+#[async_trait]
+pub trait MinerBanStore: Send + Sync {
+    /// Ban an address until `expires_at`, or permanently when None
+    async fn ban_miner(&self, address: &str, reason: &str, expires_at: Option<DateTime<Utc>>) -> Result<()>;
+}
+
+#[async_trait]
+impl MinerBanStore for DatabaseManager {
+    async fn ban_miner(&self, address: &str, reason: &str, expires_at: Option<DateTime<Utc>>) -> Result<()> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO banned_miners (address, reason, is_permanent, expires_at, banned_by) VALUES ($1, $2, $3, $4, 'share_validation')
+             ON CONFLICT (address) DO UPDATE SET reason = $2, is_permanent = $3, expires_at = $4, banned_by = 'share_validation'",
+            &[&address, &reason, &expires_at.is_none(), &expires_at],
+        )
+        .await
+        .context("Failed to ban miner")?;
+        Ok(())
+    }
+}
+
+/// A job as learned from shares
+struct JobState {
+    first_seen: DateTime<Utc>,
+    /// Share keys (address, extranonce2, nonce, nTime); dropped once the job is stale
+    shares: Option<HashSet<(String, String, String, u64)>>,
+}
+
+struct WorkerState {
+    counts: ViolationCounts,
+    recent: VecDeque<DateTime<Utc>>,
+    last_violation: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct ValidatorState {
+    jobs: HashMap<String, JobState>,
+    /// When the pool last found a block
+    last_block: Option<DateTime<Utc>>,
+    workers: HashMap<(String, String), WorkerState>,
+    /// Addresses banned by this validator, with expiry
+    bans: HashMap<String, Option<DateTime<Utc>>>,
+}
+
+/// Checks shares and escalates repeat offenders to bans
+pub struct ShareValidator {
+    config: ShareValidationConfig,
+    state: Mutex<ValidatorState>,
+    bans: Option<Arc<dyn MinerBanStore>>,
+    alerts: Option<Arc<AlertManager>>,
+}
+
+impl ShareValidator {
+    pub fn new(config: ShareValidationConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(ValidatorState::default()),
+            bans: None,
+            alerts: None,
+        }
+    }
+
+    /// Write automatic bans to a store; without one, violations are only counted
+    pub fn with_ban_store(mut self, bans: Arc<dyn MinerBanStore>) -> Self {
+        self.bans = Some(bans);
+        self
+    }
+
+    /// Raise an alert for each automatic ban
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// Whether invalid shares should be dropped
+    pub fn rejects_invalid(&self) -> bool {
+        self.config.reject_invalid
+    }
+
+    /// Check a share, counting any violation against its worker
+    pub async fn check(&self, share: &ShareSubmission, now: DateTime<Utc>) -> Option<ShareViolation> {
+        let worker = share.worker.clone().unwrap_or_else(|| "worker".to_string());
+        let (violation, ban) = {
+            let mut state = self.state.lock().await;
+            match state.bans.get(&share.address).copied() {
+                Some(expires_at) if expires_at.is_none_or(|at| at > now) => return Some(ShareViolation::Banned),
+                Some(_) => {
+                    state.bans.remove(&share.address);
+                }
+                None => {}
+            }
+            let violation = self.find_violation(&mut state, share, now)?;
+            let ban = self.count_violation(&mut state, &share.address, &worker, &violation, now);
+            (violation, ban)
+        };
+
+        debug!("Share from {}.{} failed validation: {:?}", share.address, worker, violation);
+        if let Some((violations, expires_at)) = ban {
+            self.ban(&share.address, &worker, violations, expires_at).await;
+        }
+        Some(violation)
+    }
+
+    /// Mark every job seen so far stale
+    pub async fn new_block(&self, at: DateTime<Utc>) {
+        self.state.lock().await.last_block = Some(at);
+    }
+
+    /// Workers with violations, most recent first
+    pub async fn violations(&self, now: DateTime<Utc>) -> Vec<WorkerViolations> {
+        let window_start = now - Duration::seconds(self.config.violation_window_secs as i64);
+        let state = self.state.lock().await;
+        let mut workers: Vec<WorkerViolations> = state.workers.iter()
+            .map(|((address, worker), w)| WorkerViolations {
+                address: address.clone(),
+                worker: worker.clone(),
+                counts: w.counts,
+                recent: w.recent.iter().filter(|at| **at > window_start).count(),
+                last_violation: w.last_violation,
+            })
+            .collect();
+        workers.sort_by(|a, b| b.last_violation.cmp(&a.last_violation));
+        workers
+    }
+
+    fn find_violation(&self, state: &mut ValidatorState, share: &ShareSubmission, now: DateTime<Utc>) -> Option<ShareViolation> {
+        let offset_secs = share.n_time as i64 - now.timestamp();
+        if offset_secs > self.config.max_future_secs as i64 || -offset_secs > self.config.max_past_secs as i64 {
+            return Some(ShareViolation::TimeWarp { offset_secs });
+        }
+
+        let stale_before = now - Duration::seconds(self.config.stale_job_secs as i64);
+        let forget_before = now - Duration::hours(JOB_MEMORY_HOURS);
+        // Prune once per new job rather than per share
+        if !state.jobs.contains_key(&share.job_id) {
+            state.jobs.retain(|_, job| job.first_seen > forget_before);
+            for job in state.jobs.values_mut() {
+                if job.first_seen <= stale_before {
+                    job.shares = None;
+                }
+            }
+        }
+
+        let last_block = state.last_block;
+        let job = state.jobs.entry(share.job_id.clone()).or_insert_with(|| JobState {
+            first_seen: now,
+            shares: Some(HashSet::new()),
+        });
+        if job.first_seen <= stale_before || last_block.is_some_and(|at| job.first_seen < at) {
+            job.shares = None;
+            return Some(ShareViolation::StaleJob);
+        }
+        let key = (share.address.clone(), share.extranonce2.clone(), share.nonce.clone(), share.n_time);
+        let duplicate = job.shares.as_mut().is_some_and(|shares| !shares.insert(key));
+        duplicate.then_some(ShareViolation::Duplicate)
+    }
+
+    /// Count a violation, returning the recent count and ban expiry when the worker should be banned
+    fn count_violation(&self, state: &mut ValidatorState, address: &str, worker: &str, violation: &ShareViolation, now: DateTime<Utc>) -> Option<(usize, Option<DateTime<Utc>>)> {
+        let window_start = now - Duration::seconds(self.config.violation_window_secs as i64);
+        let entry = state.workers.entry((address.to_string(), worker.to_string())).or_insert_with(|| WorkerState {
+            counts: ViolationCounts::default(),
+            recent: VecDeque::new(),
+            last_violation: now,
+        });
+        entry.counts.add(violation);
+        entry.last_violation = now;
+        entry.recent.push_back(now);
+        while entry.recent.front().is_some_and(|at| *at <= window_start) {
+            entry.recent.pop_front();
+        }
+
+        let recent = entry.recent.len();
+        if self.config.ban_threshold == 0 || recent < self.config.ban_threshold as usize {
+            return None;
+        }
+        // A new ban needs a fresh run of violations
+        entry.recent.clear();
+        let expires_at = (self.config.ban_days > 0).then(|| now + Duration::days(self.config.ban_days as i64));
+        state.bans.insert(address.to_string(), expires_at);
+        Some((recent, expires_at))
+    }
+
+    async fn ban(&self, address: &str, worker: &str, violations: usize, expires_at: Option<DateTime<Utc>>) {
+        let reason = format!(
+            "Automatic ban: {} invalid shares from worker {} within {}s",
+            violations, worker, self.config.violation_window_secs
+        );
+        warn!("Banning {}: {}", address, reason);
+
+        if let Some(bans) = &self.bans {
+            if let Err(e) = bans.ban_miner(address, &reason, expires_at).await {
+                error!("Failed to record ban of {}: {:#}", address, e);
+            }
+        }
+
+        if let Some(alerts) = &self.alerts {
+            if !alerts.get_rules().await.iter().any(|r| r.id == SHARE_BAN_ALERT_RULE) {
+                let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+                alerts.add_rule(AlertRule::new(
+                    SHARE_BAN_ALERT_RULE,
+                    "Miner banned for invalid shares",
+                    AlertCondition::Custom { message: "A miner was banned automatically for invalid shares".to_string() },
+                    AlertLevel::Warning,
+                    channels,
+                ).with_cooldown(0)).await;
+            }
+            let context = serde_json::json!({
+                "address": address,
+                "worker": worker,
+                "violations": violations,
+                "expires_at": expires_at,
+                "reason": reason,
+            });
+            if let Err(e) = alerts.trigger_alert(SHARE_BAN_ALERT_RULE, context).await {
+                warn!("Failed to raise ban alert: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ShareValidator {
+    fn name(&self) -> &str {
+        "share_validation"
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        kind == "block_found"
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        if let PoolEvent::BlockFound(_) = event {
+            self.new_block(Utc::now()).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Default)]
+    struct MemoryBans(Mutex<Vec<(String, Option<DateTime<Utc>>)>>);
+
+    #[async_trait]
+    impl MinerBanStore for MemoryBans {
+        async fn ban_miner(&self, address: &str, _reason: &str, expires_at: Option<DateTime<Utc>>) -> Result<()> {
+            self.0.lock().await.push((address.to_string(), expires_at));
+            Ok(())
+        }
+    }
+
+    fn share(job_id: &str, nonce: &str, n_time: DateTime<Utc>) -> ShareSubmission {
+        ShareSubmission {
+            address: "bc1qminer".to_string(),
+            worker: Some("rig1".to_string()),
+            job_id: job_id.to_string(),
+            extranonce2: "00000001".to_string(),
+            nonce: nonce.to_string(),
+            n_time: n_time.timestamp() as u64,
+            difficulty: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_time_warp_and_stale_jobs() {
+        let validator = ShareValidator::new(ShareValidationConfig::default());
+        let now = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(validator.check(&share("1a", "aa", now), now).await, None);
+        assert_eq!(validator.check(&share("1a", "aa", now), now).await, Some(ShareViolation::Duplicate));
+        // Another nonce, or the same nonce from another miner, is fine
+        assert_eq!(validator.check(&share("1a", "ab", now), now).await, None);
+        let other = ShareSubmission { address: "bc1qother".to_string(), ..share("1a", "aa", now) };
+        assert_eq!(validator.check(&other, now).await, None);
+
+        let warped = share("1a", "ac", now + Duration::hours(3));
+        assert_eq!(validator.check(&warped, now).await, Some(ShareViolation::TimeWarp { offset_secs: 10800 }));
+
+        // Jobs go stale with age and when the pool finds a block
+        let later = now + Duration::seconds(301);
+        assert_eq!(validator.check(&share("1a", "ad", later), later).await, Some(ShareViolation::StaleJob));
+        assert_eq!(validator.check(&share("1b", "aa", later), later).await, None);
+        validator.new_block(later + Duration::seconds(1)).await;
+        let after_block = later + Duration::seconds(2);
+        assert_eq!(validator.check(&share("1b", "ab", after_block), after_block).await, Some(ShareViolation::StaleJob));
+        assert_eq!(validator.check(&share("1c", "aa", after_block), after_block).await, None);
+
+        let workers = validator.violations(after_block).await;
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].counts, ViolationCounts { duplicate: 1, time_warp: 1, stale_job: 2 });
+    }
+
+    #[tokio::test]
+    async fn test_repeat_offenders_are_banned() {
+        let bans = Arc::new(MemoryBans::default());
+        let alerts = Arc::new(AlertManager::default());
+        let validator = ShareValidator::new(ShareValidationConfig { ban_threshold: 3, ..Default::default() })
+            .with_ban_store(bans.clone())
+            .with_alerts(alerts.clone());
+        let now = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+
+        assert_eq!(validator.check(&share("1a", "aa", now), now).await, None);
+        for _ in 0..3 {
+            assert_eq!(validator.check(&share("1a", "aa", now), now).await, Some(ShareViolation::Duplicate));
+        }
+        assert_eq!(*bans.0.lock().await, vec![("bc1qminer".to_string(), Some(now + Duration::days(1)))]);
+        assert_eq!(alerts.get_history(None).await.len(), 1);
+
+        // Valid shares are refused until the ban expires
+        assert_eq!(validator.check(&share("1a", "ff", now), now).await, Some(ShareViolation::Banned));
+        let tomorrow = now + Duration::days(1) + Duration::seconds(1);
+        assert_eq!(validator.check(&share("2a", "aa", tomorrow), tomorrow).await, None);
+
+        assert!(ShareValidationConfig { stale_job_secs: 7200, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,428 @@
+// Simulation Module for DMPool
+// End-to-end harness against a regtest bitcoind (feature `simulation`)
+//
+// Synthetic miners submit shares into an in-memory share source. Blocks are
+// mined with generatetoaddress and go through the block announcer, balance
+// crediting and automatic payouts like blocks found by the pool. The run then
+// checks the splits, balances and payouts against each other, against the
+// chain and against the Observer API.
+//
+// bitcoind needs a loaded wallet and `-minrelaytxfee=0`, since payouts are
+// built with a fixed fee estimate. With a database URL, blocks are also
+// recorded in Postgres and compared with `/api/v1/blocks/:height`.
+
+use anyhow::{Context, Result};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::{Address, CompressedPublicKey, Network};
+use chrono::Utc;
+use crate::backfill::ShareSource;
+use crate::bitcoin::BitcoinRpcClient;
+use crate::block_events::{BlockAnnouncement, BlockAnnouncer, BlockFoundEvent};
+use crate::db::DatabaseManager;
+use crate::observer_api::{self, ObserverState};
+use crate::payment::{PaymentConfig, PaymentManager, Payout, PayoutStatus};
+use crate::pplns_window::PplnsWindow;
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// Regtest coinbase outputs can be spent after this many blocks
+const COINBASE_MATURITY: u32 = 100;
+
+/// PPLNS window of the simulated pool
+const WINDOW_SECS: u64 = 86_400;
+
+/// Observer projections and block splits round differently
+const PROJECTION_TOLERANCE_SATOSHIS: u64 = 2;
+
+/// The Observer API needs a database; without one only routes that never
+/// query it are checked, so the pool is never connected
+const UNUSED_DATABASE_URL: &str = "postgres://127.0.0.1/dmpool_simulation_unused";
+
+/// Settings of a simulation run
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    /// RPC URL of a regtest bitcoind, e.g. `http://127.0.0.1:18443`
+    pub rpc_url: String,
+    pub rpc_user: String,
+    pub rpc_pass: String,
+    /// Record blocks in Postgres and check them through the Observer API
+    pub database_url: Option<String>,
+    pub miners: usize,
+    /// Shares each miner submits before every block
+    pub shares_per_round: usize,
+    pub blocks: usize,
+    pub pool_fee_bps: u32,
+    /// Payment state of the run
+    pub data_dir: PathBuf,
+}
+
+impl SimulationConfig {
+    /// Settings from `DMPOOL_SIM_RPC_URL`, `DMPOOL_SIM_RPC_USER`,
+    /// `DMPOOL_SIM_RPC_PASS` and `DMPOOL_SIM_DATABASE_URL`; None without an RPC URL
+    pub fn from_env(data_dir: PathBuf) -> Option<Self> {
+        let rpc_url = std::env::var("DMPOOL_SIM_RPC_URL").ok().filter(|url| !url.is_empty())?;
+        Some(Self {
+            rpc_url,
+            rpc_user: std::env::var("DMPOOL_SIM_RPC_USER").unwrap_or_else(|_| "bitcoin".to_string()),
+            rpc_pass: std::env::var("DMPOOL_SIM_RPC_PASS").unwrap_or_else(|_| "bitcoin".to_string()),
+            database_url: std::env::var("DMPOOL_SIM_DATABASE_URL").ok().filter(|url| !url.is_empty()),
+            miners: 5,
+            shares_per_round: 20,
+            blocks: 3,
+            pool_fee_bps: 100,
+            data_dir,
+        })
+    }
+}
+
+/// A miner with a key that is not in the node's wallet
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulatedMiner {
+    pub address: String,
+    pub worker: String,
+    /// Difficulty of each share; miners differ by hashrate
+    pub share_difficulty: u64,
+}
+
+impl SimulatedMiner {
+    pub fn new(index: usize) -> Self {
+        let secp = Secp256k1::new();
+        let secret = SecretKey::new(&mut rand::thread_rng());
+        let key = CompressedPublicKey(PublicKey::from_secret_key(&secp, &secret));
+        Self {
+            address: Address::p2wpkh(&key, Network::Regtest).to_string(),
+            worker: format!("sim{}", index),
+            share_difficulty: 1000 * (index as u64 + 1),
+        }
+    }
+}
+
+/// Shares submitted by the simulated miners
+#[derive(Default)]
+pub struct SyntheticShares {
+    shares: RwLock<Vec<SimplePplnsShare>>,
+}
+
+impl SyntheticShares {
+    /// Submit `count` shares for a miner at `n_time`
+    pub fn submit(&self, miner: &SimulatedMiner, count: usize, n_time: u64) {
+        let mut shares = self.shares.write().unwrap_or_else(|e| e.into_inner());
+        let first = shares.len();
+        shares.extend((first..first + count).map(|n| SimplePplnsShare {
+            btcaddress: Some(miner.address.clone()),
+            workername: Some(miner.worker.clone()),
+            user_id: 1,
+            difficulty: miner.share_difficulty,
+            n_time,
+            job_id: format!("sim-{}", n),
+            extranonce2: format!("{:08x}", n),
+            nonce: "00000000".to_string(),
+        }));
+    }
+}
+
+impl ShareSource for SyntheticShares {
+    fn fetch_shares(&self, start: u64, end: u64, limit: usize) -> Vec<SimplePplnsShare> {
+        self.shares.read().unwrap_or_else(|e| e.into_inner()).iter()
+            .filter(|share| share.n_time >= start && share.n_time <= end)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Outcome of a simulation run
+#[derive(Debug, Default, Serialize)]
+pub struct SimulationReport {
+    pub miners: Vec<SimulatedMiner>,
+    pub blocks: Vec<BlockAnnouncement>,
+    pub payouts: Vec<Payout>,
+    /// Inconsistencies found; empty when the run passed
+    pub mismatches: Vec<String>,
+}
+
+impl SimulationReport {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn mismatch(&mut self, message: String) {
+        tracing::warn!("Simulation mismatch: {}", message);
+        self.mismatches.push(message);
+    }
+}
+
+/// A simulated pool on a regtest node
+pub struct Simulation {
+    config: SimulationConfig,
+    rpc: BitcoinRpcClient,
+    shares: Arc<SyntheticShares>,
+    window: Arc<PplnsWindow>,
+    payments: Arc<PaymentManager>,
+    db: Option<Arc<DatabaseManager>>,
+    miners: Vec<SimulatedMiner>,
+}
+
+impl Simulation {
+    /// Connect to bitcoind (and Postgres) and set up the pool's components
+    pub async fn new(config: SimulationConfig) -> Result<Self> {
+        let rpc = BitcoinRpcClient::new(config.rpc_url.clone(), config.rpc_user.clone(), config.rpc_pass.clone());
+        let chain = rpc.get_blockchain_info().await.context("Failed to reach bitcoind")?.chain;
+        if chain != "regtest" {
+            return Err(anyhow::anyhow!("Simulations only run on regtest, bitcoind is on {}", chain));
+        }
+
+        let payments = PaymentManager::new(config.data_dir.join("payments"), PaymentConfig {
+            min_payout_satoshis: 1,
+            required_confirmations: 1,
+            pool_fee_bps: config.pool_fee_bps,
+            auto_payout_enabled: true,
+            bitcoin_rpc_url: config.rpc_url.clone(),
+            bitcoin_rpc_user: config.rpc_user.clone(),
+            bitcoin_rpc_pass: config.rpc_pass.clone(),
+            ..PaymentConfig::for_network(Network::Regtest)
+        })?;
+        let db = match &config.database_url {
+            Some(url) => {
+                let db = Arc::new(DatabaseManager::new(url)?);
+                db.init_admin_tables().await.context("Failed to migrate simulation database")?;
+                Some(db)
+            }
+            None => None,
+        };
+        let shares = Arc::new(SyntheticShares::default());
+        let window = Arc::new(PplnsWindow::new(shares.clone(), WINDOW_SECS, config.pool_fee_bps));
+        let miners = (0..config.miners).map(SimulatedMiner::new).collect();
+
+        Ok(Self { config, rpc, shares, window, payments: Arc::new(payments), db, miners })
+    }
+
+    /// Mine the configured blocks, pay the miners and check the results
+    pub async fn run(&self) -> Result<SimulationReport> {
+        let mut report = SimulationReport { miners: self.miners.clone(), ..SimulationReport::default() };
+        let observer = self.start_observer().await?;
+        let http = reqwest::Client::new();
+
+        // Mature coinbase outputs to fund one payout per miner
+        let pool_address = self.rpc.get_new_address().await?;
+        self.rpc.generate_to_address(COINBASE_MATURITY + self.miners.len() as u32, &pool_address).await
+            .context("Failed to fund the pool wallet")?;
+
+        let mut announcer = BlockAnnouncer::new(self.window.clone()).with_payments(self.payments.clone());
+        if let Some(db) = &self.db {
+            announcer = announcer.with_recorder(db.clone());
+        }
+
+        for _ in 0..self.config.blocks {
+            let now = Utc::now().timestamp() as u64;
+            for miner in &self.miners {
+                self.shares.submit(miner, self.config.shares_per_round, now);
+            }
+            let (_, template_reward) = self.rpc.get_template_reward().await?;
+            self.window.set_block_reward(template_reward);
+            self.window.fresh_snapshot().await?;
+            let projected = self.projected_payouts(&http, &observer).await?;
+
+            let block_hash = self.rpc.generate_to_address(1, &pool_address).await?
+                .pop()
+                .ok_or_else(|| anyhow::anyhow!("generatetoaddress returned no block"))?;
+            let coinbase = self.rpc.get_block_coinbase(&block_hash).await?;
+            let event = BlockFoundEvent {
+                height: self.rpc.get_block_count().await?,
+                block_hash,
+                found_at: Utc::now(),
+                reward_satoshis: coinbase.total_output_satoshis,
+                coinbase_txid: Some(coinbase.txid),
+                finder_address: self.miners.first().map(|miner| miner.address.clone()),
+                finder_worker: self.miners.first().map(|miner| miner.worker.clone()),
+                network_difficulty: Some(coinbase.difficulty),
+                coinbase_outputs: coinbase.outputs,
+            };
+            let height = event.height;
+            let announcement = announcer.announce(event).await?
+                .ok_or_else(|| anyhow::anyhow!("Block {} was not announced", height))?;
+            info!("Simulated block {} split among {} miners", height, announcement.payouts.len());
+
+            check_split(&announcement, &projected, &mut report);
+            if self.db.is_some() {
+                self.check_observer_block(&http, &observer, &announcement, &mut report).await?;
+            }
+            report.blocks.push(announcement);
+        }
+
+        report.payouts = self.pay_and_confirm(&pool_address).await?;
+        self.check_balances(&mut report).await?;
+        Ok(report)
+    }
+
+    /// Serve the Observer API on a free local port, returning its base URL
+    async fn start_observer(&self) -> Result<String> {
+        let db = match &self.db {
+            Some(db) => db.clone(),
+            None => Arc::new(DatabaseManager::new(UNUSED_DATABASE_URL)?),
+        };
+        let state = ObserverState::new(db)
+            .with_pplns_window(self.window.clone())
+            .with_network(Network::Regtest);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await
+            .context("Failed to bind the simulation Observer API")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let app = observer_api::create_router_with_state(state);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Simulation Observer API stopped: {}", e);
+            }
+        });
+        Ok(url)
+    }
+
+    /// Each miner's projected payout for the next block from the Observer API
+    async fn projected_payouts(&self, http: &reqwest::Client, observer: &str) -> Result<HashMap<String, u64>> {
+        let mut projected = HashMap::new();
+        for miner in &self.miners {
+            let contribution: serde_json::Value = http
+                .get(format!("{}/api/v1/stats/{}/pplns?units=raw", observer, miner.address))
+                .send().await?
+                .error_for_status()?
+                .json().await?;
+            let payout = contribution["projected_payout_satoshis"].as_u64()
+                .ok_or_else(|| anyhow::anyhow!("No projected payout for {}", miner.address))?;
+            projected.insert(miner.address.clone(), payout);
+        }
+        Ok(projected)
+    }
+
+    /// Compare a recorded block with its announcement
+    async fn check_observer_block(
+        &self,
+        http: &reqwest::Client,
+        observer: &str,
+        announcement: &BlockAnnouncement,
+        report: &mut SimulationReport,
+    ) -> Result<()> {
+        let height = announcement.event.height;
+        let detail: serde_json::Value = http
+            .get(format!("{}/api/v1/blocks/{}?units=raw", observer, height))
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        let observed: BTreeSet<&str> = detail["payouts"].as_array().into_iter().flatten()
+            .filter_map(|payout| payout["address"].as_str())
+            .collect();
+        let announced: BTreeSet<&str> = announcement.payouts.iter().map(|p| p.address.as_str()).collect();
+        if observed != announced {
+            report.mismatch(format!("Block {}: Observer API lists payouts to {:?}, announced {:?}", height, observed, announced));
+        }
+        if detail["pplns_window_shares"].as_u64() != Some(announcement.window_shares) {
+            report.mismatch(format!(
+                "Block {}: Observer API window shares {} != announced {}",
+                height, detail["pplns_window_shares"], announcement.window_shares
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run automatic payouts, mine them and confirm them
+    async fn pay_and_confirm(&self, pool_address: &str) -> Result<Vec<Payout>> {
+        let created = self.payments.process_auto_payouts().await?;
+        self.rpc.generate_to_address(1, pool_address).await?;
+        let height = self.rpc.get_block_count().await?;
+
+        let mut payouts = Vec::new();
+        for payout in created {
+            let id = payout.id.clone();
+            let payout = self.payments.get_payout(&id).await?.unwrap_or(payout);
+            if let Some(txid) = payout.txid.clone() {
+                let confirmations = self.rpc.get_wallet_transaction_confirmations(&txid).await?;
+                self.payments.confirm_payout(&id, txid, height, confirmations.max(0) as u32).await?;
+            }
+            payouts.push(self.payments.get_payout(&id).await?.unwrap_or(payout));
+        }
+        Ok(payouts)
+    }
+
+    /// Balances must add up to the block splits and the chain must hold the payouts
+    async fn check_balances(&self, report: &mut SimulationReport) -> Result<()> {
+        for miner in &self.miners {
+            let earned: u64 = report.blocks.iter()
+                .flat_map(|block| &block.payouts)
+                .filter(|payout| payout.address == miner.address && !payout.in_coinbase)
+                .map(|payout| payout.amount_satoshis)
+                .sum();
+            let Some(balance) = self.payments.get_balance(&miner.address).await else {
+                report.mismatch(format!("{}: no balance after earning {} sats", miner.address, earned));
+                continue;
+            };
+            if balance.total_earned_satoshis != earned {
+                report.mismatch(format!("{}: earned {} sats, blocks credited {}", miner.address, balance.total_earned_satoshis, earned));
+            }
+
+            let paid: u64 = report.payouts.iter()
+                .filter(|payout| payout.address == miner.address && payout.status == PayoutStatus::Confirmed)
+                .map(|payout| payout.amount_satoshis)
+                .sum();
+            if paid != earned || balance.total_paid_satoshis != paid || balance.balance_satoshis != 0 {
+                report.mismatch(format!(
+                    "{}: earned {} sats, confirmed payouts {}, total paid {}, balance left {}",
+                    miner.address, earned, paid, balance.total_paid_satoshis, balance.balance_satoshis
+                ));
+            }
+
+            let on_chain = (self.rpc.scan_address_balance(&miner.address).await? * 100_000_000.0).round() as u64;
+            if on_chain != paid {
+                report.mismatch(format!("{}: {} sats on chain, {} paid", miner.address, on_chain, paid));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A block's split must use the whole reward and match the projections
+fn check_split(announcement: &BlockAnnouncement, projected: &HashMap<String, u64>, report: &mut SimulationReport) {
+    let height = announcement.event.height;
+    if !announcement.errors.is_empty() {
+        report.mismatch(format!("Block {}: {:?}", height, announcement.errors));
+    }
+    let paid: u64 = announcement.payouts.iter().map(|payout| payout.amount_satoshis).sum();
+    if paid + announcement.pool_fee_satoshis != announcement.event.reward_satoshis {
+        report.mismatch(format!(
+            "Block {}: {} paid + {} pool fee != {} reward",
+            height, paid, announcement.pool_fee_satoshis, announcement.event.reward_satoshis
+        ));
+    }
+    for payout in &announcement.payouts {
+        let expected = projected.get(&payout.address).copied().unwrap_or(0);
+        if payout.amount_satoshis.abs_diff(expected) > PROJECTION_TOLERANCE_SATOSHIS {
+            report.mismatch(format!(
+                "Block {}: {} paid {} sats, Observer API projected {}",
+                height, payout.address, payout.amount_satoshis, expected
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_shares_in_window() {
+        let miners: Vec<SimulatedMiner> = (0..2).map(SimulatedMiner::new).collect();
+        assert!(miners[0].address.starts_with("bcrt1q"));
+        assert_ne!(miners[0].address, miners[1].address);
+        assert_eq!(miners[1].share_difficulty, 2000);
+
+        let shares = SyntheticShares::default();
+        shares.submit(&miners[0], 3, 100);
+        shares.submit(&miners[1], 2, 200);
+        assert_eq!(shares.fetch_shares(0, 150, 10).len(), 3);
+        assert_eq!(shares.fetch_shares(0, 300, 4).len(), 4);
+        let later = shares.fetch_shares(150, 300, 10);
+        assert_eq!(later.len(), 2);
+        assert_eq!(later[0].job_id, "sim-3");
+    }
+}
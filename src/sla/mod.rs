@@ -0,0 +1,413 @@
+// Stratum SLA Module for DMPool
+// Daily acceptance ratios, stratum response times and downtime windows
+//
+// Accepted and rejected share counts come from the stratum stats samples.
+// A probe sends `mining.subscribe` to the stratum port on an interval and
+// times the reply; consecutive failed probes form a downtime window. Totals
+// are kept per UTC day in `sla/daily.json` so operators can compare periods
+// and publish daily or weekly SLA numbers to miners.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// The `[dmpool.sla]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlaConfig {
+    pub enabled: bool,
+    /// Seconds between stratum probes
+    pub probe_interval_secs: u64,
+    /// A probe without a reply within this counts as down
+    pub probe_timeout_ms: u64,
+    /// Days of history kept
+    pub retention_days: u32,
+}
+
+impl Default for SlaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probe_interval_secs: 60,
+            probe_timeout_ms: 5000,
+            retention_days: 400,
+        }
+    }
+}
+
+impl SlaConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.probe_interval_secs < 5 {
+            return Err(anyhow::anyhow!("probe_interval_secs must be at least 5"));
+        }
+        if self.probe_timeout_ms == 0 || self.probe_timeout_ms >= self.probe_interval_secs * 1000 {
+            return Err(anyhow::anyhow!("probe_timeout_ms must be between 1 and the probe interval"));
+        }
+        if self.retention_days < 7 {
+            return Err(anyhow::anyhow!("retention_days must be at least 7"));
+        }
+        Ok(())
+    }
+}
+
+/// A period the stratum port did not answer probes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DowntimeWindow {
+    pub start: DateTime<Utc>,
+    /// None while the outage is ongoing
+    pub end: Option<DateTime<Utc>>,
+    /// Error from the first failed probe
+    pub error: String,
+}
+
+impl DowntimeWindow {
+    /// Length of the window, up to `now` if ongoing
+    pub fn seconds(&self, now: DateTime<Utc>) -> i64 {
+        (self.end.unwrap_or(now) - self.start).num_seconds().max(0)
+    }
+}
+
+/// Totals for one UTC day
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DailySla {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub probes: u64,
+    pub probes_failed: u64,
+    pub response_ms_total: u64,
+    pub response_ms_max: u64,
+    /// Windows clipped to the day
+    pub downtime: Vec<DowntimeWindow>,
+}
+
+/// Report bucket size
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlaPeriod {
+    #[default]
+    Day,
+    /// ISO weeks, Monday to Sunday
+    Week,
+}
+
+impl SlaPeriod {
+    /// First day of the period containing `date`
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+}
+
+/// SLA figures for one day or week
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SlaReport {
+    pub period_start: NaiveDate,
+    /// Last day of the period (inclusive)
+    pub period_end: NaiveDate,
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Accepted as a percentage of submitted shares; None without shares
+    pub acceptance_percent: Option<f64>,
+    pub probes: u64,
+    pub probes_failed: u64,
+    /// Answered probes as a percentage of all probes; None without probes
+    pub availability_percent: Option<f64>,
+    pub avg_response_ms: Option<f64>,
+    pub max_response_ms: Option<u64>,
+    pub downtime_secs: i64,
+    pub downtime: Vec<DowntimeWindow>,
+}
+
+impl SlaReport {
+    fn new(period: SlaPeriod, start: NaiveDate) -> Self {
+        let days = match period {
+            SlaPeriod::Day => 0,
+            SlaPeriod::Week => 6,
+        };
+        Self {
+            period_start: start,
+            period_end: start + Duration::days(days),
+            accepted: 0,
+            rejected: 0,
+            acceptance_percent: None,
+            probes: 0,
+            probes_failed: 0,
+            availability_percent: None,
+            avg_response_ms: None,
+            max_response_ms: None,
+            downtime_secs: 0,
+            downtime: Vec::new(),
+        }
+    }
+}
+
+/// Records stratum share acceptance, response times and downtime per day
+pub struct SlaTracker {
+    config: SlaConfig,
+    data_dir: PathBuf,
+    days: RwLock<BTreeMap<NaiveDate, DailySla>>,
+}
+
+impl SlaTracker {
+    pub fn new(data_dir: PathBuf, config: SlaConfig) -> Result<Self> {
+        std::fs::create_dir_all(&data_dir)
+            .context("Failed to create SLA data directory")?;
+        Ok(Self {
+            config,
+            data_dir,
+            days: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &SlaConfig {
+        &self.config
+    }
+
+    /// Load daily history from disk
+    pub async fn load(&self) -> Result<()> {
+        let path = self.data_dir.join("daily.json");
+        let Some(days) = persist::read_json::<BTreeMap<NaiveDate, DailySla>>(&path).await
+            .context("Failed to load SLA history")?
+        else {
+            return Ok(());
+        };
+        info!("Loaded {} days of SLA history", days.len());
+        *self.days.write().await = days;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        let cutoff = Utc::now().date_naive() - Duration::days(self.config.retention_days as i64);
+        let mut days = self.days.write().await;
+        days.retain(|date, _| *date >= cutoff);
+        persist::write_json(&self.data_dir.join("daily.json"), &*days).await
+            .context("Failed to write SLA history")
+    }
+
+    /// Count shares accepted and rejected since the previous sample
+    pub async fn record_shares(&self, at: DateTime<Utc>, accepted: u64, rejected: u64) {
+        let mut days = self.days.write().await;
+        let day = days.entry(at.date_naive()).or_default();
+        day.accepted += accepted;
+        day.rejected += rejected;
+    }
+
+    /// Record a probe: the reply time, or why there was no reply
+    ///
+    /// The first failure opens a downtime window and the next answered
+    /// probe closes it, splitting it at midnight when it spans days.
+    pub async fn record_probe(&self, at: DateTime<Utc>, result: std::result::Result<std::time::Duration, String>) {
+        let mut days = self.days.write().await;
+        let open = Self::open_outage(&days);
+        let today = days.entry(at.date_naive()).or_default();
+        today.probes += 1;
+
+        match result {
+            Ok(response) => {
+                let ms = response.as_millis() as u64;
+                today.response_ms_total += ms;
+                today.response_ms_max = today.response_ms_max.max(ms);
+                if let Some((date, index)) = open {
+                    Self::close_outage(&mut days, date, index, at);
+                }
+            }
+            Err(error) => {
+                today.probes_failed += 1;
+                if open.is_none() {
+                    warn!("Stratum probe failed, downtime started: {}", error);
+                    today.downtime.push(DowntimeWindow { start: at, end: None, error });
+                }
+            }
+        }
+    }
+
+    /// Day and index of the ongoing downtime window, if any
+    fn open_outage(days: &BTreeMap<NaiveDate, DailySla>) -> Option<(NaiveDate, usize)> {
+        days.iter().rev()
+            .find_map(|(date, day)| day.downtime.iter().position(|w| w.end.is_none()).map(|index| (*date, index)))
+    }
+
+    fn close_outage(days: &mut BTreeMap<NaiveDate, DailySla>, date: NaiveDate, index: usize, end: DateTime<Utc>) {
+        let Some(window) = days.get_mut(&date).map(|day| &mut day.downtime[index]) else {
+            return;
+        };
+        let error = window.error.clone();
+        let mut start_date = date;
+        let midnight = |date: NaiveDate| (date + Duration::days(1)).and_time(NaiveTime::MIN).and_utc();
+        if end <= midnight(start_date) {
+            window.end = Some(end);
+        } else {
+            window.end = Some(midnight(start_date));
+            // Carry the rest into each following day
+            loop {
+                start_date += Duration::days(1);
+                let window_end = end.min(midnight(start_date));
+                days.entry(start_date).or_default().downtime.push(DowntimeWindow {
+                    start: start_date.and_time(NaiveTime::MIN).and_utc(),
+                    end: Some(window_end),
+                    error: error.clone(),
+                });
+                if window_end == end {
+                    break;
+                }
+            }
+        }
+        info!("Stratum answering probes again after {}s of downtime", (end - days[&date].downtime[index].start).num_seconds());
+    }
+
+    /// Day or week reports covering `from` to `to` (inclusive), oldest first
+    pub async fn report(&self, period: SlaPeriod, from: NaiveDate, to: NaiveDate) -> Vec<SlaReport> {
+        let now = Utc::now();
+        let days = self.days.read().await;
+        let mut reports: BTreeMap<NaiveDate, SlaReport> = BTreeMap::new();
+        let mut response_totals: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+
+        for (date, day) in days.range(from..=to) {
+            let start = period.start_of(*date);
+            let report = reports.entry(start).or_insert_with(|| SlaReport::new(period, start));
+            report.accepted += day.accepted;
+            report.rejected += day.rejected;
+            report.probes += day.probes;
+            report.probes_failed += day.probes_failed;
+            *response_totals.entry(start).or_default() += day.response_ms_total;
+            if day.probes > day.probes_failed {
+                report.max_response_ms = Some(report.max_response_ms.unwrap_or(0).max(day.response_ms_max));
+            }
+            for window in &day.downtime {
+                report.downtime_secs += window.seconds(now);
+                report.downtime.push(window.clone());
+            }
+        }
+
+        reports.into_iter()
+            .map(|(start, mut report)| {
+                let submitted = report.accepted + report.rejected;
+                if submitted > 0 {
+                    report.acceptance_percent = Some(report.accepted as f64 / submitted as f64 * 100.0);
+                }
+                let answered = report.probes - report.probes_failed;
+                if report.probes > 0 {
+                    report.availability_percent = Some(answered as f64 / report.probes as f64 * 100.0);
+                }
+                if answered > 0 {
+                    report.avg_response_ms = Some(response_totals[&start] as f64 / answered as f64);
+                }
+                report
+            })
+            .collect()
+    }
+
+    /// Probe the stratum port on the configured interval
+    pub fn spawn(self: Arc<Self>, stratum_address: String) {
+        tokio::spawn(async move {
+            let timeout = std::time::Duration::from_millis(self.config.probe_timeout_ms);
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.config.probe_interval_secs));
+            loop {
+                interval.tick().await;
+                let result = probe_stratum(&stratum_address, timeout).await.map_err(|e| format!("{:#}", e));
+                self.record_probe(Utc::now(), result).await;
+                if let Err(e) = self.save().await {
+                    warn!("Failed to save SLA history: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Time a `mining.subscribe` round trip to a stratum server
+pub async fn probe_stratum(address: &str, timeout: std::time::Duration) -> Result<std::time::Duration> {
+    let start = std::time::Instant::now();
+    let reply = tokio::time::timeout(timeout, async {
+        let mut stream = TcpStream::connect(address).await
+            .with_context(|| format!("Failed to connect to {}", address))?;
+        stream.write_all(b"{\"id\":1,\"method\":\"mining.subscribe\",\"params\":[\"dmpool-sla\"]}\n").await
+            .context("Failed to send mining.subscribe")?;
+        let mut line = String::new();
+        let read = BufReader::new(stream).read_line(&mut line).await
+            .context("Failed to read the subscribe reply")?;
+        if read == 0 {
+            return Err(anyhow::anyhow!("Connection closed without a reply"));
+        }
+        Ok(())
+    }).await;
+    match reply {
+        Ok(result) => result.map(|_| start.elapsed()),
+        Err(_) => Err(anyhow::anyhow!("No reply within {}ms", timeout.as_millis())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // 2026-03-02 is a Monday
+        Utc.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn ms(ms: u64) -> std::result::Result<std::time::Duration, String> {
+        Ok(std::time::Duration::from_millis(ms))
+    }
+
+    #[tokio::test]
+    async fn test_daily_and_weekly_reports() {
+        let temp_dir = TempDir::new().unwrap();
+        let sla = SlaTracker::new(temp_dir.path().to_path_buf(), SlaConfig::default()).unwrap();
+
+        sla.record_shares(at(2, 1), 990, 10).await;
+        sla.record_probe(at(2, 1), ms(20)).await;
+        sla.record_probe(at(2, 2), ms(40)).await;
+        sla.record_shares(at(3, 1), 500, 500).await;
+        sla.record_probe(at(3, 1), Err("refused".to_string())).await;
+        sla.record_probe(at(3, 2), Err("refused".to_string())).await;
+        sla.record_probe(at(3, 4), ms(30)).await;
+
+        let daily = sla.report(SlaPeriod::Day, at(2, 0).date_naive(), at(8, 0).date_naive()).await;
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].acceptance_percent, Some(99.0));
+        assert_eq!(daily[0].avg_response_ms, Some(30.0));
+        assert_eq!(daily[1].probes_failed, 2);
+        assert_eq!(daily[1].downtime.len(), 1);
+        assert_eq!(daily[1].downtime_secs, 3 * 3600);
+
+        let weekly = sla.report(SlaPeriod::Week, at(2, 0).date_naive(), at(8, 0).date_naive()).await;
+        assert_eq!(weekly.len(), 1);
+        assert_eq!(weekly[0].period_end, at(8, 0).date_naive());
+        assert_eq!(weekly[0].acceptance_percent, Some(1490.0 / 2000.0 * 100.0));
+        assert_eq!(weekly[0].availability_percent, Some(60.0));
+        assert_eq!(weekly[0].max_response_ms, Some(40));
+
+        // History survives a restart
+        sla.save().await.unwrap();
+        let reloaded = SlaTracker::new(temp_dir.path().to_path_buf(), SlaConfig::default()).unwrap();
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.report(SlaPeriod::Week, at(2, 0).date_naive(), at(8, 0).date_naive()).await[0].probes, 5);
+    }
+
+    #[tokio::test]
+    async fn test_outage_split_at_midnight() {
+        let temp_dir = TempDir::new().unwrap();
+        let sla = SlaTracker::new(temp_dir.path().to_path_buf(), SlaConfig::default()).unwrap();
+
+        sla.record_probe(at(2, 22), Err("timeout".to_string())).await;
+        sla.record_probe(at(3, 3), ms(10)).await;
+
+        let daily = sla.report(SlaPeriod::Day, at(2, 0).date_naive(), at(3, 0).date_naive()).await;
+        assert_eq!(daily[0].downtime_secs, 2 * 3600);
+        assert_eq!(daily[1].downtime_secs, 3 * 3600);
+        assert_eq!(daily[1].downtime[0].error, "timeout");
+        assert!(daily.iter().all(|d| d.downtime.iter().all(|w| w.end.is_some())));
+    }
+}
@@ -0,0 +1,339 @@
+// Storage Module for DMPool
+// Pluggable backends for the state of the file-persisting managers
+//
+// Managers read and write named blobs (`balances.json`, `audit.jsonl`, ...)
+// through a `BlobStore` instead of touching the filesystem. The file backend
+// keeps the existing on-disk layout and the crash-safe writes of `persist`;
+// the Postgres backend keeps blobs in `dmpool_blobs` so several instances can
+// share state; the memory backend is for tests and throwaway runs.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use crate::db::DatabaseManager;
+use crate::persist;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+
+mod postgres;
+pub use postgres::PostgresStore;
+
+/// Named blobs of one manager
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Backend name for logs
+    fn backend(&self) -> &'static str;
+
+    /// Contents of `key`, `None` if it was never written
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Replace `key` atomically
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Add to the end of `key`, creating it if needed (append-only logs)
+    async fn append(&self, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Remove `key`; a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Every key, sorted
+    async fn list(&self) -> Result<Vec<String>>;
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.get(key).await?.is_some())
+    }
+
+    /// Move `from` to `to`, replacing `to`
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let value = self.get(from).await?
+            .ok_or_else(|| anyhow::anyhow!("{} does not exist", from))?;
+        self.put(to, &value).await?;
+        self.delete(from).await
+    }
+
+    /// File backing `key`, for the file backend
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+impl dyn BlobStore {
+    /// Read and parse a JSON blob
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.get(key).await? {
+            Some(contents) => serde_json::from_slice(&contents)
+                .map(Some)
+                .with_context(|| format!("Failed to parse {}", key)),
+            None => Ok(None),
+        }
+    }
+
+    /// Serialize `value` as pretty JSON and store it
+    pub async fn put_json<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<()> {
+        let json = serde_json::to_vec_pretty(value)
+            .with_context(|| format!("Failed to serialize {}", key))?;
+        self.put(key, &json).await
+    }
+}
+
+/// Files written next to a state file by `persist`
+const SIDECAR_SUFFIXES: &[&str] = &[".sha256", ".bak", ".tmp", ".corrupt", ".rotating"];
+
+/// One file per key in a directory, written with `persist`
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    async fn create_root(&self) -> Result<()> {
+        fs::create_dir_all(&self.root).await
+            .with_context(|| format!("Failed to create {}", self.root.display()))
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl BlobStore for FileStore {
+    fn backend(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        persist::read_verified(&self.path(key)).await
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.create_root().await?;
+        persist::write_atomic(&self.path(key), value).await
+    }
+
+    async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.create_root().await?;
+        let path = self.path(key);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        file.write_all(value).await
+            .with_context(|| format!("Failed to append to {}", path.display()))?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key);
+        let backup = persist::backup_path(&path);
+        for file in [persist::checksum_path(&path), persist::checksum_path(&backup), backup, path] {
+            remove_if_exists(&file).await?;
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let mut entries = match fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.root.display())),
+        };
+        let mut keys = Vec::new();
+        while let Some(entry) = entries.next_entry().await
+            .with_context(|| format!("Failed to read {}", self.root.display()))?
+        {
+            if !entry.file_type().await?.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !SIDECAR_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+                keys.push(name);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let path = self.path(key);
+        Ok(path.exists() || persist::backup_path(&path).exists())
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.delete(to).await?;
+        let (from_path, to_path) = (self.path(from), self.path(to));
+        fs::rename(&from_path, &to_path).await
+            .with_context(|| format!("Failed to rename {} to {}", from_path.display(), to_path.display()))?;
+        if persist::checksum_path(&from_path).exists() {
+            fs::rename(persist::checksum_path(&from_path), persist::checksum_path(&to_path)).await?;
+        }
+        self.delete(from).await
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        Some(self.path(key))
+    }
+}
+
+/// Blobs kept in memory, lost on restart
+#[derive(Default)]
+pub struct MemoryStore {
+    blobs: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+#[async_trait]
+impl BlobStore for MemoryStore {
+    fn backend(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.blobs.read().await.get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.blobs.write().await.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.blobs.write().await.entry(key.to_string()).or_default().extend_from_slice(value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.blobs.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        Ok(self.blobs.read().await.keys().cloned().collect())
+    }
+}
+
+/// Where manager state is kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Files under the data directory
+    #[default]
+    File,
+    /// The `dmpool_blobs` table, shared by every instance using the database
+    Postgres,
+    /// Process memory only
+    Memory,
+}
+
+impl std::str::FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "postgres" => Ok(Self::Postgres),
+            "memory" => Ok(Self::Memory),
+            other => Err(anyhow::anyhow!("Unknown storage backend: {} (expected file, postgres or memory)", other)),
+        }
+    }
+}
+
+/// `[dmpool.storage]` settings
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+}
+
+impl StorageConfig {
+    /// Store for the manager that keeps its files in `dir`
+    ///
+    /// `namespace` keeps managers apart in the shared Postgres table; the
+    /// Postgres backend needs `db`.
+    pub fn open(&self, namespace: &str, dir: &Path, db: Option<&Arc<DatabaseManager>>) -> Result<Arc<dyn BlobStore>> {
+        Ok(match self.backend {
+            StorageBackend::File => Arc::new(FileStore::new(dir)),
+            StorageBackend::Postgres => {
+                let db = db.ok_or_else(|| anyhow::anyhow!("The postgres storage backend needs a database"))?;
+                Arc::new(PostgresStore::new(db.clone(), namespace))
+            }
+            StorageBackend::Memory => Arc::new(MemoryStore::default()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn exercise(store: &dyn BlobStore) {
+        assert_eq!(store.get("a.json").await.unwrap(), None);
+        store.put("a.json", b"[1]").await.unwrap();
+        store.put("a.json", b"[2]").await.unwrap();
+        store.append("log.jsonl", b"one\n").await.unwrap();
+        store.append("log.jsonl", b"two\n").await.unwrap();
+        assert_eq!(store.get("a.json").await.unwrap(), Some(b"[2]".to_vec()));
+        assert_eq!(store.get("log.jsonl").await.unwrap(), Some(b"one\ntwo\n".to_vec()));
+        assert_eq!(store.list().await.unwrap(), vec!["a.json", "log.jsonl"]);
+
+        store.rename("log.jsonl", "log_1.jsonl").await.unwrap();
+        store.delete("a.json").await.unwrap();
+        store.delete("missing.json").await.unwrap();
+        assert!(!store.exists("a.json").await.unwrap());
+        assert_eq!(store.list().await.unwrap(), vec!["log_1.jsonl"]);
+        assert_eq!(store.get("log_1.jsonl").await.unwrap(), Some(b"one\ntwo\n".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_file_and_memory_stores_agree() {
+        let dir = TempDir::new().unwrap();
+        let files = FileStore::new(dir.path().join("state"));
+        exercise(&files).await;
+        exercise(&MemoryStore::default()).await;
+
+        // Sidecars of deleted keys are gone too
+        let names: Vec<_> = std::fs::read_dir(files.root()).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names, vec!["log_1.jsonl"]);
+    }
+
+    #[tokio::test]
+    async fn test_open_selects_backend() {
+        let dir = TempDir::new().unwrap();
+        let file = StorageConfig::default().open("payments", dir.path(), None).unwrap();
+        assert_eq!(file.local_path("balances.json"), Some(dir.path().join("balances.json")));
+
+        let memory = StorageConfig { backend: "memory".parse().unwrap() }.open("payments", dir.path(), None).unwrap();
+        assert_eq!(memory.backend(), "memory");
+        memory.put_json("balances.json", &vec![1]).await.unwrap();
+        assert_eq!(memory.get_json::<Vec<u32>>("balances.json").await.unwrap(), Some(vec![1]));
+
+        let postgres = StorageConfig { backend: StorageBackend::Postgres };
+        assert!(postgres.open("payments", dir.path(), None).is_err());
+    }
+}
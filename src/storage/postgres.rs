@@ -0,0 +1,128 @@
+// Postgres blob store
+//
+// Blobs live in `dmpool_blobs` (migration 013), one row per namespace and
+// key. Appends concatenate in the database, so concurrent writers from
+// several instances never lose each other's lines.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::BlobStore;
+use crate::db::DatabaseManager;
+
+/// Blobs of one namespace in the `dmpool_blobs` table
+pub struct PostgresStore {
+    db: Arc<DatabaseManager>,
+    namespace: String,
+}
+
+impl PostgresStore {
+    pub fn new(db: Arc<DatabaseManager>, namespace: impl Into<String>) -> Self {
+        Self { db, namespace: namespace.into() }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+}
+
+#[async_trait]
+impl BlobStore for PostgresStore {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let conn = self.db.get_conn().await?;
+        let row = conn
+            .query_opt(
+                "SELECT value FROM dmpool_blobs WHERE namespace = $1 AND key = $2",
+                &[&self.namespace, &key],
+            )
+            .await
+            .with_context(|| format!("Failed to read {}/{}", self.namespace, key))?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        let conn = self.db.get_conn().await?;
+        conn.execute(
+            "INSERT INTO dmpool_blobs (namespace, key, value, updated_at) VALUES ($1, $2, $3, NOW())
+             ON CONFLICT (namespace, key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()",
+            &[&self.namespace, &key, &value],
+        )
+        .await
+        .with_context(|| format!("Failed to write {}/{}", self.namespace, key))?;
+        Ok(())
+    }
+
+    async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        let conn = self.db.get_conn().await?;
+        conn.execute(
+            "INSERT INTO dmpool_blobs (namespace, key, value, updated_at) VALUES ($1, $2, $3, NOW())
+             ON CONFLICT (namespace, key) DO UPDATE SET value = dmpool_blobs.value || EXCLUDED.value, updated_at = NOW()",
+            &[&self.namespace, &key, &value],
+        )
+        .await
+        .with_context(|| format!("Failed to append to {}/{}", self.namespace, key))?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let conn = self.db.get_conn().await?;
+        conn.execute(
+            "DELETE FROM dmpool_blobs WHERE namespace = $1 AND key = $2",
+            &[&self.namespace, &key],
+        )
+        .await
+        .with_context(|| format!("Failed to delete {}/{}", self.namespace, key))?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>> {
+        let conn = self.db.get_conn().await?;
+        let rows = conn
+            .query(
+                "SELECT key FROM dmpool_blobs WHERE namespace = $1 ORDER BY key",
+                &[&self.namespace],
+            )
+            .await
+            .with_context(|| format!("Failed to list {}", self.namespace))?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let conn = self.db.get_conn().await?;
+        let row = conn
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM dmpool_blobs WHERE namespace = $1 AND key = $2)",
+                &[&self.namespace, &key],
+            )
+            .await
+            .with_context(|| format!("Failed to check {}/{}", self.namespace, key))?;
+        Ok(row.get(0))
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let mut conn = self.db.get_conn().await?;
+        let tx = conn.transaction().await?;
+        tx.execute(
+            "DELETE FROM dmpool_blobs WHERE namespace = $1 AND key = $2",
+            &[&self.namespace, &to],
+        )
+        .await?;
+        let moved = tx
+            .execute(
+                "UPDATE dmpool_blobs SET key = $3, updated_at = NOW() WHERE namespace = $1 AND key = $2",
+                &[&self.namespace, &from, &to],
+            )
+            .await
+            .with_context(|| format!("Failed to rename {}/{}", self.namespace, from))?;
+        if moved == 0 {
+            return Err(anyhow::anyhow!("{} does not exist", from));
+        }
+        tx.commit().await.context("Failed to commit rename")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+// Job freshness for the stratum layer
+//
+// Stale jobs waste miners' hashrate. The binary relays the ZMQ block trigger
+// and the GBT task's template notifications through this tracker, which
+// records when each arrived, how long a template took to follow a trigger
+// and how long the notifier took to accept it. A template older than
+// `stale_template_secs` degrades the stratum health and raises an alert.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::alert::{AlertCondition, AlertLevel, AlertManager, AlertRule};
+use crate::runtime_metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
+use tracing::warn;
+
+/// Alert rule raised when no template has arrived for `stale_template_secs`
+pub const STALE_TEMPLATE_ALERT_RULE: &str = "stale_template";
+
+/// Latency samples kept for averages
+const LATENCY_SAMPLES: usize = 64;
+
+/// The `[dmpool.job_freshness]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobFreshnessConfig {
+    pub enabled: bool,
+    /// Alert when no new template has arrived for this long
+    pub stale_template_secs: u64,
+    /// Seconds between staleness checks
+    pub check_interval_secs: u64,
+}
+
+impl Default for JobFreshnessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stale_template_secs: 120,
+            check_interval_secs: 10,
+        }
+    }
+}
+
+impl JobFreshnessConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            return Err(anyhow::anyhow!("check_interval_secs must be at least 1"));
+        }
+        if self.stale_template_secs <= self.check_interval_secs {
+            return Err(anyhow::anyhow!("stale_template_secs must be above check_interval_secs ({})", self.check_interval_secs));
+        }
+        Ok(())
+    }
+}
+
+/// Last, mean and worst of the recent samples (milliseconds)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub last_ms: Option<u64>,
+    pub mean_ms: Option<f64>,
+    pub max_ms: Option<u64>,
+    pub samples: usize,
+}
+
+/// Template and notify freshness at one point in time
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobFreshnessStatus {
+    pub last_template_at: Option<DateTime<Utc>>,
+    pub seconds_since_template: Option<u64>,
+    pub last_zmq_trigger_at: Option<DateTime<Utc>>,
+    pub seconds_since_zmq_trigger: Option<u64>,
+    /// From a ZMQ block trigger to the next template
+    pub template_fetch: LatencySummary,
+    /// From a template to the notifier accepting it for fan-out
+    pub notify_fanout: LatencySummary,
+    pub templates_total: u64,
+    pub zmq_triggers_total: u64,
+    pub stale_template_secs: u64,
+    /// No template for `stale_template_secs`, or none since startup past that
+    pub stale: bool,
+}
+
+impl JobFreshnessStatus {
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        if let Some(secs) = self.seconds_since_template {
+            gauge(&mut out, "dmpool_stratum_seconds_since_template", "Time since the last block template", secs as f64);
+        }
+        if let Some(secs) = self.seconds_since_zmq_trigger {
+            gauge(&mut out, "dmpool_stratum_seconds_since_zmq_trigger", "Time since the last ZMQ block trigger", secs as f64);
+        }
+        if let Some(ms) = self.template_fetch.last_ms {
+            gauge(&mut out, "dmpool_stratum_template_fetch_seconds", "Time from the last ZMQ trigger to its template", ms as f64 / 1000.0);
+        }
+        if let Some(ms) = self.notify_fanout.last_ms {
+            gauge(&mut out, "dmpool_stratum_notify_fanout_seconds", "Time for the notifier to accept the last template", ms as f64 / 1000.0);
+        }
+        counter(&mut out, "dmpool_stratum_templates_total", "Block templates received", self.templates_total as f64);
+        counter(&mut out, "dmpool_stratum_zmq_triggers_total", "ZMQ block triggers received", self.zmq_triggers_total as f64);
+        gauge(&mut out, "dmpool_stratum_template_stale", "Whether the current template is stale", if self.stale { 1.0 } else { 0.0 });
+        out
+    }
+}
+
+#[derive(Default)]
+struct Latencies(VecDeque<u64>);
+
+impl Latencies {
+    fn push(&mut self, latency: Duration) {
+        if self.0.len() == LATENCY_SAMPLES {
+            self.0.pop_front();
+        }
+        self.0.push_back(latency.as_millis() as u64);
+    }
+
+    fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            last_ms: self.0.back().copied(),
+            mean_ms: (!self.0.is_empty()).then(|| self.0.iter().sum::<u64>() as f64 / self.0.len() as f64),
+            max_ms: self.0.iter().max().copied(),
+            samples: self.0.len(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    last_template: Option<(Instant, DateTime<Utc>)>,
+    last_zmq: Option<(Instant, DateTime<Utc>)>,
+    /// Trigger still waiting for its template
+    pending_zmq: Option<Instant>,
+    template_fetch: Latencies,
+    notify_fanout: Latencies,
+    templates_total: u64,
+    zmq_triggers_total: u64,
+}
+
+/// Tracks block template and job notify freshness
+pub struct JobFreshness {
+    config: JobFreshnessConfig,
+    started: Instant,
+    state: RwLock<State>,
+    alerts: Option<Arc<AlertManager>>,
+}
+
+impl JobFreshness {
+    pub fn new(config: JobFreshnessConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            state: RwLock::new(State::default()),
+            alerts: None,
+        }
+    }
+
+    /// Raise an alert while templates are stale
+    pub fn with_alerts(mut self, alerts: Arc<AlertManager>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    pub fn config(&self) -> &JobFreshnessConfig {
+        &self.config
+    }
+
+    pub async fn record_zmq_trigger(&self) {
+        self.record_zmq_trigger_at(Instant::now()).await;
+    }
+
+    pub async fn record_zmq_trigger_at(&self, at: Instant) {
+        let mut state = self.state.write().await;
+        state.last_zmq = Some((at, Utc::now()));
+        // A second trigger before a template keeps the first as the start
+        if state.pending_zmq.is_none() {
+            state.pending_zmq = Some(at);
+        }
+        state.zmq_triggers_total += 1;
+    }
+
+    pub async fn record_template(&self) {
+        self.record_template_at(Instant::now()).await;
+    }
+
+    pub async fn record_template_at(&self, at: Instant) {
+        let mut state = self.state.write().await;
+        state.last_template = Some((at, Utc::now()));
+        if let Some(trigger) = state.pending_zmq.take() {
+            state.template_fetch.push(at.saturating_duration_since(trigger));
+        }
+        state.templates_total += 1;
+    }
+
+    /// Record how long the notifier took to accept a template
+    pub async fn record_notify(&self, latency: Duration) {
+        self.state.write().await.notify_fanout.push(latency);
+    }
+
+    pub async fn status(&self) -> JobFreshnessStatus {
+        self.status_at(Instant::now()).await
+    }
+
+    pub async fn status_at(&self, now: Instant) -> JobFreshnessStatus {
+        let state = self.state.read().await;
+        let since = |at: Option<(Instant, DateTime<Utc>)>| at.map(|(at, _)| now.saturating_duration_since(at).as_secs());
+        let seconds_since_template = since(state.last_template);
+        let stale_after = self.config.stale_template_secs;
+        let stale = match seconds_since_template {
+            Some(secs) => secs >= stale_after,
+            None => now.saturating_duration_since(self.started).as_secs() >= stale_after,
+        };
+        JobFreshnessStatus {
+            last_template_at: state.last_template.map(|(_, at)| at),
+            seconds_since_template,
+            last_zmq_trigger_at: state.last_zmq.map(|(_, at)| at),
+            seconds_since_zmq_trigger: since(state.last_zmq),
+            template_fetch: state.template_fetch.summary(),
+            notify_fanout: state.notify_fanout.summary(),
+            templates_total: state.templates_total,
+            zmq_triggers_total: state.zmq_triggers_total,
+            stale_template_secs: stale_after,
+            stale,
+        }
+    }
+
+    /// Check staleness, raising or clearing the stale template alert
+    pub async fn check(&self) -> JobFreshnessStatus {
+        let status = self.status().await;
+        let Some(alerts) = &self.alerts else {
+            return status;
+        };
+        let context = serde_json::json!({
+            "seconds_since_template": status.seconds_since_template,
+            "stale_template_secs": status.stale_template_secs,
+            "seconds_since_zmq_trigger": status.seconds_since_zmq_trigger,
+        });
+        if !status.stale {
+            if let Err(e) = alerts.clear_rule(STALE_TEMPLATE_ALERT_RULE, context).await {
+                warn!("Failed to clear stale template alert: {:#}", e);
+            }
+            return status;
+        }
+
+        warn!("No new block template for {:?} seconds", status.seconds_since_template);
+        if !alerts.get_rules().await.iter().any(|r| r.id == STALE_TEMPLATE_ALERT_RULE) {
+            let channels: Vec<String> = alerts.get_channels().await.into_keys().collect();
+            alerts.add_rule(AlertRule::new(
+                STALE_TEMPLATE_ALERT_RULE,
+                "No new block template",
+                AlertCondition::Custom {
+                    message: format!("No new block template in {} seconds; miners are working on stale jobs", self.config.stale_template_secs),
+                },
+                AlertLevel::Critical,
+                channels,
+            ).with_cooldown(30)).await;
+        }
+        if let Err(e) = alerts.trigger_alert(STALE_TEMPLATE_ALERT_RULE, context).await {
+            warn!("Failed to raise stale template alert: {}", e);
+        }
+        status
+    }
+
+    /// Check on the configured interval
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(self.config.check_interval_secs));
+            loop {
+                interval.tick().await;
+                self.check().await;
+            }
+        });
+    }
+
+    /// Forward ZMQ triggers, recording each
+    pub fn relay_zmq<T: Send + 'static>(self: &Arc<Self>, mut triggers: mpsc::Receiver<T>) -> mpsc::Receiver<T> {
+        let (tx, rx) = mpsc::channel(triggers.max_capacity());
+        let freshness = self.clone();
+        tokio::spawn(async move {
+            while let Some(trigger) = triggers.recv().await {
+                freshness.record_zmq_trigger().await;
+                if tx.send(trigger).await.is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Sender for the GBT task that records each template on its way to the notifier
+    pub fn relay_templates<T: Send + 'static>(self: &Arc<Self>, notify: mpsc::Sender<T>) -> mpsc::Sender<T> {
+        let (tx, mut rx) = mpsc::channel(notify.max_capacity());
+        let freshness = self.clone();
+        tokio::spawn(async move {
+            while let Some(template) = rx.recv().await {
+                freshness.record_template().await;
+                let start = Instant::now();
+                if notify.send(template).await.is_err() {
+                    break;
+                }
+                freshness.record_notify(start.elapsed()).await;
+            }
+        });
+        tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fetch_latency_and_staleness() {
+        let freshness = JobFreshness::new(JobFreshnessConfig { stale_template_secs: 60, ..Default::default() });
+        let start = Instant::now();
+        assert!(!freshness.status_at(start).await.stale);
+
+        // Two triggers before one template: latency runs from the first
+        freshness.record_zmq_trigger_at(start).await;
+        freshness.record_zmq_trigger_at(start + Duration::from_millis(100)).await;
+        freshness.record_template_at(start + Duration::from_millis(250)).await;
+        freshness.record_template_at(start + Duration::from_secs(10)).await;
+        freshness.record_notify(Duration::from_millis(4)).await;
+
+        let status = freshness.status_at(start + Duration::from_secs(30)).await;
+        assert_eq!(status.template_fetch.last_ms, Some(250));
+        assert_eq!(status.template_fetch.samples, 1);
+        assert_eq!(status.notify_fanout.max_ms, Some(4));
+        assert_eq!((status.templates_total, status.zmq_triggers_total), (2, 2));
+        assert_eq!(status.seconds_since_template, Some(20));
+        assert!(!status.stale);
+        assert!(status.to_prometheus().contains("dmpool_stratum_template_stale 0"));
+
+        assert!(freshness.status_at(start + Duration::from_secs(70)).await.stale);
+    }
+
+    #[tokio::test]
+    async fn test_stale_alert_raised_and_cleared() {
+        let alerts = Arc::new(AlertManager::default());
+        let freshness = JobFreshness::new(JobFreshnessConfig { stale_template_secs: 20, ..Default::default() })
+            .with_alerts(alerts.clone());
+        freshness.state.write().await.last_template = Some((Instant::now() - Duration::from_secs(30), Utc::now()));
+
+        assert!(freshness.check().await.stale);
+        assert!(alerts.firing_rules().await.contains_key(STALE_TEMPLATE_ALERT_RULE));
+
+        freshness.record_template().await;
+        assert!(!freshness.check().await.stale);
+        assert!(alerts.firing_rules().await.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+// Stratum Stats Module for DMPool
+// Live worker counts and share rates bridged from the p2poolv2 stratum layer
+//
+// The stratum server keeps its counters in the p2poolv2 metrics actor, which
+// only the node binary holds a handle to. The binary samples that handle on an
+// interval and records each sample here; the Observer API, the Admin API and
+// the health checker read the latest values from this shared state.
+// Template and job notify freshness is tracked in `freshness`.
+
+pub mod freshness;
+
+pub use freshness::{JobFreshness, JobFreshnessConfig, JobFreshnessStatus, LatencySummary, STALE_TEMPLATE_ALERT_RULE};
+
+use crate::health::HealthChecker;
+use crate::sla::SlaTracker;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+
+/// Counters read from the stratum metrics at one point in time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StratumSample {
+    /// Addresses with at least one authorized worker
+    pub miners: u32,
+    /// Authorized workers, one per stratum connection
+    pub workers: u32,
+    /// Accepted shares since the stratum server started
+    pub accepted_total: u64,
+    /// Rejected shares since the stratum server started
+    pub rejected_total: u64,
+}
+
+/// Current stratum activity
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LiveStats {
+    pub miners: u32,
+    pub workers: u32,
+    pub shares_per_second: f64,
+    pub rejected_per_second: f64,
+    pub accepted_total: u64,
+    pub rejected_total: u64,
+    /// None until the first sample is recorded
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Shared live stratum statistics
+pub struct StratumStats {
+    previous: RwLock<Option<(Instant, StratumSample)>>,
+    live: RwLock<LiveStats>,
+    health: Option<Arc<HealthChecker>>,
+    sla: Option<Arc<SlaTracker>>,
+}
+
+impl Default for StratumStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StratumStats {
+    pub fn new() -> Self {
+        Self {
+            previous: RwLock::new(None),
+            live: RwLock::new(LiveStats::default()),
+            health: None,
+            sla: None,
+        }
+    }
+
+    /// Keep the health checker's stratum counters up to date
+    pub fn with_health(mut self, health: Arc<HealthChecker>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Count accepted and rejected shares towards the daily SLA figures
+    pub fn with_sla(mut self, sla: Arc<SlaTracker>) -> Self {
+        self.sla = Some(sla);
+        self
+    }
+
+    /// Record a sample taken now
+    pub async fn record(&self, sample: StratumSample) {
+        self.record_at(sample, Instant::now()).await;
+    }
+
+    /// Record a sample taken at `at`
+    ///
+    /// Rates are the counter deltas since the previous sample. Counters that
+    /// went backwards (stratum restart) give a zero rate for that interval.
+    pub async fn record_at(&self, sample: StratumSample, at: Instant) -> LiveStats {
+        let mut previous = self.previous.write().await;
+        let (accepted, rejected, secs) = match *previous {
+            Some((last_at, last)) if at > last_at => (
+                sample.accepted_total.saturating_sub(last.accepted_total),
+                sample.rejected_total.saturating_sub(last.rejected_total),
+                at.duration_since(last_at).as_secs_f64(),
+            ),
+            _ => (0, 0, 1.0),
+        };
+        let (shares_per_second, rejected_per_second) = (accepted as f64 / secs, rejected as f64 / secs);
+        *previous = Some((at, sample));
+
+        let live = LiveStats {
+            miners: sample.miners,
+            workers: sample.workers,
+            shares_per_second,
+            rejected_per_second,
+            accepted_total: sample.accepted_total,
+            rejected_total: sample.rejected_total,
+            updated_at: Some(Utc::now()),
+        };
+        *self.live.write().await = live.clone();
+
+        if let Some(health) = &self.health {
+            health.update_connections(sample.workers);
+            health.update_shares_per_second(shares_per_second);
+        }
+        if let Some(sla) = &self.sla {
+            sla.record_shares(Utc::now(), accepted, rejected).await;
+        }
+        live
+    }
+
+    /// Latest live statistics
+    pub async fn current(&self) -> LiveStats {
+        self.live.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(workers: u32, accepted_total: u64, rejected_total: u64) -> StratumSample {
+        StratumSample { miners: 1, workers, accepted_total, rejected_total }
+    }
+
+    #[tokio::test]
+    async fn test_rates_from_counter_deltas() {
+        let stats = StratumStats::new();
+        assert!(stats.current().await.updated_at.is_none());
+
+        let start = Instant::now();
+        let first = stats.record_at(sample(3, 100, 2), start).await;
+        assert_eq!(first.shares_per_second, 0.0);
+
+        let second = stats.record_at(sample(4, 150, 7), start + Duration::from_secs(10)).await;
+        assert_eq!(second.shares_per_second, 5.0);
+        assert_eq!(second.rejected_per_second, 0.5);
+
+        let current = stats.current().await;
+        assert_eq!(current.workers, 4);
+        assert_eq!(current.accepted_total, 150);
+        assert!(current.updated_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_counter_reset_gives_zero_rate() {
+        let stats = StratumStats::new();
+        let start = Instant::now();
+        stats.record_at(sample(3, 500, 10), start).await;
+
+        let after_restart = stats.record_at(sample(1, 20, 0), start + Duration::from_secs(5)).await;
+        assert_eq!(after_restart.shares_per_second, 0.0);
+        assert_eq!(after_restart.rejected_per_second, 0.0);
+
+        let next = stats.record_at(sample(1, 40, 0), start + Duration::from_secs(10)).await;
+        assert_eq!(next.shares_per_second, 4.0);
+    }
+}
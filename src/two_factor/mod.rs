@@ -8,6 +8,8 @@ use aes_gcm::{
     Aes256Gcm, Nonce,
 };
 use base64::{Engine as _, engine::general_purpose};
+use crate::keys::{generate_key, EnvKeyProvider, KeyBytes, KeyProvider, KEY_ID_TWO_FACTOR};
+use crate::storage::{BlobStore, FileStore};
 use chrono::{DateTime, Utc};
 use qrcode::QrCode;
 use rand::distributions::Distribution;
@@ -15,7 +17,6 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::fs;
 use tokio::sync::RwLock;
 use totp_rs::{Algorithm, TOTP};
 use tracing::{error, info, warn};
@@ -35,29 +36,9 @@ struct EncryptionKey {
 }
 
 impl EncryptionKey {
-    /// Create a new encryption key from environment or generate one
-    fn from_env_or_generate() -> Self {
-        if let Ok(key_str) = std::env::var("TWO_FACTOR_ENCRYPTION_KEY") {
-            // Decode base64 key
-            let key_bytes = general_purpose::STANDARD
-                .decode(key_str)
-                .expect("Invalid TWO_FACTOR_ENCRYPTION_KEY: must be valid base64");
-
-            if key_bytes.len() != 32 {
-                panic!("TWO_FACTOR_ENCRYPTION_KEY must be 32 bytes (256 bits) after base64 decoding");
-            }
-
-            let mut key = [0u8; 32];
-            key.copy_from_slice(&key_bytes);
-            Self { key }
-        } else {
-            // Generate a new key
-            let key = Aes256Gcm::generate_key(&mut OsRng);
-            let key_array: [u8; 32] = key.into();
-            warn!("Generated new TOTP encryption key. Set TWO_FACTOR_ENCRYPTION_KEY environment variable to persist.");
-            warn!("Export this key: {}", general_purpose::STANDARD.encode(&key_array));
-            Self { key: key_array }
-        }
+    /// Wrap raw key bytes
+    fn from_bytes(key: KeyBytes) -> Self {
+        Self { key }
     }
 
     /// Get the key bytes
@@ -99,6 +80,28 @@ fn decrypt_data(encrypted: &EncryptedSecret, key: &EncryptionKey) -> Result<Vec<
     Ok(plaintext)
 }
 
+/// Decrypt stored secrets, failing on the first one `key` cannot open
+fn decrypt_secrets(stored: HashMap<String, TotpSecret>, key: &EncryptionKey) -> Result<HashMap<String, TotpSecret>> {
+    let mut secrets = HashMap::new();
+    for (username, mut secret) in stored {
+        if let Some(encrypted) = secret.encrypted_secret.take() {
+            let decrypted_bytes = decrypt_data(&encrypted, key)
+                .with_context(|| format!("Failed to decrypt TOTP secret for user '{}'", username))?;
+            secret.secret = Some(base32::encode(base32::Alphabet::Rfc4648 { padding: true }, &decrypted_bytes));
+        }
+        secrets.insert(username, secret);
+    }
+    Ok(secrets)
+}
+
+/// Compare two byte strings in constant time (for equal lengths)
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 /// TOTP secret for a user (stored encrypted at rest)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TotpSecret {
@@ -115,12 +118,15 @@ pub struct TotpSecret {
     pub enabled: bool,
 }
 
+/// Prefix for legacy SHA-256 backup code hashes that have been wrapped in Argon2
+const WRAPPED_SHA256_PREFIX: &str = "sha256$";
+
 /// Backup codes for account recovery
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BackupCodes {
     /// Username associated with these codes
     pub username: String,
-    /// List of backup codes (hashed)
+    /// List of backup codes (salted Argon2 PHC strings)
     pub codes: Vec<String>,
     /// When these codes were generated
     pub created_at: DateTime<Utc>,
@@ -168,12 +174,44 @@ pub struct TwoFactorStatus {
 }
 
 /// Rate limit tracker for 2FA attempts
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TwoFactorRateLimit {
     pub attempts: u32,
     pub locked_until: Option<DateTime<Utc>>,
 }
 
+/// Persisted rate limit state (survives restarts)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct RateLimitState {
+    /// Failed TOTP attempts per user
+    totp: HashMap<String, TwoFactorRateLimit>,
+    /// Failed backup code attempts per user
+    backup_code: HashMap<String, TwoFactorRateLimit>,
+}
+
+/// 2FA lockout entry for admin listing
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TwoFactorLockout {
+    /// Username with failed attempts
+    pub username: String,
+    /// Failed TOTP attempts
+    pub totp_attempts: u32,
+    /// TOTP locked until (if locked)
+    pub totp_locked_until: Option<DateTime<Utc>>,
+    /// Failed backup code attempts
+    pub backup_code_attempts: u32,
+    /// Backup codes locked until (if locked)
+    pub backup_code_locked_until: Option<DateTime<Utc>>,
+    /// Whether the user is currently locked out of either method
+    pub locked: bool,
+}
+
+const SECRETS_KEY: &str = "totp_secrets.json";
+const BACKUP_CODES_KEY: &str = "backup_codes.json";
+const RATE_LIMITS_KEY: &str = "rate_limits.json";
+/// Secrets re-encrypted by a key rotation that has not been swapped in yet
+const ROTATING_SECRETS_KEY: &str = "totp_secrets.json.rotating";
+
 /// Two-Factor Authentication manager
 pub struct TwoFactorManager {
     /// TOTP secrets storage
@@ -184,8 +222,8 @@ pub struct TwoFactorManager {
     rate_limits: Arc<RwLock<HashMap<String, TwoFactorRateLimit>>>,
     /// Rate limiting for backup code attempts (separate from TOTP)
     backup_code_rate_limits: Arc<RwLock<HashMap<String, TwoFactorRateLimit>>>,
-    /// Storage directory for persistence
-    storage_dir: PathBuf,
+    /// Where secrets, backup codes and lockouts are persisted
+    store: Arc<dyn BlobStore>,
     /// Maximum failed attempts before lockout
     max_attempts: u32,
     /// Maximum backup code attempts before lockout (lower than TOTP)
@@ -194,84 +232,169 @@ pub struct TwoFactorManager {
     lockout_duration: i64,
     /// Issuer name for TOTP (e.g., "DMPool Admin")
     issuer: String,
-    /// Encryption key for TOTP secrets
-    encryption_key: Arc<EncryptionKey>,
+    /// Encryption key for TOTP secrets (loaded from the key provider in initialize)
+    encryption_key: Arc<RwLock<EncryptionKey>>,
+    /// Source of the encryption key
+    key_provider: Arc<dyn KeyProvider>,
 }
 
 impl TwoFactorManager {
-    /// Create a new 2FA manager
+    /// Create a new 2FA manager using the environment key provider
     pub fn new(storage_dir: PathBuf, issuer: String) -> Self {
-        let encryption_key = Arc::new(EncryptionKey::from_env_or_generate());
+        Self::with_key_provider(storage_dir, issuer, Arc::new(EnvKeyProvider))
+    }
 
+    /// Create a new 2FA manager with a specific key provider
+    pub fn with_key_provider(storage_dir: PathBuf, issuer: String, key_provider: Arc<dyn KeyProvider>) -> Self {
         Self {
             secrets: Arc::new(RwLock::new(HashMap::new())),
             backup_codes: Arc::new(RwLock::new(HashMap::new())),
             rate_limits: Arc::new(RwLock::new(HashMap::new())),
             backup_code_rate_limits: Arc::new(RwLock::new(HashMap::new())),
-            storage_dir,
+            store: Arc::new(FileStore::new(storage_dir)),
             max_attempts: 5,
             max_backup_attempts: 3, // Fewer attempts for backup codes
             lockout_duration: 300, // 5 minutes
             issuer,
-            encryption_key,
+            // Placeholder until initialize() loads the real key
+            encryption_key: Arc::new(RwLock::new(EncryptionKey::from_bytes([0u8; 32]))),
+            key_provider,
         }
     }
 
+    /// Persist state in `store` instead of the storage directory
+    pub fn with_store(mut self, store: Arc<dyn BlobStore>) -> Self {
+        self.store = store;
+        self
+    }
+
     /// Initialize the 2FA manager
     pub async fn initialize(&self) -> Result<()> {
-        // Create storage directory
-        fs::create_dir_all(&self.storage_dir).await
-            .context("Failed to create 2FA storage directory")?;
+        // Load the encryption key before touching any stored secrets
+        self.load_encryption_key().await?;
 
         // Load existing secrets
         self.load_secrets().await?;
 
+        // Restore lockouts so a restart does not reset them
+        self.load_rate_limits().await?;
+
         info!("2FA manager initialized");
 
         Ok(())
     }
 
-    /// Load TOTP secrets from disk
+    /// Load the encryption key from the key provider, creating one on first run
+    async fn load_encryption_key(&self) -> Result<()> {
+        let provider = self.key_provider.name();
+
+        if let Some(key) = self.key_provider.load_key(KEY_ID_TWO_FACTOR).await
+            .with_context(|| format!("Failed to load 2FA encryption key from '{}' provider", provider))?
+        {
+            *self.encryption_key.write().await = EncryptionKey::from_bytes(key);
+            info!("Loaded 2FA encryption key from '{}' provider", provider);
+            return Ok(());
+        }
+
+        // Never silently replace a lost key: existing secrets would become unreadable
+        if self.store.exists(SECRETS_KEY).await.context("Failed to check for TOTP secrets")? {
+            return Err(anyhow::anyhow!(
+                "2FA encryption key not found in '{}' provider but encrypted TOTP secrets exist in the {} store",
+                provider,
+                self.store.backend()
+            ));
+        }
+
+        let key = generate_key();
+        match self.key_provider.store_key(KEY_ID_TWO_FACTOR, &key).await {
+            Ok(()) => info!("Generated new 2FA encryption key in '{}' provider", provider),
+            Err(e) => {
+                warn!("Generated ephemeral 2FA encryption key ({}). Set TWO_FACTOR_ENCRYPTION_KEY or configure DMPOOL_KEY_PROVIDER to persist.", e);
+                warn!("Export this key: {}", general_purpose::STANDARD.encode(key));
+            }
+        }
+        *self.encryption_key.write().await = EncryptionKey::from_bytes(key);
+
+        Ok(())
+    }
+
+    /// Rotate the encryption key and re-encrypt all stored TOTP secrets
+    ///
+    /// Returns the number of secrets re-encrypted.
+    pub async fn rotate_encryption_key(&self) -> Result<usize> {
+        let new_key = EncryptionKey::from_bytes(generate_key());
+
+        // Hold the key lock for the whole rotation so no save races with it
+        let mut current_key = self.encryption_key.write().await;
+
+        let (json, count) = {
+            let secrets = self.secrets.read().await;
+            (self.serialize_secrets(&secrets, &new_key)?, secrets.len())
+        };
+
+        // Stage the re-encrypted file, then commit the key, then swap the file in
+        self.store.put(ROTATING_SECRETS_KEY, json.as_bytes()).await
+            .context("Failed to write re-encrypted TOTP secrets")?;
+
+        if let Err(e) = self.key_provider.store_key(KEY_ID_TWO_FACTOR, new_key.as_bytes()).await {
+            let _ = self.store.delete(ROTATING_SECRETS_KEY).await;
+            return Err(e).context("Failed to store rotated 2FA encryption key");
+        }
+        // The provider now holds the new key; later saves must use it even if
+        // the swap below fails (load_secrets finishes it from the staged file)
+        *current_key = new_key;
+
+        self.store.put(SECRETS_KEY, json.as_bytes()).await
+            .context("Failed to replace TOTP secrets file")?;
+        let _ = self.store.delete(ROTATING_SECRETS_KEY).await;
+
+        info!("Rotated 2FA encryption key, re-encrypted {} TOTP secrets", count);
+        Ok(count)
+    }
+
+    /// Load TOTP secrets from the store
+    ///
+    /// Fails if any secret cannot be decrypted, rather than silently dropping
+    /// that user's 2FA.
     async fn load_secrets(&self) -> Result<()> {
-        let secrets_file = self.storage_dir.join("totp_secrets.json");
-        let backup_file = self.storage_dir.join("backup_codes.json");
-
-        // Load TOTP secrets
-        if secrets_file.exists() {
-            let json = fs::read_to_string(&secrets_file).await
-                .context("Failed to read TOTP secrets file")?;
-            let loaded_secrets: HashMap<String, TotpSecret> = serde_json::from_str(&json)
-                .context("Failed to parse TOTP secrets")?;
-
-            // Decrypt secrets
-            let mut secrets = HashMap::new();
-            for (username, mut secret) in loaded_secrets {
-                if let Some(encrypted) = secret.encrypted_secret.take() {
-                    match decrypt_data(&encrypted, &self.encryption_key) {
-                        Ok(decrypted_bytes) => {
-                            let secret_string = base32::encode(base32::Alphabet::Rfc4648 { padding: true }, &decrypted_bytes);
-                            secret.secret = Some(secret_string);
-                        }
-                        Err(e) => {
-                            error!("Failed to decrypt TOTP secret for user '{}': {}", username, e);
-                            continue;
-                        }
-                    }
+        let encryption_key = self.encryption_key.read().await;
+        let stored = self.store.get_json::<HashMap<String, TotpSecret>>(SECRETS_KEY).await
+            .context("Failed to load TOTP secrets file")?;
+        let staged = self.store.get_json::<HashMap<String, TotpSecret>>(ROTATING_SECRETS_KEY).await
+            .context("Failed to load staged TOTP secrets file")?;
+
+        // A rotation interrupted after the new key was stored leaves the
+        // secrets file under the old key and the re-encrypted copy staged
+        let loaded = match (stored.map(|stored| decrypt_secrets(stored, &encryption_key)), staged) {
+            (Some(Err(e)), Some(staged)) => {
+                let secrets = decrypt_secrets(staged, &encryption_key)
+                    .with_context(|| format!("{:#}, and the staged rotation does not match the key either", e))?;
+                warn!("Finishing an interrupted 2FA encryption key rotation");
+                self.store.rename(ROTATING_SECRETS_KEY, SECRETS_KEY).await
+                    .context("Failed to replace TOTP secrets file")?;
+                Some(secrets)
+            }
+            (loaded, staged) => {
+                if staged.is_some() {
+                    warn!("Discarding TOTP secrets staged by an unfinished 2FA key rotation");
+                    self.store.delete(ROTATING_SECRETS_KEY).await
+                        .context("Failed to remove staged TOTP secrets file")?;
                 }
-                secrets.insert(username, secret);
+                loaded.transpose()?
             }
+        };
+        drop(encryption_key);
 
+        if let Some(secrets) = loaded {
             let count = secrets.len();
             *self.secrets.write().await = secrets;
             info!("Loaded {} TOTP secrets", count);
         }
 
         // Load backup codes
-        if backup_file.exists() {
-            let json = fs::read_to_string(&backup_file).await
-                .context("Failed to read backup codes file")?;
-            let codes: HashMap<String, BackupCodes> = serde_json::from_str(&json)
-                .context("Failed to parse backup codes")?;
+        if let Some(codes) = self.store.get_json::<HashMap<String, BackupCodes>>(BACKUP_CODES_KEY).await
+            .context("Failed to load backup codes file")?
+        {
             let count = codes.len();
             *self.backup_codes.write().await = codes;
             info!("Loaded backup codes for {} users", count);
@@ -280,12 +403,21 @@ impl TwoFactorManager {
         Ok(())
     }
 
-    /// Save TOTP secrets to disk (encrypting before save)
+    /// Save TOTP secrets to the store (encrypting before save)
     async fn save_secrets(&self) -> Result<()> {
-        let secrets_file = self.storage_dir.join("totp_secrets.json");
+        let encryption_key = self.encryption_key.read().await;
+        let json = {
+            let secrets = self.secrets.read().await;
+            self.serialize_secrets(&secrets, &encryption_key)?
+        };
+        drop(encryption_key);
 
-        // Encrypt secrets before saving
-        let secrets = self.secrets.read().await;
+        self.store.put(SECRETS_KEY, json.as_bytes()).await
+            .context("Failed to write TOTP secrets file")
+    }
+
+    /// Encrypt secrets with the given key and serialize them for storage
+    fn serialize_secrets(&self, secrets: &HashMap<String, TotpSecret>, key: &EncryptionKey) -> Result<String> {
         let mut secrets_to_save = HashMap::new();
 
         for (username, secret) in secrets.iter() {
@@ -296,7 +428,7 @@ impl TwoFactorManager {
                 let secret_bytes = base32::decode(base32::Alphabet::Rfc4648 { padding: true }, plaintext)
                     .context("Failed to decode secret for encryption")?;
 
-                let encrypted = encrypt_data(&secret_bytes, &self.encryption_key)
+                let encrypted = encrypt_data(&secret_bytes, key)
                     .context("Failed to encrypt TOTP secret")?;
 
                 secret_to_save.encrypted_secret = Some(encrypted);
@@ -307,24 +439,49 @@ impl TwoFactorManager {
             secrets_to_save.insert(username.clone(), secret_to_save);
         }
 
-        drop(secrets);
+        serde_json::to_string_pretty(&secrets_to_save)
+            .context("Failed to serialize TOTP secrets")
+    }
+
+    /// Load rate limit state from the store
+    async fn load_rate_limits(&self) -> Result<()> {
+        let Some(state) = self.store.get_json::<RateLimitState>(RATE_LIMITS_KEY).await
+            .context("Failed to load 2FA rate limit file")?
+        else {
+            return Ok(());
+        };
+
+        let count = state.totp.len() + state.backup_code.len();
+        *self.rate_limits.write().await = state.totp;
+        *self.backup_code_rate_limits.write().await = state.backup_code;
+        info!("Loaded {} 2FA rate limit entries", count);
 
-        let json = serde_json::to_string_pretty(&secrets_to_save)
-            .context("Failed to serialize TOTP secrets")?;
-        fs::write(&secrets_file, json).await
-            .context("Failed to write TOTP secrets file")?;
         Ok(())
     }
 
-    /// Save backup codes to disk
+    /// Save rate limit state to the store
+    async fn save_rate_limits(&self) -> Result<()> {
+        let state = RateLimitState {
+            totp: self.rate_limits.read().await.clone(),
+            backup_code: self.backup_code_rate_limits.read().await.clone(),
+        };
+        self.store.put_json(RATE_LIMITS_KEY, &state).await
+            .context("Failed to write 2FA rate limit file")
+    }
+
+    /// Persist rate limits, logging rather than failing the calling auth flow
+    async fn persist_rate_limits(&self) {
+        if let Err(e) = self.save_rate_limits().await {
+            error!("Failed to persist 2FA rate limits: {}", e);
+        }
+    }
+
+    /// Save backup codes to the store
     async fn save_backup_codes(&self) -> Result<()> {
-        let backup_file = self.storage_dir.join("backup_codes.json");
-        let codes = self.backup_codes.read().await;
-        let json = serde_json::to_string_pretty(&*codes)
+        let json = serde_json::to_string_pretty(&*self.backup_codes.read().await)
             .context("Failed to serialize backup codes")?;
-        fs::write(&backup_file, json).await
-            .context("Failed to write backup codes file")?;
-        Ok(())
+        self.store.put(BACKUP_CODES_KEY, json.as_bytes()).await
+            .context("Failed to write backup codes file")
     }
 
     /// Generate a new TOTP secret for a user
@@ -370,9 +527,7 @@ impl TwoFactorManager {
         self.save_secrets().await?;
 
         // Store hashed backup codes
-        let hashed_codes: Vec<String> = backup_codes.iter()
-            .map(|code| Self::hash_backup_code(code))
-            .collect();
+        let hashed_codes = Self::hash_backup_codes(backup_codes.clone()).await?;
 
         let backup_data = BackupCodes {
             username: username.to_string(),
@@ -490,12 +645,13 @@ impl TwoFactorManager {
 
         // Try backup code (with separate rate limiting)
         if let Some(code) = backup_code {
-            if self.verify_backup_code_with_rate_limit(username, code).await? {
-                // Remove the used backup code
-                self.consume_backup_code(username, code).await?;
-                self.clear_rate_limit(username).await;
-                info!("User '{}' authenticated via backup code", username);
-                return Ok(true);
+            if let Some(stored_hash) = self.verify_backup_code_with_rate_limit(username, code).await? {
+                // Remove the used backup code; fails if it was consumed concurrently
+                if self.consume_backup_code(username, &stored_hash).await? {
+                    self.clear_rate_limit(username).await;
+                    info!("User '{}' authenticated via backup code", username);
+                    return Ok(true);
+                }
             }
         }
 
@@ -527,9 +683,7 @@ impl TwoFactorManager {
         let backup_codes = Self::generate_backup_codes();
 
         // Store hashed backup codes
-        let hashed_codes: Vec<String> = backup_codes.iter()
-            .map(|code| Self::hash_backup_code(code))
-            .collect();
+        let hashed_codes = Self::hash_backup_codes(backup_codes.clone()).await?;
 
         let backup_data = BackupCodes {
             username: username.to_string(),
@@ -548,6 +702,48 @@ impl TwoFactorManager {
         Ok(backup_codes)
     }
 
+    /// List users with failed 2FA attempts or active lockouts
+    pub async fn list_lockouts(&self) -> Vec<TwoFactorLockout> {
+        let now = Utc::now();
+        let totp = self.rate_limits.read().await;
+        let backup = self.backup_code_rate_limits.read().await;
+
+        let mut usernames: Vec<&String> = totp.keys().chain(backup.keys()).collect();
+        usernames.sort();
+        usernames.dedup();
+
+        usernames.into_iter().map(|username| {
+            let t = totp.get(username);
+            let b = backup.get(username);
+            let totp_locked_until = t.and_then(|l| l.locked_until).filter(|until| *until > now);
+            let backup_code_locked_until = b.and_then(|l| l.locked_until).filter(|until| *until > now);
+
+            TwoFactorLockout {
+                username: username.clone(),
+                totp_attempts: t.map(|l| l.attempts).unwrap_or(0),
+                totp_locked_until,
+                backup_code_attempts: b.map(|l| l.attempts).unwrap_or(0),
+                backup_code_locked_until,
+                locked: totp_locked_until.is_some() || backup_code_locked_until.is_some(),
+            }
+        }).collect()
+    }
+
+    /// Clear all failed attempts and lockouts for a user
+    ///
+    /// Returns true if the user had any rate limit state.
+    pub async fn unlock(&self, username: &str) -> Result<bool> {
+        let removed_totp = self.rate_limits.write().await.remove(username).is_some();
+        let removed_backup = self.backup_code_rate_limits.write().await.remove(username).is_some();
+
+        if removed_totp || removed_backup {
+            self.save_rate_limits().await?;
+            info!("Cleared 2FA lockout for user '{}'", username);
+        }
+
+        Ok(removed_totp || removed_backup)
+    }
+
     /// Check if a user is rate limited
     async fn is_rate_limited(&self, username: &str) -> bool {
         let limits = self.rate_limits.read().await;
@@ -588,6 +784,9 @@ impl TwoFactorManager {
             limit.locked_until = Some(Utc::now() + chrono::Duration::seconds(self.lockout_duration));
             warn!("User '{}' locked out due to too many failed 2FA attempts", username);
         }
+        drop(limits);
+
+        self.persist_rate_limits().await;
     }
 
     /// Record a failed backup code attempt
@@ -604,23 +803,24 @@ impl TwoFactorManager {
             limit.locked_until = Some(Utc::now() + chrono::Duration::seconds(self.lockout_duration));
             warn!("User '{}' locked out due to too many failed backup code attempts", username);
         }
+        drop(limits);
+
+        self.persist_rate_limits().await;
     }
 
     /// Clear rate limit after successful attempt
     async fn clear_rate_limit(&self, username: &str) {
-        let mut limits = self.rate_limits.write().await;
-        if let Some(limit) = limits.get_mut(username) {
-            limit.attempts = 0;
-            limit.locked_until = None;
+        let removed = self.rate_limits.write().await.remove(username).is_some();
+        if removed {
+            self.persist_rate_limits().await;
         }
     }
 
     /// Clear backup code rate limit after successful attempt
     async fn clear_backup_code_rate_limit(&self, username: &str) {
-        let mut limits = self.backup_code_rate_limits.write().await;
-        if let Some(limit) = limits.get_mut(username) {
-            limit.attempts = 0;
-            limit.locked_until = None;
+        let removed = self.backup_code_rate_limits.write().await.remove(username).is_some();
+        if removed {
+            self.persist_rate_limits().await;
         }
     }
 
@@ -647,47 +847,115 @@ impl TwoFactorManager {
         Ok(is_valid)
     }
 
-    /// Verify a backup code (with rate limiting check must be done before calling)
-    async fn verify_backup_code(&self, username: &str, code: &str) -> Result<bool> {
-        let hashed = Self::hash_backup_code(code);
+    /// Verify a backup code (rate limiting check must be done before calling)
+    ///
+    /// Returns the stored hash that matched, so the caller can consume exactly that entry.
+    async fn verify_backup_code(&self, username: &str, code: &str) -> Result<Option<String>> {
+        self.migrate_legacy_backup_codes(username).await?;
+
+        let stored = {
+            let codes = self.backup_codes.read().await;
+            match codes.get(username) {
+                Some(backup) => backup.codes.clone(),
+                None => return Ok(None),
+            }
+        };
 
-        let codes = self.backup_codes.read().await;
-        if let Some(backup) = codes.get(username) {
-            Ok(backup.codes.contains(&hashed))
-        } else {
-            Ok(false)
-        }
+        // Argon2 is slow by design, so the checks run on the blocking pool. Every
+        // entry is checked without stopping early so timing does not reveal the
+        // match position.
+        let code = code.to_string();
+        tokio::task::spawn_blocking(move || {
+            let mut matched = None;
+            for hash in stored {
+                if Self::backup_code_matches(&hash, &code) && matched.is_none() {
+                    matched = Some(hash);
+                }
+            }
+            matched
+        })
+        .await
+        .context("Backup code verification task failed")
     }
 
     /// Verify a backup code with rate limiting
-    async fn verify_backup_code_with_rate_limit(&self, username: &str, code: &str) -> Result<bool> {
+    async fn verify_backup_code_with_rate_limit(&self, username: &str, code: &str) -> Result<Option<String>> {
         // Check rate limit first
         if self.is_backup_code_rate_limited(username).await {
             warn!("User '{}' is rate limited for backup codes", username);
-            return Ok(false);
+            return Ok(None);
         }
 
-        let is_valid = self.verify_backup_code(username, code).await?;
+        let matched = self.verify_backup_code(username, code).await?;
 
-        if is_valid {
+        if matched.is_some() {
             self.clear_backup_code_rate_limit(username).await;
         } else {
             self.record_failed_backup_attempt(username).await;
         }
 
-        Ok(is_valid)
+        Ok(matched)
+    }
+
+    /// Consume a used backup code by its stored hash
+    ///
+    /// Returns false if the code was already consumed.
+    async fn consume_backup_code(&self, username: &str, stored_hash: &str) -> Result<bool> {
+        let mut codes = self.backup_codes.write().await;
+        let removed = match codes.get_mut(username) {
+            Some(backup) => {
+                let before = backup.codes.len();
+                backup.codes.retain(|c| c != stored_hash);
+                backup.codes.len() < before
+            }
+            None => false,
+        };
+        drop(codes);
+
+        if removed {
+            self.save_backup_codes().await?;
+        }
+        Ok(removed)
     }
 
-    /// Consume a used backup code
-    async fn consume_backup_code(&self, username: &str, code: &str) -> Result<()> {
-        let hashed = Self::hash_backup_code(code);
+    /// Wrap a user's unsalted SHA-256 backup code hashes in Argon2
+    ///
+    /// The plaintext codes are unknown, so the SHA-256 digest itself is hashed.
+    async fn migrate_legacy_backup_codes(&self, username: &str) -> Result<()> {
+        let legacy: Vec<String> = match self.backup_codes.read().await.get(username) {
+            Some(backup) => backup.codes.iter().filter(|hash| Self::is_legacy_hash(hash)).cloned().collect(),
+            None => return Ok(()),
+        };
+        if legacy.is_empty() {
+            return Ok(());
+        }
+
+        // Hash without holding the lock, then only take it to swap the results in
+        let wrapped = tokio::task::spawn_blocking(move || {
+            legacy.into_iter()
+                .map(|hash| Ok((format!("{}{}", WRAPPED_SHA256_PREFIX, Self::argon2_hash(&hash)?), hash)))
+                .collect::<Result<Vec<(String, String)>>>()
+        })
+        .await
+        .context("Backup code hashing task failed")??;
 
+        let mut migrated = 0;
         let mut codes = self.backup_codes.write().await;
         if let Some(backup) = codes.get_mut(username) {
-            backup.codes.retain(|c| c != &hashed);
+            for hash in backup.codes.iter_mut() {
+                // Codes consumed in the meantime are simply gone
+                if let Some((new_hash, _)) = wrapped.iter().find(|(_, legacy)| legacy == hash) {
+                    *hash = new_hash.clone();
+                    migrated += 1;
+                }
+            }
         }
+        drop(codes);
 
-        self.save_backup_codes().await?;
+        if migrated > 0 {
+            self.save_backup_codes().await?;
+            info!("Migrated {} legacy backup codes for user '{}'", migrated, username);
+        }
         Ok(())
     }
 
@@ -717,8 +985,55 @@ impl TwoFactorManager {
         }).collect()
     }
 
-    /// Hash a backup code
-    fn hash_backup_code(code: &str) -> String {
+    /// Hash a backup code with a random salt
+    fn hash_backup_code(code: &str) -> Result<String> {
+        Self::argon2_hash(code)
+    }
+
+    /// Hash backup codes on the blocking pool, keeping Argon2 off the async runtime
+    async fn hash_backup_codes(codes: Vec<String>) -> Result<Vec<String>> {
+        tokio::task::spawn_blocking(move || codes.iter().map(|code| Self::hash_backup_code(code)).collect::<Result<Vec<String>>>())
+            .await
+            .context("Backup code hashing task failed")?
+    }
+
+    /// Hash a value with Argon2id, returning a PHC string
+    fn argon2_hash(value: &str) -> Result<String> {
+        use argon2::password_hash::{PasswordHasher, SaltString};
+        let salt = SaltString::generate(&mut OsRng);
+        argon2::Argon2::default()
+            .hash_password(value.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("Failed to hash backup code: {}", e))
+    }
+
+    /// Verify a value against an Argon2 PHC string (constant-time comparison)
+    fn argon2_verify(phc: &str, value: &str) -> bool {
+        use argon2::password_hash::{PasswordHash, PasswordVerifier};
+        match PasswordHash::new(phc) {
+            Ok(parsed) => argon2::Argon2::default().verify_password(value.as_bytes(), &parsed).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Check whether a stored hash matches a backup code
+    fn backup_code_matches(stored: &str, code: &str) -> bool {
+        if let Some(wrapped) = stored.strip_prefix(WRAPPED_SHA256_PREFIX) {
+            Self::argon2_verify(wrapped, &Self::sha256_hex(code))
+        } else if Self::is_legacy_hash(stored) {
+            constant_time_eq(stored.as_bytes(), Self::sha256_hex(code).as_bytes())
+        } else {
+            Self::argon2_verify(stored, code)
+        }
+    }
+
+    /// Whether a stored hash is a legacy unsalted SHA-256 hex digest
+    fn is_legacy_hash(stored: &str) -> bool {
+        stored.len() == 64 && stored.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Legacy SHA-256 backup code digest
+    fn sha256_hex(code: &str) -> String {
         use sha2::{Sha256, Digest};
         let mut hasher = Sha256::new();
         hasher.update(code.as_bytes());
@@ -746,12 +1061,14 @@ impl TwoFactorManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keys::SealedFileKeyProvider;
+    use tempfile::TempDir;
 
     #[tokio::test]
     async fn test_generate_secret() {
-        let temp_dir = std::env::temp_dir();
+        let temp_dir = TempDir::new().unwrap();
         let manager = TwoFactorManager::new(
-            temp_dir.join("2fa_test"),
+            temp_dir.path().join("2fa_test"),
             "TestApp".to_string()
         );
 
@@ -766,9 +1083,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_2fa_enable_disable() {
-        let temp_dir = std::env::temp_dir();
+        let temp_dir = TempDir::new().unwrap();
         let manager = TwoFactorManager::new(
-            temp_dir.join("2fa_test2"),
+            temp_dir.path().join("2fa_test2"),
             "TestApp".to_string()
         );
 
@@ -782,6 +1099,183 @@ mod tests {
         assert!(!status.enabled); // Not enabled yet
     }
 
+    #[tokio::test]
+    async fn test_rotate_encryption_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = Arc::new(SealedFileKeyProvider::new(
+            temp_dir.path().join("keys"),
+            "correct horse battery staple".to_string(),
+        ).unwrap());
+        let storage_dir = temp_dir.path().join("2fa");
+
+        let manager = TwoFactorManager::with_key_provider(storage_dir.clone(), "TestApp".to_string(), provider.clone());
+        manager.initialize().await.unwrap();
+        let setup = manager.generate_secret("testuser").await.unwrap();
+
+        let old_key = provider.load_key(KEY_ID_TWO_FACTOR).await.unwrap().unwrap();
+        assert_eq!(manager.rotate_encryption_key().await.unwrap(), 1);
+        let new_key = provider.load_key(KEY_ID_TWO_FACTOR).await.unwrap().unwrap();
+        assert_ne!(old_key, new_key);
+
+        // A fresh manager decrypts the secret with the rotated key
+        let reloaded = TwoFactorManager::with_key_provider(storage_dir, "TestApp".to_string(), provider);
+        reloaded.initialize().await.unwrap();
+        let secrets = reloaded.secrets.read().await;
+        assert_eq!(secrets.get("testuser").unwrap().secret.as_deref(), Some(setup.secret.as_str()));
+    }
+
+    /// Fails writes of the secrets file while `fail_secrets` is set
+    #[derive(Default)]
+    struct FlakyStore {
+        inner: crate::storage::MemoryStore,
+        fail_secrets: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl BlobStore for FlakyStore {
+        fn backend(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            self.inner.get(key).await
+        }
+
+        async fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+            if key == SECRETS_KEY && self.fail_secrets.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("disk full"));
+            }
+            self.inner.put(key, value).await
+        }
+
+        async fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+            self.inner.append(key, value).await
+        }
+
+        async fn delete(&self, key: &str) -> Result<()> {
+            self.inner.delete(key).await
+        }
+
+        async fn list(&self) -> Result<Vec<String>> {
+            self.inner.list().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_recovers_after_store_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = Arc::new(SealedFileKeyProvider::new(
+            temp_dir.path().join("keys"),
+            "correct horse battery staple".to_string(),
+        ).unwrap());
+        let store = Arc::new(FlakyStore::default());
+        let instance = || TwoFactorManager::with_key_provider(temp_dir.path().join("2fa"), "TestApp".to_string(), provider.clone())
+            .with_store(store.clone());
+
+        let manager = instance();
+        manager.initialize().await.unwrap();
+        let setup = manager.generate_secret("testuser").await.unwrap();
+
+        // The new key is stored but swapping in the re-encrypted secrets fails
+        store.fail_secrets.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(manager.rotate_encryption_key().await.is_err());
+        let new_key = provider.load_key(KEY_ID_TWO_FACTOR).await.unwrap().unwrap();
+        assert_eq!(manager.encryption_key.read().await.as_bytes(), &new_key);
+        store.fail_secrets.store(false, std::sync::atomic::Ordering::SeqCst);
+
+        // A restart finishes the rotation from the staged file
+        let restarted = instance();
+        restarted.initialize().await.unwrap();
+        let secrets = restarted.secrets.read().await;
+        assert_eq!(secrets.get("testuser").unwrap().secret.as_deref(), Some(setup.secret.as_str()));
+        assert!(!store.exists(ROTATING_SECRETS_KEY).await.unwrap());
+
+        // Secrets the key cannot open fail initialization instead of dropping the user
+        provider.store_key(KEY_ID_TWO_FACTOR, &generate_key()).await.unwrap();
+        assert!(instance().initialize().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_code_single_use() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = TwoFactorManager::new(temp_dir.path().to_path_buf(), "TestApp".to_string());
+        manager.initialize().await.unwrap();
+
+        let codes = manager.regenerate_backup_codes("testuser").await.unwrap();
+        let stored = manager.backup_codes.read().await.get("testuser").unwrap().codes.clone();
+        assert!(stored.iter().all(|h| h.starts_with("$argon2")));
+        assert!(!stored.iter().any(|h| h.contains(&codes[0])));
+
+        let matched = manager.verify_backup_code("testuser", &codes[0]).await.unwrap().unwrap();
+        assert!(manager.consume_backup_code("testuser", &matched).await.unwrap());
+        assert!(manager.verify_backup_code("testuser", &codes[0]).await.unwrap().is_none());
+        assert!(manager.verify_backup_code("testuser", "0000000000000000").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_backup_codes_migrated() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = TwoFactorManager::new(temp_dir.path().to_path_buf(), "TestApp".to_string());
+        manager.initialize().await.unwrap();
+
+        let legacy_code = "1234567812345678";
+        manager.backup_codes.write().await.insert("olduser".to_string(), BackupCodes {
+            username: "olduser".to_string(),
+            codes: vec![TwoFactorManager::sha256_hex(legacy_code)],
+            created_at: Utc::now(),
+        });
+
+        let matched = manager.verify_backup_code("olduser", legacy_code).await.unwrap().unwrap();
+        assert!(matched.starts_with(WRAPPED_SHA256_PREFIX));
+        assert!(manager.verify_backup_code("olduser", "8765432187654321").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lockout_persists_and_unlocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = TwoFactorManager::new(temp_dir.path().to_path_buf(), "TestApp".to_string());
+        manager.initialize().await.unwrap();
+
+        for _ in 0..5 {
+            manager.record_failed_attempt("testuser").await;
+        }
+        assert!(manager.is_rate_limited("testuser").await);
+
+        // Lockout survives a restart
+        let restarted = TwoFactorManager::new(temp_dir.path().to_path_buf(), "TestApp".to_string());
+        restarted.initialize().await.unwrap();
+        assert!(restarted.is_rate_limited("testuser").await);
+        let lockouts = restarted.list_lockouts().await;
+        assert_eq!(lockouts.len(), 1);
+        assert!(lockouts[0].locked);
+
+        assert!(restarted.unlock("testuser").await.unwrap());
+        assert!(!restarted.is_rate_limited("testuser").await);
+        assert!(!restarted.unlock("testuser").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_instances_share_a_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = Arc::new(SealedFileKeyProvider::new(
+            temp_dir.path().join("keys"),
+            "correct horse battery staple".to_string(),
+        ).unwrap());
+        let store: Arc<dyn BlobStore> = Arc::new(crate::storage::MemoryStore::default());
+        let instance = |dir: &str| TwoFactorManager::with_key_provider(temp_dir.path().join(dir), "TestApp".to_string(), provider.clone())
+            .with_store(store.clone());
+
+        let first = instance("a");
+        first.initialize().await.unwrap();
+        let setup = first.generate_secret("testuser").await.unwrap();
+
+        let second = instance("b");
+        second.initialize().await.unwrap();
+        let secrets = second.secrets.read().await;
+        assert_eq!(secrets.get("testuser").unwrap().secret.as_deref(), Some(setup.secret.as_str()));
+        assert!(!temp_dir.path().join("a").exists());
+    }
+
     #[test]
     fn test_generate_backup_codes() {
         let codes = TwoFactorManager::generate_backup_codes();
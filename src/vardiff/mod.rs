@@ -0,0 +1,238 @@
+// Vardiff Advisor Module for DMPool
+// Recommends per-worker difficulty targets from observed share rates
+
+use chrono::{DateTime, Utc};
+use p2poolv2_lib::accounting::simple_pplns::SimplePplnsShare;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Default share rate each worker should be tuned for
+const DEFAULT_TARGET_SHARES_PER_MINUTE: f64 = 6.0;
+
+/// Default ratio between current and ideal difficulty considered on target
+const DEFAULT_TOLERANCE: f64 = 2.0;
+
+/// Default shares required before a worker gets a recommendation
+const DEFAULT_MIN_SHARES: usize = 10;
+
+/// Shortest span used to estimate a share rate (seconds)
+const MIN_SPAN_SECONDS: u64 = 60;
+
+/// Advisor parameters
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdvisorSettings {
+    /// Shares per minute each worker should submit
+    pub target_shares_per_minute: f64,
+    /// Current/ideal difficulty ratio allowed in either direction before flagging
+    pub tolerance: f64,
+    /// Shares needed before a worker is judged
+    pub min_shares: usize,
+    /// Recommendations are clamped to the pool's difficulty bounds
+    pub minimum_difficulty: u64,
+    pub maximum_difficulty: Option<u64>,
+}
+
+impl Default for AdvisorSettings {
+    fn default() -> Self {
+        Self {
+            target_shares_per_minute: DEFAULT_TARGET_SHARES_PER_MINUTE,
+            tolerance: DEFAULT_TOLERANCE,
+            min_shares: DEFAULT_MIN_SHARES,
+            minimum_difficulty: 1,
+            maximum_difficulty: None,
+        }
+    }
+}
+
+/// How a worker's difficulty compares to its ideal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyStatus {
+    OnTarget,
+    /// Too many shares: raise difficulty
+    TooLow,
+    /// Too few shares: lower difficulty
+    TooHigh,
+    /// Not enough shares to judge
+    InsufficientData,
+}
+
+/// Difficulty advice for one worker connection
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerDifficultyAdvice {
+    pub address: String,
+    pub worker: String,
+    pub shares: usize,
+    /// Difficulty of the most recent share
+    pub current_difficulty: u64,
+    pub actual_shares_per_minute: f64,
+    pub ideal_difficulty: u64,
+    /// Current over ideal difficulty
+    pub ratio: f64,
+    pub status: DifficultyStatus,
+}
+
+/// Pool-wide difficulty settings suggested by the worker mix
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolDifficultyAdvice {
+    pub current_start_difficulty: u64,
+    pub current_minimum_difficulty: u64,
+    /// Median ideal difficulty, so new connections start near their target
+    pub recommended_start_difficulty: u64,
+    /// Lowest ideal difficulty, so the smallest workers can still reach target
+    pub recommended_minimum_difficulty: u64,
+}
+
+/// Advisor output
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VardiffReport {
+    pub generated_at: DateTime<Utc>,
+    pub settings: AdvisorSettings,
+    pub workers: Vec<WorkerDifficultyAdvice>,
+    pub off_target: usize,
+    /// None if no worker had enough shares
+    pub pool: Option<PoolDifficultyAdvice>,
+}
+
+/// Advise on worker and pool difficulty from recent shares
+pub fn advise(
+    shares: &[SimplePplnsShare],
+    settings: AdvisorSettings,
+    current_start_difficulty: u64,
+    current_minimum_difficulty: u64,
+) -> VardiffReport {
+    let mut by_worker: HashMap<(String, String), Vec<&SimplePplnsShare>> = HashMap::new();
+    for share in shares {
+        let address = share.btcaddress.clone().unwrap_or_else(|| format!("user_{}", share.user_id));
+        let worker = share.workername.clone().unwrap_or_else(|| "worker".to_string());
+        by_worker.entry((address, worker)).or_default().push(share);
+    }
+
+    let clamp = |difficulty: f64| -> u64 {
+        let difficulty = (difficulty.round() as u64).max(settings.minimum_difficulty).max(1);
+        settings.maximum_difficulty.map_or(difficulty, |max| difficulty.min(max))
+    };
+
+    let mut workers: Vec<WorkerDifficultyAdvice> = by_worker.into_iter()
+        .map(|((address, worker), mut shares)| {
+            shares.sort_by_key(|s| s.n_time);
+            let first = shares.first().map(|s| s.n_time).unwrap_or_default();
+            let last = shares.last().map(|s| s.n_time).unwrap_or_default();
+            let minutes = (last - first).max(MIN_SPAN_SECONDS) as f64 / 60.0;
+
+            // The first share only marks the start of the span
+            let counted = &shares[usize::from(shares.len() > 1)..];
+            let work: u64 = counted.iter().map(|s| s.difficulty).sum();
+            let current_difficulty = shares.last().map(|s| s.difficulty).unwrap_or_default();
+
+            // Work per minute is independent of the difficulty the shares were mined at
+            let ideal_difficulty = clamp(work as f64 / minutes / settings.target_shares_per_minute);
+            let ratio = current_difficulty as f64 / ideal_difficulty as f64;
+
+            let status = if shares.len() < settings.min_shares {
+                DifficultyStatus::InsufficientData
+            } else if ratio > settings.tolerance {
+                DifficultyStatus::TooHigh
+            } else if ratio < 1.0 / settings.tolerance {
+                DifficultyStatus::TooLow
+            } else {
+                DifficultyStatus::OnTarget
+            };
+
+            WorkerDifficultyAdvice {
+                address,
+                worker,
+                shares: shares.len(),
+                current_difficulty,
+                actual_shares_per_minute: counted.len() as f64 / minutes,
+                ideal_difficulty,
+                ratio,
+                status,
+            }
+        })
+        .collect();
+
+    // Furthest from target first
+    workers.sort_by(|a, b| b.ratio.ln().abs().total_cmp(&a.ratio.ln().abs()));
+
+    let mut ideals: Vec<u64> = workers.iter()
+        .filter(|w| w.status != DifficultyStatus::InsufficientData)
+        .map(|w| w.ideal_difficulty)
+        .collect();
+    ideals.sort_unstable();
+
+    let pool = (!ideals.is_empty()).then(|| PoolDifficultyAdvice {
+        current_start_difficulty,
+        current_minimum_difficulty,
+        recommended_start_difficulty: ideals[ideals.len() / 2],
+        recommended_minimum_difficulty: ideals[0],
+    });
+
+    VardiffReport {
+        generated_at: Utc::now(),
+        off_target: workers.iter()
+            .filter(|w| matches!(w.status, DifficultyStatus::TooLow | DifficultyStatus::TooHigh))
+            .count(),
+        settings,
+        workers,
+        pool,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shares(address: &str, count: u64, difficulty: u64, interval: u64) -> Vec<SimplePplnsShare> {
+        (0..count).map(|i| SimplePplnsShare {
+            btcaddress: Some(address.to_string()),
+            workername: Some("rig1".to_string()),
+            user_id: 1,
+            difficulty,
+            n_time: 1_000_000 + i * interval,
+            job_id: format!("job-{}", i),
+            extranonce2: "00000001".to_string(),
+            nonce: format!("{:08x}", i),
+        }).collect()
+    }
+
+    #[test]
+    fn test_worker_recommendations() {
+        let mut all = Vec::new();
+        // 60 shares/minute at difficulty 100: ideal at 6/min is 1000
+        all.extend(shares("bc1qfast", 120, 100, 1));
+        // 6 shares/minute at difficulty 1000: on target
+        all.extend(shares("bc1qok", 30, 1000, 10));
+        // One share every 10 minutes at difficulty 6000: ideal is 100
+        all.extend(shares("bc1qslow", 12, 6000, 600));
+        // Too few shares
+        all.extend(shares("bc1qnew", 3, 1000, 10));
+
+        let report = advise(&all, AdvisorSettings::default(), 32, 16);
+        let by_address: HashMap<_, _> = report.workers.iter().map(|w| (w.address.as_str(), w)).collect();
+
+        assert_eq!(by_address["bc1qfast"].status, DifficultyStatus::TooLow);
+        assert_eq!(by_address["bc1qok"].status, DifficultyStatus::OnTarget);
+        assert_eq!(by_address["bc1qslow"].status, DifficultyStatus::TooHigh);
+        assert_eq!(by_address["bc1qslow"].ideal_difficulty, 100);
+        assert_eq!(by_address["bc1qnew"].status, DifficultyStatus::InsufficientData);
+        assert_eq!(report.off_target, 2);
+    }
+
+    #[test]
+    fn test_pool_recommendation_and_bounds() {
+        let mut all = shares("bc1qa", 60, 1000, 10);
+        all.extend(shares("bc1qb", 60, 10, 10));
+
+        let settings = AdvisorSettings { minimum_difficulty: 50, ..AdvisorSettings::default() };
+        let report = advise(&all, settings, 32, 16);
+        let pool = report.pool.unwrap();
+
+        // bc1qb's ideal (10) is clamped to the minimum
+        assert_eq!(pool.recommended_minimum_difficulty, 50);
+        assert_eq!(pool.recommended_start_difficulty, 1000);
+        assert_eq!(pool.current_start_difficulty, 32);
+
+        assert!(advise(&[], AdvisorSettings::default(), 32, 16).pool.is_none());
+    }
+}
@@ -0,0 +1,360 @@
+// Worker Status Module for DMPool
+// Persists per-worker online status, hashrate and share totals from stratum shares
+//
+// Accepted shares arrive on the event bus and are counted in memory. Each
+// flush writes the workers that submitted shares (or still have shares in
+// the hashrate window) to worker_status_cache, then marks workers that have
+// been quiet longer than `offline_after_secs` offline. Share totals that fail
+// to be written are kept and retried on the next flush.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use crate::db::DatabaseManager;
+use crate::earnings::HASHES_PER_DIFFICULTY;
+use crate::events::{EventHandler, PoolEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::debug;
+
+/// Worker name used for shares submitted without one
+pub const DEFAULT_WORKER: &str = "worker";
+
+/// The `[dmpool.worker_status]` settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerStatusConfig {
+    pub enabled: bool,
+    /// Seconds between writes to the database
+    pub flush_interval_secs: u64,
+    /// Seconds of shares the current hashrate is averaged over
+    pub hashrate_window_secs: u64,
+    /// Seconds without shares before a worker is marked offline
+    pub offline_after_secs: u64,
+}
+
+impl Default for WorkerStatusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            flush_interval_secs: 30,
+            hashrate_window_secs: 600,
+            offline_after_secs: 600,
+        }
+    }
+}
+
+impl WorkerStatusConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.flush_interval_secs == 0 {
+            return Err(anyhow::anyhow!("flush_interval_secs must be at least 1"));
+        }
+        if self.hashrate_window_secs < self.flush_interval_secs {
+            return Err(anyhow::anyhow!("hashrate_window_secs must be at least flush_interval_secs ({})", self.flush_interval_secs));
+        }
+        if self.offline_after_secs < self.flush_interval_secs {
+            return Err(anyhow::anyhow!("offline_after_secs must be at least flush_interval_secs ({})", self.flush_interval_secs));
+        }
+        Ok(())
+    }
+}
+
+/// One worker's row as written by a flush
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorkerUpdate {
+    pub address: String,
+    pub worker: String,
+    /// Shares since the last successful flush, added to the stored total
+    pub new_shares: u64,
+    /// Difficulty of the latest share
+    pub difficulty: u64,
+    /// Hashes per second over the hashrate window
+    pub hashrate: u64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Result of one flush
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlushReport {
+    pub updated: usize,
+    pub expired: u64,
+}
+
+/// Where worker status is stored (worker_status_cache in production)
+#[async_trait]
+pub trait WorkerStatusStore: Send + Sync {
+    /// Insert or update workers, marking them online
+    async fn upsert_workers(&self, updates: &[WorkerUpdate]) -> Result<()>;
+
+    /// Mark online workers last seen before `cutoff` offline, returning how many changed
+    async fn expire_workers(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+}
+
+#[async_trait]
+impl WorkerStatusStore for DatabaseManager {
+    async fn upsert_workers(&self, updates: &[WorkerUpdate]) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let addresses: Vec<&str> = updates.iter().map(|u| u.address.as_str()).collect();
+        let workers: Vec<&str> = updates.iter().map(|u| u.worker.as_str()).collect();
+        let last_seen: Vec<DateTime<Utc>> = updates.iter().map(|u| u.last_seen).collect();
+        let hashrates: Vec<i64> = updates.iter().map(|u| u.hashrate as i64).collect();
+        let difficulties: Vec<i64> = updates.iter().map(|u| u.difficulty as i64).collect();
+        let shares: Vec<i64> = updates.iter().map(|u| u.new_shares as i64).collect();
+
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "INSERT INTO worker_status_cache (miner_address, worker_name, last_seen, is_online, current_hashrate, current_difficulty, total_shares)
+             SELECT u.address, u.worker, u.last_seen, true, u.hashrate, u.difficulty, u.shares
+             FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::TIMESTAMPTZ[], $4::BIGINT[], $5::BIGINT[], $6::BIGINT[])
+                AS u(address, worker, last_seen, hashrate, difficulty, shares)
+             ON CONFLICT (miner_address, worker_name) DO UPDATE SET
+                last_seen = GREATEST(worker_status_cache.last_seen, EXCLUDED.last_seen),
+                is_online = true,
+                current_hashrate = EXCLUDED.current_hashrate,
+                current_difficulty = EXCLUDED.current_difficulty,
+                total_shares = worker_status_cache.total_shares + EXCLUDED.total_shares",
+            &[&addresses, &workers, &last_seen, &hashrates, &difficulties, &shares],
+        )
+        .await
+        .context("Failed to update worker status")?;
+        Ok(())
+    }
+
+    async fn expire_workers(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let conn = self.get_conn().await?;
+        conn.execute(
+            "UPDATE worker_status_cache SET is_online = false, current_hashrate = 0 WHERE is_online AND last_seen < $1",
+            &[&cutoff],
+        )
+        .await
+        .context("Failed to expire offline workers")
+    }
+}
+
+/// In-memory counts for one worker between flushes
+struct WorkerState {
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    difficulty: u64,
+    /// Shares not yet written
+    pending_shares: u64,
+    /// Difficulty submitted since the last flush
+    pending_work: u64,
+    /// Difficulty submitted per flush, oldest first
+    buckets: VecDeque<(DateTime<Utc>, u64)>,
+}
+
+/// Counts shares per worker and writes them to the store
+pub struct WorkerStatusTracker {
+    store: Arc<dyn WorkerStatusStore>,
+    config: WorkerStatusConfig,
+    workers: RwLock<HashMap<(String, String), WorkerState>>,
+}
+
+impl WorkerStatusTracker {
+    pub fn new(store: Arc<dyn WorkerStatusStore>, config: WorkerStatusConfig) -> Self {
+        Self {
+            store,
+            config,
+            workers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &WorkerStatusConfig {
+        &self.config
+    }
+
+    /// Count an accepted share
+    pub async fn record_share(&self, address: &str, worker: Option<&str>, difficulty: u64, at: DateTime<Utc>) {
+        let worker = worker.filter(|w| !w.is_empty()).unwrap_or(DEFAULT_WORKER);
+        let mut workers = self.workers.write().await;
+        let state = workers.entry((address.to_string(), worker.to_string())).or_insert_with(|| WorkerState {
+            first_seen: at,
+            last_seen: at,
+            difficulty,
+            pending_shares: 0,
+            pending_work: 0,
+            buckets: VecDeque::new(),
+        });
+        state.last_seen = state.last_seen.max(at);
+        state.difficulty = difficulty;
+        state.pending_shares += 1;
+        state.pending_work = state.pending_work.saturating_add(difficulty);
+    }
+
+    /// Write active workers and mark quiet ones offline
+    pub async fn flush(&self, now: DateTime<Utc>) -> Result<FlushReport> {
+        let window_start = now - Duration::seconds(self.config.hashrate_window_secs as i64);
+        let offline_cutoff = now - Duration::seconds(self.config.offline_after_secs as i64);
+
+        let updates = {
+            let mut workers = self.workers.write().await;
+            let mut updates = Vec::new();
+            for ((address, worker), state) in workers.iter_mut() {
+                if state.pending_work > 0 {
+                    state.buckets.push_back((now, std::mem::take(&mut state.pending_work)));
+                }
+                while state.buckets.front().is_some_and(|(at, _)| *at <= window_start) {
+                    state.buckets.pop_front();
+                }
+                if state.buckets.is_empty() && state.pending_shares == 0 {
+                    continue;
+                }
+                updates.push(WorkerUpdate {
+                    address: address.clone(),
+                    worker: worker.clone(),
+                    new_shares: std::mem::take(&mut state.pending_shares),
+                    difficulty: state.difficulty,
+                    hashrate: state.hashrate(now, self.config.hashrate_window_secs, self.config.flush_interval_secs),
+                    last_seen: state.last_seen,
+                });
+            }
+            // Quiet workers are left to expire in the store
+            workers.retain(|_, state| !state.buckets.is_empty() || state.last_seen >= offline_cutoff);
+            updates
+        };
+
+        if let Err(e) = self.store.upsert_workers(&updates).await {
+            // Keep the share counts for the next flush
+            let mut workers = self.workers.write().await;
+            for update in &updates {
+                if let Some(state) = workers.get_mut(&(update.address.clone(), update.worker.clone())) {
+                    state.pending_shares += update.new_shares;
+                }
+            }
+            return Err(e);
+        }
+        let expired = self.store.expire_workers(offline_cutoff).await?;
+
+        debug!("Worker status flushed: {} updated, {} went offline", updates.len(), expired);
+        Ok(FlushReport { updated: updates.len(), expired })
+    }
+}
+
+impl WorkerState {
+    /// Hashes per second over the window, not extrapolated past the worker's first share
+    fn hashrate(&self, now: DateTime<Utc>, window_secs: u64, min_secs: u64) -> u64 {
+        let work: u64 = self.buckets.iter().map(|(_, work)| *work).sum();
+        let active_secs = (now - self.first_seen).num_seconds().max(0) as u64;
+        let secs = active_secs.clamp(min_secs.max(1), window_secs.max(1));
+        (work as f64 * HASHES_PER_DIFFICULTY / secs as f64) as u64
+    }
+}
+
+#[async_trait]
+impl EventHandler for WorkerStatusTracker {
+    fn name(&self) -> &str {
+        "worker_status"
+    }
+
+    fn accepts(&self, kind: &str) -> bool {
+        kind == "share_accepted"
+    }
+
+    async fn handle(&self, event: &PoolEvent) -> Result<()> {
+        if let PoolEvent::ShareAccepted { address, worker, difficulty, .. } = event {
+            self.record_share(address, worker.as_deref(), *difficulty, Utc::now()).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// In-memory worker_status_cache
+    #[derive(Default)]
+    struct MemoryStore {
+        rows: RwLock<HashMap<(String, String), (WorkerUpdate, u64, bool)>>,
+        fail: AtomicBool,
+    }
+
+    #[async_trait]
+    impl WorkerStatusStore for MemoryStore {
+        async fn upsert_workers(&self, updates: &[WorkerUpdate]) -> Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(anyhow::anyhow!("database unavailable"));
+            }
+            let mut rows = self.rows.write().await;
+            for update in updates {
+                let row = rows.entry((update.address.clone(), update.worker.clone()))
+                    .or_insert_with(|| (update.clone(), 0, true));
+                row.1 += update.new_shares;
+                row.0 = update.clone();
+                row.2 = true;
+            }
+            Ok(())
+        }
+
+        async fn expire_workers(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+            let mut expired = 0;
+            for (update, _, online) in self.rows.write().await.values_mut() {
+                if *online && update.last_seen < cutoff {
+                    *online = false;
+                    expired += 1;
+                }
+            }
+            Ok(expired)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_counts_shares_and_hashrate() {
+        let store = Arc::new(MemoryStore::default());
+        let tracker = WorkerStatusTracker::new(store.clone(), WorkerStatusConfig::default());
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+
+        // 600 difficulty-1 shares over the 10 minute window is about 4.29 GH/s
+        for i in 0..600 {
+            tracker.record_share("bc1qminer", Some("rig1"), 1, start + Duration::seconds(i)).await;
+        }
+        tracker.record_share("bc1qminer", None, 8, start).await;
+        let report = tracker.flush(start + Duration::seconds(600)).await.unwrap();
+        assert_eq!(report, FlushReport { updated: 2, expired: 0 });
+
+        let rows = store.rows.read().await;
+        let (rig1, total, online) = &rows[&("bc1qminer".to_string(), "rig1".to_string())];
+        assert_eq!((*total, *online), (600, true));
+        assert_eq!(rig1.hashrate, 4_294_967_296);
+        assert_eq!(rig1.last_seen, start + Duration::seconds(599));
+        assert_eq!(rows[&("bc1qminer".to_string(), DEFAULT_WORKER.to_string())].0.difficulty, 8);
+        drop(rows);
+
+        // Totals accumulate across flushes
+        tracker.record_share("bc1qminer", Some("rig1"), 1, start + Duration::seconds(610)).await;
+        tracker.flush(start + Duration::seconds(630)).await.unwrap();
+        assert_eq!(store.rows.read().await[&("bc1qminer".to_string(), "rig1".to_string())].1, 601);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_workers_expire_and_failed_flushes_retry() {
+        let store = Arc::new(MemoryStore::default());
+        let tracker = WorkerStatusTracker::new(store.clone(), WorkerStatusConfig::default());
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 12, 0, 0).unwrap();
+
+        tracker.record_share("bc1qa", Some("rig1"), 4, start).await;
+        store.fail.store(true, Ordering::SeqCst);
+        assert!(tracker.flush(start + Duration::seconds(30)).await.is_err());
+        store.fail.store(false, Ordering::SeqCst);
+        tracker.flush(start + Duration::seconds(60)).await.unwrap();
+        assert_eq!(store.rows.read().await[&("bc1qa".to_string(), "rig1".to_string())].1, 1);
+
+        // Still online within the quiet period, offline after it
+        assert_eq!(tracker.flush(start + Duration::seconds(500)).await.unwrap().expired, 0);
+        let report = tracker.flush(start + Duration::seconds(700)).await.unwrap();
+        assert_eq!(report, FlushReport { updated: 0, expired: 1 });
+        assert!(!store.rows.read().await[&("bc1qa".to_string(), "rig1".to_string())].2);
+        assert!(tracker.workers.read().await.is_empty());
+
+        let config = WorkerStatusConfig { offline_after_secs: 10, ..Default::default() };
+        assert!(config.validate().is_err());
+        assert!(WorkerStatusConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+// Worker Tags Module for DMPool
+// Parses structured worker names into rig, location and tags for filtering and alerts
+
+use anyhow::Result;
+use crate::alert::{AlertCondition, AlertManager};
+use crate::db::DatabaseManager;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Separator between the rig name and its location
+const LOCATION_SEPARATOR: char = '@';
+
+/// Separator before each tag
+const TAG_SEPARATOR: char = '#';
+
+/// Structured fields parsed from a worker name
+///
+/// Worker names follow `rig[@location][#tag...]`, e.g. `s19-07@dc-east#rack-3#immersion`.
+/// Names without separators are a bare rig name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerTags {
+    pub rig: String,
+    pub location: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl WorkerTags {
+    /// Parse a worker name
+    pub fn parse(worker: &str) -> Self {
+        let mut parts = worker.split(TAG_SEPARATOR);
+        let head = parts.next().unwrap_or_default();
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in parts.map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let (rig, location) = match head.split_once(LOCATION_SEPARATOR) {
+            Some((rig, location)) => {
+                let location = location.trim().to_lowercase();
+                (rig.trim(), (!location.is_empty()).then_some(location))
+            }
+            None => (head.trim(), None),
+        };
+
+        Self {
+            rig: rig.to_string(),
+            location,
+            tags,
+        }
+    }
+
+    /// Whether the worker has a tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Filter for tagged worker queries
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct WorkerFilter {
+    pub tag: Option<String>,
+    pub location: Option<String>,
+    pub rig: Option<String>,
+    pub online: Option<bool>,
+}
+
+/// Worker with its parsed tags
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TaggedWorker {
+    pub address: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub tags: WorkerTags,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate: u64,
+    pub is_online: bool,
+    pub last_seen: String,
+}
+
+/// Field to aggregate workers by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkerGrouping {
+    #[default]
+    Tag,
+    Location,
+}
+
+/// Aggregated stats for workers sharing a tag or location
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WorkerGroup {
+    pub key: String,
+    pub workers: u64,
+    pub online: u64,
+    #[serde(serialize_with = "crate::observer_api::units::difficulty_rate")]
+    pub hashrate: u64,
+}
+
+/// Trigger TaggedWorkersOffline rules whose tagged workers are all offline
+///
+/// Rules whose workers are back online are cleared. Returns the ids of the rules that fired.
+pub async fn check_tag_alerts(alerts: &AlertManager, db: &DatabaseManager) -> Result<Vec<String>> {
+    let mut fired = Vec::new();
+    for rule in alerts.get_rules().await {
+        if !rule.enabled {
+            continue;
+        }
+        let (tag, miner) = match &rule.condition {
+            AlertCondition::TaggedWorkersOffline { tag, miner } => (tag, miner.as_deref()),
+            _ => continue,
+        };
+
+        let group = db.get_worker_groups(miner, WorkerGrouping::Tag).await?
+            .into_iter()
+            .find(|g| g.key.eq_ignore_ascii_case(tag));
+        let group = match group {
+            Some(group) if group.workers > 0 && group.online == 0 => group,
+            Some(group) if group.online > 0 => {
+                // Workers are back online
+                alerts.clear_rule(&rule.id, serde_json::to_value(&group)?).await?;
+                continue;
+            }
+            _ => continue,
+        };
+
+        warn!("All {} workers tagged {} are offline", group.workers, tag);
+        alerts.trigger_alert(&rule.id, serde_json::to_value(&group)?).await?;
+        fired.push(rule.id);
+    }
+    Ok(fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_structured_name() {
+        let tags = WorkerTags::parse("s19-07@DC-East#Rack-3#immersion#rack-3");
+        assert_eq!(tags.rig, "s19-07");
+        assert_eq!(tags.location.as_deref(), Some("dc-east"));
+        assert_eq!(tags.tags, vec!["rack-3", "immersion"]);
+        assert!(tags.has_tag("RACK-3"));
+        assert!(!tags.has_tag("rack-4"));
+    }
+
+    #[test]
+    fn test_parse_plain_and_partial_names() {
+        assert_eq!(WorkerTags::parse("rig1"), WorkerTags { rig: "rig1".to_string(), location: None, tags: Vec::new() });
+
+        let tags = WorkerTags::parse("rig2#gpu##");
+        assert_eq!(tags.location, None);
+        assert_eq!(tags.tags, vec!["gpu"]);
+
+        let tags = WorkerTags::parse("rig3@");
+        assert_eq!(tags.rig, "rig3");
+        assert_eq!(tags.location, None);
+    }
+}
@@ -77,7 +77,7 @@ mod integration_tests {
             }
         };
 
-        ObserverState { db }
+        ObserverState::new(db)
     }
 
     /// Test: GET /api/v1/stats returns pool statistics
@@ -0,0 +1,31 @@
+// End-to-end simulation against a regtest bitcoind
+//
+// Needs the `simulation` feature and a node, e.g.:
+//
+//   bitcoind -regtest -fallbackfee=0.0001 -minrelaytxfee=0 -rpcuser=bitcoin -rpcpassword=bitcoin
+//   bitcoin-cli -regtest createwallet sim
+//   DMPOOL_SIM_RPC_URL=http://127.0.0.1:18443 cargo test --features simulation --test simulation
+//
+// Set DMPOOL_SIM_DATABASE_URL to also record blocks in Postgres. Without
+// DMPOOL_SIM_RPC_URL the test is skipped.
+#![cfg(feature = "simulation")]
+
+use dmpool::simulation::{Simulation, SimulationConfig};
+
+#[tokio::test]
+async fn test_regtest_blocks_credit_and_pay_miners() {
+    let data_dir = tempfile::TempDir::new().unwrap();
+    let Some(config) = SimulationConfig::from_env(data_dir.path().to_path_buf()) else {
+        eprintln!("DMPOOL_SIM_RPC_URL is not set, skipping the regtest simulation");
+        return;
+    };
+    let blocks = config.blocks;
+    let miners = config.miners;
+
+    let simulation = Simulation::new(config).await.unwrap();
+    let report = simulation.run().await.unwrap();
+
+    assert_eq!(report.blocks.len(), blocks);
+    assert_eq!(report.payouts.len(), miners);
+    assert!(report.is_consistent(), "{:#?}", report.mismatches);
+}